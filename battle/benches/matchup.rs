@@ -0,0 +1,65 @@
+//! Compare the per-call matchup functions with a defensive profile lookup.
+//!
+//! Run with `cargo bench -p kazam-battle --bench matchup`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use kazam_battle::{DefensiveProfile, Type, is_weak_to_any, weaknesses};
+
+const ROUNDS: usize = 10_000;
+
+/// Coverage of a typical attacker: four moves
+const MOVES: [Type; 4] = [Type::Ground, Type::Rock, Type::Fire, Type::Normal];
+
+fn time(name: &str, mut f: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let ns = start.elapsed().as_secs_f64() * 1e9 / ROUNDS as f64;
+    println!("{name:>22}: {ns:>8.1} ns/iter");
+    ns
+}
+
+fn main() {
+    let mut typings = Vec::new();
+    for (i, &first) in Type::ALL.iter().enumerate() {
+        typings.push(vec![first]);
+        for &second in &Type::ALL[i + 1..] {
+            typings.push(vec![first, second]);
+        }
+    }
+    println!("{} typings per iteration", typings.len());
+
+    let per_call = time("weaknesses()", || {
+        for types in &typings {
+            black_box(weaknesses(black_box(types)));
+        }
+    });
+    let profiled = time("profile weaknesses", || {
+        for types in &typings {
+            black_box(DefensiveProfile::of(black_box(types)).weaknesses().count());
+        }
+    });
+    println!("{:>22}: {:.1}x", "speedup", per_call / profiled);
+
+    let per_call = time("per-move effectiveness", || {
+        for types in &typings {
+            for t in MOVES {
+                black_box(t.effectiveness_multi(black_box(types)));
+            }
+            black_box(is_weak_to_any(types, &MOVES));
+        }
+    });
+    let profiled = time("profile per move", || {
+        for types in &typings {
+            let profile = DefensiveProfile::of(black_box(types));
+            for t in MOVES {
+                black_box(profile.against(t));
+            }
+            black_box(profile.is_weak_to_any(&MOVES));
+        }
+    });
+    println!("{:>22}: {:.1}x", "speedup", per_call / profiled);
+}
@@ -0,0 +1,121 @@
+//! Compare cloning Pokemon state with taking snapshots, and time action evaluation.
+//!
+//! Run with `cargo bench -p kazam-battle --bench snapshot`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use kazam_battle::query::{EvalWeights, evaluate_actions};
+use kazam_battle::{TrackedBattle, Type, Volatile};
+use kazam_protocol::{BattleRequest, Player, parse_server_message};
+
+const ROUNDS: usize = 100_000;
+
+const DOUBLES_LOG: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|gen|9
+|tier|[Gen 9] VGC 2024 Reg G
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p1b: Rillaboom|Rillaboom, L50, M|100/100
+|switch|p2a: Heatran|Heatran, L50, M|100/100
+|switch|p2b: Corviknight|Corviknight, L50, F|100/100
+|-item|p2b: Corviknight|Leftovers
+|-ability|p2a: Heatran|Flash Fire
+|move|p2b: Corviknight|Tailwind|p2b: Corviknight
+|-sidestart|p2: Bob|move: Tailwind
+|move|p1a: Garchomp|Stealth Rock|p2a: Heatran
+|-sidestart|p2: Bob|move: Stealth Rock
+|-damage|p2a: Heatran|62/100
+|turn|2"#;
+
+fn doubles() -> (TrackedBattle, BattleRequest) {
+    let mut battle = TrackedBattle::for_player(Player::P1);
+    for line in DOUBLES_LOG.lines() {
+        battle.apply_message(&parse_server_message(line).unwrap());
+    }
+    let moves = [
+        ("Earthquake", "allAdjacent"),
+        ("Dragon Claw", "normal"),
+        ("Rock Slide", "allAdjacentFoes"),
+        ("Protect", "self"),
+    ];
+    let move_slots: Vec<_> = moves
+        .iter()
+        .map(|(name, target)| {
+            serde_json::json!({
+                "move": name, "id": name.to_lowercase().replace(' ', ""),
+                "pp": 16, "maxpp": 16, "target": target, "disabled": false
+            })
+        })
+        .collect();
+    let json = serde_json::json!({
+        "active": [{"moves": move_slots}, {"moves": move_slots}],
+        "side": {
+            "name": "Alice",
+            "id": "p1",
+            "pokemon": [
+                {"ident": "p1: Garchomp", "details": "Garchomp, L50, M",
+                 "condition": "183/183", "active": true, "moves": []},
+                {"ident": "p1: Rillaboom", "details": "Rillaboom, L50, M",
+                 "condition": "175/175", "active": true, "moves": []},
+                {"ident": "p1: Lapras", "details": "Lapras, L50, F",
+                 "condition": "190/190", "active": false, "moves": []},
+                {"ident": "p1: Amoonguss", "details": "Amoonguss, L50, F",
+                 "condition": "191/191", "active": false, "moves": []}
+            ]
+        },
+        "rqid": 3
+    });
+    let request = BattleRequest::parse(&json).unwrap();
+    battle.apply_request(&request);
+
+    // No species data in this crate, so typings are filled in by hand
+    let typings: [(Player, usize, &[Type]); 6] = [
+        (Player::P1, 0, &[Type::Ground, Type::Dragon]),
+        (Player::P1, 1, &[Type::Grass]),
+        (Player::P1, 2, &[Type::Water, Type::Ice]),
+        (Player::P1, 3, &[Type::Grass, Type::Poison]),
+        (Player::P2, 0, &[Type::Fire, Type::Steel]),
+        (Player::P2, 1, &[Type::Flying, Type::Steel]),
+    ];
+    for (player, index, types) in typings {
+        battle.get_side_mut(player).unwrap().pokemon[index].set_types(types.to_vec());
+    }
+    battle.get_side_mut(Player::P1).unwrap().pokemon[0].add_volatile(Volatile::LeechSeed);
+    (battle, request)
+}
+
+fn time(name: &str, mut f: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let ns = start.elapsed().as_secs_f64() * 1e9 / ROUNDS as f64;
+    println!("{name:>16}: {ns:>8.1} ns/iter");
+    ns
+}
+
+fn main() {
+    let (battle, request) = doubles();
+    let weights = EvalWeights::default();
+    let pokemon = &battle.get_side(Player::P2).unwrap().pokemon[1];
+
+    let clone = time("clone state", || {
+        black_box(black_box(pokemon).clone());
+    });
+    let snapshot = time("snapshot", || {
+        black_box(black_box(pokemon).snapshot_on(&battle.field));
+    });
+    let taken = pokemon.snapshot_on(&battle.field);
+    let copy = time("copy snapshot", || {
+        black_box(*black_box(&taken));
+    });
+    println!("{:>16}: {:.1}x", "take vs clone", clone / snapshot);
+    println!("{:>16}: {:.1}x", "copy vs clone", clone / copy);
+
+    time("evaluate", || {
+        black_box(evaluate_actions(&battle, black_box(&request), &weights));
+    });
+}
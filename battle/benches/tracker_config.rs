@@ -0,0 +1,74 @@
+//! Compare ingestion time and retained memory for the default and minimal tracker configs.
+//!
+//! Run with `cargo bench -p kazam-battle --bench tracker_config`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use kazam_battle::{TrackedBattle, TrackerConfig};
+use kazam_protocol::{ServerMessage, parse_server_message};
+
+const REPLAYS: usize = 1000;
+
+const SAMPLE_LOG: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|tier|[Gen 9] OU
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|-item|p2a: Garchomp|Air Balloon
+|turn|1
+|move|p2a: Garchomp|Swords Dance|p2a: Garchomp
+|-boost|p2a: Garchomp|atk|2
+|move|p1a: Corviknight|Brave Bird|p2a: Garchomp
+|-damage|p2a: Garchomp|55/100
+|-enditem|p2a: Garchomp|Air Balloon
+|-damage|p1a: Corviknight|88/100|[from] Recoil
+|turn|2
+|move|p2a: Garchomp|Stone Edge|p1a: Corviknight
+|-damage|p1a: Corviknight|60/100
+|move|p1a: Corviknight|Taunt|p2a: Garchomp
+|-start|p2a: Garchomp|move: Taunt
+|turn|3
+|switch|p1a: Ferrothorn|Ferrothorn, L50, M|100/100
+|move|p2a: Garchomp|Earthquake|p1a: Ferrothorn
+|-damage|p1a: Ferrothorn|58/100
+|-end|p2a: Garchomp|move: Taunt
+|-ability|p2a: Garchomp|Rough Skin
+|turn|4
+|move|p1a: Ferrothorn|Stealth Rock|p2a: Garchomp
+|-sidestart|p2: Bob|move: Stealth Rock
+|move|p2a: Garchomp|Earthquake|p1a: Ferrothorn
+|-damage|p1a: Ferrothorn|0 fnt
+|faint|p1a: Ferrothorn
+|win|Bob"#;
+
+fn ingest(messages: &[ServerMessage], config: TrackerConfig) -> (f64, usize) {
+    let start = Instant::now();
+    let mut footprint = 0;
+    for _ in 0..REPLAYS {
+        let mut battle = TrackedBattle::with_config(config);
+        for message in messages {
+            battle.apply_message(message);
+        }
+        footprint += black_box(&battle).memory_footprint_estimate();
+    }
+    (start.elapsed().as_secs_f64() * 1000.0, footprint / REPLAYS)
+}
+
+fn main() {
+    let messages: Vec<ServerMessage> = SAMPLE_LOG
+        .lines()
+        .filter_map(|line| parse_server_message(line).ok())
+        .collect();
+
+    for (name, config) in [
+        ("default", TrackerConfig::new()),
+        ("minimal", TrackerConfig::minimal()),
+    ] {
+        let (ms, bytes) = ingest(&messages, config);
+        println!("{name:>8}: {REPLAYS} replays in {ms:.2}ms, ~{bytes} bytes retained per battle");
+    }
+}
@@ -0,0 +1,484 @@
+//! Flat per-turn records for data pipelines
+//!
+//! A [`TurnRecord`] is the state at the end of one turn plus counts of what
+//! happened during it, in primitive fields only, so it loads into a warehouse
+//! or dataframe without knowing this crate's types. Each side has exactly
+//! [`PARTY_SIZE`] slots, filled in the order its Pokemon were revealed.
+//!
+//! String fields use fixed vocabularies, empty meaning none or unknown:
+//!
+//! - `game_type`: `singles`, `doubles`, `triples`, `multi`, `freeforall`
+//! - `weather`: `sun`, `rain`, `sandstorm`, `hail`, `snow`, `harshsun`,
+//!   `heavyrain`, `strongwinds`
+//! - `terrain`: `electricterrain`, `grassyterrain`, `mistyterrain`,
+//!   `psychicterrain`
+//! - `status_code`: `brn`, `frz`, `par`, `psn`, `tox`, `slp`, or `fnt` once fainted
+//! - `conditions`: `;`-separated side condition ids in the order they were
+//!   set, with `:N` for layers past the first (`spikes:2;stealthrock`)
+//!
+//! `boosts` are in the order atk, def, spa, spd, spe, accuracy, evasion.
+//!
+//! Fields are only ever added, never renamed or removed; [`SCHEMA_VERSION`]
+//! goes up with each addition. [`write_csv`] flattens nested fields into
+//! columns like `p1_slot3_boost_spe`, and `write_json_lines` (with the `serde`
+//! feature) writes one JSON object per record.
+
+use std::io::{self, Write};
+
+use kazam_protocol::{GameType, Player, ServerMessage, user_id};
+
+use crate::tracking::TrackedBattle;
+use crate::types::{PokemonState, SideState};
+
+/// Version of the [`TurnRecord`] field set
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Party slots recorded per side
+pub const PARTY_SIZE: usize = 6;
+
+/// Column suffixes of a slot's `boosts`, in order
+const BOOST_NAMES: [&str; 7] = ["atk", "def", "spa", "spd", "spe", "accuracy", "evasion"];
+
+/// One party slot at the end of a turn
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlotRecord {
+    /// Species, empty for a slot not revealed yet
+    pub species: String,
+
+    pub active: bool,
+
+    /// Remaining HP from 0.0 to 1.0
+    pub hp_fraction: f32,
+
+    pub status_code: String,
+
+    pub revealed_move_count: u8,
+
+    pub item_known: bool,
+
+    pub ability_known: bool,
+
+    pub terastallized: bool,
+
+    pub boosts: [i8; 7],
+}
+
+/// One side at the end of a turn
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SideRecord {
+    pub username: String,
+
+    /// Pokemon revealed so far
+    pub revealed: u8,
+
+    /// Revealed Pokemon that haven't fainted
+    pub remaining: u8,
+
+    pub conditions: String,
+
+    /// Moves used by this side during the turn
+    pub moves_used: u32,
+
+    /// Switches and drags into this side during the turn
+    pub switches: u32,
+
+    /// This side's Pokemon that fainted during the turn
+    pub faints: u32,
+
+    pub slots: [SlotRecord; PARTY_SIZE],
+}
+
+/// The state at the end of a turn and what happened during it
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TurnRecord {
+    pub schema_version: u32,
+
+    /// Caller's id for the battle, e.g. its replay id
+    pub battle_id: String,
+
+    /// Turn the record closes; 0 covers the leads coming out
+    pub turn: u32,
+
+    pub generation: u8,
+
+    pub tier: String,
+
+    pub game_type: String,
+
+    pub weather: String,
+
+    pub terrain: String,
+
+    pub trick_room: bool,
+
+    pub ended: bool,
+
+    pub winner: String,
+
+    /// Protocol messages applied during the turn
+    pub event_count: u32,
+
+    pub p1: SideRecord,
+
+    pub p2: SideRecord,
+}
+
+impl TurnRecord {
+    /// Flatten `battle`'s current state, with `events` as the turn's messages
+    pub fn from_battle(battle_id: &str, battle: &TrackedBattle, events: &[ServerMessage]) -> Self {
+        let side = |player| {
+            let mut record = battle.get_side(player).map(side_record).unwrap_or_default();
+            count_events(&mut record, player, events);
+            record
+        };
+        Self {
+            schema_version: SCHEMA_VERSION,
+            battle_id: battle_id.to_string(),
+            turn: battle.turn,
+            generation: battle.generation,
+            tier: battle.tier.clone(),
+            game_type: battle
+                .game_type
+                .map(game_type_code)
+                .unwrap_or_default()
+                .to_string(),
+            weather: battle
+                .field
+                .weather
+                .map_or_else(String::new, |w| code(w.as_str())),
+            terrain: battle
+                .field
+                .terrain
+                .map_or_else(String::new, |t| code(t.as_str())),
+            trick_room: battle.field.trick_room,
+            ended: battle.ended,
+            winner: battle.winner.clone().unwrap_or_default(),
+            event_count: events.len() as u32,
+            p1: side(Player::P1),
+            p2: side(Player::P2),
+        }
+    }
+
+    /// CSV column names, in the order [`TurnRecord::csv_row`] fills them
+    pub fn csv_header() -> Vec<String> {
+        Self::default()
+            .columns()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// This record's values as CSV fields, unquoted
+    pub fn csv_row(&self) -> Vec<String> {
+        self.columns().into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Every field flattened to a column name and value
+    fn columns(&self) -> Vec<(String, String)> {
+        let mut columns = vec![
+            (
+                "schema_version".to_string(),
+                self.schema_version.to_string(),
+            ),
+            ("battle_id".to_string(), self.battle_id.clone()),
+            ("turn".to_string(), self.turn.to_string()),
+            ("generation".to_string(), self.generation.to_string()),
+            ("tier".to_string(), self.tier.clone()),
+            ("game_type".to_string(), self.game_type.clone()),
+            ("weather".to_string(), self.weather.clone()),
+            ("terrain".to_string(), self.terrain.clone()),
+            ("trick_room".to_string(), self.trick_room.to_string()),
+            ("ended".to_string(), self.ended.to_string()),
+            ("winner".to_string(), self.winner.clone()),
+            ("event_count".to_string(), self.event_count.to_string()),
+        ];
+        for (prefix, side) in [("p1", &self.p1), ("p2", &self.p2)] {
+            let mut push = |name: &str, value: String| {
+                columns.push((format!("{prefix}_{name}"), value));
+            };
+            push("username", side.username.clone());
+            push("revealed", side.revealed.to_string());
+            push("remaining", side.remaining.to_string());
+            push("conditions", side.conditions.clone());
+            push("moves_used", side.moves_used.to_string());
+            push("switches", side.switches.to_string());
+            push("faints", side.faints.to_string());
+            for (n, slot) in side.slots.iter().enumerate() {
+                let slot_name = |field: &str| format!("slot{}_{field}", n + 1);
+                push(&slot_name("species"), slot.species.clone());
+                push(&slot_name("active"), slot.active.to_string());
+                push(&slot_name("hp_fraction"), slot.hp_fraction.to_string());
+                push(&slot_name("status_code"), slot.status_code.clone());
+                push(
+                    &slot_name("revealed_move_count"),
+                    slot.revealed_move_count.to_string(),
+                );
+                push(&slot_name("item_known"), slot.item_known.to_string());
+                push(&slot_name("ability_known"), slot.ability_known.to_string());
+                push(&slot_name("terastallized"), slot.terastallized.to_string());
+                for (stat, boost) in BOOST_NAMES.iter().zip(slot.boosts) {
+                    push(&slot_name(&format!("boost_{stat}")), boost.to_string());
+                }
+            }
+        }
+        columns
+    }
+}
+
+impl TrackedBattle {
+    /// Apply `messages` from this state, producing a record as each turn closes
+    ///
+    /// A turn closes at the next `|turn|` line; whatever follows the last one
+    /// (usually the `|win|`) closes the final turn.
+    pub fn turn_records<'a, I>(&self, battle_id: &str, messages: I) -> Vec<TurnRecord>
+    where
+        I: IntoIterator<Item = &'a ServerMessage>,
+    {
+        let mut battle = self.clone();
+        let mut records = Vec::new();
+        let mut events = Vec::new();
+        for message in messages {
+            if matches!(message, ServerMessage::Turn(_)) {
+                records.push(TurnRecord::from_battle(battle_id, &battle, &events));
+                events.clear();
+            } else {
+                events.push(message.clone());
+            }
+            battle.apply_message(message);
+        }
+        if !events.is_empty() {
+            records.push(TurnRecord::from_battle(battle_id, &battle, &events));
+        }
+        records
+    }
+}
+
+/// Write `records` as CSV with a header row
+pub fn write_csv<W: Write>(records: &[TurnRecord], mut out: W) -> io::Result<()> {
+    write_csv_line(&mut out, &TurnRecord::csv_header())?;
+    for record in records {
+        write_csv_line(&mut out, &record.csv_row())?;
+    }
+    Ok(())
+}
+
+/// Write `records` as JSON Lines, one object per line
+#[cfg(feature = "serde")]
+pub fn write_json_lines<W: Write>(records: &[TurnRecord], mut out: W) -> io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut out, record)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn write_csv_line<W: Write>(out: &mut W, fields: &[String]) -> io::Result<()> {
+    let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    writeln!(out, "{}", line.join(","))
+}
+
+/// Quote a field holding a comma, quote or line break, doubling its quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Lowercase letters and digits of a display name, as Showdown ids are
+fn code(name: &str) -> String {
+    user_id(name)
+}
+
+fn game_type_code(game_type: GameType) -> &'static str {
+    match game_type {
+        GameType::Singles => "singles",
+        GameType::Doubles => "doubles",
+        GameType::Triples => "triples",
+        GameType::Multi => "multi",
+        GameType::FreeForAll => "freeforall",
+    }
+}
+
+fn side_record(side: &SideState) -> SideRecord {
+    let conditions: Vec<String> = side
+        .conditions
+        .iter()
+        .map(|(condition, state)| match state.layers {
+            0 | 1 => code(condition.as_str()),
+            layers => format!("{}:{layers}", code(condition.as_str())),
+        })
+        .collect();
+    let mut slots: [SlotRecord; PARTY_SIZE] = Default::default();
+    for (slot, poke) in slots.iter_mut().zip(&side.pokemon) {
+        *slot = slot_record(poke);
+    }
+    SideRecord {
+        username: side.username.clone(),
+        revealed: side.pokemon.len() as u8,
+        remaining: side.pokemon.iter().filter(|p| !p.fainted).count() as u8,
+        conditions: conditions.join(";"),
+        slots,
+        ..SideRecord::default()
+    }
+}
+
+fn slot_record(poke: &PokemonState) -> SlotRecord {
+    let b = &poke.boosts;
+    SlotRecord {
+        species: poke.identity.species.clone(),
+        active: poke.active,
+        hp_fraction: poke.hp_percent_exact() / 100.0,
+        status_code: if poke.fainted {
+            "fnt".to_string()
+        } else {
+            poke.status
+                .map(|s| s.to_protocol())
+                .unwrap_or_default()
+                .to_string()
+        },
+        revealed_move_count: poke.known_moves.len() as u8,
+        item_known: poke.known_item.is_some(),
+        ability_known: poke.known_ability.is_some(),
+        terastallized: poke.terastallized,
+        boosts: [b.atk, b.def, b.spa, b.spd, b.spe, b.accuracy, b.evasion],
+    }
+}
+
+fn count_events(record: &mut SideRecord, player: Player, events: &[ServerMessage]) {
+    for event in events {
+        match event {
+            ServerMessage::Move { pokemon, .. } if pokemon.player == player => {
+                record.moves_used += 1;
+            }
+            ServerMessage::Switch { pokemon, .. } | ServerMessage::Drag { pokemon, .. }
+                if pokemon.player == player =>
+            {
+                record.switches += 1;
+            }
+            ServerMessage::Faint(pokemon) if pokemon.player == player => record.faints += 1,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::parse_server_message;
+
+    const LOG: &str = "|player|p1|Alice|1
+|player|p2|Bob, Jr.|2
+|gametype|singles
+|gen|9
+|tier|[Gen 9] OU
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Heatran|Heatran, L50, M|100/100
+|turn|1
+|move|p1a: Garchomp|Stealth Rock|p2a: Heatran
+|-sidestart|p2: Bob, Jr.|move: Stealth Rock
+|move|p2a: Heatran|Magma Storm|p1a: Garchomp
+|-damage|p1a: Garchomp|60/100
+|turn|2
+|move|p1a: Garchomp|Swords Dance|p1a: Garchomp
+|-boost|p1a: Garchomp|atk|2
+|move|p2a: Heatran|Will-O-Wisp|p1a: Garchomp
+|-status|p1a: Garchomp|brn
+|turn|3
+|move|p1a: Garchomp|Earthquake|p2a: Heatran
+|-damage|p2a: Heatran|0 fnt
+|faint|p2a: Heatran
+|win|Alice";
+
+    fn records() -> Vec<TurnRecord> {
+        let messages: Vec<ServerMessage> = LOG
+            .lines()
+            .map(|line| parse_server_message(line).unwrap())
+            .collect();
+        TrackedBattle::new().turn_records("gen9ou-1", &messages)
+    }
+
+    #[test]
+    fn test_records_close_each_turn() {
+        let records = records();
+        assert_eq!(
+            records.iter().map(|r| r.turn).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+
+        let first = &records[1];
+        assert_eq!(first.p2.conditions, "stealthrock");
+        assert_eq!((first.p1.moves_used, first.p2.moves_used), (1, 1));
+        assert_eq!(first.p1.slots[0].hp_fraction, 0.6);
+
+        let second = &records[2];
+        let garchomp = &second.p1.slots[0];
+        assert_eq!(garchomp.status_code, "brn");
+        assert_eq!(garchomp.boosts, [2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(garchomp.revealed_move_count, 2);
+        assert_eq!(second.p1.slots[1], SlotRecord::default());
+
+        let last = &records[3];
+        assert!(last.ended);
+        assert_eq!(last.winner, "Alice");
+        assert_eq!((last.p2.faints, last.p2.remaining), (1, 0));
+        assert_eq!(last.p2.slots[0].status_code, "fnt");
+        assert_eq!(last.game_type, "singles");
+        assert_eq!(last.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_csv_rows_match_header() {
+        let records = records();
+        let header = TurnRecord::csv_header();
+        assert_eq!(header.len(), 12 + 2 * (7 + PARTY_SIZE * 15));
+        assert!(records.iter().all(|r| r.csv_row().len() == header.len()));
+
+        let mut out = Vec::new();
+        write_csv(&records, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1 + records.len());
+        assert!(lines[0].starts_with("schema_version,battle_id,turn,"));
+        assert!(lines[0].contains(",p2_slot6_boost_evasion"));
+        // The comma in Bob's name is quoted
+        assert!(lines[1].contains(",\"Bob, Jr.\","));
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    /// Columns of every released schema version; new columns may be added
+    /// anywhere, but none of these may disappear
+    #[test]
+    fn test_columns_only_grow() {
+        let header = TurnRecord::csv_header();
+        let released = include_str!("../testdata/turn_record_columns.txt");
+        for column in released
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            assert!(
+                header.iter().any(|c| c == column),
+                "column {column} was removed; bump SCHEMA_VERSION and only add columns"
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_lines_round_trip() {
+        let records = records();
+        let mut out = Vec::new();
+        write_json_lines(&records, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let parsed: Vec<TurnRecord> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, records);
+    }
+}
@@ -0,0 +1,498 @@
+//! Structured differences between two tracked battle states
+//!
+//! Intended for regression tests: comparing a tracker's output against an
+//! expected state gives one readable line per difference instead of a
+//! wall of `Debug` output.
+
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
+
+use crate::tracking::TrackedBattle;
+use crate::types::{FieldState, PokemonState, SideState};
+
+/// A single difference between two battle states
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDifference {
+    /// Where the difference is (e.g. "p2 Garchomp hp_current")
+    pub path: String,
+
+    /// What differs (e.g. "54 vs 47" or "+Spikes(2)")
+    pub description: String,
+}
+
+impl fmt::Display for StateDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.description)
+    }
+}
+
+/// Differences to leave out of a comparison
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Skip volatile conditions entirely
+    pub ignore_volatiles: bool,
+
+    /// Compare revealed moves as a set rather than in reveal order
+    pub ignore_move_order: bool,
+
+    /// Skip knowledge mode, viewpoint and tracker configuration
+    pub ignore_viewpoint: bool,
+}
+
+/// Compare two battle states with default options
+pub fn diff_battles(a: &TrackedBattle, b: &TrackedBattle) -> Vec<StateDifference> {
+    diff_battles_with(a, b, DiffOptions::default())
+}
+
+/// Compare two battle states, skipping what `options` marks as benign
+pub fn diff_battles_with(
+    a: &TrackedBattle,
+    b: &TrackedBattle,
+    options: DiffOptions,
+) -> Vec<StateDifference> {
+    let mut diff = Differ::default();
+
+    diff.field("battle game_type", &a.game_type, &b.game_type);
+    diff.field("battle generation", &a.generation, &b.generation);
+    diff.field("battle tier", &a.tier, &b.tier);
+    diff.field("battle rules", &a.rules, &b.rules);
+    diff.field("battle turn", &a.turn, &b.turn);
+    diff.field("battle ended", &a.ended, &b.ended);
+    diff.field("battle winner", &a.winner, &b.winner);
+    diff.field("battle tie", &a.tie, &b.tie);
+    diff.field("battle combined_moves", &a.combined_moves, &b.combined_moves);
+    diff.field("battle reflected_moves", &a.reflected_moves, &b.reflected_moves);
+    if !options.ignore_viewpoint {
+        diff.field("battle knowledge", &a.knowledge(), &b.knowledge());
+        diff.field("battle viewpoint", &a.viewpoint(), &b.viewpoint());
+        diff.field("battle config", &a.config(), &b.config());
+    }
+
+    // Listed in full so a new field can't be left out of the diff
+    let FieldState {
+        weather,
+        terrain,
+        trick_room,
+        magic_room,
+        wonder_room,
+        gravity,
+        mud_sport,
+        water_sport,
+        ion_deluge,
+        fairy_lock,
+        stat_modifiers,
+        neutralizing_gas,
+    } = &a.field;
+    let fb = &b.field;
+    macro_rules! field_fields {
+        ($($name:ident),*) => {
+            $(diff.field(concat!("field ", stringify!($name)), $name, &fb.$name);)*
+        };
+    }
+    field_fields!(
+        weather,
+        terrain,
+        trick_room,
+        magic_room,
+        wonder_room,
+        gravity,
+        mud_sport,
+        water_sport,
+        ion_deluge,
+        fairy_lock,
+        stat_modifiers,
+        neutralizing_gas
+    );
+
+    for (side_a, side_b) in a.sides.iter().zip(&b.sides) {
+        match (side_a, side_b) {
+            (Some(side_a), Some(side_b)) => diff.side(side_a, side_b, options),
+            (Some(side), None) => diff.push(side.player.as_str(), "present vs missing"),
+            (None, Some(side)) => diff.push(side.player.as_str(), "missing vs present"),
+            (None, None) => {}
+        }
+    }
+
+    diff.out
+}
+
+#[derive(Default)]
+struct Differ {
+    out: Vec<StateDifference>,
+}
+
+impl Differ {
+    fn push(&mut self, path: impl Into<String>, description: impl Into<String>) {
+        self.out.push(StateDifference {
+            path: path.into(),
+            description: description.into(),
+        });
+    }
+
+    fn field<T: PartialEq + Debug>(&mut self, path: impl Into<String>, a: &T, b: &T) {
+        if a != b {
+            self.push(path, format!("{:?} vs {:?}", a, b));
+        }
+    }
+
+    fn side(&mut self, a: &SideState, b: &SideState, options: DiffOptions) {
+        // Listed in full so a new field can't be left out of the diff
+        let SideState {
+            player,
+            username,
+            pokemon,
+            active_indices,
+            conditions,
+            team_sheet,
+            previewed,
+            request_order,
+            hazards_cleared,
+            screens_cleared,
+            cleared_by,
+            total_switches,
+            pending_heals,
+        } = a;
+        let player = player.as_str();
+        macro_rules! fields {
+            ($($name:ident),*) => {
+                $(self.field(format!("{} {}", player, stringify!($name)), $name, &b.$name);)*
+            };
+        }
+        fields!(
+            username,
+            team_sheet,
+            previewed,
+            request_order,
+            hazards_cleared,
+            screens_cleared,
+            cleared_by,
+            total_switches,
+            active_indices,
+            pending_heals
+        );
+
+        // Both sides' conditions in the order they were set, a's first
+        let added = b.conditions.keys().filter(|c| !conditions.contains_key(c));
+        for condition in conditions.keys().chain(added) {
+            let description = match (conditions.get(condition), b.conditions.get(condition)) {
+                (Some(sa), Some(sb)) if sa == sb => continue,
+                (Some(sa), Some(sb)) => format!(
+                    "{:?}({}) vs {:?}({})",
+                    condition, sa.layers, condition, sb.layers
+                ),
+                (Some(sa), None) => format!("-{:?}({})", condition, sa.layers),
+                (None, Some(sb)) => format!("+{:?}({})", condition, sb.layers),
+                (None, None) => continue,
+            };
+            self.push(format!("{} conditions", player), description);
+        }
+
+        self.field(
+            format!("{} pokemon count", player),
+            &pokemon.len(),
+            &b.pokemon.len(),
+        );
+        for (pa, pb) in pokemon.iter().zip(&b.pokemon) {
+            let path = format!("{} {}", player, pa.name());
+            self.pokemon(&path, pa, pb, options);
+        }
+    }
+
+    fn pokemon(&mut self, path: &str, a: &PokemonState, b: &PokemonState, options: DiffOptions) {
+        // Listed in full so a new field can't be left out of the diff
+        let PokemonState {
+            identity,
+            hp_current,
+            hp_max,
+            stats,
+            base_stats_snapshot,
+            stat_constraints,
+            stat_bounds,
+            stat_split,
+            status,
+            status_from,
+            fainted,
+            active,
+            boosts,
+            paradox_from_booster,
+            base_types,
+            current_types,
+            tera_type,
+            terastallized,
+            known_move_pp,
+            known_ability,
+            ability_override,
+            known_item,
+            item_consumed,
+            last_move,
+            charging_move,
+            scouting,
+            contradictions,
+            transformed,
+            dynamaxed,
+            mega_evolved,
+            disguise_busted,
+            switch_in_boost_used,
+            has_acted_this_turn,
+            times_switched_in,
+            turns_on_field,
+            damage_dealt_estimate,
+            damage_taken_estimate,
+            damage_taken,
+            damage_dealt,
+            hits_taken_this_turn,
+            hits_taken_last_turn,
+            known_moves,
+            move_sources,
+            volatiles,
+        } = a;
+        macro_rules! fields {
+            ($($name:ident),*) => {
+                $(self.field(format!("{} {}", path, stringify!($name)), $name, &b.$name);)*
+            };
+        }
+        fields!(
+            identity,
+            hp_current,
+            hp_max,
+            stats,
+            base_stats_snapshot,
+            stat_constraints,
+            stat_bounds,
+            stat_split,
+            status,
+            status_from,
+            fainted,
+            active,
+            boosts,
+            paradox_from_booster,
+            base_types,
+            current_types,
+            tera_type,
+            terastallized,
+            known_move_pp,
+            known_ability,
+            ability_override,
+            known_item,
+            item_consumed,
+            last_move,
+            charging_move,
+            scouting,
+            contradictions,
+            transformed,
+            dynamaxed,
+            mega_evolved,
+            disguise_busted,
+            switch_in_boost_used,
+            has_acted_this_turn,
+            times_switched_in,
+            turns_on_field,
+            damage_dealt_estimate,
+            damage_taken_estimate,
+            damage_taken,
+            damage_dealt,
+            hits_taken_this_turn,
+            hits_taken_last_turn
+        );
+
+        if options.ignore_move_order {
+            let moves_a: HashSet<_> = known_moves.iter().collect();
+            let moves_b: HashSet<_> = b.known_moves.iter().collect();
+            self.field(format!("{} known_moves", path), &moves_a, &moves_b);
+            let sources_a: HashSet<_> = move_sources.iter().collect();
+            let sources_b: HashSet<_> = b.move_sources.iter().collect();
+            self.field(format!("{} move_sources", path), &sources_a, &sources_b);
+        } else {
+            self.field(format!("{} known_moves", path), known_moves, &b.known_moves);
+            self.field(format!("{} move_sources", path), move_sources, &b.move_sources);
+        }
+
+        if !options.ignore_volatiles {
+            let changes: Vec<String> = volatiles
+                .difference(&b.volatiles)
+                .map(|v| format!("-{:?}", v))
+                .chain(
+                    b.volatiles
+                        .difference(volatiles)
+                        .map(|v| format!("+{:?}", v)),
+                )
+                .chain(volatiles.states().filter_map(|(v, state)| {
+                    let other = b.volatiles.get(v)?;
+                    (state != other).then(|| {
+                        format!(
+                            "{:?}({:?} vs {:?})",
+                            v, state.turns_remaining, other.turns_remaining
+                        )
+                    })
+                }))
+                .collect();
+            if !changes.is_empty() {
+                self.push(format!("{} volatiles", path), changes.join(" "));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SideCondition, Volatile};
+    use kazam_protocol::{Player, parse_server_message};
+
+    const LOG: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|turn|1
+|move|p2a: Garchomp|Earthquake|p1a: Corviknight
+|-immune|p1a: Corviknight
+|move|p1a: Corviknight|Brave Bird|p2a: Garchomp
+|-damage|p2a: Garchomp|54/100"#;
+
+    fn tracked(log: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::new();
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    #[test]
+    fn test_identical_battles_have_no_diff() {
+        let a = tracked(LOG);
+        let b = tracked(LOG);
+        assert_eq!(a, b);
+        assert!(diff_battles(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_readable_differences() {
+        let a = tracked(LOG);
+        let mut b = tracked(LOG);
+        b.get_side_mut(Player::P2).unwrap().pokemon[0].hp_current = 47;
+        b.get_side_mut(Player::P1)
+            .unwrap()
+            .add_condition(SideCondition::Spikes);
+        b.get_side_mut(Player::P1)
+            .unwrap()
+            .add_condition(SideCondition::Spikes);
+        b.get_side_mut(Player::P1).unwrap().pokemon[0]
+            .volatiles
+            .insert(Volatile::Confusion);
+
+        let lines: Vec<String> = diff_battles(&a, &b).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "p1 conditions: +Spikes(2)",
+                "p1 Corviknight volatiles: +Confusion",
+                "p2 Garchomp hp_current: 54 vs 47",
+            ]
+        );
+
+        let options = DiffOptions {
+            ignore_volatiles: true,
+            ..DiffOptions::default()
+        };
+        assert_eq!(diff_battles_with(&a, &b, options).len(), 2);
+    }
+
+    #[test]
+    fn test_volatile_turns_differ() {
+        let mut a = tracked(LOG);
+        a.get_side_mut(Player::P2).unwrap().pokemon[0]
+            .volatiles
+            .insert(Volatile::Taunt);
+        let mut b = a.clone();
+        b.get_side_mut(Player::P2).unwrap().pokemon[0].volatiles.tick();
+
+        let lines: Vec<String> = diff_battles(&a, &b).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec!["p2 Garchomp volatiles: Taunt(Some(3) vs Some(2))"]
+        );
+    }
+
+    #[test]
+    fn test_ignore_move_order() {
+        let a = tracked(LOG);
+        let mut b = tracked(LOG);
+        b.get_side_mut(Player::P2).unwrap().pokemon[0].known_moves =
+            smallvec::smallvec!["Earthquake".to_string(), "Stone Edge".to_string()];
+        let mut c = b.clone();
+        c.get_side_mut(Player::P2).unwrap().pokemon[0]
+            .known_moves
+            .reverse();
+
+        assert_eq!(diff_battles(&b, &c).len(), 1);
+        let options = DiffOptions {
+            ignore_move_order: true,
+            ..DiffOptions::default()
+        };
+        assert!(diff_battles_with(&b, &c, options).is_empty());
+        assert_eq!(diff_battles_with(&a, &c, options).len(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialization_is_deterministic() {
+        let log = format!(
+            "{LOG}\n|-sidestart|p1: Alice|Spikes\n|-sidestart|p1: Alice|move: Stealth Rock\n\
+             |-sidestart|p1: Alice|Reflect\n|-sidestart|p1: Alice|Spikes\n\
+             |-start|p2a: Garchomp|confusion\n|-start|p2a: Garchomp|Substitute\n\
+             |-start|p2a: Garchomp|move: Taunt"
+        );
+        let a = tracked(&log);
+        let b = tracked(&log);
+        let conditions: Vec<_> = a.get_side(Player::P1).unwrap().conditions.keys().collect();
+        assert_eq!(
+            conditions,
+            [
+                &SideCondition::Spikes,
+                &SideCondition::StealthRock,
+                &SideCondition::Reflect
+            ]
+        );
+
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, serde_json::to_string(&b).unwrap());
+        let round_trip: TrackedBattle = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&round_trip).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_golden_replay() {
+        let mut battle = TrackedBattle::omniscient();
+        battle.set_viewpoint(Player::P1);
+        let mut via_input = battle.clone();
+        for line in include_str!("../testdata/golden_replay.log").lines() {
+            let message = parse_server_message(line).unwrap();
+            battle.apply_message(&message);
+            via_input.apply_input(crate::TrackerInput::from(&message));
+        }
+        assert_eq!(battle.inconsistencies(), []);
+        assert_eq!(diff_battles(&battle, &via_input), []);
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden_replay.json");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, serde_json::to_string_pretty(&battle).unwrap()).unwrap();
+        }
+
+        let expected: TrackedBattle =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let differences = diff_battles(&expected, &battle);
+        assert!(
+            differences.is_empty(),
+            "tracked state differs from {} (expected vs actual, rerun with UPDATE_GOLDEN=1 to accept):\n{}",
+            path,
+            differences
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+}
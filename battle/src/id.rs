@@ -0,0 +1,155 @@
+//! Showdown IDs, the normalized names every lookup in this crate matches on
+//!
+//! An ID is a name lowercased with everything but ASCII letters and digits
+//! dropped ("King's Shield" -> "kingsshield"), with a few cases Showdown's
+//! own names rely on:
+//!
+//! - "Nidoran♀" and "Nidoran♂" keep their gender as "nidoranf" and
+//!   "nidoranm", as the simulator spells them, instead of colliding
+//! - accented letters fold to their base letter ("Flabébé" -> "flabebe")
+//! - apostrophes of either kind drop out ("Farfetch’d" -> "farfetchd")
+//!
+//! Custom servers' mods and localized logs name things their own way;
+//! [`register_alias`] maps such a name onto the ID it stands for.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+/// Showdown ID form of a name, with any registered alias applied
+pub fn to_id(name: &str) -> String {
+    let id = plain_id(name);
+    if !HAS_ALIASES.load(Ordering::Acquire) {
+        return id;
+    }
+    aliases()
+        .read()
+        .ok()
+        .and_then(|aliases| aliases.get(&id).cloned())
+        .unwrap_or(id)
+}
+
+/// Make `from` normalize to the ID of `to` from now on, for every lookup in
+/// the process ("Flabebe-Mod" -> "Flabébé"); registering `from` again
+/// replaces its target
+pub fn register_alias(from: &str, to: &str) {
+    let (from, to) = (plain_id(from), plain_id(to));
+    if from.is_empty() || from == to {
+        return;
+    }
+    if let Ok(mut aliases) = aliases().write() {
+        aliases.insert(from, to);
+        HAS_ALIASES.store(true, Ordering::Release);
+    }
+}
+
+/// ID of a species name, or None for junk no real Pokemon is called: names
+/// with no letters left, and glitch species like MissingNo.
+pub fn species_id(name: &str) -> Option<String> {
+    let id = to_id(name);
+    let junk =
+        !id.chars().any(|c| c.is_ascii_alphabetic()) || GLITCH_SPECIES.contains(&id.as_str());
+    (!junk).then_some(id)
+}
+
+/// Glitch species that show up in hacked or corrupted logs, by ID
+const GLITCH_SPECIES: &[&str] = &["missingno", "m00"];
+
+/// Whether any alias was registered, so lookups skip the lock until one is
+static HAS_ALIASES: AtomicBool = AtomicBool::new(false);
+
+fn aliases() -> &'static RwLock<HashMap<String, String>> {
+    static ALIASES: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+    ALIASES.get_or_init(Default::default)
+}
+
+/// The ID before aliases
+fn plain_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(fold_accent)
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if id == "nidoran" {
+        if name.contains('♀') {
+            return "nidoranf".to_string();
+        }
+        if name.contains('♂') {
+            return "nidoranm".to_string();
+        }
+    }
+    id
+}
+
+/// A Latin letter with its accent dropped; other characters pass through
+fn fold_accent(c: char) -> char {
+    match c {
+        'à'..='å' | 'À'..='Å' => 'a',
+        'ç' | 'Ç' => 'c',
+        'è'..='ë' | 'È'..='Ë' => 'e',
+        'ì'..='ï' | 'Ì'..='Ï' => 'i',
+        'ñ' | 'Ñ' => 'n',
+        'ò'..='ö' | 'ø' | 'Ò'..='Ö' | 'Ø' => 'o',
+        'ù'..='ü' | 'Ù'..='Ü' => 'u',
+        'ý' | 'ÿ' | 'Ý' => 'y',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_names() {
+        assert_eq!(to_id("King's Shield"), "kingsshield");
+        assert_eq!(to_id("Porygon-Z"), "porygonz");
+        assert_eq!(to_id("U-turn"), "uturn");
+    }
+
+    #[test]
+    fn test_nidoran_keeps_its_gender() {
+        assert_eq!(to_id("Nidoran♀"), "nidoranf");
+        assert_eq!(to_id("Nidoran♂"), "nidoranm");
+        assert_eq!(to_id("Nidoran-F"), "nidoranf");
+        assert_eq!(to_id("Nidoran-M"), "nidoranm");
+        assert_ne!(to_id("Nidoran♀"), to_id("Nidoran♂"));
+    }
+
+    #[test]
+    fn test_apostrophes_and_accents() {
+        assert_eq!(to_id("Farfetch'd"), "farfetchd");
+        assert_eq!(to_id("Farfetch’d"), "farfetchd");
+        assert_eq!(to_id("Sirfetch’d"), "sirfetchd");
+        assert_eq!(to_id("Flabébé"), "flabebe");
+        assert_eq!(to_id("FLABÉBÉ"), "flabebe");
+        assert_eq!(to_id("Pokémon"), "pokemon");
+    }
+
+    #[test]
+    fn test_junk_species_rejected() {
+        assert_eq!(species_id("MissingNo."), None);
+        assert_eq!(species_id("M (00)"), None);
+        assert_eq!(species_id("???"), None);
+        assert_eq!(species_id(""), None);
+        assert_eq!(species_id("Flabébé").as_deref(), Some("flabebe"));
+        assert_eq!(species_id("Porygon2").as_deref(), Some("porygon2"));
+    }
+
+    #[test]
+    fn test_custom_alias_round_trip() {
+        register_alias("Kazam Test Pikachu", "Pikachu");
+        assert_eq!(to_id("Kazam Test Pikachu"), "pikachu");
+        assert_eq!(to_id("kazamtestpikachu"), to_id("Pikachu"));
+
+        register_alias("Kazam Test Pikachu", "Raichu");
+        assert_eq!(to_id("Kazam Test Pikachu"), "raichu");
+
+        // Aliasing a name to itself or from nothing is ignored
+        register_alias("Pikachu", "Pikachu");
+        register_alias("!!", "Raichu");
+        assert_eq!(to_id("Pikachu"), "pikachu");
+        assert_eq!(to_id("!!"), "");
+    }
+}
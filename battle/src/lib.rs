@@ -8,6 +8,9 @@
 //! `kazam-battle` sits between `kazam-protocol` (wire format) and higher-level components:
 //!
 //! ```text
+//! kazam-battle-core (no_std types + type chart)
+//!        │
+//!        ▼
 //! kazam-protocol (wire format)
 //!        │
 //!        ▼
@@ -26,19 +29,55 @@
 //! - [`Volatile`] - Volatile conditions (Confusion, Taunt, etc.)
 //! - [`StatStages`] - Stat stage modifiers (-6 to +6)
 //! - [`Weather`], [`Terrain`], [`SideCondition`] - Field conditions
+//! - [`Mechanics`] - Rules that differ between generations
 //! - [`PokemonState`] - Full Pokemon battle state
+//! - [`PokemonSnapshot`] - `Copy` view of a [`PokemonState`] for calculations
 //! - [`SideState`] - One player's side of the battle
 //! - [`FieldState`] - Global field conditions
 //!
+//! The protocol-independent types above ([`Type`] through [`SideCondition`])
+//! live in `kazam-battle-core` and are re-exported here unchanged.
+//!
+//! ## Query Helpers
+//! - [`weaknesses`], [`resistances`], [`immunities`] and friends - Type matchup queries
+//! - [`DefensiveProfile`] - Every incoming multiplier for a typing or Pokemon, looked up once
+//! - [`query::grounding::is_grounded`] / [`effectiveness_against`] - Grounding and Ground-move immunity
+//! - [`query::inference::ability_hypotheses`] - Candidate abilities from unexplained immunities and failures
+//! - [`query::inference::speed_item_hypotheses`] - A Choice Scarf from move orders that Speed, priority and visible effects can't explain
+//! - [`query::inference::sets_under_rules`] - Candidate sets without the moves the format's clauses ban
+//! - [`query::inference::candidate_scores`] - Candidate set likelihoods, with scouted details counting less than reveals
+//! - [`query::preview::analyze`] - Team preview lead and order analysis
+//! - [`query::trapping::opponent_trapped`] / [`query::trapping::my_active_trapped`] - Whether either active can switch out
+//! - [`query::evaluate::evaluate_actions`] - Score every legal action with tunable [`query::evaluate::EvalWeights`]
+//! - [`query::targeting::TargetingContext`] - Which doubles targets a move can pick, and Follow Me / Rage Powder redirection
+//! - [`query::damage::DamageContext`] - Spread, burn, screen, Friend Guard and Life Orb modifiers, applied to a base damage in the simulator's order
+//! - [`query::rounding`] - The simulator's 4096ths rounding and residual HP fractions
+//! - [`query::switch_in_effects`] - Boosts, drops, weather and terrain an ability brings on switching in
+//! - [`id::to_id`] / [`id::register_alias`] - Showdown IDs every name lookup matches on, with custom aliases
+//!
 //! ## State Tracking
 //! - [`TrackedBattle`] - Main entry point for reducing protocol messages into battle state
 //! - [`BattleKnowledge`] - Declares whether the state is public-only, player-enriched, or omniscient
 //! - [`BattleSnapshot`] / [`TurnSnapshot`] - Snapshot helpers for restoring or indexing reduced state
+//! - [`ScoutingReport`] - Opponent knowledge carried between games of a series
+//! - [`TrackedBattle::usage_summary`] / [`UsageSummary`] - Switch counts, field time and damage per Pokemon
+//! - [`TrackedBattle::opponent_knowledge`] / [`KnowledgeSummary`] - Unrevealed moves, ability and item per foe, with set entropy from [`CandidateSet`]s
+//! - [`TrackerConfig`] - Controls which parts of the state are retained
+//! - [`TrackedBattle::legal_moves`] / [`LegalMove`] - Request move slots filtered by tracked restrictions
+//! - [`TrackedBattle::rule_context`] / [`RuleContext`] - Sleep, OHKO, Evasion and Endless Battle clauses from |rule| lines, with [`TrackedBattle::rule_warnings`] for choices they waste
+//!
+//! ## Analytics
+//! - [`analytics::TurnRecord`] - Flat, versioned per-turn records with JSON Lines and CSV writers
+//!
+//! ## Regression Testing
+//! - [`diff::diff_battles`] - Readable field-by-field differences between two states
+//!
+//! The [`prelude`] module re-exports the items a typical bot needs.
 //!
 //! # Example Usage
 //!
 //! ```ignore
-//! use kazam_battle::{TrackedBattle, Weather, SideCondition};
+//! use kazam_battle::prelude::*;
 //! use kazam_protocol::ServerMessage;
 //!
 //! let mut battle = TrackedBattle::new();
@@ -58,23 +97,65 @@
 //! }
 //! ```
 
+pub mod analytics;
+pub mod diff;
+pub mod id;
+pub mod prelude;
 pub mod query;
 pub mod tracking;
 pub mod types;
 
 // Re-export main types at crate root for convenience
 pub use tracking::{
+    AllPokemon,
     BattleKnowledge,
     BattleSnapshot,
+    CandidateSet,
+    Clause,
+    CombinedMove,
+    ConditionRemoval,
+    EndlessBattleWarning,
+    Inconsistency,
+    ItemKnowledgeKind,
+    KnowledgeSummary,
+    LegalMove,
+    ReflectedMove,
+    Reconciled,
+    ReconciliationEvent,
+    RuleContext,
+    RuleWarning,
+    PokemonRef,
+    PokemonRefMut,
+    PokemonUsage,
+    ScoutedPokemon,
+    ScoutingReport,
+    Severity,
+    SideUsage,
+    SideVariance,
+    StalenessState,
+    StrictnessMode,
+    SwitchKind,
+    SwitchRecord,
     TrackedBattle,
+    TrackerConfig,
+    TrackerInput,
     TurnSnapshot,
+    UsageSummary,
+    VarianceReport,
     player_to_index,
     position_to_slot,
 };
 pub use types::{
-    FieldState, PokemonIdentity, PokemonState, SideCondition, SideConditionState, SideState,
-    StatStages, Status, Terrain, Type, Volatile, Weather, TYPE_CHART,
+    ChargingMove, DamageKind, DamageLedger, FieldStatModifier, FieldState, HealAmount, HealLanding,
+    HitRecord, KnowledgeEntry, KnowledgeKind, KnownMove, Mechanics, MoveProvenance, Observation, Outcome, PendingHeal,
+    PokemonIdentity, PokemonSnapshot, PokemonState, SideCondition, SideConditionState,
+    SideConditions, SideState, SplitKind, StatBound, StatConstraint, StatStages, Status, TeraType,
+    Terrain, Type, Volatile, VolatileState, Volatiles, Weather, TYPE_CHART,
+};
+
+pub use query::{
+    DefensiveProfile, effectiveness_against, immunities, is_immune_to, is_weak_to_any, resistances, resists_all, weaknesses,
 };
 
 // Re-export commonly used protocol types
-pub use kazam_protocol::{GameType, Player, Stat};
+pub use kazam_protocol::{GameType, Player, PokemonStats, Stat};
@@ -0,0 +1,20 @@
+//! Commonly used types for bots tracking battle state
+//!
+//! ```ignore
+//! use kazam_battle::prelude::*;
+//! ```
+//!
+//! There's no separate choice builder: [`LegalMove::choice`] and
+//! [`Action::choice`] give the strings to send.
+
+pub use crate::query::{
+    Action, DefensiveProfile, immunities, is_immune_to, is_weak_to_any, resistances, resists_all, weaknesses,
+};
+pub use crate::tracking::{
+    BattleKnowledge, LegalMove, TrackedBattle, player_to_index, position_to_slot,
+};
+pub use crate::types::{
+    FieldState, PokemonState, SideCondition, SideConditionState, SideState, StatStages, Status,
+    Terrain, Type, Volatile, Weather,
+};
+pub use kazam_protocol::{GameType, Player, Stat};
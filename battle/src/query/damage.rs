@@ -0,0 +1,487 @@
+//! Damage modifiers that come from the field rather than the attacker
+//!
+//! A [`DamageContext`] collects what changes a hit besides the two Pokemon's
+//! stats: their stat stages, spread moves, a burn, the defender's screens, a
+//! Friend Guard partner and Life Orb. [`DamageContext::damage_rolls`] applies
+//! them to a base damage in the simulator's order, with the 4096ths rounding
+//! of [`rounding`](super::rounding):
+//!
+//! 1. stat stages, which Unaware on the other side ignores
+//! 2. spread (0.75)
+//! 3. critical hit (1.5)
+//! 4. random roll (0.85 to 1.00)
+//! 5. STAB (1.5)
+//! 6. type effectiveness
+//! 7. burn, for physical moves (0.5)
+//! 8. final modifiers chained together: screens, Friend Guard, then Life Orb
+//!
+//! There are no stats or base powers in this crate, so the base damage from
+//! the level, power and unboosted stat formula comes from the caller, and
+//! stages scale it as a whole rather than the stats inside it. Weather, items
+//! besides Life Orb and the attacker's abilities besides Unaware, Mold Breaker
+//! and Guts are not modelled.
+
+use kazam_protocol::{Player, Stat};
+
+use super::rounding::{LIFE_ORB, UNMODIFIED, chain, poke_round};
+use crate::tracking::TrackedBattle;
+use crate::types::{PokemonState, SideCondition, StatStages, Status, to_id};
+
+/// Request targets of moves that hit every adjacent foe, or every adjacent Pokemon
+const SPREAD_TARGETS: &[&str] = &["allAdjacentFoes", "allAdjacent"];
+
+/// Attacker abilities that ignore the defender's Unaware
+const MOLD_BREAKERS: &[&str] = &["moldbreaker", "teravolt", "turboblaze"];
+
+/// Physical or special, for the screen that applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveCategory {
+    Physical,
+    Special,
+}
+
+/// Reductions applying to one hit on one defender
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DamageContext {
+    /// More than one active Pokemon per side, which weakens screens
+    pub doubles: bool,
+
+    /// The move hits more than one target
+    pub spread: bool,
+
+    /// The hit is a critical hit, which ignores screens
+    pub critical: bool,
+
+    /// Physical or special; None when unknown, so only Aurora Veil applies
+    pub category: Option<MoveCategory>,
+
+    /// Aurora Veil on the defender's side
+    pub aurora_veil: bool,
+
+    /// Reflect on the defender's side
+    pub reflect: bool,
+
+    /// Light Screen on the defender's side
+    pub light_screen: bool,
+
+    /// The defender's active partner has Friend Guard
+    pub friend_guard: bool,
+
+    /// The attacker is burned without Guts, halving its physical moves
+    pub burned: bool,
+
+    /// The attacker holds Life Orb
+    pub life_orb: bool,
+
+    /// The attacker's stat stages
+    pub attacker_boosts: StatStages,
+
+    /// The defender's stat stages
+    pub defender_boosts: StatStages,
+
+    /// The attacker has Unaware, so the defender's stages don't count
+    pub attacker_unaware: bool,
+
+    /// The defender has Unaware and no Mold Breaker gets past it, so the
+    /// attacker's stages don't count
+    pub defender_unaware: bool,
+}
+
+impl DamageContext {
+    /// Context for a move with request target `move_target` used by the
+    /// Pokemon in `attacker`'s active slot against the one in `defender`'s
+    ///
+    /// A spread move counts as spread only when more than one Pokemon it can
+    /// hit is on the field.
+    pub fn from_battle(
+        battle: &TrackedBattle,
+        attacker: (Player, usize),
+        defender: (Player, usize),
+        move_target: &str,
+    ) -> Self {
+        let Some(side) = battle.get_side(defender.0) else {
+            return Self::default();
+        };
+        let attacking = battle
+            .get_side(attacker.0)
+            .and_then(|side| side.active(attacker.1));
+        let defending = side.active(defender.1);
+        let ability = |poke: Option<&PokemonState>| {
+            poke.and_then(|poke| poke.effective_ability_on(&battle.field))
+                .map(to_id)
+        };
+        let attacker_ability = ability(attacking);
+        let standing = |player: Player, skip: Option<usize>| {
+            battle.get_side(player).map_or(0, |side| {
+                (0..side.active_indices.len())
+                    .filter(|&slot| Some(slot) != skip)
+                    .filter_map(|slot| side.active(slot))
+                    .filter(|poke| !poke.fainted)
+                    .count()
+            })
+        };
+        let targets = match move_target {
+            "allAdjacentFoes" => standing(defender.0, None),
+            "allAdjacent" => standing(defender.0, None) + standing(attacker.0, Some(attacker.1)),
+            _ => 1,
+        };
+        let friend_guard = (0..side.active_indices.len())
+            .filter(|&slot| slot != defender.1)
+            .filter_map(|slot| side.active(slot))
+            .any(|partner| {
+                !partner.fainted
+                    && partner
+                        .effective_ability_on(&battle.field)
+                        .is_some_and(|ability| to_id(ability) == "friendguard")
+            });
+        Self {
+            doubles: battle.slots_per_side() > 1,
+            spread: SPREAD_TARGETS.contains(&move_target) && targets > 1,
+            critical: false,
+            category: None,
+            aurora_veil: side.has_condition(SideCondition::AuroraVeil),
+            reflect: side.has_condition(SideCondition::Reflect),
+            light_screen: side.has_condition(SideCondition::LightScreen),
+            friend_guard,
+            burned: attacking.is_some_and(|poke| poke.status == Some(Status::Burn))
+                && attacker_ability.as_deref() != Some("guts"),
+            life_orb: attacking.is_some_and(|poke| {
+                poke.known_item
+                    .as_deref()
+                    .is_some_and(|item| to_id(item) == "lifeorb")
+            }),
+            attacker_boosts: attacking.map(|poke| poke.boosts).unwrap_or_default(),
+            defender_boosts: defending.map(|poke| poke.boosts).unwrap_or_default(),
+            attacker_unaware: attacker_ability.as_deref() == Some("unaware"),
+            defender_unaware: ability(defending).as_deref() == Some("unaware")
+                && !attacker_ability
+                    .as_deref()
+                    .is_some_and(|ability| MOLD_BREAKERS.contains(&ability)),
+        }
+    }
+
+    /// The same context for a move of a known category
+    pub fn with_category(self, category: MoveCategory) -> Self {
+        Self {
+            category: Some(category),
+            ..self
+        }
+    }
+
+    /// The same context for a critical hit
+    pub fn with_critical(self) -> Self {
+        Self {
+            critical: true,
+            ..self
+        }
+    }
+
+    /// Whether a screen reduces the hit; Aurora Veil doesn't stack with the others
+    pub fn screened(&self) -> bool {
+        !self.critical
+            && (self.aurora_veil
+                || (self.reflect && self.category == Some(MoveCategory::Physical))
+                || (self.light_screen && self.category == Some(MoveCategory::Special)))
+    }
+
+    /// Attacking over defending stage multiplier for the move's category
+    ///
+    /// 1 when the category is unknown. A critical hit ignores the attacker's
+    /// drops and the defender's raises.
+    pub fn stage_multiplier(&self) -> f32 {
+        let (attack, defense) = match self.category {
+            Some(MoveCategory::Physical) => (Stat::Atk, Stat::Def),
+            Some(MoveCategory::Special) => (Stat::Spa, Stat::Spd),
+            None => return 1.0,
+        };
+        let mut attack = if self.defender_unaware {
+            0
+        } else {
+            self.attacker_boosts.get(attack)
+        };
+        let mut defense = if self.attacker_unaware {
+            0
+        } else {
+            self.defender_boosts.get(defense)
+        };
+        if self.critical {
+            attack = attack.max(0);
+            defense = defense.min(0);
+        }
+        StatStages::multiplier(attack) / StatStages::multiplier(defense)
+    }
+
+    /// Whether the burn halves the hit; only known physical moves are
+    pub fn burn_applies(&self) -> bool {
+        self.burned && self.category == Some(MoveCategory::Physical)
+    }
+
+    /// The chained final modifier in 4096ths
+    pub fn final_modifier(&self) -> u32 {
+        let mut modifier = UNMODIFIED;
+        if self.screened() {
+            modifier = chain(modifier, if self.doubles { 2732 } else { 2048 });
+        }
+        if self.friend_guard {
+            modifier = chain(modifier, 3072);
+        }
+        if self.life_orb {
+            modifier = chain(modifier, LIFE_ORB);
+        }
+        modifier
+    }
+
+    /// Overall damage multiplier from stages, spread, burn and the final modifiers
+    pub fn multiplier(&self) -> f32 {
+        let spread = if self.spread { 0.75 } else { 1.0 };
+        let burn = if self.burn_applies() { 0.5 } else { 1.0 };
+        self.stage_multiplier() * spread * burn * self.final_modifier() as f32 / UNMODIFIED as f32
+    }
+
+    /// The 16 damage rolls, lowest first, for a hit whose level, power and
+    /// unboosted stat formula gives `base_damage`
+    ///
+    /// `effectiveness` is the type multiplier (0, 0.25 up to 4).
+    pub fn damage_rolls(&self, base_damage: u32, stab: bool, effectiveness: f32) -> [u32; 16] {
+        let mut rolls = [0; 16];
+        if effectiveness == 0.0 {
+            return rolls;
+        }
+        let mut damage = (base_damage as f32 * self.stage_multiplier()) as u32;
+        if self.spread {
+            damage = poke_round(damage, 3072);
+        }
+        if self.critical {
+            damage = poke_round(damage, 6144);
+        }
+        let steps = effectiveness.log2().round() as i32;
+        let final_modifier = self.final_modifier();
+        for (roll, out) in (85..=100).zip(rolls.iter_mut()) {
+            let mut hit = damage * roll / 100;
+            if stab {
+                hit = poke_round(hit, 6144);
+            }
+            hit = if steps >= 0 {
+                hit << steps
+            } else {
+                hit >> -steps
+            };
+            if self.burn_applies() {
+                hit = poke_round(hit, 2048);
+            }
+            *out = poke_round(hit.max(1), final_modifier).max(1);
+        }
+        rolls
+    }
+
+    /// Lowest and highest roll, see [`DamageContext::damage_rolls`]
+    pub fn damage_range(&self, base_damage: u32, stab: bool, effectiveness: f32) -> (u32, u32) {
+        let rolls = self.damage_rolls(base_damage, stab, effectiveness);
+        (rolls[0], rolls[15])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::parse_server_message;
+
+    fn doubles(lines: &[&str]) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        for line in [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|gametype|doubles",
+            "|switch|p1a: Garchomp|Garchomp, L50, M|100/100",
+            "|switch|p1b: Rotom|Rotom-Wash, L50|100/100",
+            "|switch|p2a: Heatran|Heatran, L50, M|100/100",
+            "|switch|p2b: Clefairy|Clefairy, L50, F|100/100",
+            "|turn|1",
+        ]
+        .iter()
+        .chain(lines)
+        {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    #[test]
+    fn test_spread_into_friend_guard_and_aurora_veil() {
+        let battle = doubles(&[
+            "|-ability|p2b: Clefairy|Friend Guard",
+            "|-sidestart|p2: Bob|move: Aurora Veil",
+        ]);
+        let context =
+            DamageContext::from_battle(&battle, (Player::P1, 0), (Player::P2, 0), "allAdjacent");
+        assert!(context.doubles && context.spread);
+        assert!(context.aurora_veil && context.friend_guard);
+
+        // 100 base damage: spread 75, rolls 63 to 75, then screen and Friend
+        // Guard chain to 2049/4096: 63 -> 32 and 75 -> 38
+        assert_eq!(context.final_modifier(), 2049);
+        assert_eq!(context.damage_range(100, false, 1.0), (32, 38));
+        // Super effective with STAB: 75 -> 112 -> 224 -> 112
+        assert_eq!(context.damage_range(100, true, 2.0).1, 112);
+
+        // A crit goes through the veil, not Friend Guard
+        assert_eq!(context.with_critical().final_modifier(), 3072);
+        // Friend Guard protects only its partner
+        let clefairy =
+            DamageContext::from_battle(&battle, (Player::P1, 0), (Player::P2, 1), "allAdjacent");
+        assert!(!clefairy.friend_guard);
+        assert_eq!(clefairy.final_modifier(), 2732);
+    }
+
+    #[test]
+    fn test_spread_needs_more_than_one_target() {
+        let battle = doubles(&["|-damage|p2b: Clefairy|0 fnt", "|faint|p2b: Clefairy"]);
+        let foes = DamageContext::from_battle(
+            &battle,
+            (Player::P1, 0),
+            (Player::P2, 0),
+            "allAdjacentFoes",
+        );
+        assert!(!foes.spread);
+        // Earthquake still hits the partner
+        let all =
+            DamageContext::from_battle(&battle, (Player::P1, 0), (Player::P2, 0), "allAdjacent");
+        assert!(all.spread);
+        assert_eq!(all.multiplier(), 0.75);
+
+        let single =
+            DamageContext::from_battle(&battle, (Player::P1, 0), (Player::P2, 0), "normal");
+        assert_eq!(
+            single,
+            DamageContext {
+                doubles: true,
+                ..DamageContext::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_unaware_ignores_boosts_both_ways() {
+        let physical = |battle: &TrackedBattle, defender: usize| {
+            DamageContext::from_battle(battle, (Player::P1, 0), (Player::P2, defender), "normal")
+                .with_category(MoveCategory::Physical)
+        };
+
+        // A +6 attacker into an Unaware wall hits as if unboosted
+        let battle = doubles(&[
+            "|-boost|p1a: Garchomp|atk|6",
+            "|-ability|p2a: Heatran|Unaware",
+        ]);
+        let wall = physical(&battle, 0);
+        assert!(wall.defender_unaware);
+        assert_eq!(wall.stage_multiplier(), 1.0);
+        assert_eq!(wall.damage_range(100, false, 1.0), (85, 100));
+        assert_eq!(
+            physical(&battle, 1).damage_range(100, false, 1.0),
+            (340, 400)
+        );
+        // Attack stages don't touch special moves
+        let special = wall.with_category(MoveCategory::Special);
+        assert_eq!(special.stage_multiplier(), 1.0);
+
+        // Mold Breaker gets past Unaware, and Gastro Acid suppresses it
+        let mut broken = doubles(&[
+            "|-boost|p1a: Garchomp|atk|6",
+            "|-ability|p2a: Heatran|Unaware",
+            "|-ability|p1a: Garchomp|Mold Breaker",
+        ]);
+        assert_eq!(physical(&broken, 0).stage_multiplier(), 4.0);
+        let suppressed = doubles(&[
+            "|-boost|p1a: Garchomp|atk|6",
+            "|-ability|p2a: Heatran|Unaware",
+            "|-endability|p2a: Heatran",
+        ]);
+        assert_eq!(physical(&suppressed, 0).stage_multiplier(), 4.0);
+
+        // An Unaware attacker ignores the defender's +6
+        broken = doubles(&[
+            "|-boost|p2a: Heatran|def|6",
+            "|-ability|p1a: Garchomp|Unaware",
+        ]);
+        let unaware = physical(&broken, 0);
+        assert!(unaware.attacker_unaware && !unaware.defender_unaware);
+        assert_eq!(unaware.stage_multiplier(), 1.0);
+        let boosted = doubles(&["|-boost|p2a: Heatran|def|6"]);
+        assert_eq!(physical(&boosted, 0).stage_multiplier(), 0.25);
+        // A crit ignores the defender's raises
+        assert_eq!(
+            physical(&boosted, 0).with_critical().stage_multiplier(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_screens_by_category() {
+        let battle = doubles(&["|-sidestart|p2: Bob|Reflect"]);
+        let context =
+            DamageContext::from_battle(&battle, (Player::P1, 0), (Player::P2, 0), "normal");
+        assert!(!context.screened());
+        assert!(context.with_category(MoveCategory::Physical).screened());
+        assert!(!context.with_category(MoveCategory::Special).screened());
+        assert_eq!(context.damage_range(50, false, 0.0), (0, 0));
+    }
+
+    #[test]
+    fn test_burn_life_orb_and_reflect_round_like_the_simulator() {
+        use crate::query::rounding::base_damage;
+
+        // Burned Adamant Garchomp's Earthquake (394 Atk) into Heatran (249 Def)
+        // behind Reflect, with Life Orb: 4x effective with STAB
+        let context = DamageContext {
+            category: Some(MoveCategory::Physical),
+            reflect: true,
+            burned: true,
+            life_orb: true,
+            ..DamageContext::default()
+        };
+        let base = base_damage(100, 100, 394, 249);
+        assert_eq!(context.final_modifier(), 2662);
+        assert_eq!(
+            context.damage_rolls(base, true, 4.0),
+            [
+                220, 224, 226, 227, 231, 234, 235, 239, 242, 243, 247, 250, 251, 255, 257, 261
+            ]
+        );
+        // Multiplying out as floats gives 222 for the lowest roll
+        let naive = (base as f32 * 0.85 * 1.5 * 4.0 * 0.5 * 0.5 * 1.3) as u32;
+        assert_eq!(naive, 222);
+
+        // Guts ignores the burn, and special moves never took it
+        let unburned = DamageContext {
+            reflect: false,
+            burned: false,
+            ..context
+        };
+        assert_eq!(unburned.damage_range(base, true, 4.0), (879, 1045));
+        let special = DamageContext {
+            category: Some(MoveCategory::Special),
+            reflect: false,
+            ..context
+        };
+        assert!(!special.burn_applies());
+        assert_eq!(special.damage_range(base, true, 4.0), (879, 1045));
+    }
+
+    #[test]
+    fn test_burn_and_life_orb_from_battle() {
+        let battle = doubles(&[
+            "|-status|p1a: Garchomp|brn",
+            "|-item|p1a: Garchomp|Life Orb",
+            "|-status|p1b: Rotom|brn",
+            "|-ability|p1b: Rotom|Guts",
+        ]);
+        let context = |slot| {
+            DamageContext::from_battle(&battle, (Player::P1, slot), (Player::P2, 0), "normal")
+        };
+        assert!(context(0).burned && context(0).life_orb);
+        assert!(!context(1).burned && !context(1).life_orb);
+        // Unknown category: Life Orb counts, the burn doesn't
+        assert_eq!(context(0).multiplier(), 5324.0 / 4096.0);
+    }
+}
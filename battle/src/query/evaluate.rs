@@ -0,0 +1,830 @@
+//! Score every legal action for a baseline heuristic bot
+//!
+//! [`evaluate_actions`] enumerates our legal moves ([`TrackedBattle::legal_moves_in`])
+//! and switches for the first active slot and scores each with a linear
+//! combination of a few terms, kept in [`ScoreBreakdown`] for logging:
+//!
+//! - damage dealt: type effectiveness of the move (neutral when its type is
+//!   not in the curated list, see [`move_type`]) times STAB, the target's
+//!   [`DamageContext`] reductions (spread, Aurora Veil, Friend Guard) and
+//!   its base power relative to an 80 power attack ([`move_base_power`]), 0
+//!   for status moves, switches and targets that are semi-invulnerable
+//!   mid-move
+//! - damage taken: the opponent's best effectiveness against whoever is on
+//!   the field after the action, from its types or its known attacks; an
+//!   attack only scouted in an earlier game counts for
+//!   [`PRIOR_WEIGHT`](crate::types::KnowledgeEntry::PRIOR_WEIGHT) of its
+//!   effectiveness, one used or listed in this game in full; a forced switch
+//!   in the middle of a turn only fears the foes that haven't acted yet
+//!   ([`TrackedBattle::remaining_actors`])
+//! - utility: hazards not yet set, statuses on a healthy target, recovery when
+//!   damaged, an attack's chance at a secondary effect unless the target is
+//!   known to block it (Covert Cloak, Shield Dust), minus hazards a switch-in
+//!   runs into, plus harmful volatiles a switch leaves behind, HP a pending
+//!   Wish, Healing Wish or Lunar Dance would restore to the switch-in, and
+//!   stat stages its ability raises on itself or drops on foes as it enters
+//!   ([`switch_in_effects`])
+//! - speed: priority moves
+//! - KO bonus: attacks that are at least neutral into a low-HP target
+//!
+//! A move the format's clauses make fail ([`TrackedBattle::rule_warnings`]),
+//! such as a sleep move under Sleep Clause Mod, keeps only its damage taken.
+//!
+//! Counter, Mirror Coat and Metal Burst only land after a hit of their kind,
+//! so they're scored on the foe repeating last turn's latest such hit on us:
+//! the damage returned, in units of the quarter of its HP a neutral 80 power
+//! attack is taken to deal, so countering a 25% hit scores like a neutral
+//! 160 power attack. Focus Punch scores no damage after we were hit last turn, since
+//! another hit would make it fail.
+//!
+//! There is no species or move data in this crate, so unknown types count as
+//! neutral and attacks outside the curated power list count as 80 power. Scoring is deterministic. The Pokemon
+//! involved are read through [`PokemonSnapshot`]s taken once per evaluation.
+//! A targeted move is scored against the foe it will hit per
+//! [`TargetingContext`]: the first one it can be aimed at, or a Follow Me or
+//! Rage Powder user drawing it away.
+
+use kazam_protocol::BattleRequest;
+
+use super::damage::{DamageContext, MoveCategory};
+use super::matchup::DefensiveProfile;
+use super::moves::{is_healing_move, is_status_move, move_base_power, move_type, secondary_chance};
+use super::switch_in::{SwitchInEffect, switch_in_effects};
+use super::targeting::{TargetStrictness, TargetingContext};
+use super::trapping::my_active_trapped;
+use crate::tracking::{LegalMove, TrackedBattle};
+use crate::types::{HitRecord, PokemonSnapshot, PokemonState, SideCondition, SideState, to_id};
+
+/// Opponent HP percent at or below which a hit counts toward the KO bonus
+const LOW_HP_PERCENT: u32 = 30;
+
+/// Base power an attack's damage dealt is measured against
+const REFERENCE_POWER: f32 = 80.0;
+
+/// Fraction of the target's HP a neutral attack of [`REFERENCE_POWER`] is
+/// taken to deal, to put damage returned in the same units
+const REFERENCE_HIT_FRACTION: f32 = 0.25;
+
+/// Utility of an attack whose secondary effect always happens
+const SECONDARY_UTILITY: f32 = 0.5;
+
+/// Moves that set an entry hazard on the opposing side
+const HAZARD_MOVES: &[(&str, SideCondition)] = &[
+    ("stealthrock", SideCondition::StealthRock),
+    ("spikes", SideCondition::Spikes),
+    ("toxicspikes", SideCondition::ToxicSpikes),
+    ("stickyweb", SideCondition::StickyWeb),
+];
+
+/// Status moves that inflict a non-volatile status
+#[rustfmt::skip]
+const STATUS_MOVES: &[&str] = &[
+    "darkvoid", "glare", "grasswhistle", "hypnosis", "lovelykiss", "nuzzle",
+    "poisongas", "poisonpowder", "sing", "sleeppowder", "spore", "stunspore",
+    "thunderwave", "toxic", "willowisp", "yawn",
+];
+
+/// Damaging moves with positive priority
+#[rustfmt::skip]
+const PRIORITY_MOVES: &[&str] = &[
+    "accelerock", "aquajet", "bulletpunch", "extremespeed", "fakeout", "feint",
+    "firstimpression", "grassyglide", "iceshard", "jetpunch", "machpunch",
+    "quickattack", "shadowsneak", "suckerpunch", "thunderclap", "vacuumwave",
+    "watershuriken",
+];
+
+/// Moves returning damage taken: the kind of hit they need (any for None)
+/// and the multiplier on the damage
+const COUNTER_MOVES: &[(&str, Option<MoveCategory>, f32)] = &[
+    ("counter", Some(MoveCategory::Physical), 2.0),
+    ("mirrorcoat", Some(MoveCategory::Special), 2.0),
+    ("metalburst", None, 1.5),
+];
+
+/// Weight of each term in an action's score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalWeights {
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    pub utility: f32,
+    pub speed: f32,
+    pub ko_bonus: f32,
+}
+
+impl Default for EvalWeights {
+    /// Favors the strongest attack, switches out of bad typing matchups, and
+    /// sets hazards or statuses only when no attack is clearly better
+    fn default() -> Self {
+        Self {
+            damage_dealt: 1.0,
+            damage_taken: 0.6,
+            utility: 0.8,
+            speed: 0.3,
+            ko_bonus: 0.5,
+        }
+    }
+}
+
+/// Unweighted value of each term for one action
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScoreBreakdown {
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    pub utility: f32,
+    pub speed: f32,
+    pub ko_bonus: f32,
+}
+
+impl ScoreBreakdown {
+    /// Weighted sum, damage taken counting against the action
+    pub fn score(&self, weights: &EvalWeights) -> f32 {
+        weights.damage_dealt * self.damage_dealt - weights.damage_taken * self.damage_taken
+            + weights.utility * self.utility
+            + weights.speed * self.speed
+            + weights.ko_bonus * self.ko_bonus
+    }
+}
+
+/// An action [`evaluate_actions`] considered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Move(LegalMove),
+
+    /// Switch to the Pokemon at `index` in the request's side list (0-indexed)
+    Switch {
+        index: usize,
+        name: String,
+    },
+}
+
+impl Action {
+    /// Choice string for this action (e.g. "move 2", "switch 3")
+    pub fn choice(&self) -> String {
+        match self {
+            Action::Move(legal) => legal.choice(),
+            Action::Switch { index, .. } => format!("switch {}", index + 1),
+        }
+    }
+}
+
+/// A legal action with its score
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredAction {
+    pub action: Action,
+    pub score: f32,
+    pub breakdown: ScoreBreakdown,
+}
+
+/// Score every legal action for our first active slot, best first
+///
+/// Covers move and switch requests, including forced switches. Team preview
+/// and wait requests have no actions. Ties keep moves before switches, each
+/// in request order.
+pub fn evaluate_actions(
+    battle: &TrackedBattle,
+    request: &BattleRequest,
+    weights: &EvalWeights,
+) -> Vec<ScoredAction> {
+    if request.wait || request.team_preview {
+        return Vec::new();
+    }
+    let me = battle.me();
+    let snapshot = |pokemon: &PokemonState| pokemon.snapshot_on(&battle.field);
+    let ours = me.and_then(|side| side.active(0)).map(snapshot);
+    let opponent = battle.opponent();
+    let foes: Vec<Option<PokemonSnapshot>> = opponent
+        .map(|side| {
+            (0..side.active_indices.len())
+                .map(|slot| side.active(slot).map(snapshot))
+                .collect()
+        })
+        .unwrap_or_default();
+    let theirs = foes.first().cloned().flatten();
+    let hits = me
+        .and_then(|side| side.active(0))
+        .map_or(&[][..], |pokemon| pokemon.hits_taken_last_turn.as_slice());
+    let targeting = TargetingContext::new(battle, TargetStrictness::ServerLegal);
+
+    let mut actions: Vec<ScoredAction> = Vec::new();
+    if !request.is_force_switch() {
+        for legal in battle.legal_moves_in(request, 0) {
+            // Score against the foe the move will hit, after any redirection
+            let slot = match targeting.default_hit(0, &legal.id, &legal.target) {
+                Some(location @ 1..) => location as usize - 1,
+                _ => 0,
+            };
+            let target = foes.get(slot).and_then(Option::as_ref);
+            let context = match (me, opponent) {
+                (Some(me), Some(opponent)) => DamageContext::from_battle(
+                    battle,
+                    (me.player, 0),
+                    (opponent.player, slot),
+                    &legal.target,
+                ),
+                _ => DamageContext::default(),
+            };
+            let mut breakdown = score_move(&legal, ours.as_ref(), target, opponent, &context, hits);
+            // A move the format's clauses make fail does nothing but take the foe's hit
+            if !battle.rule_warnings(&legal.id).is_empty() {
+                breakdown = ScoreBreakdown {
+                    damage_taken: breakdown.damage_taken,
+                    ..ScoreBreakdown::default()
+                };
+            }
+            actions.push(scored(Action::Move(legal), breakdown, weights));
+        }
+    }
+
+    // Mid-turn, foes that already acted can't hit the switch-in until next turn
+    let attackers: Vec<Option<PokemonSnapshot>> =
+        if request.is_force_switch() && battle.is_resolving_turn() {
+            battle
+                .remaining_actors()
+                .into_iter()
+                .filter(|p| me.is_some_and(|me| !battle.same_team(p.player, me.player)))
+                .map(|p| Some(snapshot(p.pokemon)))
+                .collect()
+        } else {
+            vec![theirs]
+        };
+
+    let trapped = request
+        .active
+        .as_ref()
+        .and_then(|active| active.first())
+        .is_some_and(|active| !active.can_switch())
+        || my_active_trapped(battle).known() == Some(true);
+    if !trapped || request.is_force_switch() {
+        for (index, name) in switch_targets(request) {
+            // Request positions shift on every switch, so look the Pokemon up by name
+            let pokemon = me.and_then(|side| side.get_pokemon(side.find_pokemon(&name)?));
+            let switch_in = pokemon.map(snapshot);
+            // A Wish or Healing Wish waiting on the slot lands on the switch-in
+            let heal = me
+                .and_then(|side| side.incoming_heal(0, battle.turn))
+                .zip(pokemon)
+                .map_or(0.0, |(heal, pokemon)| heal.percent_for(pokemon));
+            let effects = me
+                .zip(pokemon)
+                .map(|(side, pokemon)| switch_in_effects(battle, side.player, 0, pokemon))
+                .unwrap_or_default();
+            let breakdown = score_switch(
+                switch_in.as_ref(),
+                ours.as_ref(),
+                &attackers,
+                me,
+                heal,
+                &effects,
+            );
+            actions.push(scored(Action::Switch { index, name }, breakdown, weights));
+        }
+    }
+
+    actions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    actions
+}
+
+fn scored(action: Action, breakdown: ScoreBreakdown, weights: &EvalWeights) -> ScoredAction {
+    ScoredAction {
+        action,
+        score: breakdown.score(weights),
+        breakdown,
+    }
+}
+
+/// Bench Pokemon the request lets us switch to, or revive with Revival Blessing
+fn switch_targets(request: &BattleRequest) -> Vec<(usize, String)> {
+    let Some(side) = &request.side else {
+        return Vec::new();
+    };
+    let reviving = request.is_reviving();
+    side.pokemon
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.is_switch_target(reviving))
+        .map(|(index, p)| {
+            let name = p.ident.split(": ").nth(1).unwrap_or(&p.ident);
+            (index, name.to_string())
+        })
+        .collect()
+}
+
+fn score_move(
+    legal: &LegalMove,
+    ours: Option<&PokemonSnapshot>,
+    theirs: Option<&PokemonSnapshot>,
+    opponent: Option<&SideState>,
+    context: &DamageContext,
+    hits: &[HitRecord],
+) -> ScoreBreakdown {
+    let id = to_id(&legal.id);
+    let mut breakdown = ScoreBreakdown {
+        damage_taken: threat(theirs, ours),
+        ..ScoreBreakdown::default()
+    };
+
+    if is_status_move(&id, &legal.target) {
+        breakdown.utility = status_utility(&id, ours, theirs, opponent);
+        return breakdown;
+    }
+
+    // Attacks mostly miss a target in the middle of Fly, Dig and the like
+    if theirs.is_some_and(PokemonSnapshot::is_semi_invulnerable) {
+        return breakdown;
+    }
+
+    let attack_type = move_type(&id);
+    let effectiveness = match (attack_type, theirs) {
+        // Unknown grounding falls back to typing alone
+        (Some(t), Some(target)) => target
+            .effectiveness(t)
+            .unwrap_or_else(|| t.effectiveness_multi(target.types())),
+        _ => 1.0,
+    };
+    let stab = match (attack_type, ours) {
+        (Some(t), Some(user)) if user.has_stab(t) => 1.5,
+        _ => 1.0,
+    };
+    let power = move_base_power(&id).map_or(1.0, |bp| f32::from(bp) / REFERENCE_POWER);
+    breakdown.damage_dealt = effectiveness * stab * context.multiplier() * power;
+    if let Some((_, needs, multiplier)) = COUNTER_MOVES.iter().find(|(m, ..)| *m == id) {
+        let returned = hits
+            .iter()
+            .rev()
+            .find(|hit| needs.is_none() || hit.category == *needs);
+        // The hit is a fraction of our HP, taken to be about the foe's
+        breakdown.damage_dealt = returned.map_or(0.0, |hit| {
+            multiplier * hit.damage_fraction / REFERENCE_HIT_FRACTION
+        });
+    } else if id == "focuspunch" && !hits.is_empty() {
+        breakdown.damage_dealt = 0.0;
+        return breakdown;
+    }
+    if effectiveness > 0.0
+        && let Some(chance) = secondary_chance(&id)
+        && !theirs.is_some_and(|target| target.blocks_secondaries)
+    {
+        breakdown.utility = SECONDARY_UTILITY * f32::from(chance) / 100.0;
+    }
+    if effectiveness > 0.0 && PRIORITY_MOVES.contains(&id.as_str()) {
+        breakdown.speed = 1.0;
+    }
+    // An intact Disguise or Ice Face absorbs the hit, so it can't KO
+    if effectiveness >= 1.0
+        && theirs.is_some_and(|t| t.hp_percent as u32 <= LOW_HP_PERCENT && !t.intact_disguise)
+    {
+        breakdown.ko_bonus = 1.0;
+    }
+    breakdown
+}
+
+fn score_switch(
+    switch_in: Option<&PokemonSnapshot>,
+    ours: Option<&PokemonSnapshot>,
+    attackers: &[Option<PokemonSnapshot>],
+    me: Option<&SideState>,
+    heal_percent: f32,
+    effects: &[SwitchInEffect],
+) -> ScoreBreakdown {
+    let hazards = me.map_or(0, |side| {
+        [SideCondition::StealthRock, SideCondition::StickyWeb]
+            .into_iter()
+            .filter(|&c| side.has_condition(c))
+            .count() as u8
+            + side.condition_layers(SideCondition::Spikes)
+            + side.condition_layers(SideCondition::ToxicSpikes)
+    });
+    // Switching sheds whatever is wearing down the Pokemon on the field
+    let shed = ours.map_or(0, PokemonSnapshot::negative_volatiles);
+    // Only the HP the switch-in is missing counts
+    let healed = switch_in.map_or(0.0, |poke| {
+        heal_percent.min((100 - poke.hp_percent.min(100)) as f32) / 100.0
+    });
+    // Stages the switch-in's ability raises on itself or drops on foes
+    let stages: i8 = effects
+        .iter()
+        .map(|effect| match *effect {
+            SwitchInEffect::BoostSelf { stages, .. } => stages,
+            SwitchInEffect::BoostFoe { stages, .. } => -stages,
+            _ => 0,
+        })
+        .sum();
+    ScoreBreakdown {
+        damage_taken: attackers
+            .iter()
+            .map(|attacker| threat(attacker.as_ref(), switch_in))
+            .fold(0.0, f32::max),
+        utility: 0.25 * shed as f32 - 0.25 * hazards as f32 + healed + 0.25 * stages as f32,
+        ..ScoreBreakdown::default()
+    }
+}
+
+/// Best effectiveness of the attacker's types or known attacks against the
+/// defender, each attack scaled by its weight; neutral if either is unknown
+fn threat(attacker: Option<&PokemonSnapshot>, defender: Option<&PokemonSnapshot>) -> f32 {
+    let (Some(attacker), Some(defender)) = (attacker, defender) else {
+        return 1.0;
+    };
+    let types = DefensiveProfile::of(defender.types()).best_of(attacker.types());
+    attacker
+        .attack_types()
+        .map(|(t, weight)| {
+            let effectiveness = defender
+                .effectiveness(t)
+                .unwrap_or_else(|| t.effectiveness_multi(defender.types()));
+            weight * effectiveness
+        })
+        .fold(types, f32::max)
+}
+
+/// Value of a status move: unset hazards, a status on a healthy target, or healing
+fn status_utility(
+    id: &str,
+    ours: Option<&PokemonSnapshot>,
+    theirs: Option<&PokemonSnapshot>,
+    opponent: Option<&SideState>,
+) -> f32 {
+    if let Some((_, condition)) = HAZARD_MOVES.iter().find(|(m, _)| *m == id) {
+        let set = opponent.is_some_and(|side| side.has_condition(*condition));
+        return if set { 0.0 } else { 1.0 };
+    }
+    if STATUS_MOVES.contains(&id) {
+        return match theirs {
+            Some(target) if target.status.is_none() => 1.0,
+            _ => 0.0,
+        };
+    }
+    if is_healing_move(id) {
+        return ours.map_or(0.0, |user| (100 - user.hp_percent.min(100)) as f32 / 100.0);
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    use crate::types::{KnowledgeEntry, Type, Volatile};
+
+    /// Our Garchomp (Ground/Dragon) against a Heatran (Fire/Steel)
+    fn battle(moves: &[(&str, &str)]) -> (TrackedBattle, BattleRequest) {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Heatran|Heatran, L50, M|100/100
+|turn|1"#;
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let move_slots: Vec<_> = moves
+            .iter()
+            .map(|(name, target)| {
+                serde_json::json!({
+                    "move": name, "id": to_id(name), "pp": 16, "maxpp": 16,
+                    "target": target, "disabled": false
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "active": [{"moves": move_slots}],
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [
+                    {"ident": "p1: Garchomp", "details": "Garchomp, L50, M",
+                     "condition": "183/183", "active": true, "moves": []},
+                    {"ident": "p1: Lapras", "details": "Lapras, L50, F",
+                     "condition": "190/190", "active": false, "moves": []},
+                    {"ident": "p1: Skarmory", "details": "Skarmory, L50, F",
+                     "condition": "0 fnt", "active": false, "moves": []}
+                ]
+            },
+            "rqid": 2
+        });
+        let request = BattleRequest::parse(&json).unwrap();
+        battle.apply_request(&request);
+
+        // No species data in this crate, so typings are filled in by hand
+        let me = battle.get_side_mut(Player::P1).unwrap();
+        me.pokemon[0].set_types(vec![Type::Ground, Type::Dragon]);
+        me.pokemon[1].set_types(vec![Type::Water, Type::Ice]);
+        let them = battle.get_side_mut(Player::P2).unwrap();
+        them.pokemon[0].set_types(vec![Type::Fire, Type::Steel]);
+        (battle, request)
+    }
+
+    fn choices(actions: &[ScoredAction]) -> Vec<String> {
+        actions.iter().map(|a| a.action.choice()).collect()
+    }
+
+    #[test]
+    fn test_super_effective_stab_ranks_first() {
+        let (battle, request) = battle(&[
+            ("Dragon Claw", "normal"),
+            ("Earthquake", "allAdjacent"),
+            ("Stealth Rock", "foeSide"),
+            ("Swords Dance", "self"),
+        ]);
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+
+        // Dragon Claw's type is not curated, so it counts as neutral. Fainted
+        // Skarmory is not a switch target.
+        assert_eq!(
+            choices(&actions),
+            vec!["move 2", "move 1", "move 3", "move 4", "switch 2"]
+        );
+        let earthquake = &actions[0].breakdown;
+        assert_eq!(earthquake.damage_dealt, 7.5);
+        // Heatran's Steel STAB is neutral on Garchomp
+        assert_eq!(earthquake.damage_taken, 1.0);
+    }
+
+    #[test]
+    fn test_scouted_attack_threatens_less_than_revealed() {
+        let (mut battle, request) = battle(&[("Earthquake", "allAdjacent")]);
+        let lapras_damage_taken = |battle: &TrackedBattle| {
+            let actions = evaluate_actions(battle, &request, &EvalWeights::default());
+            let switch = actions.iter().find(|a| a.action.choice() == "switch 2");
+            switch.unwrap().breakdown.damage_taken
+        };
+        // Heatran's types alone are neutral on Lapras
+        assert_eq!(lapras_damage_taken(&battle), 1.0);
+
+        // Thunderbolt from an earlier game counts for half its 2x
+        let heatran = &mut battle.get_side_mut(Player::P2).unwrap().pokemon[0];
+        heatran.add_prior(crate::types::KnowledgeKind::Move, "Thunderbolt");
+        assert_eq!(
+            lapras_damage_taken(&battle),
+            2.0 * KnowledgeEntry::PRIOR_WEIGHT
+        );
+
+        battle.apply_message(
+            &parse_server_message("|move|p2a: Heatran|Thunderbolt|p1a: Garchomp").unwrap(),
+        );
+        assert_eq!(lapras_damage_taken(&battle), 2.0);
+    }
+
+    #[test]
+    fn test_damage_context_in_scores() {
+        let (mut battle, request) = battle(&[("Earthquake", "allAdjacent")]);
+        battle
+            .apply_message(&parse_server_message("|-sidestart|p2: Bob|move: Aurora Veil").unwrap());
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        // Halved by the veil in singles, and a lone foe is no spread
+        assert_eq!(actions[0].breakdown.damage_dealt, 3.75);
+    }
+
+    #[test]
+    fn test_covert_cloak_blocks_secondary_utility() {
+        let (mut battle, request) =
+            battle(&[("Fake Out", "normal"), ("Rock Slide", "allAdjacentFoes")]);
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        let utility = |actions: &[ScoredAction], choice: &str| {
+            actions
+                .iter()
+                .find(|a| a.action.choice() == choice)
+                .map(|a| a.breakdown.utility)
+        };
+        assert_eq!(utility(&actions, "move 1"), Some(0.5));
+        assert_eq!(utility(&actions, "move 2"), Some(0.15));
+
+        battle.apply_message(
+            &parse_server_message(
+                "|-item|p2a: Heatran|Covert Cloak|[from] ability: Frisk|[of] p1a: Garchomp",
+            )
+            .unwrap(),
+        );
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(utility(&actions, "move 1"), Some(0.0));
+        assert_eq!(utility(&actions, "move 2"), Some(0.0));
+    }
+
+    #[test]
+    fn test_forced_switch_and_hazards() {
+        let (mut battle, _) = battle(&[("Earthquake", "allAdjacent")]);
+        let json = serde_json::json!({
+            "forceSwitch": [true],
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [
+                    {"ident": "p1: Garchomp", "details": "Garchomp, L50, M",
+                     "condition": "0 fnt", "active": true, "moves": []},
+                    {"ident": "p1: Lapras", "details": "Lapras, L50, F",
+                     "condition": "190/190", "active": false, "moves": []}
+                ]
+            },
+            "rqid": 3
+        });
+        let request = BattleRequest::parse(&json).unwrap();
+        battle.apply_message(
+            &parse_server_message("|-sidestart|p1: Alice|move: Stealth Rock").unwrap(),
+        );
+
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(choices(&actions), vec!["switch 2"]);
+        assert_eq!(actions[0].breakdown.utility, -0.25);
+        // Heatran's Fire and Steel STABs are resisted and neutral on Lapras
+        assert_eq!(actions[0].breakdown.damage_taken, 1.0);
+
+        // Dropping Heatran's Attack on entry makes up for the rocks
+        let lapras = &mut battle.get_side_mut(Player::P1).unwrap().pokemon[1];
+        lapras.known_ability = Some("Intimidate".to_string());
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(actions[0].breakdown.utility, 0.0);
+    }
+
+    #[test]
+    fn test_mid_turn_switch_fears_only_foes_yet_to_act() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p1b: Skarmory|Skarmory, L50, F|100/100
+|switch|p2a: Heatran|Heatran, L50, M|100/100
+|switch|p2b: Kyogre|Kyogre, L50|100/100
+|turn|1
+|move|p2a: Heatran|Flamethrower|p1a: Garchomp
+|-damage|p1a: Garchomp|0 fnt
+|faint|p1a: Garchomp"#;
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let json = serde_json::json!({
+            "forceSwitch": [true, false],
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [
+                    {"ident": "p1: Garchomp", "details": "Garchomp, L50, M",
+                     "condition": "0 fnt", "active": true, "moves": []},
+                    {"ident": "p1: Skarmory", "details": "Skarmory, L50, F",
+                     "condition": "150/150", "active": true, "moves": []},
+                    {"ident": "p1: Scizor", "details": "Scizor, L50, M",
+                     "condition": "150/150", "active": false, "moves": []},
+                    {"ident": "p1: Arcanine", "details": "Arcanine, L50, M",
+                     "condition": "170/170", "active": false, "moves": []}
+                ]
+            },
+            "rqid": 3
+        });
+        let request = BattleRequest::parse(&json).unwrap();
+        battle.apply_request(&request);
+        let me = battle.get_side_mut(Player::P1).unwrap();
+        let scizor = me.find_pokemon("Scizor").unwrap();
+        me.pokemon[scizor].set_types(vec![Type::Bug, Type::Steel]);
+        let arcanine = me.find_pokemon("Arcanine").unwrap();
+        me.pokemon[arcanine].set_types(vec![Type::Fire]);
+        let them = battle.get_side_mut(Player::P2).unwrap();
+        them.pokemon[0].set_types(vec![Type::Fire, Type::Steel]);
+        them.pokemon[1].set_types(vec![Type::Water]);
+
+        let remaining: Vec<_> = battle
+            .remaining_actors()
+            .iter()
+            .map(|p| p.pokemon.name())
+            .collect();
+        assert_eq!(remaining, ["Skarmory", "Kyogre"]);
+
+        // Heatran already moved, so only Kyogre's Water can hit the switch-in
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(choices(&actions), vec!["switch 3", "switch 4"]);
+        assert_eq!(actions[0].breakdown.damage_taken, 1.0);
+        assert_eq!(actions[1].breakdown.damage_taken, 2.0);
+
+        // Once the turn is over, Heatran's Fire is the threat again
+        battle.apply_message(&parse_server_message("|upkeep").unwrap());
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(choices(&actions), vec!["switch 4", "switch 3"]);
+        assert_eq!(actions[1].breakdown.damage_taken, 4.0);
+    }
+
+    #[test]
+    fn test_pending_wish_in_switch_score() {
+        let (mut battle, request) = battle(&[("Earthquake", "allAdjacent")]);
+        battle.get_side_mut(Player::P1).unwrap().pokemon[1].hp_current = 95;
+        battle.apply_message(
+            &parse_server_message("|move|p1a: Garchomp|Wish|p1a: Garchomp").unwrap(),
+        );
+
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        let switch = actions
+            .iter()
+            .find(|a| a.action.choice() == "switch 2")
+            .unwrap();
+        // Half of Garchomp's 183 HP is 91, about 48% of Lapras's 190
+        assert!((switch.breakdown.utility - 91.0 / 190.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_sleep_clause_zeroes_sleep_moves() {
+        let (mut battle, request) = battle(&[("Spore", "normal"), ("Splash", "self")]);
+        for line in [
+            "|-status|p2a: Heatran|slp",
+            "|switch|p2a: Skarmory|Skarmory, L50, F|100/100",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let spore = |battle: &TrackedBattle| {
+            let actions = evaluate_actions(battle, &request, &EvalWeights::default());
+            let score = |choice: &str| {
+                actions
+                    .iter()
+                    .find(|a| a.action.choice() == choice)
+                    .unwrap()
+                    .score
+            };
+            (score("move 1"), score("move 2"))
+        };
+        let (unclaused, splash) = spore(&battle);
+        assert!(unclaused > splash);
+        assert!(battle.rule_warnings("spore").is_empty());
+
+        // The benched Heatran sleeping from our Spore makes another fail
+        battle
+            .rules
+            .push("Sleep Clause Mod: Limit one foe put to sleep".to_string());
+        let (claused, splash) = spore(&battle);
+        assert_eq!(claused, splash);
+        assert_eq!(
+            battle.rule_warnings("spore"),
+            [crate::tracking::RuleWarning::SleepClause {
+                asleep: "Heatran".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_volatiles_in_scores() {
+        let (mut battle, request) = battle(&[("Earthquake", "allAdjacent")]);
+        battle.get_side_mut(Player::P1).unwrap().pokemon[0].add_volatile(Volatile::LeechSeed);
+        battle.get_side_mut(Player::P2).unwrap().pokemon[0].add_volatile(Volatile::Dig);
+
+        let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+        assert_eq!(choices(&actions), vec!["switch 2", "move 1"]);
+        assert_eq!(actions[0].breakdown.utility, 0.25);
+        assert_eq!(actions[1].breakdown.damage_dealt, 0.0);
+    }
+
+    #[test]
+    fn test_counter_and_focus_punch_follow_last_turns_hits() {
+        let moves = [
+            ("Counter", "scripted"),
+            ("Mirror Coat", "scripted"),
+            ("Focus Punch", "normal"),
+        ];
+        let (mut battle, request) = battle(&moves);
+        let damage_dealt = |battle: &TrackedBattle| {
+            let actions = evaluate_actions(battle, &request, &EvalWeights::default());
+            ["move 1", "move 2", "move 3"].map(|choice| {
+                actions
+                    .iter()
+                    .find(|a| a.action.choice() == choice)
+                    .unwrap()
+                    .breakdown
+                    .damage_dealt
+            })
+        };
+        // Nothing to return yet, and nothing to break Focus Punch's focus
+        assert_eq!(damage_dealt(&battle), [0.0, 0.0, 150.0 / REFERENCE_POWER]);
+
+        for line in [
+            "|move|p2a: Heatran|Flamethrower|p1a: Garchomp",
+            "|-damage|p1a: Garchomp|92/183",
+            "|turn|2",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        // A special hit of about half Garchomp's HP is Mirror Coat's to return
+        let [counter, mirror_coat, focus_punch] = damage_dealt(&battle);
+        assert_eq!(counter, 0.0);
+        assert!((mirror_coat - 8.0 * 91.0 / 183.0).abs() < 1e-4);
+        assert_eq!(focus_punch, 0.0);
+    }
+
+    #[test]
+    fn test_deterministic_and_trapped() {
+        let (mut battle, request) = battle(&[("Earthquake", "allAdjacent")]);
+        let weights = EvalWeights::default();
+        assert_eq!(
+            evaluate_actions(&battle, &request, &weights),
+            evaluate_actions(&battle, &request, &weights)
+        );
+
+        let them = battle.get_side_mut(Player::P2).unwrap();
+        them.pokemon[0].known_ability = Some("Arena Trap".to_string());
+        let mut trapped = request.clone();
+        trapped.active.as_mut().unwrap()[0].trapped = true;
+        assert_eq!(
+            choices(&evaluate_actions(&battle, &trapped, &weights)),
+            vec!["move 1"]
+        );
+    }
+}
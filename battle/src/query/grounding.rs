@@ -0,0 +1,179 @@
+//! Whether a Pokemon is grounded
+//!
+//! Grounding decides Ground move immunity, entry hazard damage (Spikes, Toxic
+//! Spikes, Sticky Web) and terrain effects. The checks follow Showdown's order:
+//!
+//! 1. Gravity, Ingrain and Smack Down (also applied by Thousand Arrows) ground
+//!    the Pokemon no matter what else is true.
+//! 2. A held Iron Ball grounds it, unless items are suppressed (Magic Room,
+//!    Klutz, Embargo).
+//! 3. Otherwise it is airborne if any of these hold: Flying type (not while
+//!    roosting), Levitate (not under Gastro Acid), Magnet Rise, Telekinesis,
+//!    or an unpopped Air Balloon (not while items are suppressed).
+//! 4. Otherwise it is grounded.
+//!
+//! With partial information the answer is [`Grounded::Unknown`] when the
+//! outcome depends on something not revealed yet: the types (no species data
+//! was loaded), an unrevealed ability that could be Levitate, or an
+//! unrevealed item that could be an Iron Ball. Air Balloon is announced on
+//! switch-in, so an unrevealed item is never assumed to be one.
+
+use crate::types::{FieldState, PokemonState, Type, Volatile, to_id};
+
+/// Missing information that decides whether a Pokemon is grounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroundingUnknown {
+    /// Types are not known, so it could be Flying
+    Types,
+
+    /// Ability is not revealed and could be Levitate
+    Ability,
+
+    /// Item is not revealed and could be an Iron Ball
+    Item,
+}
+
+/// Result of [`is_grounded`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grounded {
+    Yes,
+    No,
+    Unknown(GroundingUnknown),
+}
+
+impl Grounded {
+    /// Definite answer, None if unknown
+    pub fn known(self) -> Option<bool> {
+        match self {
+            Grounded::Yes => Some(true),
+            Grounded::No => Some(false),
+            Grounded::Unknown(_) => None,
+        }
+    }
+}
+
+/// Check whether a Pokemon is grounded on the current field
+pub fn is_grounded(pokemon: &PokemonState, field: &FieldState) -> Grounded {
+    if field.gravity
+        || pokemon.has_volatile(&Volatile::Ingrain)
+        || pokemon.has_volatile(&Volatile::Smackdown)
+    {
+        return Grounded::Yes;
+    }
+
+    let ability = pokemon.effective_ability_on(field).map(to_id);
+    // Some(None) when the Pokemon is known to have no usable item
+    let item = if field.magic_room || !pokemon.can_use_item() {
+        Some(None)
+    } else {
+        pokemon.known_item.as_deref().map(|item| Some(to_id(item)))
+    };
+    let held = item.as_ref().and_then(Option::as_deref);
+    if held == Some("ironball") {
+        return Grounded::Yes;
+    }
+
+    let types = pokemon.get_types();
+    let flying = types.contains(&Type::Flying) && !pokemon.has_volatile(&Volatile::Roost);
+    let airborne = flying
+        || ability.as_deref() == Some("levitate")
+        || pokemon.has_volatile(&Volatile::MagnetRise)
+        || pokemon.has_volatile(&Volatile::Telekinesis)
+        || held == Some("airballoon");
+
+    if airborne {
+        return match item {
+            Some(_) => Grounded::No,
+            None => Grounded::Unknown(GroundingUnknown::Item),
+        };
+    }
+    if types.is_empty() {
+        return Grounded::Unknown(GroundingUnknown::Types);
+    }
+    // An unrevealed ability can't lift it while suppressed
+    if pokemon.current_ability().is_none()
+        && !pokemon.has_volatile(&Volatile::GastroAcid)
+        && !field.abilities_suppressed()
+    {
+        return Grounded::Unknown(GroundingUnknown::Ability);
+    }
+    Grounded::Yes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Pokemon with types, ability and item all known
+    fn poke(types: &[Type], ability: &str, item: &str) -> PokemonState {
+        let mut poke = PokemonState::new("Test", 100);
+        poke.set_types(types.to_vec());
+        poke.known_ability = Some(ability.to_string());
+        poke.known_item = Some(item.to_string());
+        poke
+    }
+
+    fn with_volatile(mut poke: PokemonState, volatile: Volatile) -> PokemonState {
+        poke.add_volatile(volatile);
+        poke
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_truth_table() {
+        use Grounded::{No, Unknown, Yes};
+
+        let field = FieldState::default();
+        let gravity = FieldState {
+            gravity: true,
+            ..FieldState::default()
+        };
+        let magic_room = FieldState {
+            magic_room: true,
+            ..FieldState::default()
+        };
+        let mut popped = poke(&[Type::Steel], "Sturdy", "Air Balloon");
+        popped.consume_item();
+        let mut unknown_item = poke(&[Type::Flying], "Keen Eye", "");
+        unknown_item.known_item = None;
+        let mut unknown_ability = poke(&[Type::Ghost], "", "Leftovers");
+        unknown_ability.known_ability = None;
+
+        let cases = [
+            ("plain", poke(&[Type::Water], "Torrent", "Leftovers"), &field, Yes),
+            ("flying", poke(&[Type::Flying], "Keen Eye", "Leftovers"), &field, No),
+            ("levitate", poke(&[Type::Ghost], "Levitate", "Leftovers"), &field, No),
+            ("air balloon", poke(&[Type::Steel], "Sturdy", "Air Balloon"), &field, No),
+            ("popped air balloon", popped, &field, Yes),
+            ("gravity over levitate", poke(&[Type::Ghost], "Levitate", "Leftovers"), &gravity, Yes),
+            ("gravity over flying", poke(&[Type::Flying], "Keen Eye", "Leftovers"), &gravity, Yes),
+            ("iron ball over flying", poke(&[Type::Flying], "Keen Eye", "Iron Ball"), &field, Yes),
+            ("magic room cancels iron ball", poke(&[Type::Flying], "Keen Eye", "Iron Ball"), &magic_room, No),
+            ("magic room cancels air balloon", poke(&[Type::Steel], "Sturdy", "Air Balloon"), &magic_room, Yes),
+            ("klutz cancels iron ball", poke(&[Type::Flying], "Klutz", "Iron Ball"), &field, No),
+            ("embargo cancels iron ball", with_volatile(poke(&[Type::Flying], "Keen Eye", "Iron Ball"), Volatile::Embargo), &field, No),
+            ("embargo cancels air balloon", with_volatile(poke(&[Type::Steel], "Sturdy", "Air Balloon"), Volatile::Embargo), &field, Yes),
+            ("magnet rise", with_volatile(poke(&[Type::Steel], "Sturdy", "Leftovers"), Volatile::MagnetRise), &field, No),
+            ("telekinesis", with_volatile(poke(&[Type::Water], "Torrent", "Leftovers"), Volatile::Telekinesis), &field, No),
+            ("ingrain over magnet rise", with_volatile(with_volatile(poke(&[Type::Steel], "Sturdy", "Leftovers"), Volatile::MagnetRise), Volatile::Ingrain), &field, Yes),
+            ("smack down over flying", with_volatile(poke(&[Type::Flying], "Keen Eye", "Leftovers"), Volatile::Smackdown), &field, Yes),
+            ("roost drops flying", with_volatile(poke(&[Type::Flying, Type::Normal], "Keen Eye", "Leftovers"), Volatile::Roost), &field, Yes),
+            ("gastro acid drops levitate", with_volatile(poke(&[Type::Ghost], "Levitate", "Leftovers"), Volatile::GastroAcid), &field, Yes),
+            ("flying with unknown item", unknown_item, &field, Unknown(GroundingUnknown::Item)),
+            ("unknown ability", unknown_ability, &field, Unknown(GroundingUnknown::Ability)),
+            ("unknown types", poke(&[], "Torrent", "Leftovers"), &field, Unknown(GroundingUnknown::Types)),
+            ("unknown types under gravity", poke(&[], "Torrent", "Leftovers"), &gravity, Yes),
+        ];
+
+        for (name, poke, field, expected) in cases {
+            assert_eq!(is_grounded(&poke, field), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_known() {
+        assert_eq!(Grounded::Yes.known(), Some(true));
+        assert_eq!(Grounded::No.known(), Some(false));
+        assert_eq!(Grounded::Unknown(GroundingUnknown::Item).known(), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! Set inference from recorded contradictions
+//!
+//! Turns the [`Observation`]s the tracker records on `|-immune|` and `|-fail|`
+//! into candidate abilities. Candidates are abilities that grant the observed
+//! immunity; nothing here knows which abilities a species can actually have.
+//! Move orderings are checked against Speed for a Choice Scarf the same way,
+//! once the visible explanations are ruled out. Candidate sets lose the moves
+//! the format's clauses ban, which the opponent can't have chosen, and are
+//! scored on how well they fit what was revealed and scouted.
+
+use kazam_protocol::Pokemon;
+
+use super::moves::move_priority;
+use crate::id::to_id;
+use crate::tracking::{CandidateSet, MoveOrder, RuleContext, TrackedBattle};
+use crate::types::{KnowledgeKind, Observation, Outcome, PokemonState, StatStages, Status, Type};
+
+/// Abilities that could explain a Pokemon's recorded contradictions
+///
+/// Empty once the ability is revealed. Candidates are listed in the order their
+/// evidence was seen, without duplicates.
+pub fn ability_hypotheses(pokemon: &PokemonState) -> Vec<&'static str> {
+    if pokemon.known_ability.is_some() {
+        return Vec::new();
+    }
+    let mut hypotheses = Vec::new();
+    for ability in pokemon.contradictions.iter().flat_map(explaining_abilities) {
+        if !hypotheses.contains(ability) {
+            hypotheses.push(*ability);
+        }
+    }
+    hypotheses
+}
+
+/// Items that could explain `pokemon` outspeeding a foe it should be slower than
+///
+/// Speed comes from known stats, or from `base_speed` (an unboosted estimate,
+/// e.g. from a species' usual spread) otherwise; orders where either Speed is
+/// unknown are skipped, as are orders explained by move priority, Trick Room
+/// or a recorded exception (Custap Berry, Quick Claw, Quick Draw). Empty once
+/// the item is revealed.
+pub fn speed_item_hypotheses(
+    battle: &TrackedBattle,
+    pokemon: &Pokemon,
+    base_speed: impl Fn(&PokemonState) -> Option<u32>,
+) -> Vec<&'static str> {
+    let Some(state) = find(battle, pokemon) else {
+        return Vec::new();
+    };
+    if state.known_item.is_some() {
+        return Vec::new();
+    }
+    let outsped = battle
+        .move_orders
+        .iter()
+        .filter(|order| order.first.player == pokemon.player && order.first.name == pokemon.name)
+        .any(|order| unexplained_outspeed(battle, order, &base_speed));
+    if outsped {
+        vec!["Choice Scarf"]
+    } else {
+        Vec::new()
+    }
+}
+
+/// `sets` without the moves `rules` ban, such as OHKO moves under the OHKO Clause
+///
+/// A random battle role built for another format may list them; it picks
+/// its moves from what's left.
+pub fn sets_under_rules(rules: &RuleContext, sets: &[CandidateSet]) -> Vec<CandidateSet> {
+    sets.iter()
+        .map(|set| CandidateSet {
+            moves: set
+                .moves
+                .iter()
+                .filter(|m| rules.banning(m).is_none())
+                .cloned()
+                .collect(),
+            ..set.clone()
+        })
+        .collect()
+}
+
+/// How likely each of `sets` is for `pokemon`, in the same order
+///
+/// A set starts at its own weight and drops to 0 if it doesn't allow what
+/// was revealed this game ([`CandidateSet::allows`]). Each scouted move,
+/// ability or item it doesn't list scales it by `1 - weight` of that entry
+/// ([`KnowledgeEntry::weight`](crate::types::KnowledgeEntry::weight)), so a
+/// detail from an earlier game counts against a set, but less than a reveal.
+/// Scores aren't normalized.
+pub fn candidate_scores(pokemon: &PokemonState, sets: &[CandidateSet]) -> Vec<f32> {
+    sets.iter()
+        .map(|set| {
+            if !set.allows(pokemon) {
+                return 0.0;
+            }
+            pokemon
+                .scouting
+                .iter()
+                .filter(|entry| {
+                    let options = match entry.kind {
+                        KnowledgeKind::Move => &set.moves,
+                        KnowledgeKind::Ability => &set.abilities,
+                        KnowledgeKind::Item => &set.items,
+                        KnowledgeKind::TeraType => return false,
+                    };
+                    let listed = options.iter().any(|o| to_id(o) == to_id(&entry.value));
+                    // An empty ability or item list allows any
+                    !listed && (entry.kind == KnowledgeKind::Move || !options.is_empty())
+                })
+                .fold(set.weight, |score, entry| score * (1.0 - entry.weight()))
+        })
+        .collect()
+}
+
+/// Whether the first mover was slower than the second with nothing to explain it
+fn unexplained_outspeed(
+    battle: &TrackedBattle,
+    order: &MoveOrder,
+    base_speed: &impl Fn(&PokemonState) -> Option<u32>,
+) -> bool {
+    if order.trick_room
+        || move_priority(&order.first_move) != move_priority(&order.second_move)
+        || battle.is_order_explained(order)
+    {
+        return false;
+    }
+    let speed = |pokemon: &Pokemon, stage: i8| {
+        let state = find(battle, pokemon)?;
+        let raw = state
+            .stats
+            .as_ref()
+            .map(|stats| stats.spe)
+            .or_else(|| base_speed(state))?;
+        Some(raw as f32 * StatStages::multiplier(stage))
+    };
+    match (
+        speed(&order.first, order.first_speed_stage),
+        speed(&order.second, order.second_speed_stage),
+    ) {
+        (Some(first), Some(second)) => first < second,
+        _ => false,
+    }
+}
+
+fn find<'a>(battle: &'a TrackedBattle, pokemon: &Pokemon) -> Option<&'a PokemonState> {
+    let side = battle.get_side(pokemon.player)?;
+    side.get_pokemon(side.find_pokemon(&pokemon.name)?)
+}
+
+/// Abilities that turn the expected outcome into the observed one
+fn explaining_abilities(observation: &Observation) -> &'static [&'static str] {
+    match (&observation.expected, &observation.observed) {
+        (Outcome::Hit(attack_type), Outcome::Immune) => match attack_type {
+            Type::Ground => &["Levitate"],
+            Type::Water => &["Water Absorb", "Storm Drain", "Dry Skin"],
+            Type::Electric => &["Volt Absorb", "Lightning Rod", "Motor Drive"],
+            Type::Fire => &["Flash Fire", "Well-Baked Body"],
+            Type::Grass => &["Sap Sipper"],
+            _ => &[],
+        },
+        (Outcome::Status(status), Outcome::Failed) => match status {
+            Status::Paralysis => &["Limber", "Purifying Salt", "Comatose"],
+            Status::Burn => &[
+                "Water Veil",
+                "Water Bubble",
+                "Thermal Exchange",
+                "Purifying Salt",
+                "Comatose",
+            ],
+            Status::Poison | Status::BadPoison => {
+                &["Immunity", "Pastel Veil", "Purifying Salt", "Comatose"]
+            }
+            Status::Sleep => &[
+                "Insomnia",
+                "Vital Spirit",
+                "Sweet Veil",
+                "Purifying Salt",
+                "Comatose",
+            ],
+            Status::Freeze => &["Magma Armor", "Purifying Salt", "Comatose"],
+        },
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::TrackedBattle;
+    use crate::types::KnowledgeEntry;
+    use kazam_protocol::{Player, parse_server_message};
+
+    fn bronzong_after(log: &str) -> PokemonState {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let setup = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Bronzong|Bronzong, L50|100/100
+|turn|1"#;
+        for line in setup.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        // No species data in this crate, so the typing is filled in by hand
+        battle.get_side_mut(Player::P2).unwrap().pokemon[0]
+            .set_types(vec![Type::Steel, Type::Psychic]);
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle.get_side(Player::P2).unwrap().pokemon[0].clone()
+    }
+
+    #[test]
+    fn test_earthquake_immunity_suggests_levitate() {
+        let bronzong =
+            bronzong_after("|move|p1a: Garchomp|Earthquake|p2a: Bronzong\n|-immune|p2a: Bronzong");
+        assert_eq!(ability_hypotheses(&bronzong), vec!["Levitate"]);
+    }
+
+    #[test]
+    fn test_magnet_rise_leaves_no_hypothesis() {
+        let bronzong = bronzong_after(
+            r#"|-start|p2a: Bronzong|Magnet Rise
+|move|p1a: Garchomp|Earthquake|p2a: Bronzong
+|-immune|p2a: Bronzong"#,
+        );
+        assert!(ability_hypotheses(&bronzong).is_empty());
+    }
+
+    #[test]
+    fn test_hypotheses_merge_and_clear_on_reveal() {
+        let mut bronzong = bronzong_after(
+            r#"|move|p1a: Garchomp|Scald|p2a: Bronzong
+|-immune|p2a: Bronzong
+|move|p1a: Garchomp|Surf|p2a: Bronzong
+|-immune|p2a: Bronzong"#,
+        );
+        assert_eq!(
+            ability_hypotheses(&bronzong),
+            vec!["Water Absorb", "Storm Drain", "Dry Skin"]
+        );
+
+        bronzong.record_ability("Water Absorb");
+        assert!(ability_hypotheses(&bronzong).is_empty());
+    }
+
+    const FERROTHORN_START: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Ferrothorn|Ferrothorn, L50, M|100/100
+|turn|1"#;
+
+    /// Rough level 50 Speed, standing in for species data
+    fn base_speed(pokemon: &PokemonState) -> Option<u32> {
+        match pokemon.identity.species.as_str() {
+            "Garchomp" => Some(154),
+            "Ferrothorn" => Some(40),
+            _ => None,
+        }
+    }
+
+    fn ferrothorn_first(log: &str) -> (TrackedBattle, Vec<&'static str>) {
+        let mut battle = TrackedBattle::new();
+        for line in FERROTHORN_START.lines().chain(log.lines()) {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let ferrothorn = Pokemon {
+            player: Player::P2,
+            position: Some('a'),
+            name: "Ferrothorn".to_string(),
+        };
+        let hypotheses = speed_item_hypotheses(&battle, &ferrothorn, base_speed);
+        (battle, hypotheses)
+    }
+
+    #[test]
+    fn test_clauses_narrow_candidate_sets() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let sets = [CandidateSet {
+            moves: names(&[
+                "Earthquake",
+                "Fissure",
+                "Double Team",
+                "Rock Slide",
+                "Curse",
+            ]),
+            abilities: names(&["Sturdy"]),
+            items: Vec::new(),
+            weight: 1.0,
+        }];
+        let rules = RuleContext::from_rules([
+            "OHKO Clause: OHKO moves are banned",
+            "Evasion Moves Clause: Evasion moves are banned",
+        ]);
+        let narrowed = sets_under_rules(&rules, &sets);
+        assert_eq!(narrowed[0].moves, ["Earthquake", "Rock Slide", "Curse"]);
+        assert_eq!(narrowed[0].abilities, sets[0].abilities);
+
+        // Four of five moves are open without the clauses, all three with them
+        let mut golem = PokemonState::new("Golem", 86);
+        assert!(golem.knowledge_summary_with(&sets).set_entropy.unwrap() > 2.0);
+        assert_eq!(
+            golem.knowledge_summary_with(&narrowed).set_entropy,
+            Some(0.0)
+        );
+        golem.known_moves.push("Fissure".to_string());
+        assert!(!narrowed[0].allows(&golem));
+        assert_eq!(sets_under_rules(&RuleContext::default(), &sets), sets);
+    }
+
+    #[test]
+    fn test_scouted_details_count_less_than_reveals() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let set = |moves: &[&str]| CandidateSet {
+            moves: names(moves),
+            abilities: Vec::new(),
+            items: Vec::new(),
+            weight: 2.0,
+        };
+        let sets = [
+            set(&["Earthquake", "Stealth Rock", "Spikes", "Dragon Tail"]),
+            set(&["Earthquake", "Outrage", "Swords Dance", "Fire Fang"]),
+        ];
+        let mut garchomp = PokemonState::new("Garchomp", 100);
+        assert_eq!(candidate_scores(&garchomp, &sets), [2.0, 2.0]);
+
+        // Stealth Rock seen last game only makes the other set less likely
+        garchomp.add_prior(KnowledgeKind::Move, "Stealth Rock");
+        assert_eq!(
+            candidate_scores(&garchomp, &sets),
+            [2.0, 2.0 * (1.0 - KnowledgeEntry::PRIOR_WEIGHT)]
+        );
+
+        // Using it this game rules the other set out
+        garchomp.record_move("Stealth Rock");
+        assert_eq!(candidate_scores(&garchomp, &sets), [2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_custap_turn_is_not_scarf_evidence() {
+        let (battle, hypotheses) = ferrothorn_first(
+            r#"|-activate|p2a: Ferrothorn|item: Custap Berry|[consumed]
+|-enditem|p2a: Ferrothorn|Custap Berry|[eat]
+|move|p2a: Ferrothorn|Gyro Ball|p1a: Garchomp
+|-damage|p1a: Garchomp|71/100
+|move|p1a: Garchomp|Earthquake|p2a: Ferrothorn
+|-damage|p2a: Ferrothorn|12/100"#,
+        );
+        assert_eq!(battle.move_orders.len(), 1);
+        assert!(battle.is_order_explained(&battle.move_orders[0]));
+        assert!(hypotheses.is_empty());
+
+        let ferrothorn = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(ferrothorn.known_item.as_deref(), Some("Custap Berry"));
+        assert!(ferrothorn.item_consumed);
+    }
+
+    #[test]
+    fn test_unexplained_outspeed_suggests_scarf() {
+        let (_, hypotheses) = ferrothorn_first(
+            r#"|move|p2a: Ferrothorn|Gyro Ball|p1a: Garchomp
+|-damage|p1a: Garchomp|71/100
+|move|p1a: Garchomp|Earthquake|p2a: Ferrothorn
+|-damage|p2a: Ferrothorn|12/100"#,
+        );
+        assert_eq!(hypotheses, vec!["Choice Scarf"]);
+
+        // Priority and Trick Room explain the order on their own
+        let (_, hypotheses) = ferrothorn_first(
+            r#"|move|p2a: Ferrothorn|Protect|p2a: Ferrothorn
+|-singleturn|p2a: Ferrothorn|Protect
+|move|p1a: Garchomp|Earthquake|p2a: Ferrothorn
+|-activate|p2a: Ferrothorn|move: Protect"#,
+        );
+        assert!(hypotheses.is_empty());
+        let (_, hypotheses) = ferrothorn_first(
+            r#"|-fieldstart|move: Trick Room
+|turn|2
+|move|p2a: Ferrothorn|Gyro Ball|p1a: Garchomp
+|move|p1a: Garchomp|Earthquake|p2a: Ferrothorn"#,
+        );
+        assert!(hypotheses.is_empty());
+    }
+}
@@ -1,136 +1,329 @@
-//! Type matchup helpers for decision making
+//! Pokemon-level type matchups
+//!
+//! The type-level helpers live in `kazam-battle-core` and are re-exported
+//! from [`crate::query`].
 
-use crate::types::Type;
+use super::grounding::{Grounded, is_grounded};
+use crate::types::{FieldState, PokemonState, TYPE_CHART, TeraType, Type, to_id};
 
-/// Check if defender is weak (>1x effectiveness) to any of the attacking types
-pub fn is_weak_to_any(defender_types: &[Type], attacking_types: &[Type]) -> bool {
-    attacking_types
-        .iter()
-        .any(|t| t.effectiveness_multi(defender_types) > 1.0)
-}
+/// Slots in a profile: the 18 types, then Stellar
+const PROFILE_LEN: usize = 19;
+
+const STELLAR: usize = 18;
 
-/// Check if defender resists (<1x effectiveness) all of the attacking types
-pub fn resists_all(defender_types: &[Type], attacking_types: &[Type]) -> bool {
-    if attacking_types.is_empty() {
-        return false;
+/// Profiles of every one- and two-type combination, indexed by the first type
+/// and the second type, or `STELLAR` for a single type
+static PROFILES: [[[f32; PROFILE_LEN]; PROFILE_LEN]; 18] = {
+    let mut table = [[[1.0; PROFILE_LEN]; PROFILE_LEN]; 18];
+    let mut first = 0;
+    while first < 18 {
+        let mut second = 0;
+        while second < PROFILE_LEN {
+            let mut attacking = 0;
+            while attacking < 18 {
+                let mut multiplier = TYPE_CHART[attacking][first];
+                if second < 18 {
+                    multiplier *= TYPE_CHART[attacking][second];
+                }
+                table[first][second][attacking] = multiplier;
+                attacking += 1;
+            }
+            second += 1;
+        }
+        first += 1;
     }
-    attacking_types
-        .iter()
-        .all(|t| t.effectiveness_multi(defender_types) < 1.0)
-}
+    table
+};
 
-/// Check if defender is immune (0x effectiveness) to a type
-pub fn is_immune_to(defender_types: &[Type], attacking_type: Type) -> bool {
-    attacking_type.effectiveness_multi(defender_types) == 0.0
+/// Incoming multiplier for every attacking type against one defender
+///
+/// Looking up a profile once beats calling [`weaknesses`](crate::query::weaknesses)
+/// and friends per move: one- and two-type profiles come from a table built at
+/// compile time. The last slot is Stellar Tera Blast, neutral unless the
+/// defender has terastallized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DefensiveProfile {
+    multipliers: [f32; PROFILE_LEN],
 }
 
-/// Get all types that are super effective against the defender
-pub fn weaknesses(defender_types: &[Type]) -> Vec<Type> {
-    Type::all()
-        .iter()
-        .copied()
-        .filter(|t| t.effectiveness_multi(defender_types) > 1.0)
-        .collect()
+impl DefensiveProfile {
+    /// Profile of a typing alone
+    pub fn of(types: &[Type]) -> Self {
+        let multipliers = match *types {
+            [] => [1.0; PROFILE_LEN],
+            [only] => PROFILES[only as usize][STELLAR],
+            [first, second] => PROFILES[first as usize][second as usize],
+            // Forest's Curse and Trick-or-Treat add a third type
+            [first, second, ref rest @ ..] => {
+                let mut multipliers = PROFILES[first as usize][second as usize];
+                for (attacking, multiplier) in Type::ALL.iter().zip(&mut multipliers) {
+                    *multiplier *= attacking.effectiveness_multi(rest);
+                }
+                multipliers
+            }
+        };
+        Self { multipliers }
+    }
+
+    /// Profile of a Pokemon's current types and known ability
+    ///
+    /// Type-absorbing abilities and Wonder Guard zero out what they block,
+    /// unless the ability is suppressed. Grounding is left to
+    /// [`effectiveness_against`], since it depends on the field.
+    pub fn of_pokemon(pokemon: &PokemonState) -> Self {
+        let mut profile = Self::of(pokemon.get_types());
+        if pokemon.terastallized {
+            profile.multipliers[STELLAR] = 2.0;
+        }
+        match pokemon.effective_ability().map(to_id).as_deref() {
+            Some("wonderguard") => {
+                for multiplier in &mut profile.multipliers {
+                    if *multiplier <= 1.0 {
+                        *multiplier = 0.0;
+                    }
+                }
+            }
+            Some(ability) => {
+                if let Some(absorbed) = absorbed_type(ability) {
+                    profile.multipliers[absorbed as usize] = 0.0;
+                }
+            }
+            None => {}
+        }
+        profile
+    }
+
+    /// Multiplier for an attacking type
+    pub fn against(&self, attacking: Type) -> f32 {
+        self.multipliers[attacking as usize]
+    }
+
+    /// Multiplier for Tera Blast of a tera type
+    pub fn against_tera(&self, tera: TeraType) -> f32 {
+        match tera {
+            TeraType::Type(t) => self.against(t),
+            TeraType::Stellar => self.multipliers[STELLAR],
+        }
+    }
+
+    /// Types that are super effective
+    pub fn weaknesses(&self) -> impl Iterator<Item = Type> {
+        self.types_where(|m| m > 1.0)
+    }
+
+    /// Types that are resisted but not blocked
+    pub fn resistances(&self) -> impl Iterator<Item = Type> {
+        self.types_where(|m| m > 0.0 && m < 1.0)
+    }
+
+    /// Types that do nothing
+    pub fn immunities(&self) -> impl Iterator<Item = Type> {
+        self.types_where(|m| m == 0.0)
+    }
+
+    pub fn is_immune_to(&self, attacking: Type) -> bool {
+        self.against(attacking) == 0.0
+    }
+
+    pub fn is_weak_to_any(&self, attacking: &[Type]) -> bool {
+        attacking.iter().any(|&t| self.against(t) > 1.0)
+    }
+
+    /// Whether every type is resisted, false for none
+    pub fn resists_all(&self, attacking: &[Type]) -> bool {
+        !attacking.is_empty() && attacking.iter().all(|&t| self.against(t) < 1.0)
+    }
+
+    /// Best multiplier among attacking types, neutral for none
+    pub fn best_of(&self, attacking: &[Type]) -> f32 {
+        attacking
+            .iter()
+            .map(|&t| self.against(t))
+            .reduce(f32::max)
+            .unwrap_or(1.0)
+    }
+
+    fn types_where(&self, keep: fn(f32) -> bool) -> impl Iterator<Item = Type> {
+        let profile = *self;
+        Type::ALL
+            .iter()
+            .copied()
+            .filter(move |&t| keep(profile.against(t)))
+    }
 }
 
-/// Get all types that the defender resists (0 < effectiveness < 1)
-pub fn resistances(defender_types: &[Type]) -> Vec<Type> {
-    Type::all()
-        .iter()
-        .copied()
-        .filter(|t| {
-            let eff = t.effectiveness_multi(defender_types);
-            eff > 0.0 && eff < 1.0
-        })
-        .collect()
+/// Type an ability absorbs or otherwise blocks entirely, by ability ID
+fn absorbed_type(ability: &str) -> Option<Type> {
+    match ability {
+        "levitate" | "eartheater" => Some(Type::Ground),
+        "waterabsorb" | "stormdrain" | "dryskin" => Some(Type::Water),
+        "voltabsorb" | "lightningrod" | "motordrive" => Some(Type::Electric),
+        "flashfire" | "wellbakedbody" => Some(Type::Fire),
+        "sapsipper" => Some(Type::Grass),
+        _ => None,
+    }
 }
 
-/// Get all types that the defender is immune to
-pub fn immunities(defender_types: &[Type]) -> Vec<Type> {
-    Type::all()
-        .iter()
-        .copied()
-        .filter(|t| t.effectiveness_multi(defender_types) == 0.0)
-        .collect()
+/// Effectiveness of an attacking type against a Pokemon on the current field
+///
+/// Ground attacks miss airborne Pokemon and hit grounded Flying types as if they
+/// were not Flying. None when a Ground attack depends on unknown grounding
+/// (see [`is_grounded`]).
+pub fn effectiveness_against(
+    attacking: Type,
+    defender: &PokemonState,
+    field: &FieldState,
+) -> Option<f32> {
+    let types = defender.get_types();
+    if attacking != Type::Ground {
+        return Some(DefensiveProfile::of(types).against(attacking));
+    }
+    match is_grounded(defender, field) {
+        Grounded::Yes => {
+            let grounded_types: Vec<Type> =
+                types.iter().copied().filter(|&t| t != Type::Flying).collect();
+            Some(attacking.effectiveness_multi(&grounded_types))
+        }
+        Grounded::No => Some(0.0),
+        Grounded::Unknown(_) => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::Volatile;
 
     #[test]
-    fn test_is_weak_to_any() {
-        let water = vec![Type::Water];
-        let attacking = vec![Type::Electric, Type::Grass];
-        assert!(is_weak_to_any(&water, &attacking));
+    fn test_effectiveness_against_uses_grounding() {
+        let mut skarmory = PokemonState::new("Skarmory", 100);
+        skarmory.set_types(vec![Type::Steel, Type::Flying]);
+        skarmory.known_ability = Some("Sturdy".to_string());
+        skarmory.known_item = Some("Leftovers".to_string());
 
-        let neutral = vec![Type::Fire, Type::Ice];
-        assert!(!is_weak_to_any(&water, &neutral));
-    }
+        let field = FieldState::default();
+        assert_eq!(effectiveness_against(Type::Ground, &skarmory, &field), Some(0.0));
+        assert_eq!(effectiveness_against(Type::Fire, &skarmory, &field), Some(2.0));
 
-    #[test]
-    fn test_resists_all() {
-        // Steel resists Normal, Flying, Rock, Bug, Steel, Grass, Psychic, Ice, Dragon, Fairy
-        let steel = vec![Type::Steel];
-        let resisted = vec![Type::Normal, Type::Ice, Type::Fairy];
-        assert!(resists_all(&steel, &resisted));
+        let gravity = FieldState {
+            gravity: true,
+            ..FieldState::default()
+        };
+        assert_eq!(effectiveness_against(Type::Ground, &skarmory, &gravity), Some(2.0));
+
+        skarmory.known_item = None;
+        assert_eq!(effectiveness_against(Type::Ground, &skarmory, &field), None);
+    }
 
-        let not_resisted = vec![Type::Fire, Type::Ice];
-        assert!(!resists_all(&steel, &not_resisted));
+    /// Every one- and two-type combination
+    fn typings() -> Vec<Vec<Type>> {
+        let mut typings = Vec::new();
+        for (i, &first) in Type::ALL.iter().enumerate() {
+            typings.push(vec![first]);
+            for &second in &Type::ALL[i + 1..] {
+                typings.push(vec![first, second]);
+            }
+        }
+        typings
     }
 
     #[test]
-    fn test_is_immune_to() {
-        let ghost = vec![Type::Ghost];
-        assert!(is_immune_to(&ghost, Type::Normal));
-        assert!(is_immune_to(&ghost, Type::Fighting));
-        assert!(!is_immune_to(&ghost, Type::Dark));
+    fn test_profile_matches_type_functions() {
+        use crate::query::{
+            immunities, is_immune_to, is_weak_to_any, resistances, resists_all, weaknesses,
+        };
 
-        let normal = vec![Type::Normal];
-        assert!(is_immune_to(&normal, Type::Ghost));
+        let typings = typings();
+        assert_eq!(typings.len(), 171);
+        for types in &typings {
+            let profile = DefensiveProfile::of(types);
+            for attacking in Type::ALL {
+                assert_eq!(
+                    profile.against(attacking),
+                    attacking.effectiveness_multi(types),
+                    "{attacking} vs {types:?}"
+                );
+                assert_eq!(
+                    profile.is_immune_to(attacking),
+                    is_immune_to(types, attacking)
+                );
+            }
+            assert_eq!(profile.weaknesses().collect::<Vec<_>>(), weaknesses(types));
+            assert_eq!(
+                profile.resistances().collect::<Vec<_>>(),
+                resistances(types)
+            );
+            assert_eq!(profile.immunities().collect::<Vec<_>>(), immunities(types));
+            for attacking in [
+                &[][..],
+                &[Type::Fire, Type::Ground],
+                &[Type::Ice, Type::Fairy],
+            ] {
+                assert_eq!(
+                    profile.is_weak_to_any(attacking),
+                    is_weak_to_any(types, attacking)
+                );
+                assert_eq!(
+                    profile.resists_all(attacking),
+                    resists_all(types, attacking)
+                );
+            }
+            assert_eq!(profile.against_tera(TeraType::Stellar), 1.0);
+        }
 
-        let ground = vec![Type::Ground];
-        assert!(is_immune_to(&ground, Type::Electric));
+        // A third type from Forest's Curse
+        let cursed = [Type::Water, Type::Ground, Type::Grass];
+        let profile = DefensiveProfile::of(&cursed);
+        for attacking in Type::ALL {
+            assert_eq!(
+                profile.against(attacking),
+                attacking.effectiveness_multi(&cursed)
+            );
+        }
+        assert_eq!(DefensiveProfile::of(&[]).best_of(&Type::ALL), 1.0);
     }
 
     #[test]
-    fn test_weaknesses() {
-        // Steel type is weak to Fire, Fighting, Ground
-        let steel = vec![Type::Steel];
-        let weak = weaknesses(&steel);
-        assert!(weak.contains(&Type::Fire));
-        assert!(weak.contains(&Type::Fighting));
-        assert!(weak.contains(&Type::Ground));
-        assert_eq!(weak.len(), 3);
-    }
+    fn test_profile_of_pokemon() {
+        let mut rotom = PokemonState::new("Rotom-Wash", 100);
+        rotom.set_types(vec![Type::Electric, Type::Water]);
+        assert_eq!(
+            DefensiveProfile::of_pokemon(&rotom).against(Type::Ground),
+            2.0
+        );
 
-    #[test]
-    fn test_weaknesses_dual_type() {
-        // Water/Ground (Swampert) is only weak to Grass (4x)
-        let swampert = vec![Type::Water, Type::Ground];
-        let weak = weaknesses(&swampert);
-        assert_eq!(weak, vec![Type::Grass]);
-    }
+        rotom.known_ability = Some("Levitate".to_string());
+        let profile = DefensiveProfile::of_pokemon(&rotom);
+        assert_eq!(profile.against(Type::Ground), 0.0);
+        assert_eq!(profile.weaknesses().collect::<Vec<_>>(), [Type::Grass]);
 
-    #[test]
-    fn test_resistances() {
-        // Steel resists many types
-        let steel = vec![Type::Steel];
-        let resists = resistances(&steel);
-        assert!(resists.contains(&Type::Normal));
-        assert!(resists.contains(&Type::Ice));
-        assert!(resists.contains(&Type::Fairy));
-        // Fire, Fighting, Ground are weaknesses, not resistances
-        assert!(!resists.contains(&Type::Fire));
-    }
+        rotom.add_volatile(Volatile::GastroAcid);
+        assert_eq!(
+            DefensiveProfile::of_pokemon(&rotom).against(Type::Ground),
+            2.0
+        );
 
-    #[test]
-    fn test_immunities() {
-        // Ghost is immune to Normal and Fighting
-        let ghost = vec![Type::Ghost];
-        let immune = immunities(&ghost);
-        assert!(immune.contains(&Type::Normal));
-        assert!(immune.contains(&Type::Fighting));
-        assert_eq!(immune.len(), 2);
+        let mut shedinja = PokemonState::new("Shedinja", 100);
+        shedinja.set_types(vec![Type::Bug, Type::Ghost]);
+        shedinja.known_ability = Some("Wonder Guard".to_string());
+        let profile = DefensiveProfile::of_pokemon(&shedinja);
+        assert_eq!(
+            profile.weaknesses().collect::<Vec<_>>(),
+            [
+                Type::Fire,
+                Type::Flying,
+                Type::Rock,
+                Type::Ghost,
+                Type::Dark
+            ]
+        );
+        assert_eq!(profile.against(Type::Water), 0.0);
+
+        let mut garganacl = PokemonState::new("Garganacl", 100);
+        garganacl.set_types(vec![Type::Rock]);
+        garganacl.terastallize(TeraType::Type(Type::Water));
+        let profile = DefensiveProfile::of_pokemon(&garganacl);
+        assert_eq!(profile.against(Type::Grass), 2.0);
+        assert_eq!(profile.against_tera(TeraType::Stellar), 2.0);
     }
 }
@@ -3,14 +3,34 @@
 //! This module provides utilities for analyzing type matchups and
 //! other battle queries useful for bot decision making.
 
+pub mod damage;
+pub mod evaluate;
+pub mod grounding;
+pub mod inference;
 mod matchup;
+pub mod moves;
+pub mod preview;
+pub mod rounding;
+pub mod switch_in;
+pub mod targeting;
+pub mod tera;
+pub mod trapping;
 
-pub use matchup::{
-    // Type-level queries
-    immunities,
-    is_immune_to,
-    is_weak_to_any,
-    resistances,
-    resists_all,
-    weaknesses,
+// Pokemon-level queries
+pub use matchup::{DefensiveProfile, effectiveness_against};
+// Type-level queries
+pub use kazam_battle_core::matchup::{
+    immunities, is_immune_to, is_weak_to_any, resistances, resists_all, weaknesses,
 };
+
+pub use damage::{DamageContext, MoveCategory};
+pub use evaluate::{Action, EvalWeights, ScoreBreakdown, ScoredAction, evaluate_actions};
+pub use grounding::{Grounded, GroundingUnknown, is_grounded};
+pub use inference::{
+    ability_hypotheses, candidate_scores, sets_under_rules, speed_item_hypotheses,
+};
+pub use preview::{PairScore, PreviewAnalysis, PreviewFlags};
+pub use switch_in::{SwitchInEffect, switch_in_effects};
+pub use targeting::{TargetCheck, TargetIssue, TargetStrictness, TargetingContext};
+pub use tera::{TeraThreatScore, threat_ranking, threat_ranking_among};
+pub use trapping::{TrapFactor, TrapVerdict, my_active_trapped, opponent_trapped};
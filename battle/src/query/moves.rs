@@ -0,0 +1,548 @@
+//! Move classification used by move legality checks and inference
+//!
+//! There is no move data in this crate, so these are curated lists of move
+//! IDs covering what restriction effects care about. Status moves are also
+//! recognized by targets that no damaging move uses.
+
+use super::damage::MoveCategory;
+use crate::types::{Type, to_id};
+
+/// Request targets used only by status moves
+const STATUS_TARGETS: &[&str] = &[
+    "self",
+    "allySide",
+    "foeSide",
+    "all",
+    "allyTeam",
+    "adjacentAlly",
+    "adjacentAllyOrSelf",
+    "allies",
+];
+
+/// Status moves aimed at another Pokemon (target "normal", "any" and friends)
+#[rustfmt::skip]
+const TARGETED_STATUS_MOVES: &[&str] = &[
+    "afteryou", "attract", "babydolleyes", "bestow", "block", "captivate", "charm",
+    "confide", "confuseray", "cottonspore", "darkvoid", "decorate", "defog", "disable",
+    "eerieimpulse", "electrify", "embargo", "encore", "entrainment", "faketears",
+    "featherdance", "flash", "flatter", "floralhealing", "foresight", "forestscurse",
+    "gastroacid", "glare", "grasswhistle", "growl", "grudge", "guardsplit", "guardswap",
+    "healblock", "healpulse", "heartswap", "helpinghand", "hypnosis", "instruct",
+    "kinesis", "leechseed", "leer", "lockon", "lovelykiss", "magicpowder", "meanlook",
+    "memento", "metalsound", "mimic", "mindreader", "miracleeye", "nightmare",
+    "nobleroar", "odorsleuth", "painsplit", "partingshot", "playnice", "poisongas",
+    "poisonpowder", "powder", "powersplit", "powerswap", "psychoshift", "psychup",
+    "purify", "quash", "roar", "roleplay", "sandattack", "scaryface", "screech",
+    "simplebeam", "sing", "skillswap", "sleeppowder", "smokescreen", "soak", "speedswap",
+    "spiderweb", "spite", "spore", "spotlight", "strengthsap", "stringshot", "stunspore",
+    "supersonic", "swagger", "sweetkiss", "switcheroo", "tailwhip", "taunt",
+    "tearfullook", "teeterdance", "telekinesis", "thunderwave", "tickle", "topsyturvy",
+    "torment", "toxic", "toxicthread", "transform", "trick", "trickortreat",
+    "venomdrench", "whirlwind", "willowisp", "worryseed", "yawn",
+];
+
+/// Moves Heal Block prevents: recovery moves plus draining attacks
+#[rustfmt::skip]
+const HEALING_MOVES: &[&str] = &[
+    "absorb", "bitterblade", "drainingkiss", "drainpunch", "dreameater", "floralhealing",
+    "gigadrain", "healorder", "healpulse", "hornleech", "junglehealing", "leechlife",
+    "lifedew", "lunarblessing", "matchagotcha", "megadrain", "milkdrink", "moonlight",
+    "morningsun", "oblivionwing", "paraboliccharge", "purify", "recover", "rest", "roost",
+    "shoreup", "slackoff", "softboiled", "strengthsap", "synthesis", "wish",
+];
+
+/// Status moves that put their target to sleep, which Sleep Clause Mod limits
+#[rustfmt::skip]
+const SLEEP_MOVES: &[&str] = &[
+    "darkvoid", "grasswhistle", "hypnosis", "lovelykiss", "sing", "sleeppowder", "spore",
+    "yawn",
+];
+
+/// One-hit KO moves, banned by the OHKO Clause
+const OHKO_MOVES: &[&str] = &["fissure", "guillotine", "horndrill", "sheercold"];
+
+/// Moves raising the user's evasion, banned by the Evasion Moves Clause
+const EVASION_MOVES: &[&str] = &["doubleteam", "minimize"];
+
+/// Attacks of the types some ability grants an immunity to, plus Flying
+#[rustfmt::skip]
+const TYPED_ATTACKS: &[(Type, &[&str])] = &[
+    (Type::Ground, &[
+        "bonemerang", "bulldoze", "dig", "drillrun", "earthpower", "earthquake",
+        "headlongrush", "highhorsepower", "landswrath", "magnitude", "mudbomb", "mudshot",
+        "mudslap", "precipiceblades", "sandsearstorm", "scorchingsands", "stompingtantrum",
+    ]),
+    (Type::Water, &[
+        "aquajet", "aquastep", "aquatail", "bubblebeam", "crabhammer", "flipturn",
+        "hydropump", "jetpunch", "liquidation", "muddywater", "originpulse", "scald",
+        "snipeshot", "surf", "surgingstrikes", "waterfall", "watergun", "waterpulse",
+        "waterspout", "wavecrash",
+    ]),
+    (Type::Electric, &[
+        "boltstrike", "discharge", "electroball", "electrodrift", "fusionbolt", "nuzzle",
+        "plasmafists", "risingvoltage", "supercellslam", "thunder", "thunderbolt",
+        "thunderfang", "thunderpunch", "thundershock", "voltswitch", "volttackle",
+        "wildcharge", "zingzap",
+    ]),
+    (Type::Fire, &[
+        "armorcannon", "blazekick", "blueflare", "bitterblade", "burningjealousy",
+        "ember", "eruption", "fireblast", "firefang", "firepunch", "flameburst",
+        "flamecharge", "flamethrower", "flareblitz", "heatwave", "lavaplume",
+        "magmastorm", "mysticalfire", "overheat", "pyroball", "sacredfire", "torchsong",
+    ]),
+    (Type::Grass, &[
+        "bulletseed", "energyball", "flowertrick", "gigadrain", "grassknot", "hornleech",
+        "leafblade", "leafstorm", "petalblizzard", "powerwhip", "seedbomb",
+        "solarbeam", "solarblade", "trailblaze", "woodhammer",
+    ]),
+    (Type::Flying, &[
+        "acrobatics", "aerialace", "aeroblast", "airslash", "beakblast", "bleakwindstorm",
+        "bounce", "bravebird", "drillpeck", "dualwingbeat", "fly", "hurricane",
+        "oblivionwing", "skyattack",
+    ]),
+];
+
+/// Commonly used special attacks
+#[rustfmt::skip]
+const SPECIAL_ATTACKS: &[&str] = &[
+    "airslash", "aurasphere", "bleakwindstorm", "blizzard", "boomburst", "bugbuzz",
+    "chargebeam", "clangingscales", "darkpulse", "dazzlinggleam", "discharge",
+    "dracometeor", "dragonpulse", "drainingkiss", "earthpower", "electroball", "electroweb",
+    "energyball", "eruption", "expandingforce", "fierydance", "fireblast", "flamethrower",
+    "flashcannon", "focusblast", "freezedry", "futuresight", "gigadrain", "heatwave",
+    "hurricane", "hydropump", "hyperbeam", "hypervoice", "icebeam", "icywind", "inferno",
+    "judgment", "lavaplume", "leafstorm", "magmastorm", "makeitrain", "matchagotcha",
+    "mirrorcoat", "moonblast", "muddywater", "mudshot", "nightshade",
+    "overheat", "psychic", "psyshock", "scald", "scorchingsands",
+    "shadowball", "sludgebomb", "sludgewave", "snarl", "solarbeam", "sparklingaria",
+    "steameruption", "storedpower", "surf", "terablast", "thunder", "thunderbolt",
+    "torchsong", "triattack", "vacuumwave", "voltswitch", "waterspout", "weatherball",
+    "wildboltstorm",
+];
+
+/// Commonly used physical attacks
+#[rustfmt::skip]
+const PHYSICAL_ATTACKS: &[&str] = &[
+    "accelerock", "aquajet", "aquatail", "avalanche", "bodypress", "bravebird",
+    "bulletpunch", "closecombat", "counter", "crunch", "dragonclaw", "dragontail", "drainpunch", "drillrun", "dualwingbeat", "earthquake", "extremespeed",
+    "facade", "fakeout", "firepunch", "firstimpression", "flareblitz", "focuspunch",
+    "gigatonhammer", "gunkshot", "headlongrush", "headsmash", "highhorsepower",
+    "hornleech", "iceshard", "iciclecrash", "icepunch", "ironhead", "jetpunch",
+    "knockoff", "leafblade", "liquidation", "machpunch", "megahorn", "metalburst", "meteormash",
+    "mortalspin", "outrage", "payback", "playrough", "poisonjab", "powerwhip",
+    "quickattack", "rapidspin", "revenge", "rockblast", "rockslide", "rocktomb",
+    "sacredsword", "seismictoss", "shadowclaw", "shadowsneak", "stoneedge", "suckerpunch", "superpower",
+    "surgingstrikes", "tripleaxel", "uturn", "wavecrash", "wickedblow", "woodhammer",
+    "xscissor", "zenheadbutt",
+];
+
+/// Moves with non-zero priority, by priority bracket
+#[rustfmt::skip]
+const PRIORITY_MOVES: &[(i8, &[&str])] = &[
+    (5, &["helpinghand"]),
+    (4, &[
+        "banefulbunker", "burningbulwark", "detect", "endure", "kingsshield", "magiccoat",
+        "maxguard", "obstruct", "protect", "silktrap", "snatch", "spikyshield",
+    ]),
+    (3, &["craftyshield", "fakeout", "quickguard", "upperhand", "wideguard"]),
+    (2, &[
+        "allyswitch", "extremespeed", "feint", "firstimpression", "followme", "ragepowder",
+        "zippyzap",
+    ]),
+    (1, &[
+        "accelerock", "aquajet", "babydolleyes", "bulletpunch", "iceshard", "jetpunch",
+        "machpunch", "quickattack", "shadowsneak", "suckerpunch", "thunderclap",
+        "vacuumwave", "watershuriken",
+    ]),
+    (-1, &["vitalthrow"]),
+    (-3, &["beakblast", "focuspunch", "shelltrap"]),
+    (-4, &["avalanche", "revenge"]),
+    (-5, &["counter", "mirrorcoat"]),
+    (-6, &["circlethrow", "dragontail", "roar", "teleport", "whirlwind"]),
+    (-7, &["trickroom"]),
+];
+
+/// Commonly used moves below 100% accuracy, by accuracy percent
+#[rustfmt::skip]
+const INACCURATE_MOVES: &[(u8, &[&str])] = &[
+    (95, &[
+        "aircutter", "diamondstorm", "drillrun", "electroweb", "firefang", "fly",
+        "flyingpress", "glaciate", "highhorsepower", "icefang", "icywind", "mudshot",
+        "pinmissile", "razorleaf", "rocktomb", "sacredfire", "snarl", "thunderfang",
+    ]),
+    (90, &[
+        "aquatail", "blazekick", "bonerush", "crabhammer", "doublehit", "dualchop",
+        "hammerarm", "heatwave", "hyperfang", "icehammer", "iciclecrash", "leaftornado",
+        "meteormash", "overheat", "playrough", "rockblast", "rockslide", "rockwrecker",
+        "skyattack", "thunderwave", "toxic", "triplekick",
+    ]),
+    (85, &[
+        "bind", "boneclub", "bounce", "clamp", "fireblast", "firespin", "megahorn",
+        "megapunch", "muddywater", "powerwhip", "rockclimb", "sandtomb", "seedflare",
+        "swagger", "whirlpool", "willowisp",
+    ]),
+    (80, &[
+        "bleakwindstorm", "crosschop", "gunkshot", "headsmash", "hydropump",
+        "sandsearstorm", "springtidestorm", "stoneedge", "wildboltstorm",
+    ]),
+    (75, &[
+        "dragonrush", "irontail", "magmastorm", "megakick", "poisonpowder", "sleeppowder",
+        "stunspore",
+    ]),
+    (70, &["blizzard", "focusblast", "hurricane", "thunder"]),
+    (60, &["hypnosis"]),
+    (55, &["grasswhistle", "sing", "supersonic"]),
+    (50, &["dynamicpunch", "inferno", "zapcannon"]),
+];
+
+/// Commonly used damaging moves with a secondary effect on their target
+/// (status, flinch, confusion or a stat drop), by its chance in percent
+#[rustfmt::skip]
+const SECONDARY_EFFECTS: &[(u8, &[&str])] = &[
+    (100, &[
+        "bulldoze", "dynamicpunch", "electroweb", "fakeout", "icywind", "inferno",
+        "lunge", "mortalspin", "mudshot", "nuzzle", "rocktomb", "snarl", "spiritbreak",
+        "tropkick", "upperhand", "zapcannon",
+    ]),
+    (30, &[
+        "airslash", "bite", "bleakwindstorm", "bodyslam", "discharge", "dragonbreath",
+        "forcepalm", "gunkshot", "hurricane", "ironhead", "lavaplume", "moonblast",
+        "poisonjab", "rockslide", "scald", "sludgebomb", "steameruption", "thunder",
+        "zingzap",
+    ]),
+    (20, &["crunch", "darkpulse", "shadowball", "zenheadbutt"]),
+    (10, &[
+        "blizzard", "bugbuzz", "earthpower", "energyball", "fireblast", "firepunch",
+        "flamethrower", "flashcannon", "focusblast", "icebeam", "icepunch", "psychic",
+        "sludgewave", "thunderbolt", "thunderpunch",
+    ]),
+];
+
+/// Commonly used moves by base PP, before PP Ups
+#[rustfmt::skip]
+const BASE_PP: &[(u8, &[&str])] = &[
+    (40, &["rapidspin"]),
+    (30, &[
+        "agility", "bulletpunch", "bulletseed", "haze", "iceshard", "iciclespear",
+        "lightscreen", "machpunch", "quickattack", "shadowsneak", "vacuumwave",
+    ]),
+    (25, &["bite"]),
+    (20, &[
+        "accelerock", "aquajet", "auroraveil", "bulkup", "calmmind", "dragonbreath",
+        "dragondance", "facade", "flipturn", "followme", "grassyglide", "helpinghand",
+        "knockoff", "nastyplot", "nuzzle", "painsplit", "partingshot", "poisonjab",
+        "psychocut", "quiverdance", "ragepowder", "reflect", "roar", "scaleshot",
+        "spikes", "stealthrock", "stickyweb", "swift", "swordsdance", "taunt",
+        "teleport", "thunderwave", "toxicspikes", "uturn", "voltswitch", "whirlwind",
+    ]),
+    (15, &[
+        "acrobatics", "airslash", "bodyslam", "bravebird", "crunch", "darkpulse",
+        "defog", "discharge", "doubleedge", "dragonclaw", "electroweb", "firefang",
+        "firepunch", "flamethrower", "flareblitz", "fly", "hydrosteam", "icefang",
+        "icepunch", "icywind", "irondefense", "ironhead", "jetpunch", "lavaplume",
+        "leafblade", "lunge", "matchagotcha", "moonblast", "mortalspin", "psyblade",
+        "quickguard", "sacredsword", "saltcure", "scald", "seedbomb", "shadowball",
+        "shadowclaw", "shellsmash", "sleeppowder", "snarl", "spiritbreak", "spore",
+        "supercellslam", "surf", "tailwind", "thunderbolt", "thunderfang",
+        "thunderpunch", "tropkick", "upperhand", "waterfall", "willowisp", "woodhammer",
+        "zenheadbutt",
+    ]),
+    (10, &[
+        "aquastep", "axekick", "banefulbunker", "bellydrum", "bitterblade",
+        "bodypress", "boomburst", "bugbuzz", "burningbulwark", "chillyreception",
+        "circlethrow", "crabhammer", "dazzlinggleam", "dragondarts", "dragonpulse",
+        "dragontail", "drainpunch", "dualwingbeat", "earthpower", "earthquake",
+        "electroshot", "energyball", "expandingforce", "fakeout", "firstimpression",
+        "flashcannon", "forcepalm", "futuresight", "gigadrain", "hammerarm",
+        "healingwish", "heatwave", "heavyslam", "hex", "highhorsepower",
+        "highjumpkick", "hornleech", "hurricane", "hypervoice", "icebeam",
+        "iciclecrash", "kingsshield", "kowtowcleave", "lastrespects", "leechseed",
+        "liquidation", "megahorn", "memento", "meteorbeam", "meteormash",
+        "muddywater", "outrage", "phantomforce", "playrough", "populationbomb",
+        "powerwhip", "protect", "psychic", "psyshock", "ragingbull", "ragingfury",
+        "rockblast", "rockslide", "ruination", "scorchingsands", "silktrap",
+        "sleeptalk", "sludgebomb", "sludgewave", "solarbeam", "spikyshield",
+        "stompingtantrum", "strengthsap", "substitute", "tachyoncutter", "temperflare",
+        "terablast", "thunder", "tidyup", "torchsong", "toxic", "trick",
+        "tripleaxel", "switcheroo", "wavecrash", "wideguard", "wish", "yawn",
+        "zingzap",
+    ]),
+    (5, &[
+        "aromatherapy", "astralbarrage", "bloodmoon", "blizzard", "bounce",
+        "closecombat", "collisioncourse", "crosschop", "destinybond", "detect",
+        "diamondstorm", "dracometeor", "dynamicpunch", "electrodrift", "encore",
+        "eruption", "explosion", "extremespeed", "ficklebeam", "finalgambit",
+        "fireblast", "fissure", "focusblast", "focuspunch", "gigaimpact",
+        "gigatonhammer", "glaciallance", "glaiverush", "guillotine", "gunkshot",
+        "gyroball", "headlongrush", "headsmash", "healbell", "horndrill",
+        "hydropump", "hyperbeam", "inferno", "lastresort", "leafstorm",
+        "magmastorm", "makeitrain", "milkdrink", "moonlight", "morningsun",
+        "overheat", "perishsong", "poltergeist", "recover", "rest", "roost",
+        "sacredfire", "seedflare", "selfdestruct", "sheercold", "shoreup",
+        "skyattack", "slackoff", "softboiled", "steameruption", "steelbeam",
+        "stoneedge", "suckerpunch", "superpower", "surgingstrikes", "synthesis",
+        "trickroom", "vcreate", "waterspout", "wickedblow", "zapcannon",
+    ]),
+];
+
+/// Commonly used attacks by base power
+///
+/// Multi-hit moves and those whose power depends on weight, HP or Speed
+/// (Gyro Ball, Heavy Slam, Eruption, ...) are left out.
+#[rustfmt::skip]
+const BASE_POWER: &[(u8, &[&str])] = &[
+    (250, &["explosion"]),
+    (200, &["selfdestruct"]),
+    (180, &["vcreate"]),
+    (160, &["gigatonhammer"]),
+    (150, &["focuspunch", "gigaimpact", "headsmash", "hyperbeam"]),
+    (140, &["bloodmoon", "boomburst", "lastresort", "skyattack", "steelbeam"]),
+    (130, &["dracometeor", "electroshot", "highjumpkick", "leafstorm", "overheat"]),
+    (120, &[
+        "astralbarrage", "axekick", "bravebird", "closecombat", "doubleedge",
+        "flareblitz", "focusblast", "glaiverush", "gunkshot", "headlongrush",
+        "makeitrain", "megahorn", "meteorbeam", "outrage", "powerwhip", "ragingfury",
+        "seedflare", "solarbeam", "superpower", "wavecrash", "woodhammer", "zapcannon",
+    ]),
+    (110, &[
+        "blizzard", "fireblast", "hurricane", "hydropump", "poltergeist",
+        "steameruption", "thunder",
+    ]),
+    (100, &[
+        "collisioncourse", "crabhammer", "crosschop", "diamondstorm", "dynamicpunch",
+        "earthquake", "electrodrift", "hammerarm", "inferno", "irontail",
+        "sacredfire", "stoneedge", "supercellslam",
+    ]),
+    (95, &["foulplay", "heatwave", "highhorsepower", "moonblast", "sludgewave"]),
+    (90, &[
+        "bitterblade", "bugbuzz", "earthpower", "energyball", "firstimpression",
+        "flamethrower", "fly", "hypervoice", "icebeam", "leafblade", "meteormash",
+        "muddywater", "phantomforce", "playrough", "psychic", "ragingbull",
+        "sacredsword", "sludgebomb", "surf", "thunderbolt",
+    ]),
+    (85, &[
+        "bodyslam", "bounce", "dragonpulse", "iciclecrash", "kowtowcleave",
+        "liquidation", "psychicfangs",
+    ]),
+    (80, &[
+        "aquastep", "aurasphere", "bodypress", "crunch", "darkpulse", "dazzlinggleam",
+        "dig", "discharge", "dragonclaw", "drillrun", "expandingforce", "extremespeed",
+        "flashcannon", "hydrosteam", "ironhead", "lavaplume", "lunge", "poisonjab",
+        "psyblade", "psyshock", "scald", "seedbomb", "shadowball", "terablast",
+        "torchsong", "waterfall", "xscissor", "zenheadbutt", "zingzap",
+    ]),
+    (75, &[
+        "airslash", "drainpunch", "firepunch", "gigadrain", "hornleech", "icepunch",
+        "rockslide", "spiritbreak", "stompingtantrum", "temperflare", "thunderpunch",
+        "wickedblow",
+    ]),
+    (70, &[
+        "facade", "psychocut", "scorchingsands", "shadowclaw", "suckerpunch",
+        "thunderclap", "tropkick", "uturn", "voltswitch",
+    ]),
+    (65, &["firefang", "hex", "icefang", "knockoff", "thunderfang", "upperhand"]),
+    (60, &[
+        "bite", "bulldoze", "circlethrow", "dragontail", "flipturn", "forcepalm",
+        "jetpunch", "rocktomb", "swift",
+    ]),
+    (55, &["acrobatics", "electroweb", "grassyglide", "icywind", "mudshot", "snarl"]),
+    (50, &["rapidspin"]),
+    (40, &[
+        "accelerock", "aquajet", "bulletpunch", "fakeout", "iceshard", "machpunch",
+        "quickattack", "saltcure", "shadowsneak", "vacuumwave",
+    ]),
+    (30, &["mortalspin"]),
+    (20, &["nuzzle"]),
+];
+
+/// Accuracy percent of a move that can miss; None for moves outside the
+/// curated list, which includes every move that can't miss
+///
+/// Ignores accuracy and evasion stages, abilities, items, and weather that
+/// changes the accuracy of Thunder, Hurricane and Blizzard.
+pub fn move_accuracy(name: &str) -> Option<u8> {
+    let id = to_id(name);
+    INACCURATE_MOVES
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map(|(accuracy, _)| *accuracy)
+}
+
+/// Max PP of a move with full PP Ups, as competitive sets run them; None for
+/// moves outside the curated list
+pub fn move_max_pp(name: &str) -> Option<u8> {
+    let id = to_id(name);
+    BASE_PP
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map(|(pp, _)| pp * 8 / 5)
+}
+
+/// Base power of a damaging move; None for status moves and attacks outside
+/// the curated list
+///
+/// Ignores power changed by the battle, such as Knock Off into an item, Hex
+/// into a status or Facade while statused.
+pub fn move_base_power(name: &str) -> Option<u8> {
+    let id = to_id(name);
+    BASE_POWER
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map(|(power, _)| *power)
+}
+
+/// Chance in percent of a damaging move's secondary effect on its target;
+/// None for moves outside the curated list
+///
+/// Ignores Serene Grace, and Covert Cloak or Shield Dust on the target.
+pub fn secondary_chance(name: &str) -> Option<u8> {
+    let id = to_id(name);
+    SECONDARY_EFFECTS
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map(|(chance, _)| *chance)
+}
+
+/// Type of a damaging move, for the types some ability grants an immunity to
+///
+/// None for moves outside the curated list, including every other type.
+pub fn move_type(name: &str) -> Option<Type> {
+    let id = to_id(name);
+    TYPED_ATTACKS
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map(|(t, _)| *t)
+}
+
+/// Physical or special, for the commonly used attacks in the curated lists
+///
+/// None for status moves and attacks outside the lists. Counter and Metal
+/// Burst count as physical and Mirror Coat as special, as the simulator has
+/// them, whatever kind of damage they return.
+pub fn move_category(name: &str) -> Option<MoveCategory> {
+    let id = to_id(name);
+    if PHYSICAL_ATTACKS.contains(&id.as_str()) {
+        Some(MoveCategory::Physical)
+    } else if SPECIAL_ATTACKS.contains(&id.as_str()) {
+        Some(MoveCategory::Special)
+    } else {
+        None
+    }
+}
+
+/// Whether a move is a status move, from its ID or name and its request target
+pub fn is_status_move(name: &str, target: &str) -> bool {
+    STATUS_TARGETS.contains(&target) || TARGETED_STATUS_MOVES.contains(&to_id(name).as_str())
+}
+
+/// Whether a move heals its user or target (blocked by Heal Block)
+pub fn is_healing_move(name: &str) -> bool {
+    HEALING_MOVES.contains(&to_id(name).as_str())
+}
+
+/// Whether a status move puts its target to sleep (Yawn after a turn)
+pub fn is_sleep_move(name: &str) -> bool {
+    SLEEP_MOVES.contains(&to_id(name).as_str())
+}
+
+/// Whether a move is a one-hit KO
+pub fn is_ohko_move(name: &str) -> bool {
+    OHKO_MOVES.contains(&to_id(name).as_str())
+}
+
+/// Whether a move raises its user's evasion
+pub fn is_evasion_move(name: &str) -> bool {
+    EVASION_MOVES.contains(&to_id(name).as_str())
+}
+
+/// Base priority of a move; 0 for moves outside the curated list
+///
+/// Ignores priority changed by abilities (Prankster, Gale Wings, Triage) and
+/// by terrain (Grassy Glide).
+pub fn move_priority(name: &str) -> i8 {
+    let id = to_id(name);
+    PRIORITY_MOVES
+        .iter()
+        .find(|(_, moves)| moves.contains(&id.as_str()))
+        .map_or(0, |(priority, _)| *priority)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_moves_by_target_and_name() {
+        assert!(is_status_move("Swords Dance", "self"));
+        assert!(is_status_move("Stealth Rock", "foeSide"));
+        assert!(is_status_move("Will-O-Wisp", "normal"));
+        assert!(is_status_move("thunderwave", "normal"));
+        assert!(!is_status_move("Earthquake", "allAdjacent"));
+        assert!(!is_status_move("Knock Off", "normal"));
+    }
+
+    #[test]
+    fn test_move_accuracy() {
+        assert_eq!(move_accuracy("Focus Blast"), Some(70));
+        assert_eq!(move_accuracy("stoneedge"), Some(80));
+        assert_eq!(move_accuracy("Will-O-Wisp"), Some(85));
+        assert_eq!(move_accuracy("Earthquake"), None);
+        assert_eq!(move_accuracy("Aerial Ace"), None);
+    }
+
+    #[test]
+    fn test_move_base_power() {
+        assert_eq!(move_base_power("Earthquake"), Some(100));
+        assert_eq!(move_base_power("Knock Off"), Some(65));
+        assert_eq!(move_base_power("explosion"), Some(250));
+        assert_eq!(move_base_power("Gyro Ball"), None);
+        assert_eq!(move_base_power("Swords Dance"), None);
+
+        // Every listed move appears once
+        let mut seen = std::collections::HashSet::new();
+        for (_, moves) in BASE_POWER {
+            for id in *moves {
+                assert!(seen.insert(id), "{id}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_secondary_chance() {
+        assert_eq!(secondary_chance("Fake Out"), Some(100));
+        assert_eq!(secondary_chance("Air Slash"), Some(30));
+        assert_eq!(secondary_chance("icebeam"), Some(10));
+        assert_eq!(secondary_chance("Earthquake"), None);
+    }
+
+    #[test]
+    fn test_move_type() {
+        assert_eq!(move_type("Earthquake"), Some(Type::Ground));
+        assert_eq!(move_type("scald"), Some(Type::Water));
+        assert_eq!(move_type("Flamethrower"), Some(Type::Fire));
+        assert_eq!(move_type("Brave Bird"), Some(Type::Flying));
+        assert_eq!(move_type("Moonblast"), None);
+    }
+
+    #[test]
+    fn test_move_category() {
+        assert_eq!(move_category("Earthquake"), Some(MoveCategory::Physical));
+        assert_eq!(move_category("Thunderbolt"), Some(MoveCategory::Special));
+        assert_eq!(move_category("Swords Dance"), None);
+    }
+
+    #[test]
+    fn test_move_priority() {
+        assert_eq!(move_priority("Extreme Speed"), 2);
+        assert_eq!(move_priority("Sucker Punch"), 1);
+        assert_eq!(move_priority("Trick Room"), -7);
+        assert_eq!(move_priority("Earthquake"), 0);
+    }
+
+    #[test]
+    fn test_healing_moves() {
+        assert!(is_healing_move("Recover"));
+        assert!(is_healing_move("Drain Punch"));
+        assert!(!is_healing_move("Protect"));
+    }
+}
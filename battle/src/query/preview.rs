@@ -0,0 +1,193 @@
+//! Team preview analysis for picking a lead and team order
+//!
+//! Scores every pairing of our team against the opponent's previewed team
+//! using typing alone (each side's best STAB effectiveness). Pokemon without
+//! known types are treated as neutral.
+
+use super::matchup::DefensiveProfile;
+use crate::types::{PokemonState, Type};
+
+/// Score of one of our Pokemon against one opposing Pokemon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairScore {
+    /// Best effectiveness of our STAB types against them
+    pub offensive: f32,
+
+    /// Best effectiveness of their STAB types against us (lower is better)
+    pub defensive: f32,
+
+    /// Combined score, `offensive - defensive`
+    pub score: f32,
+}
+
+impl PairScore {
+    fn new(ours: &[Type], theirs: &[Type]) -> Self {
+        let offensive = best_stab(ours, theirs);
+        let defensive = best_stab(theirs, ours);
+        Self {
+            offensive,
+            defensive,
+            score: offensive - defensive,
+        }
+    }
+}
+
+/// Flags explaining where one of our Pokemon stands in the matchup
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PreviewFlags {
+    /// Opposing indices whose typing resists or is immune to all of our STABs
+    pub walled_by: Vec<usize>,
+
+    /// Opposing indices for which this is our only Pokemon with a positive score
+    pub only_answer_to: Vec<usize>,
+}
+
+/// Result of analyzing our team against the opponent's preview
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewAnalysis {
+    /// `matrix[ours][theirs]` holds the score for each pairing
+    pub matrix: Vec<Vec<PairScore>>,
+
+    /// Lowest score of each of our Pokemon across all opposing leads
+    pub worst_case: Vec<f32>,
+
+    /// Index of our suggested lead (best worst-case score, lowest index on ties)
+    pub lead: Option<usize>,
+
+    /// Per-Pokemon flags, indexed like our team
+    pub flags: Vec<PreviewFlags>,
+}
+
+impl PreviewAnalysis {
+    /// Team order with the suggested lead first, the rest by worst-case score
+    pub fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.worst_case.len()).collect();
+        order.sort_by(|&a, &b| {
+            let lead_first = (Some(b) == self.lead).cmp(&(Some(a) == self.lead));
+            lead_first
+                .then_with(|| self.worst_case[b].total_cmp(&self.worst_case[a]))
+                .then_with(|| a.cmp(&b))
+        });
+        order
+    }
+
+    /// Team preview choice string (e.g. "team 312456")
+    pub fn team_choice(&self) -> String {
+        let slots: String = self.order().iter().map(|i| (i + 1).to_string()).collect();
+        format!("team {}", slots)
+    }
+}
+
+/// Analyze our team against the opponent's previewed team
+pub fn analyze(mine: &[PokemonState], theirs: &[PokemonState]) -> PreviewAnalysis {
+    let matrix: Vec<Vec<PairScore>> = mine
+        .iter()
+        .map(|ours| {
+            theirs
+                .iter()
+                .map(|opp| PairScore::new(&ours.base_types, &opp.base_types))
+                .collect()
+        })
+        .collect();
+
+    let worst_case: Vec<f32> = matrix
+        .iter()
+        .map(|row| row.iter().map(|p| p.score).fold(f32::INFINITY, f32::min))
+        .collect();
+
+    let lead = worst_case
+        .iter()
+        .enumerate()
+        .fold(None, |best: Option<(usize, f32)>, (i, &score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((i, score)),
+        })
+        .map(|(i, _)| i);
+
+    let mut flags = vec![PreviewFlags::default(); mine.len()];
+    for (i, row) in matrix.iter().enumerate() {
+        flags[i].walled_by = row
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.offensive < 1.0)
+            .map(|(j, _)| j)
+            .collect();
+    }
+    for j in 0..theirs.len() {
+        let mut answers = matrix
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row[j].score > 0.0);
+        if let (Some((i, _)), None) = (answers.next(), answers.next()) {
+            flags[i].only_answer_to.push(j);
+        }
+    }
+
+    PreviewAnalysis {
+        matrix,
+        worst_case,
+        lead,
+        flags,
+    }
+}
+
+/// Best effectiveness of any attacking type, neutral if the attacker has no known types
+fn best_stab(attacking: &[Type], defending: &[Type]) -> f32 {
+    DefensiveProfile::of(defending).best_of(attacking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poke(species: &str, types: &[Type]) -> PokemonState {
+        let mut poke = PokemonState::new(species, 100);
+        poke.base_types = types.into();
+        poke.current_types = types.into();
+        poke
+    }
+
+    #[test]
+    fn test_unambiguous_lead() {
+        let mine = vec![
+            poke("Gengar", &[Type::Ghost, Type::Poison]),
+            poke("Swampert", &[Type::Water, Type::Ground]),
+            poke("Venusaur", &[Type::Grass, Type::Poison]),
+        ];
+        let theirs = vec![
+            poke("Heatran", &[Type::Fire, Type::Steel]),
+            poke("Tyranitar", &[Type::Rock, Type::Dark]),
+        ];
+
+        let analysis = analyze(&mine, &theirs);
+
+        // Swampert hits both super-effectively and resists Heatran's STABs
+        assert_eq!(analysis.lead, Some(1));
+        assert_eq!(analysis.matrix[1][0].offensive, 4.0);
+        assert_eq!(analysis.matrix[1][0].defensive, 0.5);
+        assert_eq!(analysis.matrix[1][0].score, 3.5);
+        assert_eq!(analysis.worst_case, vec![-1.5, 1.0, -1.75]);
+
+        // Gengar is walled by Tyranitar, Venusaur by Heatran
+        assert_eq!(analysis.matrix[0][1].defensive, 2.0);
+        assert_eq!(analysis.flags[0].walled_by, vec![1]);
+        assert_eq!(analysis.flags[2].walled_by, vec![0]);
+        assert_eq!(analysis.flags[1].only_answer_to, vec![0]);
+        assert!(analysis.flags[2].only_answer_to.is_empty());
+
+        assert_eq!(analysis.order(), vec![1, 0, 2]);
+        assert_eq!(analysis.team_choice(), "team 213");
+    }
+
+    #[test]
+    fn test_unknown_types_are_neutral() {
+        let mine = vec![poke("Unknown", &[])];
+        let theirs = vec![poke("Garchomp", &[Type::Dragon, Type::Ground])];
+
+        let analysis = analyze(&mine, &theirs);
+        assert_eq!(analysis.matrix[0][0].offensive, 1.0);
+        assert_eq!(analysis.matrix[0][0].defensive, 1.0);
+        assert_eq!(analysis.lead, Some(0));
+        assert!(analysis.flags[0].walled_by.is_empty());
+    }
+}
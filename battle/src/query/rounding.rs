@@ -0,0 +1,167 @@
+//! Showdown's integer rounding for damage, recoil and residual HP changes
+//!
+//! The simulator never multiplies HP by a float. Multipliers are fixed point
+//! in 4096ths, and two kinds of rounding apply:
+//!
+//! - [`poke_round`] applies a modifier to a value, rounding exact halves
+//!   *down* (`(value * modifier + 2047) / 4096`)
+//! - [`chain`] combines two modifiers into one, rounding exact halves *up*
+//!   (`(a * b + 2048) >> 12`)
+//!
+//! A damage roll applies its steps one at a time, rounding after each:
+//!
+//! 1. [`base_damage`]: `floor(floor(floor(2 * level / 5 + 2) * power * attack / defense) / 50) + 2`
+//! 2. spread (3072), then weather (6144 or 2048)
+//! 3. critical hit (6144)
+//! 4. random roll: `floor(damage * (85..=100) / 100)`
+//! 5. STAB (6144, 8192 with Adaptability)
+//! 6. type effectiveness: doubled or halved (floored) once per step
+//! 7. burn on a physical move (2048)
+//! 8. the final modifiers, [`chain`]ed into one before [`poke_round`]: screens,
+//!    Friend Guard, then the attacker's item (Life Orb 5324)
+//! 9. at least 1
+//!
+//! Steps 7 and 8 are the ones that are easy to get wrong: burn is applied on
+//! its own rather than chained, and the final modifiers round once as a chain
+//! rather than once each. Multiplying it all out as floats is off by one on
+//! about half the rolls.
+//!
+//! HP lost to residual effects and recoil is a fraction of max HP, floored but
+//! never below 1 ([`fraction_of`]); recoil from damage dealt rounds to nearest
+//! ([`recoil`]).
+
+/// The modifier that changes nothing
+pub const UNMODIFIED: u32 = 4096;
+
+/// Life Orb's damage modifier, 5324/4096 (about 1.3)
+pub const LIFE_ORB: u32 = 5324;
+
+/// A `numerator / denominator` multiplier in 4096ths, truncated
+pub fn modifier(numerator: u32, denominator: u32) -> u32 {
+    numerator * UNMODIFIED / denominator
+}
+
+/// Apply a 4096ths modifier, rounding half down like the simulator
+pub fn poke_round(value: u32, modifier: u32) -> u32 {
+    (value * modifier + 2047) / UNMODIFIED
+}
+
+/// Chain two 4096ths modifiers, rounding half up
+pub fn chain(previous: u32, next: u32) -> u32 {
+    (previous * next + 2048) >> 12
+}
+
+/// Chain modifiers in order, starting from [`UNMODIFIED`]
+pub fn chain_all(modifiers: impl IntoIterator<Item = u32>) -> u32 {
+    modifiers.into_iter().fold(UNMODIFIED, chain)
+}
+
+/// Damage before any modifier, from the attacker's level, the move's base
+/// power and the attacking and defending stats
+pub fn base_damage(level: u32, power: u32, attack: u32, defense: u32) -> u32 {
+    (2 * level / 5 + 2) * power * attack / defense.max(1) / 50 + 2
+}
+
+/// `numerator / denominator` of `max_hp`, floored but at least 1
+///
+/// How the simulator sizes residual damage and healing: burn, poison,
+/// weather, hazards, Leftovers, Life Orb recoil.
+pub fn fraction_of(max_hp: u32, numerator: u32, denominator: u32) -> u32 {
+    (max_hp * numerator / denominator).max(1)
+}
+
+/// Recoil of `numerator / denominator` of the damage dealt, rounded to
+/// nearest but at least 1
+pub fn recoil(damage: u32, numerator: u32, denominator: u32) -> u32 {
+    ((2 * damage * numerator + denominator) / (2 * denominator)).max(1)
+}
+
+/// Life Orb recoil: a tenth of max HP
+pub fn life_orb_recoil(max_hp: u32) -> u32 {
+    fraction_of(max_hp, 1, 10)
+}
+
+/// Burn damage per turn: a sixteenth of max HP from Gen 7, an eighth before
+pub fn burn_damage(max_hp: u32, generation: u8) -> u32 {
+    fraction_of(max_hp, 1, if generation >= 7 { 16 } else { 8 })
+}
+
+/// Regular poison damage per turn: an eighth of max HP
+pub fn poison_damage(max_hp: u32) -> u32 {
+    fraction_of(max_hp, 1, 8)
+}
+
+/// Toxic damage on its `stage`th turn: that many sixteenths, each rounded
+pub fn toxic_damage(max_hp: u32, stage: u32) -> u32 {
+    fraction_of(max_hp, 1, 16) * stage
+}
+
+/// Stealth Rock damage; `effectiveness` is Rock's multiplier (0.25 up to 4)
+pub fn stealth_rock_damage(max_hp: u32, effectiveness: f32) -> u32 {
+    // In 32nds, so a quarter of an eighth is still whole
+    let parts = (effectiveness * 4.0).round() as u32;
+    fraction_of(max_hp, parts, 32)
+}
+
+/// Spikes damage for 1 to 3 layers: 3, 4 and 6 twenty-fourths of max HP
+pub fn spikes_damage(max_hp: u32, layers: u8) -> u32 {
+    match layers {
+        0 => 0,
+        1 => fraction_of(max_hp, 3, 24),
+        2 => fraction_of(max_hp, 4, 24),
+        _ => fraction_of(max_hp, 6, 24),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halves_round_down_when_applied_and_up_when_chained() {
+        // 1.5, 4.5 and 7.5 all round down
+        assert_eq!(poke_round(1, 6144), 1);
+        assert_eq!(poke_round(3, 6144), 4);
+        assert_eq!(poke_round(5, 6144), 7);
+        // Reflect in doubles chained with Life Orb: 2732 * 5324 / 4096 = 3551.06
+        assert_eq!(chain(2732, LIFE_ORB), 3551);
+        // 2049 * 2048 / 4096 = 1024.5 rounds up
+        assert_eq!(chain(2049, 2048), 1025);
+        assert_eq!(chain_all([2048, LIFE_ORB]), 2662);
+        assert_eq!(chain_all([]), UNMODIFIED);
+        assert_eq!(modifier(13, 10), 5324);
+        assert_eq!(modifier(3, 4), 3072);
+    }
+
+    #[test]
+    fn test_residuals_floor_but_never_reach_zero() {
+        // 301 max HP, e.g. a bulky 70 HP base Pokemon at level 100
+        assert_eq!(life_orb_recoil(301), 30);
+        assert_eq!(burn_damage(301, 9), 18);
+        assert_eq!(burn_damage(301, 6), 37);
+        assert_eq!(poison_damage(301), 37);
+        assert_eq!(toxic_damage(301, 3), 54);
+        assert_eq!(stealth_rock_damage(301, 4.0), 150);
+        assert_eq!(stealth_rock_damage(301, 0.25), 9);
+        assert_eq!(spikes_damage(301, 1), 37);
+        assert_eq!(spikes_damage(301, 3), 75);
+        // Shedinja still loses its one HP
+        assert_eq!(life_orb_recoil(1), 1);
+        assert_eq!(burn_damage(1, 9), 1);
+    }
+
+    #[test]
+    fn test_recoil_rounds_to_nearest() {
+        assert_eq!(recoil(100, 1, 3), 33);
+        assert_eq!(recoil(101, 1, 3), 34);
+        assert_eq!(recoil(150, 33, 100), 50);
+        assert_eq!(recoil(1, 1, 4), 1);
+    }
+
+    #[test]
+    fn test_base_damage() {
+        // Level 100, 100 power, 394 Attack into 249 Defense
+        assert_eq!(base_damage(100, 100, 394, 249), 134);
+        assert_eq!(base_damage(50, 80, 100, 100), 37);
+    }
+}
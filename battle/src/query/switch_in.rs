@@ -0,0 +1,453 @@
+//! What a Pokemon's ability does the moment it switches in
+//!
+//! [`switch_in_effects`] lists the effects certain to happen when a Pokemon
+//! enters the field, so a projection of the turn after a switch starts from
+//! the right boosts, weather and terrain. Abilities are looked up in a table
+//! of switch-in effects; only a known ability counts, and only if it takes
+//! effect ([`PokemonState::effective_ability_on`]) once the Pokemon it
+//! replaces has left. Beyond that:
+//!
+//! - weather and terrain already up aren't set again, and Desolate Land,
+//!   Primordial Sea and Delta Stream keep ordinary weather out
+//! - Intimidate misses a foe behind a Substitute, holding Clear Amulet, or
+//!   with a known ability that blocks it; Guard Dog turns it into a boost,
+//!   Mirror Armor bounces it back, Contrary and Simple invert and double it,
+//!   and Defiant and Competitive answer it. A foe's unrevealed ability is
+//!   assumed not to interfere.
+//! - Intrepid Sword and Dauntless Shield fire once per battle from Gen 9,
+//!   tracked by [`PokemonState::switch_in_boost_used`]
+//! - Embody Aspect only fires while Ogerpon is Terastallized
+//! - Download needs every opposing active's Defense and Special Defense known
+
+use kazam_protocol::{Player, Stat};
+
+use crate::tracking::TrackedBattle;
+use crate::types::{PokemonState, Terrain, Volatile, Weather, to_id};
+
+/// An effect that will fire when a Pokemon switches in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchInEffect {
+    /// Stat stages for the Pokemon switching in
+    BoostSelf {
+        stat: Stat,
+        stages: i8,
+    },
+
+    /// Stat stages for an opposing active Pokemon
+    BoostFoe {
+        player: Player,
+        slot: usize,
+        stat: Stat,
+        stages: i8,
+    },
+
+    Weather(Weather),
+
+    Terrain(Terrain),
+}
+
+/// What a switch-in ability does before the battle is taken into account
+#[derive(Debug, Clone, Copy)]
+enum OnSwitchIn {
+    /// A self boost that, from Gen 9, only happens the first time
+    OncePerBattle(Stat, i8),
+    /// A self boost that only happens while Terastallized
+    Terastallized(Stat, i8),
+    Intimidate,
+    Download,
+    Weather(Weather),
+    Terrain(Terrain),
+}
+
+#[rustfmt::skip]
+const SWITCH_IN_ABILITIES: &[(&str, OnSwitchIn)] = &[
+    ("intimidate", OnSwitchIn::Intimidate),
+    ("download", OnSwitchIn::Download),
+    ("intrepidsword", OnSwitchIn::OncePerBattle(Stat::Atk, 1)),
+    ("dauntlessshield", OnSwitchIn::OncePerBattle(Stat::Def, 1)),
+    ("embodyaspectteal", OnSwitchIn::Terastallized(Stat::Spe, 1)),
+    ("embodyaspecthearthflame", OnSwitchIn::Terastallized(Stat::Atk, 1)),
+    ("embodyaspectwellspring", OnSwitchIn::Terastallized(Stat::Spd, 1)),
+    ("embodyaspectcornerstone", OnSwitchIn::Terastallized(Stat::Def, 1)),
+    ("drought", OnSwitchIn::Weather(Weather::Sun)),
+    ("orichalcumpulse", OnSwitchIn::Weather(Weather::Sun)),
+    ("drizzle", OnSwitchIn::Weather(Weather::Rain)),
+    ("sandstream", OnSwitchIn::Weather(Weather::Sand)),
+    // Hail before Gen 9
+    ("snowwarning", OnSwitchIn::Weather(Weather::Snow)),
+    ("desolateland", OnSwitchIn::Weather(Weather::HarshSun)),
+    ("primordialsea", OnSwitchIn::Weather(Weather::HeavyRain)),
+    ("deltastream", OnSwitchIn::Weather(Weather::StrongWinds)),
+    ("electricsurge", OnSwitchIn::Terrain(Terrain::Electric)),
+    ("hadronengine", OnSwitchIn::Terrain(Terrain::Electric)),
+    ("grassysurge", OnSwitchIn::Terrain(Terrain::Grassy)),
+    ("mistysurge", OnSwitchIn::Terrain(Terrain::Misty)),
+    ("psychicsurge", OnSwitchIn::Terrain(Terrain::Psychic)),
+];
+
+/// Abilities that keep Intimidate's drop off, with the generation they started to
+const INTIMIDATE_BLOCKERS: &[(&str, u8)] = &[
+    ("clearbody", 3),
+    ("whitesmoke", 3),
+    ("hypercutter", 3),
+    ("fullmetalbody", 7),
+    ("innerfocus", 8),
+    ("oblivious", 8),
+    ("owntempo", 8),
+    ("scrappy", 8),
+];
+
+/// Whether a boost from `ability` is Intrepid Sword or Dauntless Shield
+pub(crate) fn is_once_per_battle(ability: &str) -> bool {
+    let id = to_id(ability);
+    SWITCH_IN_ABILITIES
+        .iter()
+        .any(|(a, effect)| *a == id && matches!(effect, OnSwitchIn::OncePerBattle(..)))
+}
+
+/// Effects certain to fire when `pokemon` switches into `player`'s `slot`
+///
+/// Effects come in the order the simulator applies them. An empty list means
+/// nothing is known to happen, not that nothing will.
+pub fn switch_in_effects(
+    battle: &TrackedBattle,
+    player: Player,
+    slot: usize,
+    pokemon: &PokemonState,
+) -> Vec<SwitchInEffect> {
+    let mechanics = battle.mechanics();
+    // Neutralizing Gas on the Pokemon being replaced ends as it leaves
+    let outgoing = battle.get_side(player).and_then(|side| side.active(slot));
+    let without_outgoing;
+    let field = match outgoing {
+        Some(outgoing) if battle.field.abilities_suppressed() => {
+            let mut field = battle.field.clone();
+            field.end_neutralizing_gas(player, &outgoing.identity.species);
+            without_outgoing = field;
+            &without_outgoing
+        }
+        _ => &battle.field,
+    };
+    let Some(ability) = pokemon.effective_ability_on(field) else {
+        return Vec::new();
+    };
+    let id = to_id(ability);
+    let Some((_, on_switch_in)) = SWITCH_IN_ABILITIES.iter().find(|(a, _)| *a == id) else {
+        return Vec::new();
+    };
+
+    let mut effects = Vec::new();
+    match *on_switch_in {
+        OnSwitchIn::OncePerBattle(stat, stages) => {
+            if !(pokemon.switch_in_boost_used && mechanics.switch_in_boosts_once_per_battle()) {
+                effects.push(SwitchInEffect::BoostSelf { stat, stages });
+            }
+        }
+        OnSwitchIn::Terastallized(stat, stages) => {
+            if pokemon.terastallized {
+                effects.push(SwitchInEffect::BoostSelf { stat, stages });
+            }
+        }
+        OnSwitchIn::Intimidate => intimidate(battle, player, &mut effects),
+        OnSwitchIn::Download => {
+            if let Some(stat) = download(battle, player) {
+                effects.push(SwitchInEffect::BoostSelf { stat, stages: 1 });
+            }
+        }
+        OnSwitchIn::Weather(weather) => {
+            let weather = match weather {
+                Weather::Snow if !mechanics.snow_replaces_hail() => Weather::Hail,
+                weather => weather,
+            };
+            let blocked = match field.weather {
+                Some(current) => current == weather || (is_primal(current) && !is_primal(weather)),
+                None => false,
+            };
+            if !blocked {
+                effects.push(SwitchInEffect::Weather(weather));
+            }
+        }
+        OnSwitchIn::Terrain(terrain) => {
+            if field.terrain != Some(terrain) {
+                effects.push(SwitchInEffect::Terrain(terrain));
+            }
+        }
+    }
+    effects
+}
+
+fn is_primal(weather: Weather) -> bool {
+    matches!(
+        weather,
+        Weather::HarshSun | Weather::HeavyRain | Weather::StrongWinds
+    )
+}
+
+/// Living opposing actives, with their side and slot
+fn foes(battle: &TrackedBattle, player: Player) -> Vec<(Player, usize, &PokemonState)> {
+    battle
+        .sides()
+        .filter(|side| !battle.same_team(side.player, player))
+        .flat_map(|side| {
+            (0..side.active_indices.len()).filter_map(move |slot| {
+                let foe = side.active(slot).filter(|p| p.is_alive())?;
+                Some((side.player, slot, foe))
+            })
+        })
+        .collect()
+}
+
+fn intimidate(battle: &TrackedBattle, player: Player, effects: &mut Vec<SwitchInEffect>) {
+    let generation = battle.generation;
+    for (foe_player, slot, foe) in foes(battle, player) {
+        let ability = foe.effective_ability_on(&battle.field).map(to_id);
+        let ability = ability.as_deref();
+        let amulet = foe
+            .effective_item(battle.field.magic_room)
+            .is_some_and(|item| to_id(item) == "clearamulet");
+        let blocked = INTIMIDATE_BLOCKERS
+            .iter()
+            .any(|&(a, since)| ability == Some(a) && generation >= since);
+        if foe.has_volatile(&Volatile::Substitute) || amulet || blocked {
+            continue;
+        }
+        let boost = |stat, stages| SwitchInEffect::BoostFoe {
+            player: foe_player,
+            slot,
+            stat,
+            stages: scaled(ability, stages),
+        };
+        match ability {
+            Some("guarddog") => effects.push(boost(Stat::Atk, 1)),
+            Some("mirrorarmor") => effects.push(SwitchInEffect::BoostSelf {
+                stat: Stat::Atk,
+                stages: -1,
+            }),
+            Some("defiant") => effects.extend([boost(Stat::Atk, -1), boost(Stat::Atk, 2)]),
+            Some("competitive") => effects.extend([boost(Stat::Atk, -1), boost(Stat::Spa, 2)]),
+            _ => effects.push(boost(Stat::Atk, -1)),
+        }
+    }
+}
+
+/// Contrary inverts stat changes and Simple doubles them
+fn scaled(ability: Option<&str>, stages: i8) -> i8 {
+    match ability {
+        Some("contrary") => -stages,
+        Some("simple") => stages * 2,
+        _ => stages,
+    }
+}
+
+/// Special Attack if the foes' Defense totals at least their Special
+/// Defense, Attack otherwise
+fn download(battle: &TrackedBattle, player: Player) -> Option<Stat> {
+    let foes = foes(battle, player);
+    if foes.is_empty() {
+        return None;
+    }
+    let mut defense = 0;
+    let mut special_defense = 0;
+    for (_, _, foe) in foes {
+        defense += foe.effective_stat(Stat::Def)?;
+        special_defense += foe.effective_stat(Stat::Spd)?;
+    }
+    Some(if defense >= special_defense {
+        Stat::Spa
+    } else {
+        Stat::Atk
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{PokemonStats, parse_server_message};
+
+    /// Singles from p1: our Garchomp against Bob's Heatran, with a Landorus
+    /// and a Zacian on our bench
+    fn battle(generation: u8) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let log = format!(
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n|gametype|singles\n|gen|{generation}\n|start\n\
+             |switch|p1a: Garchomp|Garchomp, L50|100/100\n\
+             |switch|p2a: Heatran|Heatran, L50|100/100\n|turn|1"
+        );
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let side = battle.get_side_mut(Player::P1).unwrap();
+        for (species, ability) in [
+            ("Landorus-Therian", "Intimidate"),
+            ("Zacian", "Intrepid Sword"),
+        ] {
+            let mut pokemon = PokemonState::new(species, 50);
+            pokemon.known_ability = Some(ability.to_string());
+            side.pokemon.push(pokemon);
+        }
+        battle
+    }
+
+    fn bench<'a>(battle: &'a TrackedBattle, species: &str) -> &'a PokemonState {
+        let side = battle.get_side(Player::P1).unwrap();
+        side.get_pokemon(side.find_pokemon(species).unwrap())
+            .unwrap()
+    }
+
+    fn effects_of(battle: &TrackedBattle, species: &str) -> Vec<SwitchInEffect> {
+        switch_in_effects(battle, Player::P1, 0, bench(battle, species))
+    }
+
+    fn foe_ability(battle: &mut TrackedBattle, ability: &str) {
+        let heatran = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        heatran.known_ability = Some(ability.to_string());
+    }
+
+    fn heatran(stat: Stat, stages: i8) -> SwitchInEffect {
+        SwitchInEffect::BoostFoe {
+            player: Player::P2,
+            slot: 0,
+            stat,
+            stages,
+        }
+    }
+
+    #[test]
+    fn test_intimidate_drops_foes_unless_blocked() {
+        let mut battle = battle(9);
+        let drop = heatran(Stat::Atk, -1);
+        assert_eq!(effects_of(&battle, "Landorus-Therian"), vec![drop]);
+
+        foe_ability(&mut battle, "Clear Body");
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+        // Inner Focus only blocks it from Gen 8
+        foe_ability(&mut battle, "Inner Focus");
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+        battle.generation = 7;
+        assert_eq!(effects_of(&battle, "Landorus-Therian"), vec![drop]);
+
+        foe_ability(&mut battle, "Defiant");
+        assert_eq!(
+            effects_of(&battle, "Landorus-Therian"),
+            vec![drop, heatran(Stat::Atk, 2)]
+        );
+        foe_ability(&mut battle, "Contrary");
+        assert_eq!(
+            effects_of(&battle, "Landorus-Therian"),
+            vec![heatran(Stat::Atk, 1)]
+        );
+        foe_ability(&mut battle, "Mirror Armor");
+        assert_eq!(
+            effects_of(&battle, "Landorus-Therian"),
+            vec![SwitchInEffect::BoostSelf {
+                stat: Stat::Atk,
+                stages: -1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_drought_sets_sun_unless_already_up() {
+        let mut battle = battle(9);
+        let side = battle.get_side_mut(Player::P1).unwrap();
+        side.pokemon[1].known_ability = Some("Drought".to_string());
+        assert_eq!(
+            effects_of(&battle, "Landorus-Therian"),
+            vec![SwitchInEffect::Weather(Weather::Sun)]
+        );
+
+        battle.field.weather = Some(Weather::Sun);
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+        battle.field.weather = Some(Weather::HeavyRain);
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+        battle.field.weather = Some(Weather::Rain);
+        assert_eq!(
+            effects_of(&battle, "Landorus-Therian"),
+            vec![SwitchInEffect::Weather(Weather::Sun)]
+        );
+
+        // Neutralizing Gas on the field stops it, but not once its holder is
+        // the one switching out
+        battle
+            .field
+            .start_neutralizing_gas(Player::P2, "Weezing-Galar");
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+        battle
+            .field
+            .end_neutralizing_gas(Player::P2, "Weezing-Galar");
+        battle.field.start_neutralizing_gas(Player::P1, "Garchomp");
+        assert_eq!(effects_of(&battle, "Landorus-Therian").len(), 1);
+    }
+
+    #[test]
+    fn test_intrepid_sword_fires_once_per_battle_from_gen_9() {
+        let boost = vec![SwitchInEffect::BoostSelf {
+            stat: Stat::Atk,
+            stages: 1,
+        }];
+        for (generation, again) in [(9, vec![]), (8, boost.clone())] {
+            let mut battle = battle(generation);
+            assert_eq!(effects_of(&battle, "Zacian"), boost);
+
+            let lines = [
+                "|switch|p1a: Zacian|Zacian, L50|100/100",
+                "|-boost|p1a: Zacian|atk|1|[from] ability: Intrepid Sword",
+                "|turn|2",
+                "|switch|p1a: Garchomp|Garchomp, L50|100/100",
+                "|turn|3",
+            ];
+            for line in lines {
+                battle.apply_message(&parse_server_message(line).unwrap());
+            }
+            assert!(bench(&battle, "Zacian").switch_in_boost_used);
+            assert_eq!(effects_of(&battle, "Zacian"), again, "gen {generation}");
+        }
+    }
+
+    #[test]
+    fn test_embody_aspect_and_download() {
+        let mut battle = battle(9);
+        let side = battle.get_side_mut(Player::P1).unwrap();
+        let mut ogerpon = PokemonState::new("Ogerpon-Wellspring", 50);
+        ogerpon.known_ability = Some("Embody Aspect (Wellspring)".to_string());
+        side.pokemon.push(ogerpon);
+        side.pokemon[1].known_ability = Some("Download".to_string());
+        assert!(effects_of(&battle, "Ogerpon-Wellspring").is_empty());
+        // Download can't pick a stat until the foe's defenses are known
+        assert!(effects_of(&battle, "Landorus-Therian").is_empty());
+
+        let side = battle.get_side_mut(Player::P1).unwrap();
+        side.pokemon[3].terastallized = true;
+        let boost = |stat| vec![SwitchInEffect::BoostSelf { stat, stages: 1 }];
+        assert_eq!(effects_of(&battle, "Ogerpon-Wellspring"), boost(Stat::Spd));
+
+        let heatran = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        heatran.set_stats(&PokemonStats {
+            atk: 110,
+            def: 126,
+            spa: 150,
+            spd: 126,
+            spe: 97,
+        });
+        assert_eq!(effects_of(&battle, "Landorus-Therian"), boost(Stat::Spa));
+        heatran_boost(&mut battle, Stat::Def, -1);
+        assert_eq!(effects_of(&battle, "Landorus-Therian"), boost(Stat::Atk));
+    }
+
+    fn heatran_boost(battle: &mut TrackedBattle, stat: Stat, stages: i8) {
+        let heatran = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        heatran.boosts.boost(stat, stages);
+    }
+}
@@ -0,0 +1,360 @@
+//! Which Pokemon a targeted move can pick in doubles, and which one it hits
+//!
+//! A [`TargetingContext`] is taken once from the tracked actives and shared
+//! by choice validation ([`TargetingContext::check`]) and by
+//! [`evaluate_actions`](super::evaluate_actions), which scores a move against
+//! the Pokemon it will actually hit. Target locations follow the choice
+//! syntax: `1` and `2` are the foes' first and second slots, `-1` and `-2`
+//! our own.
+//!
+//! Every active Pokemon counts as adjacent, as in doubles. A Follow Me or
+//! Rage Powder user draws single-target moves aimed at its partner; the
+//! tracker can't tell the two apart, so Rage Powder's powder immunities are
+//! not applied.
+
+use crate::tracking::TrackedBattle;
+use crate::types::{PokemonState, SideState, Volatile, to_id};
+
+/// Request targets of moves that take a target location
+const LOCATION_TARGETS: &[&str] = &[
+    "normal",
+    "any",
+    "adjacentFoe",
+    "adjacentAlly",
+    "adjacentAllyOrSelf",
+];
+
+/// Abilities whose moves ignore redirection
+const IGNORES_REDIRECTION: &[&str] = &["propellertail", "stalwart"];
+
+/// How strictly [`TargetingContext::check`] judges a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TargetStrictness {
+    /// Reject only targets the move can't reach at all: empty or fainted
+    /// slots and a Commanding Tatsugiri
+    #[default]
+    ServerLegal,
+
+    /// Also reject targets in the middle of Fly, Dig and the like, which the
+    /// server accepts but most moves then miss
+    BotSensible,
+}
+
+/// Why a target location can't be chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetIssue {
+    /// Not a slot the move's target type allows, or the user itself
+    OutOfReach,
+
+    /// Nobody is there, or the Pokemon there fainted
+    EmptySlot,
+
+    /// A Tatsugiri inside its Dondozo ally
+    Commanding,
+
+    /// Off the field mid-move (only under [`TargetStrictness::BotSensible`])
+    SemiInvulnerable,
+}
+
+/// Outcome of aiming a move at a target location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetCheck {
+    /// The move takes no target location (spread, self and field moves)
+    Untargeted,
+
+    /// The move hits the chosen target
+    Hits(i8),
+
+    /// A Follow Me or Rage Powder user at `location` draws the move instead
+    Redirected { location: i8, by: String },
+
+    /// The target can't be chosen
+    Rejected(TargetIssue),
+}
+
+impl TargetCheck {
+    /// Location the move ends up hitting, if it takes one
+    pub fn location(&self) -> Option<i8> {
+        match self {
+            Self::Hits(location) | Self::Redirected { location, .. } => Some(*location),
+            Self::Untargeted | Self::Rejected(_) => None,
+        }
+    }
+}
+
+/// What targeting cares about in one active slot
+#[derive(Debug, Clone, PartialEq)]
+struct SlotInfo {
+    name: String,
+    ability: Option<String>,
+    commanding: bool,
+    semi_invulnerable: bool,
+    center_of_attention: bool,
+}
+
+impl SlotInfo {
+    fn from_state(poke: &PokemonState) -> Option<Self> {
+        (!poke.fainted).then(|| Self {
+            name: poke.name().to_string(),
+            ability: poke.current_ability().map(to_id),
+            commanding: poke.has_volatile(&Volatile::Commanding),
+            semi_invulnerable: poke.volatiles.iter().any(Volatile::is_semi_invulnerable)
+                || poke
+                    .charging_move
+                    .as_ref()
+                    .is_some_and(|c| Volatile::from_protocol(&c.move_name).is_semi_invulnerable()),
+            center_of_attention: poke.has_volatile(&Volatile::CenterOfAttention),
+        })
+    }
+}
+
+/// Targetable state of every active slot, from our viewpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetingContext {
+    ours: Vec<Option<SlotInfo>>,
+    foes: Vec<Option<SlotInfo>>,
+    strictness: TargetStrictness,
+}
+
+impl TargetingContext {
+    /// Take the actives of our side and the opponent's
+    pub fn new(battle: &TrackedBattle, strictness: TargetStrictness) -> Self {
+        Self {
+            ours: slots(battle.me()),
+            foes: slots(battle.opponent()),
+            strictness,
+        }
+    }
+
+    /// Check aiming the move in `user_slot` with request target `move_target`
+    /// at `location`
+    pub fn check(
+        &self,
+        user_slot: usize,
+        move_id: &str,
+        move_target: &str,
+        location: i8,
+    ) -> TargetCheck {
+        if !LOCATION_TARGETS.contains(&move_target) {
+            return TargetCheck::Untargeted;
+        }
+        let own_location = -(user_slot as i8) - 1;
+        let reachable = match move_target {
+            "adjacentFoe" => location > 0,
+            "adjacentAlly" => location < 0 && location != own_location,
+            "adjacentAllyOrSelf" => location < 0,
+            _ => location != 0 && location != own_location,
+        };
+        if !reachable {
+            return TargetCheck::Rejected(TargetIssue::OutOfReach);
+        }
+        let Some(target) = self.at(location) else {
+            return TargetCheck::Rejected(TargetIssue::EmptySlot);
+        };
+        if target.commanding {
+            return TargetCheck::Rejected(TargetIssue::Commanding);
+        }
+        if target.semi_invulnerable && self.strictness == TargetStrictness::BotSensible {
+            return TargetCheck::Rejected(TargetIssue::SemiInvulnerable);
+        }
+        match self.redirector(user_slot, move_id, move_target, location) {
+            Some((redirect, by)) => TargetCheck::Redirected {
+                location: redirect,
+                by: by.to_string(),
+            },
+            None => TargetCheck::Hits(location),
+        }
+    }
+
+    /// Where the move in `user_slot` lands when aimed at the first foe that
+    /// can be chosen; None for moves without a target location or with no foe
+    /// to aim at
+    pub fn default_hit(&self, user_slot: usize, move_id: &str, move_target: &str) -> Option<i8> {
+        (1..=self.foes.len() as i8)
+            .map(|location| self.check(user_slot, move_id, move_target, location))
+            .find_map(|check| check.location())
+    }
+
+    /// Name of the Pokemon at `location`, if one can be there
+    pub fn name_at(&self, location: i8) -> Option<&str> {
+        self.at(location).map(|slot| slot.name.as_str())
+    }
+
+    fn at(&self, location: i8) -> Option<&SlotInfo> {
+        let (slots, index) = match location {
+            1.. => (&self.foes, location - 1),
+            ..0 => (&self.ours, -location - 1),
+            0 => return None,
+        };
+        slots.get(index as usize)?.as_ref()
+    }
+
+    /// A foe drawing single-target moves aimed at its partner
+    fn redirector(
+        &self,
+        user_slot: usize,
+        move_id: &str,
+        move_target: &str,
+        location: i8,
+    ) -> Option<(i8, &str)> {
+        if location < 0 || !matches!(move_target, "normal" | "any" | "adjacentFoe") {
+            return None;
+        }
+        let user = self.ours.get(user_slot).and_then(Option::as_ref);
+        let ignores = to_id(move_id) == "snipeshot"
+            || user
+                .and_then(|u| u.ability.as_deref())
+                .is_some_and(|a| IGNORES_REDIRECTION.contains(&a));
+        if ignores {
+            return None;
+        }
+        self.foes.iter().enumerate().find_map(|(index, slot)| {
+            let redirect = index as i8 + 1;
+            slot.as_ref()
+                .filter(|s| redirect != location && s.center_of_attention && !s.commanding)
+                .map(|s| (redirect, s.name.as_str()))
+        })
+    }
+}
+
+fn slots(side: Option<&SideState>) -> Vec<Option<SlotInfo>> {
+    side.map(|side| {
+        (0..side.active_indices.len())
+            .map(|slot| side.active(slot).and_then(SlotInfo::from_state))
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    fn doubles(lines: &[&str]) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        for line in [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|gametype|doubles",
+            "|switch|p1a: Kingambit|Kingambit, L50, M|100/100",
+            "|switch|p1b: Flutter Mane|Flutter Mane, L50|100/100",
+            "|switch|p2a: Amoonguss|Amoonguss, L50, F|100/100",
+            "|switch|p2b: Chi-Yu|Chi-Yu, L50|100/100",
+            "|turn|1",
+        ]
+        .iter()
+        .chain(lines)
+        {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    #[test]
+    fn test_rage_powder_redirects_single_target_moves() {
+        let battle = doubles(&[
+            "|move|p2a: Amoonguss|Rage Powder|p2a: Amoonguss",
+            "|-singleturn|p2a: Amoonguss|move: Rage Powder",
+        ]);
+        let context = TargetingContext::new(&battle, TargetStrictness::ServerLegal);
+
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 2),
+            TargetCheck::Redirected {
+                location: 1,
+                by: "Amoonguss".to_string(),
+            }
+        );
+        // Aimed at Amoonguss itself, or not single-target, nothing changes
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 1),
+            TargetCheck::Hits(1)
+        );
+        assert_eq!(
+            context.check(1, "dazzlinggleam", "allAdjacentFoes", 2),
+            TargetCheck::Untargeted
+        );
+        assert_eq!(
+            context.check(0, "snipeshot", "normal", 2),
+            TargetCheck::Hits(2)
+        );
+        // Moves on our own partner aren't drawn away
+        assert_eq!(
+            context.check(0, "pollenpuff", "normal", -2),
+            TargetCheck::Hits(-2)
+        );
+        assert_eq!(context.default_hit(1, "moonblast", "normal"), Some(1));
+    }
+
+    #[test]
+    fn test_empty_and_fainted_slots_are_rejected() {
+        let battle = doubles(&["|faint|p2b: Chi-Yu"]);
+        let context = TargetingContext::new(&battle, TargetStrictness::ServerLegal);
+
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 2),
+            TargetCheck::Rejected(TargetIssue::EmptySlot)
+        );
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 3),
+            TargetCheck::Rejected(TargetIssue::EmptySlot)
+        );
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", -1),
+            TargetCheck::Rejected(TargetIssue::OutOfReach)
+        );
+        assert_eq!(
+            context.check(0, "kowtowcleave", "adjacentFoe", -2),
+            TargetCheck::Rejected(TargetIssue::OutOfReach)
+        );
+        assert_eq!(context.default_hit(0, "kowtowcleave", "normal"), Some(1));
+    }
+
+    #[test]
+    fn test_commanding_tatsugiri_is_untargetable() {
+        let mut battle = doubles(&[
+            "|switch|p2a: Tatsugiri|Tatsugiri, L50|100/100",
+            "|switch|p2b: Dondozo|Dondozo, L50|100/100",
+            "|-activate|p2a: Tatsugiri|ability: Commander|[of] p2b: Dondozo",
+        ]);
+        let context = TargetingContext::new(&battle, TargetStrictness::ServerLegal);
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 1),
+            TargetCheck::Rejected(TargetIssue::Commanding)
+        );
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 2),
+            TargetCheck::Hits(2)
+        );
+        assert_eq!(context.default_hit(0, "kowtowcleave", "normal"), Some(2));
+
+        // Tatsugiri comes back out once Dondozo faints
+        battle.apply_message(&parse_server_message("|faint|p2b: Dondozo").unwrap());
+        let context = TargetingContext::new(&battle, TargetStrictness::ServerLegal);
+        assert_eq!(
+            context.check(0, "kowtowcleave", "normal", 1),
+            TargetCheck::Hits(1)
+        );
+    }
+
+    #[test]
+    fn test_semi_invulnerable_only_rejected_when_strict() {
+        let battle = doubles(&[
+            "|move|p2b: Chi-Yu|Dig||[still]",
+            "|-prepare|p2b: Chi-Yu|Dig",
+        ]);
+        let legal = TargetingContext::new(&battle, TargetStrictness::ServerLegal);
+        assert_eq!(
+            legal.check(0, "kowtowcleave", "normal", 2),
+            TargetCheck::Hits(2)
+        );
+
+        let strict = TargetingContext::new(&battle, TargetStrictness::BotSensible);
+        assert_eq!(
+            strict.check(0, "kowtowcleave", "normal", 2),
+            TargetCheck::Rejected(TargetIssue::SemiInvulnerable)
+        );
+        assert_eq!(strict.default_hit(0, "kowtowcleave", "normal"), Some(1));
+    }
+}
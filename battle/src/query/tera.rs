@@ -0,0 +1,270 @@
+//! Ranking the tera types an opposing Pokemon could still pick
+//!
+//! Each candidate is scored by typing alone against our active Pokemon:
+//!
+//! - defensive: how much the tera typing lowers the worst multiplier they take
+//!   from our known attacks (our active's STAB types when none of its moves
+//!   have a curated type, see [`move_type`])
+//! - offensive: how much the tera STAB (2x on a type they already had, 1.5x on
+//!   a new one, Tera Blast taking the tera type) raises their best known attack
+//!   against us; their current STAB types stand in for unrevealed moves
+//!
+//! STAB types only stand in while a moveset could still hold more; one listed
+//! in full (see [`PokemonState::moveset_complete`]) is taken as it is.
+//!
+//! There is no set data in this crate, so [`threat_ranking`] weighs every type
+//! equally; callers with a list of the set's tera types pass it with weights
+//! to [`threat_ranking_among`].
+
+use std::fmt;
+
+use super::matchup::DefensiveProfile;
+use super::moves::move_type;
+use crate::types::{PokemonState, TeraType, Type, to_id};
+
+/// Why one tera type ranks where it does
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeraThreatScore {
+    /// Drop in the worst multiplier they take from our attacks
+    pub defensive: f32,
+
+    /// Rise in their best attack's STAB times effectiveness against us
+    pub offensive: f32,
+
+    /// Their best attack after terastallizing, None for a STAB type standing
+    /// in for unrevealed moves
+    pub best_move: Option<String>,
+
+    /// Likelihood of the candidate, 1.0 without set data
+    pub weight: f32,
+
+    /// `weight * (defensive + offensive)`, the ranking key
+    pub total: f32,
+}
+
+impl fmt::Display for TeraThreatScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2} (defense {:+.2}, offense {:+.2}",
+            self.total, self.defensive, self.offensive
+        )?;
+        if let Some(best_move) = &self.best_move {
+            write!(f, " with {best_move}")?;
+        }
+        if self.weight != 1.0 {
+            write!(f, ", weight {:.2}", self.weight)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Every tera type the opposing Pokemon could still pick, most threatening first
+///
+/// Empty once it has terastallized; only the revealed type when its tera type
+/// is known (open team sheets).
+pub fn threat_ranking(
+    opposing: &PokemonState,
+    my_actives: &[&PokemonState],
+) -> Vec<(Type, TeraThreatScore)> {
+    let candidates: Vec<(Type, f32)> = match opposing.tera_type {
+        Some(TeraType::Type(known)) => vec![(known, 1.0)],
+        _ => Type::ALL.iter().map(|&t| (t, 1.0)).collect(),
+    };
+    threat_ranking_among(opposing, my_actives, &candidates)
+}
+
+/// [`threat_ranking`] over weighted candidates, such as a set's listed tera types
+pub fn threat_ranking_among(
+    opposing: &PokemonState,
+    my_actives: &[&PokemonState],
+    candidates: &[(Type, f32)],
+) -> Vec<(Type, TeraThreatScore)> {
+    if opposing.terastallized {
+        return Vec::new();
+    }
+    let our_attacks: Vec<Type> = my_actives
+        .iter()
+        .flat_map(|&mine| attack_types(mine))
+        .collect();
+    let worst_taken =
+        |pokemon: &PokemonState| DefensiveProfile::of_pokemon(pokemon).best_of(&our_attacks);
+    let current_taken = worst_taken(opposing);
+    let (current_dealt, _) = best_attack(opposing, None, my_actives);
+
+    let mut ranking: Vec<(Type, TeraThreatScore)> = candidates
+        .iter()
+        .map(|&(tera, weight)| {
+            let mut terastallized = opposing.clone();
+            terastallized.terastallize(TeraType::Type(tera));
+            let defensive = current_taken - worst_taken(&terastallized);
+            let (dealt, best_move) = best_attack(opposing, Some(tera), my_actives);
+            let offensive = dealt - current_dealt;
+            let score = TeraThreatScore {
+                defensive,
+                offensive,
+                best_move,
+                weight,
+                total: weight * (defensive + offensive),
+            };
+            (tera, score)
+        })
+        .collect();
+    ranking.sort_by(|(_, a), (_, b)| b.total.total_cmp(&a.total));
+    ranking
+}
+
+/// Types a Pokemon is known to attack with: its typed known moves, else its
+/// STABs unless its moveset is complete
+fn attack_types(pokemon: &PokemonState) -> Vec<Type> {
+    let known: Vec<Type> = pokemon
+        .known_moves
+        .iter()
+        .filter_map(|m| move_type(m))
+        .collect();
+    if known.is_empty() && !pokemon.moveset_complete() {
+        pokemon.get_types().to_vec()
+    } else {
+        known
+    }
+}
+
+/// Best STAB times effectiveness against any of our actives, and the move
+///
+/// `tera` adds the tera STAB; without it, only the current types count.
+fn best_attack(
+    attacker: &PokemonState,
+    tera: Option<Type>,
+    defenders: &[&PokemonState],
+) -> (f32, Option<String>) {
+    let stab = |attacking: Type| {
+        let original = attacker.current_types.contains(&attacking);
+        match (tera == Some(attacking), original) {
+            (true, true) => 2.0,
+            (true, false) | (false, true) => 1.5,
+            (false, false) => 1.0,
+        }
+    };
+    let effectiveness = |attacking: Type| {
+        defenders
+            .iter()
+            .map(|&defender| DefensiveProfile::of_pokemon(defender).against(attacking))
+            .reduce(f32::max)
+            .unwrap_or(1.0)
+    };
+
+    let mut moves: Vec<(Type, Option<String>)> = attacker
+        .known_moves
+        .iter()
+        .filter_map(|name| {
+            let attacking = match (to_id(name).as_str(), tera) {
+                ("terablast", Some(tera)) => tera,
+                _ => move_type(name)?,
+            };
+            Some((attacking, Some(name.clone())))
+        })
+        .collect();
+    if moves.is_empty() && !attacker.moveset_complete() {
+        moves = attacker.current_types.iter().map(|&t| (t, None)).collect();
+    }
+    moves
+        .into_iter()
+        .map(|(attacking, name)| (stab(attacking) * effectiveness(attacking), name))
+        .fold((0.0, None), |best, candidate| {
+            if candidate.0 > best.0 {
+                candidate
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pokemon(species: &str, types: Vec<Type>, moves: &[&str]) -> PokemonState {
+        let mut poke = PokemonState::new(species, 50);
+        poke.set_types(types);
+        for name in moves {
+            poke.record_move(name);
+        }
+        poke
+    }
+
+    #[test]
+    fn test_tera_flying_ranks_first() {
+        // Our Breloom only threatens Grass and Ground; their Fighting type has
+        // Brave Bird as coverage
+        let breloom = pokemon(
+            "Breloom",
+            vec![Type::Grass, Type::Fighting],
+            &["Seed Bomb", "Earthquake"],
+        );
+        let hawlucha = pokemon(
+            "Hawlucha",
+            vec![Type::Fighting],
+            &["Brave Bird", "Close Combat"],
+        );
+
+        let ranking = threat_ranking(&hawlucha, &[&breloom]);
+        assert_eq!(ranking.len(), 18);
+        let (first, score) = &ranking[0];
+        assert_eq!(*first, Type::Flying);
+        // Immune to Ground and resisting Grass, and Brave Bird gains STAB on 4x
+        assert_eq!(score.defensive, 0.5);
+        assert_eq!(score.offensive, 2.0);
+        assert_eq!(score.best_move.as_deref(), Some("Brave Bird"));
+        assert_eq!(
+            score.to_string(),
+            "2.50 (defense +0.50, offense +2.00 with Brave Bird)"
+        );
+
+        // Tera Grass resists both attacks but gains nothing offensively
+        let grass = ranking.iter().find(|(t, _)| *t == Type::Grass).unwrap();
+        assert_eq!((grass.1.defensive, grass.1.offensive), (0.5, 0.0));
+        // Tera Fire is weak to Earthquake
+        let fire = ranking.iter().find(|(t, _)| *t == Type::Fire).unwrap();
+        assert_eq!(fire.1.defensive, -1.0);
+        assert!(ranking.windows(2).all(|w| w[0].1.total >= w[1].1.total));
+    }
+
+    #[test]
+    fn test_known_or_used_tera_and_weights() {
+        let breloom = pokemon("Breloom", vec![Type::Grass, Type::Fighting], &["Seed Bomb"]);
+        let mut hawlucha = pokemon("Hawlucha", vec![Type::Fighting], &["Tera Blast"]);
+
+        // Weights from set data can outrank a better typing
+        let ranking = threat_ranking_among(
+            &hawlucha,
+            &[&breloom],
+            &[(Type::Flying, 0.1), (Type::Fire, 0.9)],
+        );
+        assert_eq!(ranking[0].0, Type::Fire);
+        assert_eq!(ranking[0].1.best_move.as_deref(), Some("Tera Blast"));
+
+        hawlucha.tera_type = Some(TeraType::Type(Type::Flying));
+        let ranking = threat_ranking(&hawlucha, &[&breloom]);
+        assert_eq!(ranking.len(), 1);
+        assert_eq!(ranking[0].0, Type::Flying);
+
+        hawlucha.terastallize(TeraType::Type(Type::Flying));
+        assert!(threat_ranking(&hawlucha, &[&breloom]).is_empty());
+    }
+
+    #[test]
+    fn test_complete_moveset_drops_stab_stand_in() {
+        let hawlucha = pokemon("Hawlucha", vec![Type::Fighting], &["Close Combat"]);
+        let mut chansey = pokemon("Chansey", vec![Type::Normal], &[]);
+
+        // With nothing revealed its Normal STAB stands in
+        let ranking = threat_ranking(&hawlucha, &[&chansey]);
+        let ghost = ranking.iter().find(|(t, _)| *t == Type::Ghost).unwrap();
+        assert_eq!(ghost.1.defensive, 1.0);
+
+        // A listed set of status moves threatens nothing
+        chansey.set_request_moves(&["softboiled".to_string(), "toxic".to_string()]);
+        let ranking = threat_ranking(&hawlucha, &[&chansey]);
+        assert!(ranking.iter().all(|(_, score)| score.defensive == 0.0));
+    }
+}
@@ -0,0 +1,391 @@
+//! Whether the active Pokemon on either side can switch out
+//!
+//! Evaluated for singles from the first active slot of each side. The checks
+//! follow Showdown:
+//!
+//! 1. A known Shed Shell (outside Magic Room) or Ghost typing (Gen 6+) lets
+//!    the Pokemon switch no matter what traps it.
+//! 2. Its own volatiles trap it: partial traps (Wrap, Fire Spin, ...), Mean
+//!    Look / Block / Spider Web, Octolock, No Retreat and Ingrain, as does
+//!    Fairy Lock on the field.
+//! 3. The opposing active's ability traps it: Shadow Tag (unless it has Shadow
+//!    Tag too), Arena Trap if grounded (see [`is_grounded`]) and Magnet Pull
+//!    if Steel.
+//!
+//! An unrevealed item is assumed not to be Shed Shell. Missing types, an
+//! unrevealed trapper ability or unknown grounding make the verdict
+//! [`TrapVerdict::Unknown`] when they could change the outcome.
+
+use super::grounding::{Grounded, GroundingUnknown, is_grounded};
+use crate::tracking::TrackedBattle;
+use crate::types::{FieldState, Mechanics, PokemonState, Type, Volatile, to_id};
+
+/// What decided a [`TrapVerdict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapFactor {
+    /// Opposing Shadow Tag
+    ShadowTag,
+
+    /// Opposing Arena Trap against a grounded Pokemon
+    ArenaTrap,
+
+    /// Opposing Magnet Pull against a Steel type
+    MagnetPull,
+
+    /// Partial trapping move (Wrap, Fire Spin, Whirlpool, ...)
+    PartialTrap,
+
+    /// Mean Look, Block or Spider Web
+    MeanLook,
+
+    Octolock,
+    NoRetreat,
+    Ingrain,
+    FairyLock,
+
+    /// Ghost types escape all trapping (Gen 6+)
+    GhostType,
+
+    /// Held Shed Shell
+    ShedShell,
+
+    /// Nothing tracked traps it
+    NoTrap,
+
+    /// Trapped / maybeTrapped flags of our latest request
+    Request,
+
+    /// Types are not known, so it could be a Ghost (or a Steel for Magnet Pull)
+    Types,
+
+    /// The opposing active's ability is not revealed
+    Ability,
+
+    /// Arena Trap applies only if grounded, which is unknown
+    Grounding(GroundingUnknown),
+
+    /// A side has no active Pokemon
+    NoActive,
+}
+
+/// Result of [`opponent_trapped`] and [`my_active_trapped`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapVerdict {
+    Trapped(TrapFactor),
+    Free(TrapFactor),
+    Unknown(TrapFactor),
+}
+
+impl TrapVerdict {
+    /// Definite answer, None if unknown
+    pub fn known(self) -> Option<bool> {
+        match self {
+            TrapVerdict::Trapped(_) => Some(true),
+            TrapVerdict::Free(_) => Some(false),
+            TrapVerdict::Unknown(_) => None,
+        }
+    }
+
+    /// Factor that decided the verdict
+    pub fn factor(self) -> TrapFactor {
+        match self {
+            TrapVerdict::Trapped(f) | TrapVerdict::Free(f) | TrapVerdict::Unknown(f) => f,
+        }
+    }
+}
+
+/// Whether the opposing active is trapped by our active or its own volatiles
+pub fn opponent_trapped(battle: &TrackedBattle) -> TrapVerdict {
+    let Some(target) = battle.opponent().and_then(|side| side.active_pokemon()) else {
+        return TrapVerdict::Unknown(TrapFactor::NoActive);
+    };
+    let trapper = battle.me().and_then(|side| side.active_pokemon());
+    trap_verdict(target, trapper, &battle.field, battle.generation)
+}
+
+/// Whether our active is trapped
+///
+/// The trapped / maybeTrapped flags of the latest request decide when it has
+/// an active entry; otherwise this falls back to the tracked state.
+pub fn my_active_trapped(battle: &TrackedBattle) -> TrapVerdict {
+    let flags = battle
+        .last_request()
+        .and_then(|request| request.active.as_ref())
+        .and_then(|active| active.first());
+    if let Some(active) = flags {
+        return if active.trapped {
+            TrapVerdict::Trapped(TrapFactor::Request)
+        } else if active.maybe_trapped {
+            TrapVerdict::Unknown(TrapFactor::Request)
+        } else {
+            TrapVerdict::Free(TrapFactor::Request)
+        };
+    }
+
+    let Some(target) = battle.me().and_then(|side| side.active_pokemon()) else {
+        return TrapVerdict::Unknown(TrapFactor::NoActive);
+    };
+    let trapper = battle.opponent().and_then(|side| side.active_pokemon());
+    trap_verdict(target, trapper, &battle.field, battle.generation)
+}
+
+/// Whether `target` is trapped, with `trapper` the opposing active
+fn trap_verdict(
+    target: &PokemonState,
+    trapper: Option<&PokemonState>,
+    field: &FieldState,
+    generation: u8,
+) -> TrapVerdict {
+    let item = target
+        .known_item
+        .as_deref()
+        .filter(|_| !target.item_consumed);
+    if !field.magic_room && item.map(to_id).as_deref() == Some("shedshell") {
+        return TrapVerdict::Free(TrapFactor::ShedShell);
+    }
+    let types = target.get_types();
+    let ghost_escapes = Mechanics::for_generation(generation).ghosts_escape_traps();
+    if ghost_escapes && types.contains(&Type::Ghost) {
+        return TrapVerdict::Free(TrapFactor::GhostType);
+    }
+
+    match find_trap(target, trapper, field) {
+        Ok(Some(_)) if ghost_escapes && types.is_empty() => TrapVerdict::Unknown(TrapFactor::Types),
+        Ok(Some(factor)) => TrapVerdict::Trapped(factor),
+        Ok(None) => TrapVerdict::Free(TrapFactor::NoTrap),
+        Err(missing) => TrapVerdict::Unknown(missing),
+    }
+}
+
+/// First trap that applies, or the missing information if one might
+fn find_trap(
+    target: &PokemonState,
+    trapper: Option<&PokemonState>,
+    field: &FieldState,
+) -> Result<Option<TrapFactor>, TrapFactor> {
+    let volatiles = [
+        (Volatile::PartialTrap, TrapFactor::PartialTrap),
+        (Volatile::Trapped, TrapFactor::MeanLook),
+        (Volatile::Octolock, TrapFactor::Octolock),
+        (Volatile::NoRetreat, TrapFactor::NoRetreat),
+        (Volatile::Ingrain, TrapFactor::Ingrain),
+    ];
+    if let Some((_, factor)) = volatiles.iter().find(|(v, _)| target.has_volatile(v)) {
+        return Ok(Some(*factor));
+    }
+    if field.fairy_lock {
+        return Ok(Some(TrapFactor::FairyLock));
+    }
+
+    let Some(trapper) = trapper else {
+        return Ok(None);
+    };
+    if trapper.has_volatile(&Volatile::GastroAcid) {
+        return Ok(None);
+    }
+    let Some(ability) = trapper.current_ability().map(to_id) else {
+        return Err(TrapFactor::Ability);
+    };
+    match ability.as_str() {
+        "shadowtag" => {
+            let target_ability = target.current_ability().map(to_id);
+            if target_ability.as_deref() == Some("shadowtag") {
+                Ok(None)
+            } else {
+                Ok(Some(TrapFactor::ShadowTag))
+            }
+        }
+        "arenatrap" => match is_grounded(target, field) {
+            Grounded::Yes => Ok(Some(TrapFactor::ArenaTrap)),
+            Grounded::No => Ok(None),
+            Grounded::Unknown(missing) => Err(TrapFactor::Grounding(missing)),
+        },
+        "magnetpull" => {
+            let types = target.get_types();
+            if types.contains(&Type::Steel) {
+                Ok(Some(TrapFactor::MagnetPull))
+            } else if types.is_empty() {
+                Err(TrapFactor::Types)
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{BattleRequest, Player, parse_server_message};
+
+    /// Singles battle seen from p1 with both actives' types, abilities and items known
+    fn battle(mine: (&[Type], &str), theirs: (&[Type], &str, &str)) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Trapper|Dugtrio, L50|100/100
+|switch|p2a: Target|Skarmory, L50|100/100
+|turn|1"#;
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let me = battle
+            .get_side_mut(Player::P1)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        me.set_types(mine.0.to_vec());
+        me.known_ability = Some(mine.1.to_string());
+        let them = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        them.set_types(theirs.0.to_vec());
+        them.known_ability = Some(theirs.1.to_string());
+        them.known_item = Some(theirs.2.to_string());
+        battle
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_ability_typing_matrix() {
+        use TrapFactor::*;
+        use TrapVerdict::{Free, Trapped};
+
+        let ground = &[Type::Ground][..];
+        let cases = [
+            ("shadow tag", "Shadow Tag", &[Type::Water][..], "Torrent", "Leftovers", Trapped(ShadowTag)),
+            ("shadow tag vs shadow tag", "Shadow Tag", &[Type::Psychic][..], "Shadow Tag", "Leftovers", Free(NoTrap)),
+            ("shadow tag vs ghost", "Shadow Tag", &[Type::Ghost][..], "Cursed Body", "Leftovers", Free(GhostType)),
+            ("shadow tag vs shed shell", "Shadow Tag", &[Type::Water][..], "Torrent", "Shed Shell", Free(ShedShell)),
+            ("arena trap grounded", "Arena Trap", &[Type::Water][..], "Torrent", "Leftovers", Trapped(ArenaTrap)),
+            ("arena trap vs flying", "Arena Trap", &[Type::Steel, Type::Flying][..], "Sturdy", "Leftovers", Free(NoTrap)),
+            ("arena trap vs levitate", "Arena Trap", &[Type::Psychic][..], "Levitate", "Leftovers", Free(NoTrap)),
+            ("arena trap vs air balloon", "Arena Trap", &[Type::Fire][..], "Blaze", "Air Balloon", Free(NoTrap)),
+            ("magnet pull vs steel", "Magnet Pull", &[Type::Steel, Type::Flying][..], "Sturdy", "Leftovers", Trapped(MagnetPull)),
+            ("magnet pull vs non-steel", "Magnet Pull", &[Type::Water][..], "Torrent", "Leftovers", Free(NoTrap)),
+            ("magnet pull vs steel ghost", "Magnet Pull", &[Type::Steel, Type::Ghost][..], "Stance Change", "Leftovers", Free(GhostType)),
+            ("no trapping ability", "Sand Veil", &[Type::Water][..], "Torrent", "Leftovers", Free(NoTrap)),
+        ];
+
+        for (name, ability, types, their_ability, item, expected) in cases {
+            let battle = battle((ground, ability), (types, their_ability, item));
+            assert_eq!(opponent_trapped(&battle), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_arena_trap_grounds_flying_under_gravity() {
+        let mut battle = battle(
+            (&[Type::Ground], "Arena Trap"),
+            (&[Type::Steel, Type::Flying], "Sturdy", "Leftovers"),
+        );
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Free(TrapFactor::NoTrap)
+        );
+
+        battle.field.gravity = true;
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Trapped(TrapFactor::ArenaTrap)
+        );
+    }
+
+    #[test]
+    fn test_volatiles_and_fairy_lock() {
+        let mut battle = battle(
+            (&[Type::Normal], "Run Away"),
+            (&[Type::Water], "Torrent", "Leftovers"),
+        );
+        let them = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        them.add_volatile(Volatile::PartialTrap);
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Trapped(TrapFactor::PartialTrap)
+        );
+
+        let them = battle
+            .get_side_mut(Player::P2)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        them.remove_volatile(&Volatile::PartialTrap);
+        battle.field.fairy_lock = true;
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Trapped(TrapFactor::FairyLock)
+        );
+    }
+
+    #[test]
+    fn test_unknowns() {
+        let mut battle = battle(
+            (&[Type::Ground], "Arena Trap"),
+            (&[], "Torrent", "Leftovers"),
+        );
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Unknown(TrapFactor::Grounding(GroundingUnknown::Types))
+        );
+
+        let me = battle
+            .get_side_mut(Player::P1)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        me.known_ability = Some("Shadow Tag".to_string());
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Unknown(TrapFactor::Types)
+        );
+
+        let me = battle
+            .get_side_mut(Player::P1)
+            .unwrap()
+            .active_pokemon_mut()
+            .unwrap();
+        me.known_ability = None;
+        assert_eq!(
+            opponent_trapped(&battle),
+            TrapVerdict::Unknown(TrapFactor::Ability)
+        );
+    }
+
+    #[test]
+    fn test_my_active_prefers_request_flags() {
+        let mut battle = battle(
+            (&[Type::Water], "Torrent"),
+            (&[Type::Ground], "Arena Trap", "Leftovers"),
+        );
+        assert_eq!(
+            my_active_trapped(&battle),
+            TrapVerdict::Trapped(TrapFactor::ArenaTrap)
+        );
+
+        let request: BattleRequest =
+            serde_json::from_str(r#"{"active":[{"moves":[],"maybeTrapped":true}],"rqid":3}"#)
+                .unwrap();
+        battle.apply_request(&request);
+        assert_eq!(
+            my_active_trapped(&battle),
+            TrapVerdict::Unknown(TrapFactor::Request)
+        );
+
+        let request: BattleRequest =
+            serde_json::from_str(r#"{"active":[{"moves":[]}],"rqid":4}"#).unwrap();
+        battle.apply_request(&request);
+        assert_eq!(
+            my_active_trapped(&battle),
+            TrapVerdict::Free(TrapFactor::Request)
+        );
+    }
+}
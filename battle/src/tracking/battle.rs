@@ -1,11 +1,25 @@
 //! TrackedBattle - canonical battle state reduced from protocol messages
 
-use kazam_protocol::{GameType, Player};
-
-use crate::types::{FieldState, SideState};
+use std::collections::HashMap;
+use std::fmt;
+
+use kazam_protocol::{BattleRequest, GameType, MoveSlot, Player, Pokemon, Stat, user_id};
+
+use super::config::TrackerConfig;
+use super::reconcile::Reconciliation;
+use super::roster::PokemonRef;
+use super::scouting::ScoutingReport;
+use super::staleness::StalenessState;
+use super::variance::VarianceReport;
+use crate::types::{
+    ChargingMove, FieldState, FieldStatModifier, KnowledgeEntry, KnownMove, Mechanics,
+    MoveProvenance, Observation, PokemonState, SideCondition, SideConditionState, SideState,
+    StatBound, StatConstraint, Type, Volatile, VolatileState,
+};
 
 /// How much private information has been merged into this battle state.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BattleKnowledge {
     /// Only public battle log information has been applied.
     #[default]
@@ -16,6 +30,258 @@ pub enum BattleKnowledge {
     Omniscient,
 }
 
+/// A combined move (e.g. two pledges) announced by |-waiting| and |-combine|
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CombinedMove {
+    /// Turn the combination happened on
+    pub turn: u32,
+
+    /// Pokemon that waited for its ally (the |-waiting| source)
+    pub waiting: Pokemon,
+
+    /// Ally whose move carried the combined effect (the |-waiting| target)
+    pub partner: Pokemon,
+
+    /// Move used by the partner
+    pub move_name: String,
+}
+
+/// Side conditions cleared by one move or ability, e.g. a Defog or Screen Cleaner
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConditionRemoval {
+    /// Turn the removal happened on
+    pub turn: u32,
+
+    /// Move or ability that cleared the conditions ("Defog", "Screen Cleaner")
+    pub cause: String,
+
+    /// Pokemon that used it, if known
+    pub user: Option<Pokemon>,
+
+    /// Each condition removed, with the side it was removed from
+    pub removed: Vec<(Player, SideCondition)>,
+}
+
+/// Two foes' moves in the order they were used within a turn
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveOrder {
+    /// Turn the moves were used on
+    pub turn: u32,
+
+    /// Pokemon that moved first, and its move
+    pub first: Pokemon,
+    pub first_move: String,
+
+    /// Speed stage of the first mover when it moved
+    pub first_speed_stage: i8,
+
+    /// Pokemon that moved after it, and its move
+    pub second: Pokemon,
+    pub second_move: String,
+
+    /// Speed stage of the second mover when it moved
+    pub second_speed_stage: i8,
+
+    /// Whether Trick Room was up, reversing the Speed order
+    pub trick_room: bool,
+}
+
+/// Something that let a Pokemon act ahead of its Speed order for one turn
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderException {
+    /// Turn it applied to
+    pub turn: u32,
+
+    /// Pokemon that moved early
+    pub pokemon: Pokemon,
+
+    /// Item or ability responsible ("Custap Berry", "Quick Claw", "Quick Draw")
+    pub cause: String,
+}
+
+/// Why a Pokemon came onto the field
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SwitchKind {
+    /// The player switched, by a switch action or a pivot move like U-turn
+    Chosen,
+
+    /// Filled the slot of a fainted Pokemon
+    Replacement,
+
+    /// Dragged in by Roar, Whirlwind, Dragon Tail, Circle Throw or Red Card
+    Dragged,
+
+    /// The previous Pokemon was withdrawn by its own item or ability; the
+    /// player picked the replacement, not the switch
+    ForcedByEffect {
+        /// "Emergency Exit", "Wimp Out", "Eject Button" or "Eject Pack"
+        source: String,
+    },
+}
+
+impl SwitchKind {
+    /// Whether the player decided to switch, as opposed to having to
+    pub fn was_chosen(&self) -> bool {
+        matches!(self, Self::Chosen)
+    }
+}
+
+/// A Pokemon switching or being dragged in during the battle
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwitchRecord {
+    /// Turn the switch happened on
+    pub turn: u32,
+
+    /// Pokemon that came in
+    pub pokemon: Pokemon,
+
+    pub kind: SwitchKind,
+}
+
+/// A message that didn't fit the tracked state
+///
+/// A known-good log produces none of these; each one means either a tracker
+/// gap or a message that was lost or reordered.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inconsistency {
+    /// A message named a Pokemon the side doesn't have
+    UnresolvedPokemon(Pokemon),
+
+    /// A |-damage| left the Pokemon with more HP than before, in percent
+    HpIncreasedOnDamage {
+        pokemon: Pokemon,
+        from: u32,
+        to: u32,
+    },
+
+    /// A |-boost| or |-unboost| on a Pokemon that has fainted
+    BoostOnFainted(Pokemon),
+
+    /// A message named a position outside the side's active slots
+    PositionOutOfRange(Pokemon),
+
+    /// Details or a request gave a different level than an earlier source
+    LevelMismatch {
+        pokemon: Pokemon,
+        known: u8,
+        seen: u8,
+    },
+
+    /// A request changed one of our Pokemon's HP with no message to explain it
+    UnexplainedHpChange {
+        pokemon: Pokemon,
+        belief: u32,
+        request: u32,
+    },
+
+    /// A |-sidestart| for a condition the side already had at its maximum layers
+    ConditionOverStacked {
+        player: Player,
+        condition: SideCondition,
+    },
+}
+
+/// How much an [`Inconsistency`] says about the tracker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Server state the log never showed, not necessarily a tracker gap
+    Minor,
+
+    /// A message that doesn't fit the tracked state
+    Major,
+}
+
+impl Inconsistency {
+    /// Whether this points at a tracker gap or just at state the log left out
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::UnexplainedHpChange { .. } => Severity::Minor,
+            _ => Severity::Major,
+        }
+    }
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnresolvedPokemon(pokemon) => {
+                write!(f, "no {} {} tracked", pokemon.player.as_str(), pokemon.name)
+            }
+            Self::HpIncreasedOnDamage { pokemon, from, to } => write!(
+                f,
+                "{} {} damaged from {from}% up to {to}%",
+                pokemon.player.as_str(),
+                pokemon.name
+            ),
+            Self::BoostOnFainted(pokemon) => write!(
+                f,
+                "stat change on fainted {} {}",
+                pokemon.player.as_str(),
+                pokemon.name
+            ),
+            Self::PositionOutOfRange(pokemon) => write!(
+                f,
+                "{}{} out of range for {}",
+                pokemon.player.as_str(),
+                pokemon.position.unwrap_or('?'),
+                pokemon.name
+            ),
+            Self::LevelMismatch {
+                pokemon,
+                known,
+                seen,
+            } => write!(
+                f,
+                "{} {} was level {known}, now shown as level {seen}",
+                pokemon.player.as_str(),
+                pokemon.name
+            ),
+            Self::UnexplainedHpChange {
+                pokemon,
+                belief,
+                request,
+            } => write!(
+                f,
+                "{} {} had {belief} HP, request says {request}",
+                pokemon.player.as_str(),
+                pokemon.name
+            ),
+            Self::ConditionOverStacked { player, condition } => write!(
+                f,
+                "{condition} set again on {} at its maximum layers",
+                player.as_str()
+            ),
+        }
+    }
+}
+
+/// A status move bounced back at its user by Magic Bounce or Magic Coat
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReflectedMove {
+    /// Turn the reflection happened on
+    pub turn: u32,
+
+    /// Pokemon that bounced the move back
+    pub reflector: Pokemon,
+
+    /// Effect that reflected it ("ability: Magic Bounce" or "move: Magic Coat")
+    pub source: String,
+
+    /// Pokemon that originally used the move, if its |move| line was seen
+    pub user: Option<Pokemon>,
+
+    /// The reflected move
+    pub move_name: String,
+}
+
 /// A battle being tracked from server messages
 ///
 /// This struct is the canonical reducer from Pokemon Showdown protocol messages
@@ -27,7 +293,8 @@ pub enum BattleKnowledge {
 ///
 /// The optional viewpoint is only a query convenience. It does not affect the
 /// underlying reduced state.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TrackedBattle {
     // === Battle metadata ===
     /// Game type (singles, doubles, etc.)
@@ -39,6 +306,10 @@ pub struct TrackedBattle {
     /// Format/tier name
     pub tier: String,
 
+    /// The format's |rule| lines, in order, see [`TrackedBattle::rule_context`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: Vec<String>,
+
     /// Current turn number (0 = not started)
     pub turn: u32,
 
@@ -57,6 +328,103 @@ pub struct TrackedBattle {
     /// Which player this state is currently being viewed from, if any.
     viewpoint: Option<Player>,
 
+    /// Which parts of the state are tracked.
+    pub(crate) config: TrackerConfig,
+
+    /// Latest request applied, for intersecting its move slots with tracked state
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) last_request: Option<BattleRequest>,
+
+    /// Move slots from the latest move request with each of our Pokemon active,
+    /// by `pokemon` index
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) request_moves: HashMap<usize, Vec<MoveSlot>>,
+
+    // === Action log ===
+    /// Combined moves that have occurred, in order
+    pub combined_moves: Vec<CombinedMove>,
+
+    /// Source/target pair from the latest |-waiting|, consumed by |-combine|
+    pub(crate) pending_combo: Option<(Pokemon, Pokemon)>,
+
+    /// Moves reflected back by Magic Bounce / Magic Coat, in order
+    pub reflected_moves: Vec<ReflectedMove>,
+
+    /// Side conditions cleared by moves and abilities, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub condition_removals: Vec<ConditionRemoval>,
+
+    /// Orderings between foes' moves within each turn, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub move_orders: Vec<MoveOrder>,
+
+    /// Visible reasons a Pokemon moved out of Speed order, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub order_exceptions: Vec<OrderException>,
+
+    /// Switches after the leads came out, and why each happened, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub switches: Vec<SwitchRecord>,
+
+    /// Pokemon an Emergency Exit, Wimp Out, Eject Button or Eject Pack is
+    /// withdrawing, with the effect, until their replacement comes in
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) pending_forced_switches: Vec<(Pokemon, String)>,
+
+    /// Pokemon that have used a move this turn, with their move and Speed stage
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) turn_movers: Vec<(Pokemon, String, i8)>,
+
+    /// Whether this turn's |upkeep| has come: every action is done and only
+    /// replacements for fainted Pokemon are left
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) turn_resolved: bool,
+
+    /// Last move used (user and move name)
+    pub(crate) last_move: Option<(Pokemon, String)>,
+
+    /// Reflector and effect from a Magic Bounce / Magic Coat activation that
+    /// hasn't landed yet; the next side condition, status or unboost is credited to it
+    pub(crate) pending_reflect: Option<(Pokemon, String)>,
+
+    /// User and name of a screen-breaking move or Screen Cleaner activation
+    /// whose untagged |-sideend| lines haven't all arrived yet
+    pub(crate) pending_removal: Option<(Pokemon, String)>,
+
+    /// Pokemon from the first of a Skill Swap's two |-ability| lines, and
+    /// whether its ability had already been replaced before the swap
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) pending_skill_swap: Option<(Pokemon, bool)>,
+
+    /// User and target of a `[still]` |move| line, for the |-prepare| that
+    /// may follow it on a two-turn move's charge turn
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) pending_charge: Option<(Pokemon, Option<Pokemon>)>,
+
+    /// Opponent knowledge from earlier games, applied as their Pokemon appear
+    pub(crate) scouting: Option<ScoutingReport>,
+
+    /// Messages that didn't fit the tracked state, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) inconsistencies: Vec<Inconsistency>,
+
+    /// Whether |-sidestart| lines are re-announcements, see
+    /// [`TrackedBattle::set_replaying`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) replaying: bool,
+
+    /// Progress toward the Endless Battle Clause, from the first turn on
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub staleness: Option<StalenessState>,
+
+    /// Crits, misses and secondary effects by side, see [`TrackedBattle::variance`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) variance: VarianceReport,
+
+    /// Request corrections to our side, see [`TrackedBattle::reconciliations`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) reconciliation: Reconciliation,
+
     // === Outcome ===
     /// Whether the battle has ended
     pub ended: bool,
@@ -75,11 +443,36 @@ impl TrackedBattle {
             game_type: None,
             generation: 9, // Default to latest gen
             tier: String::new(),
+            rules: Vec::new(),
             turn: 0,
             field: FieldState::new(),
             sides: [None, None, None, None],
             knowledge: BattleKnowledge::Public,
             viewpoint: None,
+            config: TrackerConfig::new(),
+            last_request: None,
+            request_moves: HashMap::new(),
+            combined_moves: Vec::new(),
+            pending_combo: None,
+            reflected_moves: Vec::new(),
+            condition_removals: Vec::new(),
+            move_orders: Vec::new(),
+            order_exceptions: Vec::new(),
+            switches: Vec::new(),
+            pending_forced_switches: Vec::new(),
+            turn_movers: Vec::new(),
+            turn_resolved: false,
+            last_move: None,
+            pending_reflect: None,
+            pending_removal: None,
+            pending_skill_swap: None,
+            pending_charge: None,
+            scouting: None,
+            inconsistencies: Vec::new(),
+            replaying: false,
+            staleness: None,
+            variance: VarianceReport::default(),
+            reconciliation: Reconciliation::default(),
             ended: false,
             winner: None,
             tie: false,
@@ -101,6 +494,34 @@ impl TrackedBattle {
         battle
     }
 
+    /// Create a tracker that only retains the state enabled in `config`.
+    pub fn with_config(config: TrackerConfig) -> Self {
+        let mut battle = Self::new();
+        battle.config = config;
+        battle
+    }
+
+    /// Start over for a new game in the same room, such as a rematch on a
+    /// server that reuses battle rooms.
+    ///
+    /// Only the configuration, knowledge mode and viewpoint are kept; call
+    /// [`set_viewpoint`](Self::set_viewpoint) if the seats changed. `scouting`
+    /// carries over what the opponent revealed, see
+    /// [`into_scouting_report`](Self::into_scouting_report).
+    pub fn reset(&mut self, scouting: Option<ScoutingReport>) {
+        let (config, knowledge, viewpoint) = (self.config, self.knowledge, self.viewpoint);
+        *self = Self::new();
+        self.config = config;
+        self.knowledge = knowledge;
+        self.viewpoint = viewpoint;
+        self.scouting = scouting;
+    }
+
+    /// Get the tracker configuration.
+    pub fn config(&self) -> TrackerConfig {
+        self.config
+    }
+
     /// Set the current knowledge mode explicitly.
     pub fn set_knowledge(&mut self, knowledge: BattleKnowledge) {
         self.knowledge = knowledge;
@@ -126,6 +547,49 @@ impl TrackedBattle {
         self.viewpoint
     }
 
+    /// Treat the messages that follow as the server re-sending state that
+    /// was already tracked, as it does when rejoining a battle
+    ///
+    /// While set, a |-sidestart| for a condition the side already has leaves
+    /// its layers alone instead of stacking another. Turn it off once the
+    /// server catches up to the live battle.
+    pub fn set_replaying(&mut self, replaying: bool) {
+        self.replaying = replaying;
+    }
+
+    /// Whether re-sent messages are being taken as re-announcements
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Generation rules for this battle
+    pub fn mechanics(&self) -> Mechanics {
+        Mechanics::for_generation(self.generation)
+    }
+
+    /// Get the latest request applied with `apply_request`
+    pub fn last_request(&self) -> Option<&BattleRequest> {
+        self.last_request.as_ref()
+    }
+
+    /// PP and disabled flags for one of our Pokemon, from the latest move request
+    /// it was active in
+    ///
+    /// Force-switch and wait requests carry no move data, so they leave this as is.
+    pub fn known_move_slots(&self, pokemon_index: usize) -> Option<&[MoveSlot]> {
+        self.request_moves.get(&pokemon_index).map(Vec::as_slice)
+    }
+
+    /// Messages so far that didn't fit the tracked state
+    pub fn inconsistencies(&self) -> &[Inconsistency] {
+        &self.inconsistencies
+    }
+
+    /// Number of messages so far that didn't fit the tracked state
+    pub fn inconsistency_count(&self) -> usize {
+        self.inconsistencies.len()
+    }
+
     /// Backwards-compatible alias for `set_viewpoint`.
     pub fn set_perspective(&mut self, player: Player) {
         self.set_viewpoint(player);
@@ -180,13 +644,68 @@ impl TrackedBattle {
         self.sides[idx].as_mut()
     }
 
-    /// Get or create a side for a player
+    /// Get or create a side for a player, with the game type's active slots
     pub fn get_or_create_side(&mut self, player: Player, username: &str) -> &mut SideState {
         let idx = player_to_index(player);
-        if self.sides[idx].is_none() {
-            self.sides[idx] = Some(SideState::new(player, username));
+        let slots = self.slots_per_side();
+        self.sides[idx].get_or_insert_with(|| {
+            let mut side = SideState::new(player, username);
+            side.set_active_slots(slots);
+            side
+        })
+    }
+
+    /// Active slots on each side for the game type (1 before |gametype|)
+    pub fn slots_per_side(&self) -> usize {
+        match self.game_type {
+            None | Some(GameType::Singles) | Some(GameType::FreeForAll) => 1,
+            Some(GameType::Doubles) | Some(GameType::Multi) => 2,
+            Some(GameType::Triples) => 3,
+        }
+    }
+
+    /// Active slot on `player`'s side for a position letter, None if out of range
+    ///
+    /// In free-for-all every side has one active Pokemon and the letter is its
+    /// place on the field (p3b), so any position maps to slot 0.
+    pub fn slot_of(&self, player: Player, position: char) -> Option<usize> {
+        let slot = position_to_slot(position)?;
+        if self.game_type == Some(GameType::FreeForAll) {
+            return Some(0);
+        }
+        let slots = self
+            .get_side(player)
+            .map_or_else(|| self.slots_per_side(), |side| side.active_indices.len());
+        (slot < slots).then_some(slot)
+    }
+
+    /// Whether `a` and `b` fight on the same team: the same player, or
+    /// partners in a multi battle
+    pub fn same_team(&self, a: Player, b: Player) -> bool {
+        match self.game_type {
+            Some(GameType::FreeForAll) => a == b,
+            _ => player_to_index(a) % 2 == player_to_index(b) % 2,
         }
-        self.sides[idx].as_mut().unwrap()
+    }
+
+    /// Rename the player whose username matches `old` by user id, returns whether one did
+    pub fn rename_player(&mut self, old: &str, new: &str) -> bool {
+        let id = user_id(old);
+        match self.sides_mut().find(|side| user_id(&side.username) == id) {
+            Some(side) => {
+                side.username = new.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Player whose username matches the winner by user id
+    pub fn winning_player(&self) -> Option<Player> {
+        let id = user_id(self.winner.as_deref()?);
+        self.sides()
+            .find(|side| user_id(&side.username) == id)
+            .map(|side| side.player)
     }
 
     /// Check if a side exists
@@ -209,14 +728,7 @@ impl TrackedBattle {
     pub fn set_game_type(&mut self, game_type: GameType) {
         self.game_type = Some(game_type);
 
-        let slots = match game_type {
-            GameType::Singles => 1,
-            GameType::Doubles => 2,
-            GameType::Triples => 3,
-            GameType::Multi => 2,
-            GameType::FreeForAll => 1,
-        };
-
+        let slots = self.slots_per_side();
         for side in self.sides_mut() {
             side.set_active_slots(slots);
         }
@@ -232,8 +744,137 @@ impl TrackedBattle {
         self.turn == 0 && !self.ended
     }
 
+    /// Get a Pokemon's stat after stages and field-wide modifiers (Ruin abilities)
+    ///
+    /// Returns None when the Pokemon's raw stat isn't known.
+    pub fn effective_stat(&self, player: Player, poke: &PokemonState, stat: Stat) -> Option<u32> {
+        let value = poke.effective_stat(stat)?;
+        let multiplier = self.field.stat_multiplier(player, poke, stat);
+        Some((value as f32 * multiplier) as u32)
+    }
+
+    /// Whether a recorded exception (Custap Berry, Quick Claw, Quick Draw) explains
+    /// the first mover going first
+    pub fn is_order_explained(&self, order: &MoveOrder) -> bool {
+        self.order_exceptions.iter().any(|exception| {
+            exception.turn == order.turn
+                && exception.pokemon.player == order.first.player
+                && exception.pokemon.name == order.first.name
+        })
+    }
+
+    /// Item or ability withdrawing `player`'s Pokemon, while the force-switch
+    /// request it causes is pending
+    ///
+    /// Tells a forced switch after Emergency Exit or Eject Button apart from
+    /// one that replaces a fainted Pokemon.
+    pub fn forced_switch_reason(&self, player: Player) -> Option<&str> {
+        self.pending_forced_switches
+            .iter()
+            .find(|(pokemon, _)| pokemon.player == player)
+            .map(|(_, source)| source.as_str())
+    }
+
+    /// Whether a turn's actions are being carried out: past its |turn| line
+    /// and before its |upkeep|
+    pub fn is_resolving_turn(&self) -> bool {
+        self.turn > 0 && !self.turn_resolved && !self.ended
+    }
+
+    /// Active Pokemon that haven't moved, been stopped from moving or
+    /// switched in this turn, side by side in party order
+    ///
+    /// Empty outside [`is_resolving_turn`](Self::is_resolving_turn). During a
+    /// forced switch mid-turn, these are the only Pokemon that can still hit
+    /// the switch-in before the next turn.
+    pub fn remaining_actors(&self) -> Vec<PokemonRef<'_>> {
+        if !self.is_resolving_turn() {
+            return Vec::new();
+        }
+        self.all_pokemon()
+            .active()
+            .alive()
+            .filter(|p| !p.pokemon.has_acted_this_turn)
+            .collect()
+    }
+
+    /// Rough estimate of the heap and inline memory used by this state, in bytes
+    ///
+    /// Intended for instrumentation; counts allocated capacity rather than exact usage.
+    pub fn memory_footprint_estimate(&self) -> usize {
+        use std::mem::size_of;
+
+        let mut total = size_of::<Self>() + self.tier.capacity();
+        total += self.rules.capacity() * size_of::<String>();
+        total += self.rules.iter().map(String::capacity).sum::<usize>();
+        total += self.field.stat_modifiers.capacity() * size_of::<FieldStatModifier>();
+        total += self.combined_moves.capacity() * size_of::<CombinedMove>();
+        total += self.reflected_moves.capacity() * size_of::<ReflectedMove>();
+        total += self.condition_removals.capacity() * size_of::<ConditionRemoval>();
+        total += self.move_orders.capacity() * size_of::<MoveOrder>();
+        total += self.order_exceptions.capacity() * size_of::<OrderException>();
+        total += self.switches.capacity() * size_of::<SwitchRecord>();
+        total += self.inconsistencies.capacity() * size_of::<Inconsistency>();
+        total += self.request_moves.capacity() * (size_of::<usize>() + size_of::<Vec<MoveSlot>>());
+        for slots in self.request_moves.values() {
+            total += slots.capacity() * size_of::<MoveSlot>();
+        }
+
+        for side in self.sides() {
+            total += side.username.capacity();
+            total += side.pokemon.capacity() * size_of::<PokemonState>();
+            total += heap_capacity(&side.active_indices) * size_of::<Option<usize>>();
+            total += side.conditions.capacity()
+                * (size_of::<SideCondition>() + size_of::<SideConditionState>());
+
+            for poke in &side.pokemon {
+                total += poke.identity.species.capacity();
+                total += poke.identity.nickname.as_ref().map_or(0, |n| n.capacity());
+                total +=
+                    poke.volatiles.len() * (size_of::<Volatile>() + size_of::<VolatileState>());
+                total += heap_capacity(&poke.base_types) * size_of::<Type>();
+                total += heap_capacity(&poke.current_types) * size_of::<Type>();
+                total += heap_capacity(&poke.known_moves) * size_of::<String>();
+                total += poke.known_moves.iter().map(String::capacity).sum::<usize>();
+                total += heap_capacity(&poke.move_sources) * size_of::<(String, MoveProvenance)>();
+                total += poke.move_sources.iter().map(|(m, _)| m.capacity()).sum::<usize>();
+                total += poke.known_move_pp.capacity() * size_of::<KnownMove>();
+                total += poke
+                    .known_move_pp
+                    .iter()
+                    .map(|m| m.name.capacity())
+                    .sum::<usize>();
+                total += poke.known_ability.as_ref().map_or(0, |a| a.capacity());
+                total += poke.ability_override.as_ref().map_or(0, |a| a.capacity());
+                total += poke.known_item.as_ref().map_or(0, |i| i.capacity());
+                total += poke.status_from.as_ref().map_or(0, |f| f.capacity());
+                total += poke
+                    .charging_move
+                    .as_ref()
+                    .map_or(0, |c| c.move_name.capacity());
+                total += poke.scouting.capacity() * size_of::<KnowledgeEntry>();
+                total += poke.contradictions.capacity() * size_of::<Observation>();
+                total += poke.stat_constraints.capacity() * size_of::<StatConstraint>();
+                total += poke.stat_bounds.capacity() * size_of::<StatBound>();
+            }
+        }
+
+        total
+    }
+
+    /// Two-turn moves the foes' active Pokemon are charging, due next turn
+    ///
+    /// Without a viewpoint this covers every side.
+    pub fn incoming_charged_attacks(&self) -> Vec<(PokemonRef<'_>, &ChargingMove)> {
+        self.all_pokemon()
+            .theirs()
+            .active()
+            .filter_map(|p| Some((p, p.pokemon.charging_move.as_ref()?)))
+            .collect()
+    }
+
     /// Get all active Pokemon from all sides in speed order (not implemented yet)
-    pub fn get_all_active(&self) -> Vec<&crate::types::PokemonState> {
+    pub fn get_all_active(&self) -> Vec<&PokemonState> {
         self.sides()
             .flat_map(|side| side.get_active())
             .collect()
@@ -246,6 +887,11 @@ impl Default for TrackedBattle {
     }
 }
 
+/// Capacity a small vector has spilled onto the heap (zero while stored inline)
+fn heap_capacity<A: smallvec::Array>(v: &smallvec::SmallVec<A>) -> usize {
+    if v.spilled() { v.capacity() } else { 0 }
+}
+
 /// Convert Player enum to array index
 pub fn player_to_index(player: Player) -> usize {
     match player {
@@ -256,13 +902,16 @@ pub fn player_to_index(player: Player) -> usize {
     }
 }
 
-/// Convert position character to slot index
-pub fn position_to_slot(pos: char) -> usize {
+/// Convert a position letter to a slot index, None for anything past 'd'
+///
+/// See [`TrackedBattle::slot_of`] for the slot on a given side.
+pub fn position_to_slot(pos: char) -> Option<usize> {
     match pos {
-        'a' => 0,
-        'b' => 1,
-        'c' => 2,
-        _ => 0,
+        'a' => Some(0),
+        'b' => Some(1),
+        'c' => Some(2),
+        'd' => Some(3),
+        _ => None,
     }
 }
 
@@ -378,9 +1027,10 @@ mod tests {
 
     #[test]
     fn test_position_to_slot() {
-        assert_eq!(position_to_slot('a'), 0);
-        assert_eq!(position_to_slot('b'), 1);
-        assert_eq!(position_to_slot('c'), 2);
-        assert_eq!(position_to_slot('d'), 0); // Default
+        assert_eq!(position_to_slot('a'), Some(0));
+        assert_eq!(position_to_slot('b'), Some(1));
+        assert_eq!(position_to_slot('c'), Some(2));
+        assert_eq!(position_to_slot('d'), Some(3));
+        assert_eq!(position_to_slot('e'), None);
     }
 }
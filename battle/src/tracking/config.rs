@@ -0,0 +1,146 @@
+//! Configuration of what a TrackedBattle retains
+
+/// What the tracker does when a message doesn't fit the state it has built
+///
+/// See [`Inconsistency`](super::Inconsistency) for what counts. Every
+/// inconsistency is recorded on the battle whatever the mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrictnessMode {
+    /// Panic, for tests and fuzzing
+    Panic,
+    /// Log a warning and carry on
+    #[default]
+    LogWarn,
+    /// Carry on without a word
+    Silent,
+}
+
+/// Controls which parts of the battle state the tracker maintains.
+///
+/// Everything is tracked by default. Pipelines that only need a subset (e.g.
+/// HP, status and boosts for training data) can switch the rest off to save
+/// memory; disabled branches of the reducer become no-ops and the matching
+/// fields stay empty.
+///
+/// ```ignore
+/// let config = TrackerConfig::new().moves(false).volatiles(false);
+/// let battle = TrackedBattle::with_config(config);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackerConfig {
+    moves: bool,
+    items_abilities: bool,
+    volatiles: bool,
+    action_log: bool,
+    #[cfg_attr(feature = "serde", serde(default = "enabled"))]
+    history: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    strictness: StrictnessMode,
+}
+
+impl TrackerConfig {
+    /// Track everything (the default)
+    pub fn new() -> Self {
+        Self {
+            moves: true,
+            items_abilities: true,
+            volatiles: true,
+            action_log: true,
+            history: true,
+            strictness: StrictnessMode::LogWarn,
+        }
+    }
+
+    /// Track only HP, status, boosts, switches and field/side conditions
+    pub fn minimal() -> Self {
+        Self {
+            moves: false,
+            items_abilities: false,
+            volatiles: false,
+            action_log: false,
+            history: false,
+            strictness: StrictnessMode::LogWarn,
+        }
+    }
+
+    /// Enable or disable revealed move tracking
+    pub fn moves(mut self, enabled: bool) -> Self {
+        self.moves = enabled;
+        self
+    }
+
+    /// Enable or disable revealed item and ability tracking
+    pub fn items_abilities(mut self, enabled: bool) -> Self {
+        self.items_abilities = enabled;
+        self
+    }
+
+    /// Enable or disable volatile condition tracking
+    pub fn volatiles(mut self, enabled: bool) -> Self {
+        self.volatiles = enabled;
+        self
+    }
+
+    /// Enable or disable the action log (combined moves)
+    pub fn action_log(mut self, enabled: bool) -> Self {
+        self.action_log = enabled;
+        self
+    }
+
+    /// Enable or disable the battle history: move orders, order exceptions
+    /// and switches
+    pub fn history(mut self, enabled: bool) -> Self {
+        self.history = enabled;
+        self
+    }
+
+    /// Choose how tracking inconsistencies are reported
+    pub fn strictness(mut self, mode: StrictnessMode) -> Self {
+        self.strictness = mode;
+        self
+    }
+
+    /// Whether revealed moves are tracked
+    pub fn tracks_moves(&self) -> bool {
+        self.moves
+    }
+
+    /// Whether revealed items and abilities are tracked
+    pub fn tracks_items_abilities(&self) -> bool {
+        self.items_abilities
+    }
+
+    /// Whether volatile conditions are tracked
+    pub fn tracks_volatiles(&self) -> bool {
+        self.volatiles
+    }
+
+    /// Whether the action log is kept
+    pub fn tracks_action_log(&self) -> bool {
+        self.action_log
+    }
+
+    /// Whether move orders, order exceptions and switches are kept
+    pub fn tracks_history(&self) -> bool {
+        self.history
+    }
+
+    /// How tracking inconsistencies are reported
+    pub fn strictness_mode(&self) -> StrictnessMode {
+        self.strictness
+    }
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Default for flags missing from configs serialized before they existed
+#[cfg(feature = "serde")]
+fn enabled() -> bool {
+    true
+}
@@ -0,0 +1,344 @@
+//! Negative evidence from |-immune| and |-fail|
+//!
+//! A move that should have landed but didn't points at something unrevealed,
+//! usually an ability. Outcomes are only recorded as contradictions after the
+//! visible explanations (typing, weather, terrain, Protect, Safeguard, ...)
+//! are ruled out, and only when the move's type or inflicted status is known.
+
+use kazam_protocol::Pokemon;
+
+use super::battle::TrackedBattle;
+use crate::query::grounding::{Grounded, GroundingUnknown, is_grounded};
+use crate::query::moves::move_type;
+use crate::types::{
+    FieldState, Observation, Outcome, PokemonState, SideCondition, Status, Terrain, Type, Volatile,
+    Weather, to_id,
+};
+
+/// Status moves Grass types (and Overcoat / Safety Goggles) are immune to
+const POWDER_MOVES: &[&str] = &["poisonpowder", "sleeppowder", "spore", "stunspore"];
+
+impl TrackedBattle {
+    /// Handle |-immune|, revealing a cited ability or noting an unexplained immunity
+    pub(crate) fn observe_immune(&mut self, target: &Pokemon, from: Option<&str>) {
+        if !self.config.tracks_items_abilities() {
+            return;
+        }
+        if let Some(from) = from {
+            if let Some(ability) = from.strip_prefix("ability: ")
+                && let Some(poke) = self.pokemon_mut(target)
+            {
+                poke.record_ability(ability);
+            }
+            return;
+        }
+        // Without abilities there is nothing unrevealed to explain a miss
+        if !self.mechanics().has_abilities() {
+            return;
+        }
+
+        let Some(move_name) = self.move_into(target) else {
+            return;
+        };
+        let Some(attack_type) = move_type(&move_name) else {
+            return;
+        };
+        let Some(poke) = self.pokemon(target) else {
+            return;
+        };
+        if !should_hit(poke, attack_type, &self.field) {
+            return;
+        }
+        self.record_contradiction(
+            target,
+            move_name,
+            Outcome::Hit(attack_type),
+            Outcome::Immune,
+        );
+    }
+
+    /// Handle |-fail|, noting a status that should have been inflicted, or
+    /// revealing what blocked a stat drop
+    pub(crate) fn observe_fail(
+        &mut self,
+        target: &Pokemon,
+        action: Option<&str>,
+        from: Option<&str>,
+    ) {
+        if !self.config.tracks_items_abilities() {
+            return;
+        }
+        if let Some(from) = from {
+            // Clear Body, Clear Amulet and the like name themselves on a blocked drop
+            if action == Some("unboost")
+                && let Some(poke) = self.pokemon_mut(target)
+            {
+                if let Some(item) = from.strip_prefix("item: ") {
+                    poke.record_item(item);
+                } else if let Some(ability) = from.strip_prefix("ability: ") {
+                    poke.record_ability(ability);
+                }
+            }
+            return;
+        }
+        if !self.mechanics().has_abilities() {
+            return;
+        }
+        let Some(status) = action.and_then(Status::from_protocol) else {
+            return;
+        };
+        let Some(move_name) = self.move_into(target) else {
+            return;
+        };
+        let Some(side) = self.get_side(target.player) else {
+            return;
+        };
+        let Some(poke) = self.pokemon(target) else {
+            return;
+        };
+
+        // Safeguard, and Sleep Clause once a teammate is asleep
+        if side.has_condition(SideCondition::Safeguard)
+            || (status == Status::Sleep
+                && side.pokemon.iter().any(|p| p.status == Some(Status::Sleep)))
+        {
+            return;
+        }
+        if poke.get_types().contains(&Type::Grass)
+            && POWDER_MOVES.contains(&to_id(&move_name).as_str())
+        {
+            return;
+        }
+        if !should_take_status(poke, status, &self.field) {
+            return;
+        }
+        self.record_contradiction(target, move_name, Outcome::Status(status), Outcome::Failed);
+    }
+
+    /// Move the last action log entry used against `target`, if someone else used it
+    fn move_into(&self, target: &Pokemon) -> Option<String> {
+        match &self.last_move {
+            Some((user, name)) if user.player != target.player || user.name != target.name => {
+                Some(name.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn pokemon(&self, pokemon: &Pokemon) -> Option<&PokemonState> {
+        let side = self.get_side(pokemon.player)?;
+        side.get_pokemon(side.find_pokemon(&pokemon.name)?)
+    }
+
+    fn pokemon_mut(&mut self, pokemon: &Pokemon) -> Option<&mut PokemonState> {
+        self.get_side_mut(pokemon.player)?
+            .find_pokemon_mut(&pokemon.name)
+    }
+
+    fn record_contradiction(
+        &mut self,
+        target: &Pokemon,
+        move_name: String,
+        expected: Outcome,
+        observed: Outcome,
+    ) {
+        let turn = self.turn;
+        if let Some(poke) = self.pokemon_mut(target) {
+            poke.contradictions.push(Observation {
+                turn,
+                move_name,
+                expected,
+                observed,
+            });
+        }
+    }
+}
+
+/// Whether everything tracked says an attack of `attack_type` connects
+fn should_hit(poke: &PokemonState, attack_type: Type, field: &FieldState) -> bool {
+    let types = poke.get_types();
+    if types.is_empty() || poke.has_volatile(&Volatile::Protect) {
+        return false;
+    }
+    let washed_out = matches!(
+        (field.weather, attack_type),
+        (Some(Weather::HeavyRain), Type::Fire) | (Some(Weather::HarshSun), Type::Water)
+    );
+    if washed_out {
+        return false;
+    }
+
+    if attack_type == Type::Ground {
+        // Only an unrevealed ability may be left to keep it airborne
+        let grounded = is_grounded(poke, field);
+        if !matches!(
+            grounded,
+            Grounded::Yes | Grounded::Unknown(GroundingUnknown::Ability)
+        ) {
+            return false;
+        }
+        let grounded_types: Vec<Type> = types
+            .iter()
+            .copied()
+            .filter(|&t| t != Type::Flying)
+            .collect();
+        return attack_type.effectiveness_multi(&grounded_types) > 0.0;
+    }
+    attack_type.effectiveness_multi(types) > 0.0
+}
+
+/// Whether everything tracked says `status` can be inflicted
+fn should_take_status(poke: &PokemonState, status: Status, field: &FieldState) -> bool {
+    let types = poke.get_types();
+    if types.is_empty()
+        || poke.status.is_some()
+        || poke.has_volatile(&Volatile::Protect)
+        || poke.has_volatile(&Volatile::Substitute)
+    {
+        return false;
+    }
+    let type_immune = match status {
+        Status::Burn => types.contains(&Type::Fire),
+        Status::Paralysis => types.contains(&Type::Electric),
+        Status::Poison | Status::BadPoison => {
+            types.contains(&Type::Poison) || types.contains(&Type::Steel)
+        }
+        Status::Freeze => types.contains(&Type::Ice),
+        Status::Sleep => false,
+    };
+    if type_immune {
+        return false;
+    }
+
+    // Misty Terrain blocks all status and Electric Terrain blocks sleep for grounded targets
+    let terrain_blocks = match field.terrain {
+        Some(Terrain::Misty) => true,
+        Some(Terrain::Electric) => status == Status::Sleep,
+        _ => false,
+    };
+    !(terrain_blocks && is_grounded(poke, field) != Grounded::No)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    fn apply_log(battle: &mut TrackedBattle, log: &str) {
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+    }
+
+    /// Garchomp against a Bronzong whose typing is known but ability is not
+    fn battle() -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(
+            &mut battle,
+            r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Bronzong|Bronzong, L50|100/100
+|turn|1"#,
+        );
+        let bronzong = &mut battle.get_side_mut(Player::P2).unwrap().pokemon[0];
+        bronzong.set_types(vec![Type::Steel, Type::Psychic]);
+        battle
+    }
+
+    fn bronzong(battle: &TrackedBattle) -> &PokemonState {
+        &battle.get_side(Player::P2).unwrap().pokemon[0]
+    }
+
+    #[test]
+    fn test_unexplained_ground_immunity() {
+        let mut battle = battle();
+        apply_log(
+            &mut battle,
+            "|move|p1a: Garchomp|Earthquake|p2a: Bronzong\n|-immune|p2a: Bronzong",
+        );
+        assert_eq!(
+            bronzong(&battle).contradictions,
+            vec![Observation {
+                turn: 1,
+                move_name: "Earthquake".to_string(),
+                expected: Outcome::Hit(Type::Ground),
+                observed: Outcome::Immune,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_magnet_rise_explains_immunity() {
+        let mut battle = battle();
+        apply_log(
+            &mut battle,
+            r#"|move|p2a: Bronzong|Magnet Rise|p2a: Bronzong
+|-start|p2a: Bronzong|Magnet Rise
+|move|p1a: Garchomp|Earthquake|p2a: Bronzong
+|-immune|p2a: Bronzong"#,
+        );
+        assert!(bronzong(&battle).contradictions.is_empty());
+    }
+
+    #[test]
+    fn test_cited_ability_is_revealed_not_contradicted() {
+        let mut battle = battle();
+        apply_log(
+            &mut battle,
+            "|move|p1a: Garchomp|Earthquake|p2a: Bronzong\n|-immune|p2a: Bronzong|[from] ability: Levitate",
+        );
+        assert_eq!(bronzong(&battle).known_ability.as_deref(), Some("Levitate"));
+        assert!(bronzong(&battle).contradictions.is_empty());
+    }
+
+    #[test]
+    fn test_blocked_drop_reveals_item_or_ability() {
+        let mut battle = battle();
+        apply_log(
+            &mut battle,
+            "|-fail|p2a: Bronzong|unboost|[from] item: Clear Amulet|[of] p2a: Bronzong",
+        );
+        assert_eq!(
+            bronzong(&battle).known_item.as_deref(),
+            Some("Clear Amulet")
+        );
+
+        apply_log(
+            &mut battle,
+            "|-fail|p2a: Bronzong|unboost|[from] ability: Clear Body|[of] p2a: Bronzong",
+        );
+        assert_eq!(
+            bronzong(&battle).known_ability.as_deref(),
+            Some("Clear Body")
+        );
+        assert!(bronzong(&battle).contradictions.is_empty());
+    }
+
+    #[test]
+    fn test_status_fail() {
+        let mut battle = battle();
+        apply_log(
+            &mut battle,
+            "|move|p1a: Garchomp|Thunder Wave|p2a: Bronzong\n|-fail|p2a: Bronzong|par",
+        );
+        let contradictions = &bronzong(&battle).contradictions;
+        assert_eq!(contradictions.len(), 1);
+        assert_eq!(
+            contradictions[0].expected,
+            Outcome::Status(Status::Paralysis)
+        );
+        assert_eq!(contradictions[0].observed, Outcome::Failed);
+
+        // Safeguard explains the next one
+        apply_log(
+            &mut battle,
+            r#"|-sidestart|p2: Bob|Safeguard
+|move|p1a: Garchomp|Thunder Wave|p2a: Bronzong
+|-fail|p2a: Bronzong|par"#,
+        );
+        assert_eq!(bronzong(&battle).contradictions.len(), 1);
+    }
+}
@@ -0,0 +1,591 @@
+//! The protocol messages the tracker consumes, decoupled from [`ServerMessage`]
+//!
+//! [`TrackedBattle::apply_input`](super::TrackedBattle::apply_input) works on
+//! [`TrackerInput`] alone, so a client on another protocol version (or a
+//! simulator feeding events directly) only has to build these borrowed views.
+//! [`TrackedBattle::apply_message`](super::TrackedBattle::apply_message) is the
+//! adapter for this crate's own protocol types.
+
+use kazam_protocol::{GameType, HpStatus, Player, Pokemon, PokemonDetails, ServerMessage, Stat};
+
+/// One battle event, borrowed from whatever message carried it
+///
+/// Variants follow the protocol line they come from, keeping only the fields
+/// the tracker reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackerInput<'a> {
+    // === Battle Initialization ===
+    /// `|player|`
+    BattlePlayer { player: Player, username: &'a str },
+    /// `|name|` for a player renaming mid-battle
+    Name { username: &'a str, old_id: &'a str },
+    /// `|teamsize|`
+    TeamSize { player: Player },
+    /// `|poke|` during team preview
+    Poke {
+        player: Player,
+        details: &'a PokemonDetails,
+    },
+    /// `|showteam|` with a packed team
+    ShowTeam { player: Player, team: &'a str },
+    /// `|gametype|`
+    GameType(GameType),
+    /// `|gen|`
+    Gen(u8),
+    /// `|tier|`
+    Tier(&'a str),
+    /// `|rule|`
+    Rule(&'a str),
+    /// `|turn|`
+    Turn(u32),
+    /// `|upkeep|`
+    Upkeep,
+
+    // === Major Actions ===
+    /// `|switch|`
+    Switch {
+        pokemon: &'a Pokemon,
+        details: &'a PokemonDetails,
+        hp_status: Option<&'a HpStatus>,
+    },
+    /// `|drag|`
+    Drag {
+        pokemon: &'a Pokemon,
+        details: &'a PokemonDetails,
+        hp_status: Option<&'a HpStatus>,
+    },
+    /// `|faint|`
+    Faint(&'a Pokemon),
+    /// `|move|`
+    Move {
+        pokemon: &'a Pokemon,
+        move_name: &'a str,
+        target: Option<&'a Pokemon>,
+        still: bool,
+        from: Option<&'a str>,
+    },
+    /// `|cant|`
+    Cant {
+        pokemon: &'a Pokemon,
+        reason: &'a str,
+    },
+    /// `|-activate|`
+    Activate {
+        pokemon: Option<&'a Pokemon>,
+        effect: &'a str,
+        detail: Option<&'a str>,
+        of: Option<&'a Pokemon>,
+    },
+    /// `|-waiting|` for a combined Pledge move
+    Waiting {
+        source: &'a Pokemon,
+        target: &'a Pokemon,
+    },
+    /// `|-combine|`
+    Combine,
+    /// `|-prepare|` for a charging move
+    Prepare {
+        attacker: &'a Pokemon,
+        move_name: &'a str,
+        defender: Option<&'a Pokemon>,
+    },
+    /// `|-anim|`
+    Anim {
+        source: &'a Pokemon,
+        move_name: &'a str,
+    },
+
+    // === HP and Status ===
+    /// `|-damage|`
+    Damage {
+        pokemon: &'a Pokemon,
+        hp_status: Option<&'a HpStatus>,
+        from: Option<&'a str>,
+    },
+    /// `|-heal|`
+    Heal {
+        pokemon: &'a Pokemon,
+        hp_status: Option<&'a HpStatus>,
+        from: Option<&'a str>,
+    },
+    /// `|-sethp|`
+    SetHp {
+        pokemon: &'a Pokemon,
+        hp_status: Option<&'a HpStatus>,
+    },
+    /// `|-status|`
+    Status {
+        pokemon: &'a Pokemon,
+        status: &'a str,
+        from: Option<&'a str>,
+    },
+    /// `|-curestatus|`
+    CureStatus(&'a Pokemon),
+    /// `|-cureteam|`
+    CureTeam(&'a Pokemon),
+
+    // === Boosts ===
+    /// `|-boost|`
+    Boost {
+        pokemon: &'a Pokemon,
+        stat: Stat,
+        amount: i8,
+        from: Option<&'a str>,
+    },
+    /// `|-unboost|`
+    Unboost {
+        pokemon: &'a Pokemon,
+        stat: Stat,
+        amount: i8,
+    },
+    /// `|-setboost|`
+    SetBoost {
+        pokemon: &'a Pokemon,
+        stat: Stat,
+        amount: i8,
+    },
+    /// `|-clearboost|`
+    ClearBoost(&'a Pokemon),
+    /// `|-clearallboost|`
+    ClearAllBoost,
+    /// `|-invertboost|`
+    InvertBoost(&'a Pokemon),
+    /// `|-clearpositiveboost|`
+    ClearPositiveBoost(&'a Pokemon),
+    /// `|-clearnegativeboost|`
+    ClearNegativeBoost(&'a Pokemon),
+    /// `|-copyboost|`
+    CopyBoost {
+        source: &'a Pokemon,
+        target: &'a Pokemon,
+    },
+    /// `|-swapboost|`
+    SwapBoost {
+        source: &'a Pokemon,
+        target: &'a Pokemon,
+        stats: &'a [Stat],
+    },
+
+    // === Volatiles and Conditions ===
+    /// `|-start|`
+    VolatileStart {
+        pokemon: &'a Pokemon,
+        effect: &'a str,
+    },
+    /// `|-end|`
+    VolatileEnd {
+        pokemon: &'a Pokemon,
+        effect: &'a str,
+    },
+    /// `|-singleturn|`
+    SingleTurn {
+        pokemon: &'a Pokemon,
+        move_name: &'a str,
+    },
+    /// `|-weather|`
+    Weather {
+        weather: &'a str,
+        upkeep: bool,
+        from: Option<&'a str>,
+        of: Option<&'a Pokemon>,
+    },
+    /// `|-fieldstart|`
+    FieldStart(&'a str),
+    /// `|-fieldend|`
+    FieldEnd(&'a str),
+    /// `|-sidestart|`
+    SideStart { side: Player, condition: &'a str },
+    /// `|-sideend|`
+    SideEnd {
+        side: Player,
+        condition: &'a str,
+        from: Option<&'a str>,
+    },
+    /// `|-swapsideconditions|`
+    SwapSideConditions,
+
+    // === Items, Abilities and Formes ===
+    /// `|-item|`
+    Item { pokemon: &'a Pokemon, item: &'a str },
+    /// `|-enditem|`
+    EndItem {
+        pokemon: &'a Pokemon,
+        item: &'a str,
+        from: Option<&'a str>,
+    },
+    /// `|-ability|`
+    Ability {
+        pokemon: &'a Pokemon,
+        ability: &'a str,
+        from: Option<&'a str>,
+        of: Option<&'a Pokemon>,
+    },
+    /// `|-endability|`
+    EndAbility(&'a Pokemon),
+    /// `|-transform|`
+    Transform {
+        pokemon: &'a Pokemon,
+        species: &'a str,
+    },
+    /// `|-mega|`
+    Mega(&'a Pokemon),
+    /// `|-terastallize|`
+    Terastallize {
+        pokemon: &'a Pokemon,
+        tera_type: &'a str,
+    },
+    /// `|detailschange|`
+    DetailsChange {
+        pokemon: &'a Pokemon,
+        details: &'a PokemonDetails,
+        hp_status: Option<&'a HpStatus>,
+    },
+    /// `|-formechange|`
+    FormeChange {
+        pokemon: &'a Pokemon,
+        species: &'a str,
+        hp_status: Option<&'a HpStatus>,
+    },
+
+    // === Outcomes ===
+    /// `|-crit|`
+    Crit(&'a Pokemon),
+    /// `|-miss|`
+    Miss(&'a Pokemon),
+    /// `|-immune|`
+    Immune {
+        pokemon: &'a Pokemon,
+        from: Option<&'a str>,
+    },
+    /// `|-fail|`
+    Fail {
+        pokemon: &'a Pokemon,
+        action: Option<&'a str>,
+        from: Option<&'a str>,
+    },
+
+    // === Battle End and Text ===
+    /// `|win|`
+    Win(&'a str),
+    /// `|tie|`
+    Tie,
+    /// `|-message|`, `|-hint|` and other server text
+    Message(&'a str),
+    /// A line the protocol parser didn't recognise
+    Raw(&'a str),
+
+    /// Anything that doesn't affect tracked state
+    Other,
+}
+
+impl<'a> From<&'a ServerMessage> for TrackerInput<'a> {
+    fn from(msg: &'a ServerMessage) -> Self {
+        match msg {
+            ServerMessage::BattlePlayer {
+                player, username, ..
+            } => Self::BattlePlayer {
+                player: *player,
+                username,
+            },
+            ServerMessage::Name { user, old_id, .. } => Self::Name {
+                username: &user.username,
+                old_id,
+            },
+            ServerMessage::TeamSize { player, .. } => Self::TeamSize { player: *player },
+            ServerMessage::Poke {
+                player, details, ..
+            } => Self::Poke {
+                player: *player,
+                details,
+            },
+            ServerMessage::ShowTeam { player, team } => Self::ShowTeam {
+                player: *player,
+                team,
+            },
+            ServerMessage::GameType(game_type) => Self::GameType(*game_type),
+            ServerMessage::Gen(generation) => Self::Gen(*generation),
+            ServerMessage::Tier(tier) => Self::Tier(tier),
+            ServerMessage::Rule(rule) => Self::Rule(rule),
+            ServerMessage::Turn(turn) => Self::Turn(*turn),
+            ServerMessage::Upkeep => Self::Upkeep,
+
+            ServerMessage::Switch {
+                pokemon,
+                details,
+                hp_status,
+            } => Self::Switch {
+                pokemon,
+                details,
+                hp_status: hp_status.as_ref(),
+            },
+            ServerMessage::Drag {
+                pokemon,
+                details,
+                hp_status,
+            } => Self::Drag {
+                pokemon,
+                details,
+                hp_status: hp_status.as_ref(),
+            },
+            ServerMessage::Faint(pokemon) => Self::Faint(pokemon),
+            ServerMessage::Move {
+                pokemon,
+                move_name,
+                target,
+                still,
+                from,
+                ..
+            } => Self::Move {
+                pokemon,
+                move_name,
+                target: target.as_ref(),
+                still: *still,
+                from: from.as_deref(),
+            },
+            ServerMessage::Cant {
+                pokemon, reason, ..
+            } => Self::Cant { pokemon, reason },
+            ServerMessage::Activate {
+                pokemon,
+                effect,
+                detail,
+                of,
+            } => Self::Activate {
+                pokemon: pokemon.as_ref(),
+                effect,
+                detail: detail.as_deref(),
+                of: of.as_ref(),
+            },
+            ServerMessage::Waiting { source, target } => Self::Waiting { source, target },
+            ServerMessage::Combine => Self::Combine,
+            ServerMessage::Prepare {
+                attacker,
+                move_name,
+                defender,
+            } => Self::Prepare {
+                attacker,
+                move_name,
+                defender: defender.as_ref(),
+            },
+            ServerMessage::Anim {
+                source, move_name, ..
+            } => Self::Anim { source, move_name },
+
+            ServerMessage::Damage {
+                pokemon,
+                hp_status,
+                from,
+            } => Self::Damage {
+                pokemon,
+                hp_status: hp_status.as_ref(),
+                from: from.as_deref(),
+            },
+            ServerMessage::Heal {
+                pokemon,
+                hp_status,
+                from,
+            } => Self::Heal {
+                pokemon,
+                hp_status: hp_status.as_ref(),
+                from: from.as_deref(),
+            },
+            ServerMessage::SetHp {
+                pokemon, hp_status, ..
+            } => Self::SetHp {
+                pokemon,
+                hp_status: hp_status.as_ref(),
+            },
+            ServerMessage::Status {
+                pokemon,
+                status,
+                from,
+            } => Self::Status {
+                pokemon,
+                status,
+                from: from.as_deref(),
+            },
+            ServerMessage::CureStatus { pokemon, .. } => Self::CureStatus(pokemon),
+            ServerMessage::CureTeam(pokemon) => Self::CureTeam(pokemon),
+
+            ServerMessage::Boost {
+                pokemon,
+                stat,
+                amount,
+                from,
+            } => Self::Boost {
+                pokemon,
+                stat: *stat,
+                amount: *amount,
+                from: from.as_deref(),
+            },
+            ServerMessage::Unboost {
+                pokemon,
+                stat,
+                amount,
+                ..
+            } => Self::Unboost {
+                pokemon,
+                stat: *stat,
+                amount: *amount,
+            },
+            ServerMessage::SetBoost {
+                pokemon,
+                stat,
+                amount,
+            } => Self::SetBoost {
+                pokemon,
+                stat: *stat,
+                amount: *amount,
+            },
+            ServerMessage::ClearBoost(pokemon) => Self::ClearBoost(pokemon),
+            ServerMessage::ClearAllBoost => Self::ClearAllBoost,
+            ServerMessage::InvertBoost(pokemon) => Self::InvertBoost(pokemon),
+            ServerMessage::ClearPositiveBoost { target, .. } => Self::ClearPositiveBoost(target),
+            ServerMessage::ClearNegativeBoost(pokemon) => Self::ClearNegativeBoost(pokemon),
+            ServerMessage::CopyBoost { source, target } => Self::CopyBoost { source, target },
+            ServerMessage::SwapBoost {
+                source,
+                target,
+                stats,
+                ..
+            } => Self::SwapBoost {
+                source,
+                target,
+                stats,
+            },
+
+            ServerMessage::VolatileStart { pokemon, effect } => {
+                Self::VolatileStart { pokemon, effect }
+            }
+            ServerMessage::VolatileEnd { pokemon, effect } => Self::VolatileEnd { pokemon, effect },
+            ServerMessage::SingleTurn { pokemon, move_name } => {
+                Self::SingleTurn { pokemon, move_name }
+            }
+            ServerMessage::Weather {
+                weather,
+                upkeep,
+                from,
+                of,
+            } => Self::Weather {
+                weather,
+                upkeep: *upkeep,
+                from: from.as_deref(),
+                of: of.as_ref(),
+            },
+            ServerMessage::FieldStart(condition) => Self::FieldStart(condition),
+            ServerMessage::FieldEnd(condition) => Self::FieldEnd(condition),
+            ServerMessage::SideStart { side, condition } => Self::SideStart {
+                side: side.player,
+                condition,
+            },
+            ServerMessage::SideEnd {
+                side,
+                condition,
+                from,
+            } => Self::SideEnd {
+                side: side.player,
+                condition,
+                from: from.as_deref(),
+            },
+            ServerMessage::SwapSideConditions => Self::SwapSideConditions,
+
+            ServerMessage::Item { pokemon, item, .. } => Self::Item { pokemon, item },
+            ServerMessage::EndItem {
+                pokemon,
+                item,
+                from,
+                ..
+            } => Self::EndItem {
+                pokemon,
+                item,
+                from: from.as_deref(),
+            },
+            ServerMessage::Ability {
+                pokemon,
+                ability,
+                from,
+                of,
+            } => Self::Ability {
+                pokemon,
+                ability,
+                from: from.as_deref(),
+                of: of.as_ref(),
+            },
+            ServerMessage::EndAbility(pokemon) => Self::EndAbility(pokemon),
+            ServerMessage::Transform { pokemon, species } => Self::Transform { pokemon, species },
+            ServerMessage::Mega { pokemon, .. } => Self::Mega(pokemon),
+            ServerMessage::Terastallize { pokemon, tera_type } => {
+                Self::Terastallize { pokemon, tera_type }
+            }
+            ServerMessage::DetailsChange {
+                pokemon,
+                details,
+                hp_status,
+            } => Self::DetailsChange {
+                pokemon,
+                details,
+                hp_status: hp_status.as_ref(),
+            },
+            ServerMessage::FormeChange {
+                pokemon,
+                species,
+                hp_status,
+            } => Self::FormeChange {
+                pokemon,
+                species,
+                hp_status: hp_status.as_ref(),
+            },
+
+            ServerMessage::Crit(target) => Self::Crit(target),
+            ServerMessage::Miss { source, .. } => Self::Miss(source),
+            ServerMessage::Immune { pokemon, from } => Self::Immune {
+                pokemon,
+                from: from.as_deref(),
+            },
+            ServerMessage::Fail {
+                pokemon,
+                action,
+                from,
+            } => Self::Fail {
+                pokemon,
+                action: action.as_deref(),
+                from: from.as_deref(),
+            },
+
+            ServerMessage::Win(winner) => Self::Win(winner),
+            ServerMessage::Tie => Self::Tie,
+            ServerMessage::Message(text) | ServerMessage::Hint(text) => Self::Message(text),
+            ServerMessage::Raw(line) => Self::Raw(line),
+
+            _ => Self::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::parse_server_message;
+
+    #[test]
+    fn test_from_server_message() {
+        let msg =
+            parse_server_message("|-damage|p2a: Gholdengo|50/100|[from] Stealth Rock").unwrap();
+        let TrackerInput::Damage {
+            pokemon,
+            hp_status,
+            from,
+        } = TrackerInput::from(&msg)
+        else {
+            panic!("expected damage");
+        };
+        assert_eq!(pokemon.name, "Gholdengo");
+        assert_eq!(hp_status.map(|hp| hp.current), Some(50));
+        assert_eq!(from, Some("Stealth Rock"));
+
+        let msg = parse_server_message("|-hint|Some text").unwrap();
+        assert_eq!(TrackerInput::from(&msg), TrackerInput::Message("Some text"));
+        let msg = parse_server_message("|-supereffective|p1a: Garchomp").unwrap();
+        assert_eq!(TrackerInput::from(&msg), TrackerInput::Other);
+    }
+}
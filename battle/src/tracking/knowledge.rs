@@ -0,0 +1,324 @@
+//! How much of each opposing Pokemon's set is still unknown
+//!
+//! A cheap uncertainty measure for exploration-style bots, read every turn, so
+//! nothing here allocates per Pokemon. Given candidate sets for a species, such
+//! as a random battle format's roles, it also gives the entropy over what the
+//! reveals so far leave open.
+
+use super::battle::TrackedBattle;
+use crate::id::to_id;
+use crate::types::{MOVE_SLOTS, PokemonState};
+
+/// What's known of a Pokemon's held item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ItemKnowledgeKind {
+    /// Not revealed yet
+    Unknown,
+
+    /// Revealed and still held
+    Known,
+
+    /// Revealed and since consumed, knocked off or stolen
+    Lost,
+}
+
+/// One set a Pokemon might have, such as a random battle role
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandidateSet {
+    /// Moves the set picks its four from, by name or ID
+    pub moves: Vec<String>,
+
+    /// Abilities it may have; empty if any
+    pub abilities: Vec<String>,
+
+    /// Items it may hold; empty if any
+    pub items: Vec<String>,
+
+    /// How likely the set is relative to the others given with it
+    pub weight: f32,
+}
+
+impl CandidateSet {
+    /// Whether the set allows every move, the ability and the item `poke` revealed
+    pub fn allows(&self, poke: &PokemonState) -> bool {
+        let listed = |options: &[String], value: &Option<String>| {
+            options.is_empty()
+                || value
+                    .as_deref()
+                    .is_none_or(|value| options.iter().any(|o| to_id(o) == to_id(value)))
+        };
+        poke.known_moves.len() <= self.slots()
+            && poke
+                .known_moves
+                .iter()
+                .all(|m| self.moves.iter().any(|o| to_id(o) == to_id(m)))
+            && listed(&self.abilities, &poke.known_ability)
+            && listed(&self.items, &poke.known_item)
+    }
+
+    fn slots(&self) -> usize {
+        self.moves.len().min(MOVE_SLOTS)
+    }
+
+    /// Bits of this set's moves, ability and item `poke` hasn't revealed
+    ///
+    /// Each unrevealed choice is taken as equally likely.
+    fn detail_entropy(&self, poke: &PokemonState) -> f32 {
+        let revealed = poke.known_moves.len();
+        let mut bits = if poke.moveset_complete() {
+            0.0
+        } else {
+            log2_choose(self.moves.len() - revealed, self.slots() - revealed)
+        };
+        if poke.known_ability.is_none() && self.abilities.len() > 1 {
+            bits += (self.abilities.len() as f32).log2();
+        }
+        if poke.known_item.is_none() && self.items.len() > 1 {
+            bits += (self.items.len() as f32).log2();
+        }
+        bits
+    }
+}
+
+/// How much is known of one Pokemon's set
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnowledgeSummary {
+    pub moves_revealed: u8,
+
+    /// Move slots it has: 4, or fewer when no candidate set has that many moves
+    pub moves_total: u8,
+
+    pub ability_known: bool,
+
+    pub item_state: ItemKnowledgeKind,
+
+    /// Bits of uncertainty over the candidate sets still possible and the
+    /// choices within them; None without candidate sets, or when none fit
+    ///
+    /// Usually falls with each reveal, though ruling out the likeliest set
+    /// can leave the rest more evenly matched.
+    pub set_entropy: Option<f32>,
+}
+
+impl KnowledgeSummary {
+    /// Move slots not revealed yet
+    pub fn moves_unrevealed(&self) -> u8 {
+        self.moves_total.saturating_sub(self.moves_revealed)
+    }
+}
+
+impl PokemonState {
+    /// What's been revealed of this Pokemon's set, without candidate sets
+    pub fn knowledge_summary(&self) -> KnowledgeSummary {
+        self.knowledge_summary_with(&[])
+    }
+
+    /// What's been revealed of this Pokemon's set, and the entropy over those
+    /// of `sets` it still fits
+    pub fn knowledge_summary_with(&self, sets: &[CandidateSet]) -> KnowledgeSummary {
+        let revealed = self.known_moves.len();
+        let fitting = || {
+            sets.iter()
+                .filter(|set| set.weight > 0.0 && set.allows(self))
+        };
+        let total_weight: f32 = fitting().map(|set| set.weight).sum();
+        let set_entropy = (total_weight > 0.0).then(|| {
+            fitting()
+                .map(|set| {
+                    let p = set.weight / total_weight;
+                    p * (set.detail_entropy(self) - p.log2())
+                })
+                .sum()
+        });
+        let slots = fitting()
+            .map(CandidateSet::slots)
+            .max()
+            .unwrap_or(MOVE_SLOTS);
+        let item_state = match (&self.known_item, self.item_consumed) {
+            (None, _) => ItemKnowledgeKind::Unknown,
+            (Some(_), false) => ItemKnowledgeKind::Known,
+            (Some(_), true) => ItemKnowledgeKind::Lost,
+        };
+        KnowledgeSummary {
+            moves_revealed: revealed as u8,
+            // A set listed in full has nothing left to reveal, however short
+            moves_total: if self.moveset_complete() {
+                revealed as u8
+            } else {
+                slots.max(revealed) as u8
+            },
+            ability_known: self.known_ability.is_some(),
+            item_state,
+            set_entropy,
+        }
+    }
+}
+
+impl TrackedBattle {
+    /// Knowledge summaries for the Pokemon seen on the foes' sides, by player
+    /// and then in the order they were seen; empty without a viewpoint
+    pub fn opponent_knowledge(&self) -> Vec<KnowledgeSummary> {
+        self.opponent_knowledge_with(|_| &[])
+    }
+
+    /// Knowledge summaries for the foes' Pokemon, with `sets` giving the
+    /// candidate sets for each (e.g. the random battle roles for its species)
+    pub fn opponent_knowledge_with<'a>(
+        &self,
+        sets: impl Fn(&PokemonState) -> &'a [CandidateSet],
+    ) -> Vec<KnowledgeSummary> {
+        let Some(viewpoint) = self.viewpoint() else {
+            return Vec::new();
+        };
+        self.sides()
+            .filter(|side| !self.same_team(side.player, viewpoint))
+            .flat_map(|side| &side.pokemon)
+            .map(|poke| poke.knowledge_summary_with(sets(poke)))
+            .collect()
+    }
+}
+
+/// log2 of the number of ways to pick `k` of `n`
+fn log2_choose(n: usize, k: usize) -> f32 {
+    (0..k.min(n))
+        .map(|i| ((n - i) as f32 / (i + 1) as f32).log2())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    fn set(moves: &[&str], abilities: &[&str], items: &[&str], weight: f32) -> CandidateSet {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        CandidateSet {
+            moves: names(moves),
+            abilities: names(abilities),
+            items: names(items),
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_sets_match_on_showdown_ids() {
+        crate::id::register_alias("Kazam Test Slam", "Body Slam");
+        let mut poke = PokemonState::new("Snorlax", 88);
+        poke.record_move("Kazam Test Slam");
+        poke.record_ability("Thick-Fat");
+        assert!(set(&["Body Slam"], &["Thick Fat"], &[], 1.0).allows(&poke));
+        assert!(!set(&["Curse"], &[], &[], 1.0).allows(&poke));
+    }
+
+    #[test]
+    fn test_reveals_narrow_random_battle_roles() {
+        let roles = [
+            set(
+                &[
+                    "Earthquake",
+                    "Outrage",
+                    "Stone Edge",
+                    "Swords Dance",
+                    "Fire Fang",
+                    "Scale Shot",
+                ],
+                &["Rough Skin"],
+                &["Life Orb", "Loaded Dice"],
+                3.0,
+            ),
+            set(
+                &[
+                    "earthquake",
+                    "stealthrock",
+                    "spikes",
+                    "dragontail",
+                    "fireblast",
+                ],
+                &["Rough Skin", "Sand Veil"],
+                &["Rocky Helmet", "Leftovers"],
+                1.0,
+            ),
+        ];
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let apply = |battle: &mut TrackedBattle, line: &str| {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        };
+        for line in [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|switch|p1a: Pikachu|Pikachu, L50|100/100",
+        ] {
+            apply(&mut battle, line);
+        }
+
+        let reveals = [
+            "|switch|p2a: Garchomp|Garchomp, L50|100/100",
+            "|move|p2a: Garchomp|Earthquake|p1a: Pikachu",
+            // Only the second role has Stealth Rock
+            "|move|p2a: Garchomp|Stealth Rock|p1a: Pikachu",
+            "|-ability|p2a: Garchomp|Rough Skin",
+            "|-item|p2a: Garchomp|Rocky Helmet",
+            "|move|p2a: Garchomp|Spikes|p1a: Pikachu",
+            "|move|p2a: Garchomp|Dragon Tail|p1a: Pikachu",
+        ];
+        let summaries: Vec<KnowledgeSummary> = reveals
+            .iter()
+            .map(|line| {
+                apply(&mut battle, line);
+                battle.opponent_knowledge_with(|_| &roles)[0]
+            })
+            .collect();
+        let entropies: Vec<f32> = summaries.iter().map(|s| s.set_entropy.unwrap()).collect();
+        assert!(
+            entropies.windows(2).all(|pair| pair[1] < pair[0]),
+            "{entropies:?}"
+        );
+        // Two of three moves, the ability and the item are open in the one role left
+        assert!((entropies[2] - (3f32.log2() + 1.0 + 1.0)).abs() < 1e-4);
+        assert_eq!(entropies[6], 0.0);
+
+        let partial = summaries[3];
+        assert_eq!(partial.moves_revealed, 2);
+        assert_eq!(partial.moves_total, 4);
+        assert_eq!(partial.moves_unrevealed(), 2);
+        assert!(partial.ability_known);
+        assert_eq!(partial.item_state, ItemKnowledgeKind::Unknown);
+        assert_eq!(summaries[4].item_state, ItemKnowledgeKind::Known);
+
+        // Without candidate sets there's no entropy and four slots
+        apply(
+            &mut battle,
+            "|-enditem|p2a: Garchomp|Rocky Helmet|[from] move: Knock Off",
+        );
+        let plain = battle.opponent_knowledge();
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].set_entropy, None);
+        assert_eq!(plain[0].moves_unrevealed(), 0);
+        assert_eq!(plain[0].item_state, ItemKnowledgeKind::Lost);
+    }
+
+    #[test]
+    fn test_sets_with_few_moves_and_no_fit() {
+        let mut ditto = PokemonState::new("Ditto", 88);
+        let transform = [set(&["Transform"], &["Imposter"], &["Choice Scarf"], 1.0)];
+        let summary = ditto.knowledge_summary_with(&transform);
+        assert_eq!(summary.moves_total, 1);
+        assert_eq!(summary.set_entropy, Some(0.0));
+
+        // A reveal no set allows leaves nothing to measure
+        ditto.known_moves.push("Tackle".to_string());
+        let summary = ditto.knowledge_summary_with(&transform);
+        assert_eq!(summary.set_entropy, None);
+        assert_eq!(summary.moves_total, 4);
+        assert_eq!(summary.moves_unrevealed(), 3);
+
+        // A request listing one move leaves none unrevealed
+        ditto.set_request_moves(&["transform".to_string()]);
+        let summary = ditto.knowledge_summary_with(&transform);
+        assert_eq!(summary.moves_total, 1);
+        assert_eq!(summary.moves_unrevealed(), 0);
+    }
+}
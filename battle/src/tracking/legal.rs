@@ -0,0 +1,316 @@
+//! Move legality from the latest request combined with tracked state
+
+use kazam_protocol::{BattleRequest, MoveSlot};
+
+use super::battle::TrackedBattle;
+use crate::query::moves::{is_healing_move, is_status_move};
+use crate::types::{PokemonState, Volatile, to_id};
+
+/// A move our active Pokemon can select this turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegalMove {
+    /// Position in the request's move list (0-indexed)
+    pub index: usize,
+
+    /// Display name
+    pub name: String,
+
+    /// Move ID
+    pub id: String,
+
+    /// Target type from the request
+    pub target: String,
+}
+
+impl LegalMove {
+    /// Choice string for this move (e.g. "move 2")
+    pub fn choice(&self) -> String {
+        format!("move {}", self.index + 1)
+    }
+}
+
+impl TrackedBattle {
+    /// Moves our Pokemon in `slot` can select, per the latest request and tracked state.
+    ///
+    /// Starts from the request's usable moves (not disabled, PP left) and drops
+    /// the ones the request's `disabled` flags may not reflect yet:
+    /// - Taunt removes status moves
+    /// - Encore restricts to the encored move
+    /// - Heal Block removes healing moves
+    /// - Torment removes the move used last
+    /// - an opposing Imprison user removes moves it also knows
+    /// - a Choice item or Gorilla Tactics restricts to the first move used
+    ///
+    /// Empty when there is no request for the slot or nothing is left to select.
+    pub fn legal_moves(&self, slot: usize) -> Vec<LegalMove> {
+        match &self.last_request {
+            Some(request) => self.legal_moves_in(request, slot),
+            None => Vec::new(),
+        }
+    }
+
+    /// [`legal_moves`](Self::legal_moves) against a given request instead of the latest one
+    pub fn legal_moves_in(&self, request: &BattleRequest, slot: usize) -> Vec<LegalMove> {
+        let Some(active) = request.active.as_ref().and_then(|active| active.get(slot)) else {
+            return Vec::new();
+        };
+        let poke = self.me().and_then(|side| side.active(slot));
+        let imprisoned: Vec<String> = self
+            .all_pokemon()
+            .theirs()
+            .active()
+            .map(|p| p.pokemon)
+            .filter(|p| p.has_volatile(&Volatile::Imprison))
+            .flat_map(|p| p.known_moves.iter().map(|m| to_id(m)))
+            .collect();
+
+        active
+            .available_moves()
+            .into_iter()
+            .filter(|(_, move_slot)| poke.is_none_or(|poke| allows(poke, move_slot, &imprisoned)))
+            .map(|(index, move_slot)| LegalMove {
+                index,
+                name: move_slot.name.clone(),
+                id: move_slot.id.clone(),
+                target: move_slot.target.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Whether tracked state lets `poke` select the move in `move_slot`
+fn allows(poke: &PokemonState, move_slot: &MoveSlot, imprisoned: &[String]) -> bool {
+    let id = move_slot.id.as_str();
+    let blocked = poke
+        .volatiles
+        .iter()
+        .filter(|v| v.blocks_moves())
+        .any(|v| match v {
+            Volatile::Taunt => is_status_move(id, &move_slot.target),
+            Volatile::HealBlock => is_healing_move(id),
+            Volatile::Torment => poke.last_move.as_deref().is_some_and(|m| to_id(m) == id),
+            // The request flags the Disabled move, Encore locks in locked_move,
+            // and Imprison restricts the holder's foes
+            _ => false,
+        });
+    if blocked {
+        return false;
+    }
+    if imprisoned.iter().any(|m| m == id) {
+        return false;
+    }
+    locked_move(poke).is_none_or(|locked| to_id(locked) == id)
+}
+
+/// Move `poke` is locked into by Encore, a Choice item or Gorilla Tactics
+fn locked_move(poke: &PokemonState) -> Option<&str> {
+    let last_move = poke.last_move.as_deref()?;
+    let choice_item = !poke.item_consumed
+        && poke.known_item.as_deref().is_some_and(|item| {
+            matches!(
+                to_id(item).as_str(),
+                "choiceband" | "choicescarf" | "choicespecs"
+            )
+        });
+    let gorilla_tactics = poke
+        .current_ability()
+        .is_some_and(|ability| to_id(ability) == "gorillatactics");
+
+    // Dynamax suspends Choice locks but not Encore
+    let encored = poke.has_volatile(&Volatile::Encore);
+    (encored || (!poke.dynamaxed && (choice_item || gorilla_tactics))).then_some(last_move)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    const MOVES: [(&str, &str); 4] = [
+        ("Earthquake", "allAdjacent"),
+        ("Swords Dance", "self"),
+        ("Recover", "self"),
+        ("Toxic", "normal"),
+    ];
+
+    fn apply_log(battle: &mut TrackedBattle, log: &str) {
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+    }
+
+    fn battle(item: &str, ability: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(
+            &mut battle,
+            r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Gengar|Gengar, L50, M|100/100
+|turn|1"#,
+        );
+
+        let moves: Vec<_> = MOVES
+            .iter()
+            .map(|(name, target)| {
+                serde_json::json!({
+                    "move": name,
+                    "id": to_id(name),
+                    "pp": 16,
+                    "maxpp": 16,
+                    "target": target,
+                    "disabled": false
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "active": [{"moves": moves}],
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Garchomp",
+                    "details": "Garchomp, L50, M",
+                    "condition": "183/183",
+                    "active": true,
+                    "moves": MOVES.iter().map(|(name, _)| to_id(name)).collect::<Vec<_>>(),
+                    "ability": ability,
+                    "item": item
+                }]
+            },
+            "rqid": 1
+        });
+        battle.apply_request(&BattleRequest::parse(&json).unwrap());
+        battle
+    }
+
+    fn legal_names(battle: &TrackedBattle) -> Vec<String> {
+        battle.legal_moves(0).into_iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn test_unrestricted_matches_request() {
+        let battle = battle("leftovers", "roughskin");
+        let legal = battle.legal_moves(0);
+        assert_eq!(legal.len(), 4);
+        assert_eq!(legal[3].choice(), "move 4");
+        assert!(battle.legal_moves(1).is_empty());
+        assert!(TrackedBattle::new().legal_moves(0).is_empty());
+    }
+
+    #[test]
+    fn test_taunt_removes_status_moves() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(&mut battle, "|-start|p1a: Garchomp|move: Taunt");
+        assert_eq!(legal_names(&battle), vec!["Earthquake"]);
+    }
+
+    #[test]
+    fn test_encore_restricts_to_last_move() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(
+            &mut battle,
+            r#"|move|p1a: Garchomp|Swords Dance|p1a: Garchomp
+|-boost|p1a: Garchomp|atk|2
+|turn|2
+|-start|p1a: Garchomp|Encore"#,
+        );
+        assert_eq!(legal_names(&battle), vec!["Swords Dance"]);
+    }
+
+    #[test]
+    fn test_heal_block_removes_healing_moves() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(&mut battle, "|-start|p1a: Garchomp|move: Heal Block");
+        assert_eq!(
+            legal_names(&battle),
+            vec!["Earthquake", "Swords Dance", "Toxic"]
+        );
+    }
+
+    #[test]
+    fn test_psychic_noise_heal_block_removes_healing_moves() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(&mut battle, "|-start|p1a: Garchomp|move: Psychic Noise");
+        assert_eq!(
+            legal_names(&battle),
+            vec!["Earthquake", "Swords Dance", "Toxic"]
+        );
+        apply_log(&mut battle, "|-end|p1a: Garchomp|move: Heal Block");
+        assert_eq!(legal_names(&battle).len(), 4);
+    }
+
+    #[test]
+    fn test_embargo_keeps_moves() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(&mut battle, "|-start|p1a: Garchomp|move: Embargo");
+        assert_eq!(legal_names(&battle).len(), 4);
+        assert!(!battle.me().unwrap().active(0).unwrap().can_use_item());
+    }
+
+    #[test]
+    fn test_torment_removes_last_move() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(
+            &mut battle,
+            r#"|move|p1a: Garchomp|Earthquake|p2a: Gengar
+|-immune|p2a: Gengar
+|turn|2
+|-start|p1a: Garchomp|Torment"#,
+        );
+        assert_eq!(
+            legal_names(&battle),
+            vec!["Swords Dance", "Recover", "Toxic"]
+        );
+    }
+
+    #[test]
+    fn test_opposing_imprison_removes_shared_moves() {
+        let mut battle = battle("leftovers", "roughskin");
+        apply_log(
+            &mut battle,
+            r#"|move|p2a: Gengar|Toxic|p1a: Garchomp
+|-status|p1a: Garchomp|tox
+|turn|2
+|move|p2a: Gengar|Imprison|p2a: Gengar
+|-start|p2a: Gengar|move: Imprison"#,
+        );
+        assert_eq!(
+            legal_names(&battle),
+            vec!["Earthquake", "Swords Dance", "Recover"]
+        );
+    }
+
+    #[test]
+    fn test_choice_lock_after_first_move() {
+        let mut battle = battle("choicescarf", "roughskin");
+        assert_eq!(battle.legal_moves(0).len(), 4);
+
+        apply_log(&mut battle, "|move|p1a: Garchomp|Earthquake|p2a: Gengar");
+        assert_eq!(legal_names(&battle), vec!["Earthquake"]);
+
+        // Losing the item ends the lock
+        apply_log(
+            &mut battle,
+            "|-enditem|p1a: Garchomp|Choice Scarf|[from] move: Knock Off",
+        );
+        assert_eq!(battle.legal_moves(0).len(), 4);
+    }
+
+    #[test]
+    fn test_gorilla_tactics_lock_clears_on_switch() {
+        let mut battle = battle("", "gorillatactics");
+        apply_log(&mut battle, "|move|p1a: Garchomp|Toxic|p2a: Gengar");
+        assert_eq!(legal_names(&battle), vec!["Toxic"]);
+
+        apply_log(
+            &mut battle,
+            r#"|switch|p1a: Dragonite|Dragonite, L50, M|100/100
+|turn|2
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100"#,
+        );
+        assert_eq!(battle.legal_moves(0).len(), 4);
+    }
+}
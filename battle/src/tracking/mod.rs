@@ -1,8 +1,37 @@
 //! Battle state tracking from server messages
 
 mod battle;
+mod config;
+mod evidence;
+mod input;
+mod knowledge;
+mod legal;
+mod paradox;
+mod reconcile;
+mod roster;
+mod rules;
+mod scouting;
 mod snapshot;
+mod staleness;
+mod team_sheet;
 mod updater;
+mod usage;
+mod variance;
 
-pub use battle::{BattleKnowledge, TrackedBattle, player_to_index, position_to_slot};
+pub use battle::{
+    BattleKnowledge, CombinedMove, ConditionRemoval, Inconsistency, MoveOrder, OrderException,
+    ReflectedMove, Severity, SwitchKind, SwitchRecord, TrackedBattle, player_to_index,
+    position_to_slot,
+};
+pub use config::{StrictnessMode, TrackerConfig};
+pub use input::TrackerInput;
+pub use knowledge::{CandidateSet, ItemKnowledgeKind, KnowledgeSummary};
+pub use legal::LegalMove;
+pub use reconcile::{Reconciled, ReconciliationEvent};
+pub use roster::{AllPokemon, PokemonRef, PokemonRefMut};
+pub use rules::{Clause, RuleContext, RuleWarning};
+pub use scouting::{ScoutedPokemon, ScoutingReport};
 pub use snapshot::{BattleSnapshot, TurnSnapshot};
+pub use staleness::{EndlessBattleWarning, StalenessState};
+pub use usage::{PokemonUsage, SideUsage, UsageSummary};
+pub use variance::{BASE_CRIT_RATE, SideVariance, VarianceReport};
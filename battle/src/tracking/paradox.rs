@@ -0,0 +1,250 @@
+//! Protosynthesis and Quark Drive boosts
+//!
+//! A Paradox Pokemon with one of these abilities raises its highest stat in
+//! sun or Electric Terrain, or by consuming Booster Energy when the field
+//! doesn't: 1.3x, or 1.5x for Speed. The server names the stat in the
+//! volatile ("protosynthesisatk"). A boost from Booster Energy lasts until the
+//! Pokemon switches out; one from the field ends with the weather or terrain.
+
+use kazam_protocol::{Pokemon, Stat};
+
+use super::battle::TrackedBattle;
+use crate::types::{FieldState, PokemonState, StatConstraint, Terrain, Volatile, Weather, to_id};
+
+/// Multiplier on a boosted stat other than Speed, 5325/4096 in the simulator
+const BOOST: f32 = 5325.0 / 4096.0;
+
+/// Multiplier on a boosted Speed
+const SPEED_BOOST: f32 = 1.5;
+
+/// Stats the boost picks from, in the order the simulator breaks ties
+const BOOSTABLE: [Stat; 5] = [Stat::Atk, Stat::Def, Stat::Spa, Stat::Spd, Stat::Spe];
+
+impl FieldState {
+    /// Whether the weather or terrain powers a Protosynthesis or Quark Drive boost
+    pub fn powers_paradox_boost(&self, volatile: &Volatile) -> bool {
+        match volatile {
+            Volatile::Protosynthesis(_) => {
+                matches!(self.weather, Some(Weather::Sun | Weather::HarshSun))
+            }
+            Volatile::QuarkDrive(_) => self.terrain == Some(Terrain::Electric),
+            _ => false,
+        }
+    }
+}
+
+impl PokemonState {
+    /// Stat a Protosynthesis or Quark Drive boost raises
+    pub fn paradox_boost(&self) -> Option<Stat> {
+        self.volatiles.iter().find_map(Volatile::paradox_stat)
+    }
+
+    /// Multiplier Protosynthesis or Quark Drive puts on `stat`; 1 unless boosted
+    pub fn paradox_multiplier(&self, stat: Stat) -> f32 {
+        match self.paradox_boost() {
+            Some(Stat::Spe) if stat == Stat::Spe => SPEED_BOOST,
+            Some(boosted) if boosted == stat => BOOST,
+            _ => 1.0,
+        }
+    }
+
+    /// Record a boost starting; without the field to power it, it came from
+    /// Booster Energy, which tells us the item
+    fn start_paradox_boost(&mut self, volatile: Volatile, powered: bool, infer_item: bool) {
+        let Some(stat) = volatile.paradox_stat() else {
+            return;
+        };
+        self.end_paradox_boost();
+        self.add_volatile(volatile);
+        self.paradox_from_booster = !powered;
+        if !powered && infer_item && self.known_item.is_none() {
+            self.record_item("Booster Energy");
+            self.consume_item();
+        }
+        // The boost picks the highest stat after stages, so the raw stats are
+        // only ordered when there are none; earlier stats win ties
+        if self.transformed.is_none() && BOOSTABLE.iter().all(|&s| self.boosts.get(s) == 0) {
+            let before = BOOSTABLE
+                .iter()
+                .position(|&s| s == stat)
+                .unwrap_or_default();
+            for (index, &lower) in BOOSTABLE.iter().enumerate() {
+                if lower != stat {
+                    self.add_stat_constraint(StatConstraint {
+                        higher: stat,
+                        lower,
+                        or_equal: index > before,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Drop a Protosynthesis or Quark Drive boost
+    fn end_paradox_boost(&mut self) {
+        self.volatiles.retain(|v| v.paradox_stat().is_none());
+        self.paradox_from_booster = false;
+    }
+}
+
+impl TrackedBattle {
+    /// A |-start| naming a boosted stat
+    pub(crate) fn observe_paradox_start(&mut self, pokemon: &Pokemon, volatile: Volatile) {
+        let powered = self.field.powers_paradox_boost(&volatile);
+        let infer_item = self.config.tracks_items_abilities();
+        if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+            poke.start_paradox_boost(volatile, powered, infer_item);
+        }
+    }
+
+    /// A |-end| of Protosynthesis or Quark Drive, which doesn't name the stat;
+    /// returns whether `effect` was one
+    pub(crate) fn observe_paradox_end(&mut self, pokemon: &Pokemon, effect: &str) -> bool {
+        let effect = to_id(effect.strip_prefix("ability: ").unwrap_or(effect));
+        if !matches!(effect.as_str(), "protosynthesis" | "quarkdrive") {
+            return false;
+        }
+        if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+            poke.end_paradox_boost();
+        }
+        true
+    }
+
+    /// End the boosts the weather or terrain powered once it's gone
+    pub(crate) fn end_unpowered_paradox_boosts(&mut self) {
+        let field = &self.field;
+        for side in self.sides.iter_mut().flatten() {
+            for poke in &mut side.pokemon {
+                let unpowered = poke.volatiles.iter().any(|v| {
+                    v.paradox_stat().is_some()
+                        && !poke.paradox_from_booster
+                        && !field.powers_paradox_boost(v)
+                });
+                if unpowered {
+                    poke.end_paradox_boost();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, PokemonStats, parse_server_message};
+
+    /// Our lead sets the field, then their Paradox Pokemon comes in
+    fn paradox_battle(lead: &str, field: &str, paradox: &str, log: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let setup = format!(
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n|gametype|singles\n|gen|9\n|start\n\
+             |switch|p1a: {lead}|{lead}, L50|100/100\n{field}\n\
+             |switch|p2a: {paradox}|{paradox}, L50|100/100\n{log}"
+        );
+        for line in setup.lines().filter(|line| !line.is_empty()) {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    const ELECTRIC_TERRAIN: &str =
+        "|-fieldstart|move: Electric Terrain|[from] ability: Hadron Engine|[of] p1a: Miraidon";
+
+    const SUN: &str = "|-weather|SunnyDay|[from] ability: Drought|[of] p1a: Torkoal";
+
+    fn theirs(battle: &TrackedBattle) -> &PokemonState {
+        &battle.get_side(Player::P2).unwrap().pokemon[0]
+    }
+
+    fn apply(battle: &mut TrackedBattle, line: &str) {
+        battle.apply_message(&parse_server_message(line).unwrap());
+    }
+
+    #[test]
+    fn test_flutter_mane_under_electric_terrain_used_booster_energy() {
+        // Electric Terrain powers Quark Drive, not Protosynthesis
+        let mut battle = paradox_battle(
+            "Miraidon",
+            ELECTRIC_TERRAIN,
+            "Flutter Mane",
+            "|-activate|p2a: Flutter Mane|ability: Protosynthesis|[fromitem]\n\
+             |-start|p2a: Flutter Mane|protosynthesisspa",
+        );
+        let mane = theirs(&battle);
+        assert_eq!(mane.paradox_boost(), Some(Stat::Spa));
+        assert!((mane.paradox_multiplier(Stat::Spa) - 1.3).abs() < 0.001);
+        assert_eq!(mane.paradox_multiplier(Stat::Spe), 1.0);
+        assert!(mane.paradox_from_booster);
+        assert_eq!(mane.known_item.as_deref(), Some("Booster Energy"));
+        assert!(mane.item_consumed);
+        // Special Attack beat Attack and Defense outright and ties Speed at most
+        assert!(mane.stat_constraints.contains(&StatConstraint {
+            higher: Stat::Spa,
+            lower: Stat::Def,
+            or_equal: false,
+        }));
+        assert!(mane.stat_constraints.contains(&StatConstraint {
+            higher: Stat::Spa,
+            lower: Stat::Spe,
+            or_equal: true,
+        }));
+
+        // The field ending leaves it be; switching out ends it
+        apply(&mut battle, "|-fieldend|move: Electric Terrain");
+        assert_eq!(theirs(&battle).paradox_boost(), Some(Stat::Spa));
+        apply(&mut battle, "|switch|p2a: Amoonguss|Amoonguss, L50|100/100");
+        assert_eq!(theirs(&battle).paradox_boost(), None);
+        assert!(!theirs(&battle).paradox_from_booster);
+    }
+
+    #[test]
+    fn test_field_powered_boosts_end_with_the_field() {
+        let mut battle = paradox_battle(
+            "Torkoal",
+            SUN,
+            "Flutter Mane",
+            "|-activate|p2a: Flutter Mane|ability: Protosynthesis\n\
+             |-start|p2a: Flutter Mane|protosynthesisspe",
+        );
+        let mane = theirs(&battle);
+        assert_eq!(mane.paradox_multiplier(Stat::Spe), 1.5);
+        assert!(!mane.paradox_from_booster);
+        assert_eq!(mane.known_item, None);
+        apply(&mut battle, "|-weather|none");
+        assert_eq!(theirs(&battle).paradox_boost(), None);
+
+        let mut terrain = paradox_battle(
+            "Miraidon",
+            ELECTRIC_TERRAIN,
+            "Iron Bundle",
+            "|-activate|p2a: Iron Bundle|ability: Quark Drive\n\
+             |-start|p2a: Iron Bundle|quarkdrivespe",
+        );
+        assert_eq!(theirs(&terrain).known_item, None);
+        apply(&mut terrain, "|-fieldend|move: Electric Terrain");
+        assert_eq!(theirs(&terrain).paradox_boost(), None);
+    }
+
+    #[test]
+    fn test_effective_stat_and_end_message() {
+        let mut battle = paradox_battle(
+            "Torkoal",
+            SUN,
+            "Great Tusk",
+            "|-activate|p2a: Great Tusk|ability: Protosynthesis\n\
+             |-start|p2a: Great Tusk|protosynthesisatk",
+        );
+        battle.get_side_mut(Player::P2).unwrap().pokemon[0].set_stats(&PokemonStats {
+            atk: 160,
+            def: 151,
+            spa: 63,
+            spd: 73,
+            spe: 107,
+        });
+        assert_eq!(theirs(&battle).effective_stat(Stat::Atk), Some(208));
+        assert_eq!(theirs(&battle).effective_stat(Stat::Spe), Some(107));
+
+        apply(&mut battle, "|-end|p2a: Great Tusk|Protosynthesis");
+        assert_eq!(theirs(&battle).effective_stat(Stat::Atk), Some(160));
+    }
+}
@@ -0,0 +1,590 @@
+//! Reconciling our side's tracked state with each request
+//!
+//! The request is the server's truth for our own Pokemon, and it is trusted
+//! as soon as it arrives. It is also sent ahead of the log leading up to it,
+//! so a disagreement with the tracked state is usually just early. Each one
+//! is held until the next |turn| or request: those the log explains by then,
+//! with a message touching the same value, are dropped, and the rest are
+//! recorded as [`ReconciliationEvent`]s. An HP change no message accounts for
+//! is also a minor [`Inconsistency::UnexplainedHpChange`].
+
+use kazam_protocol::{MoveSlot, Player, Pokemon, PokemonStats, SidePokemon, Stat};
+
+use super::battle::{Inconsistency, TrackedBattle};
+use super::input::TrackerInput;
+use crate::types::{PokemonState, SplitKind, Status};
+
+/// A value the request had different from the tracked state
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reconciled {
+    /// Current HP
+    Hp { belief: u32, request: u32 },
+
+    /// Non-volatile status
+    Status {
+        belief: Option<Status>,
+        request: Option<Status>,
+    },
+
+    /// Held item
+    Item {
+        belief: Option<String>,
+        request: Option<String>,
+    },
+
+    /// PP left on a move, compared with the previous move request
+    Pp {
+        move_id: String,
+        belief: u32,
+        request: u32,
+    },
+
+    /// A raw stat, such as one Guard Split averaged
+    Stat {
+        stat: Stat,
+        belief: u32,
+        request: u32,
+    },
+}
+
+impl Reconciled {
+    /// How far the request moved a number: HP, PP or a stat, positive when it went up
+    pub fn delta(&self) -> Option<i64> {
+        match self {
+            Self::Hp { belief, request }
+            | Self::Pp {
+                belief, request, ..
+            }
+            | Self::Stat {
+                belief, request, ..
+            } => Some(i64::from(*request) - i64::from(*belief)),
+            Self::Status { .. } | Self::Item { .. } => None,
+        }
+    }
+
+    fn touch(&self) -> Touch {
+        match self {
+            Self::Hp { .. } => Touch::Hp,
+            Self::Status { .. } => Touch::Status,
+            Self::Item { .. } => Touch::Item,
+            Self::Pp { .. } => Touch::Pp,
+            Self::Stat { .. } => Touch::Stat,
+        }
+    }
+}
+
+/// A correction the request made that the log never explained
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReconciliationEvent {
+    /// Turn the correction was confirmed on
+    pub turn: u32,
+
+    /// Our Pokemon the request corrected
+    pub pokemon: Pokemon,
+
+    /// What the tracked state had and what the request said
+    pub change: Reconciled,
+}
+
+/// Part of a Pokemon's state a message can account for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Touch {
+    Hp,
+    Status,
+    Item,
+    Pp,
+    Stat,
+}
+
+/// Request corrections so far, and those still waiting on the log
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Reconciliation {
+    events: Vec<ReconciliationEvent>,
+
+    /// Disagreements from the latest request, by our `pokemon` index
+    #[cfg_attr(feature = "serde", serde(default))]
+    pending: Vec<(usize, Reconciled)>,
+
+    /// Values messages have changed since that request, by our `pokemon` index
+    #[cfg_attr(feature = "serde", serde(default))]
+    touched: Vec<(usize, Touch)>,
+
+    /// Whether the latest request came ahead of the log still being applied,
+    /// until the next |turn|
+    #[cfg_attr(feature = "serde", serde(default))]
+    ahead: bool,
+}
+
+impl TrackedBattle {
+    /// Corrections requests made to our side that no message explained, in order
+    ///
+    /// The count is a measure of how far the tracker drifts from the server.
+    pub fn reconciliations(&self) -> &[ReconciliationEvent] {
+        &self.reconciliation.events
+    }
+
+    /// Differences between one of our tracked Pokemon and its request entry
+    ///
+    /// Only called once an earlier request has been applied, so the tracked
+    /// state isn't just empty.
+    pub(crate) fn request_drift(
+        poke: &PokemonState,
+        req_poke: &SidePokemon,
+        tracks_items: bool,
+    ) -> Vec<Reconciled> {
+        let mut drift = Vec::new();
+        let fainted = req_poke.status() == Some("fnt");
+        let hp = req_poke.hp().map(|(current, _)| current);
+        if let Some(request) = hp.or(fainted.then_some(0))
+            && poke.hp_max.is_some()
+            && poke.hp_current != request
+        {
+            drift.push(Reconciled::Hp {
+                belief: poke.hp_current,
+                request,
+            });
+        }
+        let status = req_poke.status().and_then(Status::from_protocol);
+        if !req_poke.condition.is_empty() && !fainted && poke.status != status {
+            drift.push(Reconciled::Status {
+                belief: poke.status,
+                request: status,
+            });
+        }
+        let item = Some(req_poke.item.clone()).filter(|item| !item.is_empty());
+        if tracks_items && poke.known_item != item {
+            drift.push(Reconciled::Item {
+                belief: poke.known_item.clone(),
+                request: item,
+            });
+        }
+        // Split stats change once the split is known; the log already explained it
+        let split = poke.stat_split.map(SplitKind::stats);
+        if let Some(stats) = &poke.stats
+            && req_poke.stats != PokemonStats::default()
+        {
+            for stat in [Stat::Atk, Stat::Def, Stat::Spa, Stat::Spd, Stat::Spe] {
+                let (Some(belief), Some(request)) = (stats.get(stat), req_poke.stats.get(stat))
+                else {
+                    continue;
+                };
+                if belief != request && !split.is_some_and(|split| split.contains(&stat)) {
+                    drift.push(Reconciled::Stat {
+                        stat,
+                        belief,
+                        request,
+                    });
+                }
+            }
+        }
+        drift
+    }
+
+    /// PP differences between a Pokemon's previous and new move slots
+    ///
+    /// Slots locked into a move show no PP and are skipped.
+    pub(crate) fn pp_drift(previous: &[MoveSlot], slots: &[MoveSlot]) -> Vec<Reconciled> {
+        slots
+            .iter()
+            .filter_map(|slot| {
+                let before = previous.iter().find(|p| p.id == slot.id)?;
+                (before.pp != slot.pp && before.max_pp > 0 && slot.max_pp > 0).then(|| {
+                    Reconciled::Pp {
+                        move_id: slot.id.clone(),
+                        belief: before.pp,
+                        request: slot.pp,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// One of our Pokemon's stat as it was before the latest request
+    ///
+    /// None unless that request is ahead of the log being applied, when the
+    /// tracked stats already show what the log has yet to do.
+    pub(crate) fn stat_before_request(&self, idx: usize, stat: Stat) -> Option<u32> {
+        if !self.reconciliation.ahead {
+            return None;
+        }
+        let held = self
+            .reconciliation
+            .pending
+            .iter()
+            .find_map(|(i, change)| match change {
+                Reconciled::Stat {
+                    stat: s, belief, ..
+                } if *i == idx && *s == stat => Some(*belief),
+                _ => None,
+            });
+        held.or_else(|| {
+            let side = self.get_side(self.viewpoint()?)?;
+            side.get_pokemon(idx)?.stats.as_ref()?.get(stat)
+        })
+    }
+
+    /// Note that a request has just been applied ahead of the log leading up to it
+    pub(crate) fn mark_request_ahead(&mut self) {
+        self.reconciliation.ahead = true;
+    }
+
+    /// Hold disagreements from a request until the log has caught up
+    pub(crate) fn hold_drift(&mut self, idx: usize, drift: Vec<Reconciled>) {
+        self.reconciliation
+            .pending
+            .extend(drift.into_iter().map(|change| (idx, change)));
+    }
+
+    /// Note what a message changes on one of our Pokemon with a held disagreement
+    pub(crate) fn observe_request_touch(&mut self, input: &TrackerInput) {
+        if self.reconciliation.pending.is_empty() {
+            return;
+        }
+        let touches: &[Touch] = match input {
+            TrackerInput::Damage { .. }
+            | TrackerInput::Heal { .. }
+            | TrackerInput::SetHp { .. }
+            | TrackerInput::Faint(_) => &[Touch::Hp],
+            TrackerInput::Switch { .. } | TrackerInput::Drag { .. } => &[Touch::Hp, Touch::Status],
+            TrackerInput::DetailsChange { .. } | TrackerInput::FormeChange { .. } => {
+                &[Touch::Hp, Touch::Status, Touch::Stat]
+            }
+            TrackerInput::Transform { .. } => &[Touch::Stat],
+            TrackerInput::VolatileStart { effect, .. }
+                if matches!(
+                    effect.trim_start_matches("move: "),
+                    "Power Trick" | "Power Shift"
+                ) =>
+            {
+                &[Touch::Stat]
+            }
+            TrackerInput::Status { .. } | TrackerInput::CureStatus(_) => &[Touch::Status],
+            TrackerInput::Item { .. } | TrackerInput::EndItem { .. } => &[Touch::Item],
+            TrackerInput::Move { .. } => &[Touch::Pp],
+            TrackerInput::Activate { effect, .. }
+                if matches!(*effect, "move: Spite" | "item: Leppa Berry") =>
+            {
+                &[Touch::Pp]
+            }
+            // Heal Bell and Aromatherapy cure the whole team
+            TrackerInput::CureTeam(pokemon) => {
+                self.touch_side(pokemon.player, Touch::Status);
+                return;
+            }
+            // Splits and Speed Swap change the stats of both Pokemon
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                of,
+                ..
+            } if SplitKind::from_effect(effect).is_some() || *effect == "move: Speed Swap" => {
+                for pokemon in std::iter::once(*pokemon).chain(*of) {
+                    self.touch_pokemon(pokemon, &[Touch::Stat]);
+                }
+                return;
+            }
+            _ => return,
+        };
+        let pokemon = match *input {
+            TrackerInput::Damage { pokemon, .. }
+            | TrackerInput::Heal { pokemon, .. }
+            | TrackerInput::SetHp { pokemon, .. }
+            | TrackerInput::Faint(pokemon)
+            | TrackerInput::Switch { pokemon, .. }
+            | TrackerInput::Drag { pokemon, .. }
+            | TrackerInput::DetailsChange { pokemon, .. }
+            | TrackerInput::FormeChange { pokemon, .. }
+            | TrackerInput::Status { pokemon, .. }
+            | TrackerInput::CureStatus(pokemon)
+            | TrackerInput::Item { pokemon, .. }
+            | TrackerInput::EndItem { pokemon, .. }
+            | TrackerInput::Move { pokemon, .. }
+            | TrackerInput::Transform { pokemon, .. }
+            | TrackerInput::VolatileStart { pokemon, .. }
+            | TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                ..
+            } => pokemon,
+            _ => return,
+        };
+        self.touch_pokemon(pokemon, touches);
+    }
+
+    fn touch_pokemon(&mut self, pokemon: &Pokemon, touches: &[Touch]) {
+        if self.viewpoint() != Some(pokemon.player) {
+            return;
+        }
+        let Some(idx) = self
+            .get_side(pokemon.player)
+            .and_then(|side| side.find_pokemon(&pokemon.name))
+        else {
+            return;
+        };
+        self.reconciliation
+            .touched
+            .extend(touches.iter().map(|&touch| (idx, touch)));
+    }
+
+    fn touch_side(&mut self, player: Player, touch: Touch) {
+        if self.viewpoint() != Some(player) {
+            return;
+        }
+        let count = self.get_side(player).map_or(0, |side| side.pokemon.len());
+        self.reconciliation
+            .touched
+            .extend((0..count).map(|idx| (idx, touch)));
+    }
+
+    /// Record the held disagreements no message has explained since their request
+    pub(crate) fn settle_drift(&mut self) {
+        self.reconciliation.ahead = false;
+        let pending = std::mem::take(&mut self.reconciliation.pending);
+        let touched = std::mem::take(&mut self.reconciliation.touched);
+        let Some(player) = self.viewpoint() else {
+            return;
+        };
+        for (idx, change) in pending {
+            if touched.contains(&(idx, change.touch())) {
+                continue;
+            }
+            let Some(name) = self
+                .get_side(player)
+                .and_then(|side| side.get_pokemon(idx))
+                .map(|poke| poke.name().to_string())
+            else {
+                continue;
+            };
+            let pokemon = Pokemon {
+                player,
+                position: None,
+                name,
+            };
+            if let Reconciled::Hp { belief, request } = change {
+                self.inconsistency(Inconsistency::UnexplainedHpChange {
+                    pokemon: pokemon.clone(),
+                    belief,
+                    request,
+                });
+            }
+            self.reconciliation.events.push(ReconciliationEvent {
+                turn: self.turn,
+                pokemon,
+                change,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracking::Severity;
+    use kazam_protocol::{BattleRequest, parse_server_message};
+
+    fn request(hp: u32, status: &str, item: &str, pp: u32) -> BattleRequest {
+        let json = serde_json::json!({
+            "active": [{"moves": [
+                {"move": "Thunderbolt", "id": "thunderbolt", "pp": pp, "maxpp": 24,
+                 "target": "normal", "disabled": false}
+            ]}],
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [
+                    {"ident": "p1: Pikachu", "details": "Pikachu, L50",
+                     "condition": format!("{hp}/110{status}"), "active": true,
+                     "moves": ["thunderbolt"], "item": item},
+                    {"ident": "p1: Snorlax", "details": "Snorlax, L50",
+                     "condition": "220/220", "active": false,
+                     "moves": ["bodyslam"], "item": "leftovers"}
+                ]
+            },
+            "rqid": 1
+        });
+        BattleRequest::parse(&json).unwrap()
+    }
+
+    fn apply(battle: &mut TrackedBattle, lines: &[&str]) {
+        for line in lines {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+    }
+
+    fn battle() -> TrackedBattle {
+        let mut battle = TrackedBattle::new();
+        battle.apply_request(&request(110, "", "lightball", 24));
+        apply(
+            &mut battle,
+            &[
+                "|player|p1|Alice|1",
+                "|player|p2|Bob|2",
+                "|switch|p1a: Pikachu|Pikachu, L50|110/110",
+                "|switch|p2a: Snorlax|Snorlax, L50|100/100",
+                "|turn|1",
+            ],
+        );
+        battle
+    }
+
+    #[test]
+    fn test_unexplained_pp_and_hp_changes() {
+        let mut battle = battle();
+
+        // The request for turn 3 comes before turn 2's log: Thunderbolt's use
+        // explains one PP, nothing explains the rest or the lost HP
+        battle.apply_request(&request(110, "", "lightball", 23));
+        apply(
+            &mut battle,
+            &[
+                "|move|p1a: Pikachu|Thunderbolt|p2a: Snorlax",
+                "|-damage|p2a: Snorlax|60/100",
+                "|turn|2",
+            ],
+        );
+        assert_eq!(battle.reconciliations(), []);
+
+        battle.apply_request(&request(80, "", "lightball", 20));
+        apply(&mut battle, &["|turn|3"]);
+        let pikachu = Pokemon {
+            player: Player::P1,
+            position: None,
+            name: "Pikachu".to_string(),
+        };
+        let changes: Vec<&Reconciled> =
+            battle.reconciliations().iter().map(|e| &e.change).collect();
+        assert_eq!(
+            changes,
+            [
+                &Reconciled::Hp {
+                    belief: 110,
+                    request: 80
+                },
+                &Reconciled::Pp {
+                    move_id: "thunderbolt".to_string(),
+                    belief: 23,
+                    request: 20
+                },
+            ]
+        );
+        assert_eq!(changes[0].delta(), Some(-30));
+        assert_eq!(battle.reconciliations()[0].pokemon, pikachu);
+        assert_eq!(battle.reconciliations()[0].turn, 3);
+
+        // The request is trusted, and only the HP change is an inconsistency
+        let me = battle.me().unwrap();
+        assert_eq!(me.pokemon[0].hp_current, 80);
+        assert_eq!(
+            battle.inconsistencies(),
+            [Inconsistency::UnexplainedHpChange {
+                pokemon: pikachu,
+                belief: 110,
+                request: 80
+            }]
+        );
+        assert_eq!(battle.inconsistencies()[0].severity(), Severity::Minor);
+    }
+
+    #[test]
+    fn test_log_explains_early_request() {
+        let mut battle = battle();
+        // Turn 2's request arrives before the hit, burn and Knock Off that led to it
+        battle.apply_request(&request(60, " brn", "", 24));
+        apply(
+            &mut battle,
+            &[
+                "|move|p2a: Snorlax|Knock Off|p1a: Pikachu",
+                "|-damage|p1a: Pikachu|60/110",
+                "|-enditem|p1a: Pikachu|Light Ball|[from] move: Knock Off",
+                "|-status|p1a: Pikachu|brn",
+                "|turn|2",
+            ],
+        );
+        assert_eq!(battle.reconciliations(), []);
+        assert_eq!(battle.inconsistencies(), []);
+    }
+
+    #[test]
+    fn test_guard_split_across_requests() {
+        let request = |def: u32, spd: u32| {
+            let json = serde_json::json!({
+                "side": {
+                    "name": "Alice",
+                    "id": "p1",
+                    "pokemon": [
+                        {"ident": "p1: Pikachu", "details": "Pikachu, L50",
+                         "condition": "110/110", "active": true, "moves": ["thunderbolt"],
+                         "stats": {"atk": 75, "def": def, "spa": 70, "spd": spd, "spe": 110}},
+                        {"ident": "p1: Lapras", "details": "Lapras, L50",
+                         "condition": "205/205", "active": false, "moves": ["surf"],
+                         "stats": {"atk": 105, "def": 100, "spa": 105, "spd": 115, "spe": 80}}
+                    ]
+                },
+                "wait": true,
+                "rqid": 1
+            });
+            BattleRequest::parse(&json).unwrap()
+        };
+        let mut battle = TrackedBattle::new();
+        battle.apply_request(&request(60, 70));
+        apply(
+            &mut battle,
+            &[
+                "|player|p1|Alice|1",
+                "|player|p2|Bob|2",
+                "|switch|p1a: Pikachu|Pikachu, L50|110/110",
+                "|switch|p2a: Snorlax|Snorlax, L50|100/100",
+                "|turn|1",
+            ],
+        );
+
+        // Turn 2's request has the averaged defenses before the log says why
+        battle.apply_request(&request(80, 110));
+        apply(
+            &mut battle,
+            &[
+                "|move|p2a: Snorlax|Guard Split|p1a: Pikachu",
+                "|-activate|p2a: Snorlax|move: Guard Split|[of] p1a: Pikachu",
+                "|turn|2",
+            ],
+        );
+        assert_eq!(battle.reconciliations(), []);
+        let pikachu = &battle.me().unwrap().pokemon[0];
+        assert_eq!(pikachu.stat_split, Some(SplitKind::Guard));
+        assert_eq!(pikachu.stats.as_ref().unwrap().def, 80);
+
+        // Snorlax's were 2 * 80 - 60 and 2 * 110 - 70, or one more for the flooring
+        let snorlax = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(snorlax.stat_split, Some(SplitKind::Guard));
+        assert_eq!(snorlax.stat_range(Stat::Def, 65), Some((100, 101)));
+        assert_eq!(snorlax.stat_range(Stat::Spd, 110), Some((150, 151)));
+        assert_eq!(snorlax.stat_range(Stat::Atk, 110), Some((103, 178)));
+
+        // Switching out recalculates the stats, as the next request shows
+        battle.apply_request(&request(60, 70));
+        apply(
+            &mut battle,
+            &["|switch|p1a: Lapras|Lapras, L50|205/205", "|turn|3"],
+        );
+        battle.apply_request(&request(60, 70));
+        assert_eq!(battle.reconciliations(), []);
+        let pikachu = &battle.me().unwrap().pokemon[0];
+        assert_eq!(pikachu.stat_split, None);
+        assert_eq!(pikachu.stats.as_ref().unwrap().spd, 70);
+
+        // Without a split to explain it, a changed stat is a correction
+        battle.apply_request(&request(61, 70));
+        apply(&mut battle, &["|turn|4"]);
+        assert_eq!(
+            battle.reconciliations()[0].change,
+            Reconciled::Stat {
+                stat: Stat::Def,
+                belief: 60,
+                request: 61
+            }
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Iteration over every tracked Pokemon, across all sides
+
+use kazam_protocol::Player;
+
+use super::battle::TrackedBattle;
+use crate::types::{PokemonState, SideState};
+
+/// A tracked Pokemon together with where it sits in the battle
+#[derive(Debug, Clone, Copy)]
+pub struct PokemonRef<'a> {
+    /// Owner
+    pub player: Player,
+
+    /// Index into the owner's `pokemon` list
+    pub index: usize,
+
+    /// Whether the owner is the battle's viewpoint
+    pub is_mine: bool,
+
+    /// Whether it's in one of the owner's active slots
+    pub is_active: bool,
+
+    pub side: &'a SideState,
+
+    pub pokemon: &'a PokemonState,
+}
+
+impl PokemonRef<'_> {
+    /// Whether it hasn't fainted
+    pub fn is_alive(&self) -> bool {
+        self.pokemon.is_alive()
+    }
+}
+
+/// A tracked Pokemon, mutably, together with where it sits in the battle
+#[derive(Debug)]
+pub struct PokemonRefMut<'a> {
+    pub player: Player,
+    pub index: usize,
+    pub is_mine: bool,
+    pub is_active: bool,
+    pub pokemon: &'a mut PokemonState,
+}
+
+/// Iterator over tracked Pokemon, side by side in party order, from
+/// [`TrackedBattle::all_pokemon`]
+///
+/// The filters can be chained: `battle.all_pokemon().theirs().alive()`.
+#[derive(Debug, Clone)]
+pub struct AllPokemon<'a> {
+    battle: &'a TrackedBattle,
+    side: usize,
+    index: usize,
+    alive: bool,
+    active: bool,
+    mine: Option<bool>,
+}
+
+impl<'a> AllPokemon<'a> {
+    /// Only Pokemon that haven't fainted
+    pub fn alive(mut self) -> Self {
+        self.alive = true;
+        self
+    }
+
+    /// Only Pokemon in an active slot
+    pub fn active(mut self) -> Self {
+        self.active = true;
+        self
+    }
+
+    /// Only the viewpoint's Pokemon (none without a viewpoint)
+    pub fn mine(mut self) -> Self {
+        self.mine = Some(true);
+        self
+    }
+
+    /// Only Pokemon not on the viewpoint's side (all of them without a viewpoint)
+    pub fn theirs(mut self) -> Self {
+        self.mine = Some(false);
+        self
+    }
+
+    fn keeps(&self, poke: &PokemonRef<'_>) -> bool {
+        (!self.alive || poke.is_alive())
+            && (!self.active || poke.is_active)
+            && self.mine.is_none_or(|mine| poke.is_mine == mine)
+    }
+}
+
+impl<'a> Iterator for AllPokemon<'a> {
+    type Item = PokemonRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let slot = self.battle.sides.get(self.side)?;
+            let Some((side, pokemon)) = slot
+                .as_ref()
+                .and_then(|side| Some((side, side.pokemon.get(self.index)?)))
+            else {
+                self.side += 1;
+                self.index = 0;
+                continue;
+            };
+            let poke = PokemonRef {
+                player: side.player,
+                index: self.index,
+                is_mine: self.battle.viewpoint() == Some(side.player),
+                is_active: side.active_indices.contains(&Some(self.index)),
+                side,
+                pokemon,
+            };
+            self.index += 1;
+            if self.keeps(&poke) {
+                return Some(poke);
+            }
+        }
+    }
+}
+
+impl TrackedBattle {
+    /// Every tracked Pokemon, side by side in party order
+    pub fn all_pokemon(&self) -> AllPokemon<'_> {
+        AllPokemon {
+            battle: self,
+            side: 0,
+            index: 0,
+            alive: false,
+            active: false,
+            mine: None,
+        }
+    }
+
+    /// Every tracked Pokemon mutably, side by side in party order
+    pub fn all_pokemon_mut(&mut self) -> impl Iterator<Item = PokemonRefMut<'_>> {
+        let viewpoint = self.viewpoint();
+        self.sides.iter_mut().flatten().flat_map(move |side| {
+            let SideState {
+                player,
+                pokemon,
+                active_indices,
+                ..
+            } = side;
+            let (player, active_indices) = (*player, &*active_indices);
+            pokemon
+                .iter_mut()
+                .enumerate()
+                .map(move |(index, pokemon)| PokemonRefMut {
+                    player,
+                    index,
+                    is_mine: viewpoint == Some(player),
+                    is_active: active_indices.contains(&Some(index)),
+                    pokemon,
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::parse_server_message;
+
+    const TEAM: [&str; 6] = [
+        "Garchomp",
+        "Rotom",
+        "Kingambit",
+        "Corviknight",
+        "Dragapult",
+        "Gholdengo",
+    ];
+
+    fn battle() -> TrackedBattle {
+        let log = [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|switch|p1a: Garchomp|Garchomp, L50|100/100",
+            "|switch|p2a: Rotom|Rotom, L50|100/100",
+            "|-damage|p2a: Rotom|0 fnt",
+            "|faint|p2a: Rotom",
+            "|switch|p2a: Kingambit|Kingambit, L50|100/100",
+        ];
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        for line in log {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        // Fill in the unrevealed rest of each party
+        for player in [Player::P1, Player::P2] {
+            let side = battle.get_side_mut(player).unwrap();
+            for species in TEAM {
+                if side.find_pokemon(species).is_none() {
+                    side.pokemon.push(PokemonState::new(species, 50));
+                }
+            }
+        }
+        battle
+    }
+
+    fn pairs<'a>(pokemon: impl Iterator<Item = PokemonRef<'a>>) -> Vec<(Player, usize)> {
+        pokemon.map(|p| (p.player, p.index)).collect()
+    }
+
+    #[test]
+    fn test_all_pokemon() {
+        let battle = battle();
+        let all: Vec<_> = battle.all_pokemon().collect();
+        let expected: Vec<_> = [Player::P1, Player::P2]
+            .into_iter()
+            .flat_map(|player| (0..6).map(move |index| (player, index)))
+            .collect();
+        assert_eq!(pairs(all.iter().copied()), expected);
+        for poke in &all {
+            let side = battle.get_side(poke.player).unwrap();
+            assert!(std::ptr::eq(poke.pokemon, &side.pokemon[poke.index]));
+            assert_eq!(poke.is_mine, poke.player == Player::P1);
+        }
+
+        assert_eq!(battle.all_pokemon().mine().count(), 6);
+        assert_eq!(
+            pairs(battle.all_pokemon().active()),
+            [(Player::P1, 0), (Player::P2, 1)]
+        );
+        assert_eq!(
+            pairs(battle.all_pokemon().theirs().active().alive()),
+            [(Player::P2, 1)]
+        );
+        assert_eq!(
+            pairs(battle.all_pokemon().mine().active()),
+            [(Player::P1, 0)]
+        );
+        assert_eq!(battle.all_pokemon().theirs().alive().count(), 5);
+
+        // Without a viewpoint nothing is ours
+        let mut spectated = battle.clone();
+        spectated.clear_viewpoint();
+        assert_eq!(spectated.all_pokemon().mine().count(), 0);
+        assert_eq!(spectated.all_pokemon().theirs().count(), 12);
+    }
+
+    #[test]
+    fn test_all_pokemon_mut() {
+        let mut battle = battle();
+        for poke in battle.all_pokemon_mut().filter(|p| p.is_active) {
+            poke.pokemon.turns_on_field += 1;
+        }
+        let on_field: Vec<_> = battle
+            .all_pokemon()
+            .filter(|p| p.pokemon.turns_on_field > 0)
+            .map(|p| p.pokemon.identity.species.as_str())
+            .collect();
+        assert_eq!(on_field, ["Garchomp", "Kingambit"]);
+    }
+}
@@ -0,0 +1,267 @@
+//! Clauses from a format's |rule| lines, and what they mean for choices
+//!
+//! Showdown announces each rule as "Name: description" before the battle
+//! starts. A [`RuleContext`] keeps the clauses the tracker acts on:
+//!
+//! - Sleep Clause Mod: a sleep move fails while a Pokemon on the target's
+//!   side sleeps from a foe's move; sleep its own Rest caused doesn't count
+//! - OHKO Clause and Evasion Moves Clause: those moves can't be chosen, by
+//!   us or by the opponent
+//! - Endless Battle Clause: staleness is only tracked where it applies
+//!
+//! Species Clause is recognized, but nothing here depends on it yet.
+
+use super::battle::TrackedBattle;
+use crate::query::moves::{is_evasion_move, is_ohko_move, is_sleep_move};
+use crate::types::{PokemonState, Status, to_id};
+
+/// A clause the tracker knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Clause {
+    SleepClause,
+    SpeciesClause,
+    OhkoClause,
+    /// Evasion Moves Clause, or Evasion Clause which also bans evasion items and abilities
+    EvasionMovesClause,
+    EndlessBattleClause,
+}
+
+impl Clause {
+    /// Recognize a |rule| line such as "Sleep Clause Mod: Limit one foe put to sleep"
+    pub fn parse(rule: &str) -> Option<Self> {
+        let name = rule.split_once(':').map_or(rule, |(name, _)| name).trim();
+        match name {
+            "Sleep Clause Mod" => Some(Self::SleepClause),
+            "Species Clause" => Some(Self::SpeciesClause),
+            "OHKO Clause" => Some(Self::OhkoClause),
+            "Evasion Moves Clause" | "Evasion Clause" => Some(Self::EvasionMovesClause),
+            "Endless Battle Clause" => Some(Self::EndlessBattleClause),
+            _ => None,
+        }
+    }
+}
+
+/// The clauses in effect, from a battle's |rule| lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleContext {
+    /// Whether any |rule| line was seen; without them the format's clauses
+    /// are unknown and none are assumed
+    pub known: bool,
+
+    pub sleep_clause: bool,
+
+    pub species_clause: bool,
+
+    pub ohko_clause: bool,
+
+    pub evasion_moves_clause: bool,
+
+    pub endless_battle_clause: bool,
+}
+
+impl RuleContext {
+    /// Clauses from |rule| lines, ignoring rules the tracker doesn't know
+    pub fn from_rules<'a>(rules: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut context = Self::default();
+        for rule in rules {
+            context.known = true;
+            match Clause::parse(rule) {
+                Some(Clause::SleepClause) => context.sleep_clause = true,
+                Some(Clause::SpeciesClause) => context.species_clause = true,
+                Some(Clause::OhkoClause) => context.ohko_clause = true,
+                Some(Clause::EvasionMovesClause) => context.evasion_moves_clause = true,
+                Some(Clause::EndlessBattleClause) => context.endless_battle_clause = true,
+                None => {}
+            }
+        }
+        context
+    }
+
+    /// The clause banning a move outright, if any
+    pub fn banning(&self, move_name: &str) -> Option<Clause> {
+        if self.ohko_clause && is_ohko_move(move_name) {
+            Some(Clause::OhkoClause)
+        } else if self.evasion_moves_clause && is_evasion_move(move_name) {
+            Some(Clause::EvasionMovesClause)
+        } else {
+            None
+        }
+    }
+
+    /// Whether turns without progress matter: under the Endless Battle
+    /// Clause, or when the rules are unknown
+    pub fn tracks_staleness(&self) -> bool {
+        !self.known || self.endless_battle_clause
+    }
+}
+
+/// Why a move choice is wasted or refused under the format's rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleWarning {
+    /// Sleep Clause Mod makes the move fail: `asleep` on the target's side
+    /// already sleeps from a foe's move
+    SleepClause { asleep: String },
+
+    /// The move is banned by the clause
+    Banned(Clause),
+}
+
+impl TrackedBattle {
+    /// Clauses in effect, from the |rule| lines seen so far
+    pub fn rule_context(&self) -> RuleContext {
+        RuleContext::from_rules(self.rules.iter().map(String::as_str))
+    }
+
+    /// The foe our side put to sleep, blocking further sleep under Sleep
+    /// Clause Mod; None without the clause
+    pub fn sleep_clause_holder(&self) -> Option<&PokemonState> {
+        if !self.rule_context().sleep_clause {
+            return None;
+        }
+        self.all_pokemon()
+            .theirs()
+            .alive()
+            .map(|p| p.pokemon)
+            .find(|p| p.status == Some(Status::Sleep) && !slept_from_rest(p))
+    }
+
+    /// Warnings for choosing `move_name` against the foes, for choice validation
+    pub fn rule_warnings(&self, move_name: &str) -> Vec<RuleWarning> {
+        let mut warnings = Vec::new();
+        if let Some(clause) = self.rule_context().banning(move_name) {
+            warnings.push(RuleWarning::Banned(clause));
+        }
+        if is_sleep_move(move_name)
+            && let Some(asleep) = self.sleep_clause_holder()
+        {
+            warnings.push(RuleWarning::SleepClause {
+                asleep: asleep.name().to_string(),
+            });
+        }
+        warnings
+    }
+
+    /// Start counting toward the Endless Battle Clause, where it applies
+    pub(crate) fn start_staleness(&mut self) {
+        if self.rule_context().tracks_staleness() {
+            self.begin_staleness();
+        }
+    }
+}
+
+/// Whether a Pokemon's sleep is its own Rest, which Sleep Clause Mod ignores
+fn slept_from_rest(pokemon: &PokemonState) -> bool {
+    pokemon
+        .status_from
+        .as_deref()
+        .is_some_and(|from| to_id(from) == "moverest")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{Player, parse_server_message};
+
+    fn battle(rules: &[&str], log: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        let setup = [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|gametype|singles",
+        ];
+        let rules = rules.iter().map(|rule| format!("|rule|{rule}"));
+        let start = [
+            "|start",
+            "|switch|p1a: Amoonguss|Amoonguss, L50|100/100",
+            "|switch|p2a: Snorlax|Snorlax, L50|100/100",
+            "|turn|1",
+        ];
+        let lines = setup
+            .into_iter()
+            .map(str::to_string)
+            .chain(rules)
+            .chain(start.into_iter().map(str::to_string))
+            .chain(log.lines().map(str::to_string));
+        for line in lines {
+            battle.apply_message(&parse_server_message(&line).unwrap());
+        }
+        battle
+    }
+
+    const SLEEP_CLAUSE: &str = "Sleep Clause Mod: Limit one foe put to sleep";
+
+    #[test]
+    fn test_parse_clauses() {
+        let context = RuleContext::from_rules([
+            "Species Clause: Limit one of each Pokémon",
+            "OHKO Clause: OHKO moves are banned",
+            "Evasion Moves Clause: Evasion moves are banned",
+            "Endless Battle Clause: Forcing endless battles is banned",
+            SLEEP_CLAUSE,
+            "HP Percentage Mod: HP is shown in percentages",
+        ]);
+        assert_eq!(
+            context,
+            RuleContext {
+                known: true,
+                sleep_clause: true,
+                species_clause: true,
+                ohko_clause: true,
+                evasion_moves_clause: true,
+                endless_battle_clause: true,
+            }
+        );
+        assert_eq!(context.banning("Sheer Cold"), Some(Clause::OhkoClause));
+        assert_eq!(
+            context.banning("doubleteam"),
+            Some(Clause::EvasionMovesClause)
+        );
+        assert_eq!(context.banning("Spore"), None);
+        assert_eq!(RuleContext::default().banning("Fissure"), None);
+    }
+
+    #[test]
+    fn test_sleep_clause_warns_after_a_foe_sleeps() {
+        let asleep = "|move|p1a: Amoonguss|Spore|p2a: Snorlax\n|-status|p2a: Snorlax|slp";
+        let warned = battle(&[SLEEP_CLAUSE], asleep);
+        assert_eq!(
+            warned.rule_warnings("Spore"),
+            [RuleWarning::SleepClause {
+                asleep: "Snorlax".to_string()
+            }]
+        );
+        assert!(warned.rule_warnings("Giga Drain").is_empty());
+
+        // No clause, or sleep from the foe's own Rest
+        assert!(battle(&[], asleep).rule_warnings("Spore").is_empty());
+        let rested = battle(
+            &[SLEEP_CLAUSE],
+            "|move|p2a: Snorlax|Rest|p2a: Snorlax\n|-status|p2a: Snorlax|slp|[from] move: Rest",
+        );
+        assert!(rested.rule_warnings("Spore").is_empty());
+
+        // Waking up lifts the clause
+        let woke = battle(
+            &[SLEEP_CLAUSE],
+            &format!("{asleep}\n|-curestatus|p2a: Snorlax|slp|[msg]"),
+        );
+        assert_eq!(woke.sleep_clause_holder(), None);
+    }
+
+    #[test]
+    fn test_staleness_only_under_endless_battle_clause() {
+        assert!(battle(&[], "").staleness().is_some());
+        assert!(
+            battle(
+                &["Endless Battle Clause: Forcing endless battles is banned"],
+                ""
+            )
+            .staleness()
+            .is_some()
+        );
+        let unclaused = battle(&[SLEEP_CLAUSE], "|turn|2");
+        assert_eq!(unclaused.staleness(), None);
+        assert_eq!(unclaused.turns_without_progress(), 0);
+    }
+}
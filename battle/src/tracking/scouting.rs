@@ -0,0 +1,300 @@
+//! Scouting helpers for carrying opponent knowledge across games of a series
+
+use kazam_protocol::{Player, same_user};
+
+use super::battle::{TrackedBattle, player_to_index};
+use crate::types::{KnowledgeKind, PokemonState, TeraType};
+
+/// Set details revealed by one opposing Pokemon in an earlier game.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScoutedPokemon {
+    /// Species name (including forme)
+    pub species: String,
+
+    /// Moves that were used
+    pub moves: Vec<String>,
+
+    /// Ability that was revealed
+    pub ability: Option<String>,
+
+    /// Item that was revealed (even if later consumed)
+    pub item: Option<String>,
+
+    /// Tera type, if the Pokemon terastallized
+    pub tera_type: Option<TeraType>,
+}
+
+impl ScoutedPokemon {
+    fn from_state(poke: &PokemonState) -> Self {
+        let mut moves = poke.known_moves.to_vec();
+        let mut ability = poke.known_ability.clone();
+        let mut item = poke.known_item.clone();
+        let mut tera_type = poke.tera_type;
+
+        // Keep priors that went unrevealed so a third game still sees them
+        for entry in poke.scouting.iter().filter(|e| e.prior) {
+            match entry.kind {
+                KnowledgeKind::Move => {
+                    if !moves.contains(&entry.value) {
+                        moves.push(entry.value.clone());
+                    }
+                }
+                KnowledgeKind::Ability => {
+                    ability.get_or_insert_with(|| entry.value.clone());
+                }
+                KnowledgeKind::Item => {
+                    item.get_or_insert_with(|| entry.value.clone());
+                }
+                KnowledgeKind::TeraType => {
+                    if tera_type.is_none() {
+                        tera_type = TeraType::from_protocol(&entry.value);
+                    }
+                }
+            }
+        }
+
+        Self {
+            species: poke.identity.species.clone(),
+            moves,
+            ability,
+            item,
+            tera_type,
+        }
+    }
+
+    /// Check whether anything was learned about this Pokemon
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+            && self.ability.is_none()
+            && self.item.is_none()
+            && self.tera_type.is_none()
+    }
+}
+
+/// Opponent knowledge extracted from a finished game.
+///
+/// Used to seed the next game of a best-of-N series. Everything in the report
+/// is applied as prior knowledge, never as a current reveal.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScoutingReport {
+    /// Opponent's username
+    pub username: String,
+
+    /// Scouted Pokemon, in the order they were seen
+    pub pokemon: Vec<ScoutedPokemon>,
+}
+
+impl ScoutingReport {
+    /// Get the scouted data for a species
+    pub fn get(&self, species: &str) -> Option<&ScoutedPokemon> {
+        self.pokemon.iter().find(|p| p.species == species)
+    }
+
+    /// Check if the report holds no data
+    pub fn is_empty(&self) -> bool {
+        self.pokemon.is_empty()
+    }
+}
+
+impl TrackedBattle {
+    /// Extract what the opponent revealed during this game.
+    ///
+    /// Requires a viewpoint; without one there is no opponent and the report is empty.
+    pub fn into_scouting_report(self) -> ScoutingReport {
+        let Some(opponent) = self.opponent() else {
+            return ScoutingReport::default();
+        };
+
+        ScoutingReport {
+            username: opponent.username.clone(),
+            pokemon: opponent
+                .pokemon
+                .iter()
+                .map(ScoutedPokemon::from_state)
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Start a fresh battle seeded with a scouting report from an earlier game.
+    ///
+    /// Opponent Pokemon pick up the report's data as priors when they first appear.
+    pub fn new_with_scouting(report: ScoutingReport) -> Self {
+        let mut battle = Self::new();
+        battle.scouting = Some(report);
+        battle
+    }
+
+    /// Reset for the next game of a series, keeping what the opponent revealed.
+    pub fn reset_for_new_game(&mut self) {
+        let config = self.config;
+        let report = std::mem::take(self).into_scouting_report();
+        *self = Self::new_with_scouting(report);
+        self.config = config;
+    }
+
+    /// Get the scouting report this battle was seeded with
+    pub fn scouting_report(&self) -> Option<&ScoutingReport> {
+        self.scouting.as_ref()
+    }
+
+    /// Apply scouted priors to a newly seen Pokemon on the scouted player's side
+    pub(crate) fn apply_scouting(&mut self, player: Player, poke_idx: usize) {
+        let Some(report) = &self.scouting else {
+            return;
+        };
+        let Some(side) = self.sides[player_to_index(player)].as_mut() else {
+            return;
+        };
+        if !same_user(&side.username, &report.username) {
+            return;
+        }
+        let Some(poke) = side.pokemon.get_mut(poke_idx) else {
+            return;
+        };
+        let Some(scouted) = report.get(&poke.identity.species) else {
+            return;
+        };
+
+        for move_name in &scouted.moves {
+            poke.add_prior(KnowledgeKind::Move, move_name);
+        }
+        if let Some(ability) = &scouted.ability {
+            poke.add_prior(KnowledgeKind::Ability, ability);
+        }
+        if let Some(item) = &scouted.item {
+            poke.add_prior(KnowledgeKind::Item, item);
+        }
+        if let Some(tera_type) = scouted.tera_type {
+            poke.add_prior(KnowledgeKind::TeraType, tera_type.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MoveProvenance;
+    use kazam_protocol::parse_server_message;
+
+    fn apply_log(battle: &mut TrackedBattle, log: &str) {
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+    }
+
+    const GAME_START: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|turn|1"#;
+
+    #[test]
+    fn test_scouting_report_from_finished_game() {
+        let mut game1 = TrackedBattle::for_player(Player::P1);
+        apply_log(&mut game1, GAME_START);
+        apply_log(
+            &mut game1,
+            r#"|move|p2a: Garchomp|Swords Dance|p2a: Garchomp
+|-boost|p2a: Garchomp|atk|2
+|turn|2
+|move|p2a: Garchomp|Earthquake|p1a: Corviknight
+|-immune|p1a: Corviknight
+|turn|3
+|move|p2a: Garchomp|Stone Edge|p1a: Corviknight
+|-damage|p1a: Corviknight|60/100
+|-enditem|p2a: Garchomp|Focus Sash
+|win|Bob"#,
+        );
+
+        let report = game1.into_scouting_report();
+        assert_eq!(report.username, "Bob");
+        let garchomp = report.get("Garchomp").unwrap();
+        assert_eq!(
+            garchomp.moves,
+            vec!["Swords Dance", "Earthquake", "Stone Edge"]
+        );
+
+        let mut game2 = TrackedBattle::new_with_scouting(report);
+        apply_log(&mut game2, GAME_START);
+
+        let poke = &game2.get_side(Player::P2).unwrap().pokemon[0];
+        assert!(poke.known_moves.is_empty());
+        assert_eq!(
+            poke.prior_moves().collect::<Vec<_>>(),
+            vec!["Swords Dance", "Earthquake", "Stone Edge"]
+        );
+        assert!(poke.scouting.iter().all(|e| e.weight() < 1.0));
+
+        // Our own side never picks up the opponent's priors
+        assert!(game2.get_side(Player::P1).unwrap().pokemon[0].scouting.is_empty());
+
+        apply_log(&mut game2, "|move|p2a: Garchomp|Earthquake|p1a: Corviknight");
+
+        let poke = &game2.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(poke.known_moves.as_slice(), ["Earthquake"]);
+        assert_eq!(
+            poke.prior_moves().collect::<Vec<_>>(),
+            vec!["Swords Dance", "Stone Edge"]
+        );
+        let earthquake = poke
+            .scouting
+            .iter()
+            .find(|e| e.value == "Earthquake")
+            .unwrap();
+        assert!(!earthquake.prior);
+        assert_eq!(earthquake.weight(), 1.0);
+        assert_eq!(
+            poke.move_knowledge().collect::<Vec<_>>(),
+            [
+                ("Earthquake", MoveProvenance::Observed),
+                ("Swords Dance", MoveProvenance::ScoutedPrior),
+                ("Stone Edge", MoveProvenance::ScoutedPrior),
+            ]
+        );
+        assert!(!poke.moveset_complete());
+    }
+
+    #[test]
+    fn test_reset_for_new_game_keeps_scouting() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(&mut battle, GAME_START);
+        apply_log(&mut battle, "|move|p2a: Garchomp|Earthquake|p1a: Corviknight");
+
+        battle.reset_for_new_game();
+        assert_eq!(battle.turn, 0);
+        assert!(battle.sides().next().is_none());
+        let report = battle.scouting_report().unwrap();
+        assert_eq!(report.get("Garchomp").unwrap().moves, vec!["Earthquake"]);
+    }
+
+    #[test]
+    fn test_reset_clears_the_game() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(&mut battle, GAME_START);
+        apply_log(&mut battle, "|move|p2a: Garchomp|Earthquake|p1a: Corviknight");
+        let report = battle.clone().into_scouting_report();
+
+        battle.reset(None);
+        assert_eq!(battle.turn, 0);
+        assert!(battle.sides().next().is_none());
+        assert!(battle.scouting_report().is_none());
+        assert_eq!(battle.viewpoint(), Some(Player::P1));
+
+        battle.reset(Some(report));
+        let report = battle.scouting_report().unwrap();
+        assert_eq!(report.get("Garchomp").unwrap().moves, vec!["Earthquake"]);
+    }
+
+    #[test]
+    fn test_no_viewpoint_gives_empty_report() {
+        let mut battle = TrackedBattle::new();
+        apply_log(&mut battle, GAME_START);
+        assert!(battle.into_scouting_report().is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use kazam_protocol::ServerMessage;
 use super::battle::{BattleKnowledge, TrackedBattle};
 
 /// A captured copy of reduced battle state that can be restored later.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BattleSnapshot {
     battle: TrackedBattle,
 }
@@ -39,7 +39,7 @@ impl From<TrackedBattle> for BattleSnapshot {
 }
 
 /// A snapshot captured at a turn boundary.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TurnSnapshot {
     /// Number of messages applied before this snapshot was captured.
     pub message_index: usize,
@@ -0,0 +1,183 @@
+//! Progress tracking for the Endless Battle Clause
+//!
+//! The clause ends battles that can no longer progress, such as Leppa Berry
+//! and Recycle loops. Roughly as the server judges it, a battle progresses
+//! when a Pokemon faints or drops below the lowest HP it has had so far;
+//! healing back up and taking the same chip again does not count.
+
+use kazam_protocol::{Player, Pokemon};
+
+use super::battle::TrackedBattle;
+
+/// An Endless Battle Clause or turn limit warning from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EndlessBattleWarning {
+    /// The battle auto-ties on turn 1000, `turns_left` turns from now
+    TurnLimit { turns_left: u32 },
+
+    /// Turn 1000 was reached and the battle ties
+    TurnLimitReached,
+
+    /// A team started with the means for restorative berry-cycling; `loser`
+    /// is None when every side did
+    BerryCycling { loser: Option<String> },
+
+    /// No side can progress, so the battle ties
+    CannotProgress,
+}
+
+impl EndlessBattleWarning {
+    /// Recognize a warning in `-message`, `-hint` or `bigerror` text
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.contains("hit the turn limit") {
+            return Some(Self::TurnLimitReached);
+        }
+        if let Some(rest) = text.strip_prefix("You will auto-tie if the battle doesn't end in ") {
+            let turns_left = rest.split(' ').next()?.parse().ok()?;
+            return Some(Self::TurnLimit { turns_left });
+        }
+        if text.contains("restorative berry-cycling") {
+            let loser = text
+                .split_once("'s team started")
+                .map(|(name, _)| name.to_string())
+                .filter(|name| name != "Each side");
+            return Some(Self::BerryCycling { loser });
+        }
+        if text.contains("cannot progress") && text.contains("Endless Battle Clause") {
+            return Some(Self::CannotProgress);
+        }
+        None
+    }
+}
+
+/// How long a battle has gone without progress
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StalenessState {
+    /// Turn of the latest faint or new HP low, or the first turn
+    pub last_progress_turn: u32,
+
+    /// Lowest HP percent seen for each Pokemon, by side and name
+    lowest_hp: Vec<(Player, String, f32)>,
+
+    /// Warnings the server sent, in order
+    pub warnings: Vec<EndlessBattleWarning>,
+}
+
+impl StalenessState {
+    /// Record a Pokemon's HP, returns true if it is below its previous low
+    fn record_hp(&mut self, player: Player, name: &str, hp: f32) -> bool {
+        match self
+            .lowest_hp
+            .iter_mut()
+            .find(|(p, n, _)| *p == player && n == name)
+        {
+            Some((_, _, lowest)) if hp < *lowest => {
+                *lowest = hp;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.lowest_hp.push((player, name.to_string(), hp));
+                // First damage seen: anything below full HP is a new low
+                hp < 100.0
+            }
+        }
+    }
+}
+
+impl TrackedBattle {
+    /// Progress tracking for the Endless Battle Clause, once the first turn started
+    pub fn staleness(&self) -> Option<&StalenessState> {
+        self.staleness.as_ref()
+    }
+
+    /// Turns since the latest faint or new HP low, 0 before the first turn
+    pub fn turns_without_progress(&self) -> u32 {
+        self.staleness
+            .as_ref()
+            .map_or(0, |s| self.turn.saturating_sub(s.last_progress_turn))
+    }
+
+    /// Start tracking, at the first turn or the first warning
+    pub(crate) fn begin_staleness(&mut self) {
+        let turn = self.turn;
+        self.staleness.get_or_insert_with(|| StalenessState {
+            last_progress_turn: turn,
+            ..StalenessState::default()
+        });
+    }
+
+    /// Compare a Pokemon's HP after damage with its lowest so far
+    pub(crate) fn observe_hp(&mut self, pokemon: &Pokemon) {
+        let Some(hp) = self.find_pokemon(pokemon).map(|p| p.hp_percent_exact()) else {
+            return;
+        };
+        let turn = self.turn;
+        if let Some(staleness) = &mut self.staleness
+            && staleness.record_hp(pokemon.player, &pokemon.name, hp)
+        {
+            staleness.last_progress_turn = turn;
+        }
+    }
+
+    /// A faint always counts as progress
+    pub(crate) fn observe_faint(&mut self) {
+        let turn = self.turn;
+        if let Some(staleness) = &mut self.staleness {
+            staleness.last_progress_turn = turn;
+        }
+    }
+
+    pub(crate) fn observe_endless_warning(&mut self, text: &str) {
+        if let Some(warning) = EndlessBattleWarning::parse(text) {
+            self.begin_staleness();
+            if let Some(staleness) = &mut self.staleness {
+                staleness.warnings.push(warning);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_warnings() {
+        let cases = [
+            (
+                "You will auto-tie if the battle doesn't end in 500 turns (on turn 1000).",
+                Some(EndlessBattleWarning::TurnLimit { turns_left: 500 }),
+            ),
+            (
+                "You will auto-tie if the battle doesn't end in 1 turn (on turn 1000).",
+                Some(EndlessBattleWarning::TurnLimit { turns_left: 1 }),
+            ),
+            (
+                "It is turn 1000. You have hit the turn limit!",
+                Some(EndlessBattleWarning::TurnLimitReached),
+            ),
+            (
+                "Bob's team started with the rudimentary means to perform restorative berry-cycling and thus loses.",
+                Some(EndlessBattleWarning::BerryCycling {
+                    loser: Some("Bob".to_string()),
+                }),
+            ),
+            (
+                "Each side's team started with the rudimentary means to perform restorative berry-cycling.",
+                Some(EndlessBattleWarning::BerryCycling { loser: None }),
+            ),
+            (
+                "This battle cannot progress. Endless Battle Clause activated!",
+                Some(EndlessBattleWarning::CannotProgress),
+            ),
+            ("Sleep Clause Mod activated.", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(EndlessBattleWarning::parse(text), expected, "{text}");
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Open team sheets revealed at team preview in VGC formats
+
+use kazam_protocol::Player;
+use kazam_team::{PokemonSet, TeamError, Teams};
+
+use super::battle::TrackedBattle;
+use super::config::TrackerConfig;
+use crate::types::{MoveProvenance, PokemonState, TeraType, same_species};
+
+impl TrackedBattle {
+    /// Apply an open team sheet (the packed team from `|showteam|`).
+    ///
+    /// Moves, item, ability and tera type become known for every Pokemon on
+    /// the side. Names keep the sheet's packed form ("SitrusBerry") until the
+    /// battle reveals them. Pokemon already on the side are merged by species
+    /// rather than added again. Our own side is left to the request, which
+    /// already carries the full set.
+    pub fn apply_team_sheet(&mut self, player: Player, packed: &str) -> Result<(), TeamError> {
+        let team = Teams::unpack(packed)?;
+        if self.viewpoint() == Some(player) {
+            return Ok(());
+        }
+
+        let config = self.config;
+        let side = self.get_or_create_side(player, "");
+        side.team_sheet = true;
+        for set in &team {
+            let existing = side
+                .pokemon
+                .iter()
+                .position(|p| same_species(&p.identity.species, &set.species));
+            let idx = existing.unwrap_or_else(|| {
+                side.pokemon.push(PokemonState::new(&set.species, set.level));
+                side.pokemon.len() - 1
+            });
+            apply_set(&mut side.pokemon[idx], set, config);
+        }
+        Ok(())
+    }
+}
+
+fn apply_set(poke: &mut PokemonState, set: &PokemonSet, config: TrackerConfig) {
+    // The sheet names the forme team preview hid
+    if poke.identity.species.ends_with("-*") {
+        poke.identity.species = set.species.clone();
+    }
+    poke.identity.level = set.level;
+    if let Some(gender) = set.gender.chars().next() {
+        poke.identity.gender = Some(gender);
+    }
+    if let Some(tera_type) = TeraType::from_protocol(&set.tera_type) {
+        poke.tera_type = Some(tera_type);
+    }
+    if config.tracks_moves() {
+        for move_name in &set.moves {
+            poke.record_move_from(move_name, MoveProvenance::OpenTeamSheet);
+        }
+    }
+    if config.tracks_items_abilities() {
+        if !set.ability.is_empty() && poke.known_ability.is_none() {
+            poke.record_ability(&set.ability);
+        }
+        if !set.item.is_empty() && poke.known_item.is_none() {
+            poke.record_item(&set.item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Type;
+    use kazam_protocol::{ServerMessage, parse_server_message};
+
+    const SHOWTEAM_P2: &str = "|showteam|p2|Incineroar||SitrusBerry|Intimidate|FakeOut,KnockOff,PartingShot,FlareBlitz|||M|||50|,,,,,Ghost]Rillaboom||AssaultVest|GrassySurge|FakeOut,GrassyGlide,WoodHammer,HighHorsepower|||M|||50|,,,,,Fire]Urshifu-Rapid-Strike||ChoiceScarf|UnseenFist|SurgingStrikes,CloseCombat,AquaJet,UTurn|||M|||50|,,,,,Water]Flutter Mane||BoosterEnergy|Protosynthesis|Moonblast,ShadowBall,Protect,IcyWind||||||50|,,,,,Fairy]Amoonguss||RockyHelmet|Regenerator|Spore,RagePowder,PollenPuff,Protect|||F|||50|,,,,,Water]Chi-Yu||FocusSash|BeadsofRuin|HeatWave,DarkPulse,Overheat,Protect||||||50|,,,,,Ghost";
+
+    const PREVIEW: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|gen|9
+|tier|[Gen 9] VGC 2024 Reg G
+|clearpoke
+|poke|p2|Incineroar, L50, M|
+|poke|p2|Rillaboom, L50, M|
+|poke|p2|Urshifu-*, L50, M|
+|poke|p2|Flutter Mane, L50|
+|poke|p2|Amoonguss, L50, F|
+|poke|p2|Chi-Yu, L50|
+|showteam|p1|Corviknight||Leftovers|Pressure|BraveBird,BulkUp,Roost,Protect||||||50|,,,,,Water"#;
+
+    fn apply_log(battle: &mut TrackedBattle, log: &str) {
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_showteam() {
+        match parse_server_message(SHOWTEAM_P2).unwrap() {
+            ServerMessage::ShowTeam { player, team } => {
+                assert_eq!(player, Player::P2);
+                assert!(team.starts_with("Incineroar||SitrusBerry|Intimidate|"));
+                assert_eq!(Teams::unpack(&team).unwrap().len(), 6);
+            }
+            other => panic!("expected ShowTeam, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_team_sheet_populates_opponent_at_preview() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(&mut battle, PREVIEW);
+        apply_log(&mut battle, SHOWTEAM_P2);
+        apply_log(&mut battle, "|teampreview|4");
+
+        // Our own sheet is left to the request
+        assert!(battle.get_side(Player::P1).unwrap().pokemon.is_empty());
+
+        let side = battle.get_side(Player::P2).unwrap();
+        assert!(side.team_sheet);
+        assert_eq!(side.pokemon.len(), 6);
+        assert!(side.pokemon.iter().all(|p| p.known_moves.len() == 4
+            && p.known_item.is_some()
+            && p.known_ability.is_some()
+            && p.tera_type.is_some()
+            && !p.terastallized));
+
+        let incineroar = &side.pokemon[0];
+        assert_eq!(incineroar.identity.species, "Incineroar");
+        assert_eq!(incineroar.identity.level, 50);
+        assert_eq!(incineroar.identity.gender, Some('M'));
+        assert_eq!(incineroar.known_item.as_deref(), Some("SitrusBerry"));
+        assert_eq!(incineroar.known_ability.as_deref(), Some("Intimidate"));
+        assert_eq!(incineroar.tera_type, Some(TeraType::Type(Type::Ghost)));
+        assert_eq!(
+            incineroar.known_moves.as_slice(),
+            ["FakeOut", "KnockOff", "PartingShot", "FlareBlitz"]
+        );
+    }
+
+    #[test]
+    fn test_switch_ins_merge_with_team_sheet() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        apply_log(&mut battle, PREVIEW);
+        apply_log(&mut battle, SHOWTEAM_P2);
+        apply_log(
+            &mut battle,
+            r#"|start
+|switch|p2a: Kitty|Incineroar, L50, M|100/100
+|switch|p2b: Flutter Mane|Flutter Mane, L50|100/100
+|turn|1
+|move|p2a: Kitty|Fake Out|p1a: Corviknight
+|-item|p2b: Flutter Mane|Booster Energy"#,
+        );
+
+        let side = battle.get_side(Player::P2).unwrap();
+        assert_eq!(side.pokemon.len(), 6);
+        assert_eq!(side.active_indices.as_slice(), [Some(0), Some(3)]);
+
+        let incineroar = &side.pokemon[0];
+        assert_eq!(incineroar.name(), "Kitty");
+        // The in-game name replaces the packed one without duplicating it
+        assert_eq!(
+            incineroar.known_moves.as_slice(),
+            ["Fake Out", "KnockOff", "PartingShot", "FlareBlitz"]
+        );
+        // Using a listed move doesn't make it any less certain
+        assert_eq!(
+            incineroar.move_provenance("Fake Out"),
+            Some(MoveProvenance::OpenTeamSheet)
+        );
+        assert!(incineroar.moveset_complete());
+        assert_eq!(side.pokemon[3].known_item.as_deref(), Some("Booster Energy"));
+    }
+}
@@ -1,131 +1,397 @@
 //! Update logic for processing ServerMessage into battle state
 
-use kazam_protocol::{BattleRequest, Pokemon, PokemonDetails, ServerFrame, ServerMessage};
+use kazam_protocol::{
+    BattleRequest, Player, Pokemon, PokemonDetails, PokemonStats, RequestKind, ServerFrame,
+    ServerMessage, SidePokemon, Stat, parse_server_message,
+};
 
-use super::battle::{BattleKnowledge, TrackedBattle, position_to_slot};
+use super::config::{StrictnessMode, TrackerConfig};
+use super::input::TrackerInput;
+use super::battle::{
+    BattleKnowledge, CombinedMove, ConditionRemoval, Inconsistency, MoveOrder, OrderException,
+    ReflectedMove, Severity, SwitchKind, SwitchRecord, TrackedBattle,
+};
+use crate::query::moves::move_category;
+use crate::query::switch_in::is_once_per_battle;
 use crate::types::{
-    PokemonState, SideCondition, Status, Volatile, Weather,
+    ChargingMove, DamageKind, FieldStatModifier, HealAmount, HealLanding, HitRecord, PendingHeal,
+    PokemonState, SideCondition, SplitKind, StatBound, StatConstraint, StatStages, Status,
+    TeraType, Volatile, VolatileState, Weather, to_id,
 };
 
+/// Log line kinds with no battle state: chat and timestamps
+const LOG_NOISE: &[&str] = &["c", "chat", "c:", ":", "t:"];
+
+/// Moves that break the target side's screens without tagging the |-sideend|
+const SCREEN_BREAKERS: &[&str] = &["brickbreak", "psychicfangs", "ragingbull"];
+
+/// Moves that leave a heal on the user's slot: how much, and whether it lands
+/// at the end of next turn (Wish) rather than on the next switch-in
+const HEAL_SETUP_MOVES: &[(&str, HealAmount, bool)] = &[
+    ("wish", HealAmount::Fraction(0.5), true),
+    ("healingwish", HealAmount::Full, false),
+    ("lunardance", HealAmount::Full, false),
+];
+
+/// Effects that give a Pokemon a new ability until it switches out
+const ABILITY_CHANGES: &[&str] = &[
+    "move: Worry Seed",
+    "move: Simple Beam",
+    "move: Entrainment",
+    "move: Skill Swap",
+    "ability: Trace",
+];
+
+/// Activations that let a Pokemon move ahead of its Speed order that turn
+const ORDER_EXCEPTIONS: &[&str] = &[
+    "item: Custap Berry",
+    "item: Quick Claw",
+    "ability: Quick Draw",
+];
+
 impl TrackedBattle {
     /// Apply a single protocol message to the battle state.
     pub fn apply_message(&mut self, msg: &ServerMessage) {
-        match msg {
+        self.apply_input(msg.into());
+    }
+
+    /// Apply a single battle event, however it was parsed.
+    pub fn apply_input(&mut self, input: TrackerInput) {
+        self.observe_request_touch(&input);
+        match input {
             // === Battle Initialization ===
-            ServerMessage::BattlePlayer {
-                player,
-                username,
-                avatar: _,
-                rating: _,
-            } => {
-                let side = self.get_or_create_side(*player, username);
+            TrackerInput::BattlePlayer { player, username } => {
+                let side = self.get_or_create_side(player, username);
                 if side.username.is_empty() {
-                    side.username = username.clone();
+                    side.username = username.to_string();
                 }
             }
 
-            ServerMessage::TeamSize { player, size: _ } => {
-                // Side should already exist from BattlePlayer
-                // Team size is informational, we discover actual team from switches
-                let _ = self.get_side(*player);
+            // A player renaming mid-battle; |win| uses the new name
+            TrackerInput::Name { username, old_id } => {
+                self.rename_player(old_id, username);
+            }
+
+            TrackerInput::TeamSize { player } => {
+                // Team size is informational, we discover actual team from switches,
+                // but a spectated log may not have named the player yet
+                self.get_or_create_side(player, "");
             }
 
-            ServerMessage::GameType(game_type) => {
-                self.set_game_type(*game_type);
+            TrackerInput::Poke { player, details } => {
+                self.handle_preview_pokemon(player, details);
             }
 
-            ServerMessage::Gen(generation) => {
-                self.generation = *generation;
+            TrackerInput::ShowTeam { player, team } => {
+                // A malformed sheet leaves the side as it was
+                let _ = self.apply_team_sheet(player, team);
             }
 
-            ServerMessage::Tier(tier) => {
-                self.tier = tier.clone();
+            TrackerInput::GameType(game_type) => {
+                self.set_game_type(game_type);
             }
 
-            ServerMessage::Turn(turn) => {
-                self.turn = *turn;
+            TrackerInput::Gen(generation) => {
+                self.generation = generation;
+            }
+
+            TrackerInput::Tier(tier) => {
+                self.tier = tier.to_string();
+            }
+
+            TrackerInput::Rule(rule) => {
+                self.rules.push(rule.to_string());
+            }
+
+            TrackerInput::Turn(turn) => {
+                self.turn = turn;
+                self.settle_drift();
+                self.start_staleness();
+                self.end_move_variance();
+                self.pending_reflect = None;
+                self.pending_removal = None;
+                self.pending_skill_swap = None;
+                self.pending_charge = None;
+                self.turn_movers.clear();
+                self.pending_forced_switches.clear();
+                self.turn_resolved = false;
+                for side in self.sides_mut() {
+                    side.tick_conditions();
+                    side.expire_heals(turn);
+                    for poke in &mut side.pokemon {
+                        poke.hits_taken_last_turn = std::mem::take(&mut poke.hits_taken_this_turn);
+                        poke.has_acted_this_turn = false;
+                    }
+                    for idx in side.active_indices.clone().into_iter().flatten() {
+                        side.pokemon[idx].turns_on_field += 1;
+                        side.pokemon[idx].remove_volatile(&Volatile::CenterOfAttention);
+                        side.pokemon[idx].volatiles.tick();
+                    }
+                }
             }
 
             // === Major Actions ===
-            ServerMessage::Switch {
+            TrackerInput::Switch {
                 pokemon,
                 details,
                 hp_status,
             } => {
-                self.handle_switch(pokemon, details, hp_status.as_ref(), false);
+                self.observe_switch_kind(pokemon, false);
+                self.handle_switch(pokemon, details, hp_status, false);
+                self.observe_heal_switch_in(pokemon);
+                self.end_move_variance();
             }
 
-            ServerMessage::Drag {
+            TrackerInput::Drag {
                 pokemon,
                 details,
                 hp_status,
             } => {
-                self.handle_switch(pokemon, details, hp_status.as_ref(), true);
+                self.observe_switch_kind(pokemon, true);
+                self.handle_switch(pokemon, details, hp_status, true);
+                self.observe_heal_switch_in(pokemon);
+                self.end_move_variance();
             }
 
-            ServerMessage::Faint(pokemon) => {
+            TrackerInput::Faint(pokemon) => {
                 self.handle_faint(pokemon);
+                self.observe_faint();
             }
 
-            ServerMessage::Move {
+            TrackerInput::Move {
                 pokemon,
                 move_name,
-                target: _,
-                miss: _,
-                still: _,
-                anim: _,
+                target,
+                still,
+                from,
+            } => {
+                if self.config.tracks_volatiles() {
+                    self.observe_charge_move(pokemon, move_name, target, still);
+                }
+                self.observe_move_variance(pokemon, move_name, target, still);
+                self.observe_heal_move(pokemon, move_name);
+                if let Some(effect) = from {
+                    // Called by another effect (Magic Bounce, Dancer, ...), not part of the set
+                    if is_reflect_effect(effect) {
+                        self.begin_reflect(pokemon, effect);
+                    } else if !effect.ends_with("Dancer") {
+                        // A locked-in move (Outrage's [from]lockedmove) is still its action
+                        if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                            poke.has_acted_this_turn = true;
+                        }
+                    }
+                } else {
+                    let tracks_moves = self.config.tracks_moves();
+                    let pressure = tracks_moves && self.targets_pressure(pokemon, target);
+                    if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                        poke.has_acted_this_turn = true;
+                        // Record the move as known; Struggle isn't part of the set
+                        if tracks_moves {
+                            if to_id(move_name) != "struggle" {
+                                poke.record_move(move_name);
+                                poke.spend_pp(move_name, pressure);
+                            }
+                            poke.last_move = Some(move_name.to_string());
+                        }
+                    }
+                    if self.config.tracks_action_log() {
+                        self.last_move = Some((pokemon.clone(), move_name.to_string()));
+                        self.pending_removal = SCREEN_BREAKERS
+                            .contains(&to_id(move_name).as_str())
+                            .then(|| (pokemon.clone(), move_name.to_string()));
+                    }
+                    if self.config.tracks_history() {
+                        self.record_move_order(pokemon, move_name);
+                    }
+                    self.pending_reflect = None;
+                }
+            }
+
+            // === Reflected Moves ===
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                ..
+            } if is_reflect_effect(effect) => {
+                self.begin_reflect(pokemon, effect);
+            }
+
+            // Screen Cleaner's |-sideend| lines carry no [from]
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect: "ability: Screen Cleaner",
+                ..
+            } => {
+                if self.config.tracks_items_abilities()
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    poke.record_ability("Screen Cleaner");
+                }
+                if self.config.tracks_action_log() {
+                    self.pending_removal = Some((pokemon.clone(), "Screen Cleaner".to_string()));
+                }
+            }
+
+            // Custap Berry, Quick Claw and Quick Draw announce themselves before the move
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                ..
+            } if ORDER_EXCEPTIONS.contains(&effect) => {
+                if self.config.tracks_items_abilities()
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    if let Some(item) = effect.strip_prefix("item: ") {
+                        poke.record_item(item);
+                    } else {
+                        poke.record_ability(effect.trim_start_matches("ability: "));
+                    }
+                }
+                if self.config.tracks_history() {
+                    self.order_exceptions.push(OrderException {
+                        turn: self.turn,
+                        pokemon: pokemon.clone(),
+                        cause: effect
+                            .trim_start_matches("item: ")
+                            .trim_start_matches("ability: ")
+                            .to_string(),
+                    });
+                }
+            }
+
+            // Emergency Exit and Wimp Out withdraw their holder below half HP
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect: effect @ ("ability: Emergency Exit" | "ability: Wimp Out"),
+                ..
             } => {
-                // Record the move as known
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.record_move(move_name);
+                let ability = effect.trim_start_matches("ability: ");
+                if self.config.tracks_items_abilities()
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    poke.record_ability(ability);
+                }
+                self.begin_forced_switch(pokemon, ability);
+            }
+
+            // === Combined Moves ===
+            TrackerInput::Waiting { source, target } if self.config.tracks_action_log() => {
+                self.pending_combo = Some((source.clone(), target.clone()));
+            }
+
+            TrackerInput::Combine if self.config.tracks_action_log() => {
+                // The partner's |move| line precedes |-combine|
+                if let Some((waiting, partner)) = self.pending_combo.take() {
+                    let move_name = self
+                        .last_move
+                        .as_ref()
+                        .filter(|(user, _)| *user == partner)
+                        .map(|(_, name)| name.clone())
+                        .unwrap_or_default();
+                    self.combined_moves.push(CombinedMove {
+                        turn: self.turn,
+                        waiting,
+                        partner,
+                        move_name,
+                    });
                 }
             }
 
             // === HP Changes ===
-            ServerMessage::Damage {
+            TrackerInput::Damage {
                 pokemon,
                 hp_status,
-                from: _,
+                from,
             } => {
-                if let (Some(poke), Some(hp)) = (self.find_pokemon_mut(pokemon), hp_status) {
+                // A screen breaker's |-sideend| lines come before its damage
+                self.pending_removal = None;
+                let own_move = self.last_move.as_ref().is_some_and(|(user, _)| {
+                    user.player == pokemon.player && user.name == pokemon.name
+                });
+                let kind = DamageKind::classify(from, own_move);
+                if from.is_none() {
+                    self.observe_hit(pokemon);
+                }
+                if let (Some(poke), Some(hp)) = (self.resolve_pokemon_mut(pokemon), hp_status) {
+                    let before = poke.hp_percent();
+                    let before_exact = poke.hp_percent_exact();
                     poke.apply_hp_status(hp);
+                    let after = poke.hp_percent();
+                    let lost = before.saturating_sub(after);
+                    let lost_exact = (before_exact - poke.hp_percent_exact()).max(0.0);
+                    poke.damage_taken_estimate += lost;
+                    poke.damage_taken.add(kind, lost_exact);
+                    // Hitting itself in confusion stops a charged move
+                    if from == Some("confusion") {
+                        poke.stop_charging();
+                    }
+                    if after > before {
+                        self.inconsistency(Inconsistency::HpIncreasedOnDamage {
+                            pokemon: pokemon.clone(),
+                            from: before,
+                            to: after,
+                        });
+                    }
+                    if kind == DamageKind::Direct {
+                        self.credit_damage(pokemon, lost, lost_exact);
+                        self.record_hit(pokemon, lost_exact);
+                    }
+                    self.observe_hp(pokemon);
                 }
             }
 
-            ServerMessage::Heal {
+            TrackerInput::Heal {
                 pokemon,
                 hp_status,
-                from: _,
+                from,
             } => {
-                if let (Some(poke), Some(hp)) = (self.find_pokemon_mut(pokemon), hp_status) {
+                if let Some(source) = from.and_then(|from| from.strip_prefix("move: "))
+                    && HEAL_SETUP_MOVES.iter().any(|(id, ..)| *id == to_id(source))
+                    && let Some(slot) = self.active_slot(pokemon)
+                    && let Some(side) = self.get_side_mut(pokemon.player)
+                {
+                    side.land_heal(slot, source);
+                }
+                if let (Some(poke), Some(hp)) = (self.resolve_pokemon_mut(pokemon), hp_status) {
+                    // Only Revival Blessing heals a fainted Pokemon, back on the bench
+                    if poke.fainted && hp.current > 0 {
+                        poke.fainted = false;
+                        poke.status = None;
+                    }
                     poke.apply_hp_status(hp);
                 }
             }
 
-            ServerMessage::SetHp {
-                pokemon,
-                hp_status,
-                from: _,
-            } => {
-                if let (Some(poke), Some(hp)) = (self.find_pokemon_mut(pokemon), hp_status) {
+            TrackerInput::SetHp { pokemon, hp_status } => {
+                if let (Some(poke), Some(hp)) = (self.resolve_pokemon_mut(pokemon), hp_status) {
                     poke.apply_hp_status(hp);
+                    // Pain Split can set a new low too
+                    self.observe_hp(pokemon);
                 }
             }
 
             // === Status ===
-            ServerMessage::Status { pokemon, status } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::Status {
+                pokemon,
+                status,
+                from,
+            } => {
+                self.land_reflect();
+                self.observe_status_variance(pokemon);
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.status = Status::from_protocol(status);
+                    poke.status_from = from.map(str::to_string);
                 }
             }
 
-            ServerMessage::CureStatus { pokemon, status: _ } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::CureStatus(pokemon) => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.status = None;
+                    poke.status_from = None;
                 }
             }
 
-            ServerMessage::CureTeam(pokemon) => {
+            TrackerInput::CureTeam(pokemon) => {
                 // Cure status for entire team
                 if let Some(side) = self.get_side_mut(pokemon.player) {
                     for poke in &mut side.pokemon {
@@ -135,105 +401,119 @@ impl TrackedBattle {
             }
 
             // === Boosts ===
-            ServerMessage::Boost {
+            TrackerInput::Boost {
                 pokemon,
                 stat,
                 amount,
+                from,
             } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.boosts.boost(*stat, *amount);
+                let ability = from.and_then(|f| f.strip_prefix("ability: "));
+                let track = self.config.tracks_items_abilities();
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.boosts.boost(stat, amount);
+                    // Self-boosting abilities (Intrepid Sword, Download, ...) reveal themselves
+                    if let Some(ability) = ability
+                        && track {
+                        poke.record_ability(ability);
+                    }
+                    if ability.is_some_and(is_once_per_battle) {
+                        poke.switch_in_boost_used = true;
+                    }
+                    if poke.fainted {
+                        self.inconsistency(Inconsistency::BoostOnFainted(pokemon.clone()));
+                    }
+                }
+                if ability == Some("Download") {
+                    self.handle_download(pokemon, stat);
                 }
             }
 
-            ServerMessage::Unboost {
+            TrackerInput::Unboost {
                 pokemon,
                 stat,
                 amount,
             } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.boosts.unboost(*stat, *amount);
+                self.land_reflect();
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.boosts.unboost(stat, amount);
+                    if poke.fainted {
+                        self.inconsistency(Inconsistency::BoostOnFainted(pokemon.clone()));
+                    }
                 }
             }
 
-            ServerMessage::SetBoost {
+            TrackerInput::SetBoost {
                 pokemon,
                 stat,
                 amount,
             } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.boosts.set(*stat, *amount);
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.boosts.set(stat, amount);
                 }
             }
 
-            ServerMessage::ClearBoost(pokemon) => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::ClearBoost(pokemon) => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.boosts.clear();
                 }
             }
 
-            ServerMessage::ClearAllBoost => {
+            TrackerInput::ClearAllBoost => {
                 // Clear boosts for all active Pokemon
-                for side in self.sides.iter_mut().flatten() {
-                    for idx in &side.active_indices {
-                        if let Some(idx) = idx
-                            && let Some(poke) = side.pokemon.get_mut(*idx) {
-                                poke.boosts.clear();
-                            }
-                    }
+                for poke in self.all_pokemon_mut().filter(|p| p.is_active) {
+                    poke.pokemon.boosts.clear();
                 }
             }
 
-            ServerMessage::InvertBoost(pokemon) => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::InvertBoost(pokemon) => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.boosts.invert();
                 }
             }
 
-            ServerMessage::ClearPositiveBoost {
-                target,
-                source: _,
-                effect: _,
-            } => {
-                if let Some(poke) = self.find_pokemon_mut(target) {
+            TrackerInput::ClearPositiveBoost(target) => {
+                if let Some(poke) = self.resolve_pokemon_mut(target) {
                     poke.boosts.clear_positive();
                 }
             }
 
-            ServerMessage::ClearNegativeBoost(pokemon) => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::ClearNegativeBoost(pokemon) => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.boosts.clear_negative();
                 }
             }
 
-            ServerMessage::CopyBoost { source, target } => {
+            TrackerInput::CopyBoost { source, target } => {
                 // Copy boosts from source to target
                 let source_boosts = self
                     .find_pokemon(source)
-                    .map(|p| p.boosts.clone());
+                    .map(|p| p.boosts);
 
                 if let (Some(boosts), Some(target_poke)) =
-                    (source_boosts, self.find_pokemon_mut(target))
+                    (source_boosts, self.resolve_pokemon_mut(target))
                 {
                     target_poke.boosts.copy_from(&boosts);
                 }
             }
 
-            ServerMessage::SwapBoost {
+            TrackerInput::SwapBoost {
                 source,
                 target,
                 stats,
             } => {
                 // Swap specific stat boosts between source and target
-                let source_boosts = self.find_pokemon(source).map(|p| p.boosts.clone());
-                let target_boosts = self.find_pokemon(target).map(|p| p.boosts.clone());
+                let source_boosts = self
+                    .find_pokemon(source)
+                    .map(|p| p.boosts);
+                let target_boosts = self.find_pokemon(target).map(|p| p.boosts);
 
                 if let (Some(src_boosts), Some(tgt_boosts)) = (source_boosts, target_boosts) {
-                    if let Some(src_poke) = self.find_pokemon_mut(source) {
+                    if let Some(src_poke) = self.resolve_pokemon_mut(source) {
                         for stat in stats {
                             src_poke.boosts.set(*stat, tgt_boosts.get(*stat));
                         }
                     }
-                    if let Some(tgt_poke) = self.find_pokemon_mut(target) {
+                    if let Some(tgt_poke) = self.resolve_pokemon_mut(target) {
                         for stat in stats {
                             tgt_poke.boosts.set(*stat, src_boosts.get(*stat));
                         }
@@ -241,196 +521,425 @@ impl TrackedBattle {
                 }
             }
 
+            // Guard Split and Power Split average raw stats, not boosts
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                of: Some(target),
+                ..
+            } if SplitKind::from_effect(effect).is_some() => {
+                if let Some(kind) = SplitKind::from_effect(effect) {
+                    self.handle_stat_split(pokemon, target, kind);
+                }
+            }
+
             // === Volatiles ===
-            ServerMessage::VolatileStart { pokemon, effect } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    let volatile = Volatile::from_protocol(effect);
-                    poke.add_volatile(volatile);
+            TrackerInput::VolatileStart { pokemon, effect } if self.config.tracks_volatiles() => {
+                let volatile = Volatile::from_protocol(effect);
+                if volatile.paradox_stat().is_some() {
+                    self.observe_paradox_start(pokemon, volatile);
+                } else if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    // Perish Song's count is announced each turn, down to perish0
+                    match Volatile::perish_count(effect) {
+                        Some(0) => {
+                            poke.remove_volatile(&Volatile::PerishSong);
+                        }
+                        Some(count) => {
+                            poke.add_volatile(Volatile::PerishSong);
+                            if let Some(state) = poke.volatiles.get_mut(&Volatile::PerishSong) {
+                                state.turns_remaining = Some(count);
+                            }
+                        }
+                        None => poke.add_volatile(volatile),
+                    }
+                }
+            }
+
+            // Abilities come back; their re-activations follow as ordinary reveals
+            TrackerInput::VolatileEnd {
+                pokemon,
+                effect: "ability: Neutralizing Gas",
+            } => {
+                if let Some(poke) = self.find_pokemon(pokemon) {
+                    let species = poke.identity.species.clone();
+                    self.field.end_neutralizing_gas(pokemon.player, &species);
                 }
             }
 
-            ServerMessage::VolatileEnd { pokemon, effect } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::VolatileEnd { pokemon, effect } if self.config.tracks_volatiles() => {
+                if !self.observe_paradox_end(pokemon, effect)
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
                     let volatile = Volatile::from_protocol(effect);
                     poke.remove_volatile(&volatile);
                 }
             }
 
             // === Field Conditions ===
-            ServerMessage::Weather { weather, upkeep } => {
-                if !upkeep {
-                    // Only update on initial weather set, not upkeep messages
-                    if weather == "none" || weather.is_empty() {
-                        self.field.weather = None;
-                    } else {
-                        self.field.weather = Weather::from_protocol(weather);
-                    }
+            // Only update on initial weather set, not upkeep messages
+            TrackerInput::Weather {
+                weather,
+                upkeep,
+                from,
+                of,
+            } if !upkeep => {
+                if weather == "none" || weather.is_empty() {
+                    self.field.weather = None;
+                } else {
+                    self.field.weather = Weather::from_protocol(weather);
+                }
+                self.end_unpowered_paradox_boosts();
+                // Drizzle and friends, on switch-in or when Neutralizing Gas ends
+                if let Some(ability) = from.and_then(|from| from.strip_prefix("ability: "))
+                    && let Some(setter) = of
+                    && self.config.tracks_items_abilities()
+                    && let Some(poke) = self.resolve_pokemon_mut(setter)
+                {
+                    poke.reveal_ability(ability);
                 }
             }
 
-            ServerMessage::FieldStart(condition) => {
+            TrackerInput::FieldStart(condition) => {
                 self.field.apply_field_start(condition);
+                self.end_unpowered_paradox_boosts();
             }
 
-            ServerMessage::FieldEnd(condition) => {
+            TrackerInput::FieldEnd(condition) => {
                 self.field.apply_field_end(condition);
+                self.end_unpowered_paradox_boosts();
             }
 
             // === Side Conditions ===
-            ServerMessage::SideStart { side, condition } => {
-                if let Some(side_state) = self.get_side_mut(side.player)
-                    && let Some(cond) = SideCondition::from_protocol(condition) {
-                        side_state.add_condition(cond);
+            TrackerInput::SideStart { side, condition } => {
+                self.land_reflect();
+                if let Some(cond) = SideCondition::from_protocol(condition) {
+                    let replaying = self.replaying;
+                    let side_state = self.get_or_create_side(side, "");
+                    // A re-announced condition is already counted
+                    let stacked = (replaying && side_state.has_condition(cond))
+                        || side_state.add_condition(cond);
+                    if !stacked {
+                        self.inconsistency(Inconsistency::ConditionOverStacked {
+                            player: side,
+                            condition: cond,
+                        });
                     }
+                }
             }
 
-            ServerMessage::SideEnd { side, condition } => {
-                if let Some(side_state) = self.get_side_mut(side.player)
-                    && let Some(cond) = SideCondition::from_protocol(condition) {
+            TrackerInput::SideEnd {
+                side,
+                condition,
+                from,
+            } => {
+                if let Some(cond) = SideCondition::from_protocol(condition) {
+                    if self.config.tracks_action_log() {
+                        self.attribute_removal(side, cond, from);
+                    }
+                    if let Some(side_state) = self.get_side_mut(side) {
                         side_state.remove_condition(cond);
                     }
+                }
             }
 
-            ServerMessage::SwapSideConditions => {
-                // Swap side conditions between P1 and P2 (Court Change)
-                let p1_conditions = self.get_side(kazam_protocol::Player::P1)
-                    .map(|s| s.conditions.clone());
-                let p2_conditions = self.get_side(kazam_protocol::Player::P2)
-                    .map(|s| s.conditions.clone());
-
-                if let (Some(c1), Some(c2)) = (p1_conditions, p2_conditions) {
-                    if let Some(s1) = self.get_side_mut(kazam_protocol::Player::P1) {
-                        s1.conditions = c2;
-                    }
-                    if let Some(s2) = self.get_side_mut(kazam_protocol::Player::P2) {
-                        s2.conditions = c1;
+            TrackerInput::SwapSideConditions
+                if self.get_side(Player::P1).is_some() && self.get_side(Player::P2).is_some() =>
+            {
+                // Court Change trades the swappable conditions between P1 and
+                // P2; the rest stay where they are
+                let mut take_swappable = |player| {
+                    self.get_side_mut(player)
+                        .map(|side| {
+                            side.conditions
+                                .take_where(SideCondition::is_court_change_swappable)
+                        })
+                        .unwrap_or_default()
+                };
+                let from_p1 = take_swappable(Player::P1);
+                let from_p2 = take_swappable(Player::P2);
+
+                for (player, moving) in [(Player::P1, from_p2), (Player::P2, from_p1)] {
+                    if let Some(side) = self.get_side_mut(player) {
+                        for (cond, state) in moving {
+                            side.conditions.insert(cond, state);
+                        }
                     }
                 }
             }
 
             // === Items and Abilities ===
-            ServerMessage::Item {
-                pokemon,
-                item,
-                from: _,
-            } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::Item { pokemon, item } if self.config.tracks_items_abilities() => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.record_item(item);
+                }
+            }
+
+            // Poltergeist names the target's item
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                detail: Some(item),
+                ..
+            } if effect == "move: Poltergeist" && self.config.tracks_items_abilities() => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.record_item(item);
                 }
             }
 
-            ServerMessage::EndItem {
+            // Disguise / Ice Face absorbing a hit (or Ice Face reforming in snow)
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect,
+                ..
+            } if matches!(effect, "ability: Disguise" | "ability: Ice Face")
+                && self.config.tracks_items_abilities() =>
+            {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.record_ability(effect.trim_start_matches("ability: "));
+                }
+            }
+
+            // Follow Me, Rage Powder and Spotlight draw moves for the rest of the turn
+            TrackerInput::SingleTurn { pokemon, move_name } if self.config.tracks_volatiles() => {
+                let volatile = Volatile::from_protocol(move_name);
+                if volatile == Volatile::CenterOfAttention
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    poke.add_volatile(volatile);
+                }
+            }
+
+            // Tatsugiri hides inside its Dondozo ally, out of reach until Dondozo faints
+            TrackerInput::Activate {
+                pokemon: Some(pokemon),
+                effect: "ability: Commander",
+                ..
+            } => {
+                let config = self.config;
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    if config.tracks_items_abilities() {
+                        poke.record_ability("Commander");
+                    }
+                    if config.tracks_volatiles() {
+                        poke.add_volatile(Volatile::Commanding);
+                    }
+                }
+            }
+
+            TrackerInput::EndItem {
                 pokemon,
-                item: _,
-                from: _,
-                eat: _,
+                item,
+                from,
             } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+                if self.config.tracks_items_abilities()
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    if poke.known_item.is_none() {
+                        poke.record_item(item);
+                    }
                     poke.consume_item();
                 }
+                // A knocked off or stolen Eject Button doesn't withdraw anyone
+                if from.is_none() && matches!(item, "Eject Button" | "Eject Pack") {
+                    self.begin_forced_switch(pokemon, item);
+                }
             }
 
-            ServerMessage::Ability {
+            TrackerInput::Ability {
                 pokemon,
                 ability,
-                from: _,
+                from,
+                of,
             } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.record_ability(ability);
+                let track = self.config.tracks_items_abilities();
+                let change = from.filter(|effect| ABILITY_CHANGES.contains(effect));
+                let mut was_changed = false;
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    if track {
+                        if change.is_some() {
+                            was_changed = poke.ability_override.is_some();
+                            poke.override_ability(ability);
+                        } else {
+                            poke.reveal_ability(ability);
+                        }
+                    }
+                    let species = poke.identity.species.clone();
+                    if to_id(ability) == "neutralizinggas" {
+                        self.field.start_neutralizing_gas(pokemon.player, &species);
+                    } else if let Some(modifier) =
+                        FieldStatModifier::from_ruin_ability(ability, pokemon.player, species)
+                    {
+                        self.field.add_stat_modifier(modifier);
+                    }
+                }
+                if track && let Some(effect) = change {
+                    self.learn_ability_source(pokemon, ability, effect, of, was_changed);
                 }
             }
 
-            ServerMessage::EndAbility(pokemon) => {
+            TrackerInput::EndAbility(pokemon) if self.config.tracks_volatiles() => {
                 // Ability suppressed (Gastro Acid, etc.)
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.add_volatile(Volatile::GastroAcid);
                 }
             }
 
             // === Transformations ===
-            ServerMessage::Transform { pokemon, species } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.transformed = Some(species.clone());
-                    poke.add_volatile(Volatile::Transformed);
+            TrackerInput::Transform { pokemon, species } => {
+                let track = self.config.tracks_volatiles();
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.transformed = Some(species.to_string());
+                    if track {
+                        poke.add_volatile(Volatile::Transformed);
+                    }
                 }
             }
 
-            ServerMessage::Mega { pokemon, megastone: _ } => {
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            TrackerInput::Mega(pokemon) => {
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
                     poke.mega_evolved = true;
                 }
             }
 
-            ServerMessage::DetailsChange {
+            TrackerInput::Terastallize { pokemon, tera_type } => {
+                if let Some(tera_type) = TeraType::from_protocol(tera_type)
+                    && let Some(poke) = self.resolve_pokemon_mut(pokemon)
+                {
+                    poke.terastallize(tera_type);
+                }
+            }
+
+            TrackerInput::DetailsChange {
                 pokemon,
                 details,
                 hp_status,
             } => {
                 // Forme change that persists (Mega Evolution, etc.)
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    poke.identity.species = details.species.clone();
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.change_species(&details.species);
                     if let Some(hp) = hp_status {
                         poke.apply_hp_status(hp);
                     }
                 }
             }
 
-            ServerMessage::FormeChange {
+            TrackerInput::FormeChange {
                 pokemon,
                 species,
                 hp_status,
             } => {
                 // Temporary forme change
-                if let Some(poke) = self.find_pokemon_mut(pokemon) {
-                    // Store current species if transforming
-                    poke.identity.species = species.clone();
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.change_species(species);
                     if let Some(hp) = hp_status {
                         poke.apply_hp_status(hp);
                     }
                 }
             }
 
+            // === Negative Evidence ===
+            TrackerInput::Immune { pokemon, from } => {
+                self.observe_immune(pokemon, from);
+            }
+
+            TrackerInput::Fail {
+                pokemon,
+                action,
+                from,
+            } => {
+                if action.is_none() && from.is_none() {
+                    self.observe_heal_move_failed(pokemon);
+                }
+                self.observe_fail(pokemon, action, from);
+            }
+
+            // Old-gen logs can show a status only through the moves it stops
+            TrackerInput::Cant { pokemon, reason } => {
+                self.observe_cant_variance(pokemon, reason);
+                if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                    poke.has_acted_this_turn = true;
+                    // A charged move that can't be used is lost
+                    poke.stop_charging();
+                    if let Some(status) = Status::from_protocol(reason)
+                        && poke.status.is_none()
+                    {
+                        poke.status = Some(status);
+                    }
+                }
+            }
+
+            // === Two-turn Moves ===
+            TrackerInput::Prepare {
+                attacker,
+                move_name,
+                defender,
+            } if self.config.tracks_volatiles() => {
+                let defender = match self.pending_charge.take() {
+                    Some((user, target)) if defender.is_none() && user == *attacker => target,
+                    _ => defender.cloned(),
+                };
+                if let Some(poke) = self.resolve_pokemon_mut(attacker) {
+                    poke.start_charging(ChargingMove {
+                        move_name: move_name.to_string(),
+                        target: defender.and_then(|d| Some((d.player, d.position?))),
+                    });
+                }
+            }
+
+            // Sun, rain or a Power Herb skip the charge turn: the move fires
+            // right after its |-prepare|
+            TrackerInput::Anim { source, move_name } => {
+                if let Some(poke) = self.resolve_pokemon_mut(source)
+                    && poke
+                        .charging_move
+                        .as_ref()
+                        .is_some_and(|c| to_id(&c.move_name) == to_id(move_name))
+                {
+                    poke.stop_charging();
+                }
+            }
+
             // === Battle End ===
-            ServerMessage::Win(winner) => {
+            TrackerInput::Win(winner) => {
                 self.ended = true;
-                self.winner = Some(winner.clone());
+                self.winner = Some(winner.to_string());
             }
 
-            ServerMessage::Tie => {
+            TrackerInput::Tie => {
                 self.ended = true;
                 self.tie = true;
             }
 
-            // === Ignored Messages (informational only) ===
-            ServerMessage::Crit(_)
-            | ServerMessage::SuperEffective(_)
-            | ServerMessage::Resisted(_)
-            | ServerMessage::Immune(_)
-            | ServerMessage::Miss { .. }
-            | ServerMessage::Fail { .. }
-            | ServerMessage::Block { .. }
-            | ServerMessage::NoTarget(_)
-            | ServerMessage::Cant { .. }
-            | ServerMessage::Upkeep
-            | ServerMessage::Request(_)
-            | ServerMessage::Inactive(_)
-            | ServerMessage::InactiveOff(_)
-            | ServerMessage::BattleStart
-            | ServerMessage::ClearPoke
-            | ServerMessage::Poke { .. }
-            | ServerMessage::TeamPreview(_)
-            | ServerMessage::Rated(_)
-            | ServerMessage::Rule(_)
-            | ServerMessage::Primal(_)
-            | ServerMessage::Swap { .. }
-            | ServerMessage::Replace { .. } => {
-                // These don't affect tracked state
+            TrackerInput::Message(text) => {
+                self.observe_endless_warning(text);
+            }
+
+            TrackerInput::Raw(line) => {
+                if let Some(text) = line.strip_prefix("|bigerror|") {
+                    self.observe_endless_warning(text);
+                }
+            }
+
+            // === Outcomes ===
+            TrackerInput::Crit(target) => {
+                self.observe_crit(target);
+            }
+
+            TrackerInput::Miss(source) => {
+                self.observe_miss(source);
+            }
+
+            TrackerInput::Upkeep => {
+                self.end_move_variance();
+                self.turn_resolved = true;
             }
 
-            // === Non-battle messages ===
-            _ => {
-                // Ignore non-battle messages
+            TrackerInput::Other => {
+                // Doesn't affect tracked state
             }
+
+            // Messages the config doesn't track
+            _ => {}
         }
     }
 
@@ -449,11 +958,72 @@ impl TrackedBattle {
         self.apply_messages(frame.messages.iter());
     }
 
+    /// Build a spectator's view of a battle from its protocol log, one message per line.
+    ///
+    /// Takes a saved replay's log as is, with or without a `>roomid` header;
+    /// see [`feed_line`](Self::feed_line) for what gets skipped.
+    ///
+    /// Never fails: a truncated or partly unreadable log still gives the
+    /// state as far as it got. Callers that need to reject a log can feed it
+    /// with [`feed_line`](Self::feed_line), which reports lines that don't
+    /// parse, and check `turn` or
+    /// [`inconsistency_count`](Self::inconsistency_count).
+    pub fn from_log(log: &str) -> Self {
+        Self::from_log_with_config(log, TrackerConfig::new())
+    }
+
+    /// Build a spectator's view of a battle from its protocol log, tracking what `config` asks
+    pub fn from_log_with_config(log: &str, config: TrackerConfig) -> Self {
+        let mut battle = Self::with_config(config);
+        for line in log.lines() {
+            battle.feed_line(line);
+        }
+        battle
+    }
+
+    /// Apply one line of a protocol log
+    ///
+    /// Room headers, chat and timestamps carry no battle state and are
+    /// skipped, as are lines that don't parse (such as a fifth player's),
+    /// with a warning. Lines the tracker doesn't know change nothing.
+    ///
+    /// Returns false for a line that didn't parse.
+    pub fn feed_line(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('>') {
+            return true;
+        }
+        let kind = line.split('|').nth(1).unwrap_or_default();
+        if LOG_NOISE.contains(&kind) {
+            return true;
+        }
+        match parse_server_message(line) {
+            Ok(message) => {
+                self.apply_message(&message);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("skipping unparseable log line {line:?}: {e}");
+                false
+            }
+        }
+    }
+
     /// Apply private request data for one player's view of the battle.
     ///
     /// This is an optional enrichment step used by live clients. Replay-style
     /// omniscient logs can skip it entirely.
+    ///
+    /// A section or field the request leaves out means no information, not
+    /// empty: team preview, force-switch and wait requests have no move data,
+    /// so move slots from the last move request are kept.
     pub fn apply_request(&mut self, request: &BattleRequest) {
+        // Disagreements with an earlier request are settled before new ones
+        self.settle_drift();
+        self.mark_request_ahead();
+        let reconcile = self.last_request.is_some();
+        self.last_request = Some(request.clone());
+
         // Extract perspective from side info
         if let Some(ref side_info) = request.side {
             // Parse player from side id (e.g., "p1" -> Player::P1)
@@ -466,86 +1036,117 @@ impl TrackedBattle {
                 }
 
                 // Get or create our side
+                let config = self.config;
                 let side = self.get_or_create_side(player, &side_info.name);
                 if side.username.is_empty() {
                     side.username = side_info.name.clone();
                 }
 
-                // Sync Pokemon from request (has full info)
-                for (i, req_poke) in side_info.pokemon.iter().enumerate() {
-                    if i >= side.pokemon.len() {
-                        // Add new Pokemon from request
-                        let mut poke = PokemonState::new(&req_poke.details, 100);
-
-                        // Parse details
-                        let details = PokemonDetails::parse(&req_poke.details);
-                        poke.identity.species = details.species;
-                        poke.identity.level = details.level.unwrap_or(100);
-                        poke.identity.gender = details.gender;
-                        poke.identity.shiny = details.shiny;
-
-                        // Parse nickname from ident
-                        if let Some(name) = req_poke.ident.split(": ").nth(1)
-                            && name != poke.identity.species {
-                                poke.identity.nickname = Some(name.to_string());
+                // Sync Pokemon from request (has full info). The request lists
+                // the active Pokemon first, so match entries by identity, not position.
+                let mut order = Vec::with_capacity(side_info.pokemon.len());
+                let mut level_mismatches = Vec::new();
+                let mut drift = Vec::new();
+                for req_poke in &side_info.pokemon {
+                    let details = PokemonDetails::parse(&req_poke.details);
+                    let name = req_poke.ident.split(": ").nth(1).unwrap_or(&req_poke.ident);
+                    let idx = match side.match_request_pokemon(name, &details.species, &order) {
+                        Some(idx) => {
+                            let poke = &mut side.pokemon[idx];
+                            claim_placeholder(poke, &details, name);
+                            let level = details.level.unwrap_or(100);
+                            if poke.identity.level != level {
+                                let pokemon = Pokemon {
+                                    player,
+                                    position: None,
+                                    name: name.to_string(),
+                                };
+                                level_mismatches.push((pokemon, poke.identity.level, level));
+                                poke.identity.level = level;
                             }
-
-                        // Full info from request
-                        poke.known_moves = req_poke.moves.clone();
-                        poke.known_ability = Some(req_poke.ability.clone());
-                        poke.known_item = if req_poke.item.is_empty() {
-                            None
-                        } else {
-                            Some(req_poke.item.clone())
-                        };
-                        poke.active = req_poke.active;
-
-                        // Parse HP from condition
-                        if let Some((current, max)) = req_poke.hp() {
-                            poke.hp_current = current;
-                            poke.hp_max = Some(max);
-                        }
-
-                        // Parse status from condition
-                        if let Some(status_str) = req_poke.status() {
-                            poke.status = Status::from_protocol(status_str);
-                            if status_str == "fnt" {
-                                poke.fainted = true;
+                            if reconcile {
+                                let tracks_items = config.tracks_items_abilities();
+                                drift
+                                    .push((idx, Self::request_drift(poke, req_poke, tracks_items)));
                             }
+                            idx
                         }
-
-                        side.pokemon.push(poke);
-                    } else {
-                        // Update existing Pokemon with full info
-                        let poke = &mut side.pokemon[i];
-                        poke.known_moves = req_poke.moves.clone();
-                        poke.known_ability = Some(req_poke.ability.clone());
-                        poke.known_item = if req_poke.item.is_empty() {
-                            None
-                        } else {
-                            Some(req_poke.item.clone())
-                        };
-                        poke.active = req_poke.active;
-
-                        if let Some((current, max)) = req_poke.hp() {
-                            poke.hp_current = current;
-                            poke.hp_max = Some(max);
-                        }
-
-                        if let Some(status_str) = req_poke.status() {
-                            if status_str == "fnt" {
-                                poke.fainted = true;
-                                poke.status = None;
-                            } else {
-                                poke.status = Status::from_protocol(status_str);
-                            }
-                        } else {
-                            poke.status = None;
-                            poke.fainted = poke.hp_current == 0;
+                        None => {
+                            // Add new Pokemon from request
+                            side.pokemon
+                                .push(PokemonState::from_protocol_with_name(&details, name));
+                            side.pokemon.len() - 1
                         }
+                    };
+                    sync_request_pokemon(&mut side.pokemon[idx], req_poke, config);
+                    order.push(idx);
+                }
+                side.set_request_order(order);
+
+                // A transformed Pokemon's request stats are its target's stats (minus HP)
+                let leaks: Vec<(Pokemon, PokemonStats)> = side
+                    .pokemon
+                    .iter()
+                    .filter_map(|poke| {
+                        let target = Pokemon::parse(poke.transformed.as_deref()?)?;
+                        Some((target, poke.stats.clone()?))
+                    })
+                    .filter(|(target, _)| target.player != player)
+                    .collect();
+
+                for (target, stats) in leaks {
+                    if let Some(poke) = self.find_pokemon_mut(&target) {
+                        poke.stats = Some(stats);
                     }
                 }
+                for (idx, changes) in drift {
+                    self.hold_drift(idx, changes);
+                }
+                for (pokemon, known, seen) in level_mismatches {
+                    self.inconsistency(Inconsistency::LevelMismatch {
+                        pokemon,
+                        known,
+                        seen,
+                    });
+                }
+            }
+        }
+
+        match request.kind() {
+            RequestKind::Move => self.record_request_moves(request),
+            RequestKind::TeamPreview | RequestKind::ForceSwitch | RequestKind::Wait => {}
+        }
+    }
+
+    /// Keep the move slots of each active Pokemon in a move request
+    fn record_request_moves(&mut self, request: &BattleRequest) {
+        let (Some(active), Some(side_info)) = (&request.active, &request.side) else {
+            return;
+        };
+        let Some(side) = side_info.player().and_then(|player| self.get_side(player)) else {
+            return;
+        };
+        // Active entries come first in the side list, in slot order
+        let active_indices: Vec<usize> = side_info
+            .pokemon
+            .iter()
+            .zip(side.request_order())
+            .filter(|(req_poke, _)| req_poke.active)
+            .map(|(_, &idx)| idx)
+            .collect();
+        let player = side.player;
+        for (slot, idx) in active.iter().zip(active_indices) {
+            if let Some(previous) = self.request_moves.get(&idx) {
+                let changes = Self::pp_drift(previous, &slot.moves);
+                self.hold_drift(idx, changes);
             }
+            if let Some(poke) = self
+                .get_side_mut(player)
+                .and_then(|side| side.pokemon.get_mut(idx))
+            {
+                poke.reconcile_pp(&slot.moves);
+            }
+            self.request_moves.insert(idx, slot.moves.clone());
         }
     }
 
@@ -565,73 +1166,723 @@ impl TrackedBattle {
         pokemon: &Pokemon,
         details: &PokemonDetails,
         hp_status: Option<&kazam_protocol::HpStatus>,
-        _is_drag: bool,
+        is_drag: bool,
     ) {
-        let slot = pokemon.position.map(position_to_slot).unwrap_or(0);
+        self.get_or_create_side(pokemon.player, "");
+        let slot = self.active_slot(pokemon);
+        let toxic_reverts = self.mechanics().toxic_reverts_on_switch();
 
         let side = self.get_or_create_side(pokemon.player, "");
 
-        // Find existing Pokemon or create new one
-        let poke_idx = side
-            .find_pokemon(&pokemon.name)
-            .unwrap_or_else(|| {
-                // New Pokemon
-                let poke = PokemonState::from_protocol_with_name(details, &pokemon.name);
-                side.pokemon.push(poke);
-                side.pokemon.len() - 1
-            });
+        // Whoever was in this slot is leaving the field
+        let outgoing_idx = slot.and_then(|slot| side.active_indices.get(slot).copied().flatten());
+        let outgoing = outgoing_idx
+            .and_then(|idx| side.pokemon.get(idx))
+            .map(|poke| poke.identity.species.clone());
+        let passed = outgoing_idx
+            .and_then(|idx| side.pokemon.get(idx))
+            .and_then(|poke| passed_on(poke, is_drag));
+
+        // Find existing Pokemon (or its team sheet entry) or create new one
+        let existing = side.find_pokemon(&pokemon.name);
+        let sheet_entry = existing
+            .is_none()
+            .then(|| side.find_team_sheet_entry(&details.species))
+            .flatten();
+        if let Some(idx) = sheet_entry
+            && pokemon.name != details.species
+        {
+            side.pokemon[idx].identity.nickname = Some(pokemon.name.clone());
+        }
+        let existing = existing.or(sheet_entry);
+        let poke_idx = existing.unwrap_or_else(|| {
+            // New Pokemon
+            let poke = PokemonState::from_protocol_with_name(details, &pokemon.name);
+            side.pokemon.push(poke);
+            side.pokemon.len() - 1
+        });
 
         // Update the Pokemon's details (may have changed forme)
         let poke = &mut side.pokemon[poke_idx];
+        let level = details.level.unwrap_or(100);
+        let level_mismatch =
+            (existing.is_some() && poke.identity.level != level).then_some(poke.identity.level);
         poke.identity.species = details.species.clone();
-        poke.identity.level = details.level.unwrap_or(100);
+        poke.identity.level = level;
         poke.identity.gender = details.gender;
         poke.identity.shiny = details.shiny;
 
         if let Some(hp) = hp_status {
             poke.apply_hp_status(hp);
         }
+        // Switching in takes the slot's action for the turn
+        poke.has_acted_this_turn = true;
 
         // Update active slot
-        side.set_active(slot, Some(poke_idx));
-    }
+        if let Some(slot) = slot {
+            side.set_active(slot, Some(poke_idx));
+        }
+        if outgoing_idx != Some(poke_idx) {
+            side.record_switch_in(poke_idx, outgoing_idx.is_some());
+        }
+        if let Some((boosts, volatiles)) = passed
+            && outgoing_idx != Some(poke_idx)
+        {
+            let poke = &mut side.pokemon[poke_idx];
+            if let Some(boosts) = boosts {
+                poke.boosts = boosts;
+            }
+            poke.volatiles.extend(volatiles);
+        }
 
-    /// Handle a faint message
-    fn handle_faint(&mut self, pokemon: &Pokemon) {
-        if let Some(poke) = self.find_pokemon_mut(pokemon) {
-            poke.fainted = true;
-            poke.hp_current = 0;
-            poke.active = false;
+        // Gen 1-2: Toxic's counter is lost on switching, leaving regular poison
+        if toxic_reverts
+            && let Some(idx) = outgoing_idx
+            && idx != poke_idx
+            && let Some(poke) = side.pokemon.get_mut(idx)
+            && poke.status == Some(Status::BadPoison)
+        {
+            poke.status = Some(Status::Poison);
         }
 
-        // Clear from active slot
-        if let Some(side) = self.get_side_mut(pokemon.player)
-            && let Some(slot) = pokemon.position.map(position_to_slot) {
-                side.active_indices[slot] = None;
-            }
+        if let Some(species) = outgoing {
+            self.field.remove_stat_modifiers_from(pokemon.player, &species);
+            self.field.end_neutralizing_gas(pokemon.player, &species);
+        }
+
+        if existing.is_none() {
+            self.apply_scouting(pokemon.player, poke_idx);
+        }
+        if let Some(known) = level_mismatch {
+            self.inconsistency(Inconsistency::LevelMismatch {
+                pokemon: pokemon.clone(),
+                known,
+                seen: level,
+            });
+        }
     }
 
-    /// Find a Pokemon by protocol identifier (immutable)
-    fn find_pokemon(&self, pokemon: &Pokemon) -> Option<&PokemonState> {
-        self.get_side(pokemon.player)?
-            .pokemon
-            .iter()
-            .find(|p| p.name() == pokemon.name || p.identity.species == pokemon.name)
+    /// Note that `pokemon` is being withdrawn by its own item or ability
+    fn begin_forced_switch(&mut self, pokemon: &Pokemon, source: &str) {
+        self.pending_forced_switches
+            .retain(|(out, _)| out != pokemon);
+        self.pending_forced_switches
+            .push((pokemon.clone(), source.to_string()));
     }
 
-    /// Find a Pokemon by protocol identifier (mutable)
-    fn find_pokemon_mut(&mut self, pokemon: &Pokemon) -> Option<&mut PokemonState> {
-        self.get_side_mut(pokemon.player)?
-            .find_pokemon_mut(&pokemon.name)
+    /// Log why `pokemon` is coming in, before the switch is applied
+    ///
+    /// Leads aren't logged: nothing was on the field before them.
+    fn observe_switch_kind(&mut self, pokemon: &Pokemon, is_drag: bool) {
+        let forced = self
+            .pending_forced_switches
+            .iter()
+            .position(|(out, _)| out.player == pokemon.player && out.position == pokemon.position)
+            .map(|idx| self.pending_forced_switches.remove(idx).1);
+        let outgoing = pokemon
+            .position
+            .map_or(Some(0), |position| self.slot_of(pokemon.player, position))
+            .and_then(|slot| {
+                self.get_side(pokemon.player)?
+                    .active_indices
+                    .get(slot)
+                    .copied()
+            })
+            .flatten();
+
+        let kind = match (is_drag, forced) {
+            (true, _) => SwitchKind::Dragged,
+            (false, Some(source)) => SwitchKind::ForcedByEffect { source },
+            (false, None) if outgoing.is_some() => SwitchKind::Chosen,
+            (false, None) if self.turn > 0 => SwitchKind::Replacement,
+            (false, None) => return,
+        };
+        if self.config.tracks_history() {
+            self.switches.push(SwitchRecord {
+                turn: self.turn,
+                pokemon: pokemon.clone(),
+                kind,
+            });
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use kazam_protocol::{GameType, HpStatus, Player, Stat, parse_server_message};
+    /// Add a placeholder for a Pokemon listed at team preview
+    ///
+    /// Switch-ins and requests claim placeholders by species, so the level and
+    /// gender the preview shows carry over. Hidden formes stay as `Species-*`
+    /// until revealed.
+    fn handle_preview_pokemon(&mut self, player: Player, details: &PokemonDetails) {
+        let side = self.get_or_create_side(player, "");
+        side.previewed = true;
+        if side.find_team_sheet_entry(&details.species).is_none() {
+            side.pokemon.push(PokemonState::from_protocol(details));
+        }
+    }
 
-    use crate::{BattleKnowledge, SideCondition, Weather};
+    /// Record Guard Split or Power Split averaging two Pokemon's raw stats
+    ///
+    /// When the request is ahead of the log, our Pokemon's stats from before
+    /// and after the split bound what the foe's were before it.
+    fn handle_stat_split(&mut self, user: &Pokemon, target: &Pokemon, kind: SplitKind) {
+        for pokemon in [user, target] {
+            if let Some(poke) = self.resolve_pokemon_mut(pokemon) {
+                poke.stat_split = Some(kind);
+            }
+        }
+        let Some(player) = self.viewpoint() else {
+            return;
+        };
+        for (ours, theirs) in [(user, target), (target, user)] {
+            let Some(idx) = (ours.player == player)
+                .then(|| self.get_side(player)?.find_pokemon(&ours.name))
+                .flatten()
+            else {
+                continue;
+            };
+            for stat in kind.stats() {
+                let Some(before) = self.stat_before_request(idx, stat) else {
+                    continue;
+                };
+                let Some(poke) = self.find_pokemon_mut(ours) else {
+                    continue;
+                };
+                let after = poke.stats.as_ref().and_then(|stats| stats.get(stat));
+                // The request already replaced the snapshot restored on switch-out
+                if let Some(value) = poke
+                    .base_stats_snapshot
+                    .as_mut()
+                    .and_then(|snapshot| snapshot.get_mut(stat))
+                {
+                    *value = before;
+                }
+                let Some((min, max)) =
+                    after.and_then(|after| SplitKind::partner_range(before, after))
+                else {
+                    continue;
+                };
+                if theirs.player != player
+                    && let Some(foe) = self.find_pokemon_mut(theirs)
+                {
+                    foe.add_stat_bound(StatBound { stat, min, max });
+                }
+            }
+        }
+    }
+
+    /// Record what Download's boost reveals about the opposing Pokemon's defenses
+    ///
+    /// Download raises Attack if the foe's Defense is lower than its Special
+    /// Defense, otherwise Special Attack. Only attributable with a single foe.
+    fn handle_download(&mut self, pokemon: &Pokemon, stat: Stat) {
+        let constraint = match stat {
+            Stat::Atk => StatConstraint {
+                higher: Stat::Spd,
+                lower: Stat::Def,
+                or_equal: false,
+            },
+            Stat::Spa => StatConstraint {
+                higher: Stat::Def,
+                lower: Stat::Spd,
+                or_equal: true,
+            },
+            _ => return,
+        };
+
+        let foes: Vec<(kazam_protocol::Player, usize)> = self
+            .all_pokemon()
+            .active()
+            .filter(|p| !self.same_team(p.player, pokemon.player))
+            .map(|p| (p.player, p.index))
+            .collect();
+
+        let [(player, idx)] = foes[..] else {
+            return;
+        };
+        if let Some(poke) = self
+            .get_side_mut(player)
+            .and_then(|side| side.get_pokemon_mut(idx))
+        {
+            poke.add_stat_constraint(constraint);
+        }
+    }
+
+    /// Track a two-turn move's charge and execution turns from a |move| line
+    ///
+    /// The charge turn's line comes with `[still]` and is followed by
+    /// |-prepare|; the execution turn's line uses up the charge.
+    fn observe_charge_move(
+        &mut self,
+        pokemon: &Pokemon,
+        move_name: &str,
+        target: Option<&Pokemon>,
+        still: bool,
+    ) {
+        self.pending_charge = still.then(|| (pokemon.clone(), target.cloned()));
+        if let Some(poke) = self.find_pokemon_mut(pokemon)
+            && poke
+                .charging_move
+                .as_ref()
+                .is_some_and(|c| to_id(&c.move_name) == to_id(move_name))
+        {
+            poke.stop_charging();
+        }
+    }
+
+    /// Note a Magic Bounce / Magic Coat activation so its effect is credited to the reflector
+    fn begin_reflect(&mut self, reflector: &Pokemon, effect: &str) {
+        if let Some(ability) = effect.strip_prefix("ability: ")
+            && self.config.tracks_items_abilities()
+            && let Some(poke) = self.find_pokemon_mut(reflector) {
+                poke.record_ability(ability);
+            }
+        if self.config.tracks_action_log() {
+            self.pending_reflect = Some((reflector.clone(), effect.to_string()));
+        }
+    }
+
+    /// Record a pending reflection once its effect lands
+    fn land_reflect(&mut self) {
+        let Some((reflector, source)) = self.pending_reflect.take() else {
+            return;
+        };
+        // The bounced |move| line carries [from], so last_move is still the original user's
+        let (user, move_name) = match &self.last_move {
+            Some((user, name)) if user.player != reflector.player => {
+                (Some(user.clone()), name.clone())
+            }
+            _ => (None, String::new()),
+        };
+        self.reflected_moves.push(ReflectedMove {
+            turn: self.turn,
+            reflector,
+            source,
+            user,
+            move_name,
+        });
+    }
+
+    /// Credit a removed side condition to the move or ability that cleared it
+    ///
+    /// The cause is the line's `[from]` tag, or a preceding screen breaker or
+    /// Screen Cleaner activation. Conditions that simply ran out aren't credited.
+    /// Consecutive lines from the same cause are one action.
+    fn attribute_removal(&mut self, player: Player, condition: SideCondition, from: Option<&str>) {
+        let cause = match (from, &self.pending_removal) {
+            (Some(from), _) => from
+                .split_once(": ")
+                .map_or(from, |(_, name)| name)
+                .to_string(),
+            (None, Some((_, cause))) => cause.clone(),
+            (None, None) => return,
+        };
+        // Tagged lines ([of] isn't parsed) are credited to the move's user
+        let user = match &self.pending_removal {
+            Some((user, name)) if *name == cause => Some(user.clone()),
+            _ => self
+                .last_move
+                .as_ref()
+                .filter(|(_, name)| *name == cause)
+                .map(|(user, _)| user.clone()),
+        };
+
+        let turn = self.turn;
+        let continues = self
+            .condition_removals
+            .last()
+            .is_some_and(|last| last.turn == turn && last.cause == cause && last.user == user);
+        if !continues {
+            self.condition_removals.push(ConditionRemoval {
+                turn,
+                cause: cause.clone(),
+                user,
+                removed: Vec::new(),
+            });
+        }
+        let Some(removal) = self.condition_removals.last_mut() else {
+            return;
+        };
+        let from_side: Vec<SideCondition> = removal
+            .removed
+            .iter()
+            .filter(|(p, _)| *p == player)
+            .map(|(_, c)| *c)
+            .collect();
+        removal.removed.push((player, condition));
+
+        let hazards = condition.is_hazard() && !from_side.iter().any(|c| c.is_hazard());
+        let screens = condition.is_screen() && !from_side.iter().any(|c| c.is_screen());
+        if let Some(side) = self.get_side_mut(player) {
+            side.record_clear(&cause, from_side.is_empty(), hazards, screens);
+        }
+    }
+
+    /// Handle a faint message
+    fn handle_faint(&mut self, pokemon: &Pokemon) {
+        if let Some(poke) = self.find_pokemon_mut(pokemon) {
+            poke.fainted = true;
+            poke.hp_current = 0;
+            poke.active = false;
+            let species = poke.identity.species.clone();
+            self.field.remove_stat_modifiers_from(pokemon.player, &species);
+            self.field.end_neutralizing_gas(pokemon.player, &species);
+        }
+
+        // Clear from active slot
+        if let Some(slot) = self.active_slot(pokemon)
+            && let Some(side) = self.get_side_mut(pokemon.player)
+            && let Some(active) = side.active_indices.get_mut(slot)
+        {
+            *active = None;
+        }
+
+        // A Commanding Tatsugiri comes back out when its Dondozo faints
+        if let Some(side) = self.get_side_mut(pokemon.player) {
+            for idx in side.active_indices.clone().into_iter().flatten() {
+                side.pokemon[idx].remove_volatile(&Volatile::Commanding);
+            }
+        }
+    }
+
+    /// Active slot a Pokemon's position refers to (slot 0 without a position)
+    ///
+    /// A position outside the side's active slots is an inconsistency.
+    fn active_slot(&mut self, pokemon: &Pokemon) -> Option<usize> {
+        let Some(position) = pokemon.position else {
+            return Some(0);
+        };
+        let slot = self.slot_of(pokemon.player, position);
+        if slot.is_none() {
+            self.inconsistency(Inconsistency::PositionOutOfRange(pokemon.clone()));
+        }
+        slot
+    }
+
+    /// Set up the heal a Wish, Healing Wish or Lunar Dance leaves on the user's slot
+    fn observe_heal_move(&mut self, pokemon: &Pokemon, move_name: &str) {
+        let id = to_id(move_name);
+        let Some(&(_, amount, delayed)) = HEAL_SETUP_MOVES.iter().find(|(m, ..)| *m == id) else {
+            return;
+        };
+        let Some(slot) = self.active_slot(pokemon) else {
+            return;
+        };
+        // Before Gen 5, Wish heals half the recipient's max HP instead
+        let user_max_hp = (self.generation >= 5)
+            .then(|| self.find_pokemon(pokemon).and_then(|poke| poke.hp_max))
+            .flatten();
+        let landing = if delayed {
+            HealLanding::OnTurn(self.turn + 1)
+        } else {
+            HealLanding::OnNextSwitch
+        };
+        if let Some(side) = self.get_side_mut(pokemon.player) {
+            side.add_pending_heal(PendingHeal {
+                slot,
+                source: move_name.to_string(),
+                user: pokemon.name.clone(),
+                amount,
+                user_max_hp,
+                landing,
+            });
+        }
+    }
+
+    /// Drop the heal a move just set up when the move fails
+    fn observe_heal_move_failed(&mut self, pokemon: &Pokemon) {
+        let turn = self.turn;
+        if let Some(side) = self.get_side_mut(pokemon.player)
+            && side.pending_heals.last().is_some_and(|heal| {
+                heal.user == pokemon.name
+                    && match heal.landing {
+                        HealLanding::OnTurn(landing) => landing == turn + 1,
+                        HealLanding::OnNextSwitch => true,
+                    }
+            })
+        {
+            side.pending_heals.pop();
+        }
+    }
+
+    /// Before Gen 8, Healing Wish and Lunar Dance are used up by the next
+    /// switch-in even at full HP; later gens wait for one that needs them
+    fn observe_heal_switch_in(&mut self, pokemon: &Pokemon) {
+        if self.generation >= 8 {
+            return;
+        }
+        if let Some(slot) = self.active_slot(pokemon)
+            && let Some(side) = self.get_side_mut(pokemon.player)
+        {
+            side.pending_heals
+                .retain(|heal| heal.slot != slot || heal.landing != HealLanding::OnNextSwitch);
+        }
+    }
+
+    /// Record how a move was ordered against the foes that already moved this turn
+    fn record_move_order(&mut self, pokemon: &Pokemon, move_name: &str) {
+        let speed_stage = self.find_pokemon(pokemon).map_or(0, |poke| poke.boosts.spe);
+        for (earlier, earlier_move, earlier_stage) in &self.turn_movers {
+            if self.same_team(earlier.player, pokemon.player) {
+                continue;
+            }
+            self.move_orders.push(MoveOrder {
+                turn: self.turn,
+                first: earlier.clone(),
+                first_move: earlier_move.clone(),
+                first_speed_stage: *earlier_stage,
+                second: pokemon.clone(),
+                second_move: move_name.to_string(),
+                second_speed_stage: speed_stage,
+                trick_room: self.field.trick_room,
+            });
+        }
+        self.turn_movers
+            .push((pokemon.clone(), move_name.to_string(), speed_stage));
+    }
+
+    /// Learn the ability the other Pokemon in an ability change gave up
+    ///
+    /// Entrainment hands over the user's ability, Trace copies the `[of]`
+    /// Pokemon's and Skill Swap trades both ways in two lines. What's passed on
+    /// is that Pokemon's own ability unless it had been replaced too.
+    fn learn_ability_source(
+        &mut self,
+        pokemon: &Pokemon,
+        ability: &str,
+        effect: &str,
+        of: Option<&Pokemon>,
+        was_changed: bool,
+    ) {
+        let is_other = |p: &Pokemon| p.player != pokemon.player || p.name != pokemon.name;
+        let user = self
+            .last_move
+            .as_ref()
+            .map(|(user, _)| user.clone())
+            .filter(is_other);
+        // Whether the partner's ability had been replaced before this change
+        let partner = of.cloned().or(user).map(|partner| {
+            let changed = self
+                .find_pokemon(&partner)
+                .is_some_and(|p| p.ability_override.is_some());
+            (partner, changed)
+        });
+        let source = match effect {
+            "ability: Trace" => {
+                if !was_changed && let Some(holder) = self.find_pokemon_mut(pokemon) {
+                    holder.record_ability("Trace");
+                }
+                partner
+            }
+            "move: Entrainment" => partner,
+            "move: Skill Swap" => match self.pending_skill_swap.take() {
+                // The second line: the first one already replaced its partner's ability
+                Some((first, changed)) if partner.as_ref().is_none_or(|(p, _)| *p == first) => {
+                    Some((first, changed))
+                }
+                _ => {
+                    self.pending_skill_swap = Some((pokemon.clone(), was_changed));
+                    partner
+                }
+            },
+            _ => None,
+        };
+        if let Some((source, false)) = source
+            && let Some(poke) = self.find_pokemon_mut(&source)
+        {
+            poke.record_ability(ability);
+        }
+    }
+
+    /// Credit direct damage to whoever used the last move, unless it hit themselves
+    fn credit_damage(&mut self, target: &Pokemon, lost: u32, lost_exact: f32) {
+        let Some((user, _)) = self.last_move.clone() else {
+            return;
+        };
+        if user.player == target.player && user.name == target.name {
+            return;
+        }
+        if let Some(poke) = self.find_pokemon_mut(&user) {
+            poke.damage_dealt_estimate += lost;
+            poke.damage_dealt.add(DamageKind::Direct, lost_exact);
+        }
+    }
+
+    /// Note the last move's hit on `target`, unless it hit its own user
+    fn record_hit(&mut self, target: &Pokemon, lost_exact: f32) {
+        let Some((user, move_name)) = self.last_move.clone() else {
+            return;
+        };
+        if user.player == target.player && user.name == target.name {
+            return;
+        }
+        let Some(position) = user.position else {
+            return;
+        };
+        if let Some(poke) = self.find_pokemon_mut(target) {
+            poke.hits_taken_this_turn.push(HitRecord {
+                category: move_category(&move_name),
+                move_name,
+                damage_fraction: lost_exact / 100.0,
+                attacker: (user.player, position),
+            });
+        }
+    }
+
+    /// Find a Pokemon by protocol identifier (immutable)
+    pub(super) fn find_pokemon(&self, pokemon: &Pokemon) -> Option<&PokemonState> {
+        self.get_side(pokemon.player)?
+            .pokemon
+            .iter()
+            .find(|p| p.name() == pokemon.name || p.identity.species == pokemon.name)
+    }
+
+    /// Whether a move into `target` spends an extra PP to its Pressure
+    fn targets_pressure(&self, user: &Pokemon, target: Option<&Pokemon>) -> bool {
+        target
+            .filter(|target| target.player != user.player)
+            .and_then(|target| self.find_pokemon(target))
+            .and_then(|target| target.effective_ability_on(&self.field))
+            .is_some_and(|ability| to_id(ability) == "pressure")
+    }
+
+    /// Find a Pokemon by protocol identifier (mutable)
+    fn find_pokemon_mut(&mut self, pokemon: &Pokemon) -> Option<&mut PokemonState> {
+        self.get_side_mut(pokemon.player)?
+            .find_pokemon_mut(&pokemon.name)
+    }
+
+    /// Find a Pokemon a message names, reporting it if it isn't tracked
+    pub(super) fn resolve_pokemon_mut(&mut self, pokemon: &Pokemon) -> Option<&mut PokemonState> {
+        if self.find_pokemon(pokemon).is_none() {
+            self.inconsistency(Inconsistency::UnresolvedPokemon(pokemon.clone()));
+            return None;
+        }
+        self.find_pokemon_mut(pokemon)
+    }
+
+    /// Record a message that didn't fit the tracked state and report it as
+    /// the config's strictness mode asks; minor ones never panic
+    pub(crate) fn inconsistency(&mut self, what: Inconsistency) {
+        match (self.config.strictness_mode(), what.severity()) {
+            (StrictnessMode::Silent, _) => {}
+            (StrictnessMode::Panic, Severity::Major) => panic!("tracking inconsistency: {what}"),
+            (_, Severity::Major) => tracing::warn!("tracking inconsistency: {what}"),
+            (_, Severity::Minor) => tracing::debug!("tracking inconsistency: {what}"),
+        }
+        self.inconsistencies.push(what);
+    }
+}
+
+/// Fill in what a team preview placeholder was missing: the nickname and a
+/// hidden forme
+fn claim_placeholder(poke: &mut PokemonState, details: &PokemonDetails, name: &str) {
+    if poke.identity.species.ends_with("-*") {
+        poke.identity.species = details.species.clone();
+    }
+    if poke.identity.nickname.is_none() && name != poke.identity.species {
+        poke.identity.nickname = Some(name.to_string());
+    }
+}
+
+/// Copy the full information a request carries onto a tracked Pokemon
+///
+/// Empty fields are treated as missing and leave tracked state alone, except
+/// the item, where empty means the Pokemon has none.
+fn sync_request_pokemon(poke: &mut PokemonState, req_poke: &SidePokemon, config: TrackerConfig) {
+    if config.tracks_moves() && !req_poke.moves.is_empty() {
+        poke.set_request_moves(&req_poke.moves);
+    }
+    if config.tracks_items_abilities() {
+        if !req_poke.ability.is_empty() {
+            poke.known_ability = Some(req_poke.ability.clone());
+        }
+        poke.known_item = if req_poke.item.is_empty() {
+            None
+        } else {
+            Some(req_poke.item.clone())
+        };
+    }
+    poke.active = req_poke.active;
+
+    // Our own tera type, and the type we terastallized into
+    if let Some(tera_type) = req_poke.teratype.as_deref().and_then(TeraType::from_protocol) {
+        poke.tera_type = Some(tera_type);
+    }
+    if let Some(tera_type) = req_poke
+        .terastallized
+        .as_deref()
+        .filter(|t| !t.is_empty())
+        .and_then(TeraType::from_protocol)
+    {
+        poke.terastallize(tera_type);
+    }
+
+    // Missing stats deserialize to all zeroes
+    if req_poke.stats != PokemonStats::default() {
+        poke.set_stats(&req_poke.stats);
+    }
+
+    // Max HP always comes from the condition string
+    if let Some((current, max)) = req_poke.hp() {
+        poke.hp_current = current;
+        poke.hp_max = Some(max);
+    }
+
+    if req_poke.condition.is_empty() {
+        return;
+    }
+    if let Some(status_str) = req_poke.status() {
+        if status_str == "fnt" {
+            poke.fainted = true;
+            poke.hp_current = 0;
+            poke.status = None;
+        } else {
+            poke.status = Status::from_protocol(status_str);
+        }
+    } else {
+        poke.status = None;
+        poke.fainted = poke.hp_current == 0;
+    }
+}
+
+/// Boosts (None for Shed Tail) and volatiles, with their counts, handed on by a switch
+type PassedOn = (Option<StatStages>, Vec<(Volatile, VolatileState)>);
+
+/// Boosts and volatiles a Pokemon leaving with Baton Pass or Shed Tail hands on
+///
+/// Shed Tail passes only its Substitute. A forced switch passes nothing.
+fn passed_on(poke: &PokemonState, is_drag: bool) -> Option<PassedOn> {
+    if is_drag {
+        return None;
+    }
+    let passing = |keep: fn(&Volatile) -> bool| {
+        poke.volatiles
+            .states()
+            .filter(|(v, _)| keep(v))
+            .map(|(v, state)| (v.clone(), state.clone()))
+            .collect()
+    };
+    match to_id(poke.last_move.as_deref()?).as_str() {
+        "batonpass" => Some((Some(poke.boosts), passing(Volatile::is_passable))),
+        "shedtail" => Some((None, passing(|v| *v == Volatile::Substitute))),
+        _ => None,
+    }
+}
+
+/// Whether an effect reflects status moves back at their user
+fn is_reflect_effect(effect: &str) -> bool {
+    effect.ends_with("Magic Bounce") || effect.ends_with("Magic Coat")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{GameType, HpStatus, Player, Stat, parse_server_message};
+
+    use crate::query::MoveCategory;
+    use crate::{
+        BattleKnowledge, EndlessBattleWarning, KnownMove, MoveProvenance, SideCondition, Type,
+        Weather,
+    };
 
     fn create_test_pokemon(name: &str, _level: u8) -> Pokemon {
         Pokemon {
@@ -747,6 +1998,7 @@ mod tests {
             pokemon: create_test_pokemon("Pikachu", 50),
             stat: Stat::Atk,
             amount: 2,
+            from: None,
         });
 
         let poke = &battle.get_side(Player::P1).unwrap().pokemon[0];
@@ -767,6 +2019,7 @@ mod tests {
         battle.apply_message(&ServerMessage::Status {
             pokemon: create_test_pokemon("Pikachu", 50),
             status: "par".to_string(),
+            from: None,
         });
 
         let poke = &battle.get_side(Player::P1).unwrap().pokemon[0];
@@ -788,6 +2041,8 @@ mod tests {
         battle.apply_message(&ServerMessage::Weather {
             weather: "SunnyDay".to_string(),
             upkeep: false,
+            from: None,
+            of: None,
         });
 
         assert_eq!(battle.field.weather, Some(Weather::Sun));
@@ -796,6 +2051,8 @@ mod tests {
         battle.apply_message(&ServerMessage::Weather {
             weather: "SunnyDay".to_string(),
             upkeep: true,
+            from: None,
+            of: None,
         });
 
         assert_eq!(battle.field.weather, Some(Weather::Sun));
@@ -819,6 +2076,45 @@ mod tests {
         assert_eq!(poke.hp_current, 0);
     }
 
+    #[test]
+    fn test_revival_blessing_revives_fainted_bench() {
+        let mut battle = TrackedBattle::new();
+        for line in [
+            "|player|p1|Alice|1",
+            "|switch|p1a: Kingambit|Kingambit, L78, M|100/100",
+            "|faint|p1a: Kingambit",
+            "|switch|p1a: Pawmot|Pawmot, L84, F|100/100",
+            "|move|p1a: Pawmot|Revival Blessing|p1a: Pawmot",
+            "|-heal|p1: Kingambit|50/100|[from] move: Revival Blessing",
+        ] {
+            battle.apply_message(&kazam_protocol::parse_server_message(line).unwrap());
+        }
+
+        let side = battle.get_side(Player::P1).unwrap();
+        let kingambit = &side.pokemon[side.find_pokemon("Kingambit").unwrap()];
+        assert!(!kingambit.fainted);
+        assert!(!kingambit.active);
+        assert_eq!(kingambit.hp_current, 50);
+        assert!(battle.inconsistencies().is_empty());
+    }
+
+    #[test]
+    fn test_rename_before_win() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        for line in [
+            "|player|p1|Alice|1",
+            "|player|p2|Bob|2",
+            "|n|☆Bobby|bob",
+            "|win|☆Bobby",
+        ] {
+            battle.apply_message(&kazam_protocol::parse_server_message(line).unwrap());
+        }
+
+        assert_eq!(battle.get_side(Player::P2).unwrap().username, "Bobby");
+        assert_eq!(battle.winning_player(), Some(Player::P2));
+        assert!(!battle.rename_player("nobody", "Carol"));
+    }
+
     #[test]
     fn test_update_win() {
         let mut battle = TrackedBattle::new();
@@ -860,6 +2156,93 @@ mod tests {
         assert_eq!(me.username, "Alice");
         assert_eq!(me.pokemon.len(), 1);
         assert_eq!(me.pokemon[0].known_ability.as_deref(), Some("Static"));
+        // The request lists the whole set, even with two moves
+        assert_eq!(
+            me.pokemon[0].move_provenance("surf"),
+            Some(MoveProvenance::OwnRequest)
+        );
+        assert!(me.pokemon[0].moveset_complete());
+    }
+
+    fn party_request(rqid: u64, order: &[(&str, &str, &str)]) -> BattleRequest {
+        let pokemon: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(i, (name, details, item))| {
+                serde_json::json!({
+                    "ident": format!("p1: {name}"),
+                    "details": details,
+                    "condition": "100/100",
+                    "active": i == 0,
+                    "moves": [],
+                    "ability": "Natural Cure",
+                    "item": item
+                })
+            })
+            .collect();
+        let json = serde_json::json!({
+            "rqid": rqid,
+            "side": {"name": "Alice", "id": "p1", "pokemon": pokemon}
+        });
+        BattleRequest::parse(&json).unwrap()
+    }
+
+    #[test]
+    fn test_request_claims_preview_placeholders() {
+        let mut battle = TrackedBattle::for_player(Player::P1);
+        for line in [
+            "|poke|p1|Urshifu-*, L79, M|item",
+            "|poke|p1|Blissey, L80, F|item",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle.apply_request(&party_request(
+            1,
+            &[
+                ("Fist", "Urshifu-Rapid-Strike, L79, M", "choiceband"),
+                ("Blissey", "Blissey, L82, F", "heavydutyboots"),
+            ],
+        ));
+
+        let me = battle.me().unwrap();
+        assert_eq!(me.pokemon.len(), 2);
+        assert_eq!(me.pokemon[0].name(), "Fist");
+        assert_eq!(me.pokemon[0].identity.species, "Urshifu-Rapid-Strike");
+        assert_eq!(me.pokemon[1].identity.level, 82);
+        assert!(matches!(
+            battle.inconsistencies(),
+            [Inconsistency::LevelMismatch {
+                known: 80,
+                seen: 82,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_request_reorder_keeps_party_stable() {
+        let blissey = ("Blissey", "Blissey, L80, F", "heavydutyboots");
+        let garchomp = ("Chompy", "Garchomp, L77, M", "lifeorb");
+        let corviknight = ("Corviknight", "Corviknight, L79, F", "leftovers");
+
+        let mut battle = TrackedBattle::new();
+        battle.apply_request(&party_request(1, &[blissey, garchomp, corviknight]));
+        // After "switch 3" the server lists Corviknight first and Blissey in its old slot
+        battle.apply_request(&party_request(2, &[corviknight, garchomp, blissey]));
+
+        let me = battle.me().unwrap();
+        let items: Vec<_> = me.pokemon.iter().map(|p| p.known_item.as_deref()).collect();
+        assert_eq!(
+            items,
+            [Some("heavydutyboots"), Some("lifeorb"), Some("leftovers")]
+        );
+        assert_eq!(me.pokemon[1].identity.nickname.as_deref(), Some("Chompy"));
+        assert!(me.pokemon[2].active && !me.pokemon[0].active);
+
+        assert_eq!(me.request_order(), [2, 1, 0]);
+        assert_eq!(me.switch_target(3), Some(0));
+        assert_eq!(me.switch_slot(2), Some(1));
+        assert_eq!(me.switch_target(0), None);
     }
 
     #[test]
@@ -1199,4 +2582,1819 @@ mod tests {
         assert_eq!(p1.pokemon[milotic].hp_current, 96);
         assert_eq!(p1.pokemon[milotic].hp_max, Some(394));
     }
+
+    #[test]
+    fn test_pledge_combo_in_doubles() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|gen|9
+|start
+|switch|p1a: Venusaur|Venusaur, L50, M|100/100
+|switch|p1b: Charizard|Charizard, L50, M|100/100
+|switch|p2a: Snorlax|Snorlax, L50, M|100/100
+|switch|p2b: Gengar|Gengar, L50, M|100/100
+|turn|1
+|
+|move|p1a: Venusaur|Grass Pledge|p2a: Snorlax|[still]
+|-waiting|p1a: Venusaur|p1b: Charizard
+|move|p1b: Charizard|Fire Pledge|p2a: Snorlax
+|-combine
+|-damage|p2a: Snorlax|60/100
+|-sidestart|p2: Bob|Fire Pledge
+|move|p2a: Snorlax|Horn Drill|p1a: Venusaur
+|-damage|p1a: Venusaur|0 fnt
+|-ohko|p1a: Venusaur
+|faint|p1a: Venusaur
+|
+|-damage|p2a: Snorlax|48/100|[from] Fire Pledge
+|-damage|p2b: Gengar|88/100|[from] Fire Pledge
+|turn|2"#;
+
+        let mut battle = TrackedBattle::omniscient();
+        for line in log.lines() {
+            let message = parse_server_message(line).unwrap();
+            battle.apply_message(&message);
+        }
+
+        assert_eq!(battle.combined_moves.len(), 1);
+        let combo = &battle.combined_moves[0];
+        assert_eq!(combo.turn, 1);
+        assert_eq!(combo.waiting.name, "Venusaur");
+        assert_eq!(combo.partner.name, "Charizard");
+        assert_eq!(combo.move_name, "Fire Pledge");
+
+        let p1 = battle.get_side(Player::P1).unwrap();
+        let p2 = battle.get_side(Player::P2).unwrap();
+        assert!(!p1.has_condition(SideCondition::SeaOfFire));
+        assert!(p2.has_condition(SideCondition::SeaOfFire));
+        assert_eq!(
+            p2.conditions[&SideCondition::SeaOfFire].turns_remaining,
+            Some(3)
+        );
+
+        battle.apply_message(&parse_server_message("|turn|3").unwrap());
+        let p2 = battle.get_side(Player::P2).unwrap();
+        assert_eq!(
+            p2.conditions[&SideCondition::SeaOfFire].turns_remaining,
+            Some(2)
+        );
+
+        battle.apply_message(&parse_server_message("|-sideend|p2: Bob|Fire Pledge").unwrap());
+        assert!(!battle.get_side(Player::P2).unwrap().has_condition(SideCondition::SeaOfFire));
+    }
+
+    fn charizard_request(rqid: u64, details: &str, stats: serde_json::Value) -> BattleRequest {
+        let json = serde_json::json!({
+            "rqid": rqid,
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Charizard",
+                    "details": details,
+                    "condition": "297/297",
+                    "active": true,
+                    "stats": stats,
+                    "moves": ["flareblitz", "dragonclaw"],
+                    "ability": "Blaze",
+                    "item": "Charizardite X"
+                }, {
+                    "ident": "p1: Ditto",
+                    "details": "Ditto",
+                    "condition": "0 fnt",
+                    "active": false,
+                    "stats": {"atk": 136, "def": 136, "spa": 136, "spd": 136, "spe": 136},
+                    "moves": ["transform"],
+                    "ability": "Imposter",
+                    "item": "Choice Scarf"
+                }]
+            }
+        });
+        BattleRequest::parse(&json).unwrap()
+    }
+
+    #[test]
+    fn test_request_stats_across_mega_and_transform() {
+        let mut battle = TrackedBattle::new();
+
+        battle.apply_request(&charizard_request(
+            1,
+            "Charizard, M",
+            serde_json::json!({"atk": 204, "def": 192, "spa": 254, "spd": 206, "spe": 299}),
+        ));
+        let poke = &battle.me().unwrap().pokemon[0];
+        assert_eq!(poke.stats.as_ref().unwrap().atk, 204);
+        assert_eq!(poke.hp_max, Some(297));
+        assert_eq!(poke.effective_stat(Stat::Spe), Some(299));
+
+        // Mega Evolution changes stats permanently
+        battle.apply_request(&charizard_request(
+            2,
+            "Charizard-Mega-X, M",
+            serde_json::json!({"atk": 296, "def": 258, "spa": 296, "spd": 206, "spe": 299}),
+        ));
+        let poke = &battle.me().unwrap().pokemon[0];
+        assert_eq!(poke.stats.as_ref().unwrap().atk, 296);
+        assert_eq!(poke.base_stats_snapshot.as_ref().unwrap().atk, 296);
+
+        let ditto = &battle.me().unwrap().pokemon[1];
+        assert!(ditto.fainted);
+        assert_eq!(ditto.hp_current, 0);
+
+        // Transform copies the target's stats until switch-out
+        battle.get_side_mut(Player::P1).unwrap().set_active(0, Some(0));
+        battle.apply_message(&ServerMessage::Transform {
+            pokemon: create_test_pokemon("Charizard", 50),
+            species: "Garchomp".to_string(),
+        });
+        battle.apply_request(&charizard_request(
+            3,
+            "Charizard-Mega-X, M",
+            serde_json::json!({"atk": 359, "def": 226, "spa": 196, "spd": 206, "spe": 299}),
+        ));
+        let poke = &battle.me().unwrap().pokemon[0];
+        assert_eq!(poke.stats.as_ref().unwrap().atk, 359);
+        assert_eq!(poke.base_stats_snapshot.as_ref().unwrap().atk, 296);
+
+        battle.get_side_mut(Player::P1).unwrap().set_active(0, Some(1));
+        let poke = &battle.me().unwrap().pokemon[0];
+        assert!(poke.transformed.is_none());
+        assert_eq!(poke.stats.as_ref().unwrap().atk, 296);
+    }
+
+    #[test]
+    fn test_transform_leaks_opponent_stats() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Ditto|Ditto|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|-transform|p1a: Ditto|p2a: Garchomp|[from] ability: Imposter
+|turn|1"#;
+
+        let mut battle = TrackedBattle::new();
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        // Ditto's stats while transformed are Garchomp's, except HP
+        let json = serde_json::json!({
+            "rqid": 2,
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Ditto",
+                    "details": "Ditto",
+                    "condition": "216/216",
+                    "active": true,
+                    "stats": {"atk": 182, "def": 115, "spa": 100, "spd": 105, "spe": 154},
+                    "moves": ["swordsdance", "earthquake"],
+                    "ability": "Rough Skin",
+                    "item": "Choice Scarf"
+                }]
+            }
+        });
+        battle.apply_request(&BattleRequest::parse(&json).unwrap());
+
+        let garchomp = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        let stats = garchomp.stats.as_ref().unwrap();
+        assert_eq!(stats.atk, 182);
+        assert_eq!(stats.spe, 154);
+
+        // Our own snapshot is untouched by the leaked stats
+        let ditto = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert!(ditto.base_stats_snapshot.is_none());
+    }
+
+    #[test]
+    fn test_download_records_defense_constraint() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Blissey|Blissey, F|100/100
+|switch|p2a: Porygon-Z|Porygon-Z|100/100
+|-boost|p2a: Porygon-Z|spa|1|[from] ability: Download"#;
+
+        let mut battle = TrackedBattle::new();
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let porygon = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(porygon.known_ability.as_deref(), Some("Download"));
+        assert_eq!(porygon.boosts.spa, 1);
+
+        let blissey = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert_eq!(
+            blissey.stat_constraints,
+            vec![StatConstraint {
+                higher: Stat::Def,
+                lower: Stat::Spd,
+                or_equal: true,
+            }]
+        );
+        assert!(blissey.stat_constraints[0].holds(30, 30));
+    }
+
+    #[test]
+    fn test_sword_of_ruin_lowers_opposing_defense() {
+        let json = serde_json::json!({
+            "rqid": 1,
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Corviknight",
+                    "details": "Corviknight, L50, M",
+                    "condition": "175/175",
+                    "active": true,
+                    "stats": {"atk": 107, "def": 200, "spa": 65, "spd": 105, "spe": 87},
+                    "moves": ["bravebird"],
+                    "ability": "Pressure",
+                    "item": "Leftovers"
+                }]
+            }
+        });
+
+        let mut battle = TrackedBattle::new();
+        battle.apply_request(&BattleRequest::parse(&json).unwrap());
+
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|175/175
+|switch|p2a: Chien-Pao|Chien-Pao, L50|100/100
+|-ability|p2a: Chien-Pao|Sword of Ruin"#;
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let corviknight = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert_eq!(battle.effective_stat(Player::P1, corviknight, Stat::Def), Some(150));
+        assert_eq!(battle.effective_stat(Player::P1, corviknight, Stat::Spd), Some(105));
+
+        battle.apply_message(&parse_server_message("|faint|p2a: Chien-Pao").unwrap());
+        assert!(battle.field.stat_modifiers.is_empty());
+
+        let corviknight = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert_eq!(battle.effective_stat(Player::P1, corviknight, Stat::Def), Some(200));
+    }
+
+    #[test]
+    fn test_ruin_modifier_removed_on_switch_out() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Chi-Yu|Chi-Yu, L50|100/100
+|-ability|p2a: Chi-Yu|Beads of Ruin
+|turn|1
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100"#;
+
+        let mut battle = TrackedBattle::new();
+        for (i, line) in log.lines().enumerate() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+            if i == 6 {
+                assert_eq!(battle.field.stat_modifiers.len(), 1);
+                assert_eq!(battle.field.stat_modifiers[0].stat, Stat::Spd);
+            }
+        }
+
+        assert!(battle.field.stat_modifiers.is_empty());
+    }
+
+    #[test]
+    fn test_minimal_config_still_tracks_hp_and_faints() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|turn|1
+|move|p2a: Garchomp|Swords Dance|p2a: Garchomp
+|-boost|p2a: Garchomp|atk|2
+|-start|p2a: Garchomp|confusion
+|-item|p2a: Garchomp|Leftovers
+|-ability|p2a: Garchomp|Rough Skin
+|move|p1a: Corviknight|Brave Bird|p2a: Garchomp
+|-damage|p2a: Garchomp|0 fnt
+|faint|p2a: Garchomp"#;
+
+        let mut battle = TrackedBattle::with_config(TrackerConfig::minimal());
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let garchomp = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert!(garchomp.fainted);
+        assert_eq!(garchomp.hp_current, 0);
+        assert_eq!(garchomp.boosts.atk, 2);
+        assert!(garchomp.known_moves.is_empty());
+        assert!(garchomp.known_item.is_none());
+        assert!(garchomp.known_ability.is_none());
+        assert!(garchomp.volatiles.is_empty());
+        assert!(battle.last_move.is_none());
+        assert!(battle.move_orders.is_empty());
+        assert!(battle.switches.is_empty());
+
+        let mut full = TrackedBattle::new();
+        for line in log.lines() {
+            full.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert!(full.memory_footprint_estimate() >= battle.memory_footprint_estimate());
+    }
+
+    #[test]
+    fn test_history_flag_gates_orders_and_switches() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Corviknight|Corviknight, L50, M|100/100
+|switch|p2a: Garchomp|Garchomp, L50, M|100/100
+|turn|1
+|-activate|p2a: Garchomp|item: Quick Claw
+|move|p2a: Garchomp|Earthquake|p1a: Corviknight
+|-immune|p1a: Corviknight
+|move|p1a: Corviknight|Brave Bird|p2a: Garchomp
+|-damage|p2a: Garchomp|40/100
+|turn|2
+|switch|p2a: Ferrothorn|Ferrothorn, L50, M|100/100"#;
+        let run = |config: TrackerConfig| {
+            let mut battle = TrackedBattle::with_config(config);
+            for line in log.lines() {
+                battle.apply_message(&parse_server_message(line).unwrap());
+            }
+            battle
+        };
+
+        let full = run(TrackerConfig::new());
+        assert_eq!(full.move_orders.len(), 1);
+        assert_eq!(full.order_exceptions.len(), 1);
+        assert_eq!(full.switches.len(), 1);
+
+        // The rest of the action log and move tracking carry on without it
+        let battle = run(TrackerConfig::new().history(false));
+        assert!(battle.move_orders.is_empty());
+        assert!(battle.order_exceptions.is_empty());
+        assert!(battle.switches.is_empty());
+        assert!(battle.last_move.is_some());
+        let garchomp = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(garchomp.known_moves.as_slice(), ["Earthquake"]);
+        assert!(full.memory_footprint_estimate() > battle.memory_footprint_estimate());
+    }
+
+    const BOUNCE_START: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Hatterene|Hatterene, L50, F|100/100
+|turn|1"#;
+
+    #[test]
+    fn test_magic_bounce_reflects_stealth_rock() {
+        let log = r#"|move|p1a: Garchomp|Stealth Rock|p2a: Hatterene
+|move|p2a: Hatterene|Stealth Rock|p1a: Garchomp|[from]ability: Magic Bounce
+|-sidestart|p1: Alice|move: Stealth Rock"#;
+
+        let mut battle = TrackedBattle::new();
+        for line in BOUNCE_START.lines().chain(log.lines()) {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let p1 = battle.get_side(Player::P1).unwrap();
+        let p2 = battle.get_side(Player::P2).unwrap();
+        assert!(p1.has_condition(SideCondition::StealthRock));
+        assert!(!p2.has_condition(SideCondition::StealthRock));
+
+        // The bouncer revealed its ability but didn't use Stealth Rock itself
+        let hatterene = &p2.pokemon[0];
+        assert_eq!(hatterene.known_ability.as_deref(), Some("Magic Bounce"));
+        assert!(hatterene.known_moves.is_empty());
+        assert_eq!(p1.pokemon[0].known_moves.as_slice(), ["Stealth Rock"]);
+
+        assert_eq!(battle.reflected_moves.len(), 1);
+        let reflected = &battle.reflected_moves[0];
+        assert_eq!(reflected.reflector.name, "Hatterene");
+        assert_eq!(reflected.source, "ability: Magic Bounce");
+        assert_eq!(reflected.user.as_ref().unwrap().name, "Garchomp");
+        assert_eq!(reflected.move_name, "Stealth Rock");
+        assert_eq!(reflected.turn, 1);
+    }
+
+    #[test]
+    fn test_magic_bounce_activate_credits_following_effect() {
+        let log = r#"|move|p1a: Garchomp|Stealth Rock|p2a: Hatterene
+|-activate|p2a: Hatterene|ability: Magic Bounce
+|-sidestart|p1: Alice|move: Stealth Rock
+|move|p2a: Hatterene|Dazzling Gleam|p1a: Garchomp
+|-damage|p1a: Garchomp|40/100
+|turn|2
+|move|p2a: Hatterene|Mystical Fire|p1a: Garchomp
+|-unboost|p1a: Garchomp|spa|1"#;
+
+        let mut battle = TrackedBattle::new();
+        for line in BOUNCE_START.lines().chain(log.lines()) {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let p1 = battle.get_side(Player::P1).unwrap();
+        assert!(p1.has_condition(SideCondition::StealthRock));
+        assert_eq!(
+            battle.get_side(Player::P2).unwrap().pokemon[0]
+                .known_ability
+                .as_deref(),
+            Some("Magic Bounce")
+        );
+
+        // Only the bounced rocks are reflected; the later unboost came from Hatterene's own move
+        assert_eq!(battle.reflected_moves.len(), 1);
+        assert_eq!(battle.reflected_moves[0].move_name, "Stealth Rock");
+    }
+
+    const GEN9_START: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Dragapult|Dragapult, L50, M|100/100
+|switch|p2a: Corviknight|Corviknight, L50, F|100/100
+|turn|1"#;
+
+    fn gen9_battle(log: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::new();
+        for line in GEN9_START.lines().chain(log.lines()) {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    #[test]
+    fn test_poltergeist_reveals_item() {
+        let battle = gen9_battle(
+            r#"|move|p1a: Dragapult|Poltergeist|p2a: Corviknight
+|-activate|p2a: Corviknight|move: Poltergeist|Leftovers
+|-damage|p2a: Corviknight|71/100"#,
+        );
+        let corviknight = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(corviknight.known_item.as_deref(), Some("Leftovers"));
+    }
+
+    #[test]
+    fn test_stellar_tera_keeps_types() {
+        let mut battle = gen9_battle("");
+        for (player, types) in [
+            (Player::P1, vec![Type::Dragon, Type::Ghost]),
+            (Player::P2, vec![Type::Flying, Type::Steel]),
+        ] {
+            let poke = &mut battle.get_side_mut(player).unwrap().pokemon[0];
+            poke.set_types(types);
+            poke.base_types = poke.current_types.clone();
+        }
+        for line in [
+            "|-terastallize|p1a: Dragapult|Stellar",
+            "|-terastallize|p2a: Corviknight|Fighting",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let dragapult = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert_eq!(dragapult.tera_type, Some(TeraType::Stellar));
+        assert!(dragapult.terastallized);
+        assert_eq!(dragapult.get_types(), [Type::Dragon, Type::Ghost]);
+
+        let corviknight = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(corviknight.get_types(), [Type::Fighting]);
+
+        // Terastallization outlasts a switch
+        battle.apply_message(
+            &parse_server_message("|switch|p1a: Gholdengo|Gholdengo, L50|100/100").unwrap(),
+        );
+        battle.apply_message(
+            &parse_server_message("|switch|p1a: Dragapult|Dragapult, L50, M|100/100").unwrap(),
+        );
+        let dragapult = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        assert!(dragapult.terastallized);
+        assert_eq!(dragapult.get_types(), [Type::Dragon, Type::Ghost]);
+
+        // A typed tera outlasts it too; the original types still give STAB
+        battle.apply_message(
+            &parse_server_message("|switch|p2a: Kingambit|Kingambit, L50|100/100").unwrap(),
+        );
+        battle.apply_message(
+            &parse_server_message("|switch|p2a: Corviknight|Corviknight, L50|100/100").unwrap(),
+        );
+        let corviknight = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert!(corviknight.terastallized);
+        assert_eq!(corviknight.tera_type, Some(TeraType::Type(Type::Fighting)));
+        assert_eq!(corviknight.get_types(), [Type::Fighting]);
+        assert_eq!(
+            corviknight.current_types.as_slice(),
+            [Type::Flying, Type::Steel]
+        );
+        assert!(corviknight.has_type(Type::Fighting) && !corviknight.has_type(Type::Steel));
+        assert!(corviknight.has_stab(Type::Fighting) && corviknight.has_stab(Type::Steel));
+    }
+
+    #[test]
+    fn test_disguise_busts_once() {
+        let battle = gen9_battle(
+            r#"|switch|p2a: Mimikyu|Mimikyu, L50, F|100/100
+|turn|2
+|move|p1a: Dragapult|Shadow Ball|p2a: Mimikyu
+|-activate|p2a: Mimikyu|ability: Disguise
+|detailschange|p2a: Mimikyu|Mimikyu-Busted, L50, F
+|-damage|p2a: Mimikyu|88/100|[from] pokemon: Mimikyu-Busted"#,
+        );
+        let mimikyu = battle.get_side(Player::P2).unwrap().active(0).unwrap();
+        assert_eq!(mimikyu.identity.species, "Mimikyu-Busted");
+        assert_eq!(mimikyu.known_ability.as_deref(), Some("Disguise"));
+        assert_eq!(mimikyu.hp_current, 88);
+        assert!(mimikyu.disguise_busted);
+        assert!(!mimikyu.has_intact_disguise());
+    }
+
+    #[test]
+    fn test_ice_face_reforms_in_snow() {
+        let mut battle = gen9_battle("|switch|p2a: Eiscue|Eiscue, L50, M|100/100");
+        let eiscue = battle.get_side(Player::P2).unwrap().active(0).unwrap();
+        assert!(eiscue.has_intact_disguise());
+
+        for line in [
+            "|move|p1a: Dragapult|Dragon Darts|p2a: Eiscue",
+            "|-activate|p2a: Eiscue|ability: Ice Face",
+            "|detailschange|p2a: Eiscue|Eiscue-Noice, L50, M",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let eiscue = battle.get_side(Player::P2).unwrap().active(0).unwrap();
+        assert_eq!(eiscue.known_ability.as_deref(), Some("Ice Face"));
+        assert_eq!(eiscue.hp_current, 100);
+        assert!(eiscue.disguise_busted);
+
+        for line in [
+            "|-weather|Snow",
+            "|-activate|p2a: Eiscue|ability: Ice Face",
+            "|detailschange|p2a: Eiscue|Eiscue, L50, M",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let eiscue = battle.get_side(Player::P2).unwrap().active(0).unwrap();
+        assert_eq!(eiscue.identity.species, "Eiscue");
+        assert!(!eiscue.disguise_busted);
+        assert!(eiscue.has_intact_disguise());
+    }
+
+    #[test]
+    fn test_defog_removal_attributed_to_one_action() {
+        let battle = gen9_battle(
+            r#"|-sidestart|p1: Alice|move: Stealth Rock
+|-sidestart|p1: Alice|Reflect
+|-sidestart|p2: Bob|Spikes
+|turn|2
+|move|p2a: Corviknight|Defog|p1a: Dragapult
+|-unboost|p1a: Dragapult|evasion|1
+|-sideend|p1: Alice|Reflect|[from] move: Defog|[of] p2a: Corviknight
+|-sideend|p1: Alice|Stealth Rock|[from] move: Defog|[of] p2a: Corviknight
+|-sideend|p2: Bob|Spikes|[from] move: Defog|[of] p2a: Corviknight"#,
+        );
+
+        assert_eq!(battle.condition_removals.len(), 1);
+        let defog = &battle.condition_removals[0];
+        assert_eq!(defog.turn, 2);
+        assert_eq!(defog.cause, "Defog");
+        assert_eq!(defog.user.as_ref().unwrap().name, "Corviknight");
+        assert_eq!(defog.removed.len(), 3);
+
+        let alice = battle.get_side(Player::P1).unwrap();
+        assert!(alice.conditions.is_empty());
+        assert_eq!(alice.hazards_cleared_count(), 1);
+        assert_eq!(alice.screens_cleared_count(), 1);
+        assert_eq!(alice.cleared_by().get("Defog"), Some(&1));
+
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert_eq!(bob.hazards_cleared_count(), 1);
+        assert_eq!(bob.screens_cleared_count(), 0);
+        assert_eq!(bob.cleared_by().get("Defog"), Some(&1));
+    }
+
+    #[test]
+    fn test_screen_cleaner_switch_in_attribution() {
+        let battle = gen9_battle(
+            r#"|-sidestart|p1: Alice|move: Tailwind
+|-sidestart|p2: Bob|Reflect
+|-sidestart|p2: Bob|move: Light Screen
+|turn|2
+|switch|p1a: Mr. Mime|Mr. Mime-Galar, L50, M|100/100
+|-activate|p1a: Mr. Mime|ability: Screen Cleaner
+|-sideend|p2: Bob|Reflect
+|-sideend|p2: Bob|move: Light Screen
+|move|p2a: Corviknight|Roost|p2a: Corviknight
+|-sideend|p1: Alice|move: Tailwind"#,
+        );
+
+        // Tailwind ran out on its own and isn't credited
+        assert_eq!(battle.condition_removals.len(), 1);
+        let cleaner = &battle.condition_removals[0];
+        assert_eq!(cleaner.cause, "Screen Cleaner");
+        assert_eq!(cleaner.user.as_ref().unwrap().name, "Mr. Mime");
+        assert_eq!(
+            cleaner.removed,
+            vec![
+                (Player::P2, SideCondition::Reflect),
+                (Player::P2, SideCondition::LightScreen)
+            ]
+        );
+
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert_eq!(bob.screens_cleared_count(), 1);
+        assert_eq!(bob.cleared_by().get("Screen Cleaner"), Some(&1));
+        assert!(battle.get_side(Player::P1).unwrap().cleared_by().is_empty());
+
+        let mime = battle.get_side(Player::P1).unwrap().active(0).unwrap();
+        assert_eq!(mime.known_ability.as_deref(), Some("Screen Cleaner"));
+    }
+
+    #[test]
+    fn test_guard_swap_and_heart_swap() {
+        let mut battle = gen9_battle(
+            r#"|-boost|p2a: Corviknight|def|2
+|-boost|p2a: Corviknight|atk|1
+|-unboost|p1a: Dragapult|spd|1"#,
+        );
+        battle.apply_message(
+            &parse_server_message(
+                "|-swapboost|p1a: Dragapult|p2a: Corviknight|def, spd|[from] move: Guard Swap",
+            )
+            .unwrap(),
+        );
+        let boosts = |battle: &TrackedBattle, player| {
+            battle.get_side(player).unwrap().pokemon[0].boosts
+        };
+        let (mine, theirs) = (boosts(&battle, Player::P1), boosts(&battle, Player::P2));
+        assert_eq!((mine.def, mine.spd, mine.atk), (2, 0, 0));
+        assert_eq!((theirs.def, theirs.spd, theirs.atk), (0, -1, 1));
+
+        battle.apply_message(
+            &parse_server_message(
+                "|-swapboost|p1a: Dragapult|p2a: Corviknight|[from] move: Heart Swap",
+            )
+            .unwrap(),
+        );
+        let (mine, theirs) = (boosts(&battle, Player::P1), boosts(&battle, Player::P2));
+        assert_eq!((mine.def, mine.spd, mine.atk), (0, -1, 1));
+        assert_eq!((theirs.def, theirs.spd, theirs.atk), (2, 0, 0));
+    }
+
+    #[test]
+    fn test_timed_volatiles_count_down_each_turn() {
+        let mut battle = gen9_battle(
+            r#"|move|p1a: Dragapult|Taunt|p2a: Corviknight
+|-start|p2a: Corviknight|move: Taunt
+|move|p2a: Corviknight|Yawn|p1a: Dragapult
+|-start|p1a: Dragapult|move: Yawn|[of] p2a: Corviknight"#,
+        );
+        let turns = |battle: &TrackedBattle, player, volatile| {
+            battle.get_side(player).unwrap().pokemon[0].volatile_turns_remaining(&volatile)
+        };
+        assert_eq!(turns(&battle, Player::P2, Volatile::Taunt), Some(3));
+        assert_eq!(turns(&battle, Player::P1, Volatile::Yawn), Some(1));
+
+        battle.apply_message(&parse_server_message("|turn|2").unwrap());
+        assert_eq!(turns(&battle, Player::P2, Volatile::Taunt), Some(2));
+        assert_eq!(turns(&battle, Player::P1, Volatile::Yawn), Some(0));
+
+        // Untimed volatiles have no count
+        battle.apply_message(
+            &parse_server_message("|-start|p2a: Corviknight|confusion").unwrap(),
+        );
+        let corviknight = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert!(corviknight.has_volatile(&Volatile::Confusion));
+        assert_eq!(corviknight.volatile_turns_remaining(&Volatile::Confusion), None);
+    }
+
+    #[test]
+    fn test_perish_count_follows_the_server() {
+        let mut battle = gen9_battle(
+            r#"|move|p1a: Dragapult|Perish Song|p1a: Dragapult
+|-start|p1a: Dragapult|perish3|[silent]
+|-start|p2a: Corviknight|perish3|[silent]
+|-fieldactivate|move: Perish Song"#,
+        );
+        let count = |battle: &TrackedBattle| {
+            battle.get_side(Player::P2).unwrap().pokemon[0]
+                .volatile_turns_remaining(&Volatile::PerishSong)
+        };
+        assert_eq!(count(&battle), Some(3));
+
+        // The turn doesn't count it down; the announcement does
+        for line in ["|upkeep", "|turn|2", "|-start|p2a: Corviknight|perish2"] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(count(&battle), Some(2));
+
+        battle.apply_message(&parse_server_message("|-start|p2a: Corviknight|perish0").unwrap());
+        let corviknight = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert!(!corviknight.has_volatile(&Volatile::PerishSong));
+        assert!(!corviknight.has_volatile(&Volatile::Other("perish0".to_string())));
+    }
+
+    #[test]
+    fn test_court_change_swaps_swappable_conditions() {
+        let mut battle = gen9_battle(
+            r#"|-sidestart|p1: Alice|move: Stealth Rock
+|-sidestart|p1: Alice|Spikes
+|-sidestart|p1: Alice|Spikes
+|-sidestart|p1: Alice|Wide Guard
+|-sidestart|p2: Bob|move: Light Screen"#,
+        );
+        battle.apply_message(
+            &parse_server_message("|-swapsideconditions|p1a: Dragapult|Court Change").unwrap(),
+        );
+
+        let conditions = |battle: &TrackedBattle, player| -> Vec<_> {
+            battle
+                .get_side(player)
+                .unwrap()
+                .conditions
+                .iter()
+                .map(|(cond, state)| (*cond, state.layers))
+                .collect()
+        };
+        assert_eq!(
+            conditions(&battle, Player::P1),
+            [(SideCondition::WideGuard, 1), (SideCondition::LightScreen, 1)]
+        );
+        assert_eq!(
+            conditions(&battle, Player::P2),
+            [(SideCondition::StealthRock, 1), (SideCondition::Spikes, 2)]
+        );
+    }
+
+    #[test]
+    fn test_spikes_stack_and_overstacking_is_flagged() {
+        let battle = gen9_battle(
+            r#"|-sidestart|p2: Bob|Spikes
+|-sidestart|p2: Bob|Spikes
+|-sidestart|p2: Bob|Spikes
+|-sidestart|p2: Bob|Spikes"#,
+        );
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert_eq!(bob.condition_layers(SideCondition::Spikes), 3);
+        assert_eq!(
+            battle.inconsistencies(),
+            [Inconsistency::ConditionOverStacked {
+                player: Player::P2,
+                condition: SideCondition::Spikes,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_replaying_reannouncement_keeps_layers() {
+        let mut battle = gen9_battle(
+            r#"|-sidestart|p2: Bob|Spikes
+|-sidestart|p1: Alice|move: Stealth Rock"#,
+        );
+        battle.set_replaying(true);
+        for line in ["|-sidestart|p2: Bob|Spikes", "|-sidestart|p1: Alice|move: Stealth Rock"] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle.set_replaying(false);
+
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert_eq!(bob.condition_layers(SideCondition::Spikes), 1);
+        let alice = battle.get_side(Player::P1).unwrap();
+        assert_eq!(alice.condition_layers(SideCondition::StealthRock), 1);
+        assert_eq!(battle.inconsistency_count(), 0);
+
+        // Live again, a new layer stacks
+        battle.apply_message(&parse_server_message("|-sidestart|p2: Bob|Spikes").unwrap());
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert_eq!(bob.condition_layers(SideCondition::Spikes), 2);
+    }
+
+    fn replay_log(log: &str) -> TrackedBattle {
+        let mut battle = TrackedBattle::new();
+        for line in log.lines() {
+            let message = parse_server_message(line)
+                .unwrap_or_else(|e| panic!("failed to parse {:?}: {}", line, e));
+            battle.apply_message(&message);
+        }
+        battle
+    }
+
+    fn find<'a>(battle: &'a TrackedBattle, player: Player, name: &str) -> &'a PokemonState {
+        let side = battle.get_side(player).unwrap();
+        side.get_pokemon(side.find_pokemon(name).unwrap()).unwrap()
+    }
+
+    /// Ingest an old-gen log as `from_log` does, checking every line parses
+    /// and nothing fails to resolve or contradicts itself
+    fn ingest_old_gen(log: &str) -> TrackedBattle {
+        let mut fed = TrackedBattle::new();
+        for line in log.lines() {
+            assert!(fed.feed_line(line), "failed to parse {line:?}");
+        }
+        let battle = TrackedBattle::from_log(log);
+        assert_eq!(battle, fed);
+        assert_eq!(battle.inconsistency_count(), 0, "{:?}", battle.inconsistencies());
+        for side in battle.sides() {
+            for poke in &side.pokemon {
+                assert!(poke.contradictions.is_empty(), "{}", poke.name());
+            }
+        }
+        battle
+    }
+
+    #[test]
+    fn test_gen1_log() {
+        let battle = ingest_old_gen(include_str!("../../testdata/oldgen_gen1.log"));
+        assert_eq!(battle.turn, 8);
+        assert!(!battle.mechanics().has_abilities());
+
+        // Toxic fell back to regular poison when Tauros switched out
+        assert_eq!(
+            find(&battle, Player::P2, "Tauros").status,
+            Some(Status::Poison)
+        );
+        assert_eq!(
+            find(&battle, Player::P2, "Snorlax").status,
+            Some(Status::Freeze)
+        );
+
+        let chansey = find(&battle, Player::P2, "Chansey");
+        assert_eq!(chansey.status, Some(Status::Paralysis));
+        assert_eq!(chansey.boosts.spa, 2);
+
+        for side in battle.sides() {
+            for poke in &side.pokemon {
+                assert!(poke.known_ability.is_none());
+                assert!(poke.contradictions.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_gen2_log() {
+        let battle = ingest_old_gen(include_str!("../../testdata/oldgen_gen2.log"));
+        assert_eq!(battle.turn, 5);
+
+        // Snorlax switched out badly poisoned and keeps only regular poison
+        assert_eq!(
+            find(&battle, Player::P2, "Snorlax").status,
+            Some(Status::Poison)
+        );
+        assert_eq!(
+            find(&battle, Player::P1, "Raikou").status,
+            Some(Status::Paralysis)
+        );
+        assert!(
+            battle
+                .get_side(Player::P2)
+                .unwrap()
+                .has_condition(SideCondition::Spikes)
+        );
+    }
+
+    #[test]
+    fn test_gen3_log() {
+        // An ADV OU replay from the server, timer notices and chat included
+        let battle = ingest_old_gen(include_str!("../../testdata/golden_replay.log"));
+        assert_eq!(battle.generation, 3);
+        assert_eq!(battle.winner.as_deref(), Some("Pokebasket"));
+        assert!(battle.mechanics().has_abilities());
+        assert_eq!(
+            find(&battle, Player::P1, "Hill").known_ability.as_deref(),
+            Some("Intimidate")
+        );
+    }
+
+    #[test]
+    fn test_cant_reveals_status() {
+        let battle = gen9_battle("|cant|p2a: Corviknight|slp\n|cant|p1a: Dragapult|par");
+        let status = |player| battle.get_side(player).unwrap().pokemon[0].status;
+        assert_eq!(status(Player::P2), Some(Status::Sleep));
+        assert_eq!(status(Player::P1), Some(Status::Paralysis));
+    }
+
+    #[test]
+    fn test_baton_pass_hands_on_boosts_and_volatiles() {
+        let battle = replay_log(
+            r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Ninjask|Ninjask, M|100/100
+|switch|p2a: Cyclizar|Cyclizar, F|100/100
+|turn|1
+|move|p1a: Ninjask|Swords Dance|p1a: Ninjask
+|-boost|p1a: Ninjask|atk|2
+|-start|p1a: Ninjask|Substitute
+|-start|p1a: Ninjask|move: Taunt
+|move|p2a: Cyclizar|Shed Tail|p2a: Cyclizar
+|-start|p2a: Cyclizar|Substitute
+|-start|p2a: Cyclizar|confusion
+|switch|p2a: Corviknight|Corviknight, F|100/100
+|turn|2
+|move|p1a: Ninjask|Baton Pass|p1a: Ninjask
+|switch|p1a: Garchomp|Garchomp, M|100/100"#,
+        );
+
+        // Baton Pass keeps boosts and Substitute but not Taunt
+        let garchomp = find(&battle, Player::P1, "Garchomp");
+        assert_eq!(garchomp.boosts.atk, 2);
+        assert!(garchomp.has_volatile(&Volatile::Substitute));
+        assert!(!garchomp.has_volatile(&Volatile::Taunt));
+        assert!(find(&battle, Player::P1, "Ninjask").volatiles.is_empty());
+
+        // Shed Tail passes only its Substitute
+        let corviknight = find(&battle, Player::P2, "Corviknight");
+        assert!(corviknight.has_volatile(&Volatile::Substitute));
+        assert!(!corviknight.has_volatile(&Volatile::Confusion));
+    }
+
+    #[test]
+    fn test_request_shapes_across_faint_cycle() {
+        let requests: Vec<serde_json::Value> =
+            serde_json::from_str(include_str!("../../testdata/faint_cycle_requests.json")).unwrap();
+        let requests: Vec<BattleRequest> = requests
+            .iter()
+            .map(|json| BattleRequest::parse(json).unwrap())
+            .collect();
+        let kinds: Vec<_> = requests.iter().map(BattleRequest::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                RequestKind::TeamPreview,
+                RequestKind::Move,
+                RequestKind::Move,
+                RequestKind::ForceSwitch,
+                RequestKind::Wait,
+                RequestKind::Move,
+            ]
+        );
+
+        let mut battle = TrackedBattle::new();
+        let earthquake_pp = |battle: &TrackedBattle| {
+            battle
+                .known_move_slots(0)
+                .map(|slots| (slots[0].pp, slots[3].disabled))
+        };
+
+        battle.apply_request(&requests[0]);
+        assert_eq!(battle.me().unwrap().pokemon.len(), 3);
+        assert!(battle.legal_moves(0).is_empty());
+        assert_eq!(earthquake_pp(&battle), None);
+
+        battle.apply_request(&requests[1]);
+        battle.apply_request(&requests[2]);
+        assert_eq!(earthquake_pp(&battle), Some((15, true)));
+        let earthquake = battle.me().unwrap().pokemon[0].known_move("Earthquake");
+        assert_eq!(earthquake.and_then(KnownMove::remaining_pp), Some(15));
+        assert_eq!(battle.legal_moves(0).len(), 3);
+
+        // Garchomp faints: the force switch and wait requests have no move data
+        battle.apply_request(&requests[3]);
+        assert!(battle.legal_moves(0).is_empty());
+        battle.apply_request(&requests[4]);
+        assert!(battle.legal_moves(0).is_empty());
+        assert_eq!(earthquake_pp(&battle), Some((15, true)));
+        let garchomp = &battle.me().unwrap().pokemon[0];
+        assert!(garchomp.fainted);
+        assert_eq!(garchomp.known_moves.len(), 4);
+        assert_eq!(garchomp.known_ability.as_deref(), Some("roughskin"));
+
+        // The next turn adds Corviknight's slots and leaves Garchomp's alone
+        battle.apply_request(&requests[5]);
+        assert_eq!(battle.me().unwrap().request_order(), [1, 0, 2]);
+        assert_eq!(battle.legal_moves(0).len(), 4);
+        assert_eq!(battle.known_move_slots(1).unwrap()[1].id, "roost");
+        assert_eq!(earthquake_pp(&battle), Some((15, true)));
+    }
+
+    #[test]
+    fn test_pp_spent_by_chosen_moves() {
+        let battle = TrackedBattle::from_log(
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n|gametype|singles\n|start\n\
+             |switch|p1a: Gengar|Gengar, L50|100/100\n\
+             |switch|p2a: Snorlax|Snorlax, L50|100/100\n\
+             |turn|1\n\
+             |move|p1a: Gengar|Shadow Ball|p2a: Snorlax\n\
+             |move|p2a: Snorlax|Sleep Talk|p2a: Snorlax\n\
+             |move|p2a: Snorlax|Body Slam|p1a: Gengar|[from]move: Sleep Talk\n\
+             |turn|2\n\
+             |switch|p2a: Zapdos|Zapdos, L50|100/100\n\
+             |-ability|p2a: Zapdos|Pressure\n\
+             |move|p1a: Gengar|Shadow Ball|p2a: Zapdos\n\
+             |move|p1a: Gengar|Struggle|p2a: Zapdos",
+        );
+        let gengar = &battle.get_side(Player::P1).unwrap().pokemon[0];
+        let shadow_ball = gengar.known_move("Shadow Ball").unwrap();
+        assert_eq!(shadow_ball.times_used, 2);
+        assert_eq!(shadow_ball.pp_spent, 3);
+        assert_eq!(gengar.known_move("Struggle"), None);
+        assert_eq!(gengar.known_moves.as_slice(), ["Shadow Ball"]);
+        assert_eq!(gengar.last_move.as_deref(), Some("Struggle"));
+
+        // Sleep Talk spends its own PP, not the move it calls
+        let snorlax = &battle.get_side(Player::P2).unwrap().pokemon[0];
+        assert_eq!(snorlax.known_moves.as_slice(), ["Sleep Talk"]);
+        assert_eq!(snorlax.known_move("Body Slam"), None);
+        assert_eq!(snorlax.known_move("Sleep Talk").unwrap().pp_spent, 1);
+    }
+
+    #[test]
+    fn test_request_empty_fields_keep_state() {
+        let mut battle = TrackedBattle::new();
+        let json = serde_json::json!({
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Blissey",
+                    "details": "Blissey, L80, F",
+                    "condition": "490/490",
+                    "active": true,
+                    "moves": ["seismictoss", "softboiled"],
+                    "ability": "naturalcure",
+                    "item": "heavydutyboots"
+                }]
+            },
+            "rqid": 1
+        });
+        battle.apply_request(&BattleRequest::parse(&json).unwrap());
+        let json = serde_json::json!({
+            "wait": true,
+            "side": {
+                "name": "Alice",
+                "id": "p1",
+                "pokemon": [{
+                    "ident": "p1: Blissey",
+                    "details": "Blissey, L80, F",
+                    "condition": "",
+                    "item": ""
+                }]
+            },
+            "rqid": 2
+        });
+        battle.apply_request(&BattleRequest::parse(&json).unwrap());
+
+        let blissey = &battle.me().unwrap().pokemon[0];
+        assert_eq!(blissey.known_moves.len(), 2);
+        assert_eq!(blissey.known_ability.as_deref(), Some("naturalcure"));
+        assert_eq!(blissey.hp_current, 490);
+        assert!(!blissey.fainted);
+        // An empty item is information: the item is gone
+        assert_eq!(blissey.known_item, None);
+    }
+
+    const BROKEN_LOG: &str = "|player|p1|Alice|1|
+|player|p2|Bob|2|
+|switch|p1a: Pikachu|Pikachu, L50|100/100
+|switch|p2a: Charizard|Charizard, L50|100/100
+|-damage|p2a: Blastoise|50/100
+|-damage|p2a: Charizard|40/100
+|-damage|p2a: Charizard|60/100
+|faint|p1a: Pikachu
+|-boost|p1a: Pikachu|atk|1";
+
+    fn replay_with(strictness: StrictnessMode) -> TrackedBattle {
+        let mut battle = TrackedBattle::with_config(TrackerConfig::new().strictness(strictness));
+        for line in BROKEN_LOG.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle
+    }
+
+    #[test]
+    fn test_inconsistencies_recorded() {
+        let battle = replay_with(StrictnessMode::LogWarn);
+        let blastoise = Pokemon::parse("p2a: Blastoise").unwrap();
+        let charizard = Pokemon::parse("p2a: Charizard").unwrap();
+        let pikachu = Pokemon::parse("p1a: Pikachu").unwrap();
+        assert_eq!(
+            battle.inconsistencies(),
+            [
+                Inconsistency::UnresolvedPokemon(blastoise),
+                Inconsistency::HpIncreasedOnDamage {
+                    pokemon: charizard,
+                    from: 40,
+                    to: 60,
+                },
+                Inconsistency::BoostOnFainted(pikachu),
+            ]
+        );
+        // The messages still apply where they can
+        assert_eq!(find(&battle, Player::P2, "Charizard").hp_percent(), 60);
+    }
+
+    #[test]
+    fn test_inconsistencies_silent() {
+        assert_eq!(replay_with(StrictnessMode::Silent).inconsistency_count(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "tracking inconsistency: no p2 Blastoise tracked")]
+    fn test_inconsistencies_panic() {
+        replay_with(StrictnessMode::Panic);
+    }
+
+    #[test]
+    fn test_ability_changes() {
+        const START: &str = "|player|p1|Alice|1|
+|player|p2|Bob|2|
+|switch|p1a: Alakazam|Alakazam, L50|100/100
+|switch|p2a: Garchomp|Garchomp, L50|100/100";
+        // Lines, then (known, override) for Alakazam and for Garchomp
+        type Abilities = (Option<&'static str>, Option<&'static str>);
+        let cases: [(&str, Abilities, Abilities); 5] = [
+            (
+                "|move|p1a: Alakazam|Worry Seed|p2a: Garchomp
+|-ability|p2a: Garchomp|Insomnia|[from] move: Worry Seed",
+                (None, None),
+                (None, Some("Insomnia")),
+            ),
+            (
+                "|move|p1a: Alakazam|Simple Beam|p2a: Garchomp
+|-ability|p2a: Garchomp|Simple|[from] move: Simple Beam",
+                (None, None),
+                (None, Some("Simple")),
+            ),
+            (
+                "|move|p1a: Alakazam|Entrainment|p2a: Garchomp
+|-ability|p2a: Garchomp|Magic Guard|[from] move: Entrainment",
+                (Some("Magic Guard"), None),
+                (None, Some("Magic Guard")),
+            ),
+            (
+                "|move|p1a: Alakazam|Skill Swap|p2a: Garchomp
+|-ability|p2a: Garchomp|Magic Guard|[from] move: Skill Swap
+|-ability|p1a: Alakazam|Rough Skin|[from] move: Skill Swap",
+                (Some("Magic Guard"), Some("Rough Skin")),
+                (Some("Rough Skin"), Some("Magic Guard")),
+            ),
+            (
+                "|-ability|p1a: Alakazam|Rough Skin|[from] ability: Trace|[of] p2a: Garchomp",
+                (Some("Trace"), Some("Rough Skin")),
+                (Some("Rough Skin"), None),
+            ),
+        ];
+
+        let abilities =
+            |poke: &PokemonState| (poke.known_ability.clone(), poke.ability_override.clone());
+        let owned = |(known, over): Abilities| (known.map(String::from), over.map(String::from));
+        for (lines, alakazam, garchomp) in cases {
+            let battle = replay_log(&format!("{START}\n{lines}"));
+            assert_eq!(
+                abilities(find(&battle, Player::P1, "Alakazam")),
+                owned(alakazam),
+                "{lines}"
+            );
+            assert_eq!(
+                abilities(find(&battle, Player::P2, "Garchomp")),
+                owned(garchomp),
+                "{lines}"
+            );
+        }
+
+        // The replacement lasts until switch-out; what was learned stays
+        let battle = replay_log(&format!(
+            "{START}\n{}\n|switch|p2a: Dragonite|Dragonite, L50|100/100",
+            cases[3].0
+        ));
+        let garchomp = find(&battle, Player::P2, "Garchomp");
+        assert_eq!(garchomp.ability_override, None);
+        assert_eq!(garchomp.current_ability(), Some("Rough Skin"));
+        let alakazam = find(&battle, Player::P1, "Alakazam");
+        assert_eq!(alakazam.current_ability(), Some("Rough Skin"));
+    }
+
+    #[test]
+    fn test_charging_moves() {
+        const START: &str = "|player|p1|Alice|1|
+|player|p2|Bob|2|
+|switch|p1a: Venusaur|Venusaur, L50|100/100
+|switch|p2a: Swampert|Swampert, L50|100/100
+|turn|1";
+        let charging = |battle: &TrackedBattle| {
+            let venusaur = find(battle, Player::P1, "Venusaur");
+            assert_eq!(
+                venusaur.has_volatile(&Volatile::Charging),
+                venusaur.charging_move.is_some()
+            );
+            venusaur.charging_move.clone()
+        };
+
+        // Charge turn, then the beam fires
+        let charge = "|move|p1a: Venusaur|Meteor Beam|p2a: Swampert|[still]
+|-prepare|p1a: Venusaur|Meteor Beam
+|-boost|p1a: Venusaur|spa|1
+|move|p2a: Swampert|Earthquake|p1a: Venusaur
+|-damage|p1a: Venusaur|60/100
+|turn|2";
+        let mut battle = replay_log(&format!("{START}\n{charge}"));
+        assert_eq!(
+            charging(&battle),
+            Some(ChargingMove {
+                move_name: "Meteor Beam".to_string(),
+                target: Some((Player::P2, 'a')),
+            })
+        );
+        let incoming = battle.incoming_charged_attacks();
+        assert_eq!(incoming.len(), 1);
+        assert_eq!((incoming[0].0.player, incoming[0].0.index), (Player::P1, 0));
+        for line in [
+            "|move|p1a: Venusaur|Meteor Beam|p2a: Swampert",
+            "|-damage|p2a: Swampert|30/100",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(charging(&battle), None);
+        assert!(battle.incoming_charged_attacks().is_empty());
+
+        // Sun skips the charge turn
+        let battle = replay_log(&format!(
+            "{START}
+|-weather|SunnyDay
+|move|p1a: Venusaur|Solar Beam|p2a: Swampert|[still]
+|-prepare|p1a: Venusaur|Solar Beam
+|-anim|p1a: Venusaur|Solar Beam|p2a: Swampert
+|-supereffective|p2a: Swampert
+|-damage|p2a: Swampert|10/100"
+        ));
+        assert_eq!(charging(&battle), None);
+
+        // Full paralysis loses the charge, as does switching out
+        let mut battle = replay_log(&format!("{START}\n{charge}"));
+        battle.apply_message(&parse_server_message("|cant|p1a: Venusaur|par").unwrap());
+        assert_eq!(charging(&battle), None);
+        let battle = replay_log(&format!(
+            "{START}\n{charge}\n|switch|p1a: Torkoal|Torkoal, L50|100/100"
+        ));
+        assert_eq!(charging(&battle), None);
+    }
+
+    #[test]
+    fn test_saved_replay_log() {
+        let log = include_str!("../../testdata/gen9randombattle_replay.log");
+        let battle = TrackedBattle::from_log(log);
+        assert_eq!(battle.inconsistencies(), []);
+        assert_eq!(battle.turn, 6);
+        assert!(battle.ended);
+        assert_eq!(battle.winner.as_deref(), Some("Ash"));
+        assert_eq!(battle.tier, "[Gen 9] Random Battle");
+
+        let revealed = |player| battle.get_side(player).unwrap().pokemon.len();
+        assert_eq!(revealed(Player::P1), 3);
+        assert_eq!(revealed(Player::P2), 2);
+
+        // Fed line by line under a room header, chat and all, it's the same battle
+        let mut fed = TrackedBattle::new();
+        fed.feed_line(">battle-gen9randombattle-2215417389");
+        for line in log.lines() {
+            fed.feed_line(line);
+        }
+        assert_eq!(fed.turn, battle.turn);
+        assert_eq!(fed.winner, battle.winner);
+        assert_eq!(fed.inconsistency_count(), 0);
+    }
+
+    #[test]
+    fn test_spectated_free_for_all() {
+        let battle = TrackedBattle::from_log(include_str!("../../testdata/ffa_spectate.log"));
+        assert_eq!(battle.inconsistencies(), []);
+
+        // p4 never had a |player| line; its side came from |teamsize| and |switch|
+        let dave = battle.get_side(Player::P4).unwrap();
+        assert_eq!(dave.username, "");
+        assert_eq!(dave.active(0).unwrap().identity.species, "Gholdengo");
+
+        // Each side's one active Pokemon, though p3 and p4 are addressed as 'b'
+        let active: Vec<_> = battle
+            .all_pokemon()
+            .active()
+            .map(|p| (p.player, p.pokemon.identity.species.as_str()))
+            .collect();
+        assert_eq!(
+            active,
+            [
+                (Player::P1, "Iron Valiant"),
+                (Player::P2, "Kingambit"),
+                (Player::P3, "Toxapex"),
+                (Player::P4, "Gholdengo"),
+            ]
+        );
+        let bob = battle.get_side(Player::P2).unwrap();
+        assert!(bob.has_condition(SideCondition::StealthRock));
+        assert!(battle.get_side(Player::P1).unwrap().pokemon[0].fainted);
+    }
+
+    #[test]
+    fn test_out_of_range_positions() {
+        // Lines for a fifth player don't parse and are skipped
+        let battle = TrackedBattle::from_log(
+            "|gametype|freeforall\n|switch|p4d: Gholdengo|Gholdengo|100/100\n|switch|p5a: Mew|Mew|100/100",
+        );
+        assert_eq!(battle.inconsistencies(), []);
+        assert!(battle.get_side(Player::P4).unwrap().active(0).is_some());
+
+        // Doubles has no third slot; nothing is put in slot a instead
+        let mut battle = TrackedBattle::new();
+        for line in [
+            "|gametype|doubles",
+            "|switch|p1a: Garchomp|Garchomp|100/100",
+            "|switch|p1c: Rotom|Rotom|100/100",
+            "|faint|p1c: Rotom",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let side = battle.get_side(Player::P1).unwrap();
+        assert_eq!(side.active(0).unwrap().identity.species, "Garchomp");
+        assert!(side.active(1).is_none());
+        assert_eq!(side.pokemon.len(), 2);
+        assert!(matches!(
+            battle.inconsistencies(),
+            [
+                Inconsistency::PositionOutOfRange(_),
+                Inconsistency::PositionOutOfRange(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_endless_battle_progress() {
+        let mut log = String::from(
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n|start\n\
+             |switch|p1a: Blissey|Blissey, F|100/100\n\
+             |switch|p2a: Chansey|Chansey, F|100/100\n|turn|1\n\
+             |move|p1a: Blissey|Seismic Toss|p2a: Chansey\n|-damage|p2a: Chansey|60/100\n|turn|2\n",
+        );
+        // Twenty turns of healing up and taking the same chip again
+        for turn in 3..=22 {
+            log.push_str(&format!(
+                "|move|p2a: Chansey|Soft-Boiled|p2a: Chansey\n|-heal|p2a: Chansey|100/100\n\
+                 |move|p1a: Blissey|Seismic Toss|p2a: Chansey\n|-damage|p2a: Chansey|61/100\n\
+                 |turn|{turn}\n"
+            ));
+        }
+        let mut battle = TrackedBattle::from_log(&log);
+        assert_eq!(battle.staleness().unwrap().last_progress_turn, 1);
+        assert_eq!(battle.turns_without_progress(), 21);
+
+        // A new low is progress
+        for line in ["|-damage|p2a: Chansey|55/100", "|turn|23"] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(battle.turns_without_progress(), 1);
+
+        for line in ["|faint|p1a: Blissey", "|turn|30"] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(battle.staleness().unwrap().last_progress_turn, 23);
+        assert_eq!(battle.turns_without_progress(), 7);
+    }
+
+    #[test]
+    fn test_endless_battle_warnings() {
+        let mut battle = TrackedBattle::new();
+        assert_eq!(battle.staleness(), None);
+        for line in [
+            "|-message|You will auto-tie if the battle doesn't end in 5 turns (on turn 1000).",
+            "|bigerror|This battle cannot progress. Endless Battle Clause activated!",
+            "|-hint|Sleep Clause Mod activated.",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(
+            battle.staleness().unwrap().warnings,
+            [
+                EndlessBattleWarning::TurnLimit { turns_left: 5 },
+                EndlessBattleWarning::CannotProgress,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preview_levels_carry_to_switch_ins() {
+        let mut battle = TrackedBattle::from_log(
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n|clearpoke\n\
+             |poke|p2|Flapple, L77, F|\n|poke|p2|Urshifu-*, L79, M|\n|teampreview\n|start\n\
+             |switch|p2a: Apple|Flapple, L77, F|100/100\n|turn|1",
+        );
+        let side = battle.get_side(Player::P2).unwrap();
+        assert!(side.previewed);
+        assert_eq!(side.pokemon.len(), 2);
+        let flapple = &side.pokemon[0];
+        assert_eq!(flapple.name(), "Apple");
+        assert_eq!(flapple.identity.level, 77);
+        // Base 70 Speed at level 77, not 100
+        assert_eq!(flapple.stat_range(Stat::Spe, 70), Some((100, 203)));
+        assert_eq!(side.pokemon[1].identity.species, "Urshifu-*");
+
+        // The hidden forme is claimed by species, not added again
+        for line in [
+            "|switch|p2a: Urshifu|Urshifu-Rapid-Strike, L79, M|100/100",
+            "|switch|p2a: Apple|Flapple, L80, F|100/100",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let side = battle.get_side(Player::P2).unwrap();
+        assert_eq!(side.pokemon.len(), 2);
+        assert_eq!(side.pokemon[1].identity.species, "Urshifu-Rapid-Strike");
+        assert_eq!(side.pokemon[1].identity.level, 79);
+        assert_eq!(
+            battle.inconsistencies(),
+            [Inconsistency::LevelMismatch {
+                pokemon: Pokemon::parse("p2a: Apple").unwrap(),
+                known: 77,
+                seen: 80,
+            }]
+        );
+    }
+
+    fn heals(battle: &TrackedBattle, player: Player) -> &[PendingHeal] {
+        &battle.get_side(player).unwrap().pending_heals
+    }
+
+    #[test]
+    fn test_wish_lands_end_of_next_turn() {
+        let mut battle = gen9_battle(
+            "|move|p1a: Dragapult|Wish|p1a: Dragapult\n\
+             |move|p2a: Corviknight|Brave Bird|p1a: Dragapult\n\
+             |-damage|p1a: Dragapult|40/100\n|turn|2",
+        );
+        let side = battle.get_side(Player::P1).unwrap();
+        let wish = side.incoming_heal(0, 2).unwrap();
+        assert_eq!(wish.landing, HealLanding::OnTurn(2));
+        assert_eq!(wish.amount, HealAmount::Fraction(0.5));
+        assert_eq!(wish.percent_for(&side.pokemon[0]), 50.0);
+        assert!(heals(&battle, Player::P2).is_empty());
+
+        // A second Wish while one is pending fails
+        for line in [
+            "|move|p1a: Dragapult|Wish|p1a: Dragapult",
+            "|-fail|p1a: Dragapult",
+            "|-heal|p1a: Dragapult|90/100|[from] move: Wish|[wisher] Dragapult",
+            "|turn|3",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert!(heals(&battle, Player::P1).is_empty());
+
+        // A Wish whose turn ends without a heal message (full HP) expires
+        for line in [
+            "|move|p1a: Dragapult|Wish|p1a: Dragapult",
+            "|turn|4",
+            "|move|p1a: Dragapult|Protect|p1a: Dragapult",
+            "|turn|5",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let side = battle.get_side(Player::P1).unwrap();
+        assert!(side.pending_heals.is_empty());
+        assert!(side.incoming_heal(0, 5).is_none());
+    }
+
+    #[test]
+    fn test_healing_wish_waits_for_switch_in() {
+        let mut battle = gen9_battle(
+            "|move|p2a: Corviknight|Healing Wish|p2a: Corviknight\n\
+             |faint|p2a: Corviknight",
+        );
+        let heal = battle
+            .get_side(Player::P2)
+            .unwrap()
+            .incoming_heal(0, 1)
+            .cloned()
+            .unwrap();
+        assert_eq!(heal.landing, HealLanding::OnNextSwitch);
+        assert_eq!(heal.amount, HealAmount::Full);
+        assert_eq!(heal.user, "Corviknight");
+
+        // Still waiting after the turn ends
+        for line in [
+            "|turn|2",
+            "|switch|p2a: Blissey|Blissey, L50, F|45/100 par",
+            "|-heal|p2a: Blissey|100/100|[from] move: Healing Wish",
+            "|-curestatus|p2a: Blissey|par|[msg]",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+            if line == "|turn|2" {
+                assert_eq!(heals(&battle, Player::P2).len(), 1);
+            }
+        }
+        let side = battle.get_side(Player::P2).unwrap();
+        assert!(side.pending_heals.is_empty());
+        assert_eq!(find(&battle, Player::P2, "Blissey").hp_percent(), 100);
+
+        // With nothing left to switch in, it fails and leaves nothing behind
+        for line in [
+            "|move|p2a: Blissey|Lunar Dance|p2a: Blissey",
+            "|-fail|p2a: Blissey",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert!(heals(&battle, Player::P2).is_empty());
+    }
+
+    /// Galarian Weezing switches out in front of Pelipper and Landorus, and a
+    /// Gardevoir on its side traces Intimidate
+    const NEUTRALIZING_GAS_LOG: &str = "|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|gen|9
+|start
+|switch|p1a: Pelipper|Pelipper, L50, F|100/100
+|switch|p1b: Landorus|Landorus-Therian, L50, M|100/100
+|switch|p2a: Weezing|Weezing-Galar, L50, F|100/100
+|switch|p2b: Gardevoir|Gardevoir, L50, F|100/100
+|-ability|p2a: Weezing|Neutralizing Gas
+|turn|1
+|switch|p2a: Amoonguss|Amoonguss, L50, F|100/100
+|turn|2";
+
+    /// What Weezing leaving unleashes, in the order the server sends it
+    const GAS_END: &[&str] = &[
+        "|-end|p2a: Weezing|ability: Neutralizing Gas",
+        "|-weather|RainDance|[from] ability: Drizzle|[of] p1a: Pelipper",
+        "|-ability|p1b: Landorus|Intimidate|boost",
+        "|-unboost|p2a: Weezing|atk|1",
+        "|-unboost|p2b: Gardevoir|atk|1",
+        "|-ability|p2b: Gardevoir|Intimidate|[from] ability: Trace|[of] p1b: Landorus",
+        "|-ability|p2b: Gardevoir|Intimidate|boost",
+        "|-unboost|p1a: Pelipper|atk|1",
+        "|-unboost|p1b: Landorus|atk|1",
+    ];
+
+    fn neutralizing_gas_battle(gas_end: &[&str]) -> TrackedBattle {
+        let mut log = String::new();
+        for line in NEUTRALIZING_GAS_LOG.lines() {
+            if line.starts_with("|switch|p2a: Amoonguss") {
+                for end in gas_end {
+                    log.push_str(end);
+                    log.push('\n');
+                }
+            }
+            log.push_str(line);
+            log.push('\n');
+        }
+        TrackedBattle::from_log(&log)
+    }
+
+    #[test]
+    fn test_neutralizing_gas_suppresses_until_holder_leaves() {
+        let mut battle = TrackedBattle::new();
+        for line in NEUTRALIZING_GAS_LOG.lines().take_while(|l| *l != "|turn|1") {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert!(battle.field.abilities_suppressed());
+        let landorus = find(&battle, Player::P1, "Landorus");
+        assert_eq!(landorus.effective_ability_on(&battle.field), None);
+
+        let battle = neutralizing_gas_battle(GAS_END);
+        assert!(!battle.field.abilities_suppressed());
+        assert_eq!(battle.field.weather, Some(Weather::Rain));
+        assert_eq!(
+            find(&battle, Player::P1, "Pelipper").known_ability.as_deref(),
+            Some("Drizzle")
+        );
+        let landorus = find(&battle, Player::P1, "Landorus");
+        assert_eq!(landorus.known_ability.as_deref(), Some("Intimidate"));
+        assert_eq!(landorus.boosts.atk, -1);
+        assert_eq!(
+            landorus.effective_ability_on(&battle.field),
+            Some("Intimidate")
+        );
+        // The traced Intimidate activating isn't Gardevoir's own ability
+        let gardevoir = find(&battle, Player::P2, "Gardevoir");
+        assert_eq!(gardevoir.known_ability.as_deref(), Some("Trace"));
+        assert_eq!(gardevoir.current_ability(), Some("Intimidate"));
+        assert_eq!(gardevoir.boosts.atk, -1);
+        assert!(battle.inconsistencies().is_empty());
+
+        // The -end arriving last, or not at all, leaves the same state
+        let mut reordered = GAS_END[1..].to_vec();
+        reordered.push(GAS_END[0]);
+        let late_end = neutralizing_gas_battle(&reordered);
+        let no_end = neutralizing_gas_battle(&GAS_END[1..]);
+        for other in [&late_end, &no_end] {
+            assert_eq!(other.field, battle.field);
+            assert_eq!(other.get_side(Player::P1), battle.get_side(Player::P1));
+            assert_eq!(other.get_side(Player::P2), battle.get_side(Player::P2));
+        }
+    }
+
+    #[test]
+    fn test_neutralizing_gas_ends_on_faint() {
+        let mut battle = TrackedBattle::new();
+        for line in NEUTRALIZING_GAS_LOG.lines().take_while(|l| *l != "|turn|1") {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        battle.apply_message(&parse_server_message("|faint|p2a: Weezing").unwrap());
+        assert!(!battle.field.abilities_suppressed());
+    }
+
+    fn switch_kinds(battle: &TrackedBattle) -> Vec<(u32, &str, &SwitchKind)> {
+        battle
+            .switches
+            .iter()
+            .map(|s| (s.turn, s.pokemon.name.as_str(), &s.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_emergency_exit_is_not_a_chosen_switch() {
+        let mut battle = gen9_battle(
+            "|switch|p2a: Golisopod|Golisopod, L50, M|100/100\n\
+             |move|p1a: Dragapult|Dragon Darts|p2a: Golisopod\n\
+             |-damage|p2a: Golisopod|45/100\n\
+             |-activate|p2a: Golisopod|ability: Emergency Exit",
+        );
+        assert_eq!(
+            battle.forced_switch_reason(Player::P2),
+            Some("Emergency Exit")
+        );
+        assert_eq!(battle.forced_switch_reason(Player::P1), None);
+
+        for line in [
+            "|switch|p2a: Corviknight|Corviknight, L50, F|100/100",
+            "|turn|2",
+            "|move|p2a: Corviknight|Brave Bird|p1a: Dragapult",
+            "|-damage|p1a: Dragapult|0 fnt",
+            "|faint|p1a: Dragapult",
+            "|switch|p1a: Kingambit|Kingambit, L50, M|100/100",
+        ] {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        assert_eq!(battle.forced_switch_reason(Player::P2), None);
+        assert_eq!(
+            switch_kinds(&battle),
+            [
+                (1, "Golisopod", &SwitchKind::Chosen),
+                (
+                    1,
+                    "Corviknight",
+                    &SwitchKind::ForcedByEffect {
+                        source: "Emergency Exit".to_string()
+                    }
+                ),
+                (2, "Kingambit", &SwitchKind::Replacement),
+            ]
+        );
+        assert!(!battle.switches[1].kind.was_chosen());
+        let golisopod = find(&battle, Player::P2, "Golisopod");
+        assert_eq!(golisopod.known_ability.as_deref(), Some("Emergency Exit"));
+    }
+
+    #[test]
+    fn test_eject_button_forces_a_switch() {
+        let battle = gen9_battle(
+            "|move|p1a: Dragapult|Dragon Darts|p2a: Corviknight\n\
+             |-damage|p2a: Corviknight|70/100\n\
+             |-enditem|p2a: Corviknight|Eject Button\n\
+             |switch|p2a: Golisopod|Golisopod, L50, M|100/100\n\
+             |move|p2a: Golisopod|Knock Off|p1a: Dragapult\n\
+             |-enditem|p1a: Dragapult|Eject Button|[from] move: Knock Off|[of] p2a: Golisopod\n\
+             |move|p2a: Golisopod|Dragon Tail|p1a: Dragapult\n\
+             |drag|p1a: Kingambit|Kingambit, L50, M|100/100",
+        );
+        assert_eq!(
+            switch_kinds(&battle),
+            [
+                (
+                    1,
+                    "Golisopod",
+                    &SwitchKind::ForcedByEffect {
+                        source: "Eject Button".to_string()
+                    }
+                ),
+                (1, "Kingambit", &SwitchKind::Dragged),
+            ]
+        );
+        // A knocked off Eject Button withdraws nobody
+        assert_eq!(battle.forced_switch_reason(Player::P1), None);
+
+        let corviknight = find(&battle, Player::P2, "Corviknight");
+        assert_eq!(corviknight.known_item.as_deref(), Some("Eject Button"));
+        assert!(corviknight.item_consumed);
+    }
+
+    #[test]
+    fn test_hits_taken_this_turn() {
+        let mut battle = gen9_battle(
+            "|move|p1a: Dragapult|Iron Head|p2a: Corviknight\n\
+             |-damage|p2a: Corviknight|80/100\n\
+             |move|p1a: Dragapult|Shadow Ball|p2a: Corviknight\n\
+             |-damage|p2a: Corviknight|50/100\n\
+             |move|p2a: Corviknight|Roost|p2a: Corviknight\n\
+             |-damage|p2a: Corviknight|44/100|[from] Stealth Rock",
+        );
+        let corviknight = find(&battle, Player::P2, "Corviknight");
+        let hits: Vec<_> = corviknight
+            .hits_taken_this_turn
+            .iter()
+            .map(|hit| (hit.move_name.as_str(), hit.category, hit.attacker))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                ("Iron Head", Some(MoveCategory::Physical), (Player::P1, 'a')),
+                (
+                    "Shadow Ball",
+                    Some(MoveCategory::Special),
+                    (Player::P1, 'a')
+                ),
+            ]
+        );
+        assert_eq!(corviknight.last_hit().unwrap().damage_fraction, 0.3);
+        assert!(corviknight.took_physical_hit_this_turn());
+        assert!((corviknight.damage_taken_this_turn() - 0.5).abs() < 1e-6);
+
+        battle.apply_message(&parse_server_message("|turn|2").unwrap());
+        let corviknight = find(&battle, Player::P2, "Corviknight");
+        assert!(corviknight.last_hit().is_none());
+        assert!(!corviknight.took_physical_hit_this_turn());
+        assert_eq!(corviknight.damage_taken_this_turn(), 0.0);
+        assert_eq!(corviknight.hits_taken_last_turn.len(), 2);
+    }
+
+    #[test]
+    fn test_has_acted_this_turn() {
+        let mut battle = gen9_battle("|cant|p2a: Corviknight|par");
+        let remaining: Vec<_> = battle
+            .remaining_actors()
+            .iter()
+            .map(|p| p.pokemon.name())
+            .collect();
+        assert_eq!(remaining, ["Dragapult"]);
+        assert!(find(&battle, Player::P2, "Corviknight").has_acted_this_turn);
+
+        battle.apply_message(
+            &parse_server_message("|move|p1a: Dragapult|U-turn|p2a: Corviknight").unwrap(),
+        );
+        assert!(battle.remaining_actors().is_empty());
+        assert!(battle.is_resolving_turn());
+
+        battle.apply_message(&parse_server_message("|upkeep").unwrap());
+        assert!(!battle.is_resolving_turn());
+        battle.apply_message(&parse_server_message("|turn|2").unwrap());
+        assert_eq!(battle.remaining_actors().len(), 2);
+        assert!(!find(&battle, Player::P2, "Corviknight").has_acted_this_turn);
+    }
+
+    #[test]
+    fn test_locked_move_counts_as_acting() {
+        let mut battle = TrackedBattle::from_log(
+            "|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|doubles
+|gen|9
+|start
+|switch|p1a: Dragonite|Dragonite, L50, M|100/100
+|switch|p1b: Oricorio|Oricorio-Pom-Pom, L50, F|100/100
+|switch|p2a: Lilligant|Lilligant, L50, F|100/100
+|switch|p2b: Hatterene|Hatterene, L50, F|100/100
+|turn|1
+|move|p1a: Dragonite|Outrage|p2b: Hatterene
+|-immune|p2b: Hatterene
+|turn|2
+|move|p2a: Lilligant|Quiver Dance|p2a: Lilligant
+|move|p1b: Oricorio|Quiver Dance|p1b: Oricorio|[from]ability: Dancer
+|move|p1a: Dragonite|Outrage|p2a: Lilligant|[from]lockedmove",
+        );
+        let remaining: Vec<_> = battle
+            .remaining_actors()
+            .iter()
+            .map(|p| p.pokemon.name())
+            .collect();
+        assert_eq!(remaining, ["Oricorio", "Hatterene"]);
+
+        // A reflected move isn't the reflector's action either
+        battle.apply_message(
+            &parse_server_message("|move|p2b: Hatterene|Taunt|p1b: Oricorio|[from]Magic Bounce")
+                .unwrap(),
+        );
+        assert!(!find(&battle, Player::P2, "Hatterene").has_acted_this_turn);
+    }
+
+    #[test]
+    fn test_unresolved_move_user_reported_once() {
+        let battle = gen9_battle("|move|p1a: Ghost|Shadow Ball|p2a: Corviknight");
+        let ghost = Pokemon::parse("p1a: Ghost").unwrap();
+        assert_eq!(
+            battle.inconsistencies(),
+            [Inconsistency::UnresolvedPokemon(ghost)]
+        );
+    }
 }
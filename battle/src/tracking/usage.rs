@@ -0,0 +1,265 @@
+//! Per-battle usage statistics for logging and opponent profiles
+
+use kazam_protocol::Player;
+
+use super::battle::TrackedBattle;
+use crate::types::{DamageLedger, PokemonState, SideState};
+
+/// How one Pokemon was used over a battle
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PokemonUsage {
+    /// Species name (the latest forme seen)
+    pub species: String,
+
+    /// Times it entered the field, including as the lead
+    pub times_switched_in: u32,
+
+    /// Turns started with it active
+    pub turns_on_field: u32,
+
+    /// HP percentage points it took from foes with direct move damage
+    pub damage_dealt_estimate: u32,
+
+    /// HP percentage points it lost to damage
+    pub damage_taken_estimate: u32,
+
+    /// Percent of its max HP lost, by cause
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_taken: DamageLedger,
+
+    /// Percent of foes' max HP taken by its moves
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_dealt: DamageLedger,
+
+    /// Whether it fainted
+    pub fainted: bool,
+}
+
+impl PokemonUsage {
+    fn from_state(poke: &PokemonState) -> Self {
+        Self {
+            species: poke.identity.species.clone(),
+            times_switched_in: poke.times_switched_in,
+            turns_on_field: poke.turns_on_field,
+            damage_dealt_estimate: poke.damage_dealt_estimate,
+            damage_taken_estimate: poke.damage_taken_estimate,
+            damage_taken: poke.damage_taken,
+            damage_dealt: poke.damage_dealt,
+            fainted: poke.fainted,
+        }
+    }
+}
+
+/// How one player used their team over a battle
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SideUsage {
+    pub player: Player,
+
+    pub username: String,
+
+    /// Switches that replaced a Pokemon still on the field
+    pub total_switches: u32,
+
+    /// Species of the Pokemon brought in most often
+    pub most_used: Option<String>,
+
+    /// Every Pokemon seen on this side, in the order they were first seen
+    pub pokemon: Vec<PokemonUsage>,
+
+    /// Damage the team took, by cause
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_taken: DamageLedger,
+
+    /// Damage the team dealt, by cause
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_dealt: DamageLedger,
+}
+
+impl SideUsage {
+    fn from_side(side: &SideState) -> Self {
+        Self {
+            player: side.player,
+            username: side.username.clone(),
+            total_switches: side.total_switches(),
+            most_used: side
+                .most_used_pokemon()
+                .map(|poke| poke.identity.species.clone()),
+            pokemon: side.pokemon.iter().map(PokemonUsage::from_state).collect(),
+            damage_taken: side.damage_taken(),
+            damage_dealt: side.damage_dealt(),
+        }
+    }
+}
+
+/// Usage statistics for a whole battle, from [`TrackedBattle::usage_summary`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsageSummary {
+    /// Turns played
+    pub turns: u32,
+
+    pub sides: Vec<SideUsage>,
+}
+
+impl UsageSummary {
+    /// Usage for one player's side
+    pub fn side(&self, player: Player) -> Option<&SideUsage> {
+        self.sides.iter().find(|side| side.player == player)
+    }
+
+    /// Damage taken across all sides, by cause
+    pub fn damage_taken(&self) -> DamageLedger {
+        self.sides.iter().map(|side| side.damage_taken).sum()
+    }
+}
+
+impl TrackedBattle {
+    /// Switch, field time and damage counters for every Pokemon seen so far
+    pub fn usage_summary(&self) -> UsageSummary {
+        UsageSummary {
+            turns: self.turn,
+            sides: self.sides().map(SideUsage::from_side).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::parse_server_message;
+
+    const LOG: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Dragapult|Dragapult, M|100/100
+|switch|p2a: Meloetta|Meloetta, F|100/100
+|turn|1
+|move|p1a: Dragapult|U-turn|p2a: Meloetta
+|-damage|p2a: Meloetta|70/100
+|switch|p1a: Gholdengo|Gholdengo|100/100
+|move|p2a: Meloetta|Relic Song|p1a: Gholdengo
+|-damage|p1a: Gholdengo|80/100
+|detailschange|p2a: Meloetta|Meloetta-Pirouette, F
+|turn|2
+|switch|p2a: Corviknight|Corviknight, F|100/100
+|move|p1a: Gholdengo|Make It Rain|p2a: Corviknight
+|-damage|p2a: Corviknight|75/100
+|turn|3
+|move|p2a: Corviknight|Whirlwind|p1a: Gholdengo
+|drag|p1a: Dragapult|Dragapult, M|100/100
+|turn|4
+|switch|p2a: Meloetta|Meloetta-Pirouette, F|70/100
+|move|p1a: Dragapult|Shadow Ball|p2a: Meloetta
+|-damage|p2a: Meloetta|0 fnt
+|-damage|p1a: Dragapult|90/100|[from] item: Life Orb
+|faint|p2a: Meloetta
+|switch|p2a: Corviknight|Corviknight, F|75/100
+|turn|5"#;
+
+    fn usage(side: &SideUsage, species: &str) -> (u32, u32, u32, u32) {
+        let poke = side.pokemon.iter().find(|p| p.species == species).unwrap();
+        (
+            poke.times_switched_in,
+            poke.turns_on_field,
+            poke.damage_dealt_estimate,
+            poke.damage_taken_estimate,
+        )
+    }
+
+    #[test]
+    fn test_usage_summary() {
+        let mut battle = TrackedBattle::new();
+        for line in LOG.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let summary = battle.usage_summary();
+        assert_eq!(summary.turns, 5);
+
+        // U-turn and the Whirlwind drag each replaced a Pokemon once
+        let alice = summary.side(Player::P1).unwrap();
+        assert_eq!(alice.total_switches, 2);
+        assert_eq!(alice.most_used.as_deref(), Some("Dragapult"));
+        assert_eq!(usage(alice, "Dragapult"), (2, 3, 100, 10));
+        assert_eq!(usage(alice, "Gholdengo"), (1, 2, 25, 20));
+
+        // The replacement for the fainted Meloetta isn't a switch
+        let bob = summary.side(Player::P2).unwrap();
+        assert_eq!(bob.total_switches, 2);
+        assert_eq!(bob.most_used.as_deref(), Some("Corviknight"));
+        assert_eq!(usage(bob, "Corviknight"), (2, 3, 0, 25));
+
+        // Counters followed Meloetta through its forme change
+        assert_eq!(bob.pokemon.len(), 2);
+        assert_eq!(usage(bob, "Meloetta-Pirouette"), (2, 2, 20, 100));
+        assert!(bob.pokemon[0].fainted);
+    }
+
+    #[test]
+    fn test_damage_ledgers() {
+        let log = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Talonflame|Talonflame, M|100/100
+|switch|p2a: Garchomp|Garchomp, M|100/100
+|turn|1
+|move|p1a: Talonflame|Will-O-Wisp|p2a: Garchomp
+|-status|p2a: Garchomp|brn
+|move|p2a: Garchomp|Swagger|p1a: Talonflame
+|-boost|p1a: Talonflame|atk|2
+|-start|p1a: Talonflame|confusion
+|-damage|p2a: Garchomp|94/100 brn|[from] brn
+|upkeep
+|turn|2
+|-activate|p1a: Talonflame|confusion
+|-damage|p1a: Talonflame|80/100|[from] confusion
+|move|p2a: Garchomp|Stone Edge|p1a: Talonflame
+|-damage|p1a: Talonflame|40/100
+|-damage|p2a: Garchomp|88/100 brn|[from] brn
+|upkeep
+|turn|3
+|-activate|p1a: Talonflame|confusion
+|move|p1a: Talonflame|Brave Bird|p2a: Garchomp
+|-damage|p2a: Garchomp|58/100 brn
+|-damage|p1a: Talonflame|30/100|[from] Recoil
+|-damage|p1a: Talonflame|20/100|[from] item: Life Orb
+|move|p2a: Garchomp|Substitute|p2a: Garchomp
+|-start|p2a: Garchomp|Substitute
+|-damage|p2a: Garchomp|33/100 brn
+|-damage|p2a: Garchomp|27/100 brn|[from] brn
+|upkeep
+|turn|4"#;
+        let mut battle = TrackedBattle::new();
+        for line in log.lines() {
+            battle.apply_message(&parse_server_message(line).unwrap());
+        }
+        let summary = battle.usage_summary();
+        let ledger = |direct, residual, recoil, self_inflicted| DamageLedger {
+            direct,
+            residual,
+            recoil,
+            self_inflicted,
+        };
+
+        // Stone Edge is direct; the confusion hit and Life Orb are self-inflicted
+        let alice = summary.side(Player::P1).unwrap();
+        assert_eq!(alice.pokemon[0].damage_taken, ledger(40.0, 0.0, 10.0, 30.0));
+        assert_eq!(alice.pokemon[0].damage_dealt, ledger(30.0, 0.0, 0.0, 0.0));
+
+        // Burn chip is residual and the Substitute's HP is self-inflicted
+        let bob = summary.side(Player::P2).unwrap();
+        assert_eq!(bob.damage_taken, ledger(30.0, 18.0, 0.0, 25.0));
+        assert_eq!(bob.damage_dealt, ledger(40.0, 0.0, 0.0, 0.0));
+
+        assert_eq!(summary.damage_taken().total(), 153.0);
+        assert_eq!(
+            battle.get_side(Player::P1).unwrap().damage_taken(),
+            alice.damage_taken
+        );
+    }
+}
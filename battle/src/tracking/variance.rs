@@ -0,0 +1,323 @@
+//! Luck diagnostics: crits, misses and secondary effects against expectation
+//!
+//! The server never shows its rolls, so the counts come from what the log
+//! reveals. Expected misses use base accuracy from a curated move list, and
+//! expected crits use the 1/24 base rate on every direct hit; neither knows
+//! about stages, abilities, items or high-crit moves.
+
+use kazam_protocol::{Player, Pokemon};
+
+use super::battle::TrackedBattle;
+use crate::query::moves::{is_status_move, move_accuracy, secondary_chance};
+use crate::types::to_id;
+
+/// Base critical hit chance per hit from Gen 7 on
+pub const BASE_CRIT_RATE: f32 = 1.0 / 24.0;
+
+/// RNG outcomes of one side's Pokemon
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SideVariance {
+    /// Critical hits landed
+    pub crits: u32,
+
+    /// Direct hits landed, each a chance to crit
+    pub hits: u32,
+
+    /// Moves that missed
+    pub misses: u32,
+
+    /// Moves used from the curated accuracy list
+    pub accuracy_checks: u32,
+
+    /// Misses expected from those moves' accuracy
+    pub expected_misses: f32,
+
+    /// Statuses inflicted as a damaging move's secondary effect
+    pub secondary_statuses: u32,
+
+    /// Foes that flinched from this side's moves
+    pub flinches: u32,
+
+    /// Turns this side's Pokemon were fully paralyzed
+    pub full_paralysis: u32,
+}
+
+impl SideVariance {
+    /// Crits expected from the hits landed at the base rate
+    pub fn expected_crits(&self) -> f32 {
+        self.hits as f32 * BASE_CRIT_RATE
+    }
+
+    /// Secondary effect procs: statuses and flinches
+    pub fn secondary_procs(&self) -> u32 {
+        self.secondary_statuses + self.flinches
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.crits += other.crits;
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.accuracy_checks += other.accuracy_checks;
+        self.expected_misses += other.expected_misses;
+        self.secondary_statuses += other.secondary_statuses;
+        self.flinches += other.flinches;
+        self.full_paralysis += other.full_paralysis;
+    }
+}
+
+/// Per-side luck counts for a battle, see [`TrackedBattle::variance`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VarianceReport {
+    /// Counts for each side that has had an outcome
+    sides: Vec<(Player, SideVariance)>,
+
+    /// User of the move being resolved and whether it can proc a secondary effect
+    #[cfg_attr(feature = "serde", serde(default))]
+    attacker: Option<(Pokemon, bool)>,
+}
+
+impl VarianceReport {
+    /// Outcomes of `player`'s Pokemon
+    pub fn side(&self, player: Player) -> SideVariance {
+        self.sides
+            .iter()
+            .find(|(p, _)| *p == player)
+            .map(|(_, counts)| counts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Outcomes against `player`: every other side's counts combined
+    pub fn against(&self, player: Player) -> SideVariance {
+        let mut total = SideVariance::default();
+        for (_, counts) in self.sides.iter().filter(|(p, _)| *p != player) {
+            total.add(counts);
+        }
+        total
+    }
+
+    fn side_mut(&mut self, player: Player) -> &mut SideVariance {
+        let idx = match self.sides.iter().position(|(p, _)| *p == player) {
+            Some(idx) => idx,
+            None => {
+                self.sides.push((player, SideVariance::default()));
+                self.sides.len() - 1
+            }
+        };
+        &mut self.sides[idx].1
+    }
+
+    /// Attacker of the move being resolved, when `pokemon` is on another side
+    fn foe_attacker(&self, pokemon: &Pokemon) -> Option<&(Pokemon, bool)> {
+        self.attacker
+            .as_ref()
+            .filter(|(user, _)| user.player != pokemon.player)
+    }
+}
+
+impl TrackedBattle {
+    /// Luck counts for each side so far; complete once the battle has ended
+    pub fn variance(&self) -> &VarianceReport {
+        &self.variance
+    }
+
+    /// A |move| line starts a new move's outcomes
+    pub(crate) fn observe_move_variance(
+        &mut self,
+        user: &Pokemon,
+        move_name: &str,
+        target: Option<&Pokemon>,
+        still: bool,
+    ) {
+        let id = to_id(move_name);
+        // A secondary effect that always happens isn't luck
+        let procs = !is_status_move(&id, "") && secondary_chance(&id) != Some(100);
+        self.variance.attacker = Some((user.clone(), procs));
+        // A [still] line is a charge turn or a move with nothing to hit
+        if still || target.is_none_or(|t| t == user) {
+            return;
+        }
+        if let Some(accuracy) = move_accuracy(&id) {
+            let counts = self.variance.side_mut(user.player);
+            counts.accuracy_checks += 1;
+            counts.expected_misses += 1.0 - f32::from(accuracy) / 100.0;
+        }
+    }
+
+    /// Switches, residuals and new turns end the current move's outcomes
+    pub(crate) fn end_move_variance(&mut self) {
+        self.variance.attacker = None;
+    }
+
+    pub(crate) fn observe_hit(&mut self, target: &Pokemon) {
+        if let Some((user, _)) = self.variance.foe_attacker(target).cloned() {
+            self.variance.side_mut(user.player).hits += 1;
+        }
+    }
+
+    pub(crate) fn observe_crit(&mut self, target: &Pokemon) {
+        if let Some((user, _)) = self.variance.foe_attacker(target).cloned() {
+            self.variance.side_mut(user.player).crits += 1;
+        }
+    }
+
+    pub(crate) fn observe_miss(&mut self, source: &Pokemon) {
+        self.variance.side_mut(source.player).misses += 1;
+    }
+
+    /// A status that lands during a foe's damaging move came from its secondary effect
+    pub(crate) fn observe_status_variance(&mut self, pokemon: &Pokemon) {
+        if let Some((user, true)) = self.variance.foe_attacker(pokemon).cloned() {
+            self.variance.side_mut(user.player).secondary_statuses += 1;
+        }
+    }
+
+    pub(crate) fn observe_cant_variance(&mut self, pokemon: &Pokemon, reason: &str) {
+        match reason {
+            "par" => self.variance.side_mut(pokemon.player).full_paralysis += 1,
+            "flinch" => {
+                if let Some((user, true)) = self.variance.foe_attacker(pokemon).cloned() {
+                    self.variance.side_mut(user.player).flinches += 1;
+                }
+            }
+            _ => {}
+        }
+        self.variance.attacker = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::ServerMessage;
+
+    fn mon(player: Player, name: &str) -> Pokemon {
+        Pokemon {
+            player,
+            position: Some('a'),
+            name: name.to_string(),
+        }
+    }
+
+    fn battle() -> TrackedBattle {
+        let mut battle = TrackedBattle::new();
+        battle.apply_message(&ServerMessage::Turn(1));
+        battle
+    }
+
+    fn use_move(battle: &mut TrackedBattle, user: &Pokemon, name: &str, target: &Pokemon) {
+        battle.apply_message(&ServerMessage::Move {
+            pokemon: user.clone(),
+            move_name: name.to_string(),
+            target: Some(target.clone()),
+            miss: false,
+            still: false,
+            anim: None,
+            from: None,
+        });
+    }
+
+    fn hit(battle: &mut TrackedBattle, target: &Pokemon) {
+        battle.apply_message(&ServerMessage::Damage {
+            pokemon: target.clone(),
+            hp_status: None,
+            from: None,
+        });
+    }
+
+    #[test]
+    fn test_crits_and_misses_by_side() {
+        let mut battle = battle();
+        let chomp = mon(Player::P1, "Garchomp");
+        let gold = mon(Player::P2, "Gholdengo");
+
+        use_move(&mut battle, &chomp, "Earthquake", &gold);
+        battle.apply_message(&ServerMessage::Crit(gold.clone()));
+        hit(&mut battle, &gold);
+        use_move(&mut battle, &gold, "Focus Blast", &chomp);
+        battle.apply_message(&ServerMessage::Miss {
+            source: gold.clone(),
+            target: Some(chomp.clone()),
+        });
+        battle.apply_message(&ServerMessage::Turn(2));
+
+        use_move(&mut battle, &chomp, "Stone Edge", &gold);
+        battle.apply_message(&ServerMessage::Crit(gold.clone()));
+        hit(&mut battle, &gold);
+
+        let report = battle.variance();
+        let p1 = report.side(Player::P1);
+        assert_eq!(p1.crits, 2);
+        assert_eq!(p1.hits, 2);
+        assert_eq!(p1.misses, 0);
+        assert_eq!(p1.accuracy_checks, 1);
+        assert!((p1.expected_misses - 0.2).abs() < 1e-4);
+        assert!((p1.expected_crits() - 2.0 / 24.0).abs() < 1e-4);
+
+        let against = report.against(Player::P1);
+        assert_eq!(against.crits, 0);
+        assert_eq!(against.misses, 1);
+        assert_eq!(against, report.side(Player::P2));
+    }
+
+    #[test]
+    fn test_expected_misses_from_accuracy() {
+        let mut battle = battle();
+        let chomp = mon(Player::P1, "Garchomp");
+        let gold = mon(Player::P2, "Gholdengo");
+        for turn in 2..7 {
+            use_move(&mut battle, &gold, "Focus Blast", &chomp);
+            battle.apply_message(&ServerMessage::Turn(turn));
+        }
+        let p2 = battle.variance().side(Player::P2);
+        assert_eq!(p2.accuracy_checks, 5);
+        assert!((p2.expected_misses - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_secondary_effects() {
+        let mut battle = battle();
+        let chomp = mon(Player::P1, "Garchomp");
+        let gold = mon(Player::P2, "Gholdengo");
+
+        // Scald's burn is a proc, Will-O-Wisp's is not
+        use_move(&mut battle, &gold, "Scald", &chomp);
+        hit(&mut battle, &chomp);
+        battle.apply_message(&ServerMessage::Status {
+            pokemon: chomp.clone(),
+            status: "brn".to_string(),
+            from: None,
+        });
+        use_move(&mut battle, &gold, "Will-O-Wisp", &chomp);
+        battle.apply_message(&ServerMessage::Status {
+            pokemon: chomp.clone(),
+            status: "brn".to_string(),
+            from: None,
+        });
+        battle.apply_message(&ServerMessage::Turn(2));
+
+        use_move(&mut battle, &gold, "Iron Head", &chomp);
+        hit(&mut battle, &chomp);
+        battle.apply_message(&ServerMessage::Cant {
+            pokemon: chomp.clone(),
+            reason: "flinch".to_string(),
+            move_name: None,
+        });
+        battle.apply_message(&ServerMessage::Turn(3));
+
+        battle.apply_message(&ServerMessage::Cant {
+            pokemon: chomp.clone(),
+            reason: "par".to_string(),
+            move_name: None,
+        });
+
+        let p2 = battle.variance().side(Player::P2);
+        assert_eq!(p2.secondary_statuses, 1);
+        assert_eq!(p2.flinches, 1);
+        assert_eq!(p2.secondary_procs(), 2);
+        let p1 = battle.variance().side(Player::P1);
+        assert_eq!(p1.full_paralysis, 1);
+        assert_eq!(p1.secondary_procs(), 0);
+    }
+}
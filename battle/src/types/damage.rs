@@ -0,0 +1,166 @@
+//! Damage bookkeeping split by cause
+
+use std::iter::Sum;
+use std::ops::AddAssign;
+
+use kazam_protocol::Player;
+
+use super::pokemon::to_id;
+use crate::query::MoveCategory;
+
+/// What caused a `|-damage|` line, from its `[from]` tag
+///
+/// - no `[from]`: [`Direct`](Self::Direct) move damage, or
+///   [`SelfInflicted`](Self::SelfInflicted) when the move's user paid HP
+///   itself (Substitute, Belly Drum)
+/// - `[from] Recoil`: [`Recoil`](Self::Recoil)
+/// - `[from] confusion` and `[from] item: Life Orb`: [`SelfInflicted`](Self::SelfInflicted)
+/// - anything else (brn, psn, tox, weather, hazards, Leech Seed, Rocky
+///   Helmet, ...): [`Residual`](Self::Residual) chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageKind {
+    Direct,
+    Residual,
+    Recoil,
+    SelfInflicted,
+}
+
+impl DamageKind {
+    /// Classify damage by its `[from]` source; `own_move` when the last move's user is the target
+    pub fn classify(from: Option<&str>, own_move: bool) -> Self {
+        let Some(from) = from else {
+            return if own_move {
+                DamageKind::SelfInflicted
+            } else {
+                DamageKind::Direct
+            };
+        };
+        match to_id(from).as_str() {
+            "recoil" => DamageKind::Recoil,
+            "confusion" | "itemlifeorb" => DamageKind::SelfInflicted,
+            _ => DamageKind::Residual,
+        }
+    }
+}
+
+/// HP lost or dealt by cause, in percent of the damaged Pokemon's max HP
+///
+/// Amounts are sums of HP differences, never NaN, so the ledger is `Eq`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DamageLedger {
+    /// Move damage
+    pub direct: f32,
+
+    /// Status, weather, hazard and other passive chip
+    pub residual: f32,
+
+    /// Recoil from the Pokemon's own moves
+    pub recoil: f32,
+
+    /// Confusion self-hits, Life Orb and HP paid for moves
+    pub self_inflicted: f32,
+}
+
+impl Eq for DamageLedger {}
+
+impl DamageLedger {
+    /// Record `amount` under `kind`
+    pub fn add(&mut self, kind: DamageKind, amount: f32) {
+        *self.get_mut(kind) += amount;
+    }
+
+    /// Amount recorded under `kind`
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Direct => self.direct,
+            DamageKind::Residual => self.residual,
+            DamageKind::Recoil => self.recoil,
+            DamageKind::SelfInflicted => self.self_inflicted,
+        }
+    }
+
+    fn get_mut(&mut self, kind: DamageKind) -> &mut f32 {
+        match kind {
+            DamageKind::Direct => &mut self.direct,
+            DamageKind::Residual => &mut self.residual,
+            DamageKind::Recoil => &mut self.recoil,
+            DamageKind::SelfInflicted => &mut self.self_inflicted,
+        }
+    }
+
+    /// Everything recorded
+    pub fn total(&self) -> f32 {
+        self.direct + self.residual + self.recoil + self.self_inflicted
+    }
+}
+
+/// A move's direct damage landing on a Pokemon
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitRecord {
+    pub move_name: String,
+
+    /// From the curated move lists; None for moves outside them
+    pub category: Option<MoveCategory>,
+
+    /// Fraction of the target's max HP lost, from 0.0 to 1.0
+    pub damage_fraction: f32,
+
+    /// Side and position letter of the move's user
+    pub attacker: (Player, char),
+}
+
+impl AddAssign for DamageLedger {
+    fn add_assign(&mut self, other: Self) {
+        self.direct += other.direct;
+        self.residual += other.residual;
+        self.recoil += other.recoil;
+        self.self_inflicted += other.self_inflicted;
+    }
+}
+
+impl Sum for DamageLedger {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut total, ledger| {
+            total += ledger;
+            total
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (None, false, DamageKind::Direct),
+            (None, true, DamageKind::SelfInflicted),
+            (Some("brn"), false, DamageKind::Residual),
+            (Some("tox"), false, DamageKind::Residual),
+            (Some("Stealth Rock"), false, DamageKind::Residual),
+            (Some("item: Rocky Helmet"), false, DamageKind::Residual),
+            (Some("Recoil"), false, DamageKind::Recoil),
+            (Some("confusion"), false, DamageKind::SelfInflicted),
+            (Some("item: Life Orb"), false, DamageKind::SelfInflicted),
+        ];
+        for (from, own_move, expected) in cases {
+            assert_eq!(DamageKind::classify(from, own_move), expected, "{:?}", from);
+        }
+    }
+
+    #[test]
+    fn test_sum() {
+        let mut a = DamageLedger::default();
+        a.add(DamageKind::Direct, 30.0);
+        a.add(DamageKind::Residual, 6.25);
+        let mut b = DamageLedger::default();
+        b.add(DamageKind::Recoil, 10.0);
+        b.add(DamageKind::Direct, 5.0);
+        let total: DamageLedger = [a, b].into_iter().sum();
+        assert_eq!(total.get(DamageKind::Direct), 35.0);
+        assert_eq!(total.total(), 51.25);
+    }
+}
@@ -1,9 +1,73 @@
 //! Global field state
 
-use super::conditions::{Terrain, Weather};
+use kazam_battle_core::{Terrain, Weather};
+use kazam_protocol::{Player, Stat};
+
+use super::pokemon::PokemonState;
+use crate::id::to_id;
+
+/// A stat modifier applied to every Pokemon on the field except its source
+///
+/// Used for the gen 9 Ruin abilities (Tablets, Sword, Vessel and Beads of Ruin).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldStatModifier {
+    /// Player whose Pokemon is causing the modifier
+    pub source_player: Player,
+
+    /// Species of the Pokemon causing the modifier
+    pub source_species: String,
+
+    /// Stat being modified
+    pub stat: Stat,
+
+    /// Multiplier applied to the stat
+    pub multiplier: f32,
+}
+
+impl FieldStatModifier {
+    /// Build the modifier for a Ruin ability, or None if the ability isn't one
+    pub fn from_ruin_ability(
+        ability: &str,
+        source_player: Player,
+        source_species: impl Into<String>,
+    ) -> Option<Self> {
+        let stat = match to_id(ability).as_str() {
+            "tabletsofruin" => Stat::Atk,
+            "swordofruin" => Stat::Def,
+            "vesselofruin" => Stat::Spa,
+            "beadsofruin" => Stat::Spd,
+            _ => return None,
+        };
+
+        Some(Self {
+            source_player,
+            source_species: source_species.into(),
+            stat,
+            multiplier: 0.75,
+        })
+    }
+
+    /// Name of the Ruin ability that lowers this stat
+    pub fn ruin_ability(stat: Stat) -> Option<&'static str> {
+        match stat {
+            Stat::Atk => Some("Tablets of Ruin"),
+            Stat::Def => Some("Sword of Ruin"),
+            Stat::Spa => Some("Vessel of Ruin"),
+            Stat::Spd => Some("Beads of Ruin"),
+            _ => None,
+        }
+    }
+
+    /// Check whether this modifier comes from the given Pokemon
+    pub fn is_source(&self, player: Player, species: &str) -> bool {
+        self.source_player == player && self.source_species == species
+    }
+}
 
 /// Global field state affecting all Pokemon
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldState {
     /// Current weather condition
     pub weather: Option<Weather>,
@@ -34,6 +98,13 @@ pub struct FieldState {
 
     /// Fairy Lock active (no switching)
     pub fairy_lock: bool,
+
+    /// Field-wide stat modifiers from active Pokemon (Ruin abilities)
+    pub stat_modifiers: Vec<FieldStatModifier>,
+
+    /// Player and species of each active Neutralizing Gas holder
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub neutralizing_gas: Vec<(Player, String)>,
 }
 
 impl FieldState {
@@ -74,10 +145,7 @@ impl FieldState {
             .strip_prefix("move: ")
             .unwrap_or(condition);
 
-        // Normalize
-        let normalized = clean.to_lowercase().replace([' ', '-'], "");
-
-        match normalized.as_str() {
+        match to_id(clean).as_str() {
             // Weather (handled separately usually, but just in case)
             "sunnyday" | "raindance" | "sandstorm" | "hail" | "snow" | "desolateland"
             | "primordialsea" | "deltastream" => {
@@ -112,10 +180,7 @@ impl FieldState {
             .strip_prefix("move: ")
             .unwrap_or(condition);
 
-        // Normalize
-        let normalized = clean.to_lowercase().replace([' ', '-'], "");
-
-        match normalized.as_str() {
+        match to_id(clean).as_str() {
             // Terrain
             "electricterrain" | "grassyterrain" | "mistyterrain" | "psychicterrain" => {
                 self.terrain = None;
@@ -137,6 +202,62 @@ impl FieldState {
         }
     }
 
+    /// Add a field-wide stat modifier (ignored if its source already has one active)
+    pub fn add_stat_modifier(&mut self, modifier: FieldStatModifier) {
+        let exists = self.stat_modifiers.iter().any(|m| {
+            m.stat == modifier.stat && m.is_source(modifier.source_player, &modifier.source_species)
+        });
+        if !exists {
+            self.stat_modifiers.push(modifier);
+        }
+    }
+
+    /// Remove all modifiers caused by a Pokemon (it left the field or fainted)
+    pub fn remove_stat_modifiers_from(&mut self, player: Player, species: &str) {
+        self.stat_modifiers.retain(|m| !m.is_source(player, species));
+    }
+
+    /// Note a Neutralizing Gas holder entering; other Pokemon's Ruin abilities stop
+    pub fn start_neutralizing_gas(&mut self, player: Player, species: &str) {
+        if !self
+            .neutralizing_gas
+            .iter()
+            .any(|(p, s)| *p == player && s == species)
+        {
+            self.neutralizing_gas.push((player, species.to_string()));
+        }
+        self.stat_modifiers.retain(|m| m.is_source(player, species));
+    }
+
+    /// Note a Neutralizing Gas holder leaving, fainting or losing the ability
+    pub fn end_neutralizing_gas(&mut self, player: Player, species: &str) {
+        self.neutralizing_gas
+            .retain(|(p, s)| *p != player || s != species);
+    }
+
+    /// Whether Neutralizing Gas is suppressing abilities
+    pub fn abilities_suppressed(&self) -> bool {
+        !self.neutralizing_gas.is_empty()
+    }
+
+    /// Get the combined field multiplier for one Pokemon's stat
+    ///
+    /// The source is never affected by its own modifier, a Pokemon with the same
+    /// Ruin ability is immune to it, and modifiers to the same stat don't stack.
+    pub fn stat_multiplier(&self, player: Player, poke: &PokemonState, stat: Stat) -> f32 {
+        let has_same_ability = FieldStatModifier::ruin_ability(stat)
+            .zip(poke.current_ability())
+            .is_some_and(|(ruin, ability)| to_id(ruin) == to_id(ability));
+        if has_same_ability {
+            return 1.0;
+        }
+
+        self.stat_modifiers
+            .iter()
+            .find(|m| m.stat == stat && !m.is_source(player, &poke.identity.species))
+            .map_or(1.0, |m| m.multiplier)
+    }
+
     /// Check if any field condition is active
     pub fn has_any_condition(&self) -> bool {
         self.weather.is_some()
@@ -149,6 +270,8 @@ impl FieldState {
             || self.water_sport
             || self.ion_deluge
             || self.fairy_lock
+            || !self.stat_modifiers.is_empty()
+            || self.abilities_suppressed()
     }
 }
 
@@ -226,12 +349,39 @@ mod tests {
             water_sport: false,
             ion_deluge: false,
             fairy_lock: false,
+            stat_modifiers: Vec::new(),
+            neutralizing_gas: vec![(Player::P2, "Weezing-Galar".to_string())],
         };
 
         field.clear();
         assert!(!field.has_any_condition());
     }
 
+    #[test]
+    fn test_ruin_modifiers_skip_source_and_holders() {
+        let mut field = FieldState::new();
+        let modifier =
+            FieldStatModifier::from_ruin_ability("Vessel of Ruin", Player::P2, "Ting-Lu").unwrap();
+        assert_eq!(modifier.stat, Stat::Spa);
+        field.add_stat_modifier(modifier.clone());
+        field.add_stat_modifier(modifier);
+        assert_eq!(field.stat_modifiers.len(), 1);
+
+        let ting_lu = PokemonState::new("Ting-Lu", 100);
+        assert_eq!(field.stat_multiplier(Player::P2, &ting_lu, Stat::Spa), 1.0);
+
+        let mut foe = PokemonState::new("Garchomp", 100);
+        assert_eq!(field.stat_multiplier(Player::P1, &foe, Stat::Spa), 0.75);
+        assert_eq!(field.stat_multiplier(Player::P1, &foe, Stat::Def), 1.0);
+
+        // A second Vessel of Ruin user is unaffected by the first
+        foe.known_ability = Some("Vessel of Ruin".to_string());
+        assert_eq!(field.stat_multiplier(Player::P1, &foe, Stat::Spa), 1.0);
+
+        field.remove_stat_modifiers_from(Player::P2, "Ting-Lu");
+        assert!(field.stat_modifiers.is_empty());
+    }
+
     #[test]
     fn test_has_any_condition() {
         let mut field = FieldState::new();
@@ -0,0 +1,136 @@
+//! PP spent on each of a Pokemon's moves
+//!
+//! Every move a Pokemon chooses spends a PP, or two into a foe with
+//! Pressure. Moves called by another (Sleep Talk, Copycat, Magic Bounce) and
+//! Struggle spend none. Max PP comes from the curated base PP with full PP
+//! Ups, or exactly from a request for our own side.
+
+use kazam_protocol::MoveSlot;
+
+use super::pokemon::{PokemonState, to_id};
+use crate::query::moves::move_max_pp;
+
+/// PP use of one move a Pokemon chose
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnownMove {
+    pub name: String,
+
+    /// Times the Pokemon chose the move
+    pub times_used: u8,
+
+    /// PP spent, counting Pressure's extra PP
+    pub pp_spent: u8,
+
+    /// Max PP: exact once a request shows it, otherwise assumed to have full
+    /// PP Ups; None for moves outside the curated list
+    pub estimated_max_pp: Option<u8>,
+}
+
+impl KnownMove {
+    /// A move not used yet
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            times_used: 0,
+            pp_spent: 0,
+            estimated_max_pp: move_max_pp(name),
+        }
+    }
+
+    /// PP left, if the max is known
+    pub fn remaining_pp(&self) -> Option<u8> {
+        self.estimated_max_pp
+            .map(|max| max.saturating_sub(self.pp_spent))
+    }
+
+    /// Spend PP on a use; `pressure` when the move went into a foe with Pressure
+    pub fn spend(&mut self, pressure: bool) {
+        self.times_used = self.times_used.saturating_add(1);
+        self.pp_spent = self.pp_spent.saturating_add(if pressure { 2 } else { 1 });
+    }
+
+    /// Take PP from a request, which the server reports exactly
+    pub fn reconcile(&mut self, pp: u32, max_pp: u32) {
+        let max = u8::try_from(max_pp).unwrap_or(u8::MAX);
+        self.estimated_max_pp = Some(max);
+        self.pp_spent = max.saturating_sub(u8::try_from(pp).unwrap_or(u8::MAX));
+    }
+}
+
+impl PokemonState {
+    /// PP use of a move this Pokemon chose
+    pub fn known_move(&self, move_name: &str) -> Option<&KnownMove> {
+        let id = to_id(move_name);
+        self.known_move_pp.iter().find(|m| to_id(&m.name) == id)
+    }
+
+    /// Count a use of a move this Pokemon chose itself
+    pub fn spend_pp(&mut self, move_name: &str, pressure: bool) {
+        self.known_move_entry(move_name).spend(pressure);
+    }
+
+    /// Take exact PP from a request's move slots; slots locked into a move
+    /// (Outrage) report no PP and are skipped
+    pub fn reconcile_pp(&mut self, slots: &[MoveSlot]) {
+        for slot in slots.iter().filter(|slot| slot.max_pp > 0) {
+            self.known_move_entry(&slot.name)
+                .reconcile(slot.pp, slot.max_pp);
+        }
+    }
+
+    fn known_move_entry(&mut self, move_name: &str) -> &mut KnownMove {
+        let id = to_id(move_name);
+        let index = match self.known_move_pp.iter().position(|m| to_id(&m.name) == id) {
+            Some(index) => index,
+            None => {
+                self.known_move_pp.push(KnownMove::new(move_name));
+                self.known_move_pp.len() - 1
+            }
+        };
+        &mut self.known_move_pp[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spend_with_and_without_pressure() {
+        let mut gengar = PokemonState::new("Gengar", 50);
+        gengar.spend_pp("Shadow Ball", false);
+        gengar.spend_pp("shadowball", true);
+        let shadow_ball = gengar.known_move("Shadow Ball").unwrap();
+        assert_eq!(shadow_ball.times_used, 2);
+        assert_eq!(shadow_ball.pp_spent, 3);
+        assert_eq!(shadow_ball.estimated_max_pp, Some(24));
+        assert_eq!(shadow_ball.remaining_pp(), Some(21));
+
+        gengar.spend_pp("Made Up Move", false);
+        assert_eq!(
+            gengar.known_move("Made Up Move").unwrap().remaining_pp(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reconcile_takes_the_request() {
+        let slot = |name: &str, pp, max_pp| MoveSlot {
+            name: name.to_string(),
+            id: to_id(name),
+            pp,
+            max_pp,
+            target: "normal".to_string(),
+            disabled: false,
+        };
+        let mut gengar = PokemonState::new("Gengar", 50);
+        gengar.spend_pp("Shadow Ball", false);
+        gengar.reconcile_pp(&[slot("Shadow Ball", 13, 16), slot("Outrage", 0, 0)]);
+        let shadow_ball = gengar.known_move("Shadow Ball").unwrap();
+        assert_eq!(shadow_ball.estimated_max_pp, Some(16));
+        assert_eq!(shadow_ball.pp_spent, 3);
+        assert_eq!(shadow_ball.times_used, 1);
+        assert_eq!(gengar.known_move("Outrage"), None);
+    }
+}
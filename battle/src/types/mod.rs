@@ -1,17 +1,29 @@
 //! Domain types for battle state tracking
 
-mod conditions;
+mod damage;
 mod field;
+mod known_move;
 mod pokemon;
-mod pokemon_type;
 mod side;
-mod stats;
-mod status;
+mod side_conditions;
+mod snapshot;
+mod volatiles;
 
-pub use conditions::{SideCondition, SideConditionState, Terrain, Weather};
-pub use field::FieldState;
-pub use pokemon::{PokemonIdentity, PokemonState};
-pub use pokemon_type::{Type, TYPE_CHART};
-pub use side::SideState;
-pub use stats::StatStages;
-pub use status::{Status, Volatile};
+pub use damage::{DamageKind, DamageLedger, HitRecord};
+pub use field::{FieldStatModifier, FieldState};
+pub use known_move::KnownMove;
+pub use pokemon::{
+    ChargingMove, KnowledgeEntry, KnowledgeKind, MoveProvenance, Observation, Outcome,
+    PokemonIdentity, PokemonState,
+};
+pub(crate) use pokemon::{MOVE_SLOTS, to_id};
+pub use side::{HealAmount, HealLanding, PendingHeal, SideState};
+pub(crate) use side::same_species;
+pub use side_conditions::SideConditions;
+pub use snapshot::PokemonSnapshot;
+pub use volatiles::Volatiles;
+
+pub use kazam_battle_core::{
+    Mechanics, SideCondition, SideConditionState, SplitKind, StatBound, StatConstraint,
+    StatStages, Status, TeraType, Terrain, Type, TYPE_CHART, Volatile, VolatileState, Weather,
+};
@@ -1,15 +1,18 @@
 //! Pokemon state types
 
-use std::collections::HashSet;
+use kazam_battle_core::{
+    SplitKind, StatBound, StatConstraint, StatStages, Status, TeraType, Type, Volatile,
+};
+use kazam_protocol::{HpStatus, Player, PokemonDetails, PokemonStats, Stat};
+use smallvec::SmallVec;
 
-use kazam_protocol::{HpStatus, PokemonDetails};
-
-use super::pokemon_type::Type;
-use super::stats::StatStages;
-use super::status::{Status, Volatile};
+use super::{DamageLedger, FieldState, HitRecord, KnownMove, Volatiles};
+pub(crate) use crate::id::to_id;
+use crate::query::MoveCategory;
 
 /// Core Pokemon identity (doesn't change during battle)
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PokemonIdentity {
     /// Species name (including forme, e.g., "Pikachu-Alola")
     pub species: String,
@@ -68,8 +71,146 @@ impl Default for PokemonIdentity {
     }
 }
 
+/// Kind of set detail a knowledge entry describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KnowledgeKind {
+    Move,
+    Ability,
+    Item,
+    TeraType,
+}
+
+/// A set detail known about a Pokemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KnowledgeEntry {
+    /// What the entry describes
+    pub kind: KnowledgeKind,
+
+    /// Move, ability, item or type name
+    pub value: String,
+
+    /// Seen in an earlier game of a series but not yet revealed in this one
+    pub prior: bool,
+}
+
+impl KnowledgeEntry {
+    /// Weight given to prior knowledge relative to an in-game reveal
+    pub const PRIOR_WEIGHT: f32 = 0.5;
+
+    /// Create an entry carried over from an earlier game
+    pub fn prior(kind: KnowledgeKind, value: impl Into<String>) -> Self {
+        Self {
+            kind,
+            value: value.into(),
+            prior: true,
+        }
+    }
+
+    /// How much inference should trust this entry (1.0 for in-game reveals)
+    pub fn weight(&self) -> f32 {
+        if self.prior {
+            Self::PRIOR_WEIGHT
+        } else {
+            1.0
+        }
+    }
+
+    fn matches(&self, kind: KnowledgeKind, value: &str) -> bool {
+        self.kind == kind && to_id(&self.value) == to_id(value)
+    }
+}
+
+/// Move slots a set has
+pub(crate) const MOVE_SLOTS: usize = 4;
+
+/// Where knowledge of one of a Pokemon's moves came from
+///
+/// Ordered by how much it says about the rest of the set: a request or an
+/// open team sheet lists every move, an observation only the one used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveProvenance {
+    /// Seen in an earlier game of a series but not yet in this one
+    ScoutedPrior,
+
+    /// Used in this battle
+    Observed,
+
+    /// Listed on an open team sheet
+    OpenTeamSheet,
+
+    /// Listed in a request for one of our own Pokemon
+    OwnRequest,
+}
+
+impl MoveProvenance {
+    /// Whether the source lists the whole moveset, not just this move
+    pub fn is_exhaustive(&self) -> bool {
+        matches!(self, Self::OpenTeamSheet | Self::OwnRequest)
+    }
+}
+
+/// Result of a move against a Pokemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    /// Damage from a move of this type
+    Hit(Type),
+
+    /// The status was inflicted
+    Status(Status),
+
+    /// |-immune| with no revealed cause
+    Immune,
+
+    /// |-fail| with no revealed cause
+    Failed,
+}
+
+/// A move outcome that contradicts what was known about the target
+///
+/// Only recorded once visible explanations (typing, weather, terrain,
+/// Protect, Safeguard, ...) are ruled out, so it points at an unrevealed
+/// ability or item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Observation {
+    /// Turn the outcome was seen on
+    pub turn: u32,
+
+    /// Move used against this Pokemon
+    pub move_name: String,
+
+    /// Outcome predicted from tracked state
+    pub expected: Outcome,
+
+    /// Outcome the server reported
+    pub observed: Outcome,
+}
+
+/// A two-turn move announced by |-prepare|, due on the Pokemon's next action
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChargingMove {
+    pub move_name: String,
+
+    /// Side and position the move was aimed at, if the server named one
+    pub target: Option<(Player, char)>,
+}
+
+/// Abilities Neutralizing Gas can't suppress, by ID
+#[rustfmt::skip]
+const UNSUPPRESSABLE_ABILITIES: &[&str] = &[
+    "asoneglastrier", "asonespectrier", "battlebond", "comatose", "commander", "disguise",
+    "gulpmissile", "iceface", "multitype", "powerconstruct", "rkssystem", "schooling",
+    "shieldsdown", "stancechange", "terashift", "zenmode", "zerotohero",
+];
+
 /// Pokemon state during battle (changes as battle progresses)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PokemonState {
     /// Core identity
     pub identity: PokemonIdentity,
@@ -81,10 +222,32 @@ pub struct PokemonState {
     /// Maximum HP (only known for our Pokemon)
     pub hp_max: Option<u32>,
 
+    // === Stats ===
+    /// Exact stats (from the request for our Pokemon, or leaked by Transform for theirs)
+    pub stats: Option<PokemonStats>,
+
+    /// Stats from before a Transform, restored on switch-out
+    pub base_stats_snapshot: Option<PokemonStats>,
+
+    /// Known orderings between stats, for estimating unknown spreads
+    pub stat_constraints: Vec<StatConstraint>,
+
+    /// Known ranges for raw stats, for estimating unknown spreads
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stat_bounds: Vec<StatBound>,
+
+    /// Stats averaged with another Pokemon's by Guard Split or Power Split
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stat_split: Option<SplitKind>,
+
     // === Status ===
     /// Non-volatile status condition
     pub status: Option<Status>,
 
+    /// Effect the log credited with the status (`[from]`), e.g. "move: Rest"
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub status_from: Option<String>,
+
     /// Whether this Pokemon has fainted
     pub fainted: bool,
 
@@ -95,35 +258,69 @@ pub struct PokemonState {
     /// Stat stage modifiers
     pub boosts: StatStages,
 
-    /// Active volatile conditions
-    pub volatiles: HashSet<Volatile>,
+    /// Active volatile conditions, with turns left on the timed ones
+    pub volatiles: Volatiles,
+
+    /// Whether the Protosynthesis or Quark Drive boost came from Booster
+    /// Energy, so the field ending doesn't end it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub paradox_from_booster: bool,
 
     // === Type tracking ===
     /// Original types from species
-    pub base_types: Vec<Type>,
+    pub base_types: SmallVec<[Type; 3]>,
 
     /// Current types (may change via Forest's Curse, Soak, etc.)
-    pub current_types: Vec<Type>,
+    pub current_types: SmallVec<[Type; 3]>,
 
-    /// Tera type (if terastallized)
-    pub tera_type: Option<Type>,
+    /// Tera type (if terastallized or shown on an open team sheet)
+    pub tera_type: Option<TeraType>,
 
-    /// Whether currently terastallized
+    /// Whether terastallized (lasts for the rest of the battle)
     pub terastallized: bool,
 
     // === Revealed information ===
     /// Moves that have been revealed
-    pub known_moves: Vec<String>,
+    pub known_moves: SmallVec<[String; 6]>,
+
+    /// Where each of `known_moves` came from, by move ID; a known move
+    /// missing here counts as observed
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub move_sources: SmallVec<[(String, MoveProvenance); 6]>,
+
+    /// PP spent on each move this Pokemon chose, in the order first used
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub known_move_pp: Vec<KnownMove>,
 
     /// Ability that has been revealed
     pub known_ability: Option<String>,
 
+    /// Ability given in battle by Worry Seed, Simple Beam, Entrainment, Skill
+    /// Swap or Trace, in place of `known_ability` until switch-out
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ability_override: Option<String>,
+
     /// Item that has been revealed
     pub known_item: Option<String>,
 
     /// Whether the item has been consumed
     pub item_consumed: bool,
 
+    /// Last move used since switching in (the Encore / Choice lock target)
+    pub last_move: Option<String>,
+
+    /// Two-turn move being charged (Solar Beam, Meteor Beam, Phantom Force, ...)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub charging_move: Option<ChargingMove>,
+
+    /// Set details scouted in earlier games of a series
+    ///
+    /// Entries start out as priors and are promoted once revealed in this game.
+    pub scouting: Vec<KnowledgeEntry>,
+
+    /// Move outcomes that current knowledge cannot explain
+    pub contradictions: Vec<Observation>,
+
     // === Special states ===
     /// Species this Pokemon has transformed into
     pub transformed: Option<String>,
@@ -133,6 +330,53 @@ pub struct PokemonState {
 
     /// Whether has mega evolved this battle
     pub mega_evolved: bool,
+
+    /// Whether Disguise or Ice Face has been broken, so the next hit connects
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub disguise_busted: bool,
+
+    /// Intrepid Sword or Dauntless Shield has boosted it; from Gen 9 that
+    /// only happens once per battle
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub switch_in_boost_used: bool,
+
+    /// Moved, couldn't move, or switched in since the last |turn|
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub has_acted_this_turn: bool,
+
+    // === Usage (kept across forme changes) ===
+    /// Times this Pokemon entered the field, including as the lead
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub times_switched_in: u32,
+
+    /// Turns started with this Pokemon active
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub turns_on_field: u32,
+
+    /// HP percentage points taken from foes with direct move damage
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_dealt_estimate: u32,
+
+    /// HP percentage points lost to damage from any source
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_taken_estimate: u32,
+
+    /// Percent of its max HP lost, by cause
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_taken: DamageLedger,
+
+    /// Percent of foes' max HP taken by its moves; only `direct` is filled,
+    /// since statuses and hazards don't name who set them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub damage_dealt: DamageLedger,
+
+    /// Direct hits from moves since the last |turn|, in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hits_taken_this_turn: Vec<HitRecord>,
+
+    /// Hits from the turn before, for guessing what the foe does next
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hits_taken_last_turn: Vec<HitRecord>,
 }
 
 impl PokemonState {
@@ -142,22 +386,47 @@ impl PokemonState {
             identity: PokemonIdentity::new(species, level),
             hp_current: 100,
             hp_max: None,
+            stats: None,
+            base_stats_snapshot: None,
+            stat_constraints: Vec::new(),
+            stat_bounds: Vec::new(),
+            stat_split: None,
             status: None,
+            status_from: None,
             fainted: false,
             active: false,
             boosts: StatStages::new(),
-            volatiles: HashSet::new(),
-            base_types: Vec::new(),
-            current_types: Vec::new(),
+            volatiles: Volatiles::new(),
+            paradox_from_booster: false,
+            base_types: SmallVec::new(),
+            current_types: SmallVec::new(),
             tera_type: None,
             terastallized: false,
-            known_moves: Vec::new(),
+            known_moves: SmallVec::new(),
+            move_sources: SmallVec::new(),
+            known_move_pp: Vec::new(),
             known_ability: None,
+            ability_override: None,
             known_item: None,
             item_consumed: false,
+            last_move: None,
+            charging_move: None,
+            scouting: Vec::new(),
+            contradictions: Vec::new(),
             transformed: None,
             dynamaxed: false,
             mega_evolved: false,
+            disguise_busted: false,
+            switch_in_boost_used: false,
+            has_acted_this_turn: false,
+            times_switched_in: 0,
+            turns_on_field: 0,
+            damage_dealt_estimate: 0,
+            damage_taken_estimate: 0,
+            damage_taken: DamageLedger::default(),
+            damage_dealt: DamageLedger::default(),
+            hits_taken_this_turn: Vec::new(),
+            hits_taken_last_turn: Vec::new(),
         }
     }
 
@@ -168,7 +437,7 @@ impl PokemonState {
 
         // Parse tera type if present
         if let Some(ref tera_str) = details.tera_type {
-            state.tera_type = Type::from_protocol(tera_str);
+            state.tera_type = TeraType::from_protocol(tera_str);
         }
 
         state
@@ -196,6 +465,35 @@ impl PokemonState {
         }
     }
 
+    /// HP as an unrounded percentage of max HP
+    pub fn hp_percent_exact(&self) -> f32 {
+        match self.hp_max {
+            Some(0) => 0.0,
+            Some(max) => self.hp_current as f32 * 100.0 / max as f32,
+            None => self.hp_current as f32,
+        }
+    }
+
+    /// The latest move to hit this Pokemon this turn
+    pub fn last_hit(&self) -> Option<&HitRecord> {
+        self.hits_taken_this_turn.last()
+    }
+
+    /// Whether a physical move hit it this turn, for Counter
+    pub fn took_physical_hit_this_turn(&self) -> bool {
+        self.hits_taken_this_turn
+            .iter()
+            .any(|hit| hit.category == Some(MoveCategory::Physical))
+    }
+
+    /// Fraction of max HP lost to hits this turn
+    pub fn damage_taken_this_turn(&self) -> f32 {
+        self.hits_taken_this_turn
+            .iter()
+            .map(|hit| hit.damage_fraction)
+            .sum()
+    }
+
     /// Get display name (nickname or species)
     pub fn name(&self) -> &str {
         self.identity.name()
@@ -221,30 +519,276 @@ impl PokemonState {
         self.volatiles.clear();
     }
 
+    /// Turns left on a timed volatile (Perish Song's count), None if it
+    /// isn't up or has no fixed duration
+    pub fn volatile_turns_remaining(&self, v: &Volatile) -> Option<u8> {
+        self.volatiles.get(v)?.turns_remaining
+    }
+
     /// Record a revealed move
     pub fn record_move(&mut self, move_name: &str) {
-        let move_name = move_name.to_string();
-        if !self.known_moves.contains(&move_name) {
-            self.known_moves.push(move_name);
+        self.record_move_from(move_name, MoveProvenance::Observed);
+    }
+
+    /// Record a move from `provenance`; a move already known keeps the most
+    /// telling source it came from
+    pub fn record_move_from(&mut self, move_name: &str, provenance: MoveProvenance) {
+        self.promote(KnowledgeKind::Move, move_name);
+        let id = to_id(move_name);
+        match self.known_moves.iter_mut().find(|m| to_id(m) == id) {
+            // An in-game reveal replaces a team sheet's packed name ("FakeOut")
+            Some(known) if known != move_name => *known = move_name.to_string(),
+            Some(_) => {}
+            None => self.known_moves.push(move_name.to_string()),
+        }
+        match self.move_sources.iter_mut().find(|(m, _)| *m == id) {
+            Some((_, known)) => *known = (*known).max(provenance),
+            None => self.move_sources.push((id, provenance)),
         }
     }
 
+    /// Replace the known moves with a request's, which lists the whole set
+    pub fn set_request_moves(&mut self, moves: &[String]) {
+        self.known_moves = moves.iter().cloned().collect();
+        self.move_sources = moves
+            .iter()
+            .map(|m| (to_id(m), MoveProvenance::OwnRequest))
+            .collect();
+    }
+
+    /// Where knowledge of a move came from, None if it isn't known or scouted
+    pub fn move_provenance(&self, move_name: &str) -> Option<MoveProvenance> {
+        let id = to_id(move_name);
+        if self.known_moves.iter().any(|m| to_id(m) == id) {
+            let recorded = self.move_sources.iter().find(|(m, _)| *m == id);
+            return Some(recorded.map_or(MoveProvenance::Observed, |(_, p)| *p));
+        }
+        self.prior_moves()
+            .any(|m| to_id(m) == id)
+            .then_some(MoveProvenance::ScoutedPrior)
+    }
+
+    /// Known moves, then those only scouted in earlier games, with where each came from
+    pub fn move_knowledge(&self) -> impl Iterator<Item = (&str, MoveProvenance)> {
+        self.known_moves
+            .iter()
+            .map(|m| {
+                let provenance = self.move_provenance(m).unwrap_or(MoveProvenance::Observed);
+                (m.as_str(), provenance)
+            })
+            .chain(
+                self.prior_moves()
+                    .filter(|m| !self.known_moves.iter().any(|k| to_id(k) == to_id(m)))
+                    .map(|m| (m, MoveProvenance::ScoutedPrior)),
+            )
+    }
+
+    /// Whether every move is known: a request or open team sheet listed the
+    /// set, or all four have been seen
+    ///
+    /// Scouted priors don't count; the set may have changed between games.
+    pub fn moveset_complete(&self) -> bool {
+        self.known_moves.len() >= MOVE_SLOTS
+            || self.move_sources.iter().any(|(_, p)| p.is_exhaustive())
+    }
+
     /// Record a revealed ability
     pub fn record_ability(&mut self, ability: &str) {
+        self.promote(KnowledgeKind::Ability, ability);
         self.known_ability = Some(ability.to_string());
     }
 
+    /// Record an ability seen taking effect, unless it's one this Pokemon only
+    /// borrowed (Trace, Skill Swap, ...) rather than its own
+    pub fn reveal_ability(&mut self, ability: &str) {
+        let borrowed = self
+            .ability_override
+            .as_deref()
+            .is_some_and(|current| to_id(current) == to_id(ability));
+        if !borrowed {
+            self.record_ability(ability);
+        }
+    }
+
+    /// Give this Pokemon a different ability until it switches out
+    pub fn override_ability(&mut self, ability: &str) {
+        self.ability_override = Some(ability.to_string());
+    }
+
+    /// The ability in effect: an in-battle replacement, else the revealed one
+    pub fn current_ability(&self) -> Option<&str> {
+        self.ability_override
+            .as_deref()
+            .or(self.known_ability.as_deref())
+    }
+
+    /// The ability that takes effect: [`PokemonState::current_ability`] unless
+    /// suppressed by Gastro Acid
+    pub fn effective_ability(&self) -> Option<&str> {
+        self.current_ability()
+            .filter(|_| !self.has_volatile(&Volatile::GastroAcid))
+    }
+
+    /// [`PokemonState::effective_ability`] on `field`, where Neutralizing Gas
+    /// suppresses every ability but its own, the ones it can't touch and
+    /// those protected by Ability Shield
+    pub fn effective_ability_on(&self, field: &FieldState) -> Option<&str> {
+        let ability = self.effective_ability()?;
+        let shielded = self
+            .effective_item(field.magic_room)
+            .is_some_and(|item| to_id(item) == "abilityshield");
+        let id = to_id(ability);
+        let suppressed = field.abilities_suppressed()
+            && id != "neutralizinggas"
+            && !UNSUPPRESSABLE_ABILITIES.contains(&id.as_str())
+            && !shielded;
+        (!suppressed).then_some(ability)
+    }
+
+    /// Whether HP can be restored (Leftovers, Wish, healing moves); not under Heal Block
+    pub fn can_heal(&self) -> bool {
+        !self.has_volatile(&Volatile::HealBlock)
+    }
+
+    /// Whether the held item takes effect: not consumed, under Embargo or with Klutz
+    ///
+    /// Magic Room suppresses items too; that's on the field, not the Pokemon.
+    pub fn can_use_item(&self) -> bool {
+        let klutz = self
+            .effective_ability()
+            .is_some_and(|ability| to_id(ability) == "klutz");
+        !self.item_consumed && !self.has_volatile(&Volatile::Embargo) && !klutz
+    }
+
+    /// The revealed item when it takes effect on a field with or without Magic Room
+    pub fn effective_item(&self, magic_room: bool) -> Option<&str> {
+        self.known_item
+            .as_deref()
+            .filter(|_| !magic_room && self.can_use_item())
+    }
+
+    /// Start charging a two-turn move
+    pub fn start_charging(&mut self, charging: ChargingMove) {
+        self.charging_move = Some(charging);
+        self.add_volatile(Volatile::Charging);
+    }
+
+    /// Forget the charging move, once it fired or was interrupted
+    pub fn stop_charging(&mut self) {
+        self.charging_move = None;
+        self.remove_volatile(&Volatile::Charging);
+    }
+
     /// Record a revealed item
     pub fn record_item(&mut self, item: &str) {
+        self.promote(KnowledgeKind::Item, item);
         self.known_item = Some(item.to_string());
         self.item_consumed = false;
     }
 
+    /// Add a prior entry from an earlier game (ignored if already known)
+    pub fn add_prior(&mut self, kind: KnowledgeKind, value: &str) {
+        if !self.scouting.iter().any(|e| e.matches(kind, value)) {
+            self.scouting.push(KnowledgeEntry::prior(kind, value));
+        }
+    }
+
+    /// Mark a scouted entry as revealed in this game
+    pub fn promote(&mut self, kind: KnowledgeKind, value: &str) {
+        for entry in self.scouting.iter_mut().filter(|e| e.matches(kind, value)) {
+            entry.prior = false;
+        }
+    }
+
+    /// Moves seen in earlier games that haven't been revealed yet
+    pub fn prior_moves(&self) -> impl Iterator<Item = &str> {
+        self.scouting
+            .iter()
+            .filter(|e| e.prior && e.kind == KnowledgeKind::Move)
+            .map(|e| e.value.as_str())
+    }
+
     /// Mark item as consumed
     pub fn consume_item(&mut self) {
         self.item_consumed = true;
     }
 
+    /// Record exact stats from a request
+    ///
+    /// Stats reported while transformed belong to the Transform target, and
+    /// those reported after Guard Split or Power Split are averages, so the
+    /// snapshot restored on switch-out is only taken while neither applies.
+    pub fn set_stats(&mut self, stats: &PokemonStats) {
+        self.stats = Some(stats.clone());
+        if self.transformed.is_none() && self.stat_split.is_none() {
+            self.base_stats_snapshot = Some(stats.clone());
+        }
+    }
+
+    /// Record a known ordering between two stats
+    pub fn add_stat_constraint(&mut self, constraint: StatConstraint) {
+        if !self.stat_constraints.contains(&constraint) {
+            self.stat_constraints.push(constraint);
+        }
+    }
+
+    /// Record a known range for a raw stat
+    pub fn add_stat_bound(&mut self, bound: StatBound) {
+        if !self.stat_bounds.contains(&bound) {
+            self.stat_bounds.push(bound);
+        }
+    }
+
+    /// Get a stat after stage modifiers and any Protosynthesis or Quark Drive
+    /// boost, using exact stats when known
+    ///
+    /// Returns None for accuracy/evasion or when the raw stat isn't known.
+    pub fn effective_stat(&self, stat: Stat) -> Option<u32> {
+        let raw = self.raw_stat(stat)?;
+        let multiplier =
+            StatStages::multiplier(self.boosts.get(stat)) * self.paradox_multiplier(stat);
+        Some((raw as f32 * multiplier) as u32)
+    }
+
+    /// Possible values of a raw stat at this Pokemon's level, from its base stat
+    ///
+    /// From no investment with a hindering nature up to 31 IVs, 252 EVs and a
+    /// boosting nature, narrowed by any [`StatBound`], or exact once stats are
+    /// known. None for accuracy and evasion.
+    pub fn stat_range(&self, stat: Stat, base: u32) -> Option<(u32, u32)> {
+        if matches!(stat, Stat::Accuracy | Stat::Evasion) {
+            return None;
+        }
+        if let Some(raw) = self.raw_stat(stat) {
+            return Some((raw, raw));
+        }
+        let level = self.identity.level as u32;
+        let stat_at = |iv: u32, ev: u32, nature_tenths: u32| {
+            ((2 * base + iv + ev / 4) * level / 100 + 5) * nature_tenths / 10
+        };
+        let range = (stat_at(0, 0, 9), stat_at(31, 252, 11));
+        let bounded = self
+            .stat_bounds
+            .iter()
+            .filter(|bound| bound.stat == stat)
+            .fold(range, |(min, max), bound| {
+                (min.max(bound.min), max.min(bound.max))
+            });
+        // A bound seen in battle outranks the estimate if they disagree
+        if bounded.0 > bounded.1 {
+            return self
+                .stat_bounds
+                .iter()
+                .rfind(|bound| bound.stat == stat)
+                .map(|bound| (bound.min, bound.max));
+        }
+        Some(bounded)
+    }
+
+    fn raw_stat(&self, stat: Stat) -> Option<u32> {
+        self.stats.as_ref()?.get(stat)
+    }
+
     /// Apply HP and status from protocol HpStatus
     pub fn apply_hp_status(&mut self, hp_status: &HpStatus) {
         self.hp_current = hp_status.current;
@@ -270,12 +814,24 @@ impl PokemonState {
     pub fn on_switch_out(&mut self) {
         self.active = false;
         self.boosts.clear();
-        self.volatiles.clear();
+        self.volatiles.retain(|v| !v.clears_on_switch());
         self.dynamaxed = false;
+        self.last_move = None;
+        self.ability_override = None;
+        self.charging_move = None;
+        self.paradox_from_booster = false;
 
-        // Reset types to base types
+        // Reset types to base types; terastallization persists
         self.current_types = self.base_types.clone();
-        self.terastallized = false;
+
+        // Transform ends on switch-out, taking its copied stats with it. So does
+        // a split: PS's clearVolatile calls setSpecies on the base species,
+        // which recalculates storedStats from scratch.
+        let split = self.stat_split.take().is_some();
+        if (self.transformed.take().is_some() || split)
+            && let Some(base) = &self.base_stats_snapshot {
+                self.stats = Some(base.clone());
+            }
     }
 
     /// Called when this Pokemon switches in
@@ -293,27 +849,71 @@ impl PokemonState {
         self.is_alive() && !self.active
     }
 
-    /// Get current types (considering terastallization)
+    /// Get current defensive types
+    ///
+    /// A terastallized Pokemon has only its tera type, except Stellar, which
+    /// keeps its current types.
     pub fn get_types(&self) -> &[Type] {
-        if self.terastallized {
-            // When terastallized, only has the tera type for STAB/weakness purposes
-            // This is a simplification - actual mechanics are more complex
-            if let Some(ref _tera) = self.tera_type {
-                // In practice, the current_types should be updated when terastallizing
-                return &self.current_types;
-            }
+        if self.terastallized
+            && let Some(TeraType::Type(tera)) = &self.tera_type
+        {
+            return std::slice::from_ref(tera);
         }
         &self.current_types
     }
 
-    /// Check if Pokemon has a specific type
+    /// Apply a forme change, tracking whether Disguise or Ice Face is broken
+    ///
+    /// The current name is kept, since idents don't change with the forme.
+    /// Ice Face is restored when Eiscue changes back in hail or snow.
+    pub fn change_species(&mut self, species: &str) {
+        match to_id(species).as_str() {
+            "mimikyubusted" | "mimikyubustedtotem" | "eiscuenoice" => self.disguise_busted = true,
+            "eiscue" => self.disguise_busted = false,
+            _ => {}
+        }
+        // The protocol keeps addressing the Pokemon by its original name
+        let name = self.name().to_string();
+        self.identity.nickname = (name != species).then_some(name);
+        self.identity.species = species.to_string();
+    }
+
+    /// Whether an intact Disguise or Ice Face will absorb the next hit
+    ///
+    /// Ice Face only stops physical moves. False once another ability is known.
+    pub fn has_intact_disguise(&self) -> bool {
+        let species = to_id(&self.identity.species);
+        let ability_fits = self.current_ability().is_none_or(|ability| {
+            matches!(to_id(ability).as_str(), "disguise" | "iceface")
+        });
+        !self.disguise_busted
+            && self.transformed.is_none()
+            && ability_fits
+            && matches!(species.as_str(), "mimikyu" | "mimikyutotem" | "eiscue")
+    }
+
+    /// Record terastallization into `tera_type`
+    pub fn terastallize(&mut self, tera_type: TeraType) {
+        self.tera_type = Some(tera_type);
+        self.terastallized = true;
+    }
+
+    /// Check if Pokemon has a specific defensive type, see [`Self::get_types`]
     pub fn has_type(&self, t: Type) -> bool {
+        self.get_types().contains(&t)
+    }
+
+    /// Whether moves of type `t` get STAB
+    ///
+    /// Terastallizing keeps STAB on the original types and adds the tera type.
+    pub fn has_stab(&self, t: Type) -> bool {
         self.current_types.contains(&t)
+            || self.terastallized && self.tera_type == Some(TeraType::Type(t))
     }
 
     /// Set types (for forme changes, Transform, etc.)
     pub fn set_types(&mut self, types: Vec<Type>) {
-        self.current_types = types;
+        self.current_types = SmallVec::from_vec(types);
     }
 
     /// Add a type (Forest's Curse, Trick-or-Treat)
@@ -330,22 +930,47 @@ impl Default for PokemonState {
             identity: PokemonIdentity::default(),
             hp_current: 100,
             hp_max: None,
+            stats: None,
+            base_stats_snapshot: None,
+            stat_constraints: Vec::new(),
+            stat_bounds: Vec::new(),
+            stat_split: None,
             status: None,
+            status_from: None,
             fainted: false,
             active: false,
             boosts: StatStages::new(),
-            volatiles: HashSet::new(),
-            base_types: Vec::new(),
-            current_types: Vec::new(),
+            volatiles: Volatiles::new(),
+            paradox_from_booster: false,
+            base_types: SmallVec::new(),
+            current_types: SmallVec::new(),
             tera_type: None,
             terastallized: false,
-            known_moves: Vec::new(),
+            known_moves: SmallVec::new(),
+            move_sources: SmallVec::new(),
+            known_move_pp: Vec::new(),
             known_ability: None,
+            ability_override: None,
             known_item: None,
             item_consumed: false,
+            last_move: None,
+            charging_move: None,
+            scouting: Vec::new(),
+            contradictions: Vec::new(),
             transformed: None,
             dynamaxed: false,
             mega_evolved: false,
+            disguise_busted: false,
+            switch_in_boost_used: false,
+            has_acted_this_turn: false,
+            times_switched_in: 0,
+            turns_on_field: 0,
+            damage_dealt_estimate: 0,
+            damage_taken_estimate: 0,
+            damage_taken: DamageLedger::default(),
+            damage_dealt: DamageLedger::default(),
+            hits_taken_this_turn: Vec::new(),
+            hits_taken_last_turn: Vec::new(),
         }
     }
 }
@@ -394,6 +1019,42 @@ mod tests {
         assert_eq!(state.hp_percent(), 75);
     }
 
+    #[test]
+    fn test_move_provenance() {
+        let mut state = PokemonState::new("Garchomp", 100);
+        state.add_prior(KnowledgeKind::Move, "Swords Dance");
+        state.record_move("Earthquake");
+        state.record_move_from("Earthquake", MoveProvenance::ScoutedPrior);
+        assert_eq!(
+            state.move_provenance("earthquake"),
+            Some(MoveProvenance::Observed)
+        );
+        assert_eq!(
+            state.move_provenance("Swords Dance"),
+            Some(MoveProvenance::ScoutedPrior)
+        );
+        assert_eq!(state.move_provenance("Stone Edge"), None);
+
+        for name in ["Stone Edge", "Fire Fang"] {
+            state.record_move(name);
+        }
+        assert!(!state.moveset_complete());
+        state.record_move("Swords Dance");
+        assert!(state.moveset_complete());
+        assert_eq!(
+            state.move_provenance("Swords Dance"),
+            Some(MoveProvenance::Observed)
+        );
+
+        state.set_request_moves(&["earthquake".to_string()]);
+        assert_eq!(state.known_moves.as_slice(), ["earthquake"]);
+        assert_eq!(
+            state.move_provenance("Earthquake"),
+            Some(MoveProvenance::OwnRequest)
+        );
+        assert!(state.moveset_complete());
+    }
+
     #[test]
     fn test_pokemon_state_volatiles() {
         let mut state = PokemonState::new("Test", 100);
@@ -412,6 +1073,25 @@ mod tests {
         assert!(!state.has_volatile(&Volatile::Taunt));
     }
 
+    #[test]
+    fn test_pokemon_state_heal_block_and_embargo() {
+        let mut state = PokemonState::new("Test", 100);
+        state.known_item = Some("Leftovers".to_string());
+        assert!(state.can_heal() && state.can_use_item());
+
+        state.add_volatile(Volatile::HealBlock);
+        state.add_volatile(Volatile::Embargo);
+        assert!(!state.can_heal() && !state.can_use_item());
+
+        state.on_switch_out();
+        assert!(state.can_heal() && state.can_use_item());
+
+        state.record_ability("Klutz");
+        assert!(!state.can_use_item());
+        state.add_volatile(Volatile::GastroAcid);
+        assert!(state.can_use_item());
+    }
+
     #[test]
     fn test_pokemon_state_switch_out() {
         let mut state = PokemonState::new("Test", 100);
@@ -439,6 +1119,13 @@ mod tests {
         assert_eq!(state.known_moves.len(), 2);
         assert!(state.known_moves.contains(&"Thunderbolt".to_string()));
         assert!(state.known_moves.contains(&"Quick Attack".to_string()));
+        assert!(!state.known_moves.spilled());
+
+        // More than four revealed moves (e.g. via Transform) still fit
+        for name in ["Surf", "Ice Beam", "Protect"] {
+            state.record_move(name);
+        }
+        assert_eq!(state.known_moves.len(), 5);
     }
 
     #[test]
@@ -1,14 +1,19 @@
 //! Side (player) state
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
+use kazam_battle_core::{SideCondition, SideConditionState};
 use kazam_protocol::Player;
+use smallvec::{SmallVec, smallvec};
 
-use super::conditions::{SideCondition, SideConditionState};
-use super::pokemon::PokemonState;
+use super::DamageLedger;
+use super::pokemon::{PokemonState, to_id};
+use super::side_conditions::SideConditions;
+use crate::id::species_id;
 
 /// One player's side of the battle
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SideState {
     /// Player identifier (P1, P2, etc.)
     pub player: Player,
@@ -16,16 +21,101 @@ pub struct SideState {
     /// Player's username
     pub username: String,
 
-    /// Pokemon on this side (party order)
+    /// Pokemon on this side, in the order they were first seen
+    ///
+    /// Indices are stable for the whole battle; the request's own order is
+    /// in [`SideState::request_order`].
     pub pokemon: Vec<PokemonState>,
 
     /// Currently active Pokemon indices
     /// For singles: [Some(idx)] or [None]
     /// For doubles: [Some(idx1), Some(idx2)] etc.
-    pub active_indices: Vec<Option<usize>>,
+    pub active_indices: SmallVec<[Option<usize>; 3]>,
 
-    /// Side conditions (hazards, screens, etc.)
-    pub conditions: HashMap<SideCondition, SideConditionState>,
+    /// Side conditions (hazards, screens, etc.), in the order they were set
+    pub conditions: SideConditions,
+
+    /// Whether the full team was revealed by an open team sheet
+    pub team_sheet: bool,
+
+    /// Whether the team was listed at team preview with `|poke|`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub previewed: bool,
+
+    /// `pokemon` index of each entry in the latest request, in request order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) request_order: Vec<usize>,
+
+    /// Actions that cleared hazards from this side
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) hazards_cleared: u32,
+
+    /// Actions that cleared screens from this side
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) screens_cleared: u32,
+
+    /// Clearing actions against this side, by move or ability
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) cleared_by: BTreeMap<String, u32>,
+
+    /// Switches that replaced a Pokemon still on the field
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) total_switches: u32,
+
+    /// Heals waiting to land on an active slot (Wish, Healing Wish, Lunar Dance)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pending_heals: Vec<PendingHeal>,
+}
+
+/// How much a pending heal restores
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HealAmount {
+    /// A fraction of the user's max HP (the recipient's before Gen 5)
+    Fraction(f32),
+    /// All HP, status cured
+    Full,
+}
+
+/// When a pending heal lands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HealLanding {
+    /// At the end of this turn, on whatever is in the slot
+    OnTurn(u32),
+    /// On the next Pokemon to switch into the slot
+    OnNextSwitch,
+}
+
+/// A heal set up on a slot that hasn't landed yet
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PendingHeal {
+    /// Active slot the heal lands on
+    pub slot: usize,
+    /// Move that set it up
+    pub source: String,
+    /// Name of the Pokemon that used the move
+    pub user: String,
+    pub amount: HealAmount,
+    /// The user's max HP, when known, for heals sized by it
+    pub user_max_hp: Option<u32>,
+    pub landing: HealLanding,
+}
+
+impl PendingHeal {
+    /// HP percent this would restore to `recipient`, before capping at full
+    pub fn percent_for(&self, recipient: &PokemonState) -> f32 {
+        match self.amount {
+            HealAmount::Full => 100.0,
+            HealAmount::Fraction(fraction) => match (self.user_max_hp, recipient.hp_max) {
+                (Some(user), Some(max)) if max > 0 => {
+                    (user as f32 * fraction).floor() / max as f32 * 100.0
+                }
+                _ => fraction * 100.0,
+            },
+        }
+    }
 }
 
 impl SideState {
@@ -34,10 +124,80 @@ impl SideState {
         Self {
             player,
             username: username.into(),
-            pokemon: Vec::new(),
-            active_indices: vec![None], // Default to singles
-            conditions: HashMap::new(),
+            pokemon: Vec::with_capacity(6),
+            active_indices: smallvec![None], // Default to singles
+            conditions: SideConditions::new(),
+            team_sheet: false,
+            previewed: false,
+            request_order: Vec::new(),
+            hazards_cleared: 0,
+            screens_cleared: 0,
+            cleared_by: BTreeMap::new(),
+            total_switches: 0,
+            pending_heals: Vec::new(),
+        }
+    }
+
+    /// Times an action (Defog, Rapid Spin, ...) cleared hazards from this side
+    pub fn hazards_cleared_count(&self) -> u32 {
+        self.hazards_cleared
+    }
+
+    /// Times an action (Defog, Screen Cleaner, Brick Break, ...) cleared screens from this side
+    pub fn screens_cleared_count(&self) -> u32 {
+        self.screens_cleared
+    }
+
+    /// Clearing actions against this side, by the move or ability responsible
+    pub fn cleared_by(&self) -> &BTreeMap<String, u32> {
+        &self.cleared_by
+    }
+
+    /// Count one clearing action; flags say what it cleared that wasn't counted yet
+    pub(crate) fn record_clear(
+        &mut self,
+        cause: &str,
+        new_action: bool,
+        hazards: bool,
+        screens: bool,
+    ) {
+        if new_action {
+            *self.cleared_by.entry(cause.to_string()).or_default() += 1;
         }
+        self.hazards_cleared += hazards as u32;
+        self.screens_cleared += screens as u32;
+    }
+
+    /// Switches (including drags and pivots) that replaced a Pokemon still on
+    /// the field; leads and replacements for fainted Pokemon aren't counted
+    pub fn total_switches(&self) -> u32 {
+        self.total_switches
+    }
+
+    /// The Pokemon brought in most often, ties going to the longest on the field
+    pub fn most_used_pokemon(&self) -> Option<&PokemonState> {
+        self.pokemon
+            .iter()
+            .filter(|poke| poke.times_switched_in > 0)
+            .max_by_key(|poke| (poke.times_switched_in, poke.turns_on_field))
+    }
+
+    /// Damage the whole team took, by cause
+    pub fn damage_taken(&self) -> DamageLedger {
+        self.pokemon.iter().map(|poke| poke.damage_taken).sum()
+    }
+
+    /// Damage the whole team dealt, by cause
+    pub fn damage_dealt(&self) -> DamageLedger {
+        self.pokemon.iter().map(|poke| poke.damage_dealt).sum()
+    }
+
+    /// Count `pokemon_index` entering the field, `replacing` a Pokemon that was still in
+    pub(crate) fn record_switch_in(&mut self, pokemon_index: usize, replacing: bool) {
+        if let Some(poke) = self.pokemon.get_mut(pokemon_index) {
+            poke.times_switched_in += 1;
+        }
+        self.total_switches += replacing as u32;
     }
 
     /// Set the number of active slots (1 for singles, 2 for doubles, etc.)
@@ -112,6 +272,19 @@ impl SideState {
             .position(|p| p.name() == name || p.identity.species == name)
     }
 
+    /// Find a team sheet or team preview entry that hasn't been matched to a
+    /// nicknamed switch-in yet
+    ///
+    /// Preview hides some formes (`Urshifu-*`), which match any forme of the species.
+    pub fn find_team_sheet_entry(&self, species: &str) -> Option<usize> {
+        if !self.team_sheet && !self.previewed {
+            return None;
+        }
+        self.pokemon.iter().position(|p| {
+            p.identity.nickname.is_none() && same_species(&p.identity.species, species)
+        })
+    }
+
     /// Find a Pokemon by name and get a mutable reference
     pub fn find_pokemon_mut(&mut self, name: &str) -> Option<&mut PokemonState> {
         self.pokemon
@@ -147,16 +320,83 @@ impl SideState {
             state.add_layer(cond)
         } else {
             // New condition
-            self.conditions.insert(cond, SideConditionState::new());
+            self.conditions.insert(cond, SideConditionState::for_condition(cond));
             true
         }
     }
 
+    /// Force a condition to `layers`, capped at its maximum; 0 removes it
+    ///
+    /// For correcting the count when the log can't be trusted to, such as
+    /// after a rejoin. Remaining turns are kept if the condition was up.
+    pub fn set_condition_layers(&mut self, cond: SideCondition, layers: u8) {
+        if layers == 0 {
+            self.conditions.remove(&cond);
+            return;
+        }
+        let layers = layers.min(cond.max_layers());
+        match self.conditions.get_mut(&cond) {
+            Some(state) => state.layers = layers,
+            None => {
+                let mut state = SideConditionState::for_condition(cond);
+                state.layers = layers;
+                self.conditions.insert(cond, state);
+            }
+        }
+    }
+
+    /// Count down fixed-duration side conditions at the end of a turn
+    pub fn tick_conditions(&mut self) {
+        for state in self.conditions.values_mut() {
+            state.tick();
+        }
+    }
+
     /// Remove a side condition
     pub fn remove_condition(&mut self, cond: SideCondition) -> bool {
         self.conditions.remove(&cond).is_some()
     }
 
+    /// The heal that lands on `slot` if it stays filled through `current_turn`
+    pub fn incoming_heal(&self, slot: usize, current_turn: u32) -> Option<&PendingHeal> {
+        self.pending_heals.iter().find(|heal| {
+            heal.slot == slot
+                && match heal.landing {
+                    HealLanding::OnTurn(turn) => turn >= current_turn,
+                    HealLanding::OnNextSwitch => true,
+                }
+        })
+    }
+
+    /// Set up a heal, unless the same move is already waiting on the slot
+    pub(crate) fn add_pending_heal(&mut self, heal: PendingHeal) -> bool {
+        let duplicate = self
+            .pending_heals
+            .iter()
+            .any(|pending| pending.slot == heal.slot && pending.source == heal.source);
+        if !duplicate {
+            self.pending_heals.push(heal);
+        }
+        !duplicate
+    }
+
+    /// Remove the heal `source` set up on `slot`, once it lands
+    pub(crate) fn land_heal(&mut self, slot: usize, source: &str) -> Option<PendingHeal> {
+        let idx = self
+            .pending_heals
+            .iter()
+            .position(|heal| heal.slot == slot && to_id(&heal.source) == to_id(source))?;
+        Some(self.pending_heals.remove(idx))
+    }
+
+    /// Drop timed heals whose turn passed without landing
+    pub(crate) fn expire_heals(&mut self, turn: u32) {
+        self.pending_heals.retain(|heal| match heal.landing {
+            HealLanding::OnTurn(landing) => landing >= turn,
+            HealLanding::OnNextSwitch => true,
+        });
+    }
+
     /// Clear all side conditions
     pub fn clear_conditions(&mut self) {
         self.conditions.clear();
@@ -193,6 +433,53 @@ impl SideState {
             .position(|idx| *idx == Some(pokemon_index))
     }
 
+    /// `pokemon` index of each entry in the latest request's side list
+    ///
+    /// The server lists the active Pokemon first and reorders the list on
+    /// every switch, so "switch N" refers to `request_order()[N - 1]`. Empty
+    /// until a request is applied to this side.
+    pub fn request_order(&self) -> &[usize] {
+        &self.request_order
+    }
+
+    /// `pokemon` index a "switch N" choice refers to (1-indexed, as in the choice)
+    pub fn switch_target(&self, switch_slot: usize) -> Option<usize> {
+        self.request_order.get(switch_slot.checked_sub(1)?).copied()
+    }
+
+    /// "switch N" slot number that picks the Pokemon at `pokemon_index`
+    pub fn switch_slot(&self, pokemon_index: usize) -> Option<usize> {
+        self.request_order
+            .iter()
+            .position(|&idx| idx == pokemon_index)
+            .map(|position| position + 1)
+    }
+
+    /// Index of the Pokemon a request entry describes, skipping indices in `claimed`
+    ///
+    /// Matches the ident's name first, then the species, both ID-normalized.
+    pub(crate) fn match_request_pokemon(
+        &self,
+        name: &str,
+        species: &str,
+        claimed: &[usize],
+    ) -> Option<usize> {
+        let unclaimed = |idx: &usize| !claimed.contains(idx);
+        let (name, species) = (to_id(name), to_id(species));
+        (0..self.pokemon.len())
+            .filter(unclaimed)
+            .find(|&idx| to_id(self.pokemon[idx].name()) == name)
+            .or_else(|| {
+                (0..self.pokemon.len())
+                    .filter(unclaimed)
+                    .find(|&idx| same_species(&self.pokemon[idx].identity.species, &species))
+            })
+    }
+
+    pub(crate) fn set_request_order(&mut self, order: Vec<usize>) {
+        self.request_order = order;
+    }
+
     /// Check if any hazards are set
     pub fn has_hazards(&self) -> bool {
         self.conditions.keys().any(|c| c.is_hazard())
@@ -204,6 +491,18 @@ impl SideState {
     }
 }
 
+/// Whether a tracked species is `species`, or a preview's hidden forme of it;
+/// junk species names never match
+pub(crate) fn same_species(tracked: &str, species: &str) -> bool {
+    let Some(species) = species_id(species) else {
+        return false;
+    };
+    match tracked.strip_suffix("-*") {
+        Some(base) => species.starts_with(&to_id(base)),
+        None => to_id(tracked) == species,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -248,6 +547,8 @@ mod tests {
 
         side.set_active_slots(3);
         assert_eq!(side.active_indices.len(), 3);
+        // Up to triples stays inline
+        assert!(!side.active_indices.spilled());
     }
 
     #[test]
@@ -313,6 +614,20 @@ mod tests {
         assert!(!side.has_condition(SideCondition::Spikes));
     }
 
+    #[test]
+    fn test_set_condition_layers() {
+        let mut side = SideState::new(Player::P1, "Test");
+        side.add_condition(SideCondition::Spikes);
+        side.add_condition(SideCondition::Spikes);
+
+        side.set_condition_layers(SideCondition::Spikes, 1);
+        assert_eq!(side.condition_layers(SideCondition::Spikes), 1);
+        side.set_condition_layers(SideCondition::ToxicSpikes, 5);
+        assert_eq!(side.condition_layers(SideCondition::ToxicSpikes), 2);
+        side.set_condition_layers(SideCondition::Spikes, 0);
+        assert!(!side.has_condition(SideCondition::Spikes));
+    }
+
     #[test]
     fn test_all_fainted() {
         let mut side = create_test_side();
@@ -355,4 +670,15 @@ mod tests {
         assert!(side.has_hazards());
         assert!(side.has_screens());
     }
+
+    #[test]
+    fn test_same_species_collisions_and_junk() {
+        assert!(same_species("Nidoran-F", "Nidoran♀"));
+        assert!(!same_species("Nidoran-F", "Nidoran♂"));
+        assert!(same_species("Flabébé", "Flabebe"));
+        assert!(same_species("Sirfetch'd", "Sirfetch’d"));
+        assert!(!same_species("MissingNo.", "MissingNo."));
+        assert!(!same_species("Urshifu-*", "???"));
+        assert!(same_species("Urshifu-*", "Urshifu-Rapid-Strike"));
+    }
 }
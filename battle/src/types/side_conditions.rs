@@ -0,0 +1,226 @@
+//! Side conditions in the order they were set
+
+use std::ops::Index;
+
+use kazam_battle_core::{SideCondition, SideConditionState};
+
+/// A side's conditions, iterated in the order they were first set
+///
+/// A side has at most a dozen or so conditions at once, so a small vector
+/// beats hashing and keeps rendering, diffs and serialized output identical
+/// from run to run. Re-setting a condition (another Spikes layer) keeps its
+/// place; removing one keeps the others in order.
+#[derive(Debug, Clone, Default)]
+pub struct SideConditions {
+    entries: Vec<(SideCondition, SideConditionState)>,
+}
+
+impl SideConditions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    pub fn contains_key(&self, condition: &SideCondition) -> bool {
+        self.get(condition).is_some()
+    }
+
+    pub fn get(&self, condition: &SideCondition) -> Option<&SideConditionState> {
+        self.entries
+            .iter()
+            .find(|(c, _)| c == condition)
+            .map(|(_, state)| state)
+    }
+
+    pub fn get_mut(&mut self, condition: &SideCondition) -> Option<&mut SideConditionState> {
+        self.entries
+            .iter_mut()
+            .find(|(c, _)| c == condition)
+            .map(|(_, state)| state)
+    }
+
+    /// Set a condition's state, returning the old one; a new condition goes last
+    pub fn insert(
+        &mut self,
+        condition: SideCondition,
+        state: SideConditionState,
+    ) -> Option<SideConditionState> {
+        match self.get_mut(&condition) {
+            Some(existing) => Some(std::mem::replace(existing, state)),
+            None => {
+                self.entries.push((condition, state));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, condition: &SideCondition) -> Option<SideConditionState> {
+        let idx = self.entries.iter().position(|(c, _)| c == condition)?;
+        Some(self.entries.remove(idx).1)
+    }
+
+    /// Remove the conditions matching `f`, returning them oldest first
+    pub fn take_where(
+        &mut self,
+        mut f: impl FnMut(&SideCondition) -> bool,
+    ) -> Vec<(SideCondition, SideConditionState)> {
+        let (taken, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(c, _)| f(c));
+        self.entries = kept;
+        taken
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Conditions and their states, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (&SideCondition, &SideConditionState)> {
+        self.entries.iter().map(|(c, state)| (c, state))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &SideCondition> {
+        self.entries.iter().map(|(c, _)| c)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut SideConditionState> {
+        self.entries.iter_mut().map(|(_, state)| state)
+    }
+}
+
+/// Equal with the same conditions in the same states, whatever order they
+/// were set in, as a map would be
+impl PartialEq for SideConditions {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(condition, state)| other.get(condition) == Some(state))
+    }
+}
+
+impl Index<&SideCondition> for SideConditions {
+    type Output = SideConditionState;
+
+    fn index(&self, condition: &SideCondition) -> &SideConditionState {
+        self.get(condition)
+            .unwrap_or_else(|| panic!("no {condition:?} on this side"))
+    }
+}
+
+impl FromIterator<(SideCondition, SideConditionState)> for SideConditions {
+    fn from_iter<I: IntoIterator<Item = (SideCondition, SideConditionState)>>(iter: I) -> Self {
+        let mut conditions = Self::new();
+        for (condition, state) in iter {
+            conditions.insert(condition, state);
+        }
+        conditions
+    }
+}
+
+/// Serialized as a map, in order
+#[cfg(feature = "serde")]
+impl serde::Serialize for SideConditions {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SideConditions {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = SideConditions;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a map of side conditions")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<SideConditions, A::Error> {
+                let mut conditions = SideConditions::new();
+                while let Some((condition, state)) = map.next_entry()? {
+                    conditions.insert(condition, state);
+                }
+                Ok(conditions)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order_kept() {
+        let mut conditions = SideConditions::new();
+        for condition in [
+            SideCondition::Spikes,
+            SideCondition::StealthRock,
+            SideCondition::Reflect,
+        ] {
+            conditions.insert(condition, SideConditionState::for_condition(condition));
+        }
+        // Another layer keeps Spikes first
+        conditions
+            .get_mut(&SideCondition::Spikes)
+            .unwrap()
+            .add_layer(SideCondition::Spikes);
+        conditions.remove(&SideCondition::StealthRock);
+        conditions.insert(
+            SideCondition::Tailwind,
+            SideConditionState::for_condition(SideCondition::Tailwind),
+        );
+
+        let order: Vec<_> = conditions.keys().copied().collect();
+        assert_eq!(
+            order,
+            [
+                SideCondition::Spikes,
+                SideCondition::Reflect,
+                SideCondition::Tailwind
+            ]
+        );
+        assert_eq!(conditions[&SideCondition::Spikes].layers, 2);
+        assert!(!conditions.contains_key(&SideCondition::StealthRock));
+    }
+
+    #[test]
+    fn test_equality_ignores_order() {
+        let set = |order: &[SideCondition]| -> SideConditions {
+            order
+                .iter()
+                .map(|&c| (c, SideConditionState::for_condition(c)))
+                .collect()
+        };
+        let spikes_first = set(&[SideCondition::Spikes, SideCondition::Reflect]);
+        assert_eq!(
+            spikes_first,
+            set(&[SideCondition::Reflect, SideCondition::Spikes])
+        );
+        assert_ne!(spikes_first, set(&[SideCondition::Spikes]));
+        assert_ne!(
+            spikes_first,
+            set(&[SideCondition::Spikes, SideCondition::LightScreen])
+        );
+    }
+}
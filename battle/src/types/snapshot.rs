@@ -0,0 +1,470 @@
+//! Allocation-free copies of Pokemon state for calculations
+//!
+//! [`PokemonState`] tracks what is known about a Pokemon and owns strings,
+//! vectors and a set of volatiles. Scoring hypothetical actions only reads a
+//! handful of its fields, so it works on a [`PokemonSnapshot`] taken once per
+//! evaluation instead.
+
+use kazam_battle_core::{StatStages, Status, Type, Volatile};
+
+use super::{FieldState, KnowledgeEntry, MoveProvenance, PokemonState, to_id};
+use crate::query::grounding::{Grounded, is_grounded};
+use crate::query::moves::move_type;
+
+/// Volatiles a snapshot keeps, one bit each
+///
+/// Everything that hurts the holder (see [`Volatile::is_negative`]), the
+/// semi-invulnerable states and what decides grounding or blocks attacks.
+const TRACKED_VOLATILES: [Volatile; 39] = [
+    Volatile::Trapped,
+    Volatile::PartialTrap,
+    Volatile::Confusion,
+    Volatile::Taunt,
+    Volatile::Encore,
+    Volatile::Disable,
+    Volatile::Torment,
+    Volatile::Infatuation,
+    Volatile::LeechSeed,
+    Volatile::Curse,
+    Volatile::PerishSong,
+    Volatile::Nightmare,
+    Volatile::HealBlock,
+    Volatile::Embargo,
+    Volatile::Flinch,
+    Volatile::Yawn,
+    Volatile::Recharging,
+    Volatile::Smackdown,
+    Volatile::SlowStart,
+    Volatile::Truant,
+    Volatile::GastroAcid,
+    Volatile::Electrify,
+    Volatile::Octolock,
+    Volatile::TarShot,
+    Volatile::SaltCure,
+    Volatile::Syrupy,
+    Volatile::Fly,
+    Volatile::Dig,
+    Volatile::Dive,
+    Volatile::ShadowForce,
+    Volatile::PhantomForce,
+    Volatile::Bounce,
+    Volatile::SkyDrop,
+    Volatile::Ingrain,
+    Volatile::MagnetRise,
+    Volatile::Telekinesis,
+    Volatile::Roost,
+    Volatile::Substitute,
+    Volatile::Protect,
+];
+
+/// Up to three types, stored inline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TypeSet {
+    types: [Type; 3],
+    len: u8,
+}
+
+impl TypeSet {
+    fn new(types: impl IntoIterator<Item = Type>) -> Self {
+        let mut set = Self {
+            types: [Type::Normal; 3],
+            len: 0,
+        };
+        for t in types.into_iter().take(3) {
+            set.types[set.len as usize] = t;
+            set.len += 1;
+        }
+        set
+    }
+
+    fn as_slice(&self) -> &[Type] {
+        &self.types[..self.len as usize]
+    }
+}
+
+/// Bitset over [`Type`]s
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct TypeBits(u32);
+
+impl TypeBits {
+    fn insert(&mut self, t: Type) {
+        self.0 |= 1 << t as u32;
+    }
+
+    fn contains(&self, t: Type) -> bool {
+        self.0 & (1 << t as u32) != 0
+    }
+}
+
+/// Bitset over [`TRACKED_VOLATILES`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct VolatileSet(u64);
+
+impl VolatileSet {
+    fn bit(volatile: &Volatile) -> Option<u64> {
+        TRACKED_VOLATILES
+            .iter()
+            .position(|v| v == volatile)
+            .map(|index| 1 << index)
+    }
+
+    fn contains(&self, volatile: &Volatile) -> bool {
+        Self::bit(volatile).is_some_and(|bit| self.0 & bit != 0)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'static Volatile> + '_ {
+        TRACKED_VOLATILES
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.0 & (1 << index) != 0)
+            .map(|(_, volatile)| volatile)
+    }
+}
+
+/// What calculations need from a [`PokemonState`], `Copy` and allocation-free
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PokemonSnapshot {
+    /// Defensive types, after Terastallization
+    types: TypeSet,
+
+    /// Types the Pokemon gets STAB from before Terastallization
+    stab_types: TypeSet,
+
+    /// Types of attacks used or listed in this battle
+    attacks: TypeBits,
+
+    /// Types of attacks only scouted in earlier games
+    prior_attacks: TypeBits,
+
+    volatiles: VolatileSet,
+
+    pub status: Option<Status>,
+
+    pub boosts: StatStages,
+
+    /// HP in whole percent, as [`PokemonState::hp_percent`]
+    pub hp_percent: u8,
+
+    /// Grounding on the field the snapshot was taken for
+    pub grounded: Grounded,
+
+    /// An intact Disguise or Ice Face will absorb the next hit
+    pub intact_disguise: bool,
+
+    /// A known Covert Cloak or Shield Dust keeps secondary effects off it
+    pub blocks_secondaries: bool,
+
+    pub terastallized: bool,
+}
+
+impl PokemonSnapshot {
+    /// Current defensive types
+    pub fn types(&self) -> &[Type] {
+        self.types.as_slice()
+    }
+
+    /// Check for a defensive type
+    pub fn has_type(&self, t: Type) -> bool {
+        self.types.as_slice().contains(&t)
+    }
+
+    /// Whether moves of type `t` get STAB
+    pub fn has_stab(&self, t: Type) -> bool {
+        // Once terastallized, the defensive types are the tera type (Stellar
+        // keeps the original ones)
+        self.stab_types.as_slice().contains(&t) || self.terastallized && self.has_type(t)
+    }
+
+    /// Types of its known attacks, each with the weight of the most trusted
+    /// move of that type: 1.0, or [`KnowledgeEntry::PRIOR_WEIGHT`] when every
+    /// such move was only scouted in an earlier game
+    ///
+    /// Only attacks in the curated type lists count.
+    pub fn attack_types(&self) -> impl Iterator<Item = (Type, f32)> + '_ {
+        Type::ALL.into_iter().filter_map(|t| {
+            if self.attacks.contains(t) {
+                Some((t, 1.0))
+            } else {
+                self.prior_attacks
+                    .contains(t)
+                    .then_some((t, KnowledgeEntry::PRIOR_WEIGHT))
+            }
+        })
+    }
+
+    /// Check for a volatile; only the ones calculations read are kept
+    pub fn has_volatile(&self, volatile: &Volatile) -> bool {
+        self.volatiles.contains(volatile)
+    }
+
+    /// Whether the Pokemon is off the field mid-move
+    pub fn is_semi_invulnerable(&self) -> bool {
+        self.volatiles.iter().any(Volatile::is_semi_invulnerable)
+    }
+
+    /// Number of volatiles hurting the Pokemon
+    pub fn negative_volatiles(&self) -> usize {
+        self.volatiles.iter().filter(|v| v.is_negative()).count()
+    }
+
+    /// HP as a fraction of max HP (0.0-1.0)
+    pub fn hp_fraction(&self) -> f32 {
+        self.hp_percent as f32 / 100.0
+    }
+
+    /// Effectiveness of an attacking type, as [`effectiveness_against`](crate::query::effectiveness_against)
+    pub fn effectiveness(&self, attacking: Type) -> Option<f32> {
+        let types = self.types();
+        if attacking != Type::Ground {
+            return Some(attacking.effectiveness_multi(types));
+        }
+        match self.grounded {
+            Grounded::Yes => {
+                let grounded = TypeSet::new(types.iter().copied().filter(|&t| t != Type::Flying));
+                Some(attacking.effectiveness_multi(grounded.as_slice()))
+            }
+            Grounded::No => Some(0.0),
+            Grounded::Unknown(_) => None,
+        }
+    }
+}
+
+impl PokemonState {
+    /// Snapshot for calculations on a field without Gravity or Magic Room
+    pub fn snapshot(&self) -> PokemonSnapshot {
+        self.snapshot_on(&FieldState::default())
+    }
+
+    /// Snapshot for calculations on `field`, which decides grounding
+    pub fn snapshot_on(&self, field: &FieldState) -> PokemonSnapshot {
+        let mut volatiles = VolatileSet::default();
+        for volatile in &self.volatiles {
+            if let Some(bit) = VolatileSet::bit(volatile) {
+                volatiles.0 |= bit;
+            }
+        }
+        let mut attacks = TypeBits::default();
+        let mut prior_attacks = TypeBits::default();
+        for (name, provenance) in self.move_knowledge() {
+            if let Some(t) = move_type(name) {
+                match provenance {
+                    MoveProvenance::ScoutedPrior => prior_attacks.insert(t),
+                    _ => attacks.insert(t),
+                }
+            }
+        }
+        PokemonSnapshot {
+            types: TypeSet::new(self.get_types().iter().copied()),
+            stab_types: TypeSet::new(self.current_types.iter().copied()),
+            attacks,
+            prior_attacks,
+            volatiles,
+            status: self.status,
+            boosts: self.boosts,
+            hp_percent: self.hp_percent().min(u8::MAX as u32) as u8,
+            grounded: is_grounded(self, field),
+            intact_disguise: self.has_intact_disguise(),
+            blocks_secondaries: self
+                .effective_item(field.magic_room)
+                .is_some_and(|item| to_id(item) == "covertcloak")
+                || self
+                    .effective_ability_on(field)
+                    .is_some_and(|ability| to_id(ability) == "shielddust"),
+            terastallized: self.terastallized,
+        }
+    }
+}
+
+impl From<&PokemonState> for PokemonSnapshot {
+    fn from(state: &PokemonState) -> Self {
+        state.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::effectiveness_against;
+    use crate::types::TeraType;
+
+    fn poke(types: &[Type], ability: Option<&str>, item: Option<&str>) -> PokemonState {
+        let mut poke = PokemonState::new("Test", 100);
+        poke.set_types(types.to_vec());
+        poke.known_ability = ability.map(str::to_string);
+        poke.known_item = item.map(str::to_string);
+        poke
+    }
+
+    fn with_volatile(mut poke: PokemonState, volatile: Volatile) -> PokemonState {
+        poke.add_volatile(volatile);
+        poke
+    }
+
+    /// States covering typing, Tera, grounding, volatiles and unknowns
+    fn suite() -> Vec<PokemonState> {
+        let mut tera = poke(&[Type::Dragon, Type::Ground], Some("Rough Skin"), None);
+        tera.terastallize(TeraType::Type(Type::Steel));
+        let mut stellar = poke(&[Type::Water, Type::Flying], Some("Intimidate"), None);
+        stellar.terastallize(TeraType::Stellar);
+        let mut damaged = poke(
+            &[Type::Fire, Type::Steel],
+            Some("Flash Fire"),
+            Some("Leftovers"),
+        );
+        damaged.hp_current = 27;
+        damaged.status = Some(Status::Burn);
+        damaged.boosts.spa = 2;
+        let mut mimikyu = PokemonState::new("Mimikyu", 100);
+        mimikyu.set_types(vec![Type::Ghost, Type::Fairy]);
+
+        vec![
+            poke(&[Type::Water], Some("Torrent"), Some("Leftovers")),
+            poke(
+                &[Type::Steel, Type::Flying],
+                Some("Sturdy"),
+                Some("Leftovers"),
+            ),
+            poke(&[Type::Steel, Type::Flying], Some("Sturdy"), None),
+            poke(&[Type::Ghost], Some("Levitate"), Some("Leftovers")),
+            poke(&[Type::Steel], Some("Sturdy"), Some("Air Balloon")),
+            poke(&[Type::Flying], Some("Keen Eye"), Some("Iron Ball")),
+            poke(&[], Some("Torrent"), Some("Leftovers")),
+            poke(&[Type::Ghost], None, Some("Leftovers")),
+            with_volatile(
+                poke(
+                    &[Type::Flying, Type::Normal],
+                    Some("Keen Eye"),
+                    Some("Leftovers"),
+                ),
+                Volatile::Roost,
+            ),
+            with_volatile(
+                poke(&[Type::Ghost], Some("Levitate"), Some("Leftovers")),
+                Volatile::GastroAcid,
+            ),
+            with_volatile(
+                poke(&[Type::Flying], Some("Keen Eye"), Some("Iron Ball")),
+                Volatile::Embargo,
+            ),
+            with_volatile(
+                poke(&[Type::Steel], Some("Sturdy"), Some("Leftovers")),
+                Volatile::MagnetRise,
+            ),
+            with_volatile(
+                poke(&[Type::Flying], Some("Keen Eye"), Some("Leftovers")),
+                Volatile::Smackdown,
+            ),
+            with_volatile(
+                with_volatile(
+                    poke(&[Type::Ground], Some("Sand Veil"), None),
+                    Volatile::Dig,
+                ),
+                Volatile::LeechSeed,
+            ),
+            with_volatile(
+                poke(&[Type::Normal], Some("Scrappy"), None),
+                Volatile::Other("Stockpile 2".to_string()),
+            ),
+            tera,
+            stellar,
+            damaged,
+            mimikyu,
+        ]
+    }
+
+    fn fields() -> [FieldState; 3] {
+        [
+            FieldState::default(),
+            FieldState {
+                gravity: true,
+                ..FieldState::default()
+            },
+            FieldState {
+                magic_room: true,
+                ..FieldState::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_blocks_secondaries() {
+        let cloak = poke(&[Type::Water], Some("Unaware"), Some("Covert Cloak"));
+        assert!(cloak.snapshot().blocks_secondaries);
+        let magic_room = FieldState {
+            magic_room: true,
+            ..FieldState::default()
+        };
+        assert!(!cloak.snapshot_on(&magic_room).blocks_secondaries);
+        assert!(
+            !with_volatile(cloak, Volatile::Embargo)
+                .snapshot()
+                .blocks_secondaries
+        );
+
+        let dust = poke(&[Type::Bug], Some("Shield Dust"), None);
+        assert!(dust.snapshot().blocks_secondaries);
+        assert!(
+            !with_volatile(dust, Volatile::GastroAcid)
+                .snapshot()
+                .blocks_secondaries
+        );
+        assert!(!poke(&[Type::Bug], None, None).snapshot().blocks_secondaries);
+    }
+
+    #[test]
+    fn test_snapshot_matches_state() {
+        for (index, state) in suite().iter().enumerate() {
+            for field in &fields() {
+                let snapshot = state.snapshot_on(field);
+                for t in Type::ALL {
+                    assert_eq!(
+                        snapshot.effectiveness(t),
+                        effectiveness_against(t, state, field),
+                        "case {index}, {t:?}"
+                    );
+                    assert_eq!(snapshot.has_type(t), state.has_type(t), "case {index}");
+                    assert_eq!(snapshot.has_stab(t), state.has_stab(t), "case {index}");
+                }
+                assert_eq!(snapshot.grounded, is_grounded(state, field), "case {index}");
+            }
+
+            let snapshot = state.snapshot();
+            assert_eq!(snapshot.types(), state.get_types(), "case {index}");
+            assert_eq!(
+                snapshot.hp_percent as u32,
+                state.hp_percent(),
+                "case {index}"
+            );
+            assert_eq!(snapshot.status, state.status, "case {index}");
+            assert_eq!(snapshot.boosts, state.boosts, "case {index}");
+            assert_eq!(
+                snapshot.intact_disguise,
+                state.has_intact_disguise(),
+                "case {index}"
+            );
+            assert_eq!(
+                snapshot.is_semi_invulnerable(),
+                state.volatiles.iter().any(|v| v.is_semi_invulnerable()),
+                "case {index}"
+            );
+            assert_eq!(
+                snapshot.negative_volatiles(),
+                state.volatiles.iter().filter(|v| v.is_negative()).count(),
+                "case {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tracked_volatiles_cover_calculations() {
+        // Each volatile gets its own bit
+        for (index, volatile) in TRACKED_VOLATILES.iter().enumerate() {
+            assert_eq!(VolatileSet::bit(volatile), Some(1 << index), "{volatile:?}");
+        }
+        let snapshot =
+            with_volatile(PokemonState::new("Test", 100), Volatile::Substitute).snapshot();
+        assert!(snapshot.has_volatile(&Volatile::Substitute));
+        assert!(!snapshot.has_volatile(&Volatile::Protect));
+        assert!(std::mem::size_of::<PokemonSnapshot>() <= 48);
+    }
+}
@@ -0,0 +1,175 @@
+//! A Pokemon's volatiles and how long each has left
+
+use std::collections::BTreeMap;
+
+use kazam_battle_core::{Volatile, VolatileState};
+
+/// A Pokemon's volatiles with their remaining turns
+///
+/// Reads like a set of [`Volatile`]s, iterated in declaration order so
+/// output is the same from run to run; [`Volatiles::get`] has the turns
+/// left on the ones with a fixed duration. Adding a volatile that's already
+/// there keeps its count.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Volatiles {
+    entries: BTreeMap<Volatile, VolatileState>,
+}
+
+impl Volatiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, volatile: &Volatile) -> bool {
+        self.entries.contains_key(volatile)
+    }
+
+    pub fn get(&self, volatile: &Volatile) -> Option<&VolatileState> {
+        self.entries.get(volatile)
+    }
+
+    pub fn get_mut(&mut self, volatile: &Volatile) -> Option<&mut VolatileState> {
+        self.entries.get_mut(volatile)
+    }
+
+    /// Add a volatile with its fixed duration, returning whether it's new
+    pub fn insert(&mut self, volatile: Volatile) -> bool {
+        if self.entries.contains_key(&volatile) {
+            return false;
+        }
+        let state = VolatileState::for_volatile(&volatile);
+        self.entries.insert(volatile, state);
+        true
+    }
+
+    pub fn remove(&mut self, volatile: &Volatile) -> bool {
+        self.entries.remove(volatile).is_some()
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&Volatile) -> bool) {
+        self.entries.retain(|volatile, _| f(volatile));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Volatiles in declaration order
+    pub fn iter(&self) -> impl Iterator<Item = &Volatile> {
+        self.entries.keys()
+    }
+
+    /// Volatiles and their states, in declaration order
+    pub fn states(&self) -> impl Iterator<Item = (&Volatile, &VolatileState)> {
+        self.entries.iter()
+    }
+
+    /// Volatiles here that `other` doesn't have
+    pub fn difference<'a>(&'a self, other: &'a Volatiles) -> impl Iterator<Item = &'a Volatile> {
+        self.iter().filter(|volatile| !other.contains(volatile))
+    }
+
+    /// Count down one turn of each fixed-duration volatile
+    ///
+    /// Perish Song is left alone; the server announces its count.
+    pub fn tick(&mut self) {
+        for (volatile, state) in &mut self.entries {
+            if *volatile != Volatile::PerishSong {
+                state.tick();
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Volatiles {
+    type Item = &'a Volatile;
+    type IntoIter = std::collections::btree_map::Keys<'a, Volatile, VolatileState>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.keys()
+    }
+}
+
+/// Passed-on volatiles keep their counts (Baton Pass hands over Perish Song's)
+impl Extend<(Volatile, VolatileState)> for Volatiles {
+    fn extend<I: IntoIterator<Item = (Volatile, VolatileState)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+    }
+}
+
+impl FromIterator<Volatile> for Volatiles {
+    fn from_iter<I: IntoIterator<Item = Volatile>>(iter: I) -> Self {
+        let mut volatiles = Self::new();
+        for volatile in iter {
+            volatiles.insert(volatile);
+        }
+        volatiles
+    }
+}
+
+/// Serialized as a list of `[volatile, state]` pairs, since volatiles with
+/// data (`Protosynthesis(Spa)`) can't be map keys
+#[cfg(feature = "serde")]
+impl serde::Serialize for Volatiles {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.states())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Volatiles {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: Vec<(Volatile, VolatileState)> =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            entries: entries.into_iter().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reinsert_keeps_count() {
+        let mut volatiles = Volatiles::new();
+        assert!(volatiles.insert(Volatile::Taunt));
+        volatiles.tick();
+        assert!(!volatiles.insert(Volatile::Taunt));
+        assert_eq!(
+            volatiles.get(&Volatile::Taunt).unwrap().turns_remaining,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_tick_leaves_perish_song() {
+        let mut volatiles: Volatiles =
+            [Volatile::PerishSong, Volatile::Confusion, Volatile::Encore]
+                .into_iter()
+                .collect();
+        volatiles.tick();
+
+        let turns: Vec<_> = volatiles
+            .states()
+            .map(|(v, state)| (v.clone(), state.turns_remaining))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                (Volatile::Confusion, None),
+                (Volatile::Encore, Some(2)),
+                (Volatile::PerishSong, Some(3)),
+            ]
+        );
+    }
+}
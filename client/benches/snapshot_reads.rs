@@ -0,0 +1,110 @@
+//! Time dispatch while other threads poll the handle, as a GUI frontend does.
+//!
+//! Run with `cargo bench -p kazam-client --bench snapshot_reads`. Readers
+//! never wait on dispatch or make it wait, but with fewer cores than threads
+//! they still take its CPU time.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource};
+use kazam_protocol::{ServerFrame, parse_server_frame};
+
+const ROOMS: usize = 8;
+const TURNS: usize = 2_000;
+const ROUNDS: usize = 5;
+
+struct Bot;
+
+impl KazamHandler for Bot {}
+
+fn room(index: usize) -> String {
+    format!("battle-gen9randombattle-{index}")
+}
+
+fn frames() -> Vec<ServerFrame> {
+    let mut raw = vec!["|challstr|4|aaaa".to_string()];
+    for index in 0..ROOMS {
+        raw.push(format!(
+            ">{}\n|init|battle\n|title|Alice vs. Bob\n|users|3, Alice, Bob, Eve\n\
+             |player|p1|Alice|1|\n|player|p2|Bob|2|\n|gametype|singles\n|gen|9\n|start\n\
+             |switch|p1a: Garchomp|Garchomp, L80, M|100/100\n\
+             |switch|p2a: Heatran|Heatran, L80, F|100/100\n|turn|1",
+            room(index)
+        ));
+    }
+    for turn in 2..TURNS {
+        let hp = 100 - turn % 100;
+        raw.push(format!(
+            ">{}\n|move|p1a: Garchomp|Earthquake|p2a: Heatran\n|-damage|p2a: Heatran|{hp}/100\n\
+             |turn|{turn}",
+            room(turn % ROOMS)
+        ));
+    }
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+/// Keep `readers` threads reading until dispatch finishes, returns the
+/// seconds it took and the number of reads
+fn dispatch(frames: &[ServerFrame], readers: usize) -> (f64, u64) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut client = KazamClient::with_source(ScriptedSource::new(frames.to_vec()));
+    let done = Arc::new(AtomicBool::new(false));
+    let reads = Arc::new(AtomicU64::new(0));
+    let threads: Vec<_> = (0..readers)
+        .map(|_| {
+            let handle: KazamHandle = client.handle();
+            let done = done.clone();
+            let reads = reads.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    black_box(handle.get_battle(&room(0)));
+                    black_box(handle.most_watched_battle());
+                    reads.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    runtime.block_on(client.run(&mut Bot)).unwrap();
+    let elapsed = start.elapsed().as_secs_f64();
+    done.store(true, Ordering::Relaxed);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    (elapsed, reads.load(Ordering::Relaxed))
+}
+
+fn main() {
+    let frames = frames();
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    println!("{cores} cores");
+    let mut baseline = 0.0;
+    for readers in [0, 1, 4] {
+        let mut best = f64::MAX;
+        let mut reads = 0;
+        for _ in 0..ROUNDS {
+            let (seconds, count) = dispatch(&frames, readers);
+            if seconds < best {
+                best = seconds;
+                reads = count;
+            }
+        }
+        if readers == 0 {
+            baseline = best;
+        }
+        let us = best * 1e6 / frames.len() as f64;
+        println!(
+            "{readers} readers: {us:>6.2} us/frame, {:.2}x, {:>9} reads",
+            best / baseline,
+            reads
+        );
+    }
+}
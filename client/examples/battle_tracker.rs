@@ -4,11 +4,8 @@
 //! to accumulate and print battle state at the end of each turn.
 
 use anyhow::Result;
-use kazam_battle::TrackedBattle;
-use kazam_client::{
-    BattleRequest, KazamClient, KazamHandle, KazamHandler, RoomType, SHOWDOWN_URL, ServerMessage,
-    User,
-};
+use kazam_battle::prelude::*;
+use kazam_client::prelude::*;
 use rand::seq::SliceRandom;
 use std::collections::HashMap;
 
@@ -29,7 +26,7 @@ impl BattleTrackerBot {
     fn get_or_create_battle(&mut self, room_id: &str) -> &mut TrackedBattle {
         self.battles
             .entry(room_id.to_string())
-            .or_insert_with(TrackedBattle::new)
+            .or_default()
     }
 
     fn make_choice(&self, room_id: &str, request: &BattleRequest) {
@@ -50,12 +47,11 @@ impl BattleTrackerBot {
         }
 
         // Handle force switch
-        if request.is_force_switch() {
-            if let Some(choice) = self.pick_switch(request) {
+        if request.is_force_switch()
+            && let Some(choice) = self.pick_switch(request) {
                 self.handle.choose(room_id, &choice, rqid).ok();
                 return;
             }
-        }
 
         // Normal turn - pick a random move
         if let Some(choice) = self.pick_action(request) {
@@ -80,11 +76,13 @@ impl BattleTrackerBot {
         let mut rng = rand::thread_rng();
 
         if let Some(side) = &request.side {
+            // Revival Blessing picks among the fainted instead
+            let reviving = request.is_reviving();
             let switches: Vec<String> = side
                 .pokemon
                 .iter()
                 .enumerate()
-                .filter(|(_, p)| !p.active && !p.is_fainted())
+                .filter(|(_, p)| p.is_switch_target(reviving))
                 .map(|(i, _)| format!("switch {}", i + 1))
                 .collect();
 
@@ -182,7 +180,7 @@ impl BattleTrackerBot {
     }
 }
 
-fn format_pokemon(poke: &kazam_battle::PokemonState, show_details: bool) -> String {
+fn format_pokemon(poke: &PokemonState, show_details: bool) -> String {
     let mut parts = Vec::new();
 
     // Name/species
@@ -250,11 +248,10 @@ fn format_pokemon(poke: &kazam_battle::PokemonState, show_details: bool) -> Stri
         if let Some(ability) = &poke.known_ability {
             parts.push(format!("Ability:{}", ability));
         }
-        if let Some(item) = &poke.known_item {
-            if !poke.item_consumed {
+        if let Some(item) = &poke.known_item
+            && !poke.item_consumed {
                 parts.push(format!("Item:{}", item));
             }
-        }
     }
 
     parts.join(" ")
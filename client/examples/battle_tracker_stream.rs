@@ -0,0 +1,141 @@
+//! Battle State Tracker, Stream Edition
+//!
+//! The battle_tracker example written against `KazamClient::into_stream`
+//! instead of a handler: battle room messages are picked out with `StreamExt`
+//! combinators and fed to a TrackedBattle per room, printing a short summary
+//! at the start of each turn.
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use kazam_battle::prelude::*;
+use kazam_client::prelude::*;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+
+const FORMAT: &str = "gen9randombattle";
+
+/// A random legal choice for the request, if it needs one
+fn pick_choice(request: &BattleRequest) -> Option<String> {
+    if request.wait {
+        return None;
+    }
+    if request.team_preview {
+        let team_size = request.side.as_ref().map(|s| s.pokemon.len()).unwrap_or(6);
+        let order: String = (1..=team_size).map(|i| i.to_string()).collect();
+        return Some(format!("team {}", order));
+    }
+
+    let mut rng = rand::thread_rng();
+    let choices: Vec<String> = if request.is_force_switch() {
+        let reviving = request.is_reviving();
+        request
+            .side
+            .iter()
+            .flat_map(|side| side.pokemon.iter().enumerate())
+            .filter(|(_, p)| p.is_switch_target(reviving))
+            .map(|(i, _)| format!("switch {}", i + 1))
+            .collect()
+    } else {
+        request
+            .active
+            .as_ref()
+            .and_then(|a| a.first())
+            .map(|active| {
+                active
+                    .available_moves()
+                    .into_iter()
+                    .map(|(i, _)| format!("move {}", i + 1))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    choices.choose(&mut rng).cloned()
+}
+
+fn print_turn(battle: &TrackedBattle) {
+    println!("\n--- Turn {} ---", battle.turn);
+    for side in battle.sides() {
+        let active: Vec<_> = side
+            .get_active()
+            .map(|p| format!("{} {}HP", p.name(), p.hp_current))
+            .collect();
+        println!(
+            "{} {}: {}",
+            side.player.as_str(),
+            side.username,
+            active.join(", ")
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    println!("Connecting to Pokemon Showdown...");
+    let client = KazamClient::connect(SHOWDOWN_URL).await?;
+    let (handle, events) = client.into_stream();
+
+    // Everything before a lifecycle event is a message; stop when the client does
+    let mut messages = events
+        .take_while(|event| std::future::ready(!matches!(event, ClientEvent::Closed { .. })))
+        .filter_map(|event| async move {
+            match event {
+                ClientEvent::Message { room_id, message } => Some((room_id, message)),
+                ClientEvent::Lagged(missed) => {
+                    println!("Missed {} events", missed);
+                    None
+                }
+                _ => None,
+            }
+        })
+        .boxed();
+
+    let mut battles: HashMap<String, TrackedBattle> = HashMap::new();
+    while let Some((room_id, message)) = messages.next().await {
+        match (&room_id, &message) {
+            (_, ServerMessage::Challstr(challstr)) => {
+                handle.login("bmax117", "dragon117", challstr).await?;
+            }
+            (
+                _,
+                ServerMessage::UpdateUser {
+                    named: true, user, ..
+                },
+            ) => {
+                println!("Logged in as: {}{}", user.rank, user.username);
+                handle.search(FORMAT)?;
+            }
+            (Some(rid), ServerMessage::Request(json)) => {
+                let Some(request) = BattleRequest::parse(json) else {
+                    continue;
+                };
+                let battle = battles.entry(rid.clone()).or_default();
+                battle.update_from_request(&request);
+                if let Some(choice) = pick_choice(&request) {
+                    handle.choose(rid, &choice, request.rqid).ok();
+                }
+            }
+            (Some(rid), ServerMessage::Turn(_)) => {
+                let battle = battles.entry(rid.clone()).or_default();
+                battle.update(&message);
+                print_turn(battle);
+            }
+            (Some(rid), ServerMessage::Win(_) | ServerMessage::Tie) => {
+                if let Some(mut battle) = battles.remove(rid) {
+                    battle.update(&message);
+                    print_turn(&battle);
+                }
+                match &message {
+                    ServerMessage::Win(winner) => println!("\n{} won the battle!", winner),
+                    _ => println!("\nThe battle ended in a tie!"),
+                }
+                handle.search(FORMAT).ok();
+            }
+            (Some(rid), _) if rid.starts_with("battle-") => {
+                battles.entry(rid.clone()).or_default().update(&message);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use kazam_client::{KazamClient, KazamHandle, KazamHandler, RoomState, SHOWDOWN_URL, User};
+use kazam_client::prelude::*;
 
 struct YoBot {
     handle: KazamHandle,
@@ -39,13 +39,14 @@ impl KazamHandler for YoBot {
         user: &User,
         message: &str,
         _ts: Option<i64>,
+        is_history: bool,
     ) {
         // Log all messages
         if let Some(room) = room_id {
             println!("[{}] {}{}: {}", room, user.rank, user.username, message);
 
-            // Respond to !yo
-            if message == "!yo" {
+            // Respond to !yo, but not to ones said before we joined
+            if message == "!yo" && !is_history {
                 let response = format!("hi {}", user.username);
                 self.handle.send_chat(room, &response).ok();
             }
@@ -2,7 +2,7 @@ use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
-use kazam_client::{KazamClient, KazamHandle, KazamHandler, RoomState, SHOWDOWN_URL, User};
+use kazam_client::prelude::*;
 use tokio::io::{AsyncBufReadExt, BufReader};
 
 struct CliChat {
@@ -44,19 +44,17 @@ impl KazamHandler for CliChat {
     }
 
     async fn on_join(&mut self, room_id: Option<&str>, user: &User, quiet: bool) {
-        if !quiet {
-            if let Some(room) = room_id {
+        if !quiet
+            && let Some(room) = room_id {
                 println!("[{}] {} joined", room, user.username);
             }
-        }
     }
 
     async fn on_leave(&mut self, room_id: Option<&str>, user: &User, quiet: bool) {
-        if !quiet {
-            if let Some(room) = room_id {
+        if !quiet
+            && let Some(room) = room_id {
                 println!("[{}] {} left", room, user.username);
             }
-        }
     }
 
     async fn on_chat(
@@ -65,6 +63,7 @@ impl KazamHandler for CliChat {
         user: &User,
         message: &str,
         _ts: Option<i64>,
+        _is_history: bool,
     ) {
         if let Some(room) = room_id {
             println!("[{}] {}{}: {}", room, user.rank, user.username, message);
@@ -94,8 +93,8 @@ async fn handle_input(
         return true;
     }
 
-    if line.starts_with('/') {
-        let parts: Vec<&str> = line[1..].splitn(2, ' ').collect();
+    if let Some(command) = line.strip_prefix('/') {
+        let parts: Vec<&str> = command.splitn(2, ' ').collect();
         let cmd = parts[0];
         let arg = parts.get(1).map(|s| s.trim());
 
@@ -119,11 +118,10 @@ async fn handle_input(
                         println!("Error: {}", e);
                     } else {
                         println!("Left room: {}", room);
-                        if let Ok(mut current) = current_room.lock() {
-                            if current.as_ref() == Some(&room) {
+                        if let Ok(mut current) = current_room.lock()
+                            && current.as_ref() == Some(&room) {
                                 *current = None;
                             }
-                        }
                     }
                 } else {
                     println!("Not in a room. Usage: /leave [room]");
@@ -0,0 +1,245 @@
+//! Discord Bridge Example
+//!
+//! Logs in, joins the configured rooms and posts to a Discord webhook when a
+//! battle we play ends, when someone mentions us in chat, and when a
+//! tournament in one of the rooms reaches its final.
+//!
+//! Configure with a JSON file named by DISCORD_BRIDGE_CONFIG:
+//!
+//! ```json
+//! {
+//!     "username": "KazamBot",
+//!     "password": "hunter2",
+//!     "webhook_url": "https://discord.com/api/webhooks/...",
+//!     "rooms": ["lobby", "tournaments"],
+//!     "highlights": ["kazam"]
+//! }
+//! ```
+//!
+//! or with PS_USERNAME, PS_PASSWORD, DISCORD_WEBHOOK_URL, and comma-separated
+//! BRIDGE_ROOMS and BRIDGE_HIGHLIGHTS.
+//!
+//! Run with `cargo run -p kazam-client --example discord_bridge --features examples-integrations`.
+
+use std::collections::HashSet;
+use std::env;
+
+use anyhow::{Context, Result};
+use kazam_client::prelude::*;
+use kazam_client::{HighlightMatcher, WebhookPoster};
+use kazam_protocol::same_user;
+use serde_json::Value;
+
+struct Config {
+    username: String,
+    password: String,
+    webhook_url: String,
+    rooms: Vec<String>,
+    highlights: Vec<String>,
+}
+
+impl Config {
+    fn load() -> Result<Self> {
+        match env::var("DISCORD_BRIDGE_CONFIG") {
+            Ok(path) => {
+                let text =
+                    std::fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+                Self::from_json(&serde_json::from_str(&text)?)
+            }
+            Err(_) => Self::from_env(),
+        }
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        let text = |key: &str| {
+            json.get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .with_context(|| format!("config is missing \"{key}\""))
+        };
+        let list = |key: &str| -> Vec<String> {
+            json.get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|v| Some(v.as_str()?.to_string()))
+                .collect()
+        };
+        Ok(Self {
+            username: text("username")?,
+            password: text("password")?,
+            webhook_url: text("webhook_url")?,
+            rooms: list("rooms"),
+            highlights: list("highlights"),
+        })
+    }
+
+    fn from_env() -> Result<Self> {
+        let var = |key: &str| env::var(key).with_context(|| format!("{key} is not set"));
+        let list = |key: &str| -> Vec<String> {
+            env::var(key)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        Ok(Self {
+            username: var("PS_USERNAME")?,
+            password: var("PS_PASSWORD")?,
+            webhook_url: var("DISCORD_WEBHOOK_URL")?,
+            rooms: list("BRIDGE_ROOMS"),
+            highlights: list("BRIDGE_HIGHLIGHTS"),
+        })
+    }
+}
+
+struct Bridge {
+    handle: KazamHandle,
+    config: Config,
+    webhook: WebhookPoster,
+    highlights: HighlightMatcher,
+    /// Rooms whose current tournament already had its final announced
+    finals_announced: HashSet<String>,
+}
+
+impl Bridge {
+    fn new(handle: KazamHandle, config: Config) -> Self {
+        let mut highlights = HighlightMatcher::for_username(&config.username);
+        for term in &config.highlights {
+            highlights.add(term);
+        }
+        Self {
+            handle,
+            webhook: WebhookPoster::new(&config.webhook_url),
+            highlights,
+            finals_announced: HashSet::new(),
+            config,
+        }
+    }
+
+    /// Post in the background, so a slow webhook doesn't hold up the battle
+    fn post(&self, content: String) {
+        let webhook = self.webhook.clone();
+        tokio::spawn(async move {
+            if let Err(e) = webhook.post(&content).await {
+                eprintln!("Could not post to the webhook: {e:#}");
+            }
+        });
+    }
+}
+
+/// Finalists once a tournament's bracket is down to its last match
+///
+/// Elimination brackets arrive as a tree in `|tournament|update|`; the final
+/// is the root, which becomes available once both of its children are decided.
+fn finalists(update: &Value) -> Option<Vec<String>> {
+    let root = update.get("bracketData")?.get("rootNode")?;
+    let state = root.get("state")?.as_str()?;
+    if !matches!(state, "available" | "challenging" | "inprogress") {
+        return None;
+    }
+    let teams = root
+        .get("children")?
+        .as_array()?
+        .iter()
+        .filter_map(|child| Some(child.get("team")?.as_str()?.to_string()))
+        .collect::<Vec<_>>();
+    (teams.len() == 2).then_some(teams)
+}
+
+impl KazamHandler for Bridge {
+    async fn on_challstr(&mut self, challstr: &str) {
+        if let Err(e) = self
+            .handle
+            .login(&self.config.username, &self.config.password, challstr)
+            .await
+        {
+            eprintln!("Login failed: {e:#}");
+            self.handle.shutdown();
+        }
+    }
+
+    async fn on_logged_in(&mut self, user: &User) {
+        println!("Logged in as {}", user.username);
+        for room in &self.config.rooms {
+            self.handle.join_room(room).ok();
+        }
+    }
+
+    async fn on_chat(
+        &mut self,
+        room_id: Option<&str>,
+        user: &User,
+        message: &str,
+        _timestamp: Option<i64>,
+        is_history: bool,
+    ) {
+        let Some(room) = room_id else {
+            return;
+        };
+        // Scrollback from joining isn't news, and we don't highlight ourselves
+        if is_history || same_user(&user.username, &self.config.username) {
+            return;
+        }
+        if self.highlights.matches(message) {
+            self.post(format!("**{}** in {room}: {message}", user.username));
+        }
+    }
+
+    async fn on_raw(&mut self, room_id: Option<&str>, content: &str) {
+        let Some(room) = room_id else {
+            return;
+        };
+        if content.starts_with("|tournament|end|") || content.starts_with("|tournament|create|") {
+            self.finals_announced.remove(room);
+            return;
+        }
+        let Some(update) = content
+            .strip_prefix("|tournament|update|")
+            .and_then(|json| serde_json::from_str::<Value>(json).ok())
+        else {
+            return;
+        };
+        if let Some(teams) = finalists(&update)
+            && self.finals_announced.insert(room.to_string())
+        {
+            self.post(format!(
+                "The tournament in {room} is down to its final: {} vs. {}",
+                teams[0], teams[1]
+            ));
+        }
+    }
+
+    async fn on_battle_ended(&mut self, room_id: &str, outcome: &BattleOutcome) {
+        let Some(me) = outcome.battle.player_by_name(&self.config.username) else {
+            // Spectating, not playing
+            return;
+        };
+        let opponent = outcome
+            .battle
+            .players
+            .iter()
+            .find(|p| p.player != me.player)
+            .map_or("?", |p| p.username.as_str());
+        let result = if outcome.tie {
+            "Tied"
+        } else if outcome.we_won {
+            "Won"
+        } else {
+            "Lost"
+        };
+        self.post(format!(
+            "{result} against {opponent}: https://play.pokemonshowdown.com/{room_id}"
+        ));
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = Config::load()?;
+    let mut client = KazamClient::connect(SHOWDOWN_URL).await?;
+    let mut bridge = Bridge::new(client.handle(), config);
+    client.run(&mut bridge).await
+}
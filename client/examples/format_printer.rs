@@ -2,7 +2,7 @@ use std::env;
 use std::process;
 
 use anyhow::Result;
-use kazam_client::{FormatSection, KazamClient, KazamHandler, SHOWDOWN_URL};
+use kazam_client::prelude::*;
 
 struct FormatPrinter;
 
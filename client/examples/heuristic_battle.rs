@@ -0,0 +1,140 @@
+//! Heuristic Battle Bot Example
+//!
+//! This bot joins unrated random battles, tracks each one with kazam-battle's
+//! TrackedBattle and plays the best action from `query::evaluate_actions`.
+//! Set PS_USERNAME and PS_PASSWORD to log in.
+
+use anyhow::Result;
+use kazam_battle::prelude::*;
+use kazam_battle::query::{EvalWeights, evaluate_actions};
+use kazam_client::prelude::*;
+use std::collections::HashMap;
+use std::env;
+
+const FORMAT: &str = "gen9randombattle";
+
+// Public so tests/scripted_heuristic_battle.rs can drive it
+pub struct HeuristicBot {
+    pub handle: KazamHandle,
+    pub weights: EvalWeights,
+    pub battles: HashMap<String, TrackedBattle>,
+}
+
+impl HeuristicBot {
+    fn make_choice(&self, room_id: &str, request: &BattleRequest) {
+        let rqid = request.rqid;
+        if request.wait {
+            return;
+        }
+
+        // Keep the team order at preview
+        if request.team_preview {
+            let team_size = request.side.as_ref().map(|s| s.pokemon.len()).unwrap_or(6);
+            let order: String = (1..=team_size).map(|i| i.to_string()).collect();
+            self.handle
+                .choose(room_id, &format!("team {}", order), rqid)
+                .ok();
+            return;
+        }
+
+        let Some(battle) = self.battles.get(room_id) else {
+            return;
+        };
+        let actions = evaluate_actions(battle, request, &self.weights);
+        for scored in actions.iter().take(3) {
+            println!(
+                "[{}]   {:<10} {:>6.2}  {:?}",
+                room_id,
+                scored.action.choice(),
+                scored.score,
+                scored.breakdown
+            );
+        }
+        if let Some(best) = actions.first() {
+            let choice = best.action.choice();
+            println!("[{}] Choosing: {}", room_id, choice);
+            self.handle.choose(room_id, &choice, rqid).ok();
+        }
+    }
+
+    fn finish(&mut self, room_id: &str) {
+        self.battles.remove(room_id);
+        println!("\nSearching for another battle...");
+        self.handle.search(FORMAT).ok();
+    }
+}
+
+impl KazamHandler for HeuristicBot {
+    async fn on_challstr(&mut self, challstr: &str) {
+        let username = env::var("PS_USERNAME").expect("PS_USERNAME not set");
+        let password = env::var("PS_PASSWORD").unwrap_or_default();
+        println!("Logging in as {}...", username);
+        self.handle
+            .login(&username, &password, challstr)
+            .await
+            .expect("Failed to login");
+    }
+
+    async fn on_logged_in(&mut self, user: &User) {
+        println!("Logged in as: {}{}", user.rank, user.username);
+        println!("Searching for a random battle...");
+        self.handle.search(FORMAT).expect("Failed to search");
+    }
+
+    async fn on_init(&mut self, room_id: &str, room_type: &RoomType) {
+        if *room_type == RoomType::Battle {
+            println!("Joined battle: {}", room_id);
+            self.battles
+                .insert(room_id.to_string(), TrackedBattle::new());
+        }
+    }
+
+    async fn on_request(&mut self, room_id: &str, request: &BattleRequest) {
+        self.battles
+            .entry(room_id.to_string())
+            .or_default()
+            .update_from_request(request);
+        self.make_choice(room_id, request);
+    }
+
+    async fn on_battle_message(&mut self, room_id: Option<&str>, message: ServerMessage) {
+        if let Some(rid) = room_id {
+            self.battles
+                .entry(rid.to_string())
+                .or_default()
+                .update(&message);
+        }
+    }
+
+    async fn on_win(&mut self, room_id: &str, winner: &str) {
+        println!("\n{} won the battle!", winner);
+        self.finish(room_id);
+    }
+
+    async fn on_tie(&mut self, room_id: &str) {
+        println!("\nThe battle ended in a tie!");
+        self.finish(room_id);
+    }
+
+    async fn on_popup(&mut self, message: &str) {
+        println!("Popup: {}", message.replace("||", "\n"));
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    println!("Heuristic Battle Bot");
+    println!("====================");
+    println!("Connecting to Pokemon Showdown...");
+
+    let mut client = KazamClient::connect(SHOWDOWN_URL).await?;
+    println!("Connected!");
+
+    let mut handler = HeuristicBot {
+        handle: client.handle(),
+        weights: EvalWeights::default(),
+        battles: HashMap::new(),
+    };
+
+    client.run(&mut handler).await
+}
@@ -4,14 +4,12 @@
 //! It demonstrates how to use the typed battle handlers.
 
 use anyhow::Result;
-use kazam_client::{
-    BattleRequest, HpStatus, KazamClient, KazamHandle, KazamHandler, Pokemon, PokemonDetails,
-    RoomType, SHOWDOWN_URL, User,
-};
+use kazam_client::prelude::*;
 use rand::seq::SliceRandom;
 
-struct RandomBattleBot {
-    handle: KazamHandle,
+// Public so tests/scripted_random_battle.rs can drive it
+pub struct RandomBattleBot {
+    pub handle: KazamHandle,
 }
 
 impl RandomBattleBot {
@@ -36,12 +34,12 @@ impl RandomBattleBot {
         }
 
         // Handle force switch
-        if request.is_force_switch() {
-            if let Some(choice) = self.pick_switch(request) {
-                println!("[{}] Force switch: {}", room_id, choice);
-                self.handle.choose(room_id, &choice, rqid).ok();
-                return;
-            }
+        if request.is_force_switch()
+            && let Some(choice) = self.pick_switch(request)
+        {
+            println!("[{}] Force switch: {}", room_id, choice);
+            self.handle.choose(room_id, &choice, rqid).ok();
+            return;
         }
 
         // Normal turn - pick a random move or switch
@@ -69,11 +67,13 @@ impl RandomBattleBot {
         let mut rng = rand::thread_rng();
 
         if let Some(side) = &request.side {
+            // Revival Blessing picks among the fainted instead
+            let reviving = request.is_reviving();
             let switches: Vec<String> = side
                 .pokemon
                 .iter()
                 .enumerate()
-                .filter(|(_, p)| !p.active && !p.is_fainted())
+                .filter(|(_, p)| p.is_switch_target(reviving))
                 .map(|(i, _)| format!("switch {}", i + 1))
                 .collect();
 
@@ -178,16 +178,13 @@ impl KazamHandler for RandomBattleBot {
         hp_status: Option<&HpStatus>,
         _from: Option<&str>,
     ) {
-        if let Some(hp) = hp_status {
-            if hp.max.is_some() {
-                println!(
-                    "[{}] {} took damage: {}/{}",
-                    room_id,
-                    pokemon.name,
-                    hp.current,
-                    hp.max.unwrap()
-                );
-            }
+        if let Some(hp) = hp_status
+            && let Some(max) = hp.max
+        {
+            println!(
+                "[{}] {} took damage: {}/{}",
+                room_id, pokemon.name, hp.current, max
+            );
         }
     }
 
@@ -0,0 +1,201 @@
+//! Websocket URLs of Showdown servers
+
+use std::fmt;
+
+#[cfg(feature = "resolve")]
+use crate::ConnectError;
+use crate::SHOWDOWN_URL;
+
+/// Websocket URL of a Showdown server, for [`KazamClient::connect`](crate::KazamClient::connect)
+///
+/// Strings convert as they are, so a full `ws://` or `wss://` URL still works.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerAddress {
+    url: String,
+}
+
+impl ServerAddress {
+    /// The main server behind play.pokemonshowdown.com
+    pub fn main() -> Self {
+        Self {
+            url: SHOWDOWN_URL.to_string(),
+        }
+    }
+
+    /// Server at `host`, with an optional port, e.g. `"localhost:8000"`
+    ///
+    /// A scheme or path on `host` is dropped; the websocket is always served
+    /// at `/showdown/websocket`.
+    pub fn from_host(host: &str, tls: bool) -> Self {
+        let host = host.split_once("://").map_or(host, |(_, rest)| rest);
+        let host = host.split('/').next().unwrap_or_default();
+        let scheme = if tls { "wss" } else { "ws" };
+        Self {
+            url: format!("{}://{}/showdown/websocket", scheme, host),
+        }
+    }
+
+    /// Sim server behind a client URL, e.g. `"https://play.example.com/"`
+    ///
+    /// Reads `Config.defaultserver` from the client's `config/config.js`, as
+    /// the official client does. Port 443 means the server speaks TLS.
+    #[cfg(feature = "resolve")]
+    pub async fn from_client_url(url: &str) -> Result<Self, ConnectError> {
+        let lookup = |message: String| ConnectError::Lookup {
+            url: url.to_string(),
+            message,
+        };
+        let config_url = reqwest::Url::parse(url)
+            .and_then(|base| base.join("/config/config.js"))
+            .map_err(|e| ConnectError::InvalidUrl {
+                url: url.to_string(),
+                message: e.to_string(),
+            })?;
+
+        let response = reqwest::get(config_url.clone())
+            .await
+            .map_err(|e| lookup(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(lookup(format!(
+                "{} answered {}",
+                config_url,
+                response.status()
+            )));
+        }
+        let config = response.text().await.map_err(|e| lookup(e.to_string()))?;
+
+        let (host, port) = parse_default_server(&config)
+            .ok_or_else(|| lookup(format!("no Config.defaultserver in {}", config_url)))?;
+        Ok(match port {
+            443 => Self::from_host(&host, true),
+            port => Self::from_host(&format!("{}:{}", host, port), false),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+}
+
+impl From<&str> for ServerAddress {
+    fn from(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
+
+impl From<&String> for ServerAddress {
+    fn from(url: &String) -> Self {
+        Self::from(url.as_str())
+    }
+}
+
+impl From<String> for ServerAddress {
+    fn from(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Host and port from a client config's `Config.defaultserver = {...}`
+#[cfg(feature = "resolve")]
+fn parse_default_server(config: &str) -> Option<(String, u16)> {
+    let block = &config[config.find("defaultserver")?..];
+    let block = &block[block.find('{')? + 1..];
+    let block = &block[..block.find('}')?];
+    let host = field(block, "host")?;
+    let port = field(block, "port")?.parse().ok()?;
+    Some((host.to_string(), port))
+}
+
+/// Unquoted value of `key` in the body of a JS or JSON object literal
+#[cfg(feature = "resolve")]
+fn field<'a>(block: &'a str, key: &str) -> Option<&'a str> {
+    block.split(',').find_map(|entry| {
+        let (name, value) = entry.split_once(':')?;
+        (name.trim().trim_matches(['\'', '"']) == key)
+            .then(|| value.trim().trim_matches(['\'', '"']))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_host() {
+        let cases = [
+            (
+                "localhost:8000",
+                false,
+                "ws://localhost:8000/showdown/websocket",
+            ),
+            (
+                "sim3.psim.us",
+                true,
+                "wss://sim3.psim.us/showdown/websocket",
+            ),
+            (
+                "https://example.com/",
+                true,
+                "wss://example.com/showdown/websocket",
+            ),
+            (
+                "example.com:8000/showdown/123/abcd/websocket",
+                false,
+                "ws://example.com:8000/showdown/websocket",
+            ),
+        ];
+        for (host, tls, expected) in cases {
+            assert_eq!(
+                ServerAddress::from_host(host, tls).as_str(),
+                expected,
+                "{}",
+                host
+            );
+        }
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(ServerAddress::main().as_str(), SHOWDOWN_URL);
+        assert_eq!(ServerAddress::from(SHOWDOWN_URL), ServerAddress::main());
+        let url = "ws://localhost:8000/showdown/websocket".to_string();
+        assert_eq!(ServerAddress::from(&url).to_string(), url);
+        assert_eq!(ServerAddress::from(url.clone()).as_str(), url);
+    }
+
+    #[cfg(feature = "resolve")]
+    #[test]
+    fn test_parse_default_server() {
+        let js = r#"Config.version = "0.11.2";
+Config.defaultserver = {
+	id: 'showdown',
+	host: 'sim3.psim.us',
+	port: 443,
+	httpport: 8000,
+	altport: 80,
+	registered: true
+};"#;
+        assert_eq!(
+            parse_default_server(js),
+            Some(("sim3.psim.us".to_string(), 443))
+        );
+        let json = r#"Config.defaultserver = {"id":"local","host":"localhost","port":8000};"#;
+        assert_eq!(
+            parse_default_server(json),
+            Some(("localhost".to_string(), 8000))
+        );
+        assert_eq!(
+            parse_default_server("Config.defaultserver = {id: 'x'};"),
+            None
+        );
+        assert_eq!(parse_default_server("var config = {};"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Recognizing server-wide restart broadcasts
+//!
+//! Before a restart the server locks down and sends a red broadcast to every
+//! room (as `|raw|`) and to users (as a `/raw` PM from the server). Calling
+//! it off sends a green one.
+
+use std::time::Duration;
+
+/// A restart notice from a server broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerNotice {
+    /// The server will restart; `eta` is set when the broadcast says how soon
+    RestartAnnounced { eta: Option<Duration> },
+    /// A pending restart was called off
+    RestartCanceled,
+}
+
+impl ServerNotice {
+    /// Recognize a restart notice in broadcast HTML
+    pub fn parse(html: &str) -> Option<Self> {
+        if !html.contains("broadcast-") {
+            return None;
+        }
+        let text = strip_tags(html).to_lowercase();
+        if !text.contains("restart") && !text.contains("shutting down") {
+            return None;
+        }
+        if text.contains("cancel") {
+            return Some(Self::RestartCanceled);
+        }
+        if !html.contains("broadcast-red") {
+            return None;
+        }
+        Some(Self::RestartAnnounced {
+            eta: parse_eta(&text),
+        })
+    }
+
+    /// Recognize a notice in a PM, which carries it as a `/raw` or `/html` command
+    pub fn from_pm(message: &str) -> Option<Self> {
+        message
+            .strip_prefix("/raw ")
+            .or_else(|| message.strip_prefix("/html "))
+            .and_then(Self::parse)
+    }
+}
+
+/// Text content of an HTML fragment
+pub(crate) fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Delay from an "in N minutes" phrase; vague ones ("in a few minutes") give None
+fn parse_eta(text: &str) -> Option<Duration> {
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.windows(3).find_map(|window| {
+        let [prefix, amount, unit] = window else {
+            return None;
+        };
+        if *prefix != "in" {
+            return None;
+        }
+        let amount: u64 = amount.parse().ok()?;
+        let seconds = if unit.starts_with("sec") {
+            1
+        } else if unit.starts_with("min") {
+            60
+        } else if unit.starts_with("hour") {
+            3600
+        } else {
+            return None;
+        };
+        Some(Duration::from_secs(amount * seconds))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockdown_broadcast() {
+        let html = r#"<div class="broadcast-red"><b>The server is restarting soon.</b><br />Please finish your battles quickly. No new battles can be started until the server resets in a few minutes.</div>"#;
+        assert_eq!(
+            ServerNotice::parse(html),
+            Some(ServerNotice::RestartAnnounced { eta: None })
+        );
+        assert_eq!(
+            ServerNotice::from_pm(&format!("/raw {}", html)),
+            Some(ServerNotice::RestartAnnounced { eta: None })
+        );
+    }
+
+    #[test]
+    fn test_crash_and_scheduled_broadcasts() {
+        let crash = r#"<div class="broadcast-red"><b>The server needs to restart because of a crash: TypeError: Cannot read properties of undefined</b><br />No new battles can be started until the server is done restarting.</div>"#;
+        assert_eq!(
+            ServerNotice::parse(crash),
+            Some(ServerNotice::RestartAnnounced { eta: None })
+        );
+
+        let scheduled =
+            r#"<div class="broadcast-red"><b>The server will restart in 15 minutes.</b></div>"#;
+        assert_eq!(
+            ServerNotice::parse(scheduled),
+            Some(ServerNotice::RestartAnnounced {
+                eta: Some(Duration::from_secs(15 * 60))
+            })
+        );
+
+        let shutdown =
+            r#"<div class="broadcast-red"><b>The server is shutting down soon.</b></div>"#;
+        assert!(matches!(
+            ServerNotice::parse(shutdown),
+            Some(ServerNotice::RestartAnnounced { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cancel_and_unrelated_broadcasts() {
+        assert_eq!(
+            ServerNotice::parse(
+                r#"<div class="broadcast-green"><b>The server restart was canceled.</b></div>"#
+            ),
+            Some(ServerNotice::RestartCanceled)
+        );
+        assert_eq!(
+            ServerNotice::parse(
+                r#"<div class="broadcast-red"><b>Moderated chat was set to +!</b></div>"#
+            ),
+            None
+        );
+        assert_eq!(
+            ServerNotice::parse(
+                r#"<div class="broadcast-blue">Restart your engines: the tournament begins!</div>"#
+            ),
+            None
+        );
+        assert_eq!(ServerNotice::from_pm("the server is restarting soon"), None);
+    }
+}
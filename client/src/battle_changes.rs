@@ -0,0 +1,104 @@
+//! Which parts of a room's [`BattleInfo`](kazam_protocol::BattleInfo) a frame changed
+//!
+//! Each message that updates a battle's header records what it touched, and
+//! the frame reports the union once to
+//! [`KazamHandler::on_battle_info_changed`](crate::KazamHandler::on_battle_info_changed),
+//! so a GUI or logger doesn't have to poll and compare.
+
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+/// Set of changes to a battle's info, combined with `|`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BattleInfoChanges(u8);
+
+impl BattleInfoChanges {
+    /// A |player| line added a player
+    pub const PLAYER_ADDED: Self = Self(1);
+    /// A player's team size or name changed
+    pub const PLAYER_UPDATED: Self = Self(1 << 1);
+    /// Game type, generation, tier or rules
+    pub const FORMAT_SET: Self = Self(1 << 2);
+    pub const RATED_SET: Self = Self(1 << 3);
+    /// A |poke| line added to team preview
+    pub const PREVIEW_UPDATED: Self = Self(1 << 4);
+    pub const STARTED: Self = Self(1 << 5);
+    pub const TURN_CHANGED: Self = Self(1 << 6);
+    /// A win or tie
+    pub const ENDED: Self = Self(1 << 7);
+
+    const NAMES: [(Self, &str); 8] = [
+        (Self::PLAYER_ADDED, "PLAYER_ADDED"),
+        (Self::PLAYER_UPDATED, "PLAYER_UPDATED"),
+        (Self::FORMAT_SET, "FORMAT_SET"),
+        (Self::RATED_SET, "RATED_SET"),
+        (Self::PREVIEW_UPDATED, "PREVIEW_UPDATED"),
+        (Self::STARTED, "STARTED"),
+        (Self::TURN_CHANGED, "TURN_CHANGED"),
+        (Self::ENDED, "ENDED"),
+    ];
+
+    /// No changes
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every change in `other` is in this set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for BattleInfoChanges {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for BattleInfoChanges {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl fmt::Debug for BattleInfoChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Self::NAMES
+            .iter()
+            .filter(|(change, _)| self.contains(*change))
+            .map(|(_, name)| *name);
+        write!(f, "BattleInfoChanges(")?;
+        for (i, name) in names.enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{name}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_union_and_debug() {
+        let mut changes = BattleInfoChanges::empty();
+        assert!(changes.is_empty());
+        changes |= BattleInfoChanges::TURN_CHANGED;
+        changes |= BattleInfoChanges::PLAYER_ADDED | BattleInfoChanges::TURN_CHANGED;
+        assert!(changes.contains(BattleInfoChanges::PLAYER_ADDED));
+        assert!(!changes.contains(BattleInfoChanges::PLAYER_ADDED | BattleInfoChanges::ENDED));
+        assert_eq!(
+            format!("{changes:?}"),
+            "BattleInfoChanges(PLAYER_ADDED | TURN_CHANGED)"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! End-to-end smoke test against the live server
+//!
+//! ```text
+//! PS_USERNAME=... PS_PASSWORD=... cargo run --bin smoke --features smoke-bot
+//! ```
+//!
+//! Logs in, plays one gen9randombattle with the heuristic evaluator and checks
+//! that every request got a choice, that the tracker saw no inconsistencies
+//! and that the battle ended in a win or tie. Prints a summary and exits
+//! non-zero on any violation.
+//!
+//! SMOKE_MAX_TURNS (default 300) and SMOKE_MAX_SECS (default 1200) bound the
+//! run; past either limit the bot forfeits and exits. SMOKE_SERVER overrides
+//! the websocket URL.
+
+use std::env;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use kazam_battle::prelude::*;
+use kazam_battle::query::{EvalWeights, evaluate_actions};
+use kazam_client::prelude::*;
+
+const FORMAT: &str = "gen9randombattle";
+
+/// Time left for the forfeit to end the battle before the client is stopped
+const FORFEIT_GRACE: Duration = Duration::from_secs(15);
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// What happened, and what went wrong
+#[derive(Debug, Default)]
+struct Summary {
+    room: Option<String>,
+    turns: u32,
+    requests: u32,
+    choices: u32,
+    result: Option<String>,
+    inconsistencies: Vec<String>,
+    violations: Vec<String>,
+}
+
+impl Summary {
+    fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn print(&self) {
+        println!("smoke: {}", if self.passed() { "PASS" } else { "FAIL" });
+        println!("  room:            {}", self.room.as_deref().unwrap_or("-"));
+        println!(
+            "  result:          {}",
+            self.result.as_deref().unwrap_or("unfinished")
+        );
+        println!("  turns:           {}", self.turns);
+        println!(
+            "  requests:        {} ({} answered)",
+            self.requests, self.choices
+        );
+        println!("  inconsistencies: {}", self.inconsistencies.len());
+        for inconsistency in &self.inconsistencies {
+            println!("    - {}", inconsistency);
+        }
+        println!("  violations:      {}", self.violations.len());
+        for violation in &self.violations {
+            println!("    - {}", violation);
+        }
+    }
+}
+
+struct SmokeBot {
+    handle: KazamHandle,
+    weights: EvalWeights,
+    max_turns: u32,
+    battle: TrackedBattle,
+    summary: Summary,
+}
+
+impl SmokeBot {
+    fn is_ours(&self, room_id: &str) -> bool {
+        self.summary.room.as_deref() == Some(room_id)
+    }
+
+    fn fail(&mut self, violation: String) {
+        eprintln!("violation: {}", violation);
+        self.summary.violations.push(violation);
+    }
+
+    /// Fail and stop without waiting for a battle
+    fn abort(&mut self, violation: String) {
+        self.fail(violation);
+        self.handle.shutdown();
+    }
+
+    fn choice_for(&self, request: &BattleRequest) -> String {
+        if request.team_preview {
+            return "default".to_string();
+        }
+        evaluate_actions(&self.battle, request, &self.weights)
+            .first()
+            .map(|best| best.action.choice())
+            // Nothing scored: let the server pick
+            .unwrap_or_else(|| "default".to_string())
+    }
+}
+
+impl KazamHandler for SmokeBot {
+    async fn on_challstr(&mut self, challstr: &str) {
+        let Ok(username) = env::var("PS_USERNAME") else {
+            self.abort("PS_USERNAME not set".to_string());
+            return;
+        };
+        let password = env::var("PS_PASSWORD").unwrap_or_default();
+        if let Err(e) = self.handle.login(&username, &password, challstr).await {
+            self.abort(format!("login failed: {}", e));
+        }
+    }
+
+    async fn on_name_taken(&mut self, username: &str, message: &str) {
+        self.abort(format!("name {} rejected: {}", username, message));
+    }
+
+    async fn on_logged_in(&mut self, user: &User) {
+        println!("Logged in as {}, searching {}", user.username, FORMAT);
+        if let Err(e) = self.handle.search(FORMAT) {
+            self.abort(format!("search failed: {}", e));
+        }
+    }
+
+    async fn on_popup(&mut self, message: &str) {
+        println!("popup: {}", message.replace("||", "\n"));
+    }
+
+    async fn on_init(&mut self, room_id: &str, room_type: &RoomType) {
+        if *room_type == RoomType::Battle && self.summary.room.is_none() {
+            println!("Joined {}", room_id);
+            self.summary.room = Some(room_id.to_string());
+        }
+    }
+
+    async fn on_request(&mut self, room_id: &str, request: &BattleRequest) {
+        if !self.is_ours(room_id) {
+            return;
+        }
+        self.battle.update_from_request(request);
+        if request.wait {
+            return;
+        }
+        self.summary.requests += 1;
+        let choice = self.choice_for(request);
+        match self.handle.choose(room_id, &choice, request.rqid) {
+            Ok(()) => self.summary.choices += 1,
+            Err(e) => self.fail(format!("no choice for rqid {:?}: {}", request.rqid, e)),
+        }
+    }
+
+    async fn on_raw(&mut self, room_id: Option<&str>, content: &str) {
+        if let Some(rid) = room_id
+            && self.is_ours(rid)
+            && let Some(error) = content.strip_prefix("|error|")
+            && error.starts_with("[Invalid choice]")
+        {
+            self.fail(format!("server rejected a choice: {}", error));
+        }
+    }
+
+    async fn on_battle_message(&mut self, room_id: Option<&str>, message: ServerMessage) {
+        if room_id.is_some_and(|rid| self.is_ours(rid)) {
+            self.battle.update(&message);
+        }
+    }
+
+    async fn on_turn(&mut self, room_id: &str, turn: u32) {
+        if !self.is_ours(room_id) {
+            return;
+        }
+        self.summary.turns = turn;
+        if turn == self.max_turns + 1 {
+            self.fail(format!(
+                "turn limit of {} reached, forfeiting",
+                self.max_turns
+            ));
+            self.handle.forfeit(room_id).ok();
+        }
+    }
+
+    async fn on_win(&mut self, room_id: &str, winner: &str) {
+        if self.is_ours(room_id) {
+            self.summary.result = Some(format!("won by {}", winner));
+        }
+    }
+
+    async fn on_tie(&mut self, room_id: &str) {
+        if self.is_ours(room_id) {
+            self.summary.result = Some("tie".to_string());
+        }
+    }
+
+    async fn on_battle_ended(&mut self, room_id: &str, _outcome: &BattleOutcome) {
+        if self.is_ours(room_id) {
+            self.handle.shutdown();
+        }
+    }
+}
+
+/// Forfeit every battle once the wall time runs out, then stop the client
+async fn watchdog(handle: KazamHandle, max_time: Duration, timed_out: Arc<AtomicBool>) {
+    tokio::time::sleep(max_time).await;
+    timed_out.store(true, Ordering::Relaxed);
+    for room in handle.rooms() {
+        if handle.in_battle(&room) {
+            handle.forfeit(&room).ok();
+        }
+    }
+    tokio::time::sleep(FORFEIT_GRACE).await;
+    handle.shutdown();
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let max_turns = env_or("SMOKE_MAX_TURNS", 300);
+    let max_time = Duration::from_secs(env_or("SMOKE_MAX_SECS", 1200));
+    let url = env::var("SMOKE_SERVER").unwrap_or_else(|_| SHOWDOWN_URL.to_string());
+
+    let mut client = match KazamClient::connect(&url).await {
+        Ok(client) => client,
+        Err(e) => {
+            println!("smoke: FAIL");
+            println!("  could not connect to {}: {}", url, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut bot = SmokeBot {
+        handle: client.handle(),
+        weights: EvalWeights::default(),
+        max_turns,
+        battle: TrackedBattle::new(),
+        summary: Summary::default(),
+    };
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let watchdog = tokio::spawn(watchdog(client.handle(), max_time, timed_out.clone()));
+
+    if let Err(e) = client.run(&mut bot).await {
+        bot.fail(format!("client error: {}", e));
+    }
+    watchdog.abort();
+    if timed_out.load(Ordering::Relaxed) {
+        bot.fail(format!("wall time limit of {:?} reached", max_time));
+    }
+
+    bot.summary.inconsistencies = bot
+        .battle
+        .inconsistencies()
+        .iter()
+        .map(|i| i.to_string())
+        .collect();
+    if bot.summary.room.is_some() && bot.summary.result.is_none() {
+        bot.fail("battle did not reach |win| or |tie|".to_string());
+    } else if bot.summary.room.is_none() && bot.summary.passed() {
+        bot.fail("no battle started".to_string());
+    }
+    if !bot.summary.inconsistencies.is_empty() {
+        let count = bot.summary.inconsistencies.len();
+        bot.fail(format!("tracker reported {} inconsistencies", count));
+    }
+
+    bot.summary.print();
+    if bot.summary.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
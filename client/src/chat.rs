@@ -0,0 +1,289 @@
+//! Outgoing chat, paced per room to stay under the server's flood limits
+//!
+//! Chat lines wait in a queue per room and go out one per interval in each
+//! room, so a long reply in one room doesn't hold up another. Multi-line
+//! messages become one line each, and lines over the server's length limit are
+//! split at character boundaries (on whitespace where possible) with a
+//! continuation marker on both sides of each split. With coalescing on, short
+//! lines waiting in the same room go out together, joined by ` | `. Commands
+//! (`/` or `!`) are never split or joined.
+//!
+//! Battle choices don't come through here, so they never wait behind chat.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use kazam_protocol::{ClientCommand, ClientMessage};
+use tokio::time::Instant;
+
+/// Default spacing between chat messages in the same room
+pub const DEFAULT_CHAT_INTERVAL: Duration = Duration::from_millis(600);
+
+/// Longest chat message the server accepts, in UTF-16 code units
+pub const MAX_CHAT_LENGTH: usize = 300;
+
+/// Marks where a long line was split, at the end of one part and the start of the next
+const CONTINUATION: &str = "…";
+
+/// Joins coalesced lines
+const SEPARATOR: &str = " | ";
+
+/// Length as the server counts it
+fn length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Commands and `!` broadcasts, which only work as sent; `//` escapes a slash
+fn is_command(line: &str) -> bool {
+    (line.starts_with('/') && !line.starts_with("//")) || line.starts_with('!')
+}
+
+/// Split `line` into parts of at most `limit` UTF-16 units, markers included
+///
+/// Commands and lines that fit come back whole.
+pub(crate) fn chunk(line: &str, limit: usize) -> Vec<String> {
+    if length(line) <= limit || is_command(line) {
+        return vec![line.to_string()];
+    }
+    let marker = length(CONTINUATION);
+    let mut parts = Vec::new();
+    let mut rest = line;
+    loop {
+        let prefix = if parts.is_empty() { "" } else { CONTINUATION };
+        let budget = limit - length(prefix);
+        if length(rest) <= budget {
+            parts.push(format!("{prefix}{rest}"));
+            return parts;
+        }
+
+        // The longest run of whole characters that leaves room for the marker
+        let budget = budget - marker;
+        let (mut units, mut end) = (0, 0);
+        for (index, c) in rest.char_indices() {
+            if units + c.len_utf16() > budget {
+                break;
+            }
+            units += c.len_utf16();
+            end = index + c.len_utf8();
+        }
+        // Break on whitespace unless that leaves the part less than half full
+        let cut = rest[..end]
+            .rfind(char::is_whitespace)
+            .filter(|&index| index > 0 && index >= end / 2)
+            .unwrap_or(end);
+        let (head, tail) = rest.split_at(cut);
+        parts.push(format!("{prefix}{}{CONTINUATION}", head.trim_end()));
+        rest = tail.trim_start();
+    }
+}
+
+#[derive(Debug, Default)]
+struct RoomChat {
+    lines: VecDeque<String>,
+    last_sent: Option<Instant>,
+}
+
+/// Chat waiting to be sent, per room
+#[derive(Debug)]
+pub(crate) struct ChatQueue {
+    rooms: HashMap<String, RoomChat>,
+    interval: Duration,
+    coalesce: bool,
+}
+
+impl ChatQueue {
+    pub fn new() -> Self {
+        Self {
+            rooms: HashMap::new(),
+            interval: DEFAULT_CHAT_INTERVAL,
+            coalesce: false,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub fn set_coalesce(&mut self, coalesce: bool) {
+        self.coalesce = coalesce;
+    }
+
+    /// Queue a message for `room`, one line or chunk at a time
+    pub fn push(&mut self, room: &str, message: &str) {
+        let chat = self.rooms.entry(room.to_string()).or_default();
+        for line in message.lines().filter(|line| !line.trim().is_empty()) {
+            chat.lines.extend(chunk(line, MAX_CHAT_LENGTH));
+        }
+    }
+
+    /// Messages waiting in `room`
+    pub fn depth(&self, room: &str) -> usize {
+        self.rooms.get(room).map_or(0, |chat| chat.lines.len())
+    }
+
+    /// When a room may send next, None for a room that hasn't sent yet
+    fn room_due(&self, chat: &RoomChat) -> Option<Instant> {
+        chat.last_sent.map(|last| last + self.interval)
+    }
+
+    /// When the next message may go out, None if nothing is queued
+    pub fn next_due(&self) -> Option<Instant> {
+        self.rooms
+            .values()
+            .filter(|chat| !chat.lines.is_empty())
+            .map(|chat| self.room_due(chat).unwrap_or_else(Instant::now))
+            .min()
+    }
+
+    /// Take the next message if one is due, from the room that has waited longest
+    pub fn pop_due(&mut self, now: Instant) -> Option<ClientMessage> {
+        let room = self
+            .rooms
+            .iter()
+            .filter(|(_, chat)| !chat.lines.is_empty())
+            .map(|(room, chat)| (self.room_due(chat), room))
+            .filter(|(due, _)| due.is_none_or(|due| due <= now))
+            .min()?
+            .1
+            .clone();
+        let coalesce = self.coalesce;
+        let chat = self.rooms.get_mut(&room)?;
+        let mut message = chat.lines.pop_front()?;
+        while coalesce && !is_command(&message) {
+            let Some(next) = chat.lines.front().filter(|next| {
+                !is_command(next)
+                    && length(&message) + length(SEPARATOR) + length(next) <= MAX_CHAT_LENGTH
+            }) else {
+                break;
+            };
+            message = format!("{message}{SEPARATOR}{next}");
+            chat.lines.pop_front();
+        }
+        chat.last_sent = Some(now);
+        Some(chat_message(&room, message))
+    }
+
+    /// Drop everything waiting in `room`, returns how many messages were dropped
+    pub fn clear(&mut self, room: &str) -> usize {
+        self.rooms
+            .get_mut(room)
+            .map_or(0, |chat| std::mem::take(&mut chat.lines).len())
+    }
+
+    /// Take everything waiting in `room` to send right away
+    pub fn take(&mut self, room: &str) -> Vec<ClientMessage> {
+        let lines = self
+            .rooms
+            .get_mut(room)
+            .map(|chat| std::mem::take(&mut chat.lines))
+            .unwrap_or_default();
+        lines
+            .into_iter()
+            .map(|line| chat_message(room, line))
+            .collect()
+    }
+
+    /// Take everything waiting in every room
+    pub fn take_all(&mut self) -> Vec<ClientMessage> {
+        let mut rooms: Vec<String> = self.rooms.keys().cloned().collect();
+        rooms.sort();
+        rooms.iter().flat_map(|room| self.take(room)).collect()
+    }
+}
+
+fn chat_message(room: &str, line: String) -> ClientMessage {
+    ClientMessage {
+        room_id: Some(room.to_string()),
+        command: ClientCommand::Chat(line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(message: ClientMessage) -> String {
+        match message.command {
+            ClientCommand::Chat(line) => line,
+            other => panic!("not chat: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_chunks_split_on_whitespace_under_the_limit() {
+        let line = "word ".repeat(100);
+        let parts = chunk(line.trim_end(), MAX_CHAT_LENGTH);
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| length(part) <= MAX_CHAT_LENGTH));
+        assert!(parts[0].ends_with("word…"));
+        assert!(parts[1].starts_with("…word"));
+        assert_eq!(
+            parts.concat().replace('…', " ").split_whitespace().count(),
+            100
+        );
+
+        // Commands and lines that fit are left alone
+        let command = format!("/code {}", "x".repeat(400));
+        assert_eq!(chunk(&command, MAX_CHAT_LENGTH), [command]);
+        assert_eq!(chunk("hello", MAX_CHAT_LENGTH), ["hello"]);
+    }
+
+    #[test]
+    fn test_chunks_keep_multibyte_characters_whole() {
+        // Each emoji is two UTF-16 units and four bytes; each é is one and two
+        let line = "😀é".repeat(120);
+        let parts = chunk(&line, MAX_CHAT_LENGTH);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            assert!(length(part) <= MAX_CHAT_LENGTH, "{} units", length(part));
+        }
+        // 299 units leave room for 99 pairs and an emoji before the marker
+        assert_eq!(length(&parts[0]), 300);
+        assert!(parts[0].ends_with("😀…"));
+        assert!(parts[1].starts_with("…é😀"));
+        let joined = parts.concat().replace('…', "");
+        assert_eq!(joined, line);
+    }
+
+    #[test]
+    fn test_rooms_paced_separately() {
+        let mut queue = ChatQueue::new();
+        queue.push("lobby", "one\ntwo\n\nthree");
+        queue.push("help", "hi");
+        assert_eq!(queue.depth("lobby"), 3);
+
+        let start = Instant::now();
+        let first = queue.pop_due(start).unwrap();
+        let second = queue.pop_due(start).unwrap();
+        let mut rooms = [first.room_id.unwrap(), second.room_id.unwrap()];
+        rooms.sort();
+        assert_eq!(rooms, ["help", "lobby"]);
+        // lobby waits out its interval
+        assert!(queue.pop_due(start).is_none());
+        assert_eq!(queue.next_due(), Some(start + DEFAULT_CHAT_INTERVAL));
+        assert_eq!(
+            text(queue.pop_due(start + DEFAULT_CHAT_INTERVAL).unwrap()),
+            "two"
+        );
+        assert_eq!(queue.clear("lobby"), 1);
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn test_coalesce_short_lines() {
+        let mut queue = ChatQueue::new();
+        queue.set_coalesce(true);
+        queue.push("lobby", "a\nb\n/me waves\nc");
+        queue.push("lobby", &"d".repeat(299));
+        let now = Instant::now();
+        let mut sent = Vec::new();
+        for step in 0..4 {
+            sent.push(text(
+                queue.pop_due(now + DEFAULT_CHAT_INTERVAL * step).unwrap(),
+            ));
+        }
+        assert_eq!(sent[..3], ["a | b", "/me waves", "c"]);
+        assert_eq!(sent[3].len(), 299);
+        assert_eq!(queue.depth("lobby"), 0);
+    }
+}
@@ -1,11 +1,19 @@
 use anyhow::{Context, Result};
-use futures_util::{SinkExt, StreamExt};
-use kazam_protocol::{ServerFrame, parse_server_frame};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use kazam_protocol::{ClientMessage, ServerFrame, parse_server_frame};
 use std::time::Duration;
 
 use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::http::{StatusCode, Uri};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
 
+use crate::ConnectError;
+use crate::source::MessageSource;
+
+/// Backoff between reconnect attempts
+#[derive(Debug, Clone)]
 pub struct ReconnectPolicy {
     pub max_attempts: Option<usize>,
     pub initial_delay: Duration,
@@ -24,29 +32,192 @@ impl Default for ReconnectPolicy {
     }
 }
 
+/// Liveness watchdog settings for detecting silently dropped connections
+#[derive(Debug, Clone)]
+pub struct KeepaliveConfig {
+    /// How long the connection may go without receiving anything before we ping
+    pub idle_timeout: Duration,
+    /// How long to wait for any reply to that ping before giving up
+    pub pong_timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(5 * 60),
+            pong_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Tracks when we last heard from the server and whether a ping is outstanding
+struct Keepalive {
+    config: KeepaliveConfig,
+    last_seen: Instant,
+    ping_sent: Option<Instant>,
+}
+
+impl Keepalive {
+    fn new(config: KeepaliveConfig) -> Self {
+        Self {
+            config,
+            last_seen: Instant::now(),
+            ping_sent: None,
+        }
+    }
+
+    fn record_activity(&mut self) {
+        self.last_seen = Instant::now();
+        self.ping_sent = None;
+    }
+
+    fn deadline(&self) -> Instant {
+        match self.ping_sent {
+            Some(sent) => sent + self.config.pong_timeout,
+            None => self.last_seen + self.config.idle_timeout,
+        }
+    }
+}
+
+/// Wait for the next websocket message, pinging the server when it goes quiet.
+///
+/// Returns an error if a ping goes unanswered for the configured grace period.
+async fn next_live<S>(
+    stream: &mut S,
+    keepalive: &mut Keepalive,
+) -> Result<Option<Result<Message, tungstenite::Error>>>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    loop {
+        match tokio::time::timeout_at(keepalive.deadline(), stream.next()).await {
+            Ok(message) => {
+                keepalive.record_activity();
+                return Ok(message);
+            }
+            Err(_) if keepalive.ping_sent.is_none() => {
+                tracing::debug!("Connection idle, sending keepalive ping");
+                keepalive.ping_sent = Some(Instant::now());
+                stream
+                    .send(Message::Ping(Vec::new()))
+                    .await
+                    .context("Failed to send keepalive ping")?;
+            }
+            Err(_) => {
+                anyhow::bail!(
+                    "Connection stalled: no reply to keepalive ping within {:?}",
+                    keepalive.config.pong_timeout
+                );
+            }
+        }
+    }
+}
+
+/// Look up the host of `url` before connecting, so DNS failures are reported as such
+async fn resolve_host(url: &str) -> Result<String, ConnectError> {
+    let invalid = |message: String| ConnectError::InvalidUrl {
+        url: url.to_string(),
+        message,
+    };
+    let uri: Uri = url.parse().map_err(|e| invalid(format!("{}", e)))?;
+    let host = uri
+        .host()
+        .ok_or_else(|| invalid("no host".to_string()))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("wss") => 443,
+        _ => 80,
+    });
+    let dns = |message: String| ConnectError::Dns {
+        host: host.to_string(),
+        message,
+    };
+    let mut addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| dns(e.to_string()))?;
+    if addrs.next().is_none() {
+        return Err(dns("no addresses".to_string()));
+    }
+    Ok(host.to_string())
+}
+
+/// Explain why the websocket handshake with `url` failed
+fn handshake_error(url: &str, host: &str, error: tungstenite::Error) -> ConnectError {
+    let url = url.to_string();
+    match error {
+        tungstenite::Error::Http(response) if response.status() == StatusCode::NOT_FOUND => {
+            ConnectError::NotFound { url }
+        }
+        tungstenite::Error::Http(response) => ConnectError::NotWebsocket {
+            url,
+            reason: format!("the server answered HTTP {}", response.status()),
+        },
+        tungstenite::Error::Protocol(e) => ConnectError::NotWebsocket {
+            url,
+            reason: e.to_string(),
+        },
+        tungstenite::Error::Tls(e) => ConnectError::Tls {
+            host: host.to_string(),
+            message: e.to_string(),
+        },
+        // rustls fails the handshake with invalid data on the stream
+        tungstenite::Error::Io(e)
+            if e.kind() == std::io::ErrorKind::InvalidData && url.starts_with("wss:") =>
+        {
+            ConnectError::Tls {
+                host: host.to_string(),
+                message: e.to_string(),
+            }
+        }
+        tungstenite::Error::Url(e) => ConnectError::InvalidUrl {
+            url,
+            message: e.to_string(),
+        },
+        e => ConnectError::Unreachable {
+            url,
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Live websocket connection to a Showdown server
+///
+/// Reconnects with backoff when the socket drops and pings the server when
+/// it goes quiet (see [`KeepaliveConfig`]).
 pub struct Connection {
     ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
     url: String,
     reconnect_policy: ReconnectPolicy,
+    keepalive: Keepalive,
+    /// Set by a successful reconnect until the client picks it up
+    reconnected: bool,
 }
 
 impl Connection {
-    pub async fn connect(url: String, policy: ReconnectPolicy) -> Result<Self> {
-        let ws_stream = Self::establish_connection(&url)
-            .await
-            .with_context(|| format!("Failed to connect to {}", url))?;
+    pub(crate) async fn connect(url: String, policy: ReconnectPolicy) -> Result<Self> {
+        let ws_stream = Self::establish_connection(&url).await?;
 
         Ok(Self {
             ws_stream,
             url,
             reconnect_policy: policy,
+            keepalive: Keepalive::new(KeepaliveConfig::default()),
+            reconnected: false,
         })
     }
 
+    pub(crate) fn set_keepalive(&mut self, config: KeepaliveConfig) {
+        self.keepalive = Keepalive::new(config);
+    }
+
     async fn establish_connection(url: &str) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let host = resolve_host(url).await?;
         let (ws_stream, _) = connect_async(url)
             .await
-            .with_context(|| "WebSocket handshake failed")?;
+            .map_err(|e| handshake_error(url, &host, e))?;
         Ok(ws_stream)
     }
 
@@ -65,6 +236,8 @@ impl Connection {
             match Self::establish_connection(&self.url).await {
                 Ok(ws_stream) => {
                     self.ws_stream = ws_stream;
+                    self.keepalive.record_activity();
+                    self.reconnected = true;
                     return Ok(());
                 }
                 Err(e) => {
@@ -84,9 +257,20 @@ impl Connection {
         }
     }
 
-    pub async fn recv(&mut self) -> Result<ServerFrame> {
+    async fn recv(&mut self) -> Result<ServerFrame> {
         loop {
-            match self.ws_stream.next().await {
+            let message = match next_live(&mut self.ws_stream, &mut self.keepalive).await {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Connection stalled, attempting reconnect");
+                    self.reconnect()
+                        .await
+                        .context("Connection stalled and reconnection failed")?;
+                    continue;
+                }
+            };
+
+            match message {
                 Some(Ok(Message::Text(text))) => {
                     return parse_server_frame(&text).context("Failed to parse server frame");
                 }
@@ -113,7 +297,7 @@ impl Connection {
         }
     }
 
-    pub async fn send(&mut self, message: String) -> Result<()> {
+    async fn send_text(&mut self, message: String) -> Result<()> {
         self.ws_stream
             .send(Message::Text(message))
             .await
@@ -121,3 +305,117 @@ impl Connection {
         Ok(())
     }
 }
+
+impl MessageSource for Connection {
+    /// Never returns None; a dropped socket is reconnected or reported as an error
+    async fn next_frame(&mut self) -> Result<Option<ServerFrame>> {
+        self.recv().await.map(Some)
+    }
+
+    async fn send(&mut self, message: ClientMessage) -> Result<()> {
+        self.send_text(message.to_wire_format()).await
+    }
+
+    fn take_reconnected(&mut self) -> bool {
+        std::mem::take(&mut self.reconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, duplex};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    async fn socket_pair() -> (WebSocketStream<DuplexStream>, WebSocketStream<DuplexStream>) {
+        let (client, server) = duplex(1024);
+        (
+            WebSocketStream::from_raw_socket(client, Role::Client, None).await,
+            WebSocketStream::from_raw_socket(server, Role::Server, None).await,
+        )
+    }
+
+    fn config() -> KeepaliveConfig {
+        KeepaliveConfig {
+            idle_timeout: Duration::from_secs(60),
+            pong_timeout: Duration::from_secs(10),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_stream_is_reported() {
+        // The server half is never polled, so our ping goes unanswered
+        let (mut client, _server) = socket_pair().await;
+        let mut keepalive = Keepalive::new(config());
+        let start = Instant::now();
+
+        let result = next_live(&mut client, &mut keepalive).await;
+
+        assert!(result.is_err());
+        assert_eq!(start.elapsed(), Duration::from_secs(70));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_answered_ping_keeps_connection_alive() {
+        let (mut client, mut server) = socket_pair().await;
+        let mut keepalive = Keepalive::new(config());
+
+        // A responsive server replies to pings and then sends a message much later
+        tokio::spawn(async move {
+            let ping = server.next().await;
+            assert!(matches!(ping, Some(Ok(Message::Ping(_)))));
+            server.send(Message::Pong(Vec::new())).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            server.send(Message::Text("|updateuser|".into())).await.unwrap();
+        });
+
+        let start = Instant::now();
+        let pong = next_live(&mut client, &mut keepalive).await.unwrap();
+        assert!(matches!(pong, Some(Ok(Message::Pong(_)))));
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+
+        let text = next_live(&mut client, &mut keepalive).await.unwrap();
+        assert!(matches!(text, Some(Ok(Message::Text(_)))));
+        assert_eq!(start.elapsed(), Duration::from_secs(90));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quiet_period_without_timeout() {
+        let (mut client, mut server) = socket_pair().await;
+        let mut keepalive = Keepalive::new(config());
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(59)).await;
+            server.send(Message::Text("|updateuser|".into())).await.unwrap();
+            // Keep the server open so the client doesn't see a close
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        });
+
+        let message = next_live(&mut client, &mut keepalive).await.unwrap();
+        assert!(matches!(message, Some(Ok(Message::Text(_)))));
+        assert!(keepalive.ping_sent.is_none());
+    }
+
+    #[test]
+    fn test_handshake_error() {
+        let url = "wss://example.com/showdown/websocket";
+        let invalid_data =
+            || std::io::Error::new(std::io::ErrorKind::InvalidData, "bad certificate");
+        assert!(matches!(
+            handshake_error(url, "example.com", tungstenite::Error::Io(invalid_data())),
+            ConnectError::Tls { .. }
+        ));
+        assert!(matches!(
+            handshake_error(
+                "ws://example.com/",
+                "example.com",
+                tungstenite::Error::Io(invalid_data())
+            ),
+            ConnectError::Unreachable { .. }
+        ));
+        assert!(matches!(
+            handshake_error(url, "example.com", tungstenite::Error::ConnectionClosed),
+            ConnectError::Unreachable { .. }
+        ));
+    }
+}
@@ -0,0 +1,2153 @@
+//! Message dispatch: the client state a server message updates, and the
+//! handler callbacks it fires
+//!
+//! [`apply_to_state`] does all the bookkeeping, [`route`] decides from its
+//! result which callbacks fire without touching state, and
+//! [`Callback::invoke`] runs them.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use kazam_protocol::{
+    BattleInfo, BattleRequest, ChallengeState, ChatContent, ClientMessage, FormatSection, HpStatus,
+    ModerationEvent, Player, PlayerInfo, Pokemon, PokemonDetails, PreviewPokemon, RoomType,
+    SearchState, ServerMessage, Side, Stat, SwapBoostKind, User, same_user,
+};
+
+use crate::announcement::ServerNotice;
+use crate::battle_changes::BattleInfoChanges;
+use crate::handle::ClientState;
+use crate::joins::is_battle_room;
+use crate::members::MemberIndex;
+use crate::settings::SettingReply;
+use crate::team_sheets::OtsNotice;
+use crate::{BattleOutcome, ClientError, KazamHandler, LadderUpdate, RoomState};
+
+/// What applying a message to client state produced, for routing
+#[derive(Debug, Default)]
+pub(crate) struct Applied {
+    /// |challstr| from a server that restarted since we last logged in
+    pub restarted: bool,
+
+    /// First named |updateuser| of the session
+    pub logged_in: bool,
+
+    /// |formats| identical to the list already known, e.g. after a reconnect
+    pub formats_unchanged: bool,
+
+    /// Restart broadcast not announced before, with its ETA if given
+    pub restart_announced: Option<Option<Duration>>,
+
+    /// Chat line from the scrollback sent on join
+    pub is_history: bool,
+
+    /// Room as filled in by |users|
+    pub room: Option<RoomState>,
+
+    /// Battle as of |start|
+    pub battle: Option<BattleInfo>,
+
+    /// A new game began in a room whose previous battle had ended
+    pub battle_reset: bool,
+
+    /// Parsed |request|, with its staleness checked
+    pub request: Option<BattleRequest>,
+
+    /// Failed /undo reported by |error|
+    pub choice_error: Option<ClientError>,
+
+    /// Ranked players of a |raw| ladder table
+    pub ladder_updates: Vec<LadderUpdate>,
+
+    /// Timings of a battle that just ended
+    pub outcome: Option<BattleOutcome>,
+
+    /// Open team sheets prompt or answer in a battle room
+    pub ots: Option<OtsNotice>,
+
+    /// Parts of the room's battle info the message changed
+    pub battle_changes: BattleInfoChanges,
+
+    /// Held-back messages to send after the frame
+    pub requeue: Vec<ClientMessage>,
+}
+
+/// Update client state for one message of a frame in `room_id`
+pub(crate) fn apply_to_state(
+    state: &ClientState,
+    room_id: Option<&str>,
+    message: &ServerMessage,
+) -> Applied {
+    let mut applied = Applied::default();
+
+    match message {
+        ServerMessage::Challstr(_) => {
+            applied.restarted = state.on_challstr();
+        }
+
+        ServerMessage::UpdateUser { user, named, .. } => {
+            let was_logged_in = state.logged_in.load(Ordering::Relaxed);
+            if *named {
+                state.logged_in.store(true, Ordering::Relaxed);
+                if let Ok(mut username) = state.username.write() {
+                    *username = Some(user.username.clone());
+                }
+            }
+            if *named && !was_logged_in {
+                applied.logged_in = true;
+                applied.requeue = state.settings_to_reapply();
+                applied.requeue.extend(state.on_logged_in());
+            }
+        }
+
+        ServerMessage::NameTaken { .. } => {
+            // The server may have refused a stale assertion; don't offer it again
+            if let Ok(login) = state.login.read() {
+                login.forget_assertion();
+            }
+        }
+
+        ServerMessage::Formats(sections) => {
+            applied.formats_unchanged = !state.record_formats(sections);
+        }
+
+        ServerMessage::Popup(message) => {
+            if let Some(reply) = SettingReply::parse(message) {
+                state.resolve_setting(&reply);
+            }
+        }
+
+        ServerMessage::Pm {
+            sender, message, ..
+        } => {
+            // Only the server and admins can send /raw
+            if matches!(sender.rank, '&' | '~')
+                && let Some(notice) = ServerNotice::from_pm(message)
+            {
+                apply_notice(state, notice, &mut applied);
+            }
+            // Command replies come from the server user, who has no name
+            if matches!(sender.rank, '&' | '~')
+                && sender.username.is_empty()
+                && let Some(reply) = SettingReply::from_pm(message)
+            {
+                state.resolve_setting(&reply);
+            }
+        }
+
+        ServerMessage::Usercount(count) => {
+            // Some servers send |usercount| inside a room to report its population
+            with_room(state, room_id, |room| room.set_user_count(*count as usize));
+        }
+
+        ServerMessage::Init(room_type) => {
+            if let Some(rid) = room_id {
+                let room = state.new_room(rid, room_type.clone());
+                if let Ok(mut rooms) = state.rooms.write() {
+                    rooms.insert(rid.to_string(), Arc::new(room));
+                }
+                if let Ok(mut members) = state.members.write() {
+                    members.clear_room(rid);
+                }
+                state.clear_battle_progress(rid);
+                state.resolve_join(rid, Ok(()));
+            }
+        }
+
+        ServerMessage::NoInit { reason, message } => {
+            if let Some(rid) = room_id {
+                state.resolve_join(
+                    rid,
+                    Err(ClientError::JoinFailed {
+                        room: rid.to_string(),
+                        reason: reason.clone(),
+                        message: message.clone(),
+                    }),
+                );
+            }
+        }
+
+        ServerMessage::Title(title) => {
+            with_room(state, room_id, |room| room.title = Some(title.clone()));
+        }
+
+        ServerMessage::Users(users) => {
+            with_members(state, room_id, |members, rid| members.set_room(rid, users));
+            applied.room = with_room(state, room_id, |room| {
+                room.set_users(users.clone());
+                room.clone()
+            });
+        }
+
+        ServerMessage::Join { user, .. } => {
+            with_members(state, room_id, |members, rid| {
+                members.join(rid, &user.username)
+            });
+            with_room(state, room_id, |room| room.add_user(user.clone()));
+        }
+
+        ServerMessage::Leave { user, .. } => {
+            with_members(state, room_id, |members, rid| {
+                members.leave(rid, &user.username)
+            });
+            with_room(state, room_id, |room| room.remove_user(&user.username));
+        }
+
+        ServerMessage::Chat {
+            user,
+            message,
+            timestamp,
+        } => {
+            applied.is_history =
+                room_id.is_some_and(|rid| state.record_chat(rid, user, message, *timestamp));
+        }
+
+        ServerMessage::Timestamp(timestamp) => {
+            if let Ok(mut server_time) = state.server_time.write() {
+                *server_time = Some(*timestamp);
+            }
+        }
+
+        ServerMessage::Name { user, old_id, .. } => {
+            state.record_rename(old_id, &user.username);
+            with_members(state, room_id, |members, rid| {
+                members.rename(rid, old_id, &user.username)
+            });
+            with_room(state, room_id, |room| {
+                // Update user in room's user list
+                if let Some(existing) = room
+                    .users
+                    .iter_mut()
+                    .find(|u| same_user(&u.username, old_id))
+                {
+                    *existing = user.clone();
+                }
+            });
+            // |win| and rematch PMs use the new name
+            let renamed = with_battle(state, room_id, |battle| {
+                battle.rename_player(old_id, &user.username)
+            });
+            if renamed == Some(true) {
+                applied.battle_changes |= BattleInfoChanges::PLAYER_UPDATED;
+            }
+        }
+
+        ServerMessage::Html(html) => {
+            if let Some(notice) = ServerNotice::parse(html) {
+                apply_notice(state, notice, &mut applied);
+            }
+            if room_id.is_some_and(is_battle_room) {
+                applied.ots = OtsNotice::parse(html);
+            }
+        }
+
+        ServerMessage::Uhtml { name, html } | ServerMessage::UhtmlChange { name, html }
+            if room_id.is_some_and(is_battle_room) =>
+        {
+            applied.ots = OtsNotice::from_uhtml(name, html);
+        }
+
+        ServerMessage::Raw(content) => {
+            if let Some(notice) = content.strip_prefix("|raw|").and_then(ServerNotice::parse) {
+                apply_notice(state, notice, &mut applied);
+            }
+            // The answers come as plain lines or as |raw| infoboxes
+            if room_id.is_some_and(is_battle_room) {
+                applied.ots = OtsNotice::parse(content.strip_prefix("|raw|").unwrap_or(content));
+            }
+            if let Some(rid) = room_id
+                && let Some(message) = content.strip_prefix("|error|")
+                && state.take_undo_failure(rid)
+            {
+                applied.choice_error = Some(ClientError::UndoFailed {
+                    room: rid.to_string(),
+                    message: message.to_string(),
+                });
+            }
+            if let Some(rid) = room_id
+                && let Some(html) = content.strip_prefix("|raw|")
+                && html.contains("<table")
+            {
+                applied.ladder_updates =
+                    LadderUpdate::parse_table(html, &battle_format(state, rid));
+                state.record_ladder_updates(&applied.ladder_updates);
+            }
+        }
+
+        // ===================
+        // Battle Initialization
+        // ===================
+        ServerMessage::BattlePlayer {
+            player,
+            username,
+            avatar,
+            rating,
+        } => {
+            // A |player| line without a name is a player leaving, not a new game
+            applied.battle_reset =
+                !username.is_empty() && room_id.is_some_and(|rid| state.restart_ended_battle(rid));
+            if let Some(rid) = room_id
+                && let Ok(mut battles) = state.battles.write()
+            {
+                let battle = battles.entry(rid.to_string()).or_default();
+                Arc::make_mut(battle).players.push(PlayerInfo {
+                    player: *player,
+                    username: username.clone(),
+                    avatar: avatar.clone(),
+                    rating: *rating,
+                    team_size: 0,
+                });
+                applied.battle_changes |= BattleInfoChanges::PLAYER_ADDED;
+            }
+        }
+
+        ServerMessage::TeamSize { player, size } => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::PLAYER_UPDATED,
+                |battle| {
+                    if let Some(p) = battle.players.iter_mut().find(|p| p.player == *player) {
+                        p.team_size = *size;
+                    }
+                },
+            );
+        }
+
+        ServerMessage::GameType(game_type) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::FORMAT_SET,
+                |battle| battle.game_type = Some(*game_type),
+            );
+        }
+
+        ServerMessage::Gen(generation) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::FORMAT_SET,
+                |battle| battle.generation = *generation,
+            );
+        }
+
+        ServerMessage::Tier(tier) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::FORMAT_SET,
+                |battle| battle.tier = tier.clone(),
+            );
+        }
+
+        ServerMessage::Rated(message) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::RATED_SET,
+                |battle| {
+                    battle.rated = true;
+                    battle.rated_message = message.clone();
+                },
+            );
+        }
+
+        ServerMessage::Rule(rule) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::FORMAT_SET,
+                |battle| battle.rules.push(rule.clone()),
+            );
+        }
+
+        ServerMessage::Poke {
+            player,
+            details,
+            has_item,
+        } => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::PREVIEW_UPDATED,
+                |battle| {
+                    battle.preview.push(PreviewPokemon {
+                        player: *player,
+                        species: details.species.clone(),
+                        level: details.level,
+                        gender: details.gender,
+                        has_item: *has_item,
+                    })
+                },
+            );
+        }
+
+        ServerMessage::ClearPoke => {
+            applied.battle_reset = room_id.is_some_and(|rid| state.restart_ended_battle(rid));
+        }
+
+        ServerMessage::BattleStart => {
+            if let Some(rid) = room_id {
+                state.with_timing(rid, |timing| timing.on_start());
+            }
+            applied.battle = change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::STARTED,
+                |battle| {
+                    battle.started = true;
+                    battle.clone()
+                },
+            );
+        }
+
+        // ===================
+        // Battle Progress
+        // ===================
+        ServerMessage::Request(json) => {
+            if let Some(rid) = room_id
+                && let Some(mut request) = BattleRequest::parse(json)
+            {
+                request.is_stale = request.rqid.is_some_and(|id| state.is_stale_rqid(rid, id));
+                state.clear_pending_choice(rid);
+                if let Ok(mut timers) = state.timers.write() {
+                    timers.entry(rid.to_string()).or_default().on_request();
+                }
+                state.with_timing(rid, |timing| timing.on_request(request.rqid));
+                state.with_settle(rid, |settle| settle.on_request());
+                applied.request = Some(request);
+            }
+        }
+
+        ServerMessage::Turn(turn) => {
+            if let Some(rid) = room_id {
+                state.clear_pending_choice(rid);
+                state.with_timing(rid, |timing| timing.on_turn(*turn));
+                state.with_settle(rid, |settle| settle.on_boundary());
+            }
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::TURN_CHANGED,
+                |battle| battle.turn = *turn,
+            );
+        }
+
+        ServerMessage::Upkeep => {
+            if let Some(rid) = room_id {
+                state.with_settle(rid, |settle| settle.on_boundary());
+            }
+        }
+
+        ServerMessage::Win(winner) => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::ENDED,
+                |battle| battle.winner = Some(winner.clone()),
+            );
+            applied.outcome = room_id.and_then(|rid| state.end_battle(rid));
+        }
+
+        ServerMessage::Tie => {
+            change_battle(
+                state,
+                room_id,
+                &mut applied,
+                BattleInfoChanges::ENDED,
+                |battle| battle.tie = true,
+            );
+            applied.outcome = room_id.and_then(|rid| state.end_battle(rid));
+        }
+
+        ServerMessage::BattleTimestamp(timestamp) => {
+            if let Some(rid) = room_id {
+                state.with_timing(rid, |timing| timing.on_battle_timestamp(*timestamp));
+            }
+        }
+
+        ServerMessage::Inactive(message) => {
+            if let Some(rid) = room_id
+                && let Ok(mut timers) = state.timers.write()
+            {
+                timers
+                    .entry(rid.to_string())
+                    .or_default()
+                    .on_inactive(message);
+            }
+        }
+
+        ServerMessage::InactiveOff(_) => {
+            if let Some(rid) = room_id
+                && let Ok(mut timers) = state.timers.write()
+                && let Some(timer) = timers.get_mut(rid)
+            {
+                timer.on_inactive_off();
+            }
+        }
+
+        // Everything else only reaches the handler
+        _ => {}
+    }
+
+    applied
+}
+
+/// Restart announcements are reported once; a cancellation releases held searches
+fn apply_notice(state: &ClientState, notice: ServerNotice, applied: &mut Applied) {
+    match notice {
+        ServerNotice::RestartAnnounced { eta } => {
+            if state.announce_restart() {
+                tracing::info!(?eta, "Server restart announced");
+                applied.restart_announced = Some(eta);
+            }
+        }
+        ServerNotice::RestartCanceled => {
+            applied.requeue.extend(state.cancel_restart());
+        }
+    }
+}
+
+fn with_room<T>(
+    state: &ClientState,
+    room_id: Option<&str>,
+    f: impl FnOnce(&mut RoomState) -> T,
+) -> Option<T> {
+    let mut rooms = state.rooms.write().ok()?;
+    rooms.get_mut(room_id?).map(Arc::make_mut).map(f)
+}
+
+fn with_members(
+    state: &ClientState,
+    room_id: Option<&str>,
+    f: impl FnOnce(&mut MemberIndex, &str),
+) {
+    if let Some(rid) = room_id
+        && let Ok(mut members) = state.members.write()
+    {
+        f(&mut members, rid);
+    }
+}
+
+fn with_battle<T>(
+    state: &ClientState,
+    room_id: Option<&str>,
+    f: impl FnOnce(&mut BattleInfo) -> T,
+) -> Option<T> {
+    let mut battles = state.battles.write().ok()?;
+    battles.get_mut(room_id?).map(Arc::make_mut).map(f)
+}
+
+/// [`with_battle`], recording `change` in `applied` when the room has a battle
+fn change_battle<T>(
+    state: &ClientState,
+    room_id: Option<&str>,
+    applied: &mut Applied,
+    change: BattleInfoChanges,
+    f: impl FnOnce(&mut BattleInfo) -> T,
+) -> Option<T> {
+    let result = with_battle(state, room_id, f);
+    if result.is_some() {
+        applied.battle_changes |= change;
+    }
+    result
+}
+
+/// Format of a battle room: the |tier| if seen, else the id in the room name
+fn battle_format(state: &ClientState, room_id: &str) -> String {
+    let tier = state
+        .battles
+        .read()
+        .ok()
+        .and_then(|battles| battles.get(room_id).map(|b| b.tier.clone()))
+        .filter(|tier| !tier.is_empty());
+    tier.unwrap_or_else(|| room_id.split('-').nth(1).unwrap_or_default().to_string())
+}
+
+/// One [`KazamHandler`] callback, with the arguments it's invoked with
+///
+/// Callbacks taking a room id are only routed for frames that have one.
+#[derive(Debug)]
+pub(crate) enum Callback<'a> {
+    Challstr(&'a str),
+    UpdateUser {
+        user: &'a User,
+        named: bool,
+        avatar: &'a str,
+    },
+    NameTaken {
+        username: &'a str,
+        message: &'a str,
+    },
+    Popup(&'a str),
+    Pm {
+        sender: &'a User,
+        receiver: &'a User,
+        message: &'a str,
+    },
+    PmContent {
+        sender: &'a User,
+        receiver: &'a User,
+        content: ChatContent,
+    },
+    Usercount(u32),
+    Formats(&'a [FormatSection]),
+    UpdateSearch(&'a SearchState),
+    UpdateChallenges(&'a ChallengeState),
+    LoggedIn(&'a User),
+    ServerRestartAnnounced(Option<Duration>),
+    ServerRestarted,
+    Init(&'a RoomType),
+    NoInit {
+        reason: &'a str,
+        message: &'a str,
+    },
+    Title(&'a str),
+    Users(&'a [User]),
+    RoomJoined(&'a RoomState),
+    Join {
+        user: &'a User,
+        quiet: bool,
+    },
+    Leave {
+        user: &'a User,
+        quiet: bool,
+    },
+    Chat {
+        user: &'a User,
+        message: &'a str,
+        timestamp: Option<i64>,
+        is_history: bool,
+    },
+    ChatContent {
+        user: &'a User,
+        content: ChatContent,
+        timestamp: Option<i64>,
+        is_history: bool,
+    },
+    Timestamp(i64),
+    Battle {
+        room_id: &'a str,
+        user1: &'a User,
+        user2: &'a User,
+    },
+    Notify {
+        title: &'a str,
+        message: Option<&'a str>,
+        highlight_token: Option<&'a str>,
+    },
+    Name {
+        user: &'a User,
+        old_id: &'a str,
+        quiet: bool,
+    },
+    Html(&'a str),
+    Uhtml {
+        name: &'a str,
+        html: &'a str,
+    },
+    UhtmlChange {
+        name: &'a str,
+        html: &'a str,
+    },
+    Moderation(ModerationEvent),
+    Raw(&'a str),
+    LadderUpdate(&'a LadderUpdate),
+    BattleReset,
+    BattleStarted(&'a BattleInfo),
+    Request(&'a BattleRequest),
+    ChoiceError(&'a ClientError),
+    /// Answer the open team sheets prompt; the caller applies the policy,
+    /// which may call `on_ots_prompt`
+    OtsPrompt,
+    OtsAnswer {
+        username: Option<&'a str>,
+        accepted: bool,
+    },
+    ShowTeam {
+        player: Player,
+        team: &'a str,
+    },
+    Turn(u32),
+    Win(&'a str),
+    Tie,
+    BattleEnded(&'a BattleOutcome),
+    Switch {
+        pokemon: &'a Pokemon,
+        details: &'a PokemonDetails,
+        hp_status: Option<&'a HpStatus>,
+        is_drag: bool,
+    },
+    MoveUsed {
+        pokemon: &'a Pokemon,
+        move_name: &'a str,
+        target: Option<&'a Pokemon>,
+    },
+    Faint(&'a Pokemon),
+    Cant {
+        pokemon: &'a Pokemon,
+        reason: &'a str,
+        move_name: Option<&'a str>,
+    },
+    Damage {
+        pokemon: &'a Pokemon,
+        hp_status: Option<&'a HpStatus>,
+        from: Option<&'a str>,
+    },
+    Heal {
+        pokemon: &'a Pokemon,
+        hp_status: Option<&'a HpStatus>,
+        from: Option<&'a str>,
+    },
+    Status {
+        pokemon: &'a Pokemon,
+        status: &'a str,
+    },
+    CureStatus {
+        pokemon: &'a Pokemon,
+        status: &'a str,
+    },
+    Boost {
+        pokemon: &'a Pokemon,
+        stat: Stat,
+        amount: i8,
+    },
+    Unboost {
+        pokemon: &'a Pokemon,
+        stat: Stat,
+        amount: i8,
+    },
+    SwapBoost {
+        source: &'a Pokemon,
+        target: &'a Pokemon,
+        kind: &'a SwapBoostKind,
+    },
+    Weather {
+        weather: &'a str,
+        upkeep: bool,
+    },
+    FieldStart(&'a str),
+    FieldEnd(&'a str),
+    SideStart {
+        side: &'a Side,
+        condition: &'a str,
+    },
+    SideEnd {
+        side: &'a Side,
+        condition: &'a str,
+        from: Option<&'a str>,
+    },
+    Crit(&'a Pokemon),
+    SuperEffective(&'a Pokemon),
+    Resisted(&'a Pokemon),
+    Immune(&'a Pokemon),
+    Miss {
+        source: &'a Pokemon,
+        target: Option<&'a Pokemon>,
+    },
+    Fail {
+        pokemon: &'a Pokemon,
+        action: Option<&'a str>,
+    },
+    Item {
+        pokemon: &'a Pokemon,
+        item: &'a str,
+        from: Option<&'a str>,
+    },
+    EndItem {
+        pokemon: &'a Pokemon,
+        item: &'a str,
+        from: Option<&'a str>,
+        eaten: bool,
+    },
+    Ability {
+        pokemon: &'a Pokemon,
+        ability: &'a str,
+        from: Option<&'a str>,
+    },
+    EndAbility(&'a Pokemon),
+    Mega {
+        pokemon: &'a Pokemon,
+        megastone: &'a str,
+    },
+    Terastallize {
+        pokemon: &'a Pokemon,
+        tera_type: &'a str,
+    },
+    Primal(&'a Pokemon),
+    ZPower(&'a Pokemon),
+    UltraBurst {
+        pokemon: &'a Pokemon,
+        species: &'a str,
+        item: &'a str,
+    },
+    Transform {
+        pokemon: &'a Pokemon,
+        species: &'a str,
+    },
+    Inactive(&'a str),
+    InactiveOff(&'a str),
+    Activate {
+        pokemon: Option<&'a Pokemon>,
+        effect: &'a str,
+        detail: Option<&'a str>,
+    },
+    Hint(&'a str),
+    BattleMessageText(&'a str),
+    /// `on_battle_message`, which takes the message itself, so the caller
+    /// that owns it makes the call
+    BattleMessage,
+}
+
+/// The callbacks `message` fires, in order, given what it did to state
+///
+/// Every variant is listed so a new one can't silently fall through to
+/// the wrong callback.
+pub(crate) fn route<'a>(
+    room_id: Option<&str>,
+    message: &'a ServerMessage,
+    applied: &'a Applied,
+) -> Vec<Callback<'a>> {
+    use Callback as C;
+
+    let in_room = room_id.is_some();
+    let notice = applied.restart_announced.map(C::ServerRestartAnnounced);
+    let ots = applied.ots.as_ref().and_then(|ots| match ots {
+        OtsNotice::Prompt => Some(C::OtsPrompt),
+        OtsNotice::Answer { username, accepted } => Some(C::OtsAnswer {
+            username: username.as_deref(),
+            accepted: *accepted,
+        }),
+        OtsNotice::Closed => None,
+    });
+
+    match message {
+        // ===================
+        // Global Messages
+        // ===================
+        ServerMessage::Challstr(challstr) => applied
+            .restarted
+            .then_some(C::ServerRestarted)
+            .into_iter()
+            .chain([C::Challstr(challstr)])
+            .collect(),
+
+        ServerMessage::UpdateUser {
+            user,
+            named,
+            avatar,
+        } => [C::UpdateUser {
+            user,
+            named: *named,
+            avatar,
+        }]
+        .into_iter()
+        .chain(applied.logged_in.then_some(C::LoggedIn(user)))
+        .collect(),
+
+        ServerMessage::NameTaken { username, message } => {
+            vec![C::NameTaken { username, message }]
+        }
+
+        ServerMessage::Popup(message) => vec![C::Popup(message)],
+
+        ServerMessage::Pm {
+            sender,
+            receiver,
+            message,
+        } => notice
+            .into_iter()
+            .chain([
+                C::Pm {
+                    sender,
+                    receiver,
+                    message,
+                },
+                C::PmContent {
+                    sender,
+                    receiver,
+                    content: ChatContent::parse(message),
+                },
+            ])
+            .collect(),
+
+        ServerMessage::Usercount(count) => vec![C::Usercount(*count)],
+        ServerMessage::Formats(sections) => {
+            if applied.formats_unchanged {
+                vec![]
+            } else {
+                vec![C::Formats(sections)]
+            }
+        }
+        ServerMessage::UpdateSearch(state) => vec![C::UpdateSearch(state)],
+        ServerMessage::UpdateChallenges(state) => vec![C::UpdateChallenges(state)],
+
+        // ===================
+        // Room Messages
+        // ===================
+        ServerMessage::Init(room_type) => [C::Init(room_type)]
+            .into_iter()
+            .filter(|_| in_room)
+            .collect(),
+
+        ServerMessage::NoInit { reason, message } => [C::NoInit { reason, message }]
+            .into_iter()
+            .filter(|_| in_room)
+            .collect(),
+
+        ServerMessage::Title(title) => [C::Title(title)].into_iter().filter(|_| in_room).collect(),
+
+        ServerMessage::Users(users) => [C::Users(users)]
+            .into_iter()
+            .filter(|_| in_room)
+            .chain(applied.room.as_ref().map(C::RoomJoined))
+            .collect(),
+
+        ServerMessage::Join { user, quiet } => vec![C::Join {
+            user,
+            quiet: *quiet,
+        }],
+
+        ServerMessage::Leave { user, quiet } => vec![C::Leave {
+            user,
+            quiet: *quiet,
+        }],
+
+        ServerMessage::Chat {
+            user,
+            message,
+            timestamp,
+        } => vec![
+            C::Chat {
+                user,
+                message,
+                timestamp: *timestamp,
+                is_history: applied.is_history,
+            },
+            C::ChatContent {
+                user,
+                content: ChatContent::parse(message),
+                timestamp: *timestamp,
+                is_history: applied.is_history,
+            },
+        ],
+
+        ServerMessage::Timestamp(timestamp) => vec![C::Timestamp(*timestamp)],
+
+        ServerMessage::Battle {
+            room_id,
+            user1,
+            user2,
+        } => vec![C::Battle {
+            room_id,
+            user1,
+            user2,
+        }],
+
+        ServerMessage::Notify {
+            title,
+            message,
+            highlight_token,
+        } => vec![C::Notify {
+            title,
+            message: message.as_deref(),
+            highlight_token: highlight_token.as_deref(),
+        }],
+
+        ServerMessage::Name {
+            user,
+            old_id,
+            quiet,
+        } => vec![C::Name {
+            user,
+            old_id,
+            quiet: *quiet,
+        }],
+
+        ServerMessage::Html(html) => notice
+            .into_iter()
+            .chain([C::Html(html)])
+            .chain(ots)
+            .collect(),
+
+        ServerMessage::Uhtml { name, html } => {
+            [C::Uhtml { name, html }].into_iter().chain(ots).collect()
+        }
+
+        ServerMessage::UhtmlChange { name, html } => [C::UhtmlChange { name, html }]
+            .into_iter()
+            .chain(ots)
+            .collect(),
+
+        ServerMessage::Unlink { .. }
+        | ServerMessage::HideLines { .. }
+        | ServerMessage::Badge { .. }
+        | ServerMessage::ModAction(_) => ModerationEvent::from_message(message)
+            .map(C::Moderation)
+            .into_iter()
+            .collect(),
+
+        ServerMessage::Raw(content) => notice
+            .into_iter()
+            .chain(applied.choice_error.as_ref().map(C::ChoiceError))
+            .chain([C::Raw(content)])
+            .chain(ots)
+            .chain(applied.ladder_updates.iter().map(C::LadderUpdate))
+            .collect(),
+
+        // ===================
+        // Battle Initialization
+        // ===================
+        ServerMessage::ShowTeam { player, team } => battle(
+            in_room,
+            [C::ShowTeam {
+                player: *player,
+                team,
+            }],
+        ),
+
+        ServerMessage::BattleStart => {
+            battle(in_room, applied.battle.as_ref().map(C::BattleStarted))
+        }
+
+        ServerMessage::BattlePlayer { .. } | ServerMessage::ClearPoke => {
+            battle(in_room, applied.battle_reset.then_some(C::BattleReset))
+        }
+
+        ServerMessage::TeamSize { .. }
+        | ServerMessage::GameType(_)
+        | ServerMessage::Gen(_)
+        | ServerMessage::Tier(_)
+        | ServerMessage::Rated(_)
+        | ServerMessage::Rule(_)
+        | ServerMessage::Poke { .. }
+        | ServerMessage::TeamPreview(_) => battle(in_room, []),
+
+        // ===================
+        // Battle Progress
+        // ===================
+        ServerMessage::Request(_) => battle(in_room, applied.request.as_ref().map(C::Request)),
+
+        ServerMessage::Turn(turn) => battle(in_room, [C::Turn(*turn)]),
+
+        ServerMessage::Win(winner) => battle(
+            in_room,
+            [C::Win(winner)]
+                .into_iter()
+                .chain(applied.outcome.as_ref().map(C::BattleEnded)),
+        ),
+
+        ServerMessage::Tie => battle(
+            in_room,
+            [C::Tie]
+                .into_iter()
+                .chain(applied.outcome.as_ref().map(C::BattleEnded)),
+        ),
+
+        ServerMessage::Inactive(message) => battle(in_room, [C::Inactive(message)]),
+
+        ServerMessage::InactiveOff(message) => battle(in_room, [C::InactiveOff(message)]),
+
+        ServerMessage::Upkeep | ServerMessage::BattleTimestamp(_) => battle(in_room, []),
+
+        // ===================
+        // Major Actions
+        // ===================
+        ServerMessage::Switch {
+            pokemon,
+            details,
+            hp_status,
+        } => battle(
+            in_room,
+            [C::Switch {
+                pokemon,
+                details,
+                hp_status: hp_status.as_ref(),
+                is_drag: false,
+            }],
+        ),
+
+        ServerMessage::Drag {
+            pokemon,
+            details,
+            hp_status,
+        } => battle(
+            in_room,
+            [C::Switch {
+                pokemon,
+                details,
+                hp_status: hp_status.as_ref(),
+                is_drag: true,
+            }],
+        ),
+
+        ServerMessage::Move {
+            pokemon,
+            move_name,
+            target,
+            ..
+        } => battle(
+            in_room,
+            [C::MoveUsed {
+                pokemon,
+                move_name,
+                target: target.as_ref(),
+            }],
+        ),
+
+        ServerMessage::Faint(pokemon) => battle(in_room, [C::Faint(pokemon)]),
+
+        ServerMessage::Cant {
+            pokemon,
+            reason,
+            move_name,
+        } => battle(
+            in_room,
+            [C::Cant {
+                pokemon,
+                reason,
+                move_name: move_name.as_deref(),
+            }],
+        ),
+
+        ServerMessage::DetailsChange { .. }
+        | ServerMessage::FormeChange { .. }
+        | ServerMessage::Replace { .. }
+        | ServerMessage::Swap { .. } => battle(in_room, []),
+
+        // ===================
+        // Minor Actions
+        // ===================
+        ServerMessage::Damage {
+            pokemon,
+            hp_status,
+            from,
+        } => battle(
+            in_room,
+            [C::Damage {
+                pokemon,
+                hp_status: hp_status.as_ref(),
+                from: from.as_deref(),
+            }],
+        ),
+
+        ServerMessage::Heal {
+            pokemon,
+            hp_status,
+            from,
+        } => battle(
+            in_room,
+            [C::Heal {
+                pokemon,
+                hp_status: hp_status.as_ref(),
+                from: from.as_deref(),
+            }],
+        ),
+
+        ServerMessage::Status {
+            pokemon, status, ..
+        } => battle(in_room, [C::Status { pokemon, status }]),
+
+        ServerMessage::CureStatus { pokemon, status } => {
+            battle(in_room, [C::CureStatus { pokemon, status }])
+        }
+
+        ServerMessage::Boost {
+            pokemon,
+            stat,
+            amount,
+            ..
+        } => battle(
+            in_room,
+            [C::Boost {
+                pokemon,
+                stat: *stat,
+                amount: *amount,
+            }],
+        ),
+
+        ServerMessage::Unboost {
+            pokemon,
+            stat,
+            amount,
+            ..
+        } => battle(
+            in_room,
+            [C::Unboost {
+                pokemon,
+                stat: *stat,
+                amount: *amount,
+            }],
+        ),
+
+        ServerMessage::SwapBoost {
+            source,
+            target,
+            kind,
+            ..
+        } => battle(
+            in_room,
+            [C::SwapBoost {
+                source,
+                target,
+                kind,
+            }],
+        ),
+
+        ServerMessage::Weather {
+            weather, upkeep, ..
+        } => battle(
+            in_room,
+            [C::Weather {
+                weather,
+                upkeep: *upkeep,
+            }],
+        ),
+
+        ServerMessage::FieldStart(condition) => battle(in_room, [C::FieldStart(condition)]),
+
+        ServerMessage::FieldEnd(condition) => battle(in_room, [C::FieldEnd(condition)]),
+
+        ServerMessage::SideStart { side, condition } => {
+            battle(in_room, [C::SideStart { side, condition }])
+        }
+
+        ServerMessage::SideEnd {
+            side,
+            condition,
+            from,
+        } => battle(
+            in_room,
+            [C::SideEnd {
+                side,
+                condition,
+                from: from.as_deref(),
+            }],
+        ),
+
+        ServerMessage::Crit(pokemon) => battle(in_room, [C::Crit(pokemon)]),
+
+        ServerMessage::SuperEffective(pokemon) => battle(in_room, [C::SuperEffective(pokemon)]),
+
+        ServerMessage::Resisted(pokemon) => battle(in_room, [C::Resisted(pokemon)]),
+
+        ServerMessage::Immune { pokemon, .. } => battle(in_room, [C::Immune(pokemon)]),
+
+        ServerMessage::Miss { source, target } => battle(
+            in_room,
+            [C::Miss {
+                source,
+                target: target.as_ref(),
+            }],
+        ),
+
+        ServerMessage::Fail {
+            pokemon, action, ..
+        } => battle(
+            in_room,
+            [C::Fail {
+                pokemon,
+                action: action.as_deref(),
+            }],
+        ),
+
+        ServerMessage::Item {
+            pokemon,
+            item,
+            from,
+        } => battle(
+            in_room,
+            [C::Item {
+                pokemon,
+                item,
+                from: from.as_deref(),
+            }],
+        ),
+
+        ServerMessage::EndItem {
+            pokemon,
+            item,
+            from,
+            eat,
+        } => battle(
+            in_room,
+            [C::EndItem {
+                pokemon,
+                item,
+                from: from.as_deref(),
+                eaten: *eat,
+            }],
+        ),
+
+        ServerMessage::Ability {
+            pokemon,
+            ability,
+            from,
+            ..
+        } => battle(
+            in_room,
+            [C::Ability {
+                pokemon,
+                ability,
+                from: from.as_deref(),
+            }],
+        ),
+
+        ServerMessage::EndAbility(pokemon) => battle(in_room, [C::EndAbility(pokemon)]),
+
+        ServerMessage::Mega { pokemon, megastone } => {
+            battle(in_room, [C::Mega { pokemon, megastone }])
+        }
+
+        ServerMessage::Terastallize { pokemon, tera_type } => {
+            battle(in_room, [C::Terastallize { pokemon, tera_type }])
+        }
+
+        ServerMessage::Primal(pokemon) => battle(in_room, [C::Primal(pokemon)]),
+
+        ServerMessage::ZPower(pokemon) => battle(in_room, [C::ZPower(pokemon)]),
+
+        ServerMessage::Burst {
+            pokemon,
+            species,
+            item,
+        } => battle(
+            in_room,
+            [C::UltraBurst {
+                pokemon,
+                species,
+                item,
+            }],
+        ),
+
+        ServerMessage::Transform { pokemon, species } => {
+            battle(in_room, [C::Transform { pokemon, species }])
+        }
+
+        ServerMessage::Activate {
+            pokemon,
+            effect,
+            detail,
+            ..
+        } => battle(
+            in_room,
+            [C::Activate {
+                pokemon: pokemon.as_ref(),
+                effect,
+                detail: detail.as_deref(),
+            }],
+        ),
+
+        ServerMessage::Hint(message) => battle(in_room, [C::Hint(message)]),
+
+        ServerMessage::Message(message) => battle(in_room, [C::BattleMessageText(message)]),
+
+        // Battle messages with no callback of their own
+        ServerMessage::Block { .. }
+        | ServerMessage::NoTarget(_)
+        | ServerMessage::SetHp { .. }
+        | ServerMessage::CureTeam(_)
+        | ServerMessage::SetBoost { .. }
+        | ServerMessage::InvertBoost(_)
+        | ServerMessage::ClearBoost(_)
+        | ServerMessage::ClearAllBoost
+        | ServerMessage::ClearPositiveBoost { .. }
+        | ServerMessage::ClearNegativeBoost(_)
+        | ServerMessage::CopyBoost { .. }
+        | ServerMessage::SwapSideConditions
+        | ServerMessage::VolatileStart { .. }
+        | ServerMessage::VolatileEnd { .. }
+        | ServerMessage::Ohko(_)
+        | ServerMessage::ZBroken(_)
+        | ServerMessage::Center
+        | ServerMessage::Combine
+        | ServerMessage::Waiting { .. }
+        | ServerMessage::Prepare { .. }
+        | ServerMessage::Anim { .. }
+        | ServerMessage::MustRecharge(_)
+        | ServerMessage::Nothing
+        | ServerMessage::HitCount { .. }
+        | ServerMessage::SingleMove { .. }
+        | ServerMessage::SingleTurn { .. } => battle(in_room, []),
+    }
+}
+
+/// Room callbacks (dropped outside a room), then `on_battle_message`
+fn battle<'a>(
+    in_room: bool,
+    callbacks: impl IntoIterator<Item = Callback<'a>>,
+) -> Vec<Callback<'a>> {
+    let mut routed: Vec<_> = callbacks.into_iter().filter(|_| in_room).collect();
+    routed.push(Callback::BattleMessage);
+    routed
+}
+
+impl Callback<'_> {
+    /// Name of the [`KazamHandler`] method this invokes
+    #[cfg(test)]
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Challstr(_) => "on_challstr",
+            Self::UpdateUser { .. } => "on_update_user",
+            Self::NameTaken { .. } => "on_name_taken",
+            Self::Popup(_) => "on_popup",
+            Self::Pm { .. } => "on_pm",
+            Self::PmContent { .. } => "on_pm_content",
+            Self::Usercount(_) => "on_usercount",
+            Self::Formats(_) => "on_formats",
+            Self::UpdateSearch(_) => "on_update_search",
+            Self::UpdateChallenges(_) => "on_update_challenges",
+            Self::LoggedIn(_) => "on_logged_in",
+            Self::ServerRestartAnnounced(_) => "on_server_restart_announced",
+            Self::ServerRestarted => "on_server_restarted",
+            Self::Init(_) => "on_init",
+            Self::NoInit { .. } => "on_noinit",
+            Self::Title(_) => "on_title",
+            Self::Users(_) => "on_users",
+            Self::RoomJoined(_) => "on_room_joined",
+            Self::Join { .. } => "on_join",
+            Self::Leave { .. } => "on_leave",
+            Self::Chat { .. } => "on_chat",
+            Self::ChatContent { .. } => "on_chat_content",
+            Self::Timestamp(_) => "on_timestamp",
+            Self::Battle { .. } => "on_battle",
+            Self::Notify { .. } => "on_notify",
+            Self::Name { .. } => "on_name",
+            Self::Html(_) => "on_html",
+            Self::Uhtml { .. } => "on_uhtml",
+            Self::UhtmlChange { .. } => "on_uhtml_change",
+            Self::Moderation(_) => "on_moderation",
+            Self::Raw(_) => "on_raw",
+            Self::LadderUpdate(_) => "on_ladder_update",
+            Self::BattleReset => "on_battle_reset",
+            Self::BattleStarted(_) => "on_battle_started",
+            Self::Request(_) => "on_request",
+            Self::ChoiceError(_) => "on_choice_error",
+            Self::OtsPrompt => "on_ots_prompt",
+            Self::OtsAnswer { .. } => "on_ots_answer",
+            Self::ShowTeam { .. } => "on_show_team",
+            Self::Turn(_) => "on_turn",
+            Self::Win(_) => "on_win",
+            Self::Tie => "on_tie",
+            Self::BattleEnded(_) => "on_battle_ended",
+            Self::Switch { .. } => "on_switch",
+            Self::MoveUsed { .. } => "on_move_used",
+            Self::Faint(_) => "on_faint",
+            Self::Cant { .. } => "on_cant",
+            Self::Damage { .. } => "on_damage",
+            Self::Heal { .. } => "on_heal",
+            Self::Status { .. } => "on_status",
+            Self::CureStatus { .. } => "on_cure_status",
+            Self::Boost { .. } => "on_boost",
+            Self::Unboost { .. } => "on_unboost",
+            Self::SwapBoost { .. } => "on_swap_boost",
+            Self::Weather { .. } => "on_weather",
+            Self::FieldStart(_) => "on_field_start",
+            Self::FieldEnd(_) => "on_field_end",
+            Self::SideStart { .. } => "on_side_start",
+            Self::SideEnd { .. } => "on_side_end",
+            Self::Crit(_) => "on_crit",
+            Self::SuperEffective(_) => "on_super_effective",
+            Self::Resisted(_) => "on_resisted",
+            Self::Immune(_) => "on_immune",
+            Self::Miss { .. } => "on_miss",
+            Self::Fail { .. } => "on_fail",
+            Self::Item { .. } => "on_item",
+            Self::EndItem { .. } => "on_end_item",
+            Self::Ability { .. } => "on_ability",
+            Self::EndAbility(_) => "on_end_ability",
+            Self::Mega { .. } => "on_mega",
+            Self::Terastallize { .. } => "on_terastallize",
+            Self::Primal(_) => "on_primal",
+            Self::ZPower(_) => "on_z_power",
+            Self::UltraBurst { .. } => "on_ultra_burst",
+            Self::Transform { .. } => "on_transform",
+            Self::Inactive(_) => "on_inactive",
+            Self::InactiveOff(_) => "on_inactive_off",
+            Self::Activate { .. } => "on_activate",
+            Self::Hint(_) => "on_hint",
+            Self::BattleMessageText(_) => "on_battle_message_text",
+            Self::BattleMessage => "on_battle_message",
+        }
+    }
+
+    /// Call the handler method for a message in `room_id`
+    ///
+    /// [`Callback::BattleMessage`] and [`Callback::OtsPrompt`] are left to the caller.
+    pub(crate) async fn invoke<H: KazamHandler>(self, handler: &mut H, room_id: Option<&str>) {
+        // Room callbacks are only routed when there is a room
+        let rid = room_id.unwrap_or_default();
+        match self {
+            Self::Challstr(challstr) => handler.on_challstr(challstr).await,
+            Self::UpdateUser {
+                user,
+                named,
+                avatar,
+            } => handler.on_update_user(user, named, avatar).await,
+            Self::NameTaken { username, message } => handler.on_name_taken(username, message).await,
+            Self::Popup(message) => handler.on_popup(message).await,
+            Self::Pm {
+                sender,
+                receiver,
+                message,
+            } => handler.on_pm(sender, receiver, message).await,
+            Self::PmContent {
+                sender,
+                receiver,
+                content,
+            } => handler.on_pm_content(sender, receiver, &content).await,
+            Self::Usercount(count) => handler.on_usercount(count).await,
+            Self::Formats(sections) => handler.on_formats(sections).await,
+            Self::UpdateSearch(state) => handler.on_update_search(state).await,
+            Self::UpdateChallenges(state) => handler.on_update_challenges(state).await,
+            Self::LoggedIn(user) => handler.on_logged_in(user).await,
+            Self::ServerRestartAnnounced(eta) => handler.on_server_restart_announced(eta).await,
+            Self::ServerRestarted => handler.on_server_restarted().await,
+            Self::Init(room_type) => handler.on_init(rid, room_type).await,
+            Self::NoInit { reason, message } => handler.on_noinit(rid, reason, message).await,
+            Self::Title(title) => handler.on_title(rid, title).await,
+            Self::Users(users) => handler.on_users(rid, users).await,
+            Self::RoomJoined(room) => handler.on_room_joined(room).await,
+            Self::Join { user, quiet } => handler.on_join(room_id, user, quiet).await,
+            Self::Leave { user, quiet } => handler.on_leave(room_id, user, quiet).await,
+            Self::Chat {
+                user,
+                message,
+                timestamp,
+                is_history,
+            } => {
+                handler
+                    .on_chat(room_id, user, message, timestamp, is_history)
+                    .await
+            }
+            Self::ChatContent {
+                user,
+                content,
+                timestamp,
+                is_history,
+            } => {
+                handler
+                    .on_chat_content(room_id, user, &content, timestamp, is_history)
+                    .await
+            }
+            Self::Timestamp(timestamp) => handler.on_timestamp(timestamp).await,
+            Self::Battle {
+                room_id,
+                user1,
+                user2,
+            } => handler.on_battle(room_id, user1, user2).await,
+            Self::Notify {
+                title,
+                message,
+                highlight_token,
+            } => handler.on_notify(title, message, highlight_token).await,
+            Self::Name {
+                user,
+                old_id,
+                quiet,
+            } => handler.on_name(room_id, user, old_id, quiet).await,
+            Self::Html(html) => handler.on_html(room_id, html).await,
+            Self::Uhtml { name, html } => handler.on_uhtml(room_id, name, html).await,
+            Self::UhtmlChange { name, html } => handler.on_uhtml_change(room_id, name, html).await,
+            Self::Moderation(event) => handler.on_moderation(room_id, &event).await,
+            Self::Raw(content) => handler.on_raw(room_id, content).await,
+            Self::LadderUpdate(update) => handler.on_ladder_update(room_id, update).await,
+            Self::BattleReset => handler.on_battle_reset(rid).await,
+            Self::BattleStarted(battle) => handler.on_battle_started(rid, battle).await,
+            Self::Request(request) => handler.on_request(rid, request).await,
+            Self::ChoiceError(error) => handler.on_choice_error(rid, error).await,
+            Self::OtsPrompt => {}
+            Self::OtsAnswer { username, accepted } => {
+                handler.on_ots_answer(rid, username, accepted).await
+            }
+            Self::ShowTeam { player, team } => handler.on_show_team(rid, player, team).await,
+            Self::Turn(turn) => handler.on_turn(rid, turn).await,
+            Self::Win(winner) => handler.on_win(rid, winner).await,
+            Self::Tie => handler.on_tie(rid).await,
+            Self::BattleEnded(outcome) => handler.on_battle_ended(rid, outcome).await,
+            Self::Switch {
+                pokemon,
+                details,
+                hp_status,
+                is_drag,
+            } => {
+                handler
+                    .on_switch(rid, pokemon, details, hp_status, is_drag)
+                    .await
+            }
+            Self::MoveUsed {
+                pokemon,
+                move_name,
+                target,
+            } => handler.on_move_used(rid, pokemon, move_name, target).await,
+            Self::Faint(pokemon) => handler.on_faint(rid, pokemon).await,
+            Self::Cant {
+                pokemon,
+                reason,
+                move_name,
+            } => handler.on_cant(rid, pokemon, reason, move_name).await,
+            Self::Damage {
+                pokemon,
+                hp_status,
+                from,
+            } => handler.on_damage(rid, pokemon, hp_status, from).await,
+            Self::Heal {
+                pokemon,
+                hp_status,
+                from,
+            } => handler.on_heal(rid, pokemon, hp_status, from).await,
+            Self::Status { pokemon, status } => handler.on_status(rid, pokemon, status).await,
+            Self::CureStatus { pokemon, status } => {
+                handler.on_cure_status(rid, pokemon, status).await
+            }
+            Self::Boost {
+                pokemon,
+                stat,
+                amount,
+            } => handler.on_boost(rid, pokemon, stat, amount).await,
+            Self::Unboost {
+                pokemon,
+                stat,
+                amount,
+            } => handler.on_unboost(rid, pokemon, stat, amount).await,
+            Self::SwapBoost {
+                source,
+                target,
+                kind,
+            } => handler.on_swap_boost(rid, source, target, kind).await,
+            Self::Weather { weather, upkeep } => handler.on_weather(rid, weather, upkeep).await,
+            Self::FieldStart(condition) => handler.on_field_start(rid, condition).await,
+            Self::FieldEnd(condition) => handler.on_field_end(rid, condition).await,
+            Self::SideStart { side, condition } => {
+                handler.on_side_start(rid, side, condition).await
+            }
+            Self::SideEnd {
+                side,
+                condition,
+                from,
+            } => handler.on_side_end(rid, side, condition, from).await,
+            Self::Crit(pokemon) => handler.on_crit(rid, pokemon).await,
+            Self::SuperEffective(pokemon) => handler.on_super_effective(rid, pokemon).await,
+            Self::Resisted(pokemon) => handler.on_resisted(rid, pokemon).await,
+            Self::Immune(pokemon) => handler.on_immune(rid, pokemon).await,
+            Self::Miss { source, target } => handler.on_miss(rid, source, target).await,
+            Self::Fail { pokemon, action } => handler.on_fail(rid, pokemon, action).await,
+            Self::Item {
+                pokemon,
+                item,
+                from,
+            } => handler.on_item(rid, pokemon, item, from).await,
+            Self::EndItem {
+                pokemon,
+                item,
+                from,
+                eaten,
+            } => handler.on_end_item(rid, pokemon, item, from, eaten).await,
+            Self::Ability {
+                pokemon,
+                ability,
+                from,
+            } => handler.on_ability(rid, pokemon, ability, from).await,
+            Self::EndAbility(pokemon) => handler.on_end_ability(rid, pokemon).await,
+            Self::Mega { pokemon, megastone } => handler.on_mega(rid, pokemon, megastone).await,
+            Self::Terastallize { pokemon, tera_type } => {
+                handler.on_terastallize(rid, pokemon, tera_type).await
+            }
+            Self::Primal(pokemon) => handler.on_primal(rid, pokemon).await,
+            Self::ZPower(pokemon) => handler.on_z_power(rid, pokemon).await,
+            Self::UltraBurst {
+                pokemon,
+                species,
+                item,
+            } => handler.on_ultra_burst(rid, pokemon, species, item).await,
+            Self::Transform { pokemon, species } => {
+                handler.on_transform(rid, pokemon, species).await
+            }
+            Self::Inactive(message) => handler.on_inactive(rid, message).await,
+            Self::InactiveOff(message) => handler.on_inactive_off(rid, message).await,
+            Self::Activate {
+                pokemon,
+                effect,
+                detail,
+            } => handler.on_activate(rid, pokemon, effect, detail).await,
+            Self::Hint(message) => handler.on_hint(rid, message).await,
+            Self::BattleMessageText(message) => handler.on_battle_message_text(rid, message).await,
+            Self::BattleMessage => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::mem::discriminant;
+
+    use kazam_protocol::parse_server_message;
+
+    use super::*;
+
+    const ROOM: &str = "battle-gen9ou-1";
+
+    /// Variants of [`ServerMessage`]; `ROUTES` must cover each one
+    const VARIANTS: usize = 114;
+
+    /// Every message kind once, with the callbacks it fires in a joined battle room
+    const ROUTES: &[(&str, &[&str])] = &[
+        // Global
+        ("|challstr|4|0123abcd", &["on_challstr"]),
+        (
+            "|updateuser| Kazam|1|1|{}",
+            &["on_update_user", "on_logged_in"],
+        ),
+        (
+            "|nametaken|Kazam|Someone is already using that name",
+            &["on_name_taken"],
+        ),
+        ("|popup|Your team was rejected", &["on_popup"]),
+        ("|pm| Alice| Kazam|gg", &["on_pm", "on_pm_content"]),
+        ("|usercount|1234", &["on_usercount"]),
+        ("|formats|,1|S/V Singles|[Gen 9] OU,e", &["on_formats"]),
+        (
+            "|updatesearch|{\"searching\":[],\"games\":null}",
+            &["on_update_search"],
+        ),
+        (
+            "|updatechallenges|{\"challengesFrom\":{},\"challengeTo\":null}",
+            &["on_update_challenges"],
+        ),
+        // Room
+        ("|init|battle", &["on_init"]),
+        (
+            "|noinit|nonexistent|The room does not exist.",
+            &["on_noinit"],
+        ),
+        ("|title|Alice vs. Bob", &["on_title"]),
+        ("|users|2,@Alice,+Bob", &["on_users", "on_room_joined"]),
+        ("|j| Carol", &["on_join"]),
+        ("|l| Carol", &["on_leave"]),
+        ("|c| Carol|hi", &["on_chat", "on_chat_content"]),
+        ("|:|1700000000", &["on_timestamp"]),
+        ("|b|battle-gen9ou-2| Alice| Bob", &["on_battle"]),
+        ("|notify|Your turn|Alice vs. Bob", &["on_notify"]),
+        ("|n| Carol2|carol", &["on_name"]),
+        ("|html|<b>Welcome</b>", &["on_html"]),
+        ("|uhtml|poll|<b>Poll</b>", &["on_uhtml"]),
+        ("|uhtmlchange|poll|<i>Closed</i>", &["on_uhtml_change"]),
+        ("|unlink|spammer", &["on_moderation"]),
+        ("|hidelines|hide|spammer|1|3", &["on_moderation"]),
+        ("|badge|Alice|gold|Champion", &["on_moderation"]),
+        (
+            "(spammer was muted by Zarel for 7 minutes.)",
+            &["on_moderation"],
+        ),
+        ("|error|Unknown command", &["on_raw"]),
+        // Battle initialization
+        ("|player|p2|Bob|2|1500", &["on_battle_message"]),
+        ("|teamsize|p1|6", &["on_battle_message"]),
+        ("|gametype|singles", &["on_battle_message"]),
+        ("|gen|9", &["on_battle_message"]),
+        ("|tier|[Gen 9] OU", &["on_battle_message"]),
+        ("|rated|", &["on_battle_message"]),
+        (
+            "|rule|Sleep Clause Mod: Limit one foe put to sleep",
+            &["on_battle_message"],
+        ),
+        ("|clearpoke", &["on_battle_message"]),
+        ("|poke|p1|Garchomp, L50, M|item", &["on_battle_message"]),
+        ("|teampreview", &["on_battle_message"]),
+        (
+            "|showteam|p1|Garchomp||||Earthquake|||||50|",
+            &["on_show_team", "on_battle_message"],
+        ),
+        ("|start", &["on_battle_started", "on_battle_message"]),
+        // Battle progress
+        (
+            "|request|{\"wait\":true,\"rqid\":3}",
+            &["on_request", "on_battle_message"],
+        ),
+        (
+            "|inactive|Alice has 120 seconds left.",
+            &["on_inactive", "on_battle_message"],
+        ),
+        (
+            "|inactiveoff|Battle timer is now OFF.",
+            &["on_inactive_off", "on_battle_message"],
+        ),
+        ("|upkeep", &["on_battle_message"]),
+        ("|turn|1", &["on_turn", "on_battle_message"]),
+        ("|t:|1700000000", &["on_battle_message"]),
+        (
+            "|win|Alice",
+            &["on_win", "on_battle_ended", "on_battle_message"],
+        ),
+        ("|tie", &["on_tie", "on_battle_ended", "on_battle_message"]),
+        // Major actions
+        (
+            "|move|p1a: Garchomp|Earthquake|p2a: Rotom",
+            &["on_move_used", "on_battle_message"],
+        ),
+        (
+            "|switch|p1a: Garchomp|Garchomp, L50|100/100",
+            &["on_switch", "on_battle_message"],
+        ),
+        (
+            "|drag|p2a: Rotom|Rotom-Wash, L50|100/100",
+            &["on_switch", "on_battle_message"],
+        ),
+        (
+            "|detailschange|p1a: Garchomp|Garchomp-Mega, L50",
+            &["on_battle_message"],
+        ),
+        (
+            "|-formechange|p1a: Minior|Minior-Meteor",
+            &["on_battle_message"],
+        ),
+        ("|replace|p2a: Zoroark|Zoroark, L50", &["on_battle_message"]),
+        ("|swap|p1a: Garchomp|1", &["on_battle_message"]),
+        ("|cant|p1a: Garchomp|par", &["on_cant", "on_battle_message"]),
+        ("|faint|p2a: Rotom", &["on_faint", "on_battle_message"]),
+        // Minor actions
+        ("|-fail|p1a: Garchomp", &["on_fail", "on_battle_message"]),
+        ("|-block|p1a: Garchomp|Dynamax", &["on_battle_message"]),
+        ("|-notarget|p1a: Garchomp", &["on_battle_message"]),
+        (
+            "|-miss|p1a: Garchomp|p2a: Rotom",
+            &["on_miss", "on_battle_message"],
+        ),
+        (
+            "|-damage|p2a: Rotom|50/100",
+            &["on_damage", "on_battle_message"],
+        ),
+        (
+            "|-heal|p2a: Rotom|56/100|[from] item: Leftovers",
+            &["on_heal", "on_battle_message"],
+        ),
+        ("|-sethp|p2a: Rotom|50/100", &["on_battle_message"]),
+        (
+            "|-status|p2a: Rotom|brn",
+            &["on_status", "on_battle_message"],
+        ),
+        (
+            "|-curestatus|p2a: Rotom|brn",
+            &["on_cure_status", "on_battle_message"],
+        ),
+        ("|-cureteam|p2a: Rotom", &["on_battle_message"]),
+        (
+            "|-boost|p1a: Garchomp|atk|1",
+            &["on_boost", "on_battle_message"],
+        ),
+        (
+            "|-unboost|p1a: Garchomp|atk|1",
+            &["on_unboost", "on_battle_message"],
+        ),
+        ("|-setboost|p1a: Garchomp|atk|6", &["on_battle_message"]),
+        (
+            "|-swapboost|p1a: Garchomp|p2a: Rotom|atk",
+            &["on_swap_boost", "on_battle_message"],
+        ),
+        ("|-invertboost|p1a: Garchomp", &["on_battle_message"]),
+        ("|-clearboost|p1a: Garchomp", &["on_battle_message"]),
+        ("|-clearallboost", &["on_battle_message"]),
+        (
+            "|-clearpositiveboost|p1a: Garchomp|p2a: Rotom|move: Spectral Thief",
+            &["on_battle_message"],
+        ),
+        ("|-clearnegativeboost|p1a: Garchomp", &["on_battle_message"]),
+        (
+            "|-copyboost|p1a: Garchomp|p2a: Rotom",
+            &["on_battle_message"],
+        ),
+        ("|-weather|RainDance", &["on_weather", "on_battle_message"]),
+        (
+            "|-fieldstart|move: Electric Terrain",
+            &["on_field_start", "on_battle_message"],
+        ),
+        (
+            "|-fieldend|move: Electric Terrain",
+            &["on_field_end", "on_battle_message"],
+        ),
+        (
+            "|-sidestart|p1: Alice|move: Stealth Rock",
+            &["on_side_start", "on_battle_message"],
+        ),
+        (
+            "|-sideend|p1: Alice|move: Stealth Rock",
+            &["on_side_end", "on_battle_message"],
+        ),
+        ("|-swapsideconditions", &["on_battle_message"]),
+        ("|-start|p2a: Rotom|confusion", &["on_battle_message"]),
+        ("|-end|p2a: Rotom|confusion", &["on_battle_message"]),
+        ("|-crit|p2a: Rotom", &["on_crit", "on_battle_message"]),
+        (
+            "|-supereffective|p2a: Rotom",
+            &["on_super_effective", "on_battle_message"],
+        ),
+        (
+            "|-resisted|p2a: Rotom",
+            &["on_resisted", "on_battle_message"],
+        ),
+        ("|-immune|p2a: Rotom", &["on_immune", "on_battle_message"]),
+        ("|-ohko|p2a: Rotom", &["on_battle_message"]),
+        (
+            "|-item|p2a: Rotom|Choice Scarf",
+            &["on_item", "on_battle_message"],
+        ),
+        (
+            "|-enditem|p2a: Rotom|Sitrus Berry|[eat]",
+            &["on_end_item", "on_battle_message"],
+        ),
+        (
+            "|-ability|p2a: Rotom|Levitate",
+            &["on_ability", "on_battle_message"],
+        ),
+        (
+            "|-endability|p2a: Rotom",
+            &["on_end_ability", "on_battle_message"],
+        ),
+        (
+            "|-transform|p1a: Ditto|p2a: Rotom",
+            &["on_transform", "on_battle_message"],
+        ),
+        (
+            "|-mega|p1a: Garchomp|Garchomp|Garchompite",
+            &["on_mega", "on_battle_message"],
+        ),
+        ("|-primal|p1a: Groudon", &["on_primal", "on_battle_message"]),
+        (
+            "|-burst|p1a: Necrozma|Necrozma-Ultra|Ultranecrozium Z",
+            &["on_ultra_burst", "on_battle_message"],
+        ),
+        (
+            "|-terastallize|p1a: Garchomp|Ground",
+            &["on_terastallize", "on_battle_message"],
+        ),
+        (
+            "|-zpower|p1a: Garchomp",
+            &["on_z_power", "on_battle_message"],
+        ),
+        ("|-zbroken|p2a: Rotom", &["on_battle_message"]),
+        (
+            "|-activate|p2a: Rotom|move: Protect",
+            &["on_activate", "on_battle_message"],
+        ),
+        (
+            "|-hint|Some effects can't be copied.",
+            &["on_hint", "on_battle_message"],
+        ),
+        ("|-center", &["on_battle_message"]),
+        (
+            "|-message|Alice forfeited.",
+            &["on_battle_message_text", "on_battle_message"],
+        ),
+        ("|-combine", &["on_battle_message"]),
+        ("|-waiting|p1a: Garchomp|p1b: Rotom", &["on_battle_message"]),
+        ("|-prepare|p1a: Garchomp|Solar Beam", &["on_battle_message"]),
+        (
+            "|-anim|p1a: Garchomp|Solar Beam|p2a: Rotom",
+            &["on_battle_message"],
+        ),
+        ("|-mustrecharge|p1a: Garchomp", &["on_battle_message"]),
+        ("|-nothing", &["on_battle_message"]),
+        ("|-hitcount|p2a: Rotom|3", &["on_battle_message"]),
+        (
+            "|-singlemove|p1a: Garchomp|Destiny Bond",
+            &["on_battle_message"],
+        ),
+        ("|-singleturn|p1a: Garchomp|Protect", &["on_battle_message"]),
+    ];
+
+    fn parse(line: &str) -> ServerMessage {
+        parse_server_message(line).unwrap_or_else(|e| panic!("failed to parse {:?}: {}", line, e))
+    }
+
+    /// State for a joined battle room with one player announced
+    fn joined() -> ClientState {
+        let state = ClientState::new();
+        for line in ["|init|battle", "|player|p1|Alice|1|"] {
+            apply_to_state(&state, Some(ROOM), &parse(line));
+        }
+        state
+    }
+
+    fn names(
+        state: &ClientState,
+        room_id: Option<&str>,
+        message: &ServerMessage,
+    ) -> Vec<&'static str> {
+        let applied = apply_to_state(state, room_id, message);
+        route(room_id, message, &applied)
+            .iter()
+            .map(Callback::name)
+            .collect()
+    }
+
+    #[test]
+    fn test_routes() {
+        let mut seen = HashSet::new();
+        for (line, expected) in ROUTES {
+            let message = parse(line);
+            seen.insert(discriminant(&message));
+            assert_eq!(
+                names(&joined(), Some(ROOM), &message),
+                *expected,
+                "{}",
+                line
+            );
+        }
+        assert_eq!(
+            seen.len(),
+            VARIANTS,
+            "ROUTES must cover every ServerMessage variant"
+        );
+    }
+
+    #[test]
+    fn test_routes_outside_room() {
+        // Room callbacks need a room; battle messages still reach on_battle_message
+        for (line, expected) in [
+            ("|init|chat", &[][..]),
+            ("|title|Lobby", &[]),
+            ("|turn|2", &["on_battle_message"]),
+            ("|win|Alice", &["on_battle_message"]),
+            ("|j| Carol", &["on_join"]),
+            ("|c| Carol|hi", &["on_chat", "on_chat_content"]),
+        ] {
+            assert_eq!(
+                names(&ClientState::new(), None, &parse(line)),
+                expected,
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_restart_notice_routes_once() {
+        let state = ClientState::new();
+        let broadcast = parse(
+            r#"|html|<div class="broadcast-red"><b>The server will restart in 15 minutes.</b></div>"#,
+        );
+        assert_eq!(
+            names(&state, None, &broadcast),
+            ["on_server_restart_announced", "on_html"]
+        );
+        assert_eq!(names(&state, None, &broadcast), ["on_html"]);
+    }
+
+    #[test]
+    fn test_unchanged_formats_route_once() {
+        let state = ClientState::new();
+        let formats = parse("|formats|,1|S/V Singles|[Gen 9] OU,e");
+        assert_eq!(names(&state, None, &formats), ["on_formats"]);
+        state.on_reconnected();
+        assert!(names(&state, None, &formats).is_empty());
+        assert_eq!(
+            names(&state, None, &parse("|formats|,1|S/V Singles|[Gen 9] UU,e")),
+            ["on_formats"]
+        );
+        let formats = state.formats.read().unwrap().clone().unwrap();
+        assert_eq!(formats[0].formats[0].name, "[Gen 9] UU");
+    }
+
+    #[test]
+    fn test_ladder_table_stores_own_rating() {
+        let state = joined();
+        apply_to_state(&state, None, &parse("|updateuser| Kazam Bot|1|1|{}"));
+        apply_to_state(&state, Some(ROOM), &parse("|tier|[Gen 9] OU"));
+        let table = format!(
+            "|raw|{}",
+            include_str!("../tests/fixtures/ladder_gen9ou.html").trim()
+        );
+        assert_eq!(
+            names(&state, Some(ROOM), &parse(&table)),
+            ["on_raw", "on_ladder_update", "on_ladder_update"]
+        );
+        let handle =
+            crate::KazamHandle::new(tokio::sync::mpsc::unbounded_channel().0, state.into());
+        let rating = handle.rating("gen9ou").unwrap();
+        assert_eq!((rating.elo_after, rating.gxe), (1506, Some(68.4)));
+        assert!(handle.rating("gen9uu").is_none());
+    }
+
+    #[test]
+    fn test_apply_to_state() {
+        let state = joined();
+        let room = Some(ROOM);
+        for line in [
+            "|title|Alice vs. Bob",
+            "|users|2,@Alice,+Bob",
+            "|j| Carol",
+            "|n| Caroline|carol",
+            "|player|p2|Bob|2|1500",
+            "|teamsize|p2|6",
+            "|gen|9",
+            "|tier|[Gen 9] OU",
+            "|rule|Sleep Clause Mod: Limit one foe put to sleep",
+            "|poke|p2|Rotom-Wash, L50|item",
+            "|start",
+            "|turn|3",
+            "|win|Bob",
+            "|:|1700000000",
+        ] {
+            apply_to_state(&state, room, &parse(line));
+        }
+
+        let rooms = state.rooms.read().unwrap();
+        let joined_room = &rooms[ROOM];
+        assert_eq!(joined_room.title.as_deref(), Some("Alice vs. Bob"));
+        let users: Vec<_> = joined_room
+            .users
+            .iter()
+            .map(|u| u.username.as_str())
+            .collect();
+        assert_eq!(users, ["Alice", "Bob", "Caroline"]);
+
+        let battles = state.battles.read().unwrap();
+        let battle = &battles[ROOM];
+        let players: Vec<_> = battle
+            .players
+            .iter()
+            .map(|p| (p.username.as_str(), p.team_size))
+            .collect();
+        assert_eq!(players, [("Alice", 0), ("Bob", 6)]);
+        assert_eq!((battle.generation, battle.tier.as_str()), (9, "[Gen 9] OU"));
+        assert_eq!(battle.rules.len(), 1);
+        assert_eq!(battle.preview[0].species, "Rotom-Wash");
+        assert!(battle.started);
+        assert_eq!(battle.turn, 3);
+        assert_eq!(battle.winner.as_deref(), Some("Bob"));
+        assert_eq!(*state.server_time.read().unwrap(), Some(1700000000));
+    }
+
+    #[test]
+    fn test_apply_login_and_request() {
+        let state = joined();
+        assert!(!apply_to_state(&state, None, &parse("|updateuser| Guest 1|0|1|{}")).logged_in);
+        assert!(apply_to_state(&state, None, &parse("|updateuser| Kazam|1|1|{}")).logged_in);
+        assert!(!apply_to_state(&state, None, &parse("|updateuser| Kazam|1|2|{}")).logged_in);
+
+        let applied = apply_to_state(
+            &state,
+            Some(ROOM),
+            &parse("|request|{\"wait\":true,\"rqid\":3}"),
+        );
+        let request = applied.request.expect("request");
+        assert_eq!(request.rqid, Some(3));
+        assert!(!request.is_stale);
+        // Not routed to on_request outside a room
+        let outside = apply_to_state(&state, None, &parse("|request|{\"wait\":true,\"rqid\":4}"));
+        assert!(outside.request.is_none());
+    }
+}
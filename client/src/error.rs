@@ -0,0 +1,53 @@
+use thiserror::Error;
+
+use crate::settings::AccountSetting;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ClientError {
+    #[error("a choice for request {rqid} in {room} was already sent")]
+    AlreadyChosen { room: String, rqid: u64 },
+
+    #[error("could not undo the choice in {room}: {message}")]
+    UndoFailed { room: String, message: String },
+
+    #[error("the server refused {setting:?}: {message}")]
+    SettingRejected {
+        setting: AccountSetting,
+        message: String,
+    },
+
+    #[error("no reply from the server for {setting:?}")]
+    SettingUnconfirmed { setting: AccountSetting },
+
+    #[error("could not join {room} ({reason}): {message}")]
+    JoinFailed {
+        room: String,
+        reason: String,
+        message: String,
+    },
+}
+
+/// Why a websocket connection to a server couldn't be opened
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConnectError {
+    #[error("invalid server URL {url}: {message}")]
+    InvalidUrl { url: String, message: String },
+
+    #[error("could not resolve {host} (DNS lookup failed): {message}")]
+    Dns { host: String, message: String },
+
+    #[error("TLS handshake with {host} failed: {message}")]
+    Tls { host: String, message: String },
+
+    #[error("could not reach {url}: {message}")]
+    Unreachable { url: String, message: String },
+
+    #[error("{url} was not found (404); Showdown servers accept websockets at /showdown/websocket")]
+    NotFound { url: String },
+
+    #[error("{url} is not a websocket endpoint: {reason}")]
+    NotWebsocket { url: String, reason: String },
+
+    #[error("could not look up the sim server behind {url}: {message}")]
+    Lookup { url: String, message: String },
+}
@@ -1,27 +1,577 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
-use kazam_protocol::{BattleInfo, ClientCommand, ClientMessage};
-use tokio::sync::mpsc;
+use kazam_protocol::{
+    BattleInfo, ClientCommand, ClientMessage, FormatSection, RoomType, User, same_user, user_id,
+};
+use tokio::sync::{Notify, mpsc, oneshot};
+use tokio::time::Instant;
 
-use crate::room::RoomState;
+use crate::chat::ChatQueue;
+use crate::error::ClientError;
+use crate::intercept::DryRun;
+use crate::joins::JoinQueue;
+use crate::ladder::LadderUpdate;
+use crate::login::LoginClient;
+use crate::members::MemberIndex;
+use crate::room::{ChatLine, DEFAULT_CHAT_HISTORY, HISTORY_AGE_SECS, RoomState};
+use crate::settings::{AccountSetting, SettingReply};
+use crate::settle::SettleState;
+use crate::snapshot::{SnapshotCell, StateSnapshot};
+use crate::team_sheets::OtsPolicy;
+use crate::timer::{TimeBudget, TimerState};
+use crate::timing::{BattleOutcome, BattleTimings, TimingState};
 
-const LOGIN_URL: &str = "https://play.pokemonshowdown.com/api/login";
+/// How long to wait for the server to confirm an account setting
+const SETTING_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+type SettingWaiter = (AccountSetting, oneshot::Sender<Result<(), ClientError>>);
+
+/// Choice sent for a room's current request, cleared by the next request or turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingChoice {
+    pub rqid: u64,
+    /// An /undo was sent ahead of this choice and has not been rejected
+    pub undoing: bool,
+}
+
+/// Progress through a server restart announced by broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPhase {
+    #[default]
+    Running,
+    /// A restart was announced and the connection hasn't dropped yet
+    Announced,
+    /// The connection dropped and came back after an announcement
+    Reconnected,
+    /// The restarted server sent a challstr; waiting to log back in
+    Restarted,
+}
 
 pub struct ClientState {
-    pub rooms: RwLock<HashMap<String, RoomState>>,
-    pub battles: RwLock<HashMap<String, BattleInfo>>,
+    /// Copied on write with `Arc::make_mut` while a snapshot shares them
+    pub rooms: RwLock<HashMap<String, Arc<RoomState>>>,
+    /// Rooms each user is listed in, across all rooms
+    pub(crate) members: RwLock<MemberIndex>,
+    pub battles: RwLock<HashMap<String, Arc<BattleInfo>>>,
+    /// `rooms` and `battles` as handle readers see them
+    pub(crate) snapshot: SnapshotCell,
     pub logged_in: AtomicBool,
+    /// Name from the latest named |updateuser|
+    pub username: RwLock<Option<String>>,
+    /// Our latest ladder table row per format id
+    pub ratings: RwLock<HashMap<String, LadderUpdate>>,
+    /// Highest rqid we sent a choice for, per room
+    pub answered_rqids: RwLock<HashMap<String, u64>>,
+    /// Choice awaiting the next request, per room
+    pub pending_choices: RwLock<HashMap<String, PendingChoice>>,
+    pub timers: RwLock<HashMap<String, TimerState>>,
+    pub timings: RwLock<HashMap<String, TimingState>>,
+    /// Whether each battle's latest request frame has been fully dispatched
+    pub settling: RwLock<HashMap<String, SettleState>>,
+    /// Hold on_request until the rest of the request's frame is dispatched
+    pub defer_requests: AtomicBool,
+    /// How to answer open team sheets prompts
+    pub ots_policy: RwLock<OtsPolicy>,
+    /// Latest server time from |:|, the anchor for spotting old chat
+    pub server_time: RwLock<Option<i64>>,
+    /// Chat lines kept per room
+    pub chat_history_capacity: AtomicUsize,
+    /// User id → the id that user had when first seen renaming this session
+    pub aliases: RwLock<HashMap<String, String>>,
+    /// Hold back searches from an announced restart until we're logged back in
+    pub pause_matchmaking_on_restart: AtomicBool,
+    pub restart: RwLock<RestartPhase>,
+    /// Searches held back while matchmaking is paused
+    pub deferred_searches: RwLock<Vec<ClientMessage>>,
+    /// Latest requested account setting of each kind, re-applied after reconnecting
+    pub account_settings: RwLock<Vec<AccountSetting>>,
+    /// Settings waiting for the server's reply, oldest first
+    pub setting_waiters: RwLock<Vec<SettingWaiter>>,
+    /// Re-apply account settings at the next login
+    pub reapply_settings: AtomicBool,
+    /// Rooms waiting to be joined at the throttled rate
+    pub(crate) joins: RwLock<JoinQueue>,
+    /// Wakes the client when a join is queued
+    pub join_queued: Notify,
+    /// Chat waiting to be sent at each room's flood-safe rate
+    pub(crate) chat: RwLock<ChatQueue>,
+    /// Wakes the client when chat is queued
+    pub chat_queued: Notify,
+    /// Login server client, keeping the session across reconnects
+    pub login: RwLock<LoginClient>,
+    /// Latest |formats| list, kept across reconnects
+    pub formats: RwLock<Option<Vec<FormatSection>>>,
+    /// Messages a dry run kept instead of sending
+    pub dry_run: DryRun,
+    /// Stops the client's run loop
+    pub shutdown: Notify,
 }
 
 impl ClientState {
     pub fn new() -> Self {
         Self {
             rooms: RwLock::new(HashMap::new()),
+            members: RwLock::new(MemberIndex::new()),
             battles: RwLock::new(HashMap::new()),
+            snapshot: SnapshotCell::new(),
             logged_in: AtomicBool::new(false),
+            username: RwLock::new(None),
+            ratings: RwLock::new(HashMap::new()),
+            answered_rqids: RwLock::new(HashMap::new()),
+            pending_choices: RwLock::new(HashMap::new()),
+            timers: RwLock::new(HashMap::new()),
+            timings: RwLock::new(HashMap::new()),
+            settling: RwLock::new(HashMap::new()),
+            defer_requests: AtomicBool::new(false),
+            ots_policy: RwLock::new(OtsPolicy::default()),
+            server_time: RwLock::new(None),
+            chat_history_capacity: AtomicUsize::new(DEFAULT_CHAT_HISTORY),
+            aliases: RwLock::new(HashMap::new()),
+            pause_matchmaking_on_restart: AtomicBool::new(false),
+            restart: RwLock::new(RestartPhase::Running),
+            deferred_searches: RwLock::new(Vec::new()),
+            account_settings: RwLock::new(Vec::new()),
+            setting_waiters: RwLock::new(Vec::new()),
+            reapply_settings: AtomicBool::new(false),
+            joins: RwLock::new(JoinQueue::new()),
+            join_queued: Notify::new(),
+            chat: RwLock::new(ChatQueue::new()),
+            chat_queued: Notify::new(),
+            login: RwLock::new(LoginClient::new()),
+            formats: RwLock::new(None),
+            dry_run: DryRun::new(),
+            shutdown: Notify::new(),
+        }
+    }
+
+    /// Publish a room's current state and battle to handle readers
+    pub fn publish(&self, room_id: &str) {
+        if let (Ok(rooms), Ok(battles)) = (self.rooms.read(), self.battles.read()) {
+            self.snapshot
+                .update(room_id, rooms.get(room_id), battles.get(room_id));
+        }
+    }
+
+    /// Create the state for a room we just joined
+    pub fn new_room(&self, room_id: &str, room_type: RoomType) -> RoomState {
+        let mut room = RoomState::new(room_id, room_type);
+        room.set_history_capacity(self.chat_history_capacity.load(Ordering::Relaxed));
+        room
+    }
+
+    /// Store a chat line in its room's history, returns whether it is scrollback
+    ///
+    /// Lines count as history while the room's join burst is still being
+    /// delivered, or when their timestamp is well behind the server time anchor.
+    pub fn record_chat(
+        &self,
+        room_id: &str,
+        user: &User,
+        message: &str,
+        timestamp: Option<i64>,
+    ) -> bool {
+        let anchor = self.server_time.read().ok().and_then(|t| *t);
+        let old = matches!(
+            (timestamp, anchor),
+            (Some(ts), Some(now)) if now - ts > HISTORY_AGE_SECS
+        );
+
+        let Ok(mut rooms) = self.rooms.write() else {
+            return old;
+        };
+        let Some(room) = rooms.get_mut(room_id).map(Arc::make_mut) else {
+            return old;
+        };
+        let is_history = old || !room.is_live();
+        room.push_chat(ChatLine {
+            user: user.clone(),
+            message: message.to_string(),
+            timestamp,
+            is_history,
+        });
+        is_history
+    }
+
+    /// Store a |formats| list, returns false if it matches the one already known
+    pub fn record_formats(&self, sections: &[FormatSection]) -> bool {
+        let Ok(mut formats) = self.formats.write() else {
+            return true;
+        };
+        if formats.as_deref() == Some(sections) {
+            return false;
+        }
+        *formats = Some(sections.to_vec());
+        true
+    }
+
+    /// Keep the rows of a ladder table that are ours
+    pub fn record_ladder_updates(&self, updates: &[LadderUpdate]) {
+        let Some(username) = self.username.read().ok().and_then(|name| name.clone()) else {
+            return;
+        };
+        let Ok(mut ratings) = self.ratings.write() else {
+            return;
+        };
+        for update in updates.iter().filter(|update| update.is_for(&username)) {
+            ratings.insert(user_id(&update.format), update.clone());
+        }
+    }
+
+    /// Record a |n| rename so the new name resolves to the original user id
+    pub fn record_rename(&self, old_id: &str, new_name: &str) {
+        let (old, new) = (user_id(old_id), user_id(new_name));
+        if old == new {
+            return;
+        }
+        if let Ok(mut aliases) = self.aliases.write() {
+            let original = aliases.get(&old).cloned().unwrap_or(old);
+            aliases.insert(new, original);
+        }
+    }
+
+    /// User id a name had before any renames seen this session
+    pub fn original_userid(&self, name: &str) -> String {
+        let id = user_id(name);
+        self.aliases
+            .read()
+            .ok()
+            .and_then(|aliases| aliases.get(&id).cloned())
+            .unwrap_or(id)
+    }
+
+    /// Check if a request id is not newer than the last one we answered in a room
+    pub fn is_stale_rqid(&self, room_id: &str, rqid: u64) -> bool {
+        self.answered_rqids
+            .read()
+            .ok()
+            .and_then(|r| r.get(room_id).copied())
+            .is_some_and(|last| rqid <= last)
+    }
+
+    /// Record that a choice was sent for a request, returns false if it was stale
+    pub fn record_choice(&self, room_id: &str, rqid: u64) -> bool {
+        let Ok(mut answered) = self.answered_rqids.write() else {
+            return true;
+        };
+        let last = answered.entry(room_id.to_string()).or_insert(0);
+        let fresh = rqid > *last;
+        *last = (*last).max(rqid);
+        fresh
+    }
+
+    /// Check if a choice for `rqid` was sent and the room has not moved on yet
+    pub fn has_chosen(&self, room_id: &str, rqid: u64) -> bool {
+        self.pending_choices
+            .read()
+            .ok()
+            .and_then(|p| p.get(room_id).copied())
+            .is_some_and(|pending| pending.rqid == rqid)
+    }
+
+    /// Mark a choice as sent for a room's current request
+    pub fn mark_chosen(&self, room_id: &str, rqid: u64, undoing: bool) {
+        if let Ok(mut pending) = self.pending_choices.write() {
+            pending.insert(room_id.to_string(), PendingChoice { rqid, undoing });
+        }
+    }
+
+    /// Forget the pending choice once the next request or turn arrives
+    pub fn clear_pending_choice(&self, room_id: &str) {
+        if let Ok(mut pending) = self.pending_choices.write() {
+            pending.remove(room_id);
+        }
+    }
+
+    /// Update a room's timing state
+    pub fn with_timing(&self, room_id: &str, f: impl FnOnce(&mut TimingState)) {
+        if let Ok(mut timings) = self.timings.write() {
+            f(timings.entry(room_id.to_string()).or_default());
+        }
+    }
+
+    /// Update a room's settling state
+    pub fn with_settle<T>(
+        &self,
+        room_id: &str,
+        f: impl FnOnce(&mut SettleState) -> T,
+    ) -> Option<T> {
+        let mut settling = self.settling.write().ok()?;
+        Some(f(settling.entry(room_id.to_string()).or_default()))
+    }
+
+    /// Mark the end of a frame in `room_id`, waking anyone waiting for it
+    pub fn settle(&self, room_id: &str) {
+        if let Ok(mut settling) = self.settling.write()
+            && let Some(settle) = settling.get_mut(room_id)
+        {
+            settle.settle();
+        }
+    }
+
+    /// Forget a room's answered requests, pending choice, timer, timings and
+    /// settling state
+    pub fn clear_battle_progress(&self, room_id: &str) {
+        if let Ok(mut answered) = self.answered_rqids.write() {
+            answered.remove(room_id);
+        }
+        self.clear_pending_choice(room_id);
+        if let Ok(mut timers) = self.timers.write() {
+            timers.remove(room_id);
+        }
+        if let Ok(mut timings) = self.timings.write() {
+            timings.remove(room_id);
+        }
+        if let Ok(mut settling) = self.settling.write()
+            && let Some(mut settle) = settling.remove(room_id)
+        {
+            settle.settle();
+        }
+    }
+
+    /// Start over in a battle room whose previous game ended, returns whether
+    /// there was one; some servers reuse the room for a rematch
+    pub fn restart_ended_battle(&self, room_id: &str) -> bool {
+        let Ok(mut battles) = self.battles.write() else {
+            return false;
+        };
+        let Some(battle) = battles.get_mut(room_id).filter(|b| b.is_ended()) else {
+            return false;
+        };
+        *battle = Arc::new(BattleInfo::new());
+        drop(battles);
+        self.clear_battle_progress(room_id);
+        true
+    }
+
+    /// Close a battle's timings after |win| or |tie| and describe how it ended
+    pub fn end_battle(&self, room_id: &str) -> Option<BattleOutcome> {
+        let battle = BattleInfo::clone(self.battles.read().ok()?.get(room_id)?);
+        let mut timings = self.timings.write().ok()?;
+        let timing = timings.entry(room_id.to_string()).or_default();
+        timing.on_end();
+        let username = self.username.read().ok().and_then(|name| name.clone());
+        Some(BattleOutcome {
+            winner: battle.winner.clone(),
+            we_won: username.is_some_and(|name| battle.is_winner(&name)),
+            tie: battle.tie,
+            timings: timing.snapshot(),
+            battle,
+        })
+    }
+
+    fn restart_phase(&self) -> RestartPhase {
+        self.restart.read().map(|phase| *phase).unwrap_or_default()
+    }
+
+    /// Move from `from` to `to`, returns false if the restart was elsewhere
+    fn advance_restart(&self, from: RestartPhase, to: RestartPhase) -> bool {
+        let Ok(mut phase) = self.restart.write() else {
+            return false;
+        };
+        if *phase != from {
+            return false;
+        }
+        *phase = to;
+        true
+    }
+
+    /// Record a restart broadcast, returns false if one was already pending
+    pub fn announce_restart(&self) -> bool {
+        self.advance_restart(RestartPhase::Running, RestartPhase::Announced)
+    }
+
+    /// Record a cancelled restart, returns the searches to send now
+    pub fn cancel_restart(&self) -> Vec<ClientMessage> {
+        if self.advance_restart(RestartPhase::Announced, RestartPhase::Running) {
+            self.take_deferred_searches()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Record that the source reconnected; the new connection starts as a guest
+    pub fn on_reconnected(&self) {
+        self.logged_in.store(false, Ordering::Relaxed);
+        self.reapply_settings.store(true, Ordering::Relaxed);
+        // Joins sent on the old connection will never be answered
+        if let Ok(mut joins) = self.joins.write() {
+            joins.requeue_in_flight();
+        }
+        self.join_queued.notify_one();
+        self.advance_restart(RestartPhase::Announced, RestartPhase::Reconnected);
+    }
+
+    /// Record a challstr, returns true if it is the first from a restarted server
+    pub fn on_challstr(&self) -> bool {
+        self.advance_restart(RestartPhase::Reconnected, RestartPhase::Restarted)
+    }
+
+    /// Record a login, returns the searches held back across a restart
+    pub fn on_logged_in(&self) -> Vec<ClientMessage> {
+        if self.advance_restart(RestartPhase::Restarted, RestartPhase::Running) {
+            self.take_deferred_searches()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Remember a setting for reconnects, returns a receiver for the server's reply
+    pub fn record_setting(
+        &self,
+        setting: AccountSetting,
+    ) -> oneshot::Receiver<Result<(), ClientError>> {
+        if let Ok(mut settings) = self.account_settings.write() {
+            settings.retain(|s| s.kind() != setting.kind());
+            settings.push(setting.clone());
+        }
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut waiters) = self.setting_waiters.write() {
+            waiters.push((setting, tx));
+        }
+        rx
+    }
+
+    /// Pass a server reply to the oldest setting it answers
+    ///
+    /// A refused setting is forgotten so reconnects don't retry it.
+    pub(crate) fn resolve_setting(&self, reply: &SettingReply) {
+        let Ok(mut waiters) = self.setting_waiters.write() else {
+            return;
+        };
+        let Some(index) = waiters
+            .iter()
+            .position(|(setting, _)| setting.answered_by(reply))
+        else {
+            return;
+        };
+        let (setting, tx) = waiters.remove(index);
+        let result = match &reply.error {
+            Some(message) => {
+                if let Ok(mut settings) = self.account_settings.write() {
+                    settings.retain(|s| *s != setting);
+                }
+                Err(ClientError::SettingRejected {
+                    setting,
+                    message: message.clone(),
+                })
+            }
+            None => Ok(()),
+        };
+        // The caller may have stopped waiting
+        let _ = tx.send(result);
+    }
+
+    /// Commands re-applying account settings, once per reconnect
+    pub fn settings_to_reapply(&self) -> Vec<ClientMessage> {
+        if !self.reapply_settings.swap(false, Ordering::Relaxed) {
+            return Vec::new();
+        }
+        self.account_settings
+            .read()
+            .map(|settings| {
+                settings
+                    .iter()
+                    .map(|setting| ClientMessage {
+                        room_id: None,
+                        command: setting.command(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// When the next queued join may be sent, None if nothing is queued
+    pub fn next_join_due(&self) -> Option<Instant> {
+        self.joins.read().ok()?.next_due()
+    }
+
+    /// Take the next room to join if one is due
+    pub fn pop_join(&self) -> Option<String> {
+        self.joins.write().ok()?.pop_due(Instant::now())
+    }
+
+    /// Give back a join an interceptor dropped, failing anyone waiting on it
+    pub fn cancel_join(&self, room_id: &str, reason: &str) {
+        if let Ok(mut joins) = self.joins.write() {
+            joins.cancel(room_id, reason);
+        }
+    }
+
+    /// Wait until a queued join is due to be sent
+    pub async fn join_due(&self) {
+        loop {
+            match self.next_join_due() {
+                Some(due) => {
+                    // A battle queued meanwhile doesn't change when the next join is due
+                    tokio::time::sleep_until(due).await;
+                    return;
+                }
+                None => self.join_queued.notified().await,
+            }
+        }
+    }
+
+    /// Settle a join with the server's |init| or |noinit|
+    pub fn resolve_join(&self, room_id: &str, result: Result<(), ClientError>) {
+        if let Ok(mut joins) = self.joins.write() {
+            joins.resolve(room_id, result);
+        }
+    }
+
+    /// Take the next chat message if one is due
+    pub fn pop_chat(&self) -> Option<ClientMessage> {
+        self.chat.write().ok()?.pop_due(Instant::now())
+    }
+
+    /// Take all queued chat, to send without pacing
+    pub fn take_all_chat(&self) -> Vec<ClientMessage> {
+        self.chat
+            .write()
+            .map(|mut chat| chat.take_all())
+            .unwrap_or_default()
+    }
+
+    /// Wait until a queued chat message is due to be sent
+    pub async fn chat_due(&self) {
+        loop {
+            match self.chat.read().ok().and_then(|chat| chat.next_due()) {
+                Some(due) => {
+                    tokio::time::sleep_until(due).await;
+                    return;
+                }
+                None => self.chat_queued.notified().await,
+            }
+        }
+    }
+
+    /// Whether searches are being held back for a restart
+    pub fn matchmaking_paused(&self) -> bool {
+        self.pause_matchmaking_on_restart.load(Ordering::Relaxed)
+            && self.restart_phase() != RestartPhase::Running
+    }
+
+    fn take_deferred_searches(&self) -> Vec<ClientMessage> {
+        self.deferred_searches
+            .write()
+            .map(|mut searches| std::mem::take(&mut *searches))
+            .unwrap_or_default()
+    }
+
+    /// Claim an /undo rejection for a room, returns false if no undo was in flight
+    pub fn take_undo_failure(&self, room_id: &str) -> bool {
+        let Ok(mut pending) = self.pending_choices.write() else {
+            return false;
+        };
+        match pending.get_mut(room_id) {
+            Some(choice) if choice.undoing => {
+                choice.undoing = false;
+                true
+            }
+            _ => false,
         }
     }
 }
@@ -43,8 +593,19 @@ impl KazamHandle {
             .map_err(|_| anyhow!("Client disconnected"))
     }
 
+    /// Log in for `challstr`
+    ///
+    /// Goes through [`LoginClient::resume_or_login`], so calling this again
+    /// with the challstr of a reconnected connection refreshes the existing
+    /// session instead of posting the password.
     pub async fn login(&self, username: &str, password: &str, challstr: &str) -> Result<()> {
-        let assertion = get_assertion(username, password, challstr).await?;
+        let login = self
+            .state
+            .login
+            .read()
+            .map_err(|_| anyhow!("Login client unavailable"))?
+            .clone();
+        let assertion = login.resume_or_login(username, password, challstr).await?;
         self.send(ClientMessage {
             room_id: Some(String::new()),
             command: ClientCommand::TrustedLogin {
@@ -54,11 +615,47 @@ impl KazamHandle {
         })
     }
 
+    /// Queue a join for `room`
+    ///
+    /// Joins go out one per join interval (see
+    /// [`KazamClient::set_join_interval`](crate::KazamClient::set_join_interval)),
+    /// battle rooms first. Joining a room that's already queued does nothing.
     pub fn join_room(&self, room: &str) -> Result<()> {
-        self.send(ClientMessage {
-            room_id: None,
-            command: ClientCommand::JoinRoom(room.to_string()),
-        })
+        if self.tx.is_closed() {
+            return Err(anyhow!("Client disconnected"));
+        }
+        if let Ok(mut joins) = self.state.joins.write() {
+            joins.push(room);
+        }
+        self.state.join_queued.notify_one();
+        Ok(())
+    }
+
+    /// Queue a join for `room` and wait for the server to let us in
+    ///
+    /// Fails with [`ClientError::JoinFailed`] if the server refuses the join.
+    pub fn join_room_wait(&self, room: &str) -> impl Future<Output = Result<()>> + Send + use<> {
+        let answer = self
+            .state
+            .joins
+            .write()
+            .ok()
+            .map(|mut joins| joins.push_waiting(room));
+        self.state.join_queued.notify_one();
+        async move {
+            let answer = answer.ok_or_else(|| anyhow!("Join queue unavailable"))?;
+            answer.await.map_err(|_| anyhow!("Client disconnected"))??;
+            Ok(())
+        }
+    }
+
+    /// Rooms we asked to join and haven't been let into yet, in send order
+    pub fn pending_joins(&self) -> Vec<String> {
+        self.state
+            .joins
+            .read()
+            .map(|joins| joins.pending())
+            .unwrap_or_default()
     }
 
     pub fn leave_room(&self, room: &str) -> Result<()> {
@@ -68,11 +665,53 @@ impl KazamHandle {
         })
     }
 
+    /// Queue a chat message for `room`
+    ///
+    /// Each room sends one message per chat interval (see
+    /// [`KazamClient::set_chat_interval`](crate::KazamClient::set_chat_interval)).
+    /// Lines of a multi-line message go out separately, and lines over
+    /// [`MAX_CHAT_LENGTH`](crate::MAX_CHAT_LENGTH) are split with continuation
+    /// markers. Battle choices skip the queue.
     pub fn send_chat(&self, room: &str, message: &str) -> Result<()> {
-        self.send(ClientMessage {
-            room_id: Some(room.to_string()),
-            command: ClientCommand::Chat(message.to_string()),
-        })
+        if self.tx.is_closed() {
+            return Err(anyhow!("Client disconnected"));
+        }
+        if let Ok(mut chat) = self.state.chat.write() {
+            chat.push(room, message);
+        }
+        self.state.chat_queued.notify_one();
+        Ok(())
+    }
+
+    /// Chat messages waiting to be sent in `room`
+    pub fn chat_queue_depth(&self, room: &str) -> usize {
+        self.state
+            .chat
+            .read()
+            .map(|chat| chat.depth(room))
+            .unwrap_or_default()
+    }
+
+    /// Send everything queued for `room` now, ignoring the chat interval
+    pub fn flush_chat(&self, room: &str) -> Result<()> {
+        let messages = self
+            .state
+            .chat
+            .write()
+            .map(|mut chat| chat.take(room))
+            .unwrap_or_default();
+        messages
+            .into_iter()
+            .try_for_each(|message| self.send(message))
+    }
+
+    /// Drop the chat queued for `room`, returns how many messages were dropped
+    pub fn clear_chat(&self, room: &str) -> usize {
+        self.state
+            .chat
+            .write()
+            .map(|mut chat| chat.clear(room))
+            .unwrap_or_default()
     }
 
     pub fn send_raw(&self, message: &str) -> Result<()> {
@@ -82,21 +721,151 @@ impl KazamHandle {
         })
     }
 
+    /// Search for a battle
+    ///
+    /// Queued instead of sent while matchmaking is paused for a server restart
+    /// (see [`is_matchmaking_paused`](Self::is_matchmaking_paused)).
     pub fn search(&self, format: &str) -> Result<()> {
-        self.send(ClientMessage {
+        let message = ClientMessage {
             room_id: None,
             command: ClientCommand::Search(format.to_string()),
-        })
+        };
+        if self.state.matchmaking_paused()
+            && let Ok(mut deferred) = self.state.deferred_searches.write()
+        {
+            deferred.push(message);
+            return Ok(());
+        }
+        self.send(message)
     }
 
+    /// Cancel a search, including one held back for a server restart
     pub fn cancel_search(&self) -> Result<()> {
+        if let Ok(mut deferred) = self.state.deferred_searches.write() {
+            deferred.clear();
+        }
         self.send(ClientMessage {
             room_id: None,
             command: ClientCommand::CancelSearch,
         })
     }
 
+    /// Refuse incoming challenges, resolving once the server confirms
+    pub fn block_challenges(&self) -> impl Future<Output = Result<()>> + Send + use<> {
+        self.apply_setting(AccountSetting::BlockChallenges(true))
+    }
+
+    /// Accept incoming challenges again, resolving once the server confirms
+    pub fn allow_challenges(&self) -> impl Future<Output = Result<()>> + Send + use<> {
+        self.apply_setting(AccountSetting::BlockChallenges(false))
+    }
+
+    /// Only accept PMs from staff and users at `level` ("+", "autoconfirmed",
+    /// "friends", or "" for staff only), resolving once the server confirms
+    pub fn block_pms(&self, level: &str) -> impl Future<Output = Result<()>> + Send + use<> {
+        self.apply_setting(AccountSetting::BlockPms(Some(level.to_string())))
+    }
+
+    /// Accept PMs from everyone again, resolving once the server confirms
+    pub fn allow_pms(&self) -> impl Future<Output = Result<()>> + Send + use<> {
+        self.apply_setting(AccountSetting::BlockPms(None))
+    }
+
+    /// Keep our battles off the room list, resolving once the server confirms
+    pub fn set_hidden_room_battles(
+        &self,
+        hidden: bool,
+    ) -> impl Future<Output = Result<()>> + Send + use<> {
+        self.apply_setting(AccountSetting::HideBattles(hidden))
+    }
+
+    /// Send a setting and wait for its reply
+    ///
+    /// The setting is recorded and re-applied after every reconnect. The reply
+    /// is registered for before anything is sent, so it can't be missed.
+    fn apply_setting(
+        &self,
+        setting: AccountSetting,
+    ) -> impl Future<Output = Result<()>> + Send + use<> {
+        let reply = self.state.record_setting(setting.clone());
+        let sent = self.send(ClientMessage {
+            room_id: None,
+            command: setting.command(),
+        });
+        async move {
+            sent?;
+            match tokio::time::timeout(SETTING_REPLY_TIMEOUT, reply).await {
+                Ok(Ok(result)) => Ok(result?),
+                Ok(Err(_)) | Err(_) => Err(ClientError::SettingUnconfirmed { setting }.into()),
+            }
+        }
+    }
+
+    /// Account settings that will be re-applied after reconnecting
+    pub fn account_settings(&self) -> Vec<AccountSetting> {
+        self.state
+            .account_settings
+            .read()
+            .map(|settings| settings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Send a battle choice.
+    ///
+    /// Fails with [`ClientError::AlreadyChosen`] if a choice for `rqid` was already
+    /// sent and no new request or turn has arrived since. Warns when `rqid` is
+    /// older than a request we already answered in this room.
     pub fn choose(&self, room: &str, choice: &str, rqid: Option<u64>) -> Result<()> {
+        if let Some(id) = rqid
+            && self.state.has_chosen(room, id) {
+                return Err(ClientError::AlreadyChosen {
+                    room: room.to_string(),
+                    rqid: id,
+                }
+                .into());
+            }
+        if let Some(id) = rqid
+            && !self.state.record_choice(room, id) {
+                tracing::warn!(room, rqid = id, "Sending choice for a stale request");
+            }
+        self.send_choice(room, choice, rqid, false)
+    }
+
+    /// Send a battle choice, replacing one already sent for the same request.
+    ///
+    /// Sends /undo first when a choice for `rqid` is pending. Undo only works
+    /// until the opponent has chosen; a rejected undo is reported through
+    /// `KazamHandler::on_choice_error` and the original choice stands.
+    pub fn choose_replace(&self, room: &str, choice: &str, rqid: Option<u64>) -> Result<()> {
+        let undoing = rqid.is_some_and(|id| self.state.has_chosen(room, id));
+        if undoing {
+            self.send(ClientMessage {
+                room_id: Some(room.to_string()),
+                command: ClientCommand::Undo,
+            })?;
+        } else if let Some(id) = rqid
+            && !self.state.record_choice(room, id) {
+                tracing::warn!(room, rqid = id, "Sending choice for a stale request");
+            }
+        self.send_choice(room, choice, rqid, undoing)
+    }
+
+    fn send_choice(
+        &self,
+        room: &str,
+        choice: &str,
+        rqid: Option<u64>,
+        undoing: bool,
+    ) -> Result<()> {
+        if let Some(id) = rqid {
+            self.state.mark_chosen(room, id, undoing);
+            self.state.with_timing(room, |timing| timing.on_choice(id));
+        }
+        if let Ok(mut timers) = self.state.timers.write()
+            && let Some(timer) = timers.get_mut(room)
+        {
+            timer.on_choice();
+        }
         self.send(ClientMessage {
             room_id: Some(room.to_string()),
             command: ClientCommand::Choose {
@@ -120,65 +889,548 @@ impl KazamHandle {
         })
     }
 
+    /// Accept or reject open team sheets in a battle at team preview
+    pub fn answer_open_team_sheets(&self, room: &str, accept: bool) -> Result<()> {
+        self.send(ClientMessage {
+            room_id: Some(room.to_string()),
+            command: ClientCommand::OpenTeamSheets(accept),
+        })
+    }
+
+    /// Make [`KazamClient::run`](crate::KazamClient::run) return
+    ///
+    /// The frame being handled is finished and commands already queued, such
+    /// as a forfeit, are still sent first.
+    pub fn shutdown(&self) {
+        self.state.shutdown.notify_one();
+    }
+
+    /// Check if a choice for `rqid` was already sent in a room
+    pub fn has_chosen(&self, room_id: &str, rqid: u64) -> bool {
+        self.state.has_chosen(room_id, rqid)
+    }
+
     pub fn is_logged_in(&self) -> bool {
         self.state.logged_in.load(Ordering::Relaxed)
     }
 
+    /// Whether [`search`](Self::search) is holding searches back for an announced restart
+    ///
+    /// Only with `pause_matchmaking_on_restart_announcement` enabled on the client.
+    /// Matchmaking resumes once we log in to the restarted server, or when the
+    /// restart is cancelled.
+    pub fn is_matchmaking_paused(&self) -> bool {
+        self.state.matchmaking_paused()
+    }
+
+    /// Rooms and battles as of the latest dispatched message
+    ///
+    /// Loading takes no lock the client's dispatch loop waits on, so it's
+    /// cheap enough to call every rendered frame. The snapshot never changes;
+    /// call again for newer state.
+    pub fn snapshot(&self) -> Arc<StateSnapshot> {
+        self.state.snapshot.load()
+    }
+
     pub fn get_room(&self, room_id: &str) -> Option<RoomState> {
-        self.state.rooms.read().ok()?.get(room_id).cloned()
+        self.snapshot().room(room_id).cloned()
     }
 
-    pub fn rooms(&self) -> Vec<String> {
+    /// Recent chat in a room, oldest first
+    pub fn chat_history(&self, room_id: &str) -> Vec<ChatLine> {
+        self.snapshot()
+            .room(room_id)
+            .map(|r| r.chat_history().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Our latest ladder table row for `format`, e.g. `"gen9ou"` or `"[Gen 9] OU"`
+    pub fn rating(&self, format: &str) -> Option<LadderUpdate> {
         self.state
-            .rooms
+            .ratings
             .read()
-            .map(|r| r.keys().cloned().collect())
-            .unwrap_or_default()
+            .ok()?
+            .get(&user_id(format))
+            .cloned()
+    }
+
+    /// Formats from the latest |formats|, None before the server sent any
+    pub fn formats(&self) -> Option<Vec<FormatSection>> {
+        self.state.formats.read().ok()?.clone()
+    }
+
+    /// What a [dry run](crate::KazamClient::dry_run) would have sent so far, oldest first
+    pub fn dry_run_messages(&self) -> Vec<ClientMessage> {
+        self.state.dry_run.messages()
+    }
+
+    pub fn rooms(&self) -> Vec<String> {
+        self.snapshot()
+            .rooms()
+            .map(|(id, _)| id.to_string())
+            .collect()
     }
 
     pub fn in_room(&self, room_id: &str) -> bool {
+        self.snapshot().room(room_id).is_some()
+    }
+
+    /// Chat rooms we're in that list `username`, sorted
+    ///
+    /// Battle rooms are left out, since an opponent is always in theirs.
+    /// Names match by user id, so case and punctuation don't matter.
+    pub fn shared_rooms_with(&self, username: &str) -> Vec<String> {
+        let Ok(members) = self.state.members.read() else {
+            return Vec::new();
+        };
+        let snapshot = self.snapshot();
+        members
+            .rooms_of(username)
+            .filter(|id| {
+                snapshot
+                    .room(id)
+                    .is_some_and(|r| r.room_type == RoomType::Chat)
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whether `room_id`'s user list has `username`, by user id
+    pub fn is_room_member(&self, room_id: &str, username: &str) -> bool {
         self.state
-            .rooms
+            .members
             .read()
-            .map(|r| r.contains_key(room_id))
-            .unwrap_or(false)
+            .is_ok_and(|members| members.is_member(room_id, username))
     }
 
     pub fn get_battle(&self, room_id: &str) -> Option<BattleInfo> {
-        self.state.battles.read().ok()?.get(room_id).cloned()
+        self.snapshot().battle(room_id).cloned()
     }
 
     pub fn in_battle(&self, room_id: &str) -> bool {
+        self.snapshot().battle(room_id).is_some()
+    }
+
+    /// Remaining battle timer budget for our current decision.
+    ///
+    /// None if the timer is off or no timer messages were seen in the room.
+    pub fn time_budget(&self, room_id: &str) -> Option<TimeBudget> {
+        self.state.timers.read().ok()?.get(room_id)?.budget()
+    }
+
+    /// Wait until the frame holding the room's latest request has been applied
+    /// to client state and passed to every handler callback
+    ///
+    /// Resolves right away when no request frame is being dispatched. Awaiting
+    /// this inside a handler callback never resolves, since the frame can't
+    /// finish until the callback returns; spawn a task, or use
+    /// [`KazamClient::set_defer_request_until_settled`](crate::KazamClient::set_defer_request_until_settled)
+    /// instead.
+    pub fn wait_for_settled(&self, room_id: &str) -> impl Future<Output = Result<()>> + Send + use<> {
+        let settled = self.state.with_settle(room_id, |settle| settle.wait());
+        async move {
+            let settled = settled.ok_or_else(|| anyhow!("Settling state unavailable"))?;
+            settled.await.map_err(|_| anyhow!("Client disconnected"))
+        }
+    }
+
+    /// Whether the frame holding the room's latest request has been fully dispatched
+    pub fn is_settled(&self, room_id: &str) -> bool {
+        self.state
+            .settling
+            .read()
+            .ok()
+            .and_then(|s| s.get(room_id).map(|settle| settle.is_settled()))
+            .unwrap_or(true)
+    }
+
+    /// Whether an |upkeep| or |turn| arrived since the room's latest request
+    ///
+    /// Showdown usually sends a request in its own frame, just before the log
+    /// that resolves the previous turn; until this is true, that log is still
+    /// to come.
+    pub fn turn_resolved_since_request(&self, room_id: &str) -> bool {
         self.state
-            .battles
+            .settling
             .read()
-            .map(|b| b.contains_key(room_id))
+            .ok()
+            .and_then(|s| s.get(room_id).map(|settle| settle.boundary_seen()))
             .unwrap_or(false)
     }
+
+    /// User id a name had before any renames seen this session
+    ///
+    /// Lets challenge whitelists keyed by user id keep matching a user who renamed.
+    pub fn original_userid(&self, name: &str) -> String {
+        self.state.original_userid(name)
+    }
+
+    /// Start time, turn durations and decision latencies recorded for a battle
+    pub fn battle_timings(&self, room_id: &str) -> Option<BattleTimings> {
+        Some(self.state.timings.read().ok()?.get(room_id)?.snapshot())
+    }
+
+    /// Number of spectators in a battle room.
+    ///
+    /// Excludes the battle's players and bots (rank `*`) that are present in the user list.
+    pub fn spectator_count(&self, room_id: &str) -> Option<usize> {
+        let snapshot = self.snapshot();
+        let room = snapshot.room(room_id)?;
+        let battle = snapshot.battle(room_id)?;
+        Some(spectators_in(room, battle))
+    }
+
+    /// Find the tracked battle with the most spectators.
+    ///
+    /// Ties are broken by room id so the result is stable.
+    pub fn most_watched_battle(&self) -> Option<(String, usize)> {
+        let snapshot = self.snapshot();
+        snapshot
+            .battles()
+            .filter_map(|(id, battle)| {
+                let room = snapshot.room(id)?;
+                Some((id.to_string(), spectators_in(room, battle)))
+            })
+            .max_by(|(a_id, a), (b_id, b)| a.cmp(b).then_with(|| b_id.cmp(a_id)))
+    }
+}
+
+fn spectators_in(room: &RoomState, battle: &BattleInfo) -> usize {
+    let excluded = room
+        .users
+        .iter()
+        .filter(|u| {
+            u.rank == '*'
+                || battle
+                    .players
+                    .iter()
+                    .any(|p| same_user(&p.username, &u.username))
+        })
+        .count();
+    room.user_count().saturating_sub(excluded)
 }
 
-async fn get_assertion(username: &str, password: &str, challstr: &str) -> Result<String> {
-    let client = reqwest::Client::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{BattleRequest, Player, ServerMessage};
 
-    let params = [
-        ("name", username),
-        ("pass", password),
-        ("challstr", challstr),
-    ];
+    fn setup() -> KazamHandle {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let handle = KazamHandle::new(tx, Arc::new(ClientState::new()));
+        for (id, p1, p2) in [
+            ("battle-gen9ou-1", "Alice", "Bob"),
+            ("battle-gen9ou-2", "Carol", "Dave"),
+        ] {
+            feed(
+                &handle,
+                id,
+                &[
+                    "|init|battle",
+                    &format!("|player|p1|{p1}|1|"),
+                    &format!("|player|p2|{p2}|2|"),
+                    &format!("|users|2, {p1}, {p2}"),
+                ],
+            );
+        }
+        handle
+    }
+
+    /// Apply protocol lines to a room as `dispatch_frame` does, then publish it
+    fn feed(handle: &KazamHandle, room_id: &str, lines: &[&str]) {
+        for line in lines {
+            let message = kazam_protocol::parse_server_message(line).unwrap();
+            crate::dispatch::apply_to_state(&handle.state, Some(room_id), &message);
+        }
+        handle.state.publish(room_id);
+    }
 
-    let response = client.post(LOGIN_URL).form(&params).send().await?;
-    let text = response.text().await?;
+    fn set_winner(handle: &KazamHandle, room_id: &str, winner: &str) {
+        let mut battles = handle.state.battles.write().unwrap();
+        Arc::make_mut(battles.get_mut(room_id).unwrap()).winner = Some(winner.to_string());
+    }
 
-    // Response is prefixed with "]"
-    let json_str = text.trim_start_matches(']');
-    let json: serde_json::Value = serde_json::from_str(json_str)?;
+    #[test]
+    fn test_duplicated_request_is_stale() {
+        let handle = setup();
+        let room = "battle-gen9ou-1";
+        let json: serde_json::Value =
+            serde_json::from_str(r#"{"wait":true,"noCancel":false,"rqid":3}"#).unwrap();
 
-    if let Some(assertion) = json.get("assertion").and_then(|v| v.as_str()) {
-        if let Some(error_msg) = assertion.strip_prefix(";;") {
-            return Err(anyhow!("Login failed: {}", error_msg));
+        // rqid 3 arrives, we answer it, then the server re-sends it
+        let mut sequence = Vec::new();
+        for rqid in [3, 3, 4] {
+            let mut request = BattleRequest::parse(&json).unwrap();
+            request.rqid = Some(rqid);
+            request.is_stale = handle.state.is_stale_rqid(room, rqid);
+            sequence.push(request.is_stale);
+            // The receiver is dropped in setup, so only the bookkeeping matters here
+            let _ = handle.choose(room, "default", request.rqid);
         }
-        Ok(assertion.to_string())
-    } else {
-        Err(anyhow!("Login response missing assertion"))
+        assert_eq!(sequence, vec![false, true, false]);
+
+        // Other rooms are tracked independently
+        assert!(!handle.state.is_stale_rqid("battle-gen9ou-2", 3));
+        assert!(handle.state.is_stale_rqid(room, 4));
+        assert!(!handle.state.is_stale_rqid(room, 5));
+    }
+
+    fn sent(rx: &mut mpsc::UnboundedReceiver<ClientMessage>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|msg| msg.command.to_protocol_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicate_choose_is_rejected() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = KazamHandle::new(tx, Arc::new(ClientState::new()));
+        let room = "battle-gen9ou-1";
+
+        handle.choose(room, "move 1", Some(5)).unwrap();
+        assert!(handle.has_chosen(room, 5));
+        assert!(!handle.has_chosen(room, 4));
+        assert!(!handle.has_chosen("battle-gen9ou-2", 5));
+
+        let err = handle.choose(room, "move 2", Some(5)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ClientError>(),
+            Some(&ClientError::AlreadyChosen {
+                room: room.to_string(),
+                rqid: 5
+            })
+        );
+        assert_eq!(sent(&mut rx), vec!["/choose move 1|5"]);
+    }
+
+    #[test]
+    fn test_choose_replace_undoes_first() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = KazamHandle::new(tx, Arc::new(ClientState::new()));
+        let room = "battle-gen9ou-1";
+
+        // Nothing to replace yet, so it is a plain choose
+        handle.choose_replace(room, "move 1", Some(5)).unwrap();
+        handle.choose_replace(room, "switch 2", Some(5)).unwrap();
+        assert_eq!(
+            sent(&mut rx),
+            vec!["/choose move 1|5", "/undo", "/choose switch 2|5"]
+        );
+        assert!(handle.has_chosen(room, 5));
+
+        // A rejected undo is claimed once
+        assert!(handle.state.take_undo_failure(room));
+        assert!(!handle.state.take_undo_failure(room));
+    }
+
+    #[test]
+    fn test_next_request_clears_pending_choice() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = KazamHandle::new(tx, Arc::new(ClientState::new()));
+        let room = "battle-gen9ou-1";
+
+        handle.choose(room, "move 1", Some(5)).unwrap();
+        // What dispatch_frame does when |request| or |turn| arrives
+        handle.state.clear_pending_choice(room);
+        assert!(!handle.has_chosen(room, 5));
+
+        handle.choose(room, "move 2", Some(6)).unwrap();
+        assert_eq!(sent(&mut rx), vec!["/choose move 1|5", "/choose move 2|6"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_choose_records_decision_latency() {
+        let handle = setup();
+        let room = "battle-gen9ou-1";
+        let second = std::time::Duration::from_secs(1);
+
+        // What dispatch_frame does for |start|, |turn| and |request|
+        handle.state.with_timing(room, |timing| timing.on_start());
+        handle.state.with_timing(room, |timing| timing.on_turn(1));
+        handle.state.with_timing(room, |timing| timing.on_request(Some(2)));
+        tokio::time::advance(3 * second).await;
+        let _ = handle.choose(room, "move 1", Some(2));
+        tokio::time::advance(7 * second).await;
+
+        set_winner(&handle, room, "Alice");
+        let outcome = handle.state.end_battle(room).unwrap();
+        assert_eq!(outcome.winner.as_deref(), Some("Alice"));
+        assert_eq!(outcome.timings.decision_latencies[&2], 3 * second);
+        assert_eq!(outcome.timings.turn_durations[&1], 10 * second);
+        assert_eq!(outcome.timings.length, Some(10 * second));
+        assert_eq!(handle.battle_timings(room), Some(outcome.timings));
+        assert_eq!(handle.battle_timings("battle-gen9ou-2"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_choose_stops_timer_clock() {
+        let handle = setup();
+        let room = "battle-gen9ou-1";
+        let second = std::time::Duration::from_secs(1);
+        let request = |handle: &KazamHandle| {
+            let mut timers = handle.state.timers.write().unwrap();
+            timers.entry(room.to_string()).or_default().on_request();
+        };
+
+        // What dispatch_frame does for |inactive| and |request|
+        handle
+            .state
+            .timers
+            .write()
+            .unwrap()
+            .entry(room.to_string())
+            .or_default()
+            .on_inactive("Time left: 60 sec this turn | 100 sec total");
+        request(&handle);
+        tokio::time::advance(10 * second).await;
+        let _ = handle.choose(room, "move 1", Some(2));
+
+        // A long wait on the opponent leaves our bank alone
+        tokio::time::advance(200 * second).await;
+        request(&handle);
+        let budget = handle.time_budget(room).unwrap();
+        assert_eq!(budget.remaining_this_turn(), 60 * second);
+    }
+
+    #[test]
+    fn test_we_won_compares_user_ids() {
+        let handle = setup();
+        let room = "battle-gen9ou-1";
+        *handle.state.username.write().unwrap() = Some("alice".to_string());
+
+        set_winner(&handle, room, "☆Alice");
+        assert!(handle.state.end_battle(room).unwrap().we_won);
+        set_winner(&handle, room, "Bob");
+        assert!(!handle.state.end_battle(room).unwrap().we_won);
+    }
+
+    #[test]
+    fn test_rename_keeps_alias_chain_and_battle_players() {
+        let handle = setup();
+        let room = "battle-gen9ou-1";
+
+        // What dispatch_frame does for |n| in a battle room
+        for (old_id, new_name) in [("bob", "Bobby"), ("bobby", "☆Robert")] {
+            handle.state.record_rename(old_id, new_name);
+            let mut battles = handle.state.battles.write().unwrap();
+            Arc::make_mut(battles.get_mut(room).unwrap()).rename_player(old_id, new_name);
+        }
+        set_winner(&handle, room, "☆Robert");
+        handle.state.publish(room);
+
+        let battle = handle.get_battle(room).unwrap();
+        assert_eq!(battle.player_by_name("robert").unwrap().player, Player::P2);
+        assert!(battle.is_winner("Robert"));
+        assert!(!battle.is_winner("Alice"));
+
+        // A rematch challenge from the new name still maps to the whitelisted id
+        assert_eq!(handle.original_userid("Robert"), "bob");
+        assert_eq!(handle.original_userid("Bobby"), "bob");
+        assert_eq!(handle.original_userid("Alice"), "alice");
+    }
+
+    #[test]
+    fn test_spectator_count_excludes_players_and_bots() {
+        let handle = setup();
+        assert_eq!(handle.spectator_count("battle-gen9ou-1"), Some(0));
+
+        feed(&handle, "battle-gen9ou-1", &["|j| Eve", "|j|*ShowdownBot"]);
+        assert_eq!(handle.spectator_count("battle-gen9ou-1"), Some(1));
+        assert_eq!(handle.spectator_count("lobby"), None);
+    }
+
+    #[test]
+    fn test_most_watched_battle_ranking_flips() {
+        let handle = setup();
+
+        feed(&handle, "battle-gen9ou-1", &["|j| Eve", "|j| Frank"]);
+        feed(&handle, "battle-gen9ou-2", &["|j| Grace"]);
+        assert_eq!(
+            handle.most_watched_battle(),
+            Some(("battle-gen9ou-1".to_string(), 2))
+        );
+
+        feed(&handle, "battle-gen9ou-1", &["|l| Eve", "|l| Frank"]);
+        feed(&handle, "battle-gen9ou-2", &["|j| Heidi"]);
+        assert_eq!(
+            handle.most_watched_battle(),
+            Some(("battle-gen9ou-2".to_string(), 2))
+        );
+    }
+
+    #[test]
+    fn test_room_usercount_overrides_listed_users() {
+        let handle = setup();
+        feed(&handle, "battle-gen9ou-2", &["|usercount|10"]);
+        assert_eq!(handle.spectator_count("battle-gen9ou-2"), Some(8));
+        assert_eq!(
+            handle.most_watched_battle(),
+            Some(("battle-gen9ou-2".to_string(), 8))
+        );
+    }
+
+    /// Room bookkeeping done by `dispatch_frame`, returns the history flag of each chat line
+    fn deliver(state: &ClientState, raw: &str) -> Vec<bool> {
+        let frame = kazam_protocol::parse_server_frame(raw).unwrap();
+        let rid = frame.room_id.unwrap();
+        let mut joined = false;
+        let mut flags = Vec::new();
+        for message in frame.messages {
+            match message {
+                ServerMessage::Init(room_type) => {
+                    let room = state.new_room(&rid, room_type);
+                    let mut rooms = state.rooms.write().unwrap();
+                    rooms.insert(rid.clone(), Arc::new(room));
+                }
+                ServerMessage::Users(users) => {
+                    joined = true;
+                    let mut rooms = state.rooms.write().unwrap();
+                    Arc::make_mut(rooms.get_mut(&rid).unwrap()).set_users(users);
+                }
+                ServerMessage::Timestamp(ts) => *state.server_time.write().unwrap() = Some(ts),
+                ServerMessage::Chat {
+                    user,
+                    message,
+                    timestamp,
+                } => flags.push(state.record_chat(&rid, &user, &message, timestamp)),
+                _ => {}
+            }
+        }
+        if joined {
+            let mut rooms = state.rooms.write().unwrap();
+            Arc::make_mut(rooms.get_mut(&rid).unwrap()).mark_live();
+        }
+        state.publish(&rid);
+        flags
+    }
+
+    #[test]
+    fn test_join_scrollback_is_history() {
+        let state = Arc::new(ClientState::new());
+        state.chat_history_capacity.store(3, Ordering::Relaxed);
+
+        let flags = deliver(
+            &state,
+            ">lobby\n|init|chat\n|title|Lobby\n|users|2, Alice,+Bob\n|:|1700000000\n\
+             |c:|1699990000| Alice|!yo\n|c:|1699995000|+Bob|hello\n|c:|1699999990| Alice|!yo",
+        );
+        // The last line is recent but still part of the join burst
+        assert_eq!(flags, vec![true, true, true]);
+
+        assert_eq!(deliver(&state, ">lobby\n|c:|1700000100| Alice|!yo"), vec![false]);
+
+        // A line far behind the server time anchor is history even after joining
+        assert_eq!(deliver(&state, ">lobby\n|c:|1699000000|+Bob|old"), vec![true]);
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let handle = KazamHandle::new(tx, state);
+        let history = handle.chat_history("lobby");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].message, "!yo");
+        assert!(history[0].is_history);
+        assert_eq!(history[1].timestamp, Some(1700000100));
+        assert!(!history[1].is_history);
+        assert_eq!(history[2].message, "old");
+        assert!(handle.chat_history("nowhere").is_empty());
     }
 }
@@ -1,7 +1,10 @@
-use crate::RoomState;
+use std::time::Duration;
+
+use crate::{BattleInfoChanges, BattleOutcome, ClientError, LadderUpdate, RoomState};
 use kazam_protocol::{
-    BattleInfo, BattleRequest, ChallengeState, FormatSection, HpStatus, Pokemon, PokemonDetails,
-    RoomType, SearchState, ServerMessage, Side, Stat, User,
+    BattleInfo, BattleRequest, ChallengeState, ChatContent, FormatSection, HpStatus,
+    ModerationEvent, Player, Pokemon, PokemonDetails, RoomType, SearchState, ServerMessage, Side,
+    Stat, SwapBoostKind, User,
 };
 
 #[allow(async_fn_in_trait)]
@@ -32,12 +35,19 @@ pub trait KazamHandler: Send {
         let _ = (sender, receiver, message);
     }
 
+    /// Called after `on_pm` with the body's `/html`, `/raw`, `/error`, ...
+    /// command resolved
+    async fn on_pm_content(&mut self, sender: &User, receiver: &User, content: &ChatContent) {
+        let _ = (sender, receiver, content);
+    }
+
     /// Called when |usercount|USERCOUNT is received
     async fn on_usercount(&mut self, count: u32) {
         let _ = count;
     }
 
-    /// Called when |formats|FORMATSLIST is received
+    /// Called when |formats|FORMATSLIST is received, but not again for an
+    /// identical list after a reconnect
     async fn on_formats(&mut self, sections: &[FormatSection]) {
         let _ = sections;
     }
@@ -57,6 +67,18 @@ pub trait KazamHandler: Send {
         let _ = user;
     }
 
+    /// Called once when a broadcast announces a server restart
+    ///
+    /// `eta` is set when the broadcast says how soon the restart happens.
+    async fn on_server_restart_announced(&mut self, eta: Option<Duration>) {
+        let _ = eta;
+    }
+
+    /// Called on the first challstr after the connection dropped following a restart announcement
+    ///
+    /// Called before [`on_challstr`](Self::on_challstr) for the same message.
+    async fn on_server_restarted(&mut self) {}
+
     // ===================
     // Room Messages
     // ===================
@@ -66,6 +88,11 @@ pub trait KazamHandler: Send {
         let _ = (room_id, room_type);
     }
 
+    /// Called when |noinit|REASON|MESSAGE is received for a refused join
+    async fn on_noinit(&mut self, room_id: &str, reason: &str, message: &str) {
+        let _ = (room_id, reason, message);
+    }
+
     /// Called when |title|TITLE is received
     async fn on_title(&mut self, room_id: &str, title: &str) {
         let _ = (room_id, title);
@@ -89,14 +116,32 @@ pub trait KazamHandler: Send {
         let _ = (room_id, user, quiet);
     }
 
+    /// Called for chat messages
+    ///
+    /// `is_history` is set for scrollback the server sends when we join a room,
+    /// so bots don't answer commands that were said before they arrived.
     async fn on_chat(
         &mut self,
         room_id: Option<&str>,
         user: &User,
         message: &str,
         timestamp: Option<i64>,
+        is_history: bool,
     ) {
-        let _ = (room_id, user, message, timestamp);
+        let _ = (room_id, user, message, timestamp, is_history);
+    }
+
+    /// Called after `on_chat` with the body's `/html`, `/uhtml`, `/log`, ...
+    /// command resolved, so HTML replies from bots arrive with their sender
+    async fn on_chat_content(
+        &mut self,
+        room_id: Option<&str>,
+        user: &User,
+        content: &ChatContent,
+        timestamp: Option<i64>,
+        is_history: bool,
+    ) {
+        let _ = (room_id, user, content, timestamp, is_history);
     }
 
     /// Called when |:|TIMESTAMP is received (server's current time)
@@ -139,24 +184,94 @@ pub trait KazamHandler: Send {
         let _ = (room_id, name, html);
     }
 
+    /// Called for moderation messages: |unlink|, |hidelines|, |badge| and modlog echoes
+    async fn on_moderation(&mut self, room_id: Option<&str>, event: &ModerationEvent) {
+        let _ = (room_id, event);
+    }
+
     async fn on_raw(&mut self, room_id: Option<&str>, content: &str) {
         let _ = (room_id, content);
     }
 
+    /// Called after `on_raw` for each ranked player in a ladder table
+    async fn on_ladder_update(&mut self, room_id: Option<&str>, update: &LadderUpdate) {
+        let _ = (room_id, update);
+    }
+
     // ===================
     // Battle Events - High Level
     // ===================
 
+    /// Called when a new game begins in a battle room whose previous game
+    /// ended, as rematches do on servers that reuse the room; rebuild any
+    /// per-battle state such as a tracker
+    async fn on_battle_reset(&mut self, room_id: &str) {
+        let _ = room_id;
+    }
+
     /// Called when battle initialization is complete (all player/teamsize/gametype/rules received + |start|)
     async fn on_battle_started(&mut self, room_id: &str, battle: &BattleInfo) {
         let _ = (room_id, battle);
     }
 
+    /// Called once per frame that changed a room's battle info, after the
+    /// whole frame is applied, with what changed
+    async fn on_battle_info_changed(
+        &mut self,
+        room_id: &str,
+        battle: &BattleInfo,
+        changes: BattleInfoChanges,
+    ) {
+        let _ = (room_id, battle, changes);
+    }
+
     /// Called when a battle request is received (player needs to make a decision)
+    ///
+    /// `request.is_stale` is set when we already sent a choice for this rqid or a later one.
+    ///
+    /// Messages are handled one at a time in frame order: each is applied to
+    /// client state, then its callbacks (ending with `on_battle_message`) run
+    /// before the next is applied. So by default this fires in the request's
+    /// place, before any later lines of its frame (|-damage|, |faint|,
+    /// |upkeep|) are applied or forwarded. Showdown usually sends a request in
+    /// its own frame, ahead of the log that resolves the previous turn; see
+    /// [`KazamHandle::turn_resolved_since_request`](crate::KazamHandle::turn_resolved_since_request).
+    /// [`KazamClient::set_defer_request_until_settled`](crate::KazamClient::set_defer_request_until_settled)
+    /// moves this call to after the rest of the frame.
     async fn on_request(&mut self, room_id: &str, request: &BattleRequest) {
         let _ = (room_id, request);
     }
 
+    /// Called when the server rejects a choice-related command we sent
+    ///
+    /// Currently reports [`ClientError::UndoFailed`] when `choose_replace` could not undo.
+    async fn on_choice_error(&mut self, room_id: &str, error: &ClientError) {
+        let _ = (room_id, error);
+    }
+
+    /// Called when a battle asks us to consent to open team sheets, under
+    /// [`OtsPolicy::Ask`](crate::OtsPolicy::Ask)
+    ///
+    /// Return whether to accept. None, or no answer within the policy's
+    /// timeout, rejects.
+    async fn on_ots_prompt(&mut self, room_id: &str) -> Option<bool> {
+        let _ = room_id;
+        None
+    }
+
+    /// Called when a player answers the open team sheets prompt; `username`
+    /// is None for our own answer
+    async fn on_ots_answer(&mut self, room_id: &str, username: Option<&str>, accepted: bool) {
+        let _ = (room_id, username, accepted);
+    }
+
+    /// Called when |showteam| reveals a player's open team sheet
+    ///
+    /// `team` is in packed format; `kazam_team::Teams::unpack` turns it into sets.
+    async fn on_show_team(&mut self, room_id: &str, player: Player, team: &str) {
+        let _ = (room_id, player, team);
+    }
+
     /// Called when |turn|NUMBER is received
     async fn on_turn(&mut self, room_id: &str, turn: u32) {
         let _ = (room_id, turn);
@@ -172,6 +287,11 @@ pub trait KazamHandler: Send {
         let _ = room_id;
     }
 
+    /// Called after `on_win` / `on_tie` with the final battle info and timings
+    async fn on_battle_ended(&mut self, room_id: &str, outcome: &BattleOutcome) {
+        let _ = (room_id, outcome);
+    }
+
     // ===================
     // Battle Events - Major Actions
     // ===================
@@ -269,6 +389,17 @@ pub trait KazamHandler: Send {
         let _ = (room_id, pokemon, stat, amount);
     }
 
+    /// Called when |-swapboost| is received (Guard Swap, Power Swap, Heart Swap, ...)
+    async fn on_swap_boost(
+        &mut self,
+        room_id: &str,
+        source: &Pokemon,
+        target: &Pokemon,
+        kind: &SwapBoostKind,
+    ) {
+        let _ = (room_id, source, target, kind);
+    }
+
     // ===================
     // Battle Events - Field Conditions
     // ===================
@@ -294,8 +425,16 @@ pub trait KazamHandler: Send {
     }
 
     /// Called when |-sideend| is received
-    async fn on_side_end(&mut self, room_id: &str, side: &Side, condition: &str) {
-        let _ = (room_id, side, condition);
+    ///
+    /// `from` names the move that cleared it (e.g. "move: Defog"), if any.
+    async fn on_side_end(
+        &mut self,
+        room_id: &str,
+        side: &Side,
+        condition: &str,
+        from: Option<&str>,
+    ) {
+        let _ = (room_id, side, condition, from);
     }
 
     // ===================
@@ -378,6 +517,11 @@ pub trait KazamHandler: Send {
         let _ = (room_id, pokemon, megastone);
     }
 
+    /// Called when |-terastallize| is received (`tera_type` may be "Stellar")
+    async fn on_terastallize(&mut self, room_id: &str, pokemon: &Pokemon, tera_type: &str) {
+        let _ = (room_id, pokemon, tera_type);
+    }
+
     /// Called when |-primal| is received
     async fn on_primal(&mut self, room_id: &str, pokemon: &Pokemon) {
         let _ = (room_id, pokemon);
@@ -417,8 +561,16 @@ pub trait KazamHandler: Send {
     // ===================
 
     /// Called when |-activate| is received (misc effect activation)
-    async fn on_activate(&mut self, room_id: &str, pokemon: Option<&Pokemon>, effect: &str) {
-        let _ = (room_id, pokemon, effect);
+    ///
+    /// `detail` is the argument after the effect, e.g. the item Poltergeist reveals.
+    async fn on_activate(
+        &mut self,
+        room_id: &str,
+        pokemon: Option<&Pokemon>,
+        effect: &str,
+        detail: Option<&str>,
+    ) {
+        let _ = (room_id, pokemon, effect, detail);
     }
 
     /// Called when |-hint| is received
@@ -0,0 +1,108 @@
+//! Whether a chat message mentions us
+//!
+//! A term matches as the Showdown client highlights: anywhere in the message,
+//! ignoring case, as long as no letter or digit runs into it on either side.
+//! "alice" is found in "hi Alice!" and "@alice:" but not in "malice".
+
+use kazam_protocol::user_id;
+
+/// Terms to look for in chat, such as our own name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HighlightMatcher {
+    /// Lowercased, trimmed and never empty
+    terms: Vec<String>,
+}
+
+impl HighlightMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matcher for `username` as written and as its user id, so "Bob Smith"
+    /// is found in both "Bob Smith" and "bobsmith"
+    pub fn for_username(username: &str) -> Self {
+        let mut matcher = Self::new();
+        matcher.add(username);
+        matcher.add(&user_id(username));
+        matcher
+    }
+
+    /// Look for `term` too; blank and repeated terms are ignored
+    pub fn add(&mut self, term: &str) {
+        let term = term.trim().to_lowercase();
+        if !term.is_empty() && !self.terms.contains(&term) {
+            self.terms.push(term);
+        }
+    }
+
+    /// Terms in the order they were added, lowercased
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    /// First term, in the order added, that `message` mentions
+    pub fn find(&self, message: &str) -> Option<&str> {
+        let message = message.to_lowercase();
+        self.terms
+            .iter()
+            .find(|term| mentions(&message, term))
+            .map(String::as_str)
+    }
+
+    pub fn matches(&self, message: &str) -> bool {
+        self.find(message).is_some()
+    }
+}
+
+/// Whether `term` appears in `message` with a word boundary at both ends
+fn mentions(message: &str, term: &str) -> bool {
+    message.match_indices(term).any(|(start, _)| {
+        let before = message[..start].chars().next_back();
+        let after = message[start + term.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_boundaries() {
+        let matcher = HighlightMatcher::for_username("Alice");
+        assert!(matcher.matches("hi Alice!"));
+        assert!(matcher.matches("ALICE"));
+        assert!(matcher.matches("@alice: gg"));
+        assert!(matcher.matches("alice's team"));
+        assert!(!matcher.matches("malice"));
+        assert!(!matcher.matches("alice2 is someone else"));
+        // A later occurrence can match after an earlier one doesn't
+        assert!(matcher.matches("malice, said alice"));
+        assert!(!matcher.matches(""));
+    }
+
+    #[test]
+    fn test_names_with_spaces_and_unicode() {
+        let matcher = HighlightMatcher::for_username("Bob Smith");
+        assert_eq!(matcher.terms(), ["bob smith", "bobsmith"]);
+        assert_eq!(matcher.find("ask Bob Smith"), Some("bob smith"));
+        assert_eq!(matcher.find("ask bobsmith"), Some("bobsmith"));
+        assert_eq!(matcher.find("ask bob"), None);
+
+        let matcher = HighlightMatcher::for_username("Zoë");
+        assert!(matcher.matches("ZOË, your turn"));
+        // ë is a letter, so it isn't a boundary
+        assert!(!matcher.matches("zoëy"));
+    }
+
+    #[test]
+    fn test_extra_terms() {
+        let mut matcher = HighlightMatcher::new();
+        assert!(!matcher.matches("anything"));
+        matcher.add("  ");
+        matcher.add("Kazam Bot");
+        matcher.add("kazam bot");
+        assert_eq!(matcher.terms(), ["kazam bot"]);
+        assert!(matcher.matches("is kazam bot online?"));
+    }
+}
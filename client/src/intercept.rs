@@ -0,0 +1,149 @@
+//! Hooks on the outgoing path
+//!
+//! Interceptors see each message right before it's sent, in the order they
+//! were added, and may let it through, drop it or swap in another message for
+//! the interceptors after them. Queued joins are checked before they use up
+//! the join throttle, so a dropped join doesn't delay the next one.
+
+use std::sync::{Arc, Mutex};
+
+use kazam_protocol::ClientMessage;
+
+/// What to do with an outgoing message
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendDecision {
+    Allow,
+
+    /// Don't send it; `reason` is logged
+    Drop {
+        reason: String,
+    },
+
+    /// Send this instead, after the remaining interceptors see it
+    Replace(ClientMessage),
+}
+
+/// Inspects outgoing messages, see [`KazamClient::add_send_interceptor`](crate::KazamClient::add_send_interceptor)
+pub trait SendInterceptor: Send + Sync {
+    fn intercept(&self, message: &ClientMessage) -> SendDecision;
+}
+
+impl<F> SendInterceptor for F
+where
+    F: Fn(&ClientMessage) -> SendDecision + Send + Sync,
+{
+    fn intercept(&self, message: &ClientMessage) -> SendDecision {
+        self(message)
+    }
+}
+
+/// Drops every message and keeps it, to see what a bot would have sent
+///
+/// [`KazamClient::dry_run`](crate::KazamClient::dry_run) installs one whose
+/// messages [`KazamHandle::dry_run_messages`](crate::KazamHandle::dry_run_messages) returns.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    messages: Arc<Mutex<Vec<ClientMessage>>>,
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages dropped so far, oldest first
+    pub fn messages(&self) -> Vec<ClientMessage> {
+        self.messages
+            .lock()
+            .map(|messages| messages.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl SendInterceptor for DryRun {
+    fn intercept(&self, message: &ClientMessage) -> SendDecision {
+        if let Ok(mut messages) = self.messages.lock() {
+            messages.push(message.clone());
+        }
+        SendDecision::Drop {
+            reason: "dry run".to_string(),
+        }
+    }
+}
+
+/// Interceptors in registration order
+#[derive(Default)]
+pub(crate) struct Interceptors(Vec<Box<dyn SendInterceptor>>);
+
+impl Interceptors {
+    pub fn push(&mut self, interceptor: impl SendInterceptor + 'static) {
+        self.0.push(Box::new(interceptor));
+    }
+
+    /// The message to send after every interceptor, or why one dropped it
+    pub fn apply(&self, mut message: ClientMessage) -> Result<ClientMessage, String> {
+        for interceptor in &self.0 {
+            match interceptor.intercept(&message) {
+                SendDecision::Allow => {}
+                SendDecision::Drop { reason } => {
+                    tracing::debug!(?message, reason, "Outgoing message dropped");
+                    return Err(reason);
+                }
+                SendDecision::Replace(replacement) => message = replacement,
+            }
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::ClientCommand;
+
+    fn chat(text: &str) -> ClientMessage {
+        ClientMessage {
+            room_id: Some("lobby".to_string()),
+            command: ClientCommand::Chat(text.to_string()),
+        }
+    }
+
+    fn text(message: &ClientMessage) -> &str {
+        match &message.command {
+            ClientCommand::Chat(text) => text,
+            other => panic!("expected chat, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_replace_chains_in_order() {
+        let mut interceptors = Interceptors::default();
+        interceptors
+            .push(|m: &ClientMessage| SendDecision::Replace(chat(&format!("{} one", text(m)))));
+        interceptors
+            .push(|m: &ClientMessage| SendDecision::Replace(chat(&format!("{} two", text(m)))));
+        let sent = interceptors.apply(chat("hi")).unwrap();
+        assert_eq!(text(&sent), "hi one two");
+    }
+
+    #[test]
+    fn test_drop_stops_the_chain() {
+        let dry_run = DryRun::new();
+        let mut interceptors = Interceptors::default();
+        interceptors.push(|m: &ClientMessage| match text(m) {
+            "secret" => SendDecision::Drop {
+                reason: "policy".to_string(),
+            },
+            _ => SendDecision::Allow,
+        });
+        interceptors.push(dry_run.clone());
+
+        assert_eq!(
+            interceptors.apply(chat("secret")),
+            Err("policy".to_string())
+        );
+        assert_eq!(interceptors.apply(chat("hi")), Err("dry run".to_string()));
+        // The dry run only saw what got past the policy
+        assert_eq!(dry_run.messages(), [chat("hi")]);
+    }
+}
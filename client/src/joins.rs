@@ -0,0 +1,209 @@
+//! Room joins, paced to stay under the server's join throttle
+//!
+//! Joins wait in a queue and go out one per interval. Battle rooms have their
+//! own lane ahead of everything else, so joining a battle never waits behind a
+//! backlog of chat rooms. A join stays pending until the server answers with
+//! `|init|` or `|noinit|` for that room.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+use crate::error::ClientError;
+
+/// Default spacing between /join commands
+pub const DEFAULT_JOIN_INTERVAL: Duration = Duration::from_secs(1);
+
+type JoinWaiter = oneshot::Sender<Result<(), ClientError>>;
+
+/// Rooms waiting to be joined, and the joins sent but not yet answered
+#[derive(Debug)]
+pub(crate) struct JoinQueue {
+    battles: VecDeque<String>,
+    rooms: VecDeque<String>,
+    /// Sent, waiting for |init| or |noinit|, oldest first
+    in_flight: Vec<String>,
+    waiters: HashMap<String, Vec<JoinWaiter>>,
+    interval: Duration,
+    last_sent: Option<Instant>,
+    /// `last_sent` before the latest join, restored if it's cancelled
+    previous_sent: Option<Instant>,
+}
+
+pub(crate) fn is_battle_room(room: &str) -> bool {
+    room.starts_with("battle-")
+}
+
+impl JoinQueue {
+    pub fn new() -> Self {
+        Self {
+            battles: VecDeque::new(),
+            rooms: VecDeque::new(),
+            in_flight: Vec::new(),
+            waiters: HashMap::new(),
+            interval: DEFAULT_JOIN_INTERVAL,
+            last_sent: None,
+            previous_sent: None,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    fn is_pending(&self, room: &str) -> bool {
+        self.battles.iter().chain(&self.rooms).any(|r| r == room)
+            || self.in_flight.iter().any(|r| r == room)
+    }
+
+    /// Queue a join, returns false if the room was already queued or in flight
+    pub fn push(&mut self, room: &str) -> bool {
+        if self.is_pending(room) {
+            return false;
+        }
+        if is_battle_room(room) {
+            self.battles.push_back(room.to_string());
+        } else {
+            self.rooms.push_back(room.to_string());
+        }
+        true
+    }
+
+    /// Queue a join and wait for its answer; joins already pending share it
+    pub fn push_waiting(&mut self, room: &str) -> oneshot::Receiver<Result<(), ClientError>> {
+        self.push(room);
+        let (tx, rx) = oneshot::channel();
+        self.waiters.entry(room.to_string()).or_default().push(tx);
+        rx
+    }
+
+    /// When the next join may go out, None if nothing is queued
+    pub fn next_due(&self) -> Option<Instant> {
+        if self.battles.is_empty() && self.rooms.is_empty() {
+            return None;
+        }
+        Some(match self.last_sent {
+            Some(last) => last + self.interval,
+            None => Instant::now(),
+        })
+    }
+
+    /// Take the next room to join if one is due, battles first
+    pub fn pop_due(&mut self, now: Instant) -> Option<String> {
+        if self
+            .last_sent
+            .is_some_and(|last| last + self.interval > now)
+        {
+            return None;
+        }
+        let room = self
+            .battles
+            .pop_front()
+            .or_else(|| self.rooms.pop_front())?;
+        self.in_flight.push(room.clone());
+        self.previous_sent = self.last_sent.replace(now);
+        Some(room)
+    }
+
+    /// Forget the join just taken without sending it, so it doesn't count
+    /// against the throttle
+    pub fn cancel(&mut self, room: &str, reason: &str) {
+        self.last_sent = self.previous_sent;
+        self.resolve(
+            room,
+            Err(ClientError::JoinFailed {
+                room: room.to_string(),
+                reason: "dropped".to_string(),
+                message: reason.to_string(),
+            }),
+        );
+    }
+
+    /// Settle a join with the server's answer, whether or not we asked for it
+    pub fn resolve(&mut self, room: &str, result: Result<(), ClientError>) {
+        self.in_flight.retain(|r| r != room);
+        self.battles.retain(|r| r != room);
+        self.rooms.retain(|r| r != room);
+        for tx in self.waiters.remove(room).unwrap_or_default() {
+            // The caller may have stopped waiting
+            let _ = tx.send(result.clone());
+        }
+    }
+
+    /// Put unanswered joins back at the front of their lanes after a reconnect
+    pub fn requeue_in_flight(&mut self) {
+        for room in std::mem::take(&mut self.in_flight).into_iter().rev() {
+            if is_battle_room(&room) {
+                self.battles.push_front(room);
+            } else {
+                self.rooms.push_front(room);
+            }
+        }
+    }
+
+    /// Rooms not joined yet: unanswered joins, then the queue in send order
+    pub fn pending(&self) -> Vec<String> {
+        self.in_flight
+            .iter()
+            .chain(&self.battles)
+            .chain(&self.rooms)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut JoinQueue, start: Instant) -> Vec<(String, Duration)> {
+        let mut sent = Vec::new();
+        while let Some(due) = queue.next_due() {
+            let at = due.max(start);
+            let room = queue.pop_due(at).unwrap();
+            sent.push((room, at - start));
+        }
+        sent
+    }
+
+    #[test]
+    fn test_battles_jump_the_queue() {
+        let mut queue = JoinQueue::new();
+        for room in ["lobby", "help", "battle-gen9ou-1", "techcode"] {
+            queue.push(room);
+        }
+        assert!(!queue.push("help"));
+        let _answer = queue.push_waiting("battle-gen9ou-1");
+
+        let start = Instant::now();
+        let sent = drain(&mut queue, start);
+        let rooms: Vec<_> = sent.iter().map(|(room, _)| room.as_str()).collect();
+        assert_eq!(rooms, ["battle-gen9ou-1", "lobby", "help", "techcode"]);
+        assert_eq!(sent[3].1 - sent[0].1, DEFAULT_JOIN_INTERVAL * 3);
+
+        // Sent joins stay pending, and deduplicated, until answered
+        assert!(!queue.push("lobby"));
+        queue.resolve("lobby", Ok(()));
+        assert_eq!(queue.pending(), ["battle-gen9ou-1", "help", "techcode"]);
+    }
+
+    #[test]
+    fn test_unanswered_joins_requeued_in_order() {
+        let mut queue = JoinQueue::new();
+        queue.set_interval(Duration::ZERO);
+        for room in ["lobby", "help", "battle-gen9ou-1", "techcode"] {
+            queue.push(room);
+        }
+        let now = Instant::now();
+        for _ in 0..3 {
+            queue.pop_due(now).unwrap();
+        }
+        queue.resolve("lobby", Ok(()));
+
+        queue.requeue_in_flight();
+        assert_eq!(queue.pending(), ["battle-gen9ou-1", "help", "techcode"]);
+        assert_eq!(queue.pop_due(now).as_deref(), Some("battle-gen9ou-1"));
+    }
+}
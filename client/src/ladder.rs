@@ -0,0 +1,237 @@
+//! Ladder tables sent as `|raw|` at the end of rated battles
+//!
+//! Besides the one-line "rating: X → Y" notices, the server can send an HTML
+//! table with each player's Elo change, GXE, Glicko-1 and record. Columns are
+//! found by their header, so tables from side servers that drop some of them
+//! still parse.
+
+use kazam_protocol::{same_user, user_id};
+
+use crate::announcement::strip_tags;
+
+/// One player's row of a ladder table
+#[derive(Debug, Clone, PartialEq)]
+pub struct LadderUpdate {
+    pub username: String,
+
+    /// Format as the table or the battle names it, e.g. `[Gen 9] OU`
+    pub format: String,
+
+    /// Elo before the battle, when the table shows the change
+    pub elo_before: Option<u32>,
+
+    pub elo_after: u32,
+
+    /// Glicko X-Act Estimate, in percent
+    pub gxe: Option<f32>,
+
+    /// Glicko-1 rating and deviation
+    pub glicko: Option<(f32, f32)>,
+
+    pub wins: Option<u32>,
+
+    pub losses: Option<u32>,
+}
+
+impl LadderUpdate {
+    /// Ranked players in a ladder table, empty if `html` isn't one
+    ///
+    /// `format` names rows of tables without a format column. Rows without
+    /// an Elo, e.g. unranked players, are left out.
+    pub fn parse_table(html: &str, format: &str) -> Vec<Self> {
+        let Some(start) = html.find("<table") else {
+            return Vec::new();
+        };
+        let mut rows = html[start..].split("<tr").skip(1).map(cells);
+        let Some(columns) = rows.next() else {
+            return Vec::new();
+        };
+        let columns: Vec<Column> = columns.iter().map(|name| Column::parse(name)).collect();
+        if !columns.contains(&Column::Name) || !columns.contains(&Column::Elo) {
+            return Vec::new();
+        }
+
+        rows.filter_map(|row| {
+            let cell = |column: Column| {
+                let index = columns.iter().position(|c| *c == column)?;
+                row.get(index).map(String::as_str)
+            };
+            let username = cell(Column::Name).filter(|name| !name.is_empty())?;
+            let elo = numbers(cell(Column::Elo)?);
+            let (elo_before, elo_after) = match elo[..] {
+                [after] => (None, after),
+                [before, after, ..] => (Some(before as u32), after),
+                [] => return None,
+            };
+            Some(Self {
+                username: username.to_string(),
+                format: cell(Column::Format)
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(format)
+                    .to_string(),
+                elo_before,
+                elo_after: elo_after as u32,
+                gxe: cell(Column::Gxe).and_then(|gxe| numbers(gxe).first().copied()),
+                glicko: cell(Column::Glicko).and_then(|glicko| match numbers(glicko)[..] {
+                    [rating, deviation, ..] => Some((rating, deviation)),
+                    _ => None,
+                }),
+                wins: cell(Column::Wins).and_then(count),
+                losses: cell(Column::Losses).and_then(count),
+            })
+        })
+        .collect()
+    }
+
+    /// Whether this row belongs to `username`
+    pub fn is_for(&self, username: &str) -> bool {
+        same_user(&self.username, username)
+    }
+}
+
+/// What a ladder table column holds, from its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Name,
+    Format,
+    Elo,
+    Gxe,
+    Glicko,
+    Wins,
+    Losses,
+    Other,
+}
+
+impl Column {
+    fn parse(header: &str) -> Self {
+        match user_id(header).as_str() {
+            "name" | "user" | "username" | "player" => Column::Name,
+            "format" | "tier" => Column::Format,
+            "elo" | "rating" => Column::Elo,
+            "gxe" => Column::Gxe,
+            "glicko" | "glicko1" => Column::Glicko,
+            "w" | "wins" => Column::Wins,
+            "l" | "losses" => Column::Losses,
+            _ => Column::Other,
+        }
+    }
+}
+
+/// Text of each `<th>` or `<td>` in one row's HTML
+fn cells(row: &str) -> Vec<String> {
+    let row = row.split("</tr").next().unwrap_or_default();
+    row.split("<t")
+        .skip(1)
+        .filter(|cell| cell.starts_with('h') || cell.starts_with('d'))
+        .map(|cell| {
+            let body = cell.split_once('>').map_or("", |(_, body)| body);
+            let body = body.split("</t").next().unwrap_or_default();
+            strip_tags(body).replace("&nbsp;", " ").trim().to_string()
+        })
+        .collect()
+}
+
+/// Numbers in a cell, skipping HTML entities like `&rarr;` and `&#177;`
+fn numbers(text: &str) -> Vec<f32> {
+    let mut found = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || (c == '.' && !current.is_empty()) {
+            current.push(c);
+            continue;
+        }
+        if let Ok(number) = current.parse() {
+            found.push(number);
+        }
+        current.clear();
+        if c == '&' {
+            chars.by_ref().find(|&c| c == ';');
+        }
+    }
+    if let Ok(number) = current.parse() {
+        found.push(number);
+    }
+    found
+}
+
+fn count(text: &str) -> Option<u32> {
+    text.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen9ou_table() {
+        let html = include_str!("../tests/fixtures/ladder_gen9ou.html");
+        let updates = LadderUpdate::parse_table(html, "[Gen 9] OU");
+        assert_eq!(
+            updates,
+            [
+                LadderUpdate {
+                    username: "Kazam Bot".to_string(),
+                    format: "[Gen 9] OU".to_string(),
+                    elo_before: Some(1487),
+                    elo_after: 1506,
+                    gxe: Some(68.4),
+                    glicko: Some((1621.0, 41.0)),
+                    wins: Some(53),
+                    losses: Some(29),
+                },
+                LadderUpdate {
+                    username: "Ashen Wing".to_string(),
+                    format: "[Gen 9] OU".to_string(),
+                    elo_before: Some(1533),
+                    elo_after: 1514,
+                    gxe: Some(71.9),
+                    glicko: Some((1655.0, 38.0)),
+                    wins: Some(61),
+                    losses: Some(30),
+                },
+            ]
+        );
+        assert!(updates[0].is_for("kazambot"));
+    }
+
+    #[test]
+    fn test_one_ranked_player() {
+        let html = include_str!("../tests/fixtures/ladder_gen9randombattle.html");
+        let updates = LadderUpdate::parse_table(html, "gen9randombattle");
+        assert_eq!(updates.len(), 1);
+        let update = &updates[0];
+        assert_eq!(update.username, "kazambot");
+        assert_eq!(update.format, "[Gen 9] Random Battle");
+        assert_eq!((update.elo_before, update.elo_after), (Some(1210), 1234));
+        assert_eq!(update.gxe, Some(55.0));
+        assert_eq!(update.glicko, Some((1544.0, 102.0)));
+        assert_eq!((update.wins, update.losses), (Some(9), Some(4)));
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let html = include_str!("../tests/fixtures/ladder_side_server.html");
+        let updates = LadderUpdate::parse_table(html, "gen9ou");
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].format, "gen9ou");
+        assert_eq!((updates[0].elo_before, updates[0].elo_after), (None, 1042));
+        assert_eq!((updates[0].gxe, updates[0].glicko), (None, None));
+        assert_eq!((updates[0].wins, updates[0].losses), (Some(3), Some(1)));
+        // Short row
+        assert_eq!((updates[1].elo_after, updates[1].wins), (1000, None));
+    }
+
+    #[test]
+    fn test_not_a_ladder_table() {
+        assert!(
+            LadderUpdate::parse_table(
+                "Kazam Bot's rating: 1487 &rarr; <strong>1506</strong>",
+                "gen9ou"
+            )
+            .is_empty()
+        );
+        assert!(LadderUpdate::parse_table("<table><tr><th>Move</th><th>PP</th></tr><tr><td>Tackle</td><td>35</td></tr></table>", "gen9ou").is_empty());
+        assert_eq!(numbers("1621 &#177; 41"), [1621.0, 41.0]);
+    }
+}
@@ -1,1035 +1,398 @@
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
-use kazam_protocol::{ClientMessage, ServerFrame};
+use kazam_protocol::{ClientCommand, ClientMessage, ServerFrame};
 use tokio::sync::mpsc;
 
+mod address;
+mod announcement;
+mod battle_changes;
+mod chat;
 mod connection;
+mod dispatch;
+mod error;
 mod handle;
 mod handler;
+mod highlight;
+mod intercept;
+mod joins;
+mod ladder;
+mod login;
+mod members;
+pub mod prelude;
+mod pool;
 mod room;
-
-use connection::{Connection, ReconnectPolicy};
+mod settings;
+mod settle;
+mod snapshot;
+mod source;
+pub mod stream;
+mod team_sheets;
+mod timer;
+mod timing;
+#[cfg(feature = "examples-integrations")]
+mod webhook;
+
+pub use address::ServerAddress;
+pub use announcement::ServerNotice;
+pub use battle_changes::BattleInfoChanges;
+pub use chat::{DEFAULT_CHAT_INTERVAL, MAX_CHAT_LENGTH};
+pub use connection::{Connection, KeepaliveConfig, ReconnectPolicy};
+pub use error::{ClientError, ConnectError};
+use dispatch::Callback;
 use handle::ClientState;
+use intercept::Interceptors;
 
 pub use handle::KazamHandle;
 pub use handler::KazamHandler;
+pub use highlight::HighlightMatcher;
+pub use intercept::{DryRun, SendDecision, SendInterceptor};
+pub use joins::DEFAULT_JOIN_INTERVAL;
+pub use ladder::LadderUpdate;
+pub use login::{LOGIN_API_URL, LoginClient};
 pub use kazam_protocol::{
-    ActivePokemon, BattleInfo, BattleRequest, ChallengeInfo, ChallengeState, Format, FormatSection,
-    GameType, HpStatus, MaxMoveSlot, MaxMoves, MoveSlot, Player, PlayerInfo, Pokemon,
-    PokemonDetails, PokemonStats, PreviewPokemon, RoomType, SearchState, ServerMessage, Side,
-    SideInfo, SidePokemon, Stat, User, ZMoveInfo,
+    ActivePokemon, BattleInfo, BattleRequest, ChallengeInfo, ChallengeState, ChatContent, Format,
+    FormatSection, GameType, HpStatus, MaxMoveSlot, MaxMoves, ModAction, ModActionKind,
+    ModerationEvent, MoveSlot, Player, PlayerInfo, Pokemon, PokemonDetails, PokemonStats,
+    PreviewPokemon, RoomType, SearchState, ServerMessage, Side, SideInfo, SidePokemon, Stat,
+    SwapBoostKind, TimerInfo, User, ZMoveInfo,
 };
-pub use room::RoomState;
+pub use room::{ChatLine, RoomState};
+pub use pool::{AccountContext, AccountReport, Board, KazamClientPool, PoolHandle};
+pub use settings::AccountSetting;
+pub use snapshot::StateSnapshot;
+pub use source::MessageSource;
+pub use stream::{Backpressure, ClientEvent, DEFAULT_STREAM_CAPACITY};
+pub use team_sheets::{DEFAULT_OTS_TIMEOUT, OTS_PROMPT_NAME, OtsNotice, OtsPolicy};
+#[cfg(feature = "test-util")]
+pub use source::{ScriptedSource, SentMessages};
+pub use timer::TimeBudget;
+pub use timing::{BattleOutcome, BattleTimings};
+#[cfg(feature = "examples-integrations")]
+pub use webhook::{MAX_WEBHOOK_CONTENT, WebhookPoster};
 
 pub const SHOWDOWN_URL: &str = "wss://sim3.psim.us/showdown/websocket";
 
-pub struct KazamClient {
-    connection: Connection,
+pub struct KazamClient<S: MessageSource = Connection> {
+    source: S,
     state: Arc<ClientState>,
     cmd_rx: mpsc::UnboundedReceiver<ClientMessage>,
     cmd_tx: mpsc::UnboundedSender<ClientMessage>,
+    interceptors: Interceptors,
+    /// Where stream mode sends each message, see [`KazamClient::into_stream`]
+    events: Option<Arc<stream::EventQueue>>,
 }
 
 impl KazamClient {
-    pub async fn connect(url: &str) -> Result<Self> {
-        let connection = Connection::connect(url.to_string(), ReconnectPolicy::default()).await?;
+    /// Connect to a server: a websocket URL or any [`ServerAddress`]
+    ///
+    /// Failures to connect carry a [`ConnectError`].
+    pub async fn connect(address: impl Into<ServerAddress>) -> Result<Self> {
+        let url = address.into().to_string();
+        let connection = Connection::connect(url, ReconnectPolicy::default()).await?;
+        Ok(Self::with_source(connection))
+    }
+
+    /// Configure the watchdog that detects silently dropped connections
+    pub fn set_keepalive(&mut self, config: KeepaliveConfig) {
+        self.source.set_keepalive(config);
+    }
+}
+
+impl<S: MessageSource> KazamClient<S> {
+    /// Create a client reading from any message source, e.g. a `ScriptedSource`
+    pub fn with_source(source: S) -> Self {
         let state = Arc::new(ClientState::new());
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
 
-        Ok(Self {
-            connection,
+        Self {
+            source,
             state,
             cmd_rx,
             cmd_tx,
-        })
+            interceptors: Interceptors::default(),
+            events: None,
+        }
     }
 
-    pub fn handle(&self) -> KazamHandle {
-        KazamHandle::new(self.cmd_tx.clone(), self.state.clone())
+    /// Set how many chat lines are kept per room (default 100) for rooms joined after this
+    pub fn set_chat_history_capacity(&mut self, capacity: usize) {
+        self.state
+            .chat_history_capacity
+            .store(capacity, Ordering::Relaxed);
     }
 
-    pub async fn run<H: KazamHandler>(&mut self, handler: &mut H) -> Result<()> {
-        loop {
-            tokio::select! {
-                frame = self.connection.recv() => {
-                    self.dispatch_frame(frame?, handler).await?;
-                }
-
-                cmd = self.cmd_rx.recv() => {
-                    if let Some(cmd) = cmd {
-                        self.handle_command(cmd).await?;
-                    }
-                }
-            }
-        }
+    /// Hold back [`KazamHandle::search`] calls from a restart announcement until
+    /// we're logged in to the restarted server (off by default)
+    pub fn set_pause_matchmaking_on_restart_announcement(&mut self, pause: bool) {
+        self.state
+            .pause_matchmaking_on_restart
+            .store(pause, Ordering::Relaxed);
     }
 
-    async fn handle_command(&mut self, msg: ClientMessage) -> Result<()> {
-        self.connection.send(msg.to_wire_format()).await
+    /// Call [`KazamHandler::on_request`] after the rest of the request's frame
+    /// has been dispatched, rather than in the request's place (off by default)
+    ///
+    /// Handlers normally see a frame's messages one at a time, in order, so a
+    /// request followed by |-damage| and |faint| lines reaches `on_request`
+    /// before they're applied. Deferred, it comes last, with client state and
+    /// anything the handler tracks from [`KazamHandler::on_battle_message`]
+    /// up to date.
+    pub fn set_defer_request_until_settled(&mut self, defer: bool) {
+        self.state.defer_requests.store(defer, Ordering::Relaxed);
     }
 
-    async fn dispatch_frame<H: KazamHandler>(
-        &mut self,
-        frame: ServerFrame,
-        handler: &mut H,
-    ) -> Result<()> {
-        let room_id = frame.room_id.clone();
-
-        for message in frame.messages {
-            match message {
-                ServerMessage::Challstr(challstr) => {
-                    handler.on_challstr(&challstr).await;
-                }
+    /// How to answer the open team sheets prompt some formats show at team
+    /// preview (default [`OtsPolicy::Ask`], rejecting after ten seconds)
+    pub fn set_open_team_sheets(&mut self, policy: OtsPolicy) {
+        if let Ok(mut current) = self.state.ots_policy.write() {
+            *current = policy;
+        }
+    }
 
-                ServerMessage::UpdateUser {
-                    user,
-                    named,
-                    avatar,
-                } => {
-                    let was_logged_in = self.state.logged_in.load(Ordering::Relaxed);
-                    if named {
-                        self.state.logged_in.store(true, Ordering::Relaxed);
-                    }
-                    handler.on_update_user(&user, named, &avatar).await;
-                    if named && !was_logged_in {
-                        handler.on_logged_in(&user).await;
-                    }
-                }
+    /// Set the spacing between /join commands (default one second)
+    pub fn set_join_interval(&mut self, interval: Duration) {
+        if let Ok(mut joins) = self.state.joins.write() {
+            joins.set_interval(interval);
+        }
+    }
 
-                ServerMessage::NameTaken { username, message } => {
-                    handler.on_name_taken(&username, &message).await;
-                }
+    /// Set the spacing between chat messages in the same room (default 600ms)
+    pub fn set_chat_interval(&mut self, interval: Duration) {
+        if let Ok(mut chat) = self.state.chat.write() {
+            chat.set_interval(interval);
+        }
+    }
 
-                ServerMessage::Popup(message) => {
-                    handler.on_popup(&message).await;
-                }
+    /// Join short chat lines queued for the same room into one message,
+    /// separated by ` | ` (off by default)
+    pub fn set_chat_coalescing(&mut self, coalesce: bool) {
+        if let Ok(mut chat) = self.state.chat.write() {
+            chat.set_coalesce(coalesce);
+        }
+    }
 
-                ServerMessage::Pm {
-                    sender,
-                    receiver,
-                    message,
-                } => {
-                    handler.on_pm(&sender, &receiver, &message).await;
-                }
+    /// Use `login` for [`KazamHandle::login`], e.g. one pointed at another login server
+    pub fn set_login_client(&mut self, login: LoginClient) {
+        if let Ok(mut current) = self.state.login.write() {
+            *current = login;
+        }
+    }
 
-                ServerMessage::Usercount(count) => {
-                    handler.on_usercount(count).await;
-                }
+    /// Run `interceptor` on every outgoing message, after those added before it
+    ///
+    /// Closures taking `&ClientMessage` and returning a [`SendDecision`] work
+    /// as interceptors.
+    pub fn add_send_interceptor(&mut self, interceptor: impl SendInterceptor + 'static) {
+        self.interceptors.push(interceptor);
+    }
 
-                ServerMessage::Formats(sections) => {
-                    handler.on_formats(&sections).await;
-                }
+    /// Send nothing, keeping what would have gone out for
+    /// [`KazamHandle::dry_run_messages`]
+    ///
+    /// Interceptors added before this still run first.
+    pub fn dry_run(&mut self) {
+        self.interceptors.push(self.state.dry_run.clone());
+    }
 
-                ServerMessage::UpdateSearch(state) => {
-                    handler.on_update_search(&state).await;
-                }
+    pub fn handle(&self) -> KazamHandle {
+        KazamHandle::new(self.cmd_tx.clone(), self.state.clone())
+    }
 
-                ServerMessage::UpdateChallenges(state) => {
-                    handler.on_update_challenges(&state).await;
+    /// Dispatch frames to `handler` until the source runs out or fails, or
+    /// [`KazamHandle::shutdown`] is called
+    ///
+    /// Chat still queued when it stops is sent right away.
+    pub async fn run<H: KazamHandler>(&mut self, handler: &mut H) -> Result<()> {
+        loop {
+            tokio::select! {
+                () = self.state.shutdown.notified() => {
+                    return self.finish().await;
                 }
 
-                ServerMessage::Init(room_type) => {
-                    if let Some(ref rid) = room_id {
-                        let state = RoomState {
-                            id: rid.clone(),
-                            room_type: room_type.clone(),
-                            title: None,
-                            users: vec![],
-                        };
-                        if let Ok(mut rooms) = self.state.rooms.write() {
-                            rooms.insert(rid.clone(), state);
+                frame = self.source.next_frame() => {
+                    let Some(frame) = frame? else {
+                        return self.finish().await;
+                    };
+                    if self.source.take_reconnected() {
+                        self.state.on_reconnected();
+                        if let Some(events) = &self.events {
+                            events.push(ClientEvent::Reconnected).await;
                         }
-                        handler.on_init(rid, &room_type).await;
                     }
+                    self.dispatch_frame(frame, handler).await?;
+                    // Send what the handler queued before reading on
+                    self.flush_commands().await?;
                 }
 
-                ServerMessage::Title(title) => {
-                    if let Some(ref rid) = room_id {
-                        if let Ok(mut rooms) = self.state.rooms.write()
-                            && let Some(room) = rooms.get_mut(rid) {
-                                room.title = Some(title.clone());
-                            }
-                        handler.on_title(rid, &title).await;
+                cmd = self.cmd_rx.recv() => {
+                    if let Some(cmd) = cmd {
+                        self.handle_command(cmd).await?;
                     }
                 }
 
-                ServerMessage::Users(users) => {
-                    if let Some(ref rid) = room_id {
-                        let room_snapshot = if let Ok(mut rooms) = self.state.rooms.write() {
-                            if let Some(room) = rooms.get_mut(rid) {
-                                room.users = users.clone();
-                                Some(room.clone())
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
+                () = self.state.join_due() => {
+                    if let Some(room) = self.state.pop_join() {
+                        let join = ClientMessage {
+                            room_id: None,
+                            command: ClientCommand::JoinRoom(room.clone()),
                         };
-
-                        handler.on_users(rid, &users).await;
-
-                        if let Some(room) = room_snapshot {
-                            handler.on_room_joined(&room).await;
-                        }
-                    }
-                }
-
-                ServerMessage::Join { user, quiet } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut rooms) = self.state.rooms.write()
-                            && let Some(room) = rooms.get_mut(rid)
-                                && !room.users.iter().any(|u| u.username == user.username) {
-                                    room.users.push(user.clone());
-                                }
-                    handler.on_join(room_id.as_deref(), &user, quiet).await;
-                }
-
-                ServerMessage::Leave { user, quiet } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut rooms) = self.state.rooms.write()
-                            && let Some(room) = rooms.get_mut(rid) {
-                                room.users.retain(|u| u.username != user.username);
-                            }
-                    handler.on_leave(room_id.as_deref(), &user, quiet).await;
-                }
-
-                ServerMessage::Chat {
-                    user,
-                    message,
-                    timestamp,
-                } => {
-                    handler
-                        .on_chat(room_id.as_deref(), &user, &message, timestamp)
-                        .await;
-                }
-
-                ServerMessage::Timestamp(timestamp) => {
-                    handler.on_timestamp(timestamp).await;
-                }
-
-                ServerMessage::Battle {
-                    room_id: battle_room_id,
-                    user1,
-                    user2,
-                } => {
-                    handler.on_battle(&battle_room_id, &user1, &user2).await;
-                }
-
-                ServerMessage::Notify {
-                    title,
-                    message,
-                    highlight_token,
-                } => {
-                    handler
-                        .on_notify(&title, message.as_deref(), highlight_token.as_deref())
-                        .await;
-                }
-
-                ServerMessage::Name {
-                    user,
-                    old_id,
-                    quiet,
-                } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut rooms) = self.state.rooms.write()
-                            && let Some(room) = rooms.get_mut(rid) {
-                                // Update user in room's user list
-                                if let Some(existing) = room
-                                    .users
-                                    .iter_mut()
-                                    .find(|u| u.username.to_lowercase() == old_id.to_lowercase())
-                                {
-                                    *existing = user.clone();
-                                }
-                            }
-                    handler
-                        .on_name(room_id.as_deref(), &user, &old_id, quiet)
-                        .await;
-                }
-
-                ServerMessage::Html(html) => {
-                    handler.on_html(room_id.as_deref(), &html).await;
-                }
-
-                ServerMessage::Uhtml { name, html } => {
-                    handler.on_uhtml(room_id.as_deref(), &name, &html).await;
-                }
-
-                ServerMessage::UhtmlChange { name, html } => {
-                    handler
-                        .on_uhtml_change(room_id.as_deref(), &name, &html)
-                        .await;
-                }
-
-                ServerMessage::Raw(content) => {
-                    handler.on_raw(room_id.as_deref(), &content).await;
-                }
-
-                // ===================
-                // Battle Initialization
-                // ===================
-                ServerMessage::BattlePlayer {
-                    player,
-                    username,
-                    avatar,
-                    rating,
-                } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write() {
-                            let battle = battles.entry(rid.clone()).or_insert_with(BattleInfo::new);
-                            battle.players.push(PlayerInfo {
-                                player,
-                                username: username.clone(),
-                                avatar: avatar.clone(),
-                                rating,
-                                team_size: 0,
-                            });
-                        }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::BattlePlayer {
-                            player,
-                            username,
-                            avatar,
-                            rating,
-                        })
-                        .await;
-                }
-
-                ServerMessage::TeamSize { player, size } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid)
-                                && let Some(p) = battle.players.iter_mut().find(|p| p.player == player) {
-                                    p.team_size = size;
-                                }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::TeamSize { player, size })
-                        .await;
-                }
-
-                ServerMessage::GameType(game_type) => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.game_type = Some(game_type);
-                            }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::GameType(game_type))
-                        .await;
-                }
-
-                ServerMessage::Gen(generation) => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.generation = generation;
-                            }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Gen(generation))
-                        .await;
-                }
-
-                ServerMessage::Tier(tier) => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.tier = tier.clone();
-                            }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Tier(tier))
-                        .await;
-                }
-
-                ServerMessage::Rated(message) => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.rated = true;
-                                battle.rated_message = message.clone();
-                            }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Rated(message))
-                        .await;
-                }
-
-                ServerMessage::Rule(rule) => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.rules.push(rule.clone());
-                            }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Rule(rule))
-                        .await;
-                }
-
-                ServerMessage::Poke {
-                    player,
-                    details,
-                    has_item,
-                } => {
-                    if let Some(ref rid) = room_id
-                        && let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.preview.push(PreviewPokemon {
-                                    player,
-                                    species: details.species.clone(),
-                                    level: details.level,
-                                    gender: details.gender,
-                                    has_item,
-                                });
-                            }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Poke {
-                                player,
-                                details,
-                                has_item,
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::BattleStart => {
-                    let battle_snapshot = if let Some(ref rid) = room_id {
-                        if let Ok(mut battles) = self.state.battles.write() {
-                            if let Some(battle) = battles.get_mut(rid) {
-                                battle.started = true;
-                                Some(battle.clone())
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
+                        match self.interceptors.apply(join) {
+                            Ok(message) => self.source.send(message).await?,
+                            Err(reason) => self.state.cancel_join(&room, &reason),
                         }
-                    } else {
-                        None
-                    };
-
-                    if let (Some(rid), Some(battle)) = (&room_id, battle_snapshot) {
-                        handler.on_battle_started(rid, &battle).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::BattleStart)
-                        .await;
-                }
-
-                // ===================
-                // Battle Progress
-                // ===================
-                ServerMessage::Request(ref json) => {
-                    if let Some(ref rid) = room_id
-                        && let Some(request) = BattleRequest::parse(json) {
-                            handler.on_request(rid, &request).await;
-                        }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Request(json.clone()))
-                        .await;
-                }
-
-                ServerMessage::Turn(turn) => {
-                    if let Some(ref rid) = room_id {
-                        if let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.turn = turn;
-                            }
-                        handler.on_turn(rid, turn).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Turn(turn))
-                        .await;
-                }
-
-                ServerMessage::Win(ref winner) => {
-                    if let Some(ref rid) = room_id {
-                        if let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.winner = Some(winner.clone());
-                            }
-                        handler.on_win(rid, winner).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Win(winner.clone()))
-                        .await;
-                }
-
-                ServerMessage::Tie => {
-                    if let Some(ref rid) = room_id {
-                        if let Ok(mut battles) = self.state.battles.write()
-                            && let Some(battle) = battles.get_mut(rid) {
-                                battle.tie = true;
-                            }
-                        handler.on_tie(rid).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Tie)
-                        .await;
-                }
-
-                ServerMessage::Inactive(ref message) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_inactive(rid, message).await;
                     }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Inactive(message.clone()))
-                        .await;
                 }
 
-                ServerMessage::InactiveOff(ref message) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_inactive_off(rid, message).await;
+                () = self.state.chat_due() => {
+                    // Choices and other commands never wait behind chat
+                    self.flush_commands().await?;
+                    if let Some(message) = self.state.pop_chat() {
+                        self.handle_command(message).await?;
                     }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::InactiveOff(message.clone()))
-                        .await;
-                }
-
-                // ===================
-                // Major Actions
-                // ===================
-                ServerMessage::Switch {
-                    ref pokemon,
-                    ref details,
-                    ref hp_status,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_switch(rid, pokemon, details, hp_status.as_ref(), false)
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Switch {
-                                pokemon: pokemon.clone(),
-                                details: details.clone(),
-                                hp_status: hp_status.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Drag {
-                    ref pokemon,
-                    ref details,
-                    ref hp_status,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_switch(rid, pokemon, details, hp_status.as_ref(), true)
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Drag {
-                                pokemon: pokemon.clone(),
-                                details: details.clone(),
-                                hp_status: hp_status.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Move {
-                    ref pokemon,
-                    ref move_name,
-                    ref target,
-                    ..
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_move_used(rid, pokemon, move_name, target.as_ref())
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), message)
-                        .await;
-                }
-
-                ServerMessage::Faint(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_faint(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Faint(pokemon.clone()))
-                        .await;
-                }
-
-                ServerMessage::Cant {
-                    ref pokemon,
-                    ref reason,
-                    ref move_name,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_cant(rid, pokemon, reason, move_name.as_deref())
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Cant {
-                                pokemon: pokemon.clone(),
-                                reason: reason.clone(),
-                                move_name: move_name.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                // ===================
-                // Minor Actions
-                // ===================
-                ServerMessage::Damage {
-                    ref pokemon,
-                    ref hp_status,
-                    ref from,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_damage(rid, pokemon, hp_status.as_ref(), from.as_deref()).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Damage {
-                                pokemon: pokemon.clone(),
-                                hp_status: hp_status.clone(),
-                                from: from.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Heal {
-                    ref pokemon,
-                    ref hp_status,
-                    ref from,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_heal(rid, pokemon, hp_status.as_ref(), from.as_deref()).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Heal {
-                                pokemon: pokemon.clone(),
-                                hp_status: hp_status.clone(),
-                                from: from.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Status {
-                    ref pokemon,
-                    ref status,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_status(rid, pokemon, status).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Status {
-                                pokemon: pokemon.clone(),
-                                status: status.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::CureStatus {
-                    ref pokemon,
-                    ref status,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_cure_status(rid, pokemon, status).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::CureStatus {
-                                pokemon: pokemon.clone(),
-                                status: status.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Boost {
-                    ref pokemon,
-                    stat,
-                    amount,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_boost(rid, pokemon, stat, amount).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Boost {
-                                pokemon: pokemon.clone(),
-                                stat,
-                                amount,
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Unboost {
-                    ref pokemon,
-                    stat,
-                    amount,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_unboost(rid, pokemon, stat, amount).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Unboost {
-                                pokemon: pokemon.clone(),
-                                stat,
-                                amount,
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Weather { ref weather, upkeep } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_weather(rid, weather, upkeep).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Weather {
-                                weather: weather.clone(),
-                                upkeep,
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::FieldStart(ref condition) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_field_start(rid, condition).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::FieldStart(condition.clone()))
-                        .await;
-                }
-
-                ServerMessage::FieldEnd(ref condition) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_field_end(rid, condition).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::FieldEnd(condition.clone()))
-                        .await;
-                }
-
-                ServerMessage::SideStart {
-                    ref side,
-                    ref condition,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_side_start(rid, side, condition).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::SideStart {
-                                side: side.clone(),
-                                condition: condition.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::SideEnd {
-                    ref side,
-                    ref condition,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_side_end(rid, side, condition).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::SideEnd {
-                                side: side.clone(),
-                                condition: condition.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Crit(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_crit(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Crit(pokemon.clone()))
-                        .await;
-                }
-
-                ServerMessage::SuperEffective(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_super_effective(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::SuperEffective(pokemon.clone()))
-                        .await;
-                }
-
-                ServerMessage::Resisted(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_resisted(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Resisted(pokemon.clone()))
-                        .await;
-                }
-
-                ServerMessage::Immune(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_immune(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Immune(pokemon.clone()))
-                        .await;
-                }
-
-                ServerMessage::Miss {
-                    ref source,
-                    ref target,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_miss(rid, source, target.as_ref()).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Miss {
-                                source: source.clone(),
-                                target: target.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Fail {
-                    ref pokemon,
-                    ref action,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_fail(rid, pokemon, action.as_deref()).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Fail {
-                                pokemon: pokemon.clone(),
-                                action: action.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Item {
-                    ref pokemon,
-                    ref item,
-                    ref from,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_item(rid, pokemon, item, from.as_deref()).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Item {
-                                pokemon: pokemon.clone(),
-                                item: item.clone(),
-                                from: from.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::EndItem {
-                    ref pokemon,
-                    ref item,
-                    ref from,
-                    eat,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_end_item(rid, pokemon, item, from.as_deref(), eat)
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::EndItem {
-                                pokemon: pokemon.clone(),
-                                item: item.clone(),
-                                from: from.clone(),
-                                eat,
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::Ability {
-                    ref pokemon,
-                    ref ability,
-                    ref from,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler
-                            .on_ability(rid, pokemon, ability, from.as_deref())
-                            .await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Ability {
-                                pokemon: pokemon.clone(),
-                                ability: ability.clone(),
-                                from: from.clone(),
-                            },
-                        )
-                        .await;
-                }
-
-                ServerMessage::EndAbility(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_end_ability(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::EndAbility(pokemon.clone()))
-                        .await;
                 }
+            }
+        }
+    }
 
-                ServerMessage::Mega {
-                    ref pokemon,
-                    ref megastone,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_mega(rid, pokemon, megastone).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Mega {
-                                pokemon: pokemon.clone(),
-                                megastone: megastone.clone(),
-                            },
-                        )
-                        .await;
-                }
+    async fn handle_command(&mut self, msg: ClientMessage) -> Result<()> {
+        match self.interceptors.apply(msg) {
+            Ok(message) => self.source.send(message).await,
+            Err(_) => Ok(()),
+        }
+    }
 
-                ServerMessage::Primal(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_primal(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Primal(pokemon.clone()))
-                        .await;
-                }
+    async fn flush_commands(&mut self) -> Result<()> {
+        while let Ok(cmd) = self.cmd_rx.try_recv() {
+            self.handle_command(cmd).await?;
+        }
+        Ok(())
+    }
 
-                ServerMessage::ZPower(ref pokemon) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_z_power(rid, pokemon).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::ZPower(pokemon.clone()))
-                        .await;
-                }
+    /// Send what's left when the run loop stops: commands, then queued chat
+    async fn finish(&mut self) -> Result<()> {
+        self.flush_commands().await?;
+        for message in self.state.take_all_chat() {
+            self.handle_command(message).await?;
+        }
+        Ok(())
+    }
 
-                ServerMessage::Burst {
-                    ref pokemon,
-                    ref species,
-                    ref item,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_ultra_burst(rid, pokemon, species, item).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Burst {
-                                pokemon: pokemon.clone(),
-                                species: species.clone(),
-                                item: item.clone(),
-                            },
-                        )
-                        .await;
-                }
+    /// Send messages that were held back, after the current frame
+    fn requeue(&self, messages: Vec<ClientMessage>) {
+        for message in messages {
+            // The receiver lives in self, so this can't fail
+            let _ = self.cmd_tx.send(message);
+        }
+    }
 
-                ServerMessage::Transform {
-                    ref pokemon,
-                    ref species,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_transform(rid, pokemon, species).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Transform {
-                                pokemon: pokemon.clone(),
-                                species: species.clone(),
-                            },
-                        )
-                        .await;
-                }
+    /// Answer an open team sheets prompt in `room_id` by the policy, rejecting
+    /// if an asked handler has no answer in time
+    async fn answer_ots_prompt<H: KazamHandler>(&self, room_id: &str, handler: &mut H) {
+        let policy = self
+            .state
+            .ots_policy
+            .read()
+            .map(|policy| *policy)
+            .unwrap_or_default();
+        let accept = match policy {
+            OtsPolicy::Accept => true,
+            OtsPolicy::Reject => false,
+            OtsPolicy::Ask { timeout } => {
+                tokio::time::timeout(timeout, handler.on_ots_prompt(room_id))
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or(false)
+            }
+        };
+        // The receiver lives in self, so this can't fail
+        let _ = self.cmd_tx.send(ClientMessage {
+            room_id: Some(room_id.to_string()),
+            command: ClientCommand::OpenTeamSheets(accept),
+        });
+    }
 
-                ServerMessage::Activate {
-                    ref pokemon,
-                    ref effect,
-                } => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_activate(rid, pokemon.as_ref(), effect).await;
-                    }
-                    handler
-                        .on_battle_message(
-                            room_id.as_deref(),
-                            ServerMessage::Activate {
-                                pokemon: pokemon.clone(),
-                                effect: effect.clone(),
-                            },
-                        )
-                        .await;
-                }
+    async fn dispatch_frame<H: KazamHandler>(
+        &mut self,
+        frame: ServerFrame,
+        handler: &mut H,
+    ) -> Result<()> {
+        let room_id = frame.room_id.as_deref();
+        // Chat in the same frame as |users| is the scrollback sent on join
+        let mut joined = false;
+        let defer_requests = self.state.defer_requests.load(Ordering::Relaxed);
+        let mut deferred = Vec::new();
+        let mut battle_changes = BattleInfoChanges::empty();
 
-                ServerMessage::Hint(ref msg) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_hint(rid, msg).await;
+        for message in frame.messages {
+            joined |= matches!(message, ServerMessage::Users(_));
+            let applied = dispatch::apply_to_state(&self.state, room_id, &message);
+            battle_changes |= applied.battle_changes;
+            let callbacks = dispatch::route(room_id, &message, &applied);
+            // Whatever runs next reads the handle, so it sees this message
+            // applied. Publishing a room the message didn't change is a no-op.
+            if let Some(rid) = room_id
+                && (self.events.is_some() || !callbacks.is_empty())
+            {
+                self.state.publish(rid);
+            }
+            if let Some(events) = &self.events {
+                events
+                    .push(ClientEvent::Message {
+                        room_id: frame.room_id.clone(),
+                        message: message.clone(),
+                    })
+                    .await;
+            }
+            let mut forward = false;
+            for callback in callbacks {
+                match callback {
+                    Callback::BattleMessage => forward = true,
+                    Callback::Request(request) if defer_requests => {
+                        deferred.push(request.clone());
+                    }
+                    Callback::OtsPrompt => {
+                        if let Some(rid) = room_id {
+                            self.answer_ots_prompt(rid, handler).await;
+                        }
                     }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Hint(msg.clone()))
-                        .await;
+                    callback => callback.invoke(handler, room_id).await,
                 }
+            }
+            self.requeue(applied.requeue);
+            if forward {
+                handler.on_battle_message(room_id, message).await;
+            }
+        }
 
-                ServerMessage::Message(ref msg) => {
-                    if let Some(ref rid) = room_id {
-                        handler.on_battle_message_text(rid, msg).await;
-                    }
-                    handler
-                        .on_battle_message(room_id.as_deref(), ServerMessage::Message(msg.clone()))
-                        .await;
-                }
+        if joined
+            && let Some(rid) = room_id
+            && let Ok(mut rooms) = self.state.rooms.write()
+            && let Some(room) = rooms.get_mut(rid).map(Arc::make_mut)
+        {
+            room.mark_live();
+        }
 
-                // All other battle messages just go to on_battle_message
-                other => {
-                    handler.on_battle_message(room_id.as_deref(), other).await;
-                }
+        if let Some(rid) = room_id {
+            self.state.publish(rid);
+            self.state.settle(rid);
+            if !battle_changes.is_empty()
+                && let Some(battle) = self
+                    .state
+                    .battles
+                    .read()
+                    .ok()
+                    .and_then(|battles| battles.get(rid).cloned())
+            {
+                handler
+                    .on_battle_info_changed(rid, &battle, battle_changes)
+                    .await;
+            }
+            for request in deferred {
+                Callback::Request(&request).invoke(handler, room_id).await;
             }
         }
         Ok(())
@@ -0,0 +1,232 @@
+//! Login server requests, with the session kept for reconnects
+//!
+//! A full login posts the password; the login server answers with an
+//! assertion for the challstr and a session cookie. After a reconnect the
+//! cookie alone is enough to get an assertion for the new challstr through
+//! `upkeep`, as the official client does, so flaky connections don't send
+//! a password login each time.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use kazam_protocol::user_id;
+use reqwest::header::{COOKIE, SET_COOKIE};
+
+/// Login API of play.pokemonshowdown.com
+pub const LOGIN_API_URL: &str = "https://play.pokemonshowdown.com/api";
+
+/// Name of the login server's session cookie
+const SESSION_COOKIE: &str = "sid";
+
+/// Assertion handed out for one challstr
+#[derive(Debug, Clone)]
+struct CachedAssertion {
+    userid: String,
+    challstr: String,
+    assertion: String,
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    /// `sid=...` from the last login or upkeep that set it
+    cookie: Option<String>,
+    assertion: Option<CachedAssertion>,
+}
+
+/// Client for the login server, shared by clones
+///
+/// Remembers the last assertion and the session cookie, see
+/// [`resume_or_login`](Self::resume_or_login).
+#[derive(Debug, Clone)]
+pub struct LoginClient {
+    url: String,
+    http: reqwest::Client,
+    session: Arc<Mutex<Session>>,
+}
+
+impl Default for LoginClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoginClient {
+    pub fn new() -> Self {
+        Self::with_url(LOGIN_API_URL)
+    }
+
+    /// Client for the login API at `url`, e.g. a local login server
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            session: Arc::new(Mutex::new(Session::default())),
+        }
+    }
+
+    /// Assertion for `challstr`, spending as little as possible on the login server
+    ///
+    /// Reuses the cached assertion when the challstr hasn't changed, then
+    /// tries `upkeep` with the session cookie, and only posts the password
+    /// when there is no cookie or the session behind it expired.
+    pub async fn resume_or_login(
+        &self,
+        username: &str,
+        password: &str,
+        challstr: &str,
+    ) -> Result<String> {
+        let userid = user_id(username);
+        if let Some(assertion) = self.cached_assertion(&userid, challstr) {
+            return Ok(assertion);
+        }
+        if self.has_session() {
+            match self.upkeep(challstr).await {
+                Ok(Some((name, assertion))) if user_id(&name) == userid => {
+                    return Ok(assertion);
+                }
+                Ok(_) => tracing::debug!("login session expired, logging in again"),
+                Err(e) => tracing::warn!("upkeep failed, logging in again: {}", e),
+            }
+        }
+        self.login(username, password, challstr).await
+    }
+
+    /// Log in with a password, always asking the login server
+    pub async fn login(&self, username: &str, password: &str, challstr: &str) -> Result<String> {
+        let params = [
+            ("act", "login"),
+            ("name", username),
+            ("pass", password),
+            ("challstr", challstr),
+        ];
+        let json = self.post("login", &params).await?;
+        let assertion = match json.get("assertion").and_then(|v| v.as_str()) {
+            Some(assertion) => assertion,
+            None => return Err(anyhow!("Login response missing assertion")),
+        };
+        if let Some(error_msg) = assertion.strip_prefix(";;") {
+            return Err(anyhow!("Login failed: {}", error_msg));
+        }
+        self.cache(user_id(username), challstr, assertion);
+        Ok(assertion.to_string())
+    }
+
+    /// Refresh the session for `challstr`, returns the session's username and
+    /// its assertion, or None if there is no session or it expired
+    pub async fn upkeep(&self, challstr: &str) -> Result<Option<(String, String)>> {
+        if !self.has_session() {
+            return Ok(None);
+        }
+        let json = self
+            .post("upkeep", &[("act", "upkeep"), ("challstr", challstr)])
+            .await?;
+        let logged_in = json.get("loggedin").and_then(|v| v.as_bool()) == Some(true);
+        let username = json.get("username").and_then(|v| v.as_str());
+        let assertion = json.get("assertion").and_then(|v| v.as_str());
+        match (logged_in, username, assertion) {
+            (true, Some(username), Some(assertion)) if !assertion.starts_with(";;") => {
+                self.cache(user_id(username), challstr, assertion);
+                Ok(Some((username.to_string(), assertion.to_string())))
+            }
+            _ => {
+                // The server ended the session; don't keep offering its cookie
+                self.forget_session();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Whether a session cookie is held for [`upkeep`](Self::upkeep)
+    pub fn has_session(&self) -> bool {
+        self.session.lock().is_ok_and(|s| s.cookie.is_some())
+    }
+
+    /// Drop the cached assertion, e.g. after the server rejected it
+    ///
+    /// The session cookie is kept, so the next login can still use `upkeep`.
+    pub fn forget_assertion(&self) {
+        if let Ok(mut session) = self.session.lock() {
+            session.assertion = None;
+        }
+    }
+
+    /// Drop the cached assertion and the session cookie
+    pub fn forget_session(&self) {
+        if let Ok(mut session) = self.session.lock() {
+            *session = Session::default();
+        }
+    }
+
+    fn cached_assertion(&self, userid: &str, challstr: &str) -> Option<String> {
+        let session = self.session.lock().ok()?;
+        session
+            .assertion
+            .as_ref()
+            .filter(|cached| cached.userid == userid && cached.challstr == challstr)
+            .map(|cached| cached.assertion.clone())
+    }
+
+    fn cache(&self, userid: String, challstr: &str, assertion: &str) {
+        if let Ok(mut session) = self.session.lock() {
+            session.assertion = Some(CachedAssertion {
+                userid,
+                challstr: challstr.to_string(),
+                assertion: assertion.to_string(),
+            });
+        }
+    }
+
+    /// Post a form to `action`, keeping the session cookie it sets
+    async fn post(&self, action: &str, params: &[(&str, &str)]) -> Result<serde_json::Value> {
+        let mut request = self
+            .http
+            .post(format!("{}/{}", self.url, action))
+            .form(params);
+        let cookie = self.session.lock().ok().and_then(|s| s.cookie.clone());
+        if let Some(cookie) = cookie {
+            request = request.header(COOKIE, cookie);
+        }
+        let response = request.send().await?;
+
+        for value in response.headers().get_all(SET_COOKIE) {
+            let Some(cookie) = value.to_str().ok().and_then(session_cookie) else {
+                continue;
+            };
+            if let Ok(mut session) = self.session.lock() {
+                session.cookie = cookie;
+            }
+        }
+
+        let text = response.text().await?;
+        // Response is prefixed with "]"
+        let json_str = text.trim_start_matches(']');
+        Ok(serde_json::from_str(json_str)?)
+    }
+}
+
+/// Session cookie from a Set-Cookie header: Some(None) when it is cleared,
+/// None when the header sets another cookie
+fn session_cookie(header: &str) -> Option<Option<String>> {
+    let pair = header.split(';').next()?.trim();
+    let (name, value) = pair.split_once('=')?;
+    if name != SESSION_COOKIE {
+        return None;
+    }
+    Some((!value.is_empty() && value != "deleted").then(|| pair.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_cookie() {
+        assert_eq!(
+            session_cookie("sid=abc%2C123; Path=/; HttpOnly"),
+            Some(Some("sid=abc%2C123".to_string()))
+        );
+        assert_eq!(session_cookie("sid=deleted; Max-Age=0"), Some(None));
+        assert_eq!(session_cookie("sid=; Path=/"), Some(None));
+        assert_eq!(session_cookie("theme=dark; Path=/"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Which of our rooms each user is in
+//!
+//! Kept up to date from |users|, |j|, |l| and |n| as they arrive, so asking
+//! where a user is doesn't scan every room's user list.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use kazam_protocol::{User, user_id};
+
+/// User id → rooms listing them, and the reverse for replacing a room's list
+#[derive(Debug, Default)]
+pub(crate) struct MemberIndex {
+    rooms_by_user: HashMap<String, BTreeSet<String>>,
+    users_by_room: HashMap<String, HashSet<String>>,
+}
+
+impl MemberIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace a room's members, e.g. from the |users| sent on join
+    pub fn set_room(&mut self, room_id: &str, users: &[User]) {
+        self.clear_room(room_id);
+        for user in users {
+            self.join(room_id, &user.username);
+        }
+    }
+
+    /// Forget everyone in a room, e.g. when we join it again
+    pub fn clear_room(&mut self, room_id: &str) {
+        for id in self.users_by_room.remove(room_id).unwrap_or_default() {
+            self.unlink(&id, room_id);
+        }
+    }
+
+    pub fn join(&mut self, room_id: &str, name: &str) {
+        let id = user_id(name);
+        if id.is_empty() {
+            return;
+        }
+        self.rooms_by_user
+            .entry(id.clone())
+            .or_default()
+            .insert(room_id.to_string());
+        self.users_by_room
+            .entry(room_id.to_string())
+            .or_default()
+            .insert(id);
+    }
+
+    pub fn leave(&mut self, room_id: &str, name: &str) {
+        let id = user_id(name);
+        if let Some(users) = self.users_by_room.get_mut(room_id) {
+            users.remove(&id);
+        }
+        self.unlink(&id, room_id);
+    }
+
+    /// Move a user renamed in `room_id` from their old id to the new name's
+    ///
+    /// The server sends |n| to every room the user is in, so each room moves
+    /// on its own message.
+    pub fn rename(&mut self, room_id: &str, old_id: &str, new_name: &str) {
+        let listed = self
+            .users_by_room
+            .get(room_id)
+            .is_some_and(|users| users.contains(&user_id(old_id)));
+        if listed {
+            self.leave(room_id, old_id);
+            self.join(room_id, new_name);
+        }
+    }
+
+    /// Rooms listing `name`, sorted
+    pub fn rooms_of(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.rooms_by_user
+            .get(&user_id(name))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn is_member(&self, room_id: &str, name: &str) -> bool {
+        self.rooms_by_user
+            .get(&user_id(name))
+            .is_some_and(|rooms| rooms.contains(room_id))
+    }
+
+    fn unlink(&mut self, id: &str, room_id: &str) {
+        if let Some(rooms) = self.rooms_by_user.get_mut(id) {
+            rooms.remove(room_id);
+            if rooms.is_empty() {
+                self.rooms_by_user.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str) -> User {
+        User::parse(name).unwrap()
+    }
+
+    #[test]
+    fn test_users_list_replaces_room() {
+        let mut index = MemberIndex::new();
+        index.set_room("lobby", &[user(" Alice"), user("@Bob")]);
+        index.set_room("help", &[user(" alice")]);
+        assert_eq!(
+            index.rooms_of("ALICE").collect::<Vec<_>>(),
+            ["help", "lobby"]
+        );
+
+        index.set_room("lobby", &[user(" Bob")]);
+        assert_eq!(index.rooms_of("Alice").collect::<Vec<_>>(), ["help"]);
+        assert!(index.is_member("lobby", "bob"));
+
+        index.leave("help", "Alice");
+        assert_eq!(index.rooms_of("Alice").count(), 0);
+        assert!(!index.rooms_by_user.contains_key("alice"));
+    }
+
+    #[test]
+    fn test_rename_moves_one_room_at_a_time() {
+        let mut index = MemberIndex::new();
+        index.set_room("lobby", &[user(" Alice")]);
+        index.set_room("help", &[user(" Alice")]);
+
+        index.rename("lobby", "alice", "Alicia");
+        assert!(index.is_member("lobby", "Alicia"));
+        assert!(!index.is_member("lobby", "Alice"));
+        assert!(index.is_member("help", "Alice"));
+
+        // A rename in a room they aren't listed in adds nobody
+        index.rename("tours", "alice", "Alicia");
+        assert!(!index.is_member("tours", "Alicia"));
+    }
+}
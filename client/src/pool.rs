@@ -0,0 +1,432 @@
+//! Several accounts in one process, each restarted on its own when it fails
+//!
+//! A [`KazamClientPool`] runs one supervisor task per account. Each attempt
+//! calls the account's factory, which connects, builds its handler and runs
+//! the client; the attempt runs in its own task, so an error or a panic only
+//! ends that account's attempt. The supervisor then waits out the account's
+//! backoff and calls the factory again for a fresh client. Reconnecting a
+//! dropped socket is still the connection's job; the pool only steps in once
+//! `run` gives up.
+//!
+//! Accounts coordinate through a [`Board`], string values shared by key that
+//! handlers can read, set and wait on.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::connection::ReconnectPolicy;
+use crate::handle::KazamHandle;
+
+type Attempt = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// Values shared between the accounts of a pool
+#[derive(Debug, Clone)]
+pub struct Board(Arc<watch::Sender<HashMap<String, String>>>);
+
+impl Default for Board {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(HashMap::new())))
+    }
+}
+
+impl Board {
+    pub fn set(&self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        self.0.send_if_modified(|entries| {
+            entries.insert(key.to_string(), value.clone()).as_ref() != Some(&value)
+        });
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.0.borrow().get(key).cloned()
+    }
+
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut removed = None;
+        self.0.send_if_modified(|entries| {
+            removed = entries.remove(key);
+            removed.is_some()
+        });
+        removed
+    }
+
+    /// Wait until `key` is set, returning its value right away if it is
+    pub async fn wait_for(&self, key: &str) -> String {
+        self.wait_until(key, |_| true).await
+    }
+
+    /// Wait until `key` holds a value other than `current`
+    pub async fn wait_for_change(&self, key: &str, current: Option<&str>) -> String {
+        self.wait_until(key, |value| Some(value) != current).await
+    }
+
+    async fn wait_until(&self, key: &str, accept: impl Fn(&str) -> bool) -> String {
+        let mut rx = self.0.subscribe();
+        // The sender lives in self, so the channel can't close while we wait
+        let entries = rx
+            .wait_for(|entries| entries.get(key).is_some_and(|value| accept(value)))
+            .await
+            .expect("board sender outlives its receivers");
+        entries[key].clone()
+    }
+}
+
+/// Shared view of a running pool: current handles, the board and shutdown
+#[derive(Clone)]
+pub struct PoolHandle {
+    handles: Arc<RwLock<HashMap<String, KazamHandle>>>,
+    board: Board,
+    stopping: Arc<watch::Sender<bool>>,
+}
+
+impl Default for PoolHandle {
+    fn default() -> Self {
+        Self {
+            handles: Arc::default(),
+            board: Board::default(),
+            stopping: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl PoolHandle {
+    /// Handle to the account's current client, None while it's restarting
+    pub fn account(&self, label: &str) -> Option<KazamHandle> {
+        self.handles.read().ok()?.get(label).cloned()
+    }
+
+    /// Labels of the accounts with a client running, sorted
+    pub fn running(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self
+            .handles
+            .read()
+            .map(|handles| handles.keys().cloned().collect())
+            .unwrap_or_default();
+        labels.sort();
+        labels
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Stop every account and don't restart any
+    ///
+    /// Each client finishes its frame and sends what it has queued, as with
+    /// [`KazamHandle::shutdown`].
+    pub fn shutdown(&self) {
+        self.stopping.send_replace(true);
+        if let Ok(handles) = self.handles.read() {
+            for handle in handles.values() {
+                handle.shutdown();
+            }
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.stopping.borrow()
+    }
+
+    fn attach(&self, label: &str, handle: KazamHandle) {
+        if self.is_shutting_down() {
+            handle.shutdown();
+        }
+        if let Ok(mut handles) = self.handles.write() {
+            handles.insert(label.to_string(), handle);
+        }
+    }
+
+    fn detach(&self, label: &str) {
+        if let Ok(mut handles) = self.handles.write() {
+            handles.remove(label);
+        }
+    }
+
+    async fn stopped(&self) {
+        let mut rx = self.stopping.subscribe();
+        let _ = rx.wait_for(|stopping| *stopping).await;
+    }
+}
+
+/// What an account's factory gets for one attempt
+#[derive(Clone)]
+pub struct AccountContext {
+    label: String,
+    attempt: u32,
+    pool: PoolHandle,
+}
+
+impl AccountContext {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// 0 for the first client, counting up with each restart
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.pool.board
+    }
+
+    pub fn pool(&self) -> &PoolHandle {
+        &self.pool
+    }
+
+    /// Publish this attempt's client as the account's handle
+    ///
+    /// Call it before `run`, so [`PoolHandle::account`] and
+    /// [`PoolHandle::shutdown`] reach the client.
+    pub fn attach(&self, handle: KazamHandle) {
+        self.pool.attach(&self.label, handle);
+    }
+}
+
+/// How an account's supervisor ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountReport {
+    pub label: String,
+
+    /// Clients started after the first one
+    pub restarts: u32,
+
+    /// Every failed attempt's error or panic message, oldest first
+    pub failures: Vec<String>,
+
+    /// Stopped after too many failures in a row rather than finishing
+    pub gave_up: bool,
+}
+
+struct Account {
+    label: String,
+    factory: Box<dyn FnMut(AccountContext) -> Attempt + Send>,
+}
+
+/// Runs several clients, restarting each one on its own when it fails
+///
+/// ```ignore
+/// let mut pool = KazamClientPool::new();
+/// pool.add("player", |ctx| async move {
+///     let mut client = KazamClient::connect(SHOWDOWN_URL).await?;
+///     ctx.attach(client.handle());
+///     let mut bot = Player::new(client.handle(), ctx.board().clone());
+///     client.run(&mut bot).await
+/// });
+/// let reports = pool.run().await;
+/// ```
+#[derive(Default)]
+pub struct KazamClientPool {
+    accounts: Vec<Account>,
+    policy: ReconnectPolicy,
+    pool: PoolHandle,
+}
+
+impl KazamClientPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Backoff between restarts of the same account
+    ///
+    /// `max_attempts` bounds failures in a row; an attempt that ran for
+    /// `max_delay` or longer starts the count and the delay over.
+    pub fn set_restart_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    /// Add an account; `factory` is called for each attempt to connect, build
+    /// the handler and run the client until it stops
+    ///
+    /// Returning `Ok` ends the account for good, as when
+    /// [`PoolHandle::shutdown`] stops its client. An error or a panic restarts it.
+    pub fn add<F, Fut>(&mut self, label: impl Into<String>, mut factory: F)
+    where
+        F: FnMut(AccountContext) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.accounts.push(Account {
+            label: label.into(),
+            factory: Box::new(move |ctx| Box::pin(factory(ctx))),
+        });
+    }
+
+    pub fn handle(&self) -> PoolHandle {
+        self.pool.clone()
+    }
+
+    /// Run every account until each finishes, gives up, or the pool shuts down
+    ///
+    /// Reports come in the order the accounts were added.
+    pub async fn run(self) -> Vec<AccountReport> {
+        let supervisors: Vec<_> = self
+            .accounts
+            .into_iter()
+            .map(|account| tokio::spawn(supervise(account, self.policy.clone(), self.pool.clone())))
+            .collect();
+        let mut reports = Vec::with_capacity(supervisors.len());
+        for supervisor in supervisors {
+            // Attempts run in tasks of their own, so supervisors don't panic
+            if let Ok(report) = supervisor.await {
+                reports.push(report);
+            }
+        }
+        reports
+    }
+}
+
+async fn supervise(
+    mut account: Account,
+    policy: ReconnectPolicy,
+    pool: PoolHandle,
+) -> AccountReport {
+    let mut report = AccountReport {
+        label: account.label.clone(),
+        restarts: 0,
+        failures: Vec::new(),
+        gave_up: false,
+    };
+    let mut delay = policy.initial_delay;
+    let mut failures_in_a_row = 0;
+
+    for attempt in 0.. {
+        if pool.is_shutting_down() {
+            break;
+        }
+        report.restarts = attempt;
+        let ctx = AccountContext {
+            label: account.label.clone(),
+            attempt,
+            pool: pool.clone(),
+        };
+        let started = Instant::now();
+        let outcome = tokio::spawn((account.factory)(ctx)).await;
+        pool.detach(&account.label);
+
+        let failure = match outcome {
+            Ok(Ok(())) => break,
+            Ok(Err(e)) => format!("{e:#}"),
+            Err(e) if e.is_panic() => panic_message(e.into_panic()),
+            Err(e) => e.to_string(),
+        };
+        tracing::warn!(account = %account.label, attempt, error = %failure, "Account stopped");
+        report.failures.push(failure);
+        if pool.is_shutting_down() {
+            break;
+        }
+
+        if started.elapsed() >= policy.max_delay {
+            failures_in_a_row = 0;
+            delay = policy.initial_delay;
+        }
+        failures_in_a_row += 1;
+        if policy
+            .max_attempts
+            .is_some_and(|max| failures_in_a_row > max)
+        {
+            report.gave_up = true;
+            break;
+        }
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {}
+            () = pool.stopped() => break,
+        }
+        delay = delay
+            .mul_f64(policy.backoff_multiplier)
+            .min(policy.max_delay)
+            .max(Duration::ZERO);
+    }
+    report
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("panicked: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_board_waits_for_values() {
+        let board = Board::default();
+        assert_eq!(board.get("battle"), None);
+
+        let waiter = tokio::spawn({
+            let board = board.clone();
+            async move { board.wait_for("battle").await }
+        });
+        board.set("other", "x");
+        board.set("battle", "battle-gen9ou-1");
+        assert_eq!(waiter.await.unwrap(), "battle-gen9ou-1");
+        // Already set, so no waiting
+        assert_eq!(board.wait_for("battle").await, "battle-gen9ou-1");
+
+        let change = tokio::spawn({
+            let board = board.clone();
+            async move {
+                board
+                    .wait_for_change("battle", Some("battle-gen9ou-1"))
+                    .await
+            }
+        });
+        board.set("battle", "battle-gen9ou-1");
+        tokio::task::yield_now().await;
+        assert!(!change.is_finished());
+        board.set("battle", "battle-gen9ou-2");
+        assert_eq!(change.await.unwrap(), "battle-gen9ou-2");
+        assert_eq!(board.remove("battle").as_deref(), Some("battle-gen9ou-2"));
+        assert_eq!(board.get("battle"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_attempts_with_backoff() {
+        let mut pool = KazamClientPool::new();
+        pool.set_restart_policy(ReconnectPolicy {
+            max_attempts: Some(2),
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            backoff_multiplier: 2.0,
+        });
+        let starts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = starts.clone();
+        pool.add("flaky", move |ctx| {
+            recorded.lock().unwrap().push(Instant::now());
+            async move { anyhow::bail!("attempt {} failed", ctx.attempt()) }
+        });
+        pool.add("panicky", |_| async { panic!("handler bug") });
+
+        let begin = Instant::now();
+        let reports = pool.run().await;
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].restarts, 2);
+        assert!(reports[0].gave_up);
+        assert_eq!(
+            reports[0].failures,
+            ["attempt 0 failed", "attempt 1 failed", "attempt 2 failed"]
+        );
+        assert_eq!(reports[1].failures[0], "panicked: handler bug");
+
+        let offsets: Vec<_> = starts.lock().unwrap().iter().map(|t| *t - begin).collect();
+        assert_eq!(
+            offsets,
+            [
+                Duration::ZERO,
+                Duration::from_secs(1),
+                Duration::from_secs(3)
+            ]
+        );
+    }
+}
@@ -0,0 +1,15 @@
+//! Commonly used types for writing a bot
+//!
+//! ```ignore
+//! use kazam_client::prelude::*;
+//! ```
+
+pub use crate::{
+    Backpressure, BattleInfoChanges, BattleOutcome, BattleTimings, ChatLine, ClientError, ClientEvent, ConnectError, KazamClient, KazamHandle,
+    HighlightMatcher, KazamHandler, KeepaliveConfig, LadderUpdate, LoginClient, RoomState, SendDecision, ServerAddress, TimeBudget, SHOWDOWN_URL,
+};
+pub use kazam_protocol::{
+    ActivePokemon, BattleInfo, BattleRequest, ChatContent, Format, FormatSection, GameType,
+    HpStatus, ModerationEvent, MoveSlot, Player, Pokemon, PokemonDetails, RoomType, ServerMessage,
+    SidePokemon, Stat, User,
+};
@@ -1,4 +1,23 @@
-use kazam_protocol::{RoomType, User};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use kazam_protocol::{RoomType, User, same_user};
+
+/// Chat lines kept per room unless configured otherwise
+pub const DEFAULT_CHAT_HISTORY: usize = 100;
+
+/// A timestamped line this much older than the server time anchor is history
+pub const HISTORY_AGE_SECS: i64 = 60;
+
+/// A chat line seen in a room
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatLine {
+    pub user: User,
+    pub message: String,
+    pub timestamp: Option<i64>,
+    /// Scrollback sent on join rather than said while we were in the room
+    pub is_history: bool,
+}
 
 #[derive(Debug, Clone)]
 pub struct RoomState {
@@ -6,4 +25,102 @@ pub struct RoomState {
     pub room_type: RoomType,
     pub title: Option<String>,
     pub users: Vec<User>,
+    user_count: usize,
+    /// Shared so that copying a room for a state snapshot doesn't copy its chat
+    history: VecDeque<Arc<ChatLine>>,
+    history_capacity: usize,
+    /// Set once the join burst (|init| through |users|) has been delivered
+    live: bool,
+}
+
+impl RoomState {
+    pub fn new(id: impl Into<String>, room_type: RoomType) -> Self {
+        Self {
+            id: id.into(),
+            room_type,
+            title: None,
+            users: vec![],
+            user_count: 0,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_CHAT_HISTORY,
+            live: false,
+        }
+    }
+
+    /// Set how many chat lines are kept, dropping the oldest beyond it
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    /// Recent chat lines, oldest first
+    pub fn chat_history(&self) -> impl Iterator<Item = &ChatLine> {
+        self.history.iter().map(Arc::as_ref)
+    }
+
+    /// Store a chat line, dropping the oldest once at capacity
+    pub fn push_chat(&mut self, line: ChatLine) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(Arc::new(line));
+    }
+
+    /// Whether the join burst is over and new chat is live
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Mark the join burst as delivered
+    pub fn mark_live(&mut self) {
+        self.live = true;
+    }
+
+    /// Number of users currently in the room.
+    ///
+    /// Seeded from |users| and kept up to date by |j|, |l| and room-level |usercount|.
+    pub fn user_count(&self) -> usize {
+        self.user_count
+    }
+
+    /// Replace the user list (from |users|)
+    pub fn set_users(&mut self, users: Vec<User>) {
+        self.user_count = users.len();
+        self.users = users;
+    }
+
+    /// Override the user count (from a room-level |usercount|)
+    pub fn set_user_count(&mut self, count: usize) {
+        self.user_count = count;
+    }
+
+    /// Add a user, returns false if they were already listed under any spelling
+    pub fn add_user(&mut self, user: User) -> bool {
+        if self
+            .users
+            .iter()
+            .any(|u| same_user(&u.username, &user.username))
+        {
+            return false;
+        }
+        self.users.push(user);
+        self.user_count += 1;
+        true
+    }
+
+    /// Remove a user by name, compared by user id, returns false if they weren't listed
+    pub fn remove_user(&mut self, username: &str) -> bool {
+        let before = self.users.len();
+        self.users.retain(|u| !same_user(&u.username, username));
+        if self.users.len() == before {
+            return false;
+        }
+        self.user_count = self.user_count.saturating_sub(1);
+        true
+    }
 }
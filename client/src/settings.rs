@@ -0,0 +1,208 @@
+//! Account settings the server confirms with a reply
+//!
+//! Replies come back as `/text` or `/error` PMs from the server user (`~`), or
+//! as a popup. Being told a setting is already in place counts as applied.
+
+use kazam_protocol::ClientCommand;
+
+/// A protective account setting, re-applied after reconnecting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountSetting {
+    /// Refuse (true) or accept (false) incoming challenges
+    BlockChallenges(bool),
+    /// Only accept PMs from staff and users at this level (`Some("")` for staff
+    /// only), or from everyone (`None`)
+    BlockPms(Option<String>),
+    /// Keep our battles off the room list
+    HideBattles(bool),
+}
+
+/// Which setting a reply is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SettingKind {
+    Challenges,
+    Pms,
+    Battles,
+}
+
+/// A server reply confirming or rejecting a setting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SettingReply {
+    pub kind: SettingKind,
+    /// Whether the setting is now on (blocking or hiding); unknown for errors
+    pub enabled: Option<bool>,
+    /// The server's message when it refused the setting
+    pub error: Option<String>,
+}
+
+impl AccountSetting {
+    /// The command that applies this setting
+    pub fn command(&self) -> ClientCommand {
+        match self {
+            Self::BlockChallenges(true) => ClientCommand::BlockChallenges,
+            Self::BlockChallenges(false) => ClientCommand::AllowChallenges,
+            Self::BlockPms(Some(level)) => ClientCommand::BlockPms(level.clone()),
+            Self::BlockPms(None) => ClientCommand::AllowPms,
+            Self::HideBattles(hidden) => ClientCommand::HideBattles(*hidden),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> SettingKind {
+        match self {
+            Self::BlockChallenges(_) => SettingKind::Challenges,
+            Self::BlockPms(_) => SettingKind::Pms,
+            Self::HideBattles(_) => SettingKind::Battles,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        match self {
+            Self::BlockChallenges(on) | Self::HideBattles(on) => *on,
+            Self::BlockPms(level) => level.is_some(),
+        }
+    }
+
+    /// Whether `reply` answers the command for this setting
+    pub(crate) fn answered_by(&self, reply: &SettingReply) -> bool {
+        self.kind() == reply.kind && reply.enabled.is_none_or(|on| on == self.enabled())
+    }
+}
+
+impl SettingReply {
+    fn applied(kind: SettingKind, enabled: bool) -> Self {
+        Self {
+            kind,
+            enabled: Some(enabled),
+            error: None,
+        }
+    }
+
+    /// Recognize a reply in a PM from the server user
+    pub fn from_pm(message: &str) -> Option<Self> {
+        if let Some(text) = message.strip_prefix("/text ") {
+            return Self::parse(text);
+        }
+        let error = message.strip_prefix("/error ")?;
+        Self::parse(error).or_else(|| Self::parse_error(error))
+    }
+
+    /// Recognize a confirmation, or an error that still leaves the setting in place
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (kind, enabled) = if text.starts_with("You are now blocking all incoming challenge")
+            || text.starts_with("You are already blocking challenges")
+        {
+            (SettingKind::Challenges, true)
+        } else if text.starts_with("You are available for challenges")
+            || text.starts_with("You are already available for challenges")
+        {
+            (SettingKind::Challenges, false)
+        } else if text.starts_with("You are now blocking private messages")
+            || text.starts_with("You are already blocking private messages")
+        {
+            (SettingKind::Pms, true)
+        } else if text.starts_with("You are no longer blocking private messages")
+            || text.starts_with("You are not blocking private messages")
+        {
+            (SettingKind::Pms, false)
+        } else if text.starts_with("Your next battle will be hidden")
+            || text.starts_with("Your next battle will be invite-only")
+        {
+            (SettingKind::Battles, true)
+        } else if text.starts_with("Your next battle will be publicly visible") {
+            (SettingKind::Battles, false)
+        } else {
+            return None;
+        };
+        Some(Self::applied(kind, enabled))
+    }
+
+    /// Recognize a refusal, which names the command ("/hidenext - Access denied.")
+    fn parse_error(text: &str) -> Option<Self> {
+        let command = text.split_whitespace().next()?;
+        let kind = match command {
+            "/blockchallenges" | "/unblockchallenges" => SettingKind::Challenges,
+            "/blockpms" | "/unblockpms" => SettingKind::Pms,
+            "/hidenext" => SettingKind::Battles,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            enabled: None,
+            error: Some(text.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmations() {
+        let cases = [
+            (
+                "/text You are now blocking all incoming challenge requests.",
+                AccountSetting::BlockChallenges(true),
+            ),
+            (
+                "/text You are available for challenges from now on.",
+                AccountSetting::BlockChallenges(false),
+            ),
+            (
+                "/text You are now blocking private messages, except from staff and autoconfirmed users.",
+                AccountSetting::BlockPms(Some("autoconfirmed".to_string())),
+            ),
+            (
+                "/text You are no longer blocking private messages.",
+                AccountSetting::BlockPms(None),
+            ),
+            (
+                "/text Your next battle will be hidden from the room list.",
+                AccountSetting::HideBattles(true),
+            ),
+            (
+                "/text Your next battle will be publicly visible.",
+                AccountSetting::HideBattles(false),
+            ),
+        ];
+        for (message, setting) in &cases {
+            let reply = SettingReply::from_pm(message).unwrap();
+            assert!(setting.answered_by(&reply), "{}", message);
+            assert_eq!(reply.error, None);
+        }
+
+        let blocked = SettingReply::from_pm(cases[0].0).unwrap();
+        assert!(!AccountSetting::BlockChallenges(false).answered_by(&blocked));
+        assert!(!AccountSetting::HideBattles(true).answered_by(&blocked));
+    }
+
+    #[test]
+    fn test_already_set_counts_as_applied() {
+        let reply = SettingReply::from_pm("/error You are already blocking challenges!").unwrap();
+        assert!(AccountSetting::BlockChallenges(true).answered_by(&reply));
+        assert_eq!(reply.error, None);
+
+        let reply = SettingReply::from_pm(
+            "/error You are already blocking private messages! To unblock, use /unblockpms",
+        )
+        .unwrap();
+        assert!(AccountSetting::BlockPms(Some(String::new())).answered_by(&reply));
+
+        // Popups carry the text without a prefix
+        let reply =
+            SettingReply::parse("You are not blocking private messages! To block, use /blockpms")
+                .unwrap();
+        assert!(AccountSetting::BlockPms(None).answered_by(&reply));
+    }
+
+    #[test]
+    fn test_refusals() {
+        let reply = SettingReply::from_pm("/error /hidenext - Access denied.").unwrap();
+        assert!(AccountSetting::HideBattles(true).answered_by(&reply));
+        assert_eq!(reply.error.as_deref(), Some("/hidenext - Access denied."));
+
+        assert_eq!(SettingReply::from_pm("/error /join - Access denied."), None);
+        assert_eq!(SettingReply::from_pm("hi, please block challenges"), None);
+    }
+}
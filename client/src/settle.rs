@@ -0,0 +1,86 @@
+//! Whether a battle's latest request has been followed by the rest of its frame
+//!
+//! Handlers run message by message, so `on_request` fires before any lines
+//! after the |request| in the same frame are applied. A battle is unsettled
+//! from its request until the client finishes dispatching that frame.
+
+use tokio::sync::oneshot;
+
+/// Settling progress of one battle room
+#[derive(Debug, Default)]
+pub struct SettleState {
+    /// A request arrived in the frame being dispatched
+    unsettled: bool,
+    /// An |upkeep| or |turn| was applied since the latest request
+    boundary_seen: bool,
+    /// Waiting for the current frame to finish
+    waiters: Vec<oneshot::Sender<()>>,
+}
+
+impl SettleState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A request was applied; the room is unsettled until its frame ends
+    pub fn on_request(&mut self) {
+        self.unsettled = true;
+        self.boundary_seen = false;
+    }
+
+    /// An |upkeep| or |turn| was applied
+    pub fn on_boundary(&mut self) {
+        self.boundary_seen = true;
+    }
+
+    pub fn is_settled(&self) -> bool {
+        !self.unsettled
+    }
+
+    pub fn boundary_seen(&self) -> bool {
+        self.boundary_seen
+    }
+
+    /// Resolves once the current frame is dispatched, right away if settled
+    pub fn wait(&mut self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        if self.unsettled {
+            self.waiters.push(tx);
+        } else {
+            let _ = tx.send(());
+        }
+        rx
+    }
+
+    /// The frame holding the request was fully dispatched
+    pub fn settle(&mut self) {
+        self.unsettled = false;
+        for tx in self.waiters.drain(..) {
+            let _ = tx.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waiters_resolve_when_frame_ends() {
+        let mut state = SettleState::new();
+        assert!(state.wait().try_recv().is_ok());
+
+        state.on_boundary();
+        state.on_request();
+        assert!(!state.is_settled());
+        assert!(!state.boundary_seen());
+        let mut waiter = state.wait();
+        assert!(waiter.try_recv().is_err());
+
+        state.on_boundary();
+        state.settle();
+        assert!(state.is_settled());
+        assert!(state.boundary_seen());
+        assert!(waiter.try_recv().is_ok());
+    }
+}
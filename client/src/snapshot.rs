@@ -0,0 +1,155 @@
+//! Room and battle state published so reads don't block dispatch
+//!
+//! The dispatch task owns the `rooms` and `battles` maps in
+//! [`ClientState`](crate::handle::ClientState). Before running the callbacks
+//! for a message it publishes a [`StateSnapshot`] of them, and again at the end
+//! of each frame, and [`KazamHandle`](crate::KazamHandle) reads load the latest
+//! one instead of locking the maps. Loading holds the cell's read guard only
+//! long enough to clone an `Arc`, so a reader polling every frame never holds
+//! up dispatch, and a callback always sees its own message applied.
+//!
+//! Rooms and battles are shared between the maps and every snapshot, and
+//! copied on write, so publishing copies nothing but the map of `Arc`s.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use kazam_protocol::BattleInfo;
+use tokio::sync::watch;
+
+use crate::room::RoomState;
+
+/// Rooms and battles as of the latest dispatched message
+#[derive(Debug, Clone, Default)]
+pub struct StateSnapshot {
+    rooms: HashMap<Arc<str>, Arc<RoomState>>,
+    battles: HashMap<Arc<str>, Arc<BattleInfo>>,
+}
+
+impl StateSnapshot {
+    pub fn room(&self, room_id: &str) -> Option<&RoomState> {
+        self.rooms.get(room_id).map(Arc::as_ref)
+    }
+
+    pub fn battle(&self, room_id: &str) -> Option<&BattleInfo> {
+        self.battles.get(room_id).map(Arc::as_ref)
+    }
+
+    /// Every room we're in, in no particular order
+    pub fn rooms(&self) -> impl Iterator<Item = (&str, &RoomState)> {
+        self.rooms
+            .iter()
+            .map(|(id, room)| (id.as_ref(), room.as_ref()))
+    }
+
+    /// Every battle we're tracking, in no particular order
+    pub fn battles(&self) -> impl Iterator<Item = (&str, &BattleInfo)> {
+        self.battles
+            .iter()
+            .map(|(id, battle)| (id.as_ref(), battle.as_ref()))
+    }
+}
+
+/// The latest snapshot, replaced whole by the dispatch task
+pub(crate) struct SnapshotCell {
+    tx: watch::Sender<Arc<StateSnapshot>>,
+}
+
+impl SnapshotCell {
+    pub fn new() -> Self {
+        Self {
+            tx: watch::Sender::new(Arc::default()),
+        }
+    }
+
+    pub fn load(&self) -> Arc<StateSnapshot> {
+        self.tx.borrow().clone()
+    }
+
+    /// Publish `room_id`'s current state, returns whether it changed
+    ///
+    /// The canonical maps are copied on write while a snapshot shares their
+    /// entries, so a changed room or battle is a different `Arc`.
+    pub fn update(
+        &self,
+        room_id: &str,
+        room: Option<&Arc<RoomState>>,
+        battle: Option<&Arc<BattleInfo>>,
+    ) -> bool {
+        let current = self.load();
+        let room_changed = !same(current.rooms.get(room_id), room);
+        let battle_changed = !same(current.battles.get(room_id), battle);
+        if !room_changed && !battle_changed {
+            return false;
+        }
+        let mut next = StateSnapshot::clone(&current);
+        if room_changed {
+            replace(&mut next.rooms, room_id, room);
+        }
+        if battle_changed {
+            replace(&mut next.battles, room_id, battle);
+        }
+        self.tx.send_replace(Arc::new(next));
+        true
+    }
+}
+
+fn same<T>(published: Option<&Arc<T>>, current: Option<&Arc<T>>) -> bool {
+    match (published, current) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn replace<T>(map: &mut HashMap<Arc<str>, Arc<T>>, room_id: &str, value: Option<&Arc<T>>) {
+    match value {
+        Some(value) => {
+            map.insert(room_id.into(), value.clone());
+        }
+        None => {
+            map.remove(room_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::RoomType;
+
+    #[test]
+    fn test_update_shares_unchanged_rooms() {
+        let cell = SnapshotCell::new();
+        let lobby = Arc::new(RoomState::new("lobby", RoomType::Chat));
+        let mut battle = Arc::new(RoomState::new("battle-1", RoomType::Battle));
+        let info = Arc::new(BattleInfo::new());
+        assert!(cell.update("lobby", Some(&lobby), None));
+        assert!(cell.update("battle-1", Some(&battle), Some(&info)));
+        let before = cell.load();
+
+        // Nothing changed, nothing published
+        assert!(!cell.update("lobby", Some(&lobby), None));
+        assert!(Arc::ptr_eq(&before, &cell.load()));
+
+        // What dispatch does to a room the snapshot shares
+        Arc::make_mut(&mut battle).title = Some("Alice vs. Bob".to_string());
+        assert!(cell.update("battle-1", Some(&battle), Some(&info)));
+        let after = cell.load();
+        assert!(Arc::ptr_eq(&before.rooms["lobby"], &after.rooms["lobby"]));
+        assert!(Arc::ptr_eq(
+            &before.battles["battle-1"],
+            &after.battles["battle-1"]
+        ));
+        // An earlier snapshot keeps what it saw
+        assert_eq!(before.room("battle-1").unwrap().title, None);
+        assert_eq!(
+            after.room("battle-1").unwrap().title.as_deref(),
+            Some("Alice vs. Bob")
+        );
+
+        assert!(cell.update("battle-1", None, None));
+        assert!(cell.load().room("battle-1").is_none());
+        assert_eq!(cell.load().rooms().count(), 1);
+    }
+}
@@ -0,0 +1,191 @@
+//! Where a client's frames come from and its messages go
+//!
+//! [`KazamClient`](crate::KazamClient) reads from a [`MessageSource`]. The
+//! real one is [`Connection`](crate::Connection), which owns reconnects and
+//! keepalive. With the `test-util` feature, `ScriptedSource` plays back
+//! recorded frames without a socket.
+
+use anyhow::Result;
+use kazam_protocol::{ClientMessage, ServerFrame};
+
+#[cfg(feature = "test-util")]
+pub use scripted::{ScriptedSource, SentMessages};
+
+/// A stream of server frames plus a sink for our messages
+///
+/// Implementations can write `async fn`; the futures must be `Send` so
+/// [`KazamClient::into_stream`](crate::KazamClient::into_stream) can run the
+/// client on its own task.
+pub trait MessageSource: Send {
+    /// Next frame from the server, None once the source is exhausted
+    fn next_frame(&mut self) -> impl Future<Output = Result<Option<ServerFrame>>> + Send;
+
+    /// Send a message to the server
+    fn send(&mut self, message: ClientMessage) -> impl Future<Output = Result<()>> + Send;
+
+    /// Whether the source reconnected after a dropped connection since the last call
+    fn take_reconnected(&mut self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "test-util")]
+mod scripted {
+    use std::collections::VecDeque;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+    use kazam_protocol::{ClientCommand, ClientMessage, ServerFrame, parse_server_frame};
+    use tokio::time::Instant;
+
+    use super::MessageSource;
+
+    enum Step {
+        Frame(ServerFrame),
+        Reconnect,
+        Quiet(Duration),
+    }
+
+    /// Plays back a fixed list of frames and records what the client sends
+    pub struct ScriptedSource {
+        steps: VecDeque<Step>,
+        reconnected: bool,
+        /// End of the quiet period in progress, kept across cancelled reads
+        quiet_until: Option<Instant>,
+        sent: SentMessages,
+    }
+
+    impl ScriptedSource {
+        pub fn new(frames: impl IntoIterator<Item = ServerFrame>) -> Self {
+            Self {
+                steps: frames.into_iter().map(Step::Frame).collect(),
+                reconnected: false,
+                quiet_until: None,
+                sent: SentMessages::default(),
+            }
+        }
+
+        /// Drop the connection after the frames so far and continue with `frames`
+        pub fn then_reconnect(mut self, frames: impl IntoIterator<Item = ServerFrame>) -> Self {
+            self.steps.push_back(Step::Reconnect);
+            self.steps.extend(frames.into_iter().map(Step::Frame));
+            self
+        }
+
+        /// Stay connected but silent for `duration` after the frames so far
+        ///
+        /// Gives the client time to send paced messages before the script ends.
+        pub fn then_quiet(mut self, duration: Duration) -> Self {
+            self.steps.push_back(Step::Quiet(duration));
+            self
+        }
+
+        /// Continue with `frames` after the steps so far
+        pub fn then(mut self, frames: impl IntoIterator<Item = ServerFrame>) -> Self {
+            self.steps.extend(frames.into_iter().map(Step::Frame));
+            self
+        }
+
+        /// Parse frames as they arrive over the websocket (">ROOMID" then lines)
+        pub fn from_raw<I, S>(raw: I) -> Result<Self>
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            let frames = raw
+                .into_iter()
+                .map(|frame| parse_server_frame(frame.as_ref()))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Self::new(frames))
+        }
+
+        /// Parse a recording with one raw frame per block, blocks separated by blank lines
+        pub fn from_recording(recording: &str) -> Result<Self> {
+            Self::from_raw(
+                recording
+                    .split("\n\n")
+                    .map(str::trim)
+                    .filter(|frame| !frame.is_empty()),
+            )
+        }
+
+        /// Read a recording file (see [`ScriptedSource::from_recording`])
+        pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+            let path = path.as_ref();
+            let recording = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Self::from_recording(&recording.replace("\r\n", "\n"))
+        }
+
+        /// Handle to the messages sent so far, usable after the source moves into a client
+        pub fn sent(&self) -> SentMessages {
+            self.sent.clone()
+        }
+    }
+
+    impl MessageSource for ScriptedSource {
+        async fn next_frame(&mut self) -> Result<Option<ServerFrame>> {
+            loop {
+                if let Some(until) = self.quiet_until {
+                    tokio::time::sleep_until(until).await;
+                    self.quiet_until = None;
+                }
+                match self.steps.pop_front() {
+                    Some(Step::Frame(frame)) => return Ok(Some(frame)),
+                    Some(Step::Reconnect) => self.reconnected = true,
+                    Some(Step::Quiet(duration)) => {
+                        self.quiet_until = Some(Instant::now() + duration);
+                    }
+                    None => return Ok(None),
+                }
+            }
+        }
+
+        async fn send(&mut self, message: ClientMessage) -> Result<()> {
+            self.sent.0.lock().unwrap().push((Instant::now(), message));
+            Ok(())
+        }
+
+        fn take_reconnected(&mut self) -> bool {
+            std::mem::take(&mut self.reconnected)
+        }
+    }
+
+    /// Messages a [`ScriptedSource`] has received from the client
+    #[derive(Debug, Clone, Default)]
+    pub struct SentMessages(Arc<Mutex<Vec<(Instant, ClientMessage)>>>);
+
+    impl SentMessages {
+        /// Everything sent so far, in order
+        pub fn all(&self) -> Vec<ClientMessage> {
+            self.timed()
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect()
+        }
+
+        /// Everything sent so far with when it was sent, in order
+        pub fn timed(&self) -> Vec<(Instant, ClientMessage)> {
+            self.0.lock().unwrap().clone()
+        }
+
+        /// Battle choices sent so far as (room, choice, rqid)
+        pub fn choices(&self) -> Vec<(String, String, Option<u64>)> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|(_, message)| match &message.command {
+                    ClientCommand::Choose { choice, rqid } => Some((
+                        message.room_id.clone().unwrap_or_default(),
+                        choice.clone(),
+                        *rqid,
+                    )),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! The client as a [`Stream`] of events instead of handler callbacks
+//!
+//! [`KazamClient::into_stream`] runs the client on its own task and yields
+//! each server message, owned, with its room id, plus lifecycle events. State
+//! is kept exactly as in [`KazamClient::run`], so [`KazamHandle`] reads
+//! (rooms, battles, timers) work the same.
+//!
+//! Compared to a [`KazamHandler`](crate::KazamHandler):
+//!
+//! - Events are raw [`ServerMessage`]s; the handler's derived callbacks
+//!   (`on_room_joined`, `on_battle_started`, `on_battle_ended`, ...) have no
+//!   counterpart, so read those from the handle's state instead
+//! - Requests aren't flagged stale, and the open team sheets prompt is
+//!   answered by policy alone, [`OtsPolicy::Ask`](crate::OtsPolicy::Ask)
+//!   rejecting it
+//! - The client runs ahead of the consumer by up to the buffer's capacity,
+//!   so the handle's state can be newer than the event being looked at
+//! - A slow consumer either holds up the connection ([`Backpressure::Wait`])
+//!   or loses the oldest events ([`Backpressure::DropOldest`])
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::Stream;
+use kazam_protocol::ServerMessage;
+use tokio::sync::Notify;
+
+use crate::{KazamClient, KazamHandle, KazamHandler, MessageSource};
+
+/// Events buffered by [`KazamClient::into_stream`]
+pub const DEFAULT_STREAM_CAPACITY: usize = 256;
+
+/// What the client does when the stream's buffer is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Stop reading from the server until the consumer catches up
+    #[default]
+    Wait,
+    /// Keep reading, discarding the oldest buffered events; the consumer
+    /// gets [`ClientEvent::Lagged`] with how many it missed
+    DropOldest,
+}
+
+/// One item of the client's event stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent {
+    /// A server message, with the room of its frame
+    Message {
+        room_id: Option<String>,
+        message: ServerMessage,
+    },
+    /// The connection dropped and came back
+    Reconnected,
+    /// Events discarded under [`Backpressure::DropOldest`] since the last one delivered
+    Lagged(u64),
+    /// The client stopped: the source ran out, it was shut down, or it failed
+    /// with `error`. Always the last event.
+    Closed { error: Option<String> },
+}
+
+/// Bounded buffer between the client's task and the stream
+#[derive(Debug)]
+pub(crate) struct EventQueue {
+    events: Mutex<VecDeque<ClientEvent>>,
+    capacity: usize,
+    backpressure: Backpressure,
+    dropped: AtomicU64,
+    /// The stream was dropped; events go nowhere
+    abandoned: AtomicBool,
+    readable: Notify,
+    writable: Notify,
+}
+
+impl EventQueue {
+    fn new(capacity: usize, backpressure: Backpressure) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            backpressure,
+            dropped: AtomicU64::new(0),
+            abandoned: AtomicBool::new(false),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        }
+    }
+
+    /// Buffer an event, waiting for room under [`Backpressure::Wait`]
+    pub(crate) async fn push(&self, event: ClientEvent) {
+        loop {
+            {
+                let Ok(mut events) = self.events.lock() else {
+                    return;
+                };
+                if self.abandoned.load(Ordering::Acquire) {
+                    return;
+                }
+                if events.len() >= self.capacity && self.backpressure == Backpressure::DropOldest {
+                    events.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                if events.len() < self.capacity {
+                    events.push_back(event);
+                    self.readable.notify_one();
+                    return;
+                }
+            }
+            self.writable.notified().await;
+        }
+    }
+
+    /// Next event, reporting discarded ones first
+    async fn pop(&self) -> Option<ClientEvent> {
+        loop {
+            {
+                let mut events = self.events.lock().ok()?;
+                let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    return Some(ClientEvent::Lagged(dropped));
+                }
+                if let Some(event) = events.pop_front() {
+                    self.writable.notify_one();
+                    return Some(event);
+                }
+            }
+            self.readable.notified().await;
+        }
+    }
+}
+
+/// Reading end; dropping it stops the client
+struct Receiver {
+    queue: Arc<EventQueue>,
+    handle: KazamHandle,
+    closed: bool,
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.queue.abandoned.store(true, Ordering::Release);
+        self.queue.writable.notify_one();
+        self.handle.shutdown();
+    }
+}
+
+/// Handler for stream mode: state is kept by the client, events go to the queue
+struct Forwarder;
+
+impl KazamHandler for Forwarder {}
+
+impl<S: MessageSource + Sync + 'static> KazamClient<S> {
+    /// Run the client on its own task, as a stream of events buffering up to
+    /// [`DEFAULT_STREAM_CAPACITY`] and waiting for the consumer when full
+    ///
+    /// See the [module docs](crate::stream) for how this differs from
+    /// [`KazamClient::run`]. Dropping the stream shuts the client down.
+    pub fn into_stream(self) -> (KazamHandle, impl Stream<Item = ClientEvent> + Unpin + Send) {
+        self.into_stream_with(DEFAULT_STREAM_CAPACITY, Backpressure::Wait)
+    }
+
+    /// [`KazamClient::into_stream`] with a buffer of `capacity` events and
+    /// what to do when it's full
+    pub fn into_stream_with(
+        mut self,
+        capacity: usize,
+        backpressure: Backpressure,
+    ) -> (KazamHandle, impl Stream<Item = ClientEvent> + Unpin + Send) {
+        let queue = Arc::new(EventQueue::new(capacity, backpressure));
+        self.events = Some(queue.clone());
+        let handle = self.handle();
+        let task_queue = queue.clone();
+        tokio::spawn(async move {
+            let result = self.run(&mut Forwarder).await;
+            let error = result.err().map(|e| format!("{e:#}"));
+            task_queue.push(ClientEvent::Closed { error }).await;
+        });
+
+        let receiver = Receiver {
+            queue,
+            handle: handle.clone(),
+            closed: false,
+        };
+        let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            if receiver.closed {
+                return None;
+            }
+            let event = receiver.queue.pop().await?;
+            receiver.closed = matches!(event, ClientEvent::Closed { .. });
+            Some((event, receiver))
+        });
+        (handle, Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(n: usize) -> ClientEvent {
+        ClientEvent::Message {
+            room_id: None,
+            message: ServerMessage::Raw(n.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_reports_the_gap() {
+        let queue = EventQueue::new(2, Backpressure::DropOldest);
+        for n in 0..5 {
+            queue.push(chat(n)).await;
+        }
+        assert_eq!(queue.pop().await, Some(ClientEvent::Lagged(3)));
+        assert_eq!(queue.pop().await, Some(chat(3)));
+        assert_eq!(queue.pop().await, Some(chat(4)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_holds_the_producer() {
+        let queue = Arc::new(EventQueue::new(1, Backpressure::Wait));
+        queue.push(chat(0)).await;
+        let producer = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(chat(1)).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!producer.is_finished());
+
+        assert_eq!(queue.pop().await, Some(chat(0)));
+        producer.await.unwrap();
+        assert_eq!(queue.pop().await, Some(chat(1)));
+    }
+}
@@ -0,0 +1,141 @@
+//! Consent to open team sheets at team preview
+//!
+//! Formats with the optional Open Team Sheets rule ask each player at team
+//! preview with an `|uhtml|otsrequest|` prompt whose buttons send
+//! `/acceptopenteamsheets` or `/rejectopenteamsheets`. Once both players
+//! agree, the server sends each team as `|showteam|`; an unanswered prompt
+//! holds up team preview until the timer runs out.
+
+use std::time::Duration;
+
+use crate::announcement::strip_tags;
+
+/// Name of the uhtml box holding the prompt
+pub const OTS_PROMPT_NAME: &str = "otsrequest";
+
+/// How long [`OtsPolicy::Ask`] waits for [`crate::KazamHandler::on_ots_prompt`] by default
+pub const DEFAULT_OTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How to answer an open team sheets prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtsPolicy {
+    Accept,
+    Reject,
+    /// Call [`crate::KazamHandler::on_ots_prompt`], rejecting if it gives no
+    /// answer within `timeout`
+    Ask {
+        timeout: Duration,
+    },
+}
+
+impl Default for OtsPolicy {
+    fn default() -> Self {
+        Self::Ask {
+            timeout: DEFAULT_OTS_TIMEOUT,
+        }
+    }
+}
+
+/// An open team sheets message in a battle room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OtsNotice {
+    /// The prompt asking us to accept or reject
+    Prompt,
+    /// A player's answer; `username` is None when the server means us ("You ...")
+    Answer {
+        username: Option<String>,
+        accepted: bool,
+    },
+    /// The prompt was taken down, e.g. as the battle started
+    Closed,
+}
+
+impl OtsNotice {
+    /// Recognize a notice in `|uhtml|` or `|uhtmlchange|`
+    pub fn from_uhtml(name: &str, html: &str) -> Option<Self> {
+        if html.contains("/acceptopenteamsheets") {
+            return Some(Self::Prompt);
+        }
+        if name != OTS_PROMPT_NAME {
+            return None;
+        }
+        Self::parse(html).or(Some(Self::Closed))
+    }
+
+    /// Recognize an answer in a plain or HTML line, such as
+    /// "Alice has agreed to open team sheets." or "Bob rejected open team sheets."
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = strip_tags(text);
+        let text = text.trim().trim_end_matches('.').trim_end();
+        let (who, accepted) = [
+            (" has agreed to open team sheets", true),
+            (" have agreed to open team sheets", true),
+            (" rejected open team sheets", false),
+        ]
+        .into_iter()
+        .find_map(|(suffix, accepted)| {
+            let cut = text.len().checked_sub(suffix.len())?;
+            let tail = text.get(cut..)?;
+            tail.eq_ignore_ascii_case(suffix)
+                .then(|| (text[..cut].trim(), accepted))
+        })?;
+        if who.is_empty() {
+            return None;
+        }
+        let username = (!who.eq_ignore_ascii_case("you")).then(|| who.to_string());
+        Some(Self::Answer { username, accepted })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_protocol::{ServerMessage, parse_server_message};
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/open_team_sheets.log");
+
+    fn notices() -> Vec<OtsNotice> {
+        FIXTURE
+            .lines()
+            .filter_map(|line| match parse_server_message(line).ok()? {
+                ServerMessage::Uhtml { name, html } | ServerMessage::UhtmlChange { name, html } => {
+                    OtsNotice::from_uhtml(&name, &html)
+                }
+                ServerMessage::Raw(text) => {
+                    OtsNotice::parse(text.strip_prefix("|raw|").unwrap_or(&text))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_prompt_and_answers() {
+        assert_eq!(
+            notices(),
+            [
+                OtsNotice::Prompt,
+                OtsNotice::Answer {
+                    username: None,
+                    accepted: true
+                },
+                OtsNotice::Answer {
+                    username: Some("Rival Trainer".to_string()),
+                    accepted: true
+                },
+                OtsNotice::Answer {
+                    username: Some("Bob".to_string()),
+                    accepted: false
+                },
+                OtsNotice::Closed,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_uhtml_is_ignored() {
+        assert_eq!(OtsNotice::from_uhtml("poll", "<b>Vote!</b>"), None);
+        assert_eq!(OtsNotice::parse("Alice has agreed to a rematch."), None);
+        assert_eq!(OtsNotice::parse(" has agreed to open team sheets."), None);
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use kazam_protocol::TimerInfo;
+use tokio::time::Instant;
+
+/// Per-turn time used when the timer turns on before we've seen our own time left
+pub const DEFAULT_TURN_SECONDS: u32 = 150;
+
+/// Total bank used when the timer turns on before we've seen our own time left
+pub const DEFAULT_TOTAL_SECONDS: u32 = 300;
+
+/// Battle timer state for one room, fed by |inactive| messages, request
+/// arrivals and our own choices
+#[derive(Debug, Clone)]
+pub struct TimerState {
+    on: bool,
+    turn_seconds: u32,
+    total_seconds: u32,
+    /// When the seconds above were last known to be accurate
+    anchor: Instant,
+    /// Whether our clock has run since `anchor`, i.e. a decision is pending
+    running: bool,
+}
+
+impl TimerState {
+    pub fn new() -> Self {
+        Self {
+            on: false,
+            turn_seconds: DEFAULT_TURN_SECONDS,
+            total_seconds: DEFAULT_TOTAL_SECONDS,
+            anchor: Instant::now(),
+            running: false,
+        }
+    }
+
+    /// Apply an |inactive| message, ignoring the opponent's public time notices
+    pub fn on_inactive(&mut self, message: &str) {
+        match TimerInfo::parse(message) {
+            Some(TimerInfo::On) => self.on = true,
+            Some(TimerInfo::TimeLeft {
+                turn_seconds,
+                total_seconds,
+            }) => {
+                self.on = true;
+                self.turn_seconds = turn_seconds;
+                self.total_seconds = total_seconds;
+                self.anchor = Instant::now();
+                self.running = true;
+            }
+            Some(TimerInfo::PlayerTimeLeft { .. }) | None => {}
+        }
+    }
+
+    /// Apply an |inactiveoff| message
+    pub fn on_inactive_off(&mut self) {
+        self.on = false;
+    }
+
+    /// Record that a request was delivered, restarting the per-turn clock
+    pub fn on_request(&mut self) {
+        self.stop();
+        self.running = true;
+    }
+
+    /// Record that we sent a choice, stopping the clock until the next request
+    ///
+    /// The opponent's thinking time and the turn's animations aren't ours, so
+    /// only the time from request to choice comes out of the bank.
+    pub fn on_choice(&mut self) {
+        self.stop();
+    }
+
+    /// Charge the time since `anchor` to the bank if our clock was running
+    fn stop(&mut self) {
+        let now = Instant::now();
+        if self.running {
+            let spent = now.saturating_duration_since(self.anchor).as_secs() as u32;
+            self.total_seconds = self.total_seconds.saturating_sub(spent);
+        }
+        self.anchor = now;
+        self.running = false;
+    }
+
+    /// Snapshot of the remaining budget, None while the timer is off
+    pub fn budget(&self) -> Option<TimeBudget> {
+        self.on.then(|| TimeBudget {
+            deadline: self.anchor
+                + Duration::from_secs(self.turn_seconds.min(self.total_seconds) as u64),
+        })
+    }
+}
+
+impl Default for TimerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How long we can spend on the current decision before the battle timer runs out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBudget {
+    deadline: Instant,
+}
+
+impl TimeBudget {
+    /// Instant at which our time for this turn runs out
+    ///
+    /// Suitable for `tokio::time::timeout_at` around the bot's evaluation.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Time left before the deadline
+    pub fn remaining_this_turn(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    /// Time left minus a margin for sending the choice
+    pub fn safe_think_duration(&self, safety_margin: Duration) -> Duration {
+        self.remaining_this_turn().saturating_sub(safety_margin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_off_has_no_budget() {
+        let mut timer = TimerState::new();
+        assert!(timer.budget().is_none());
+
+        timer.on_inactive("Battle timer is ON: inactive players will automatically lose when time's up.");
+        assert!(timer.budget().is_some());
+
+        timer.on_inactive_off();
+        assert!(timer.budget().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_from_time_left() {
+        let mut timer = TimerState::new();
+        let start = Instant::now();
+        timer.on_inactive("Battle timer is ON: inactive players will automatically lose when time's up.");
+
+        // Format defaults until our own time left arrives
+        let budget = timer.budget().unwrap();
+        assert_eq!(budget.deadline(), start + Duration::from_secs(150));
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        timer.on_inactive("Time left: 60 sec this turn | 240 sec total");
+        let budget = timer.budget().unwrap();
+        assert_eq!(budget.deadline(), start + Duration::from_secs(65));
+
+        // The opponent's public notices don't affect our budget
+        timer.on_inactive("Bob has 30 seconds left.");
+        assert_eq!(timer.budget().unwrap(), budget);
+
+        tokio::time::advance(Duration::from_secs(20)).await;
+        assert_eq!(budget.remaining_this_turn(), Duration::from_secs(40));
+        assert_eq!(
+            budget.safe_think_duration(Duration::from_secs(5)),
+            Duration::from_secs(35)
+        );
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(budget.remaining_this_turn(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_restarts_turn_clock_against_bank() {
+        let mut timer = TimerState::new();
+        timer.on_inactive("Time left: 60 sec this turn | 70 sec total");
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        timer.on_request();
+        let requested = Instant::now();
+
+        // 40 seconds left in the bank caps the new turn
+        let budget = timer.budget().unwrap();
+        assert_eq!(budget.deadline(), requested + Duration::from_secs(40));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_opponent_delay_is_not_charged() {
+        let mut timer = TimerState::new();
+        timer.on_inactive("Time left: 60 sec this turn | 100 sec total");
+        timer.on_request();
+
+        // We answer in 10 seconds, then the opponent takes two minutes
+        tokio::time::advance(Duration::from_secs(10)).await;
+        timer.on_choice();
+        tokio::time::advance(Duration::from_secs(120)).await;
+        timer.on_request();
+        let requested = Instant::now();
+
+        // Only our 10 seconds came out of the bank
+        let budget = timer.budget().unwrap();
+        assert_eq!(budget.deadline(), requested + Duration::from_secs(60));
+        tokio::time::advance(Duration::from_secs(55)).await;
+        timer.on_choice();
+        timer.on_request();
+        let budget = timer.budget().unwrap();
+        assert_eq!(budget.deadline(), Instant::now() + Duration::from_secs(35));
+    }
+}
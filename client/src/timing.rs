@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use kazam_protocol::BattleInfo;
+use tokio::time::Instant;
+
+/// Timing state for one battle room, fed by |t:|, |start|, |turn|, requests and our choices
+#[derive(Debug, Clone, Default)]
+pub struct TimingState {
+    started: Option<(Instant, SystemTime)>,
+    ended: Option<(Instant, SystemTime)>,
+    /// Latest |t:| server time
+    server_time: Option<i64>,
+    /// Turn in progress: number, local arrival and server time at arrival
+    current_turn: Option<(u32, Instant, Option<i64>)>,
+    turn_durations: BTreeMap<u32, Duration>,
+    /// Arrival of the request we have not answered yet
+    pending_request: Option<(u64, Instant)>,
+    decision_latencies: BTreeMap<u64, Duration>,
+}
+
+impl TimingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a |t:| message
+    pub fn on_battle_timestamp(&mut self, timestamp: i64) {
+        self.server_time = Some(timestamp);
+    }
+
+    /// Record |start|
+    pub fn on_start(&mut self) {
+        self.started.get_or_insert((Instant::now(), SystemTime::now()));
+    }
+
+    /// Record a |turn|, closing the previous one
+    pub fn on_turn(&mut self, turn: u32) {
+        let now = Instant::now();
+        self.close_turn(now);
+        self.current_turn = Some((turn, now, self.server_time));
+    }
+
+    /// Record a request arrival
+    pub fn on_request(&mut self, rqid: Option<u64>) {
+        self.pending_request = rqid.map(|id| (id, Instant::now()));
+    }
+
+    /// Record that we sent a choice for `rqid`
+    pub fn on_choice(&mut self, rqid: u64) {
+        if let Some((id, arrived)) = self.pending_request
+            && id == rqid {
+                self.decision_latencies.insert(rqid, arrived.elapsed());
+            }
+    }
+
+    /// Record |win| or |tie|, closing the last turn
+    pub fn on_end(&mut self) {
+        if self.ended.is_some() {
+            return;
+        }
+        let now = Instant::now();
+        self.close_turn(now);
+        self.ended = Some((now, SystemTime::now()));
+    }
+
+    /// Turn durations use the |t:| times seen at both ends when present,
+    /// otherwise the local arrival times of the turn messages
+    fn close_turn(&mut self, now: Instant) {
+        let Some((turn, arrived, server_start)) = self.current_turn.take() else {
+            return;
+        };
+        let duration = match (server_start, self.server_time) {
+            (Some(start), Some(end)) if end > start => Duration::from_secs((end - start) as u64),
+            _ => now.saturating_duration_since(arrived),
+        };
+        self.turn_durations.insert(turn, duration);
+    }
+
+    /// Snapshot of everything recorded so far
+    pub fn snapshot(&self) -> BattleTimings {
+        let length = self.started.map(|(start, _)| match self.ended {
+            Some((end, _)) => end.saturating_duration_since(start),
+            None => start.elapsed(),
+        });
+        BattleTimings {
+            started_at: self.started.map(|(_, wall)| wall),
+            ended_at: self.ended.map(|(_, wall)| wall),
+            turn_durations: self.turn_durations.clone(),
+            decision_latencies: self.decision_latencies.clone(),
+            length,
+        }
+    }
+}
+
+/// Wall-clock timing of a battle
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BattleTimings {
+    /// Local time |start| arrived
+    pub started_at: Option<SystemTime>,
+
+    /// Local time |win| or |tie| arrived
+    pub ended_at: Option<SystemTime>,
+
+    /// How long each finished turn took, keyed by turn number
+    ///
+    /// Measured between |turn| arrivals, or between the |t:| server times in
+    /// effect at each end when the room sends them.
+    pub turn_durations: BTreeMap<u32, Duration>,
+
+    /// Time from a request's arrival to our choice, keyed by rqid
+    pub decision_latencies: BTreeMap<u64, Duration>,
+
+    /// Game length from |start| to the end, or to now while still running
+    pub length: Option<Duration>,
+}
+
+/// How a battle ended, passed to `KazamHandler::on_battle_ended`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BattleOutcome {
+    /// Winner's username, None on a tie
+    pub winner: Option<String>,
+
+    /// Whether the winner is the account we're logged in as, by user id
+    pub we_won: bool,
+
+    pub tie: bool,
+
+    /// Battle info as of the end
+    pub battle: BattleInfo,
+
+    pub timings: BattleTimings,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[tokio::test(start_paused = true)]
+    async fn test_turn_durations_from_arrivals() {
+        let mut timing = TimingState::new();
+        timing.on_start();
+        timing.on_turn(1);
+
+        tokio::time::advance(12 * SECOND).await;
+        timing.on_turn(2);
+        tokio::time::advance(30 * SECOND).await;
+        timing.on_turn(3);
+        tokio::time::advance(5 * SECOND).await;
+        assert_eq!(timing.snapshot().length, Some(47 * SECOND));
+
+        tokio::time::advance(3 * SECOND).await;
+        timing.on_end();
+        tokio::time::advance(60 * SECOND).await;
+
+        let timings = timing.snapshot();
+        assert_eq!(
+            timings.turn_durations,
+            BTreeMap::from([(1, 12 * SECOND), (2, 30 * SECOND), (3, 8 * SECOND)])
+        );
+        assert_eq!(timings.length, Some(50 * SECOND));
+        assert!(timings.started_at.is_some() && timings.ended_at.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_server_timestamps_are_authoritative() {
+        let mut timing = TimingState::new();
+        timing.on_battle_timestamp(1_700_000_000);
+        timing.on_start();
+        timing.on_turn(1);
+
+        // The frame is delivered late, but the server says 20 seconds passed
+        tokio::time::advance(25 * SECOND).await;
+        timing.on_battle_timestamp(1_700_000_020);
+        timing.on_turn(2);
+
+        // No |t:| for this turn, so the local clock decides
+        tokio::time::advance(7 * SECOND).await;
+        timing.on_turn(3);
+
+        let durations = timing.snapshot().turn_durations;
+        assert_eq!(durations[&1], 20 * SECOND);
+        assert_eq!(durations[&2], 7 * SECOND);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_decision_latency() {
+        let mut timing = TimingState::new();
+        timing.on_request(Some(3));
+        tokio::time::advance(4 * SECOND).await;
+        timing.on_choice(3);
+
+        // A choice for some other request is not measured
+        timing.on_request(Some(4));
+        tokio::time::advance(SECOND).await;
+        timing.on_choice(9);
+
+        assert_eq!(
+            timing.snapshot().decision_latencies,
+            BTreeMap::from([(3, 4 * SECOND)])
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Posting to a Discord webhook, retrying failures that may pass
+//!
+//! Rate limits (429) wait as long as the reply's `retry_after` asks, server
+//! errors and network failures back off per the [`ReconnectPolicy`]. Other
+//! refusals, such as a deleted webhook (404) or a malformed message (400),
+//! fail right away.
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::connection::ReconnectPolicy;
+
+/// Longest message content Discord accepts, in characters
+pub const MAX_WEBHOOK_CONTENT: usize = 2000;
+
+/// Posts messages to one webhook URL, shared by clones
+#[derive(Debug, Clone)]
+pub struct WebhookPoster {
+    url: String,
+    http: reqwest::Client,
+    policy: ReconnectPolicy,
+}
+
+impl WebhookPoster {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::Client::new(),
+            policy: ReconnectPolicy::default(),
+        }
+    }
+
+    /// How many times to retry and how long to wait between tries
+    pub fn set_retry_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    /// Post `content` as a plain message, cut to [`MAX_WEBHOOK_CONTENT`]
+    pub async fn post(&self, content: &str) -> Result<()> {
+        let content: String = content.chars().take(MAX_WEBHOOK_CONTENT).collect();
+        self.post_json(&json!({ "content": content })).await
+    }
+
+    /// Post any webhook body, e.g. one with embeds
+    pub async fn post_json(&self, body: &Value) -> Result<()> {
+        let mut delay = self.policy.initial_delay;
+        let mut retries = 0;
+        loop {
+            let (error, wait) = match self.http.post(&self.url).json(body).send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    let asked = response.json::<Value>().await.ok().and_then(|reply| {
+                        Duration::try_from_secs_f64(reply.get("retry_after")?.as_f64()?).ok()
+                    });
+                    let wait = asked.unwrap_or(delay);
+                    (anyhow!("rate limited for {wait:?}"), wait)
+                }
+                Ok(response) if response.status().is_server_error() => {
+                    (anyhow!("webhook returned {}", response.status()), delay)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let reply = response.text().await.unwrap_or_default();
+                    return Err(anyhow!("webhook refused the message ({status}): {reply}"));
+                }
+                Err(e) => (anyhow!("could not reach the webhook: {e}"), delay),
+            };
+            if self.policy.max_attempts.is_some_and(|max| retries >= max) {
+                return Err(error);
+            }
+            tracing::warn!("Webhook post failed, retrying: {:#}", error);
+            tokio::time::sleep(wait).await;
+            retries += 1;
+            delay = delay
+                .mul_f64(self.policy.backoff_multiplier)
+                .min(self.policy.max_delay);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Account settings confirmed by server replies and re-applied after reconnecting
+
+use kazam_client::{
+    AccountSetting, ClientError, KazamClient, KazamHandler, ScriptedSource, SentMessages, User,
+};
+use kazam_protocol::{ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+fn sent_commands(sent: &SentMessages) -> Vec<String> {
+    sent.all()
+        .iter()
+        .map(|m| m.command.to_protocol_string())
+        .collect()
+}
+
+/// Records how many commands had been sent at each login
+#[derive(Default)]
+struct Bot {
+    sent: Option<SentMessages>,
+    sent_at_login: Vec<usize>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_logged_in(&mut self, _user: &User) {
+        let count = self.sent.as_ref().map_or(0, |sent| sent.all().len());
+        self.sent_at_login.push(count);
+    }
+}
+
+const LOGIN: &[&str] = &["|challstr|4|aaaa", "|updateuser| KazamBot|1|1"];
+
+#[tokio::test]
+async fn test_settings_resolve_from_server_replies() {
+    let mut script = LOGIN.to_vec();
+    script.extend([
+        "|pm|~|~KazamBot|/text You are now blocking all incoming challenge requests.",
+        "|pm|~|~KazamBot|/error You are already blocking private messages! To unblock, use /unblockpms",
+        "|pm|~|~KazamBot|/error /hidenext - Access denied.",
+    ]);
+    let source = ScriptedSource::new(frames(&script));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let handle = client.handle();
+
+    // Replies are registered for when the call is made, before the frames arrive
+    let challenges = tokio::spawn(handle.block_challenges());
+    let pms = tokio::spawn(handle.block_pms("autoconfirmed"));
+    let hidden = tokio::spawn(handle.set_hidden_room_battles(true));
+    client.run(&mut Bot::default()).await.unwrap();
+
+    challenges.await.unwrap().unwrap();
+    pms.await.unwrap().unwrap();
+    let err = hidden.await.unwrap().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ClientError>(),
+        Some(&ClientError::SettingRejected {
+            setting: AccountSetting::HideBattles(true),
+            message: "/hidenext - Access denied.".to_string(),
+        })
+    );
+
+    assert_eq!(
+        sent_commands(&sent),
+        ["/blockchallenges", "/blockpms autoconfirmed", "/hidenext"]
+    );
+    // The refused setting isn't retried after a reconnect
+    assert_eq!(
+        handle.account_settings(),
+        [
+            AccountSetting::BlockChallenges(true),
+            AccountSetting::BlockPms(Some("autoconfirmed".to_string())),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_settings_reapplied_after_reconnect() {
+    let mut script = LOGIN.to_vec();
+    script.extend([
+        "|pm|~|~KazamBot|/text You are now blocking all incoming challenge requests.",
+        "|pm|~|~KazamBot|/text You are available for challenges from now on.",
+        "|pm|~|~KazamBot|/text You are now blocking private messages, except from staff.",
+    ]);
+    let source = ScriptedSource::new(frames(&script)).then_reconnect(frames(LOGIN));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let handle = client.handle();
+
+    let first = tokio::spawn(handle.block_challenges());
+    let second = tokio::spawn(handle.allow_challenges());
+    let pms = tokio::spawn(handle.block_pms(""));
+    let mut bot = Bot {
+        sent: Some(sent.clone()),
+        ..Bot::default()
+    };
+    client.run(&mut bot).await.unwrap();
+    for task in [first, second, pms] {
+        task.await.unwrap().unwrap();
+    }
+
+    // Only the latest challenge setting is re-applied, after logging back in
+    assert_eq!(bot.sent_at_login.len(), 2);
+    assert_eq!(bot.sent_at_login[1], 3);
+    assert_eq!(
+        sent_commands(&sent),
+        [
+            "/blockchallenges",
+            "/unblockchallenges",
+            "/blockpms",
+            "/unblockchallenges",
+            "/blockpms",
+        ]
+    );
+}
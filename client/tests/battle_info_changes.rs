@@ -0,0 +1,64 @@
+//! One battle info change notification per frame that changed it
+
+use kazam_client::{BattleInfoChanges, KazamClient, KazamHandler, ScriptedSource};
+use kazam_protocol::BattleInfo;
+
+const ROOM: &str = "battle-gen9ou-1";
+
+#[derive(Default)]
+struct Bot {
+    changes: Vec<(String, BattleInfoChanges, BattleInfo)>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_battle_info_changed(
+        &mut self,
+        room_id: &str,
+        battle: &BattleInfo,
+        changes: BattleInfoChanges,
+    ) {
+        self.changes
+            .push((room_id.to_string(), changes, battle.clone()));
+    }
+}
+
+#[tokio::test]
+async fn test_one_callback_per_changing_frame() {
+    let source = ScriptedSource::from_raw([
+        format!(">{ROOM}\n|init|battle\n|title|Alice vs. Bob"),
+        format!(
+            ">{ROOM}\n|player|p1|Alice|1|\n|player|p2|Bob|2|\n\
+             |teamsize|p1|6\n|teamsize|p2|6\n|gametype|singles"
+        ),
+        format!(">{ROOM}\n|c|Alice|glhf"),
+        format!(">{ROOM}\n|start\n|turn|1"),
+        format!(">{ROOM}\n|win|Alice"),
+    ])
+    .unwrap();
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot::default();
+    client.run(&mut bot).await.unwrap();
+
+    let flags: Vec<_> = bot.changes.iter().map(|(_, changes, _)| *changes).collect();
+    assert_eq!(
+        flags,
+        [
+            BattleInfoChanges::PLAYER_ADDED
+                | BattleInfoChanges::PLAYER_UPDATED
+                | BattleInfoChanges::FORMAT_SET,
+            BattleInfoChanges::STARTED | BattleInfoChanges::TURN_CHANGED,
+            BattleInfoChanges::ENDED,
+        ]
+    );
+    assert!(bot.changes.iter().all(|(room, _, _)| room == ROOM));
+
+    // Each callback sees the info as of the end of its frame
+    let (_, _, setup) = &bot.changes[0];
+    assert_eq!(setup.players.len(), 2);
+    assert!(setup.players.iter().all(|p| p.team_size == 6));
+    assert!(!setup.started);
+    let (_, _, started) = &bot.changes[1];
+    assert!(started.started);
+    assert_eq!(started.turn, 1);
+    assert_eq!(bot.changes[2].2.winner.as_deref(), Some("Alice"));
+}
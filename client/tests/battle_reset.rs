@@ -0,0 +1,131 @@
+//! A rematch that reuses the finished battle's room id
+
+use kazam_battle::TrackedBattle;
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource};
+use kazam_protocol::{
+    BattleInfo, BattleRequest, Player, ServerFrame, ServerMessage, parse_server_frame,
+};
+
+const ROOM: &str = "battle-gen9customgame-1";
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+const REQUEST: &str = r#"{"active":[{"moves":[{"move":"Tackle","id":"tackle","pp":35,"maxpp":35,"target":"normal","disabled":false}]}],"side":{"name":"KazamBot","id":"p1","pokemon":[{"ident":"p1: Pikachu","details":"Pikachu, L50","condition":"100/100","active":true,"stats":{"atk":60,"def":50,"spa":60,"spd":60,"spe":100},"moves":["tackle"],"baseAbility":"static","item":"","pokeball":"pokeball"}]},"rqid":RQID}"#;
+
+fn request(rqid: u64) -> String {
+    format!(
+        ">{ROOM}\n|request|{}",
+        REQUEST.replace("RQID", &rqid.to_string())
+    )
+}
+
+struct Bot {
+    handle: KazamHandle,
+    tracker: TrackedBattle,
+    resets: usize,
+    started: Vec<BattleInfo>,
+    stale: Vec<bool>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_battle_reset(&mut self, _room_id: &str) {
+        self.resets += 1;
+        self.tracker.reset(None);
+    }
+
+    async fn on_battle_started(&mut self, _room_id: &str, battle: &BattleInfo) {
+        self.started.push(battle.clone());
+    }
+
+    async fn on_request(&mut self, room_id: &str, request: &BattleRequest) {
+        self.stale.push(request.is_stale);
+        if !request.is_stale {
+            self.handle.choose(room_id, "move 1", request.rqid).unwrap();
+        }
+    }
+
+    async fn on_battle_message(&mut self, _room_id: Option<&str>, message: ServerMessage) {
+        self.tracker.apply_message(&message);
+    }
+}
+
+fn game(p1: &str, p2: &str, init: bool) -> String {
+    let init = if init { "|init|battle\n" } else { "" };
+    format!(
+        ">{ROOM}\n{init}|player|p1|{p1}|1|\n|player|p2|{p2}|2|\n|teamsize|p1|1\n|teamsize|p2|1\n\
+         |gametype|singles\n|gen|9\n|tier|[Gen 9] Custom Game\n|start\n\
+         |switch|p1a: Pikachu|Pikachu, L50|100/100\n|switch|p2a: Eevee|Eevee, L50|100/100\n|turn|1"
+    )
+}
+
+#[tokio::test]
+async fn test_rematch_in_same_room_starts_clean() {
+    let first = game("KazamBot", "Rival", true);
+    let first_move = format!(
+        ">{ROOM}\n|move|p1a: Pikachu|Tackle|p2a: Eevee\n|-damage|p2a: Eevee|0 fnt\n|faint|p2a: Eevee\n|win|KazamBot"
+    );
+    let second = game("KazamBot", "Challenger", false);
+    let source = ScriptedSource::new(frames(&[
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        &first,
+        &request(5),
+        &first_move,
+        &second,
+        &request(1),
+    ]));
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot {
+        handle: client.handle(),
+        tracker: TrackedBattle::new(),
+        resets: 0,
+        started: Vec::new(),
+        stale: Vec::new(),
+    };
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(bot.resets, 1);
+    assert_eq!(bot.started.len(), 2);
+    let rematch = &bot.started[1];
+    assert_eq!(rematch.players.len(), 2);
+    assert_eq!(
+        rematch.get_player(Player::P2).unwrap().username,
+        "Challenger"
+    );
+    assert!(!rematch.is_ended());
+
+    // The new game's request ids start over
+    assert_eq!(bot.stale, vec![false, false]);
+
+    let info = client.handle().get_battle(ROOM).unwrap();
+    assert_eq!(info.turn, 1);
+    assert!(info.winner.is_none());
+
+    assert!(!bot.tracker.ended);
+    assert_eq!(bot.tracker.turn, 1);
+    let opponent = bot.tracker.get_side(Player::P2).unwrap();
+    assert_eq!(opponent.username, "Challenger");
+    assert_eq!(opponent.pokemon.len(), 1);
+    assert!(!opponent.pokemon[0].fainted);
+}
+
+#[tokio::test]
+async fn test_leaving_player_after_win_is_not_a_rematch() {
+    let first = game("KazamBot", "Rival", true);
+    let end = format!(">{ROOM}\n|win|KazamBot\n|player|p2|");
+    let source = ScriptedSource::new(frames(&["|challstr|4|aaaa", &first, &end]));
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot {
+        handle: client.handle(),
+        tracker: TrackedBattle::new(),
+        resets: 0,
+        started: Vec::new(),
+        stale: Vec::new(),
+    };
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(bot.resets, 0);
+    assert!(client.handle().get_battle(ROOM).unwrap().is_ended());
+}
@@ -0,0 +1,110 @@
+//! Chat and PM bodies from a room with an HTML-replying bot
+
+use kazam_client::{ChatContent, KazamClient, KazamHandler, ScriptedSource, User};
+use kazam_protocol::{ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+#[derive(Default)]
+struct Bot {
+    chat: Vec<String>,
+    content: Vec<(String, ChatContent)>,
+    pms: Vec<(String, ChatContent)>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_chat(
+        &mut self,
+        _room_id: Option<&str>,
+        _user: &User,
+        message: &str,
+        _timestamp: Option<i64>,
+        _is_history: bool,
+    ) {
+        self.chat.push(message.to_string());
+    }
+
+    async fn on_chat_content(
+        &mut self,
+        room_id: Option<&str>,
+        user: &User,
+        content: &ChatContent,
+        _timestamp: Option<i64>,
+        _is_history: bool,
+    ) {
+        assert_eq!(room_id, Some("techcode"));
+        self.content.push((user.username.clone(), content.clone()));
+    }
+
+    async fn on_pm_content(&mut self, sender: &User, _receiver: &User, content: &ChatContent) {
+        self.pms.push((sender.username.clone(), content.clone()));
+    }
+}
+
+#[tokio::test]
+async fn test_bot_replies_resolved() {
+    let source = ScriptedSource::new(frames(&[
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        ">techcode\n|init|chat\n|title|Tech & Code\n|users|2,*DexBot,+Kazam",
+        ">techcode\n|c:|1700000001|+Kazam|/dt garchomp\n\
+         |c:|1700000002|*DexBot|/html <b>Garchomp</b> | Dragon/Ground\n\
+         |c:|1700000003|*DexBot|/uhtml dexsearch-1,<table><tr><td>Garchomp</td></tr></table>\n\
+         |c:|1700000004|*DexBot|/uhtmlchange dexsearch-1,<i>expired</i>\n\
+         |c:|1700000005|+Kazam|//dt is the command",
+        "|pm|*DexBot| KazamBot|/error Use this command in a room.",
+        "|pm|*DexBot| KazamBot|/raw <div class=\"infobox\">Ready</div>",
+    ]));
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot::default();
+    client.run(&mut bot).await.unwrap();
+
+    // on_chat still sees the bodies as sent
+    assert_eq!(bot.chat[1], "/html <b>Garchomp</b> | Dragon/Ground");
+    assert_eq!(
+        bot.content,
+        [
+            (
+                "Kazam".to_string(),
+                ChatContent::Text("/dt garchomp".to_string())
+            ),
+            (
+                "DexBot".to_string(),
+                ChatContent::Html("<b>Garchomp</b> | Dragon/Ground".to_string())
+            ),
+            (
+                "DexBot".to_string(),
+                ChatContent::Uhtml {
+                    name: "dexsearch-1".to_string(),
+                    html: "<table><tr><td>Garchomp</td></tr></table>".to_string(),
+                }
+            ),
+            (
+                "DexBot".to_string(),
+                ChatContent::UhtmlChange {
+                    name: "dexsearch-1".to_string(),
+                    html: "<i>expired</i>".to_string(),
+                }
+            ),
+            (
+                "Kazam".to_string(),
+                ChatContent::Text("/dt is the command".to_string())
+            ),
+        ]
+    );
+    assert_eq!(
+        bot.pms,
+        [
+            (
+                "DexBot".to_string(),
+                ChatContent::Error("Use this command in a room.".to_string())
+            ),
+            (
+                "DexBot".to_string(),
+                ChatContent::Html("<div class=\"infobox\">Ready</div>".to_string())
+            ),
+        ]
+    );
+}
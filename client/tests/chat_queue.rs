@@ -0,0 +1,130 @@
+//! Outgoing chat paced per room, with battle choices skipping the queue
+
+use std::time::Duration;
+
+use kazam_client::{
+    DEFAULT_CHAT_INTERVAL, KazamClient, KazamHandler, MAX_CHAT_LENGTH, ScriptedSource, SentMessages,
+};
+use kazam_protocol::{ClientCommand, ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+/// Chat lines sent to `room`, with the time since the first message sent
+fn chat(sent: &SentMessages, room: &str) -> Vec<(String, Duration)> {
+    let timed = sent.timed();
+    let Some((start, _)) = timed.first() else {
+        return Vec::new();
+    };
+    let start = *start;
+    timed
+        .into_iter()
+        .filter(|(_, message)| message.room_id.as_deref() == Some(room))
+        .filter_map(|(at, message)| match message.command {
+            ClientCommand::Chat(line) => Some((line, at - start)),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Bot;
+
+impl KazamHandler for Bot {}
+
+const LOGIN: &[&str] = &["|challstr|4|aaaa", "|updateuser| KazamBot|1|1"];
+
+#[tokio::test(start_paused = true)]
+async fn test_burst_paced_per_room_and_choices_skip_it() {
+    let source = ScriptedSource::new(frames(LOGIN)).then_quiet(Duration::from_secs(10));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let handle = client.handle();
+
+    for n in 1..=10 {
+        handle.send_chat("lobby", &format!("reply {n}")).unwrap();
+    }
+    handle.send_chat("help", "hi").unwrap();
+    assert_eq!(handle.chat_queue_depth("lobby"), 10);
+
+    let chooser = handle.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        chooser
+            .choose("battle-gen9ou-1", "move 1", Some(2))
+            .unwrap();
+    });
+    client.run(&mut Bot).await.unwrap();
+
+    // One lobby message per interval, never faster
+    let lobby = chat(&sent, "lobby");
+    assert_eq!(lobby.len(), 10);
+    for (n, (line, at)) in lobby.iter().enumerate() {
+        assert_eq!(line, &format!("reply {}", n + 1));
+        assert_eq!(*at, DEFAULT_CHAT_INTERVAL * n as u32);
+    }
+    // Another room doesn't wait for the lobby
+    assert_eq!(chat(&sent, "help"), [("hi".to_string(), Duration::ZERO)]);
+
+    // The choice went out when it was made, mid-burst
+    let start = sent.timed()[0].0;
+    let (chosen_at, _) = sent
+        .timed()
+        .into_iter()
+        .find(|(_, message)| matches!(message.command, ClientCommand::Choose { .. }))
+        .unwrap();
+    assert_eq!(chosen_at - start, Duration::from_millis(1000));
+    assert_eq!(handle.chat_queue_depth("lobby"), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_long_replies_chunked_and_coalesced() {
+    let source = ScriptedSource::new(frames(LOGIN)).then_quiet(Duration::from_secs(5));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_chat_coalescing(true);
+    client.set_chat_interval(Duration::from_millis(250));
+    let handle = client.handle();
+
+    handle
+        .send_chat("lobby", "Commands:\n.help\n.rank")
+        .unwrap();
+    handle.send_chat("lobby", &"ポケモン ".repeat(80)).unwrap();
+    client.run(&mut Bot).await.unwrap();
+
+    let lobby = chat(&sent, "lobby");
+    let lines: Vec<_> = lobby.iter().map(|(line, _)| line.as_str()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "Commands: | .help | .rank");
+    assert!(lines[1].ends_with('…') && lines[2].starts_with('…'));
+    for line in &lines {
+        assert!(line.encode_utf16().count() <= MAX_CHAT_LENGTH);
+    }
+    assert_eq!(lobby[2].1, Duration::from_millis(500));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_flush_and_clear() {
+    let source = ScriptedSource::new(frames(LOGIN)).then_quiet(Duration::from_secs(1));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_chat_interval(Duration::from_secs(10));
+    let handle = client.handle();
+
+    for n in 1..=3 {
+        handle.send_chat("lobby", &format!("lobby {n}")).unwrap();
+        handle.send_chat("help", &format!("help {n}")).unwrap();
+    }
+    assert_eq!(handle.clear_chat("help"), 3);
+    handle.flush_chat("lobby").unwrap();
+    assert_eq!(handle.chat_queue_depth("lobby"), 0);
+    handle.send_chat("help", "late").unwrap();
+    client.run(&mut Bot).await.unwrap();
+
+    let lobby: Vec<_> = chat(&sent, "lobby")
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect();
+    assert_eq!(lobby, ["lobby 1", "lobby 2", "lobby 3"]);
+    assert_eq!(chat(&sent, "help"), [("late".to_string(), Duration::ZERO)]);
+}
@@ -0,0 +1,121 @@
+//! Several accounts run side by side, restarted on their own and sharing a board
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kazam_client::{
+    Board, KazamClient, KazamClientPool, KazamHandler, ReconnectPolicy, ScriptedSource,
+    SentMessages,
+};
+use kazam_protocol::{ClientCommand, ServerFrame, parse_server_frame};
+
+const ROOM: &str = "battle-gen9randombattle-1";
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+/// Posts its battle room to the board once the battle starts
+struct Player {
+    board: Board,
+}
+
+impl KazamHandler for Player {
+    async fn on_turn(&mut self, room_id: &str, _turn: u32) {
+        self.board.set("battle", room_id);
+    }
+}
+
+struct Spectator;
+
+impl KazamHandler for Spectator {}
+
+#[tokio::test(start_paused = true)]
+async fn test_accounts_restart_alone_and_share_a_board() {
+    let mut pool = KazamClientPool::new();
+    pool.set_restart_policy(ReconnectPolicy {
+        max_attempts: Some(3),
+        initial_delay: Duration::from_secs(5),
+        max_delay: Duration::from_secs(60),
+        backoff_multiplier: 2.0,
+    });
+
+    pool.add("player", |ctx| async move {
+        if ctx.attempt() == 0 {
+            anyhow::bail!("login failed");
+        }
+        let start = format!(
+            ">{ROOM}\n|init|battle\n|title|KazamBot vs. Rival\n|player|p1|KazamBot|1|\n\
+             |player|p2|Rival|2|\n|gametype|singles\n|gen|9\n|start\n|turn|1"
+        );
+        let source = ScriptedSource::new(frames(&["|challstr|4|aaaa", &start]))
+            .then_quiet(Duration::from_secs(3600));
+        let mut client = KazamClient::with_source(source);
+        ctx.attach(client.handle());
+        let mut player = Player {
+            board: ctx.board().clone(),
+        };
+        client.run(&mut player).await
+    });
+
+    let spectator_sent: Arc<Mutex<Vec<SentMessages>>> = Arc::default();
+    let recorded = spectator_sent.clone();
+    pool.add("spectator", move |ctx| {
+        let recorded = recorded.clone();
+        async move {
+            let room = ctx.board().wait_for("battle").await;
+            let source = ScriptedSource::new(frames(&["|challstr|4|bbbb"]))
+                .then_quiet(Duration::from_secs(3600));
+            recorded.lock().unwrap().push(source.sent());
+            let mut client = KazamClient::with_source(source);
+            ctx.attach(client.handle());
+            client.handle().join_room(&room)?;
+            client.run(&mut Spectator).await
+        }
+    });
+
+    let pool_handle = pool.handle();
+    let running = tokio::spawn(pool.run());
+
+    let start = tokio::time::Instant::now();
+    assert_eq!(pool_handle.board().wait_for("battle").await, ROOM);
+    // The player's first attempt failed and was retried after the initial delay
+    assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    assert_eq!(pool_handle.running(), ["player", "spectator"]);
+    assert!(pool_handle.account("player").is_some());
+    pool_handle.shutdown();
+    let reports = running.await.unwrap();
+
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].label, "player");
+    assert_eq!(reports[0].restarts, 1);
+    assert_eq!(reports[0].failures, ["login failed"]);
+    assert!(!reports[0].gave_up);
+    assert_eq!(reports[1].label, "spectator");
+    assert_eq!(reports[1].restarts, 0);
+    assert!(reports[1].failures.is_empty());
+    assert!(pool_handle.running().is_empty());
+
+    let sent = spectator_sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    let commands: Vec<_> = sent[0].all().into_iter().map(|m| m.command).collect();
+    assert_eq!(commands, [ClientCommand::JoinRoom(ROOM.to_string())]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shutdown_cancels_pending_restarts() {
+    let mut pool = KazamClientPool::new();
+    pool.add("flaky", |_| async { anyhow::bail!("connection refused") });
+    let pool_handle = pool.handle();
+    let running = tokio::spawn(pool.run());
+
+    // Shut down while the account waits out its first backoff
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    pool_handle.shutdown();
+    let reports = running.await.unwrap();
+    assert_eq!(reports[0].restarts, 0);
+    assert_eq!(reports[0].failures, ["connection refused"]);
+    assert!(!reports[0].gave_up);
+}
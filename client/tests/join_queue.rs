@@ -0,0 +1,111 @@
+//! Room joins paced through the join queue and answered by |init| or |noinit|
+
+use std::time::Duration;
+
+use kazam_client::{ClientError, KazamClient, KazamHandler, ScriptedSource, SentMessages};
+use kazam_protocol::{ClientCommand, ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+/// Rooms joined so far, with the time since the first join
+fn joins(sent: &SentMessages) -> Vec<(String, Duration)> {
+    let timed = sent.timed();
+    let Some((start, _)) = timed.first() else {
+        return Vec::new();
+    };
+    let start = *start;
+    timed
+        .into_iter()
+        .filter_map(|(at, message)| match message.command {
+            ClientCommand::JoinRoom(room) => Some((room, at - start)),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Bot;
+
+impl KazamHandler for Bot {}
+
+const LOGIN: &[&str] = &["|challstr|4|aaaa", "|updateuser| KazamBot|1|1"];
+
+#[tokio::test(start_paused = true)]
+async fn test_joins_paced_with_battles_first() {
+    let source = ScriptedSource::new(frames(LOGIN))
+        .then_quiet(Duration::from_secs(10))
+        .then(frames(&[
+            ">battle-gen9ou-1\n|init|battle",
+            ">lobby\n|init|chat",
+            ">help\n|noinit|nonexistent|The room \"help\" does not exist.",
+        ]));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let handle = client.handle();
+
+    for room in ["lobby", "help", "techcode", "lobby"] {
+        handle.join_room(room).unwrap();
+    }
+    let battle = tokio::spawn(handle.join_room_wait("battle-gen9ou-1"));
+    let help = tokio::spawn(handle.join_room_wait("help"));
+    assert_eq!(
+        handle.pending_joins(),
+        ["battle-gen9ou-1", "lobby", "help", "techcode"]
+    );
+    client.run(&mut Bot).await.unwrap();
+
+    // The battle skipped the chat rooms and the repeated lobby join was dropped
+    let second = Duration::from_secs(1);
+    assert_eq!(
+        joins(&sent),
+        [
+            ("battle-gen9ou-1".to_string(), Duration::ZERO),
+            ("lobby".to_string(), second),
+            ("help".to_string(), second * 2),
+            ("techcode".to_string(), second * 3),
+        ]
+    );
+
+    battle.await.unwrap().unwrap();
+    let err = help.await.unwrap().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ClientError>(),
+        Some(&ClientError::JoinFailed {
+            room: "help".to_string(),
+            reason: "nonexistent".to_string(),
+            message: "The room \"help\" does not exist.".to_string(),
+        })
+    );
+    assert_eq!(handle.pending_joins(), ["techcode"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_unanswered_joins_survive_reconnect() {
+    let source = ScriptedSource::new(frames(LOGIN))
+        .then_quiet(Duration::from_millis(1250))
+        .then_reconnect(frames(LOGIN))
+        .then_quiet(Duration::from_secs(5));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_join_interval(Duration::from_millis(500));
+    let handle = client.handle();
+
+    for room in ["lobby", "help", "techcode", "overused"] {
+        handle.join_room(room).unwrap();
+    }
+    client.run(&mut Bot).await.unwrap();
+
+    // Three joins went out before the drop and are sent again afterwards
+    let rooms: Vec<_> = joins(&sent).into_iter().map(|(room, _)| room).collect();
+    assert_eq!(
+        rooms,
+        [
+            "lobby", "help", "techcode", "lobby", "help", "techcode", "overused"
+        ]
+    );
+    assert_eq!(
+        handle.pending_joins(),
+        ["lobby", "help", "techcode", "overused"]
+    );
+}
@@ -0,0 +1,182 @@
+//! Login session reuse against a local mock login server
+
+use std::sync::{Arc, Mutex};
+
+use kazam_client::LoginClient;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// One canned reply: extra headers and a JSON body
+struct Reply {
+    headers: &'static str,
+    body: &'static str,
+}
+
+const LOGIN_OK: Reply = Reply {
+    headers: "Set-Cookie: sid=alice%2Csession; Path=/; HttpOnly\r\n",
+    body: r#"]{"actionsuccess":true,"assertion":"login-assertion","curuser":{"loggedin":true,"username":"Alice","userid":"alice"}}"#,
+};
+
+const LOGIN_NO_COOKIE: Reply = Reply {
+    headers: "",
+    body: r#"]{"actionsuccess":true,"assertion":"login-assertion","curuser":{"loggedin":true,"username":"Alice","userid":"alice"}}"#,
+};
+
+const UPKEEP_OK: Reply = Reply {
+    headers: "",
+    body: r#"]{"loggedin":true,"username":"Alice","userid":"alice","assertion":"upkeep-assertion"}"#,
+};
+
+const UPKEEP_EXPIRED: Reply = Reply {
+    headers: "Set-Cookie: sid=deleted; Max-Age=0; Path=/\r\n",
+    body: r#"]{"loggedin":false,"username":"Guest 12","userid":"guest12"}"#,
+};
+
+/// Read one request, headers and form body
+async fn read_request(stream: &mut TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        let Some(end) = text.find("\r\n\r\n") else {
+            continue;
+        };
+        let length = text
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().parse::<usize>().ok())?
+            })
+            .unwrap_or(0);
+        if n == 0 || request.len() >= end + 4 + length {
+            return text;
+        }
+    }
+}
+
+/// Answer requests with `replies` in order, returning the API URL and the requests seen
+async fn mock_login_server(replies: Vec<Reply>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        for reply in replies {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut stream).await;
+            seen.lock().unwrap().push(request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                reply.headers,
+                reply.body.len(),
+                reply.body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+    (url, requests)
+}
+
+fn form_field<'a>(request: &'a str, key: &str) -> Option<&'a str> {
+    let body = request.split("\r\n\r\n").nth(1)?;
+    body.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == key).then_some(value)
+    })
+}
+
+fn cookie(request: &str) -> Option<&str> {
+    request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("cookie").then(|| value.trim())
+    })
+}
+
+#[tokio::test]
+async fn test_resume_success() {
+    let (url, requests) = mock_login_server(vec![LOGIN_OK, UPKEEP_OK]).await;
+    let login = LoginClient::with_url(&url);
+
+    let assertion = login.resume_or_login("Alice", "hunter2", "4|first").await;
+    assert_eq!(assertion.unwrap(), "login-assertion");
+    assert!(login.has_session());
+
+    // Same challstr: no request at all
+    let assertion = login.resume_or_login("Alice", "hunter2", "4|first").await;
+    assert_eq!(assertion.unwrap(), "login-assertion");
+
+    // Reconnected: the cookie refreshes the session without the password
+    let assertion = login.resume_or_login("Alice", "hunter2", "4|second").await;
+    assert_eq!(assertion.unwrap(), "upkeep-assertion");
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].starts_with("POST /api/login "));
+    assert_eq!(form_field(&requests[0], "act"), Some("login"));
+    assert_eq!(cookie(&requests[0]), None);
+    assert!(requests[1].starts_with("POST /api/upkeep "));
+    assert_eq!(form_field(&requests[1], "act"), Some("upkeep"));
+    assert_eq!(form_field(&requests[1], "challstr"), Some("4%7Csecond"));
+    assert_eq!(form_field(&requests[1], "pass"), None);
+    assert_eq!(cookie(&requests[1]), Some("sid=alice%2Csession"));
+}
+
+#[tokio::test]
+async fn test_resume_expired_falls_back_to_login() {
+    let (url, requests) = mock_login_server(vec![LOGIN_OK, UPKEEP_EXPIRED, LOGIN_OK]).await;
+    let login = LoginClient::with_url(&url);
+
+    login
+        .resume_or_login("Alice", "hunter2", "4|first")
+        .await
+        .unwrap();
+    let assertion = login.resume_or_login("Alice", "hunter2", "4|second").await;
+    assert_eq!(assertion.unwrap(), "login-assertion");
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[1].starts_with("POST /api/upkeep "));
+    assert!(requests[2].starts_with("POST /api/login "));
+    assert_eq!(form_field(&requests[2], "pass"), Some("hunter2"));
+    assert_eq!(form_field(&requests[2], "challstr"), Some("4%7Csecond"));
+}
+
+#[tokio::test]
+async fn test_resume_without_cookie_logs_in() {
+    let (url, requests) = mock_login_server(vec![LOGIN_NO_COOKIE, LOGIN_NO_COOKIE]).await;
+    let login = LoginClient::with_url(&url);
+
+    login
+        .resume_or_login("Alice", "hunter2", "4|first")
+        .await
+        .unwrap();
+    assert!(!login.has_session());
+    login
+        .resume_or_login("Alice", "hunter2", "4|second")
+        .await
+        .unwrap();
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|r| r.starts_with("POST /api/login ")));
+}
+
+#[tokio::test]
+async fn test_forgotten_assertion_uses_upkeep() {
+    let (url, requests) = mock_login_server(vec![LOGIN_OK, UPKEEP_OK]).await;
+    let login = LoginClient::with_url(&url);
+
+    login
+        .resume_or_login("Alice", "hunter2", "4|first")
+        .await
+        .unwrap();
+    // The server refused the assertion, e.g. it expired before it was sent
+    login.forget_assertion();
+    let assertion = login.resume_or_login("Alice", "hunter2", "4|first").await;
+    assert_eq!(assertion.unwrap(), "upkeep-assertion");
+    assert_eq!(requests.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,109 @@
+//! Answering the open team sheets prompt at team preview by policy
+
+use std::time::Duration;
+
+use kazam_client::{KazamClient, KazamHandler, OtsPolicy, ScriptedSource};
+use kazam_protocol::{ClientCommand, ClientMessage};
+
+const ROOM: &str = "battle-gen9vgc2024regg-1";
+
+fn scripted() -> ScriptedSource {
+    let log = include_str!("fixtures/open_team_sheets.log");
+    ScriptedSource::from_raw([
+        "|challstr|4|aaaa".to_string(),
+        "|updateuser| KazamBot|1|1".to_string(),
+        format!(">{ROOM}\n|init|battle\n{}", log.trim_end()),
+    ])
+    .unwrap()
+}
+
+/// What the client sent in answer to prompts
+fn answers(sent: &[ClientMessage]) -> Vec<(Option<String>, bool)> {
+    sent.iter()
+        .filter_map(|message| match message.command {
+            ClientCommand::OpenTeamSheets(accept) => Some((message.room_id.clone(), accept)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Bot {
+    /// What on_ots_prompt answers, and how long it takes to
+    decision: Option<bool>,
+    delay: Duration,
+    prompts: Vec<String>,
+    answers: Vec<(Option<String>, bool)>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_ots_prompt(&mut self, room_id: &str) -> Option<bool> {
+        self.prompts.push(room_id.to_string());
+        tokio::time::sleep(self.delay).await;
+        self.decision
+    }
+
+    async fn on_ots_answer(&mut self, _room_id: &str, username: Option<&str>, accepted: bool) {
+        self.answers.push((username.map(str::to_string), accepted));
+    }
+}
+
+#[tokio::test]
+async fn test_accept_policy_answers_without_asking() {
+    let source = scripted();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_open_team_sheets(OtsPolicy::Accept);
+    let mut bot = Bot::default();
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(answers(&sent.all()), [(Some(ROOM.to_string()), true)]);
+    assert!(bot.prompts.is_empty());
+    assert_eq!(
+        bot.answers,
+        [
+            (None, true),
+            (Some("Rival Trainer".to_string()), true),
+            (Some("Bob".to_string()), false),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_ask_policy_sends_the_handlers_decision() {
+    let source = scripted();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot {
+        decision: Some(true),
+        delay: Duration::from_secs(2),
+        ..Bot::default()
+    };
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(bot.prompts, [ROOM]);
+    assert_eq!(answers(&sent.all()), [(Some(ROOM.to_string()), true)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_ask_policy_rejects_on_timeout_or_no_answer() {
+    let source = scripted();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_open_team_sheets(OtsPolicy::Ask {
+        timeout: Duration::from_secs(1),
+    });
+    let mut bot = Bot {
+        decision: Some(true),
+        delay: Duration::from_secs(5),
+        ..Bot::default()
+    };
+    client.run(&mut bot).await.unwrap();
+    assert_eq!(answers(&sent.all()), [(Some(ROOM.to_string()), false)]);
+
+    let source = scripted();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.run(&mut Bot::default()).await.unwrap();
+    assert_eq!(answers(&sent.all()), [(Some(ROOM.to_string()), false)]);
+}
@@ -0,0 +1,93 @@
+//! A request in the same frame as the lines that resolve the turn
+
+use kazam_battle::TrackedBattle;
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource};
+use kazam_protocol::{BattleRequest, Player, ServerFrame, ServerMessage, parse_server_frame};
+use tokio::task::JoinHandle;
+
+const ROOM: &str = "battle-gen9customgame-1";
+
+const REQUEST: &str = r#"{"forceSwitch":[true],"side":{"name":"KazamBot","id":"p1","pokemon":[{"ident":"p1: Pikachu","details":"Pikachu, L50","condition":"0 fnt","active":true,"stats":{"atk":60,"def":50,"spa":60,"spd":60,"spe":100},"moves":["tackle"],"baseAbility":"static","item":"","pokeball":"pokeball"},{"ident":"p1: Eevee","details":"Eevee, L50","condition":"100/100","active":false,"stats":{"atk":60,"def":50,"spa":60,"spd":60,"spe":60},"moves":["tackle"],"baseAbility":"runaway","item":"","pokeball":"pokeball"}]},"rqid":3}"#;
+
+fn frames() -> Vec<ServerFrame> {
+    let start = format!(
+        ">{ROOM}\n|init|battle\n|player|p1|KazamBot|1|\n|player|p2|Rival|2|\n|teamsize|p1|2\n\
+         |teamsize|p2|1\n|gametype|singles\n|gen|9\n|start\n\
+         |switch|p1a: Pikachu|Pikachu, L50|100/100\n|switch|p2a: Eevee|Eevee, L50|100/100\n|turn|1"
+    );
+    // The request lands ahead of the damage and faint it follows from
+    let resolution = format!(
+        ">{ROOM}\n|move|p2a: Eevee|Tackle|p1a: Pikachu\n|request|{REQUEST}\n\
+         |-damage|p1a: Pikachu|0 fnt\n|faint|p1a: Pikachu\n|upkeep"
+    );
+    [
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        &start,
+        &resolution,
+    ]
+    .iter()
+    .map(|f| parse_server_frame(f).unwrap())
+    .collect()
+}
+
+struct Bot {
+    handle: KazamHandle,
+    tracker: TrackedBattle,
+    /// Pikachu fainted, room settled and turn resolved, as seen by on_request
+    seen: Vec<(bool, bool, bool)>,
+    waiter: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl Bot {
+    fn new(handle: KazamHandle) -> Self {
+        Self {
+            handle,
+            tracker: TrackedBattle::new(),
+            seen: Vec::new(),
+            waiter: None,
+        }
+    }
+}
+
+impl KazamHandler for Bot {
+    async fn on_request(&mut self, room_id: &str, _request: &BattleRequest) {
+        let fainted = self.tracker.get_side(Player::P1).unwrap().pokemon[0].fainted;
+        self.seen.push((
+            fainted,
+            self.handle.is_settled(room_id),
+            self.handle.turn_resolved_since_request(room_id),
+        ));
+        self.waiter = Some(tokio::spawn(self.handle.wait_for_settled(room_id)));
+    }
+
+    async fn on_battle_message(&mut self, _room_id: Option<&str>, message: ServerMessage) {
+        self.tracker.apply_message(&message);
+    }
+}
+
+#[tokio::test]
+async fn test_request_fires_in_place_by_default() {
+    let mut client = KazamClient::with_source(ScriptedSource::new(frames()));
+    let mut bot = Bot::new(client.handle());
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(bot.seen, [(false, false, false)]);
+    // Anyone waiting is let go once the frame is done
+    bot.waiter.unwrap().await.unwrap().unwrap();
+    let handle = client.handle();
+    assert!(handle.is_settled(ROOM));
+    assert!(handle.turn_resolved_since_request(ROOM));
+    assert!(bot.tracker.get_side(Player::P1).unwrap().pokemon[0].fainted);
+}
+
+#[tokio::test]
+async fn test_deferred_request_sees_the_whole_frame() {
+    let mut client = KazamClient::with_source(ScriptedSource::new(frames()));
+    client.set_defer_request_until_settled(true);
+    let mut bot = Bot::new(client.handle());
+    client.run(&mut bot).await.unwrap();
+
+    assert_eq!(bot.seen, [(true, true, true)]);
+    bot.waiter.unwrap().await.unwrap().unwrap();
+}
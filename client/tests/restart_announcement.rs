@@ -0,0 +1,124 @@
+//! Matchmaking pauses across an announced server restart
+
+use std::time::Duration;
+
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource, SentMessages, User};
+use kazam_protocol::{ClientCommand, ServerFrame, parse_server_frame};
+
+const FORMAT: &str = "gen9randombattle";
+const LOCKDOWN: &str = r#"<div class="broadcast-red"><b>The server is restarting soon.</b><br />Please finish your battles quickly. No new battles can be started until the server resets in a few minutes.</div>"#;
+
+fn frames(raw: &[String]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+fn searches(sent: &SentMessages) -> usize {
+    sent.all()
+        .iter()
+        .filter(|m| matches!(&m.command, ClientCommand::Search(f) if f == FORMAT))
+        .count()
+}
+
+/// Searches as soon as a restart is announced, and records what it sees
+struct Bot {
+    handle: KazamHandle,
+    sent: SentMessages,
+    events: Vec<String>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_challstr(&mut self, _challstr: &str) {
+        self.events.push("challstr".into());
+    }
+
+    async fn on_logged_in(&mut self, _user: &User) {
+        self.events.push("logged_in".into());
+    }
+
+    async fn on_server_restart_announced(&mut self, eta: Option<Duration>) {
+        self.events.push(format!("announced {:?}", eta));
+        self.handle.search(FORMAT).unwrap();
+    }
+
+    async fn on_server_restarted(&mut self) {
+        self.events.push(format!(
+            "restarted paused={} searches={}",
+            self.handle.is_matchmaking_paused(),
+            searches(&self.sent)
+        ));
+    }
+}
+
+async fn run_bot(source: ScriptedSource, pause: bool) -> (Bot, SentMessages, KazamHandle) {
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.set_pause_matchmaking_on_restart_announcement(pause);
+    let handle = client.handle();
+    let mut bot = Bot {
+        handle: handle.clone(),
+        sent: sent.clone(),
+        events: Vec::new(),
+    };
+    client.run(&mut bot).await.unwrap();
+    (bot, sent, handle)
+}
+
+fn before_restart() -> Vec<ServerFrame> {
+    frames(&[
+        "|challstr|4|aaaa".to_string(),
+        "|updateuser| KazamBot|1|1".to_string(),
+        format!("|pm|&| KazamBot|/raw {}", LOCKDOWN),
+        // The same lockdown is broadcast to every room we're in
+        format!(">lobby\n|raw|{}", LOCKDOWN),
+    ])
+}
+
+fn after_restart() -> Vec<ServerFrame> {
+    frames(&[
+        "|challstr|4|bbbb".to_string(),
+        "|updateuser| KazamBot|1|1".to_string(),
+    ])
+}
+
+#[tokio::test]
+async fn test_search_deferred_until_logged_in_after_restart() {
+    let source = ScriptedSource::new(before_restart()).then_reconnect(after_restart());
+    let (bot, sent, handle) = run_bot(source, true).await;
+
+    assert_eq!(
+        bot.events,
+        [
+            "challstr",
+            "logged_in",
+            "announced None",
+            "restarted paused=true searches=0",
+            "challstr",
+            "logged_in",
+        ]
+    );
+    assert_eq!(searches(&sent), 1);
+    assert!(!handle.is_matchmaking_paused());
+}
+
+#[tokio::test]
+async fn test_search_sent_without_policy() {
+    let source = ScriptedSource::new(before_restart()).then_reconnect(after_restart());
+    let (bot, sent, _) = run_bot(source, false).await;
+
+    assert_eq!(bot.events[3], "restarted paused=false searches=1");
+    assert_eq!(searches(&sent), 1);
+}
+
+#[tokio::test]
+async fn test_cancelled_restart_resumes_matchmaking() {
+    let mut script = before_restart();
+    script.extend(frames(&[format!(
+        ">lobby\n|raw|{}",
+        r#"<div class="broadcast-green"><b>The server restart was canceled.</b></div>"#
+    )]));
+    let (bot, sent, handle) = run_bot(ScriptedSource::new(script), true).await;
+
+    assert!(!bot.events.iter().any(|e| e.starts_with("restarted")));
+    assert_eq!(searches(&sent), 1);
+    assert!(!handle.is_matchmaking_paused());
+}
@@ -0,0 +1,66 @@
+//! Matching battle players against the user lists of our chat rooms
+
+use kazam_client::{KazamClient, KazamHandler, Player, ScriptedSource};
+use kazam_protocol::{ServerFrame, parse_server_frame};
+
+const BATTLE: &str = "battle-gen9randombattle-1";
+
+struct Bot;
+
+impl KazamHandler for Bot {}
+
+async fn run(raw: &[String]) -> KazamClient<ScriptedSource> {
+    let frames: Vec<ServerFrame> = raw.iter().map(|f| parse_server_frame(f).unwrap()).collect();
+    let mut client = KazamClient::with_source(ScriptedSource::new(frames));
+    client.run(&mut Bot).await.unwrap();
+    client
+}
+
+fn rooms() -> Vec<String> {
+    vec![
+        ">lobby\n|init|chat\n|title|Lobby\n|users|3, KazamBot,@Rival, Bob".to_string(),
+        ">club\n|init|chat\n|title|Club\n|users|2,+KazamBot, Bob".to_string(),
+        format!(
+            ">{BATTLE}\n|init|battle\n|users|2, KazamBot, Rival\n|player|p1|KazamBot|1|\n\
+             |player|p2|Rival|2|\n|start"
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_opponent_rooms_joined_by_user_id() {
+    let mut raw = vec!["|challstr|4|aaaa".to_string()];
+    raw.extend(rooms());
+    raw.push(">club\n|j|@R.I.V.A.L".to_string());
+    let handle = run(&raw).await.handle();
+
+    let battle = handle.get_battle(BATTLE).unwrap();
+    let opponent = &battle.get_player(Player::P2).unwrap().username;
+    // The battle room itself isn't counted
+    assert_eq!(handle.shared_rooms_with(opponent), ["club", "lobby"]);
+    assert!(handle.is_room_member("club", "rival"));
+    assert!(handle.is_room_member(BATTLE, opponent));
+    assert_eq!(handle.shared_rooms_with("Bob"), ["club", "lobby"]);
+    assert!(handle.shared_rooms_with("Nobody").is_empty());
+}
+
+#[tokio::test]
+async fn test_rename_and_rejoin_keep_index_current() {
+    let mut raw = vec!["|challstr|4|aaaa".to_string()];
+    raw.extend(rooms());
+    raw.extend([
+        // |n| reaches every room the user is in
+        ">lobby\n|n|@Rival2|rival".to_string(),
+        format!(">{BATTLE}\n|n| Rival2|rival"),
+        ">lobby\n|l| Bob".to_string(),
+        // Joining the club again replaces its user list
+        ">club\n|init|chat\n|users|1,+KazamBot".to_string(),
+    ]);
+    let handle = run(&raw).await.handle();
+
+    assert_eq!(handle.shared_rooms_with("Rival2"), ["lobby"]);
+    assert!(handle.shared_rooms_with("Rival").is_empty());
+    assert!(handle.is_room_member(BATTLE, "rival2"));
+    assert!(handle.shared_rooms_with("Bob").is_empty());
+    assert_eq!(handle.shared_rooms_with("KazamBot"), ["club", "lobby"]);
+}
@@ -0,0 +1,105 @@
+//! Replays a recorded ladder game through the heuristic_battle example's
+//! handler and compares its choices with the random_battle example's
+
+#[path = "../examples/heuristic_battle.rs"]
+#[allow(dead_code)]
+mod heuristic_battle;
+#[path = "../examples/random_battle.rs"]
+#[allow(dead_code)]
+mod random_battle;
+
+use std::collections::HashMap;
+
+use heuristic_battle::HeuristicBot;
+use kazam_battle::query::{EvalWeights, evaluate_actions};
+use kazam_battle::tracking::TrackedBattle;
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource};
+use kazam_protocol::{ServerMessage, parse_server_frame};
+use random_battle::RandomBattleBot;
+
+const RECORDING: &str = include_str!("fixtures/gen9randombattle.log");
+const ROOM: &str = "battle-gen9randombattle-2215417389";
+
+/// Runs `handler` over the recording and returns the choices it sent
+async fn play<H: KazamHandler>(make: impl FnOnce(KazamHandle) -> H) -> Vec<String> {
+    let source = ScriptedSource::from_recording(RECORDING).unwrap();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let mut handler = make(client.handle());
+    client.run(&mut handler).await.unwrap();
+    sent.choices()
+        .into_iter()
+        .map(|(room, choice, _)| {
+            assert_eq!(room, ROOM);
+            choice
+        })
+        .collect()
+}
+
+async fn heuristic_choices() -> Vec<String> {
+    play(|handle| HeuristicBot {
+        handle,
+        weights: EvalWeights::default(),
+        battles: HashMap::new(),
+    })
+    .await
+}
+
+/// Scores of every action at each move or switch decision in the recording,
+/// keyed by choice string
+fn recorded_scores() -> Vec<HashMap<String, f32>> {
+    let mut battle = TrackedBattle::new();
+    let mut scores = Vec::new();
+    for frame in RECORDING.split("\n\n") {
+        let frame = parse_server_frame(frame).unwrap();
+        if frame.room_id.as_deref() != Some(ROOM) {
+            continue;
+        }
+        for message in frame.messages {
+            let ServerMessage::Request(json) = &message else {
+                battle.update(&message);
+                continue;
+            };
+            let Some(request) = kazam_protocol::BattleRequest::parse(json) else {
+                continue;
+            };
+            battle.update_from_request(&request);
+            if request.wait || request.team_preview {
+                continue;
+            }
+            let actions = evaluate_actions(&battle, &request, &EvalWeights::default());
+            scores.push(
+                actions
+                    .into_iter()
+                    .map(|scored| (scored.action.choice(), scored.score))
+                    .collect(),
+            );
+        }
+    }
+    scores
+}
+
+#[tokio::test]
+async fn test_heuristic_bot_is_deterministic() {
+    let first = heuristic_choices().await;
+    assert!(!first.is_empty());
+    assert_eq!(first, heuristic_choices().await);
+}
+
+#[tokio::test]
+async fn test_heuristic_bot_scores_at_least_random() {
+    let heuristic = heuristic_choices().await;
+    let random = play(|handle| RandomBattleBot { handle }).await;
+    let scores = recorded_scores();
+    assert_eq!(heuristic.len(), scores.len());
+    assert_eq!(random.len(), scores.len());
+
+    for ((heuristic, random), scores) in heuristic.iter().zip(&random).zip(&scores) {
+        let best = scores.values().copied().fold(f32::MIN, f32::max);
+        assert_eq!(scores[heuristic], best, "{heuristic} in {scores:?}");
+        assert!(
+            scores[heuristic] >= scores[random],
+            "{random} in {scores:?}"
+        );
+    }
+}
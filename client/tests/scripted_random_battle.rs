@@ -0,0 +1,72 @@
+//! Replays a recorded ladder game through the random_battle example's handler
+
+#[path = "../examples/random_battle.rs"]
+#[allow(dead_code)]
+mod random_battle;
+
+use kazam_client::{KazamClient, ScriptedSource};
+use kazam_protocol::{BattleRequest, ClientCommand, ServerMessage, parse_server_frame};
+use random_battle::RandomBattleBot;
+
+const RECORDING: &str = include_str!("fixtures/gen9randombattle.log");
+const ROOM: &str = "battle-gen9randombattle-2215417389";
+
+/// Every request in the recording, in order
+fn recorded_requests() -> Vec<BattleRequest> {
+    RECORDING
+        .split("\n\n")
+        .flat_map(|frame| parse_server_frame(frame).unwrap().messages)
+        .filter_map(|message| match message {
+            ServerMessage::Request(json) => BattleRequest::parse(&json),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `choice` is something the server would accept for `request`
+fn is_valid(choice: &str, request: &BattleRequest) -> bool {
+    let side = request.side.as_ref().unwrap();
+    let (kind, index) = choice.split_once(' ').unwrap();
+    let index: usize = index.parse().unwrap();
+    match kind {
+        "move" if !request.is_force_switch() => request.active.as_ref().unwrap()[0]
+            .available_moves()
+            .iter()
+            .any(|(i, _)| i + 1 == index),
+        "switch" => side
+            .pokemon
+            .get(index - 1)
+            .is_some_and(|p| !p.active && !p.is_fainted()),
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn test_random_bot_answers_every_request() {
+    let source = ScriptedSource::from_recording(RECORDING).unwrap();
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let mut bot = RandomBattleBot {
+        handle: client.handle(),
+    };
+    client.run(&mut bot).await.unwrap();
+
+    let requests = recorded_requests();
+    assert_eq!(requests.len(), 8);
+    let choices = sent.choices();
+    let decisions: Vec<_> = requests.iter().filter(|r| !r.wait).collect();
+    assert_eq!(choices.len(), decisions.len());
+    for ((room, choice, rqid), request) in choices.iter().zip(decisions) {
+        assert_eq!(room, ROOM);
+        assert_eq!(*rqid, request.rqid);
+        assert!(is_valid(choice, request), "{choice} for rqid {rqid:?}");
+    }
+
+    // Searched after logging in and again after the win
+    let searches = sent
+        .all()
+        .into_iter()
+        .filter(|m| m.command == ClientCommand::Search("gen9randombattle".to_string()))
+        .count();
+    assert_eq!(searches, 2);
+}
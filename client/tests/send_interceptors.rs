@@ -0,0 +1,155 @@
+//! Outgoing messages passed through send interceptors and dry runs
+
+use std::time::Duration;
+
+use kazam_client::{ClientError, KazamClient, KazamHandler, ScriptedSource, SendDecision};
+use kazam_protocol::{ClientCommand, ClientMessage, ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+struct Bot;
+
+impl KazamHandler for Bot {}
+
+const LOGIN: &[&str] = &["|challstr|4|aaaa", "|updateuser| KazamBot|1|1"];
+
+/// Never click Explosion: switch to the first bench slot instead
+fn no_explosion(message: &ClientMessage) -> SendDecision {
+    match &message.command {
+        ClientCommand::Choose { choice, rqid } if choice == "move explosion" => {
+            SendDecision::Replace(ClientMessage {
+                room_id: message.room_id.clone(),
+                command: ClientCommand::Choose {
+                    choice: "switch 2".to_string(),
+                    rqid: *rqid,
+                },
+            })
+        }
+        _ => SendDecision::Allow,
+    }
+}
+
+/// Never say anything in the lobby
+fn quiet_lobby(message: &ClientMessage) -> SendDecision {
+    match (&message.command, message.room_id.as_deref()) {
+        (ClientCommand::Chat(_), Some("lobby")) => SendDecision::Drop {
+            reason: "lobby is off limits".to_string(),
+        },
+        _ => SendDecision::Allow,
+    }
+}
+
+#[tokio::test]
+async fn test_interceptors_run_in_order() {
+    let source = ScriptedSource::new(frames(LOGIN));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.add_send_interceptor(no_explosion);
+    client.add_send_interceptor(quiet_lobby);
+    // Sees what the earlier interceptors replaced
+    client.add_send_interceptor(|message: &ClientMessage| match &message.command {
+        ClientCommand::Choose { choice, rqid } => SendDecision::Replace(ClientMessage {
+            room_id: message.room_id.clone(),
+            command: ClientCommand::Choose {
+                choice: format!("{choice}|logged"),
+                rqid: *rqid,
+            },
+        }),
+        _ => SendDecision::Allow,
+    });
+    let handle = client.handle();
+
+    let room = "battle-gen9ou-1";
+    handle.choose(room, "move explosion", Some(3)).unwrap();
+    handle.send_chat("lobby", "hello").unwrap();
+    handle.send_chat(room, "glhf").unwrap();
+    client.run(&mut Bot).await.unwrap();
+
+    let commands: Vec<_> = sent.all().into_iter().map(|m| m.command).collect();
+    assert_eq!(
+        commands,
+        [
+            ClientCommand::Choose {
+                choice: "switch 2|logged".to_string(),
+                rqid: Some(3),
+            },
+            ClientCommand::Chat("glhf".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_records_instead_of_sending() {
+    let source = ScriptedSource::new(frames(LOGIN));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.add_send_interceptor(no_explosion);
+    client.dry_run();
+    let handle = client.handle();
+
+    handle.search("gen9randombattle").unwrap();
+    handle
+        .choose("battle-gen9randombattle-1", "move explosion", Some(1))
+        .unwrap();
+    client.run(&mut Bot).await.unwrap();
+
+    assert!(sent.all().is_empty());
+    let would_send: Vec<_> = handle
+        .dry_run_messages()
+        .into_iter()
+        .map(|m| m.command)
+        .collect();
+    assert_eq!(
+        would_send,
+        [
+            ClientCommand::Search("gen9randombattle".to_string()),
+            ClientCommand::Choose {
+                choice: "switch 2".to_string(),
+                rqid: Some(1),
+            },
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_dropped_join_keeps_throttle_budget() {
+    let source = ScriptedSource::new(frames(LOGIN))
+        .then_quiet(Duration::from_secs(5))
+        .then(frames(&[">lobby\n|init|chat"]));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    client.add_send_interceptor(|message: &ClientMessage| match &message.command {
+        ClientCommand::JoinRoom(room) if room == "spam" => SendDecision::Drop {
+            reason: "blocked room".to_string(),
+        },
+        _ => SendDecision::Allow,
+    });
+    let handle = client.handle();
+
+    let spam = tokio::spawn(handle.join_room_wait("spam"));
+    handle.join_room("lobby").unwrap();
+    let start = tokio::time::Instant::now();
+    client.run(&mut Bot).await.unwrap();
+
+    // The lobby join goes out at once rather than a join interval after the dropped one
+    let timed = sent.timed();
+    assert_eq!(timed.len(), 1);
+    assert_eq!(timed[0].0 - start, Duration::ZERO);
+    assert_eq!(
+        timed[0].1.command,
+        ClientCommand::JoinRoom("lobby".to_string())
+    );
+
+    let err = spam.await.unwrap().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ClientError>(),
+        Some(&ClientError::JoinFailed {
+            room: "spam".to_string(),
+            reason: "dropped".to_string(),
+            message: "blocked room".to_string(),
+        })
+    );
+    assert!(handle.pending_joins().is_empty());
+}
@@ -0,0 +1,85 @@
+//! Sim server lookup and connection errors against a local HTTP server
+
+use kazam_client::{ConnectError, KazamClient, ServerAddress};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serve one HTTP response to the first connection, returning the address
+async fn serve_once(status: &str, body: &str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request).await;
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+    });
+    addr
+}
+
+#[tokio::test]
+async fn test_from_client_url() {
+    let config = r#"Config.defaultserver = {
+	id: 'local',
+	host: 'localhost',
+	port: 8000,
+	registered: false
+};"#;
+    let addr = serve_once("200 OK", config).await;
+    let address = ServerAddress::from_client_url(&format!("http://{}/", addr))
+        .await
+        .unwrap();
+    assert_eq!(address.as_str(), "ws://localhost:8000/showdown/websocket");
+}
+
+#[tokio::test]
+async fn test_from_client_url_without_config() {
+    let addr = serve_once("404 Not Found", "").await;
+    let result = ServerAddress::from_client_url(&format!("http://{}/", addr)).await;
+    assert!(matches!(result, Err(ConnectError::Lookup { .. })));
+
+    let addr = serve_once("200 OK", "Config.version = '1';").await;
+    let result = ServerAddress::from_client_url(&format!("http://{}/", addr)).await;
+    assert!(matches!(result, Err(ConnectError::Lookup { .. })));
+}
+
+async fn connect_error(address: ServerAddress) -> ConnectError {
+    let Err(error) = KazamClient::connect(address).await else {
+        panic!("connected");
+    };
+    error.downcast::<ConnectError>().unwrap()
+}
+
+#[tokio::test]
+async fn test_connect_errors() {
+    let addr = serve_once("404 Not Found", "").await;
+    assert!(matches!(
+        connect_error(ServerAddress::from_host(&addr, false)).await,
+        ConnectError::NotFound { .. }
+    ));
+
+    let addr = serve_once("200 OK", "<html></html>").await;
+    let error = connect_error(ServerAddress::from_host(&addr, false)).await;
+    assert!(
+        matches!(&error, ConnectError::NotWebsocket { reason, .. } if reason.contains("200")),
+        "{}",
+        error
+    );
+
+    let error = connect_error(ServerAddress::from_host("kazam.invalid", true)).await;
+    assert!(matches!(error, ConnectError::Dns { .. }), "{}", error);
+
+    let error = connect_error("not a url".into()).await;
+    assert!(
+        matches!(error, ConnectError::InvalidUrl { .. }),
+        "{}",
+        error
+    );
+}
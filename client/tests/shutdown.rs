@@ -0,0 +1,40 @@
+//! Stopping a client from its handler
+
+use std::time::Duration;
+
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource, User};
+use kazam_protocol::{ClientCommand, ServerFrame, parse_server_frame};
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+/// Forfeits its battle and stops once logged in
+struct Bot {
+    handle: KazamHandle,
+}
+
+impl KazamHandler for Bot {
+    async fn on_logged_in(&mut self, _user: &User) {
+        self.handle.forfeit("battle-gen9randombattle-1").unwrap();
+        self.handle.shutdown();
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shutdown_sends_queued_commands() {
+    let source = ScriptedSource::new(frames(&["|challstr|4|aaaa", "|updateuser| KazamBot|1|1"]))
+        .then_quiet(Duration::from_secs(3600));
+    let sent = source.sent();
+    let mut client = KazamClient::with_source(source);
+    let mut bot = Bot {
+        handle: client.handle(),
+    };
+
+    let start = tokio::time::Instant::now();
+    client.run(&mut bot).await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    let commands: Vec<_> = sent.all().into_iter().map(|m| m.command).collect();
+    assert_eq!(commands, [ClientCommand::Forfeit]);
+}
@@ -0,0 +1,70 @@
+//! Handle reads served from the snapshot published after each message
+
+use std::sync::Arc;
+
+use kazam_client::{KazamClient, KazamHandle, KazamHandler, ScriptedSource, StateSnapshot};
+use kazam_protocol::{ServerFrame, parse_server_frame};
+
+const ROOM: &str = "battle-gen9randombattle-1";
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+struct Bot {
+    handle: KazamHandle,
+    titles: Vec<Option<String>>,
+    turns: Vec<u32>,
+    at_turn_one: Option<Arc<StateSnapshot>>,
+}
+
+impl KazamHandler for Bot {
+    async fn on_title(&mut self, room_id: &str, _title: &str) {
+        self.titles
+            .push(self.handle.get_room(room_id).and_then(|r| r.title));
+    }
+
+    async fn on_turn(&mut self, room_id: &str, _turn: u32) {
+        self.turns
+            .push(self.handle.get_battle(room_id).map_or(0, |b| b.turn));
+        if self.at_turn_one.is_none() {
+            self.at_turn_one = Some(self.handle.snapshot());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_follows_dispatched_messages() {
+    let start = format!(
+        ">{ROOM}\n|init|battle\n|title|KazamBot vs. Rival\n|users|2, KazamBot, Rival\n\
+         |player|p1|KazamBot|1|\n|player|p2|Rival|2|\n|gametype|singles\n|gen|9\n|start\n|turn|1"
+    );
+    let next = format!(">{ROOM}\n|j| Eve\n|turn|2");
+    let source = ScriptedSource::new(frames(&["|challstr|4|aaaa", &start, &next]));
+    let mut client = KazamClient::with_source(source);
+    let handle = client.handle();
+    assert!(handle.snapshot().rooms().next().is_none());
+
+    let mut bot = Bot {
+        handle: handle.clone(),
+        titles: Vec::new(),
+        turns: Vec::new(),
+        at_turn_one: None,
+    };
+    client.run(&mut bot).await.unwrap();
+
+    // Callbacks see their own message already applied
+    assert_eq!(bot.titles, vec![Some("KazamBot vs. Rival".to_string())]);
+    assert_eq!(bot.turns, vec![1, 2]);
+
+    let snapshot = handle.snapshot();
+    assert_eq!(snapshot.battle(ROOM).unwrap().turn, 2);
+    assert_eq!(handle.spectator_count(ROOM), Some(1));
+    assert_eq!(handle.rooms(), vec![ROOM.to_string()]);
+
+    // A snapshot that was loaded earlier keeps showing what it saw then
+    let earlier = bot.at_turn_one.unwrap();
+    assert_eq!(earlier.battle(ROOM).unwrap().turn, 1);
+    assert_eq!(earlier.room(ROOM).unwrap().user_count(), 2);
+    assert_eq!(snapshot.room(ROOM).unwrap().user_count(), 3);
+}
@@ -0,0 +1,108 @@
+//! Running the client as a stream of events
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use kazam_client::{Backpressure, ClientEvent, KazamClient, ScriptedSource};
+use kazam_protocol::{ServerFrame, ServerMessage, parse_server_frame};
+
+const ROOM: &str = "battle-gen9randombattle-1";
+
+fn frames(raw: &[&str]) -> Vec<ServerFrame> {
+    raw.iter().map(|f| parse_server_frame(f).unwrap()).collect()
+}
+
+fn battle() -> String {
+    format!(
+        ">{ROOM}\n|init|battle\n|player|p1|KazamBot|1|\n|player|p2|Rival|2|\n\
+         |gametype|singles\n|gen|9\n|tier|[Gen 9] Random Battle\n|start\n|turn|1"
+    )
+}
+
+#[tokio::test]
+async fn test_stream_keeps_state() {
+    let battle = battle();
+    let source = ScriptedSource::new(frames(&[
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        &battle,
+    ]));
+    let (handle, stream) = KazamClient::with_source(source).into_stream();
+
+    let events: Vec<_> = stream.collect().await;
+    assert_eq!(events.last(), Some(&ClientEvent::Closed { error: None }));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        ClientEvent::Message { room_id: Some(r), message: ServerMessage::Turn(1) } if r == ROOM
+    )));
+
+    assert!(handle.is_logged_in());
+    assert!(handle.in_room(ROOM));
+    let snapshot = handle.snapshot();
+    let info = snapshot.battle(ROOM).unwrap();
+    let names: Vec<_> = info.players.iter().map(|p| p.username.as_str()).collect();
+    assert_eq!(names, ["KazamBot", "Rival"]);
+    assert_eq!(info.generation, 9);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_wait_stops_reading_until_consumed() {
+    let battle = battle();
+    let source = ScriptedSource::new(frames(&[
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        &battle,
+    ]))
+    .then_quiet(Duration::from_secs(3600));
+    let (handle, mut stream) =
+        KazamClient::with_source(source).into_stream_with(1, Backpressure::Wait);
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    // The challstr fills the buffer and the login waits on it, so the
+    // battle frame hasn't been read
+    assert!(handle.is_logged_in());
+    assert!(!handle.in_room(ROOM));
+
+    assert!(matches!(
+        stream.next().await,
+        Some(ClientEvent::Message {
+            message: ServerMessage::Challstr(_),
+            ..
+        })
+    ));
+    while !handle.in_room(ROOM) {
+        stream.next().await.unwrap();
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_drop_oldest_keeps_reading() {
+    let battle = battle();
+    let source = ScriptedSource::new(frames(&[
+        "|challstr|4|aaaa",
+        "|updateuser| KazamBot|1|1",
+        &battle,
+    ]))
+    .then_quiet(Duration::from_secs(3600));
+    let (handle, mut stream) =
+        KazamClient::with_source(source).into_stream_with(2, Backpressure::DropOldest);
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(handle.in_room(ROOM));
+
+    let Some(ClientEvent::Lagged(missed)) = stream.next().await else {
+        panic!("expected a lag report first");
+    };
+    assert!(missed > 0);
+    let mut rest = Vec::new();
+    for _ in 0..2 {
+        rest.push(stream.next().await.unwrap());
+    }
+    assert_eq!(
+        rest.last(),
+        Some(&ClientEvent::Message {
+            room_id: Some(ROOM.to_string()),
+            message: ServerMessage::Turn(1),
+        })
+    );
+}
@@ -0,0 +1,100 @@
+//! Webhook posts against a local mock server, with retries
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kazam_client::{ReconnectPolicy, WebhookPoster};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Answer one request per reply, each a status line and a JSON body,
+/// returning the URL and the request bodies seen
+async fn mock_webhook(
+    replies: Vec<(&'static str, &'static str)>,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!(
+        "http://{}/api/webhooks/1/token",
+        listener.local_addr().unwrap()
+    );
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let seen = bodies.clone();
+    tokio::spawn(async move {
+        for (status, body) in replies {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // The JSON body ends the request; none of ours contain a newline
+            while !request.ends_with(b"}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let text = String::from_utf8_lossy(&request);
+            let posted = text.split("\r\n\r\n").nth(1).unwrap_or_default();
+            seen.lock().unwrap().push(posted.to_string());
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+    (url, bodies)
+}
+
+fn quick_retries(max_attempts: usize) -> ReconnectPolicy {
+    ReconnectPolicy {
+        max_attempts: Some(max_attempts),
+        initial_delay: Duration::from_millis(5),
+        max_delay: Duration::from_millis(20),
+        backoff_multiplier: 2.0,
+    }
+}
+
+#[tokio::test]
+async fn test_retries_server_errors_and_rate_limits() {
+    let (url, bodies) = mock_webhook(vec![
+        ("500 Internal Server Error", "{}"),
+        (
+            "429 Too Many Requests",
+            r#"{"retry_after": 0.01, "global": false}"#,
+        ),
+        ("204 No Content", ""),
+    ])
+    .await;
+    let mut webhook = WebhookPoster::new(url);
+    webhook.set_retry_policy(quick_retries(3));
+
+    webhook.post("Won against Rival").await.unwrap();
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 3);
+    assert!(
+        bodies
+            .iter()
+            .all(|b| b == r#"{"content":"Won against Rival"}"#)
+    );
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts_and_on_refusal() {
+    let (url, bodies) = mock_webhook(vec![
+        ("502 Bad Gateway", "{}"),
+        ("503 Service Unavailable", "{}"),
+    ])
+    .await;
+    let mut webhook = WebhookPoster::new(url);
+    webhook.set_retry_policy(quick_retries(1));
+    let error = webhook.post("hello").await.unwrap_err();
+    assert!(error.to_string().contains("503"), "{error}");
+    assert_eq!(bodies.lock().unwrap().len(), 2);
+
+    // A deleted webhook won't come back, so there's no retry
+    let (url, bodies) =
+        mock_webhook(vec![("404 Not Found", r#"{"message": "Unknown Webhook"}"#)]).await;
+    let mut webhook = WebhookPoster::new(url);
+    webhook.set_retry_policy(quick_retries(3));
+    let error = webhook.post("hello").await.unwrap_err();
+    assert!(error.to_string().contains("Unknown Webhook"), "{error}");
+    assert_eq!(bodies.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,59 @@
+//! Compile check for `kazam-battle-core` as a `no_std` dependency
+//!
+//! This crate is `no_std` itself and uses the core crate with default features
+//! off, so a `std`-only item sneaking into the core breaks the workspace build.
+//! The tests exercise the effectiveness math through that dependency.
+
+#![no_std]
+
+use kazam_battle_core::{Stat, StatStages, Type, is_immune_to};
+
+/// Effectiveness of `attacking` against a dual-typed defender
+pub fn dual_effectiveness(attacking: Type, defender: [Type; 2]) -> f32 {
+    attacking.effectiveness_multi(&defender)
+}
+
+/// Whether a Ground attack can hit the defender at all
+pub fn ground_connects(defender: &[Type]) -> bool {
+    !is_immune_to(defender, Type::Ground)
+}
+
+/// Speed stage after a boost, clamped like the tracker does
+pub fn boosted_speed(boost: i8) -> i8 {
+    let mut stages = StatStages::new();
+    stages.boost(Stat::Spe, boost);
+    stages.get(Stat::Spe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dual_effectiveness() {
+        assert_eq!(
+            dual_effectiveness(Type::Grass, [Type::Water, Type::Ground]),
+            4.0
+        );
+        assert_eq!(
+            dual_effectiveness(Type::Fire, [Type::Water, Type::Dragon]),
+            0.25
+        );
+        assert_eq!(
+            dual_effectiveness(Type::Ground, [Type::Steel, Type::Flying]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_ground_connects() {
+        assert!(ground_connects(&[Type::Steel]));
+        assert!(!ground_connects(&[Type::Flying]));
+    }
+
+    #[test]
+    fn test_boosted_speed() {
+        assert_eq!(boosted_speed(2), 2);
+        assert_eq!(boosted_speed(12), 6);
+    }
+}
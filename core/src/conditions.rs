@@ -2,6 +2,7 @@
 
 /// Weather conditions
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Weather {
     Sun,
     Rain,
@@ -56,14 +57,15 @@ impl Weather {
     }
 }
 
-impl std::fmt::Display for Weather {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Weather {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
 /// Terrain conditions
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Terrain {
     Electric,
     Grassy,
@@ -103,14 +105,15 @@ impl Terrain {
     }
 }
 
-impl std::fmt::Display for Terrain {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Terrain {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
 /// Side conditions (hazards, screens, etc.)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SideCondition {
     // Screens
     Reflect,
@@ -131,6 +134,11 @@ pub enum SideCondition {
     WideGuard,
     QuickGuard,
     MatBlock,
+
+    // Pledge combinations
+    SeaOfFire, // Fire Pledge + Grass Pledge
+    Rainbow,   // Water Pledge + Fire Pledge
+    Swamp,     // Grass Pledge + Water Pledge
 }
 
 impl SideCondition {
@@ -159,6 +167,9 @@ impl SideCondition {
             "wideguard" => Some(SideCondition::WideGuard),
             "quickguard" => Some(SideCondition::QuickGuard),
             "matblock" => Some(SideCondition::MatBlock),
+            "firepledge" | "seaoffire" => Some(SideCondition::SeaOfFire),
+            "waterpledge" | "rainbow" => Some(SideCondition::Rainbow),
+            "grasspledge" | "swamp" => Some(SideCondition::Swamp),
             _ => None,
         }
     }
@@ -177,6 +188,22 @@ impl SideCondition {
         }
     }
 
+    /// Get the fixed duration in turns, if this condition always lasts the same length
+    pub fn duration(&self) -> Option<u8> {
+        match self {
+            SideCondition::SeaOfFire | SideCondition::Rainbow | SideCondition::Swamp => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a pledge combination effect
+    pub fn is_pledge(&self) -> bool {
+        matches!(
+            self,
+            SideCondition::SeaOfFire | SideCondition::Rainbow | SideCondition::Swamp
+        )
+    }
+
     /// Check if this is a screen
     pub fn is_screen(&self) -> bool {
         matches!(
@@ -196,6 +223,17 @@ impl SideCondition {
         )
     }
 
+    /// Check if Court Change moves this condition to the other side
+    ///
+    /// Everything but the single-turn protections (Wide Guard, Quick Guard,
+    /// Mat Block) is swapped, layers and remaining turns included.
+    pub fn is_court_change_swappable(&self) -> bool {
+        !matches!(
+            self,
+            SideCondition::WideGuard | SideCondition::QuickGuard | SideCondition::MatBlock
+        )
+    }
+
     /// Get display name
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -213,26 +251,51 @@ impl SideCondition {
             SideCondition::WideGuard => "Wide Guard",
             SideCondition::QuickGuard => "Quick Guard",
             SideCondition::MatBlock => "Mat Block",
+            SideCondition::SeaOfFire => "Sea of Fire",
+            SideCondition::Rainbow => "Rainbow",
+            SideCondition::Swamp => "Swamp",
         }
     }
 }
 
-impl std::fmt::Display for SideCondition {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for SideCondition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
 /// State for a side condition (tracks layers for stackable conditions)
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SideConditionState {
     pub layers: u8,
+
+    /// Turns left before the condition wears off (only for fixed-duration conditions)
+    pub turns_remaining: Option<u8>,
 }
 
 impl SideConditionState {
     /// Create a new condition state with 1 layer
     pub fn new() -> Self {
-        Self { layers: 1 }
+        Self {
+            layers: 1,
+            turns_remaining: None,
+        }
+    }
+
+    /// Create a new condition state with 1 layer and the condition's fixed duration
+    pub fn for_condition(condition: SideCondition) -> Self {
+        Self {
+            layers: 1,
+            turns_remaining: condition.duration(),
+        }
+    }
+
+    /// Count down one turn of a fixed-duration condition
+    pub fn tick(&mut self) {
+        if let Some(turns) = self.turns_remaining.as_mut() {
+            *turns = turns.saturating_sub(1);
+        }
     }
 
     /// Add a layer, returns true if successful
@@ -322,6 +385,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pledge_side_conditions() {
+        assert_eq!(
+            SideCondition::from_protocol("Fire Pledge"),
+            Some(SideCondition::SeaOfFire)
+        );
+        assert_eq!(
+            SideCondition::from_protocol("move: Water Pledge"),
+            Some(SideCondition::Rainbow)
+        );
+        assert_eq!(
+            SideCondition::from_protocol("Grass Pledge"),
+            Some(SideCondition::Swamp)
+        );
+        assert!(SideCondition::Swamp.is_pledge());
+        assert_eq!(SideCondition::SeaOfFire.duration(), Some(4));
+        assert_eq!(SideCondition::Reflect.duration(), None);
+
+        let mut state = SideConditionState::for_condition(SideCondition::Rainbow);
+        state.tick();
+        assert_eq!(state.turns_remaining, Some(3));
+    }
+
     #[test]
     fn test_side_condition_stackable() {
         assert!(SideCondition::Spikes.is_stackable());
@@ -354,6 +440,15 @@ mod tests {
         assert!(!SideCondition::Reflect.is_hazard());
     }
 
+    #[test]
+    fn test_side_condition_court_change_swappable() {
+        assert!(SideCondition::Spikes.is_court_change_swappable());
+        assert!(SideCondition::Tailwind.is_court_change_swappable());
+        assert!(SideCondition::Rainbow.is_court_change_swappable());
+        assert!(!SideCondition::WideGuard.is_court_change_swappable());
+        assert!(!SideCondition::MatBlock.is_court_change_swappable());
+    }
+
     #[test]
     fn test_side_condition_state() {
         let mut state = SideConditionState::new();
@@ -0,0 +1,34 @@
+//! Core battle domain types for Pokemon Showdown
+//!
+//! The protocol-independent part of `kazam-battle`: types and the type chart,
+//! status conditions and volatiles, stat stages, weather, terrain, side
+//! conditions, generation rules, and the type-level matchup math. Everything here is `no_std`
+//! and only needs `alloc`, so it can be used from WASM or embedded targets
+//! that don't want the parser or tracker.
+//!
+//! `kazam-protocol` and `kazam-battle` re-export these types, so most users
+//! never depend on this crate directly.
+//!
+//! # Features
+//!
+//! - `serde`: Serialize/Deserialize for every type (no_std compatible)
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod conditions;
+pub mod matchup;
+mod mechanics;
+mod pokemon_type;
+mod stat;
+mod stats;
+mod status;
+
+pub use conditions::{SideCondition, SideConditionState, Terrain, Weather};
+pub use matchup::{immunities, is_immune_to, is_weak_to_any, resistances, resists_all, weaknesses};
+pub use mechanics::Mechanics;
+pub use pokemon_type::{TYPE_CHART, TeraType, Type};
+pub use stat::Stat;
+pub use stats::{SplitKind, StatBound, StatConstraint, StatStages};
+pub use status::{Status, Volatile, VolatileState};
@@ -0,0 +1,74 @@
+//! Generation-dependent battle rules
+
+/// Rules that changed between generations, for code that has to branch on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mechanics {
+    pub generation: u8,
+}
+
+impl Mechanics {
+    pub const fn for_generation(generation: u8) -> Self {
+        Self { generation }
+    }
+
+    /// Abilities exist (gen 3+)
+    pub const fn has_abilities(&self) -> bool {
+        self.generation >= 3
+    }
+
+    /// Held items exist (gen 2+)
+    pub const fn has_items(&self) -> bool {
+        self.generation >= 2
+    }
+
+    /// Toxic's counter is lost on switching out, leaving regular poison (gen 1-2)
+    pub const fn toxic_reverts_on_switch(&self) -> bool {
+        self.generation <= 2
+    }
+
+    /// The log has |upkeep| lines between the end of a turn and the next (gen 3+)
+    pub const fn has_upkeep(&self) -> bool {
+        self.generation >= 3
+    }
+
+    /// Ghost types can always switch out of trapping moves and abilities (gen 6+)
+    pub const fn ghosts_escape_traps(&self) -> bool {
+        self.generation >= 6
+    }
+
+    /// Intrepid Sword and Dauntless Shield boost only once per battle (gen 9+)
+    pub const fn switch_in_boosts_once_per_battle(&self) -> bool {
+        self.generation >= 9
+    }
+
+    /// Snow Warning summons snow instead of hail (gen 9+)
+    pub const fn snow_replaces_hail(&self) -> bool {
+        self.generation >= 9
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generation_rules() {
+        let rby = Mechanics::for_generation(1);
+        assert!(rby.toxic_reverts_on_switch());
+        assert!(!rby.has_items() && !rby.has_abilities());
+
+        let gsc = Mechanics::for_generation(2);
+        assert!(gsc.has_items() && gsc.toxic_reverts_on_switch());
+        assert!(!gsc.has_abilities() && !gsc.has_upkeep());
+
+        let adv = Mechanics::for_generation(3);
+        assert!(adv.has_abilities() && adv.has_upkeep());
+        assert!(!adv.toxic_reverts_on_switch() && !adv.ghosts_escape_traps());
+
+        let swsh = Mechanics::for_generation(8);
+        assert!(!swsh.switch_in_boosts_once_per_battle() && !swsh.snow_replaces_hail());
+        let sv = Mechanics::for_generation(9);
+        assert!(sv.switch_in_boosts_once_per_battle() && sv.snow_replaces_hail());
+    }
+}
@@ -2,6 +2,7 @@
 
 /// Pokemon types (18 types as of Gen 6+)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Type {
     Normal = 0,
@@ -115,8 +116,57 @@ impl Type {
     }
 }
 
-impl std::fmt::Display for Type {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Type {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Tera type: one of the 18 types, or Stellar
+///
+/// Kept apart from [`Type`] so the type chart stays 18x18. A Stellar
+/// terastallized Pokemon keeps its original types for defense.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TeraType {
+    Type(Type),
+    Stellar,
+}
+
+impl TeraType {
+    /// Parse from protocol string (case-insensitive)
+    pub fn from_protocol(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("stellar") {
+            return Some(TeraType::Stellar);
+        }
+        Type::from_protocol(s).map(TeraType::Type)
+    }
+
+    /// Convert to canonical string representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeraType::Type(t) => t.as_str(),
+            TeraType::Stellar => "Stellar",
+        }
+    }
+
+    /// The regular type, None for Stellar
+    pub fn as_type(&self) -> Option<Type> {
+        match self {
+            TeraType::Type(t) => Some(*t),
+            TeraType::Stellar => None,
+        }
+    }
+}
+
+impl From<Type> for TeraType {
+    fn from(t: Type) -> Self {
+        TeraType::Type(t)
+    }
+}
+
+impl core::fmt::Display for TeraType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
@@ -230,4 +280,13 @@ mod tests {
         assert_eq!(Type::all()[0], Type::Normal);
         assert_eq!(Type::all()[17], Type::Fairy);
     }
+
+    #[test]
+    fn test_tera_type_from_protocol() {
+        assert_eq!(TeraType::from_protocol("Stellar"), Some(TeraType::Stellar));
+        assert_eq!(TeraType::from_protocol("fairy"), Some(TeraType::Type(Type::Fairy)));
+        assert_eq!(TeraType::from_protocol("unknown"), None);
+        assert_eq!(TeraType::Stellar.as_type(), None);
+        assert_eq!(TeraType::Stellar.as_str(), "Stellar");
+    }
 }
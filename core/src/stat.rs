@@ -0,0 +1,30 @@
+//! Stat identifiers
+
+/// Stat abbreviation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stat {
+    Atk,
+    Def,
+    Spa,
+    Spd,
+    Spe,
+    Accuracy,
+    Evasion,
+}
+
+impl Stat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "atk" => Some(Stat::Atk),
+            "def" => Some(Stat::Def),
+            // Gen 1 logs can name the single Special stat
+            "spa" | "spc" => Some(Stat::Spa),
+            "spd" => Some(Stat::Spd),
+            "spe" => Some(Stat::Spe),
+            "accuracy" => Some(Stat::Accuracy),
+            "evasion" => Some(Stat::Evasion),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,10 @@
 //! Stat stages and related types
 
-use kazam_protocol::Stat;
+use crate::stat::Stat;
 
 /// Stat stages (-6 to +6)
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatStages {
     pub atk: i8,
     pub def: i8,
@@ -129,7 +130,7 @@ impl StatStages {
 
     /// Copy boosts from another StatStages (Psych Up)
     pub fn copy_from(&mut self, other: &StatStages) {
-        *self = other.clone();
+        *self = *other;
     }
 
     /// Get the multiplier for a stat stage (for atk/def/spa/spd/spe)
@@ -168,10 +169,113 @@ impl StatStages {
     }
 }
 
+/// A known ordering between two of a Pokemon's stats (e.g. from Download)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatConstraint {
+    /// The stat known to be at least as high
+    pub higher: Stat,
+
+    /// The stat known to be at most as high
+    pub lower: Stat,
+
+    /// Whether the two stats may be equal
+    pub or_equal: bool,
+}
+
+impl StatConstraint {
+    /// Check whether a pair of stat values satisfies this constraint
+    pub fn holds(&self, higher: u32, lower: u32) -> bool {
+        if self.or_equal {
+            higher >= lower
+        } else {
+            higher > lower
+        }
+    }
+}
+
+/// A known range for one of a Pokemon's raw stats (e.g. from Guard Split)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatBound {
+    pub stat: Stat,
+
+    /// Lowest possible value
+    pub min: u32,
+
+    /// Highest possible value
+    pub max: u32,
+}
+
+impl StatBound {
+    /// Check whether a stat value is within this bound
+    pub fn contains(&self, value: u32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+}
+
+/// Which pair of stats Guard Split or Power Split averaged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SplitKind {
+    /// Guard Split: Defense and Special Defense
+    Guard,
+
+    /// Power Split: Attack and Special Attack
+    Power,
+}
+
+impl SplitKind {
+    /// Parse the effect of an `|-activate|` line, e.g. "move: Guard Split"
+    pub fn from_effect(effect: &str) -> Option<Self> {
+        match effect.trim_start_matches("move: ") {
+            "Guard Split" => Some(Self::Guard),
+            "Power Split" => Some(Self::Power),
+            _ => None,
+        }
+    }
+
+    /// The stats this split averages
+    pub fn stats(self) -> [Stat; 2] {
+        match self {
+            Self::Guard => [Stat::Def, Stat::Spd],
+            Self::Power => [Stat::Atk, Stat::Spa],
+        }
+    }
+
+    /// Range of the partner's stat before the split, from ours before and after
+    ///
+    /// The split sets both to the floor of their mean, so the partner's was
+    /// `2 * after - ours` or one more. None if `after` couldn't have come from `ours`.
+    pub fn partner_range(ours: u32, after: u32) -> Option<(u32, u32)> {
+        let min = (2 * after).checked_sub(ours)?;
+        Some((min, min + 1))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_partner_range() {
+        assert_eq!(
+            SplitKind::from_effect("move: Guard Split"),
+            Some(SplitKind::Guard)
+        );
+        assert_eq!(SplitKind::Power.stats(), [Stat::Atk, Stat::Spa]);
+        // (200 + 301) / 2 and (200 + 300) / 2 both floor to 250
+        assert_eq!(SplitKind::partner_range(200, 250), Some((300, 301)));
+        assert_eq!(SplitKind::partner_range(300, 250), Some((200, 201)));
+        assert_eq!(SplitKind::partner_range(600, 250), None);
+        let bound = StatBound {
+            stat: Stat::Def,
+            min: 300,
+            max: 301,
+        };
+        assert!(bound.contains(301));
+    }
+
     #[test]
     fn test_new_stages_are_zero() {
         let stages = StatStages::new();
@@ -0,0 +1,1001 @@
+//! Status conditions (volatile and non-volatile)
+
+use alloc::string::{String, ToString};
+
+use crate::stat::Stat;
+
+/// Non-volatile status conditions (persist through switching)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Status {
+    Burn,
+    Freeze,
+    Paralysis,
+    Poison,
+    BadPoison, // Toxic
+    Sleep,
+}
+
+impl Status {
+    /// Parse from protocol string ("brn", "frz", "par", "psn", "tox", "slp")
+    pub fn from_protocol(s: &str) -> Option<Self> {
+        match s {
+            "brn" => Some(Status::Burn),
+            "frz" => Some(Status::Freeze),
+            "par" => Some(Status::Paralysis),
+            "psn" => Some(Status::Poison),
+            "tox" => Some(Status::BadPoison),
+            "slp" => Some(Status::Sleep),
+            _ => None,
+        }
+    }
+
+    /// Convert to protocol format
+    pub fn to_protocol(&self) -> &'static str {
+        match self {
+            Status::Burn => "brn",
+            Status::Freeze => "frz",
+            Status::Paralysis => "par",
+            Status::Poison => "psn",
+            Status::BadPoison => "tox",
+            Status::Sleep => "slp",
+        }
+    }
+
+    /// Get display name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Burn => "Burn",
+            Status::Freeze => "Freeze",
+            Status::Paralysis => "Paralysis",
+            Status::Poison => "Poison",
+            Status::BadPoison => "Toxic",
+            Status::Sleep => "Sleep",
+        }
+    }
+}
+
+impl core::fmt::Display for Status {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Volatile status conditions (cleared on switching)
+///
+/// Ordered by declaration, so sets of them iterate the same way every run.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Volatile {
+    // Movement restriction
+    Trapped,     // Mean Look, Spider Web, Block
+    PartialTrap, // Bind, Wrap, Fire Spin, etc.
+
+    // Mental effects
+    Confusion,
+    Taunt,
+    Encore,
+    Disable,
+    Torment,
+    Infatuation, // Attract
+
+    // Stat/combat related
+    FocusEnergy,
+    LaserFocus,
+
+    // Damage over time / healing
+    LeechSeed,
+    Curse, // Ghost-type curse
+    PerishSong,
+    Nightmare,
+    HealBlock,
+    Embargo, // Held item unusable
+
+    // Protection
+    Protect,
+    Endure,
+    Substitute,
+
+    // Semi-invulnerable
+    Fly,
+    Dig,
+    Dive,
+    ShadowForce,
+    PhantomForce,
+    Bounce,
+    SkyDrop,
+
+    // Turn-based states
+    Flinch,
+    Yawn,       // About to fall asleep
+    Recharging, // Hyper Beam, etc.
+    Charging,   // Solar Beam, etc.
+
+    // Multi-turn moves
+    Bide,
+    Uproar,
+    Thrash, // Outrage, Petal Dance, etc.
+    Rollout,
+
+    // Type/immunity related
+    MagnetRise,
+    Telekinesis,
+    Smackdown, // Grounded
+    Ingrain,
+    AquaRing,
+
+    // Ability-related
+    FlashFire,   // Flash Fire activated
+    SlowStart,   // Regigigas ability counter
+    Truant,      // Truant turn tracking
+    Unburden,    // Speed boost after item loss
+    GastroAcid,  // Ability suppressed
+    Imprison,    // Moves locked
+    Minimize,    // Stomp/etc does double damage
+    DefenseCurl, // Rollout does double damage
+
+    // Transform
+    Transformed,
+
+    // Misc
+    Roost,        // Lost Flying type this turn
+    Stockpile,    // 1-3 layers
+    HelpingHand,  // Power boost from ally
+    PowerTrick,   // Atk/Def swapped
+    Autotomize,   // Weight reduced
+    MagicCoat,    // Reflecting moves
+    Snatch,       // Stealing moves
+    DestinyBond,  // Taking opponent down
+    Grudge,       // PP drain on KO
+    Rage,         // Attack boost on hit
+    FocusPunch,   // Charging Focus Punch
+    MudSport,     // Electric weakened (old gens)
+    WaterSport,   // Fire weakened (old gens)
+    Electrify,    // Next move becomes Electric
+    CenterOfAttention, // Follow Me/Rage Powder
+    Commanding,        // Tatsugiri inside Dondozo, can't be targeted
+
+    // Gen 8+
+    Dynamaxed,
+    Octolock,
+    TarShot,
+    NoRetreat,
+
+    // Gen 9+
+    Terastallized,
+    SaltCure,
+    Syrupy,
+    /// Protosynthesis boosting a stat, from sun or Booster Energy
+    Protosynthesis(Stat),
+    /// Quark Drive boosting a stat, from Electric Terrain or Booster Energy
+    QuarkDrive(Stat),
+
+    /// Unknown volatile from protocol
+    Other(String),
+}
+
+impl Volatile {
+    /// Parse from protocol string
+    pub fn from_protocol(s: &str) -> Self {
+        // Strip common prefixes
+        let clean = s
+            .strip_prefix("move: ")
+            .or_else(|| s.strip_prefix("ability: "))
+            .unwrap_or(s);
+
+        // Normalize: lowercase and remove spaces, dashes, apostrophes
+        let normalized = clean.to_lowercase().replace([' ', '-', '\''], "");
+
+        match normalized.as_str() {
+            "trapped" | "meanloop" | "spiderweb" | "block" => Volatile::Trapped,
+            "partialtrap" | "bind" | "wrap" | "firespin" | "clamp" | "whirlpool" | "sandtomb"
+            | "magmastorm" | "infestation" | "snaptrip" => Volatile::PartialTrap,
+
+            "confusion" | "confused" => Volatile::Confusion,
+            "taunt" => Volatile::Taunt,
+            "encore" => Volatile::Encore,
+            "disable" | "disabled" => Volatile::Disable,
+            "torment" => Volatile::Torment,
+            "attract" | "infatuation" => Volatile::Infatuation,
+
+            "focusenergy" => Volatile::FocusEnergy,
+            "laserfocus" => Volatile::LaserFocus,
+
+            "leechseed" => Volatile::LeechSeed,
+            "curse" => Volatile::Curse,
+            "perishsong" | "perish3" | "perish2" | "perish1" => Volatile::PerishSong,
+            "nightmare" => Volatile::Nightmare,
+            "healblock" | "psychicnoise" => Volatile::HealBlock,
+            "embargo" => Volatile::Embargo,
+
+            "protect" | "detect" | "kingsshield" | "spikyshield" | "banefulbunker"
+            | "obstruct" | "silktrap" | "burningbulwark" => Volatile::Protect,
+            "endure" => Volatile::Endure,
+            "substitute" => Volatile::Substitute,
+
+            "fly" | "bounce" | "skydrop" => Volatile::Fly,
+            "dig" => Volatile::Dig,
+            "dive" => Volatile::Dive,
+            "shadowforce" => Volatile::ShadowForce,
+            "phantomforce" => Volatile::PhantomForce,
+
+            "flinch" => Volatile::Flinch,
+            "yawn" => Volatile::Yawn,
+            "mustrecharge" | "recharging" => Volatile::Recharging,
+            "twoturnmove" | "charging" | "solarbeam" | "razorwind" | "skullbash" | "skyattack"
+            | "freezeshock" | "iceburn" | "geomancy" | "meteorbeam" | "electroshot" => {
+                Volatile::Charging
+            }
+
+            "bide" => Volatile::Bide,
+            "uproar" => Volatile::Uproar,
+            "lockedmove" | "thrash" | "outrage" | "petaldance" => Volatile::Thrash,
+            "rollout" | "iceball" => Volatile::Rollout,
+
+            "magnetrise" => Volatile::MagnetRise,
+            "telekinesis" => Volatile::Telekinesis,
+            "smackdown" => Volatile::Smackdown,
+            "ingrain" => Volatile::Ingrain,
+            "aquaring" => Volatile::AquaRing,
+
+            "flashfire" => Volatile::FlashFire,
+            "slowstart" => Volatile::SlowStart,
+            "truant" => Volatile::Truant,
+            "unburden" => Volatile::Unburden,
+            "gastroacid" => Volatile::GastroAcid,
+            "imprison" => Volatile::Imprison,
+            "minimize" => Volatile::Minimize,
+            "defensecurl" => Volatile::DefenseCurl,
+
+            "transform" | "transformed" => Volatile::Transformed,
+
+            "roost" => Volatile::Roost,
+            "stockpile" | "stockpile1" | "stockpile2" | "stockpile3" => Volatile::Stockpile,
+            "helpinghand" => Volatile::HelpingHand,
+            "powertrick" => Volatile::PowerTrick,
+            "autotomize" => Volatile::Autotomize,
+            "magiccoat" => Volatile::MagicCoat,
+            "snatch" => Volatile::Snatch,
+            "destinybond" => Volatile::DestinyBond,
+            "grudge" => Volatile::Grudge,
+            "rage" => Volatile::Rage,
+            "focuspunch" => Volatile::FocusPunch,
+            "mudsport" => Volatile::MudSport,
+            "watersport" => Volatile::WaterSport,
+            "electrify" => Volatile::Electrify,
+            "followme" | "ragepowder" | "centerofattention" | "spotlight" => {
+                Volatile::CenterOfAttention
+            }
+            "commanding" | "commander" => Volatile::Commanding,
+
+            "dynamax" | "dynamaxed" => Volatile::Dynamaxed,
+            "octolock" => Volatile::Octolock,
+            "tarshot" => Volatile::TarShot,
+            "noretreat" => Volatile::NoRetreat,
+
+            "terastallized" | "tera" => Volatile::Terastallized,
+            "saltcure" => Volatile::SaltCure,
+            "syrupy" | "syrupbomb" => Volatile::Syrupy,
+
+            // Unknown volatile
+            _ => Self::paradox_boost_from(&normalized)
+                .unwrap_or_else(|| Volatile::Other(s.to_string())),
+        }
+    }
+
+    /// Protosynthesis or Quark Drive, whose boosted stat is a suffix
+    /// ("protosynthesisspa", "quarkdrivespe")
+    fn paradox_boost_from(normalized: &str) -> Option<Self> {
+        if let Some(stat) = normalized.strip_prefix("protosynthesis") {
+            Stat::parse(stat).map(Volatile::Protosynthesis)
+        } else {
+            Stat::parse(normalized.strip_prefix("quarkdrive")?).map(Volatile::QuarkDrive)
+        }
+    }
+
+    /// Stat a Protosynthesis or Quark Drive boost raises
+    pub fn paradox_stat(&self) -> Option<Stat> {
+        match self {
+            Volatile::Protosynthesis(stat) | Volatile::QuarkDrive(stat) => Some(*stat),
+            _ => None,
+        }
+    }
+
+    /// Check if this is a known volatile (not Other)
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Volatile::Other(_))
+    }
+
+    /// Whether Baton Pass hands this volatile to the incoming Pokemon
+    ///
+    /// Shed Tail passes only its Substitute, which isn't covered here.
+    pub fn is_passable(&self) -> bool {
+        match self {
+            Volatile::Trapped
+            | Volatile::Confusion
+            | Volatile::FocusEnergy
+            | Volatile::LaserFocus
+            | Volatile::LeechSeed
+            | Volatile::Curse
+            | Volatile::PerishSong
+            | Volatile::HealBlock
+            | Volatile::Embargo
+            | Volatile::Substitute
+            | Volatile::MagnetRise
+            | Volatile::Telekinesis
+            | Volatile::Ingrain
+            | Volatile::AquaRing
+            | Volatile::GastroAcid
+            | Volatile::PowerTrick => true,
+
+            Volatile::PartialTrap
+            | Volatile::Taunt
+            | Volatile::Encore
+            | Volatile::Disable
+            | Volatile::Torment
+            | Volatile::Infatuation
+            | Volatile::Nightmare
+            | Volatile::Protect
+            | Volatile::Endure
+            | Volatile::Fly
+            | Volatile::Dig
+            | Volatile::Dive
+            | Volatile::ShadowForce
+            | Volatile::PhantomForce
+            | Volatile::Bounce
+            | Volatile::SkyDrop
+            | Volatile::Flinch
+            | Volatile::Yawn
+            | Volatile::Recharging
+            | Volatile::Charging
+            | Volatile::Bide
+            | Volatile::Uproar
+            | Volatile::Thrash
+            | Volatile::Rollout
+            | Volatile::Smackdown
+            | Volatile::FlashFire
+            | Volatile::SlowStart
+            | Volatile::Truant
+            | Volatile::Unburden
+            | Volatile::Imprison
+            | Volatile::Minimize
+            | Volatile::DefenseCurl
+            | Volatile::Transformed
+            | Volatile::Roost
+            | Volatile::Stockpile
+            | Volatile::HelpingHand
+            | Volatile::Autotomize
+            | Volatile::MagicCoat
+            | Volatile::Snatch
+            | Volatile::DestinyBond
+            | Volatile::Grudge
+            | Volatile::Rage
+            | Volatile::FocusPunch
+            | Volatile::MudSport
+            | Volatile::WaterSport
+            | Volatile::Electrify
+            | Volatile::CenterOfAttention
+            | Volatile::Commanding
+            | Volatile::Dynamaxed
+            | Volatile::Octolock
+            | Volatile::TarShot
+            | Volatile::NoRetreat
+            | Volatile::Terastallized
+            | Volatile::SaltCure
+            | Volatile::Syrupy
+            | Volatile::Protosynthesis(_)
+            | Volatile::QuarkDrive(_)
+            | Volatile::Other(_) => false,
+        }
+    }
+
+    /// Whether this volatile hurts the Pokemon holding it, a reason to switch out
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Volatile::Trapped
+            | Volatile::PartialTrap
+            | Volatile::Confusion
+            | Volatile::Taunt
+            | Volatile::Encore
+            | Volatile::Disable
+            | Volatile::Torment
+            | Volatile::Infatuation
+            | Volatile::LeechSeed
+            | Volatile::Curse
+            | Volatile::PerishSong
+            | Volatile::Nightmare
+            | Volatile::HealBlock
+            | Volatile::Embargo
+            | Volatile::Flinch
+            | Volatile::Yawn
+            | Volatile::Recharging
+            | Volatile::Smackdown
+            | Volatile::SlowStart
+            | Volatile::Truant
+            | Volatile::GastroAcid
+            | Volatile::Electrify
+            | Volatile::Octolock
+            | Volatile::TarShot
+            | Volatile::SaltCure
+            | Volatile::Syrupy => true,
+
+            Volatile::Protosynthesis(_) | Volatile::QuarkDrive(_) => false,
+
+            Volatile::FocusEnergy
+            | Volatile::LaserFocus
+            | Volatile::Protect
+            | Volatile::Endure
+            | Volatile::Substitute
+            | Volatile::Fly
+            | Volatile::Dig
+            | Volatile::Dive
+            | Volatile::ShadowForce
+            | Volatile::PhantomForce
+            | Volatile::Bounce
+            | Volatile::SkyDrop
+            | Volatile::Charging
+            | Volatile::Bide
+            | Volatile::Uproar
+            | Volatile::Thrash
+            | Volatile::Rollout
+            | Volatile::MagnetRise
+            | Volatile::Telekinesis
+            | Volatile::Ingrain
+            | Volatile::AquaRing
+            | Volatile::FlashFire
+            | Volatile::Unburden
+            | Volatile::Imprison
+            | Volatile::Minimize
+            | Volatile::DefenseCurl
+            | Volatile::Transformed
+            | Volatile::Roost
+            | Volatile::Stockpile
+            | Volatile::HelpingHand
+            | Volatile::PowerTrick
+            | Volatile::Autotomize
+            | Volatile::MagicCoat
+            | Volatile::Snatch
+            | Volatile::DestinyBond
+            | Volatile::Grudge
+            | Volatile::Rage
+            | Volatile::FocusPunch
+            | Volatile::MudSport
+            | Volatile::WaterSport
+            | Volatile::CenterOfAttention
+            | Volatile::Commanding
+            | Volatile::Dynamaxed
+            | Volatile::NoRetreat
+            | Volatile::Terastallized
+            | Volatile::Other(_) => false,
+        }
+    }
+
+    /// Whether this volatile restricts which moves can be selected
+    ///
+    /// Imprison restricts the holder's foes rather than the holder.
+    pub fn blocks_moves(&self) -> bool {
+        match self {
+            Volatile::Taunt
+            | Volatile::Encore
+            | Volatile::Disable
+            | Volatile::Torment
+            | Volatile::HealBlock
+            | Volatile::Imprison => true,
+
+            Volatile::Trapped
+            | Volatile::PartialTrap
+            | Volatile::Confusion
+            | Volatile::Infatuation
+            | Volatile::FocusEnergy
+            | Volatile::LaserFocus
+            | Volatile::LeechSeed
+            | Volatile::Curse
+            | Volatile::PerishSong
+            | Volatile::Nightmare
+            | Volatile::Embargo
+            | Volatile::Protect
+            | Volatile::Endure
+            | Volatile::Substitute
+            | Volatile::Fly
+            | Volatile::Dig
+            | Volatile::Dive
+            | Volatile::ShadowForce
+            | Volatile::PhantomForce
+            | Volatile::Bounce
+            | Volatile::SkyDrop
+            | Volatile::Flinch
+            | Volatile::Yawn
+            | Volatile::Recharging
+            | Volatile::Charging
+            | Volatile::Bide
+            | Volatile::Uproar
+            | Volatile::Thrash
+            | Volatile::Rollout
+            | Volatile::MagnetRise
+            | Volatile::Telekinesis
+            | Volatile::Smackdown
+            | Volatile::Ingrain
+            | Volatile::AquaRing
+            | Volatile::FlashFire
+            | Volatile::SlowStart
+            | Volatile::Truant
+            | Volatile::Unburden
+            | Volatile::GastroAcid
+            | Volatile::Minimize
+            | Volatile::DefenseCurl
+            | Volatile::Transformed
+            | Volatile::Roost
+            | Volatile::Stockpile
+            | Volatile::HelpingHand
+            | Volatile::PowerTrick
+            | Volatile::Autotomize
+            | Volatile::MagicCoat
+            | Volatile::Snatch
+            | Volatile::DestinyBond
+            | Volatile::Grudge
+            | Volatile::Rage
+            | Volatile::FocusPunch
+            | Volatile::MudSport
+            | Volatile::WaterSport
+            | Volatile::Electrify
+            | Volatile::CenterOfAttention
+            | Volatile::Commanding
+            | Volatile::Dynamaxed
+            | Volatile::Octolock
+            | Volatile::TarShot
+            | Volatile::NoRetreat
+            | Volatile::Terastallized
+            | Volatile::SaltCure
+            | Volatile::Syrupy
+            | Volatile::Protosynthesis(_)
+            | Volatile::QuarkDrive(_)
+            | Volatile::Other(_) => false,
+        }
+    }
+
+    /// Turns this volatile lasts, for the ones with a fixed gen 9 duration
+    ///
+    /// Perish Song's is the starting count, which the server announces
+    /// again each turn, see [`Volatile::perish_count`].
+    pub fn duration(&self) -> Option<u8> {
+        match self {
+            Volatile::Yawn => Some(1),
+            Volatile::Taunt | Volatile::Encore | Volatile::PerishSong | Volatile::Telekinesis => {
+                Some(3)
+            }
+            Volatile::SlowStart
+            | Volatile::MagnetRise
+            | Volatile::HealBlock
+            | Volatile::Embargo => Some(5),
+            _ => None,
+        }
+    }
+
+    /// Count in a Perish Song protocol string ("perish3" through "perish0")
+    pub fn perish_count(s: &str) -> Option<u8> {
+        s.strip_prefix("perish")?.parse().ok()
+    }
+
+    /// Whether the holder is off the field mid-move, out of reach of most attacks
+    pub fn is_semi_invulnerable(&self) -> bool {
+        match self {
+            Volatile::Fly
+            | Volatile::Dig
+            | Volatile::Dive
+            | Volatile::ShadowForce
+            | Volatile::PhantomForce
+            | Volatile::Bounce
+            | Volatile::SkyDrop => true,
+
+            Volatile::Trapped
+            | Volatile::PartialTrap
+            | Volatile::Confusion
+            | Volatile::Taunt
+            | Volatile::Encore
+            | Volatile::Disable
+            | Volatile::Torment
+            | Volatile::Infatuation
+            | Volatile::FocusEnergy
+            | Volatile::LaserFocus
+            | Volatile::LeechSeed
+            | Volatile::Curse
+            | Volatile::PerishSong
+            | Volatile::Nightmare
+            | Volatile::HealBlock
+            | Volatile::Embargo
+            | Volatile::Protect
+            | Volatile::Endure
+            | Volatile::Substitute
+            | Volatile::Flinch
+            | Volatile::Yawn
+            | Volatile::Recharging
+            | Volatile::Charging
+            | Volatile::Bide
+            | Volatile::Uproar
+            | Volatile::Thrash
+            | Volatile::Rollout
+            | Volatile::MagnetRise
+            | Volatile::Telekinesis
+            | Volatile::Smackdown
+            | Volatile::Ingrain
+            | Volatile::AquaRing
+            | Volatile::FlashFire
+            | Volatile::SlowStart
+            | Volatile::Truant
+            | Volatile::Unburden
+            | Volatile::GastroAcid
+            | Volatile::Imprison
+            | Volatile::Minimize
+            | Volatile::DefenseCurl
+            | Volatile::Transformed
+            | Volatile::Roost
+            | Volatile::Stockpile
+            | Volatile::HelpingHand
+            | Volatile::PowerTrick
+            | Volatile::Autotomize
+            | Volatile::MagicCoat
+            | Volatile::Snatch
+            | Volatile::DestinyBond
+            | Volatile::Grudge
+            | Volatile::Rage
+            | Volatile::FocusPunch
+            | Volatile::MudSport
+            | Volatile::WaterSport
+            | Volatile::Electrify
+            | Volatile::CenterOfAttention
+            | Volatile::Commanding
+            | Volatile::Dynamaxed
+            | Volatile::Octolock
+            | Volatile::TarShot
+            | Volatile::NoRetreat
+            | Volatile::Terastallized
+            | Volatile::SaltCure
+            | Volatile::Syrupy
+            | Volatile::Protosynthesis(_)
+            | Volatile::QuarkDrive(_)
+            | Volatile::Other(_) => false,
+        }
+    }
+
+    /// Whether switching out ends this volatile
+    ///
+    /// Everything but Terastallized, which lasts for the rest of the battle.
+    /// Baton Pass ([`is_passable`](Self::is_passable)) and Shed Tail hand some
+    /// volatiles to the incoming Pokemon, but the outgoing one still loses them.
+    pub fn clears_on_switch(&self) -> bool {
+        match self {
+            Volatile::Terastallized => false,
+
+            Volatile::Trapped
+            | Volatile::PartialTrap
+            | Volatile::Confusion
+            | Volatile::Taunt
+            | Volatile::Encore
+            | Volatile::Disable
+            | Volatile::Torment
+            | Volatile::Infatuation
+            | Volatile::FocusEnergy
+            | Volatile::LaserFocus
+            | Volatile::LeechSeed
+            | Volatile::Curse
+            | Volatile::PerishSong
+            | Volatile::Nightmare
+            | Volatile::HealBlock
+            | Volatile::Embargo
+            | Volatile::Protect
+            | Volatile::Endure
+            | Volatile::Substitute
+            | Volatile::Fly
+            | Volatile::Dig
+            | Volatile::Dive
+            | Volatile::ShadowForce
+            | Volatile::PhantomForce
+            | Volatile::Bounce
+            | Volatile::SkyDrop
+            | Volatile::Flinch
+            | Volatile::Yawn
+            | Volatile::Recharging
+            | Volatile::Charging
+            | Volatile::Bide
+            | Volatile::Uproar
+            | Volatile::Thrash
+            | Volatile::Rollout
+            | Volatile::MagnetRise
+            | Volatile::Telekinesis
+            | Volatile::Smackdown
+            | Volatile::Ingrain
+            | Volatile::AquaRing
+            | Volatile::FlashFire
+            | Volatile::SlowStart
+            | Volatile::Truant
+            | Volatile::Unburden
+            | Volatile::GastroAcid
+            | Volatile::Imprison
+            | Volatile::Minimize
+            | Volatile::DefenseCurl
+            | Volatile::Transformed
+            | Volatile::Roost
+            | Volatile::Stockpile
+            | Volatile::HelpingHand
+            | Volatile::PowerTrick
+            | Volatile::Autotomize
+            | Volatile::MagicCoat
+            | Volatile::Snatch
+            | Volatile::DestinyBond
+            | Volatile::Grudge
+            | Volatile::Rage
+            | Volatile::FocusPunch
+            | Volatile::MudSport
+            | Volatile::WaterSport
+            | Volatile::Electrify
+            | Volatile::CenterOfAttention
+            | Volatile::Commanding
+            | Volatile::Dynamaxed
+            | Volatile::Octolock
+            | Volatile::TarShot
+            | Volatile::NoRetreat
+            | Volatile::SaltCure
+            | Volatile::Syrupy
+            | Volatile::Protosynthesis(_)
+            | Volatile::QuarkDrive(_)
+            | Volatile::Other(_) => true,
+        }
+    }
+
+    /// Get display name
+    pub fn as_str(&self) -> &str {
+        match self {
+            Volatile::Trapped => "Trapped",
+            Volatile::PartialTrap => "Partial Trap",
+            Volatile::Confusion => "Confusion",
+            Volatile::Taunt => "Taunt",
+            Volatile::Encore => "Encore",
+            Volatile::Disable => "Disable",
+            Volatile::Torment => "Torment",
+            Volatile::Infatuation => "Infatuation",
+            Volatile::FocusEnergy => "Focus Energy",
+            Volatile::LaserFocus => "Laser Focus",
+            Volatile::LeechSeed => "Leech Seed",
+            Volatile::Curse => "Curse",
+            Volatile::PerishSong => "Perish Song",
+            Volatile::Nightmare => "Nightmare",
+            Volatile::HealBlock => "Heal Block",
+            Volatile::Embargo => "Embargo",
+            Volatile::Protect => "Protect",
+            Volatile::Endure => "Endure",
+            Volatile::Substitute => "Substitute",
+            Volatile::Fly => "Fly",
+            Volatile::Dig => "Dig",
+            Volatile::Dive => "Dive",
+            Volatile::ShadowForce => "Shadow Force",
+            Volatile::PhantomForce => "Phantom Force",
+            Volatile::Bounce => "Bounce",
+            Volatile::SkyDrop => "Sky Drop",
+            Volatile::Flinch => "Flinch",
+            Volatile::Yawn => "Yawn",
+            Volatile::Recharging => "Recharging",
+            Volatile::Charging => "Charging",
+            Volatile::Bide => "Bide",
+            Volatile::Uproar => "Uproar",
+            Volatile::Thrash => "Thrash",
+            Volatile::Rollout => "Rollout",
+            Volatile::MagnetRise => "Magnet Rise",
+            Volatile::Telekinesis => "Telekinesis",
+            Volatile::Smackdown => "Smack Down",
+            Volatile::Ingrain => "Ingrain",
+            Volatile::AquaRing => "Aqua Ring",
+            Volatile::FlashFire => "Flash Fire",
+            Volatile::SlowStart => "Slow Start",
+            Volatile::Truant => "Truant",
+            Volatile::Unburden => "Unburden",
+            Volatile::GastroAcid => "Gastro Acid",
+            Volatile::Imprison => "Imprison",
+            Volatile::Minimize => "Minimize",
+            Volatile::DefenseCurl => "Defense Curl",
+            Volatile::Transformed => "Transformed",
+            Volatile::Roost => "Roost",
+            Volatile::Stockpile => "Stockpile",
+            Volatile::HelpingHand => "Helping Hand",
+            Volatile::PowerTrick => "Power Trick",
+            Volatile::Autotomize => "Autotomize",
+            Volatile::MagicCoat => "Magic Coat",
+            Volatile::Snatch => "Snatch",
+            Volatile::DestinyBond => "Destiny Bond",
+            Volatile::Grudge => "Grudge",
+            Volatile::Rage => "Rage",
+            Volatile::FocusPunch => "Focus Punch",
+            Volatile::MudSport => "Mud Sport",
+            Volatile::WaterSport => "Water Sport",
+            Volatile::Electrify => "Electrify",
+            Volatile::CenterOfAttention => "Center of Attention",
+            Volatile::Commanding => "Commanding",
+            Volatile::Dynamaxed => "Dynamaxed",
+            Volatile::Octolock => "Octolock",
+            Volatile::TarShot => "Tar Shot",
+            Volatile::NoRetreat => "No Retreat",
+            Volatile::Terastallized => "Terastallized",
+            Volatile::SaltCure => "Salt Cure",
+            Volatile::Syrupy => "Syrupy",
+            Volatile::Protosynthesis(_) => "Protosynthesis",
+            Volatile::QuarkDrive(_) => "Quark Drive",
+            Volatile::Other(s) => s.as_str(),
+        }
+    }
+}
+
+impl core::fmt::Display for Volatile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// State for a volatile (turns left for the fixed-duration ones)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VolatileState {
+    /// Turns left before the volatile wears off, or Perish Song's count
+    pub turns_remaining: Option<u8>,
+}
+
+impl VolatileState {
+    /// Create a new volatile state with the volatile's fixed duration
+    pub fn for_volatile(volatile: &Volatile) -> Self {
+        Self {
+            turns_remaining: volatile.duration(),
+        }
+    }
+
+    /// Count down one turn of a fixed-duration volatile
+    pub fn tick(&mut self) {
+        if let Some(turns) = self.turns_remaining.as_mut() {
+            *turns = turns.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_from_protocol() {
+        assert_eq!(Status::from_protocol("brn"), Some(Status::Burn));
+        assert_eq!(Status::from_protocol("frz"), Some(Status::Freeze));
+        assert_eq!(Status::from_protocol("par"), Some(Status::Paralysis));
+        assert_eq!(Status::from_protocol("psn"), Some(Status::Poison));
+        assert_eq!(Status::from_protocol("tox"), Some(Status::BadPoison));
+        assert_eq!(Status::from_protocol("slp"), Some(Status::Sleep));
+        assert_eq!(Status::from_protocol("fnt"), None);
+        assert_eq!(Status::from_protocol("unknown"), None);
+    }
+
+    #[test]
+    fn test_status_to_protocol() {
+        assert_eq!(Status::Burn.to_protocol(), "brn");
+        assert_eq!(Status::BadPoison.to_protocol(), "tox");
+    }
+
+    #[test]
+    fn test_volatile_from_protocol_basic() {
+        assert_eq!(Volatile::from_protocol("confusion"), Volatile::Confusion);
+        assert_eq!(Volatile::from_protocol("Confusion"), Volatile::Confusion);
+        assert_eq!(Volatile::from_protocol("taunt"), Volatile::Taunt);
+        assert_eq!(Volatile::from_protocol("substitute"), Volatile::Substitute);
+    }
+
+    #[test]
+    fn test_volatile_from_protocol_with_prefix() {
+        assert_eq!(Volatile::from_protocol("move: Taunt"), Volatile::Taunt);
+        assert_eq!(
+            Volatile::from_protocol("ability: Flash Fire"),
+            Volatile::FlashFire
+        );
+        assert_eq!(
+            Volatile::from_protocol("ability: Commander"),
+            Volatile::Commanding
+        );
+    }
+
+    #[test]
+    fn test_volatile_from_protocol_unknown() {
+        let v = Volatile::from_protocol("some_unknown_volatile");
+        assert_eq!(v, Volatile::Other("some_unknown_volatile".to_string()));
+        assert!(!v.is_known());
+    }
+
+    #[test]
+    fn test_volatile_is_known() {
+        assert!(Volatile::Confusion.is_known());
+        assert!(Volatile::Substitute.is_known());
+        assert!(!Volatile::Other("test".to_string()).is_known());
+    }
+
+    #[test]
+    fn test_volatile_heal_block_and_embargo() {
+        assert_eq!(
+            Volatile::from_protocol("move: Heal Block"),
+            Volatile::HealBlock
+        );
+        assert_eq!(Volatile::from_protocol("healblock"), Volatile::HealBlock);
+        assert_eq!(
+            Volatile::from_protocol("move: Psychic Noise"),
+            Volatile::HealBlock
+        );
+        assert_eq!(Volatile::from_protocol("move: Embargo"), Volatile::Embargo);
+        assert_eq!(Volatile::from_protocol("embargo"), Volatile::Embargo);
+        assert!(Volatile::Embargo.is_passable() && Volatile::Embargo.is_negative());
+        assert!(Volatile::HealBlock.blocks_moves());
+        assert!(!Volatile::Embargo.blocks_moves());
+    }
+
+    #[test]
+    fn test_volatile_protect_variants() {
+        assert_eq!(Volatile::from_protocol("protect"), Volatile::Protect);
+        assert_eq!(Volatile::from_protocol("detect"), Volatile::Protect);
+        assert_eq!(Volatile::from_protocol("King's Shield"), Volatile::Protect);
+        assert_eq!(Volatile::from_protocol("spikyshield"), Volatile::Protect);
+    }
+
+    #[test]
+    fn test_volatile_passable() {
+        assert!(Volatile::Substitute.is_passable());
+        assert!(Volatile::Confusion.is_passable());
+        assert!(Volatile::PerishSong.is_passable());
+        assert!(!Volatile::Taunt.is_passable());
+        assert!(!Volatile::PartialTrap.is_passable());
+        assert!(!Volatile::Other("test".to_string()).is_passable());
+    }
+
+    #[test]
+    fn test_volatile_negative() {
+        assert!(Volatile::LeechSeed.is_negative());
+        assert!(Volatile::Yawn.is_negative());
+        assert!(Volatile::SaltCure.is_negative());
+        assert!(!Volatile::Substitute.is_negative());
+        assert!(!Volatile::FocusEnergy.is_negative());
+    }
+
+    #[test]
+    fn test_volatile_blocks_moves() {
+        assert!(Volatile::Taunt.blocks_moves());
+        assert!(Volatile::Encore.blocks_moves());
+        assert!(Volatile::Imprison.blocks_moves());
+        assert!(!Volatile::Confusion.blocks_moves());
+        assert!(!Volatile::Thrash.blocks_moves());
+    }
+
+    #[test]
+    fn test_volatile_semi_invulnerable() {
+        assert!(Volatile::Fly.is_semi_invulnerable());
+        assert!(Volatile::PhantomForce.is_semi_invulnerable());
+        assert!(!Volatile::Protect.is_semi_invulnerable());
+        assert!(!Volatile::Charging.is_semi_invulnerable());
+    }
+
+    #[test]
+    fn test_volatile_duration() {
+        assert_eq!(Volatile::Taunt.duration(), Some(3));
+        assert_eq!(Volatile::Yawn.duration(), Some(1));
+        assert_eq!(Volatile::SlowStart.duration(), Some(5));
+        assert_eq!(Volatile::Confusion.duration(), None);
+
+        let mut state = VolatileState::for_volatile(&Volatile::Yawn);
+        state.tick();
+        state.tick();
+        assert_eq!(state.turns_remaining, Some(0));
+    }
+
+    #[test]
+    fn test_perish_count() {
+        assert_eq!(Volatile::perish_count("perish3"), Some(3));
+        assert_eq!(Volatile::perish_count("perish0"), Some(0));
+        assert_eq!(Volatile::perish_count("perishsong"), None);
+    }
+
+    #[test]
+    fn test_volatile_clears_on_switch() {
+        assert!(Volatile::Substitute.clears_on_switch());
+        assert!(Volatile::Other("test".to_string()).clears_on_switch());
+        assert!(!Volatile::Terastallized.clears_on_switch());
+    }
+}
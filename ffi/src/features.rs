@@ -0,0 +1,204 @@
+//! Flat feature vector for policy and evaluation models
+//!
+//! # Layout (version 1, 716 `f32`s)
+//!
+//! The vector is two side blocks (p1 then p2) followed by a field block.
+//! Flags are 0.0 or 1.0; everything else is scaled to roughly -1.0..=1.0.
+//!
+//! Side block (350 values) is 6 Pokemon blocks in party order (reveal order
+//! for the opponent, empty blocks are all zeroes) followed by 8 side conditions.
+//!
+//! | Offset | Len | Pokemon block (57 values)                                   |
+//! |--------|-----|-------------------------------------------------------------|
+//! | 0      | 1   | present                                                     |
+//! | 1      | 1   | HP fraction (0.0..=1.0)                                     |
+//! | 2      | 1   | fainted                                                     |
+//! | 3      | 1   | active                                                      |
+//! | 4      | 6   | status one-hot: brn, frz, par, psn, tox, slp                |
+//! | 10     | 7   | boosts / 6: atk, def, spa, spd, spe, accuracy, evasion      |
+//! | 17     | 18  | defensive types one-hot, in [`Type::ALL`] order             |
+//! | 35     | 1   | terastallized                                               |
+//! | 36     | 1   | revealed moves / 4 (capped at 1.0)                          |
+//! | 37     | 1   | item known                                                  |
+//! | 38     | 1   | item consumed                                               |
+//! | 39     | 18  | revealed move types, in [`Type::ALL`] order                 |
+//!
+//! | Offset | Len | Side conditions (after the 6 Pokemon blocks)                |
+//! |--------|-----|-------------------------------------------------------------|
+//! | 342    | 1   | Stealth Rock                                                |
+//! | 343    | 1   | Spikes layers / 3                                           |
+//! | 344    | 1   | Toxic Spikes layers / 2                                     |
+//! | 345    | 1   | Sticky Web                                                  |
+//! | 346    | 1   | Reflect                                                     |
+//! | 347    | 1   | Light Screen                                                |
+//! | 348    | 1   | Aurora Veil                                                 |
+//! | 349    | 1   | Tailwind                                                    |
+//!
+//! | Offset | Len | Field block (starts at 700)                                 |
+//! |--------|-----|-------------------------------------------------------------|
+//! | 0      | 8   | weather one-hot: sun, rain, sand, hail, snow, harsh sun, heavy rain, strong winds |
+//! | 8      | 4   | terrain one-hot: electric, grassy, misty, psychic           |
+//! | 12     | 1   | Trick Room                                                  |
+//! | 13     | 1   | Gravity                                                     |
+//! | 14     | 1   | Magic Room                                                  |
+//! | 15     | 1   | Wonder Room                                                 |
+//!
+//! Types are `PokemonState::get_types`, so a terastallized Pokemon has only
+//! its tera type. The tracker has no species data, so they stay zero until
+//! the embedder fills them in or the Pokemon terastallizes.
+//!
+//! Revealed move types flag each type the Pokemon's known moves cover. Moves
+//! are typed with [`move_type`], so those outside its curated list (status
+//! moves among them) add nothing.
+//!
+//! # Stability
+//!
+//! [`FEATURES_VERSION`] changes whenever an offset, length or scaling changes.
+//! New values are only ever appended in a new version, so consumers should
+//! check the version and length before reading.
+
+use kazam_battle::query::moves::move_type;
+use kazam_battle::{
+    PokemonState, SideCondition, SideState, Status, Terrain, TrackedBattle, Type, Weather,
+};
+use kazam_protocol::Player;
+
+/// Layout version of [`encode`]'s output
+pub const FEATURES_VERSION: u32 = 1;
+
+/// Values per Pokemon block
+pub const POKEMON_FEATURES: usize = 57;
+
+/// Pokemon blocks per side
+pub const POKEMON_PER_SIDE: usize = 6;
+
+/// Side conditions per side block
+pub const SIDE_CONDITION_FEATURES: usize = 8;
+
+/// Values per side block
+pub const SIDE_FEATURES: usize = POKEMON_PER_SIDE * POKEMON_FEATURES + SIDE_CONDITION_FEATURES;
+
+/// Values in the field block
+pub const FIELD_FEATURES: usize = 16;
+
+/// Offset of the field block
+pub const FIELD_OFFSET: usize = 2 * SIDE_FEATURES;
+
+/// Total length of the feature vector
+pub const FEATURES_LEN: usize = FIELD_OFFSET + FIELD_FEATURES;
+
+const STATUSES: [Status; 6] = [
+    Status::Burn,
+    Status::Freeze,
+    Status::Paralysis,
+    Status::Poison,
+    Status::BadPoison,
+    Status::Sleep,
+];
+
+const WEATHERS: [Weather; 8] = [
+    Weather::Sun,
+    Weather::Rain,
+    Weather::Sand,
+    Weather::Hail,
+    Weather::Snow,
+    Weather::HarshSun,
+    Weather::HeavyRain,
+    Weather::StrongWinds,
+];
+
+const TERRAINS: [Terrain; 4] = [
+    Terrain::Electric,
+    Terrain::Grassy,
+    Terrain::Misty,
+    Terrain::Psychic,
+];
+
+/// Encode a battle into the documented feature layout
+pub fn encode(battle: &TrackedBattle) -> Vec<f32> {
+    let mut out = vec![0.0; FEATURES_LEN];
+    for (i, player) in [Player::P1, Player::P2].into_iter().enumerate() {
+        if let Some(side) = battle.get_side(player) {
+            encode_side(side, &mut out[i * SIDE_FEATURES..(i + 1) * SIDE_FEATURES]);
+        }
+    }
+
+    let field = &mut out[FIELD_OFFSET..];
+    let field_state = &battle.field;
+    one_hot(&mut field[0..8], &WEATHERS, field_state.weather.as_ref());
+    one_hot(&mut field[8..12], &TERRAINS, field_state.terrain.as_ref());
+    field[12] = flag(field_state.trick_room);
+    field[13] = flag(field_state.gravity);
+    field[14] = flag(field_state.magic_room);
+    field[15] = flag(field_state.wonder_room);
+    out
+}
+
+fn encode_side(side: &SideState, out: &mut [f32]) {
+    for (poke, block) in side
+        .pokemon
+        .iter()
+        .zip(out.chunks_exact_mut(POKEMON_FEATURES))
+        .take(POKEMON_PER_SIDE)
+    {
+        encode_pokemon(poke, block);
+    }
+
+    let layers = |condition| {
+        side.conditions
+            .get(&condition)
+            .map_or(0.0, |state| state.layers as f32)
+    };
+    let conditions = &mut out[POKEMON_PER_SIDE * POKEMON_FEATURES..];
+    conditions[0] = layers(SideCondition::StealthRock).min(1.0);
+    conditions[1] = layers(SideCondition::Spikes) / 3.0;
+    conditions[2] = layers(SideCondition::ToxicSpikes) / 2.0;
+    conditions[3] = layers(SideCondition::StickyWeb).min(1.0);
+    conditions[4] = layers(SideCondition::Reflect).min(1.0);
+    conditions[5] = layers(SideCondition::LightScreen).min(1.0);
+    conditions[6] = layers(SideCondition::AuroraVeil).min(1.0);
+    conditions[7] = layers(SideCondition::Tailwind).min(1.0);
+}
+
+fn encode_pokemon(poke: &PokemonState, out: &mut [f32]) {
+    out[0] = 1.0;
+    out[1] = (poke.hp_current as f32 / poke.hp_max.unwrap_or(100).max(1) as f32).min(1.0);
+    out[2] = flag(poke.fainted);
+    out[3] = flag(poke.active);
+    one_hot(&mut out[4..10], &STATUSES, poke.status.as_ref());
+
+    let boosts = &poke.boosts;
+    for (slot, stage) in out[10..17].iter_mut().zip([
+        boosts.atk,
+        boosts.def,
+        boosts.spa,
+        boosts.spd,
+        boosts.spe,
+        boosts.accuracy,
+        boosts.evasion,
+    ]) {
+        *slot = stage as f32 / 6.0;
+    }
+
+    for (slot, t) in out[17..35].iter_mut().zip(Type::ALL) {
+        *slot = flag(poke.has_type(t));
+    }
+    out[35] = flag(poke.terastallized);
+    out[36] = (poke.known_moves.len() as f32 / 4.0).min(1.0);
+    out[37] = flag(poke.known_item.is_some());
+    out[38] = flag(poke.item_consumed);
+
+    for (slot, t) in out[39..57].iter_mut().zip(Type::ALL) {
+        *slot = flag(poke.known_moves.iter().any(|m| move_type(m) == Some(t)));
+    }
+}
+
+fn one_hot<T: PartialEq>(out: &mut [f32], values: &[T], value: Option<&T>) {
+    for (slot, v) in out.iter_mut().zip(values) {
+        *slot = flag(value == Some(v));
+    }
+}
+
+fn flag(value: bool) -> f32 {
+    if value { 1.0 } else { 0.0 }
+}
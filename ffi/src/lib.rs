@@ -0,0 +1,469 @@
+//! C ABI over the kazam-battle tracker
+//!
+//! Exposes a small, allocation-owning surface that any language with a C FFI
+//! (or a WASM host) can drive:
+//!
+//! - [`kazam_tracker_new`] / [`kazam_tracker_free`] manage an opaque tracker handle
+//! - [`kazam_tracker_feed`] applies protocol lines
+//! - [`kazam_tracker_state_json`] returns the full tracked state as JSON
+//! - [`kazam_tracker_features`] writes the versioned feature vector (see [`features`])
+//!
+//! Strings returned by this crate must be released with [`kazam_string_free`].
+//! Functions returning `i32` use [`KAZAM_OK`] for success and a negative error
+//! code otherwise.
+//!
+//! A panic inside the tracker never unwinds into the caller: it's caught at
+//! the boundary and reported as [`KAZAM_ERR_PANIC`] (null for functions
+//! returning a pointer). The tracker may have applied part of the input by
+//! then, so it's poisoned: every later call on it fails the same way (0 from
+//! [`kazam_tracker_features`]) and the handle can only be freed.
+
+pub mod features;
+
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use kazam_battle::{BattleKnowledge, Player, TrackedBattle};
+
+/// Success
+pub const KAZAM_OK: i32 = 0;
+
+/// A required pointer argument was null
+pub const KAZAM_ERR_NULL: i32 = -1;
+
+/// Input was not valid UTF-8
+pub const KAZAM_ERR_UTF8: i32 = -2;
+
+/// A protocol line failed to parse (the other lines in the same call were applied)
+pub const KAZAM_ERR_PARSE: i32 = -3;
+
+/// An argument was out of range
+pub const KAZAM_ERR_ARG: i32 = -4;
+
+/// The tracker panicked, now or in an earlier call; the handle can only be freed
+pub const KAZAM_ERR_PANIC: i32 = -5;
+
+/// Opaque battle tracker handle
+pub struct KazamTracker {
+    battle: TrackedBattle,
+    /// Set once an update panics partway through
+    poisoned: bool,
+}
+
+impl KazamTracker {
+    /// Tracked battle behind this handle
+    pub fn battle(&self) -> &TrackedBattle {
+        &self.battle
+    }
+
+    /// Whether an earlier update panicked, leaving the battle half-applied
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Run an update through [`guard`], poisoning the tracker if it panics
+    fn update(&mut self, f: impl FnOnce(&mut TrackedBattle) -> i32) -> i32 {
+        if self.poisoned {
+            return KAZAM_ERR_PANIC;
+        }
+        let result = guard(None, || Some(f(&mut self.battle)));
+        result.unwrap_or_else(|| {
+            self.poisoned = true;
+            KAZAM_ERR_PANIC
+        })
+    }
+
+    /// Apply newline-separated protocol lines as [`TrackedBattle::feed_line`]
+    /// does, skipping any that don't parse
+    fn feed(&mut self, input: &str) -> i32 {
+        self.update(|battle| {
+            let mut result = KAZAM_OK;
+            for line in input.lines() {
+                if !battle.feed_line(line) {
+                    result = KAZAM_ERR_PARSE;
+                }
+            }
+            result
+        })
+    }
+}
+
+/// Run `f`, returning `on_panic` instead of unwinding across the C ABI
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Create a tracker with no viewpoint. Free it with [`kazam_tracker_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn kazam_tracker_new() -> *mut KazamTracker {
+    guard(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(KazamTracker {
+            battle: TrackedBattle::new(),
+            poisoned: false,
+        }))
+    })
+}
+
+/// Destroy a tracker. Null is ignored.
+///
+/// # Safety
+///
+/// `tracker` must be null or a pointer from [`kazam_tracker_new`] that has
+/// not already been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_tracker_free(tracker: *mut KazamTracker) {
+    if !tracker.is_null() {
+        guard((), || drop(unsafe { Box::from_raw(tracker) }));
+    }
+}
+
+/// Set the player whose side is ours (1-4 for p1-p4).
+///
+/// # Safety
+///
+/// `tracker` must be null or a live pointer from [`kazam_tracker_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_tracker_set_viewpoint(
+    tracker: *mut KazamTracker,
+    player: u8,
+) -> i32 {
+    let Some(tracker) = (unsafe { tracker.as_mut() }) else {
+        return KAZAM_ERR_NULL;
+    };
+    let player = match player {
+        1 => Player::P1,
+        2 => Player::P2,
+        3 => Player::P3,
+        4 => Player::P4,
+        _ => return KAZAM_ERR_ARG,
+    };
+    tracker.update(|battle| {
+        battle.set_viewpoint(player);
+        battle.set_knowledge(BattleKnowledge::Player(player));
+        KAZAM_OK
+    })
+}
+
+/// Apply one or more newline-separated protocol lines.
+///
+/// Lines are applied in order, skipping room headers, chat and timestamps
+/// like `TrackedBattle::feed_line`. A line that fails to parse is skipped
+/// too, and [`KAZAM_ERR_PARSE`] is returned once the rest are applied.
+///
+/// # Safety
+///
+/// `tracker` must be null or a live pointer from [`kazam_tracker_new`], and
+/// `lines` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_tracker_feed(
+    tracker: *mut KazamTracker,
+    lines: *const c_char,
+) -> i32 {
+    let Some(tracker) = (unsafe { tracker.as_mut() }) else {
+        return KAZAM_ERR_NULL;
+    };
+    if lines.is_null() {
+        return KAZAM_ERR_NULL;
+    }
+    match unsafe { CStr::from_ptr(lines) }.to_str() {
+        Ok(input) => tracker.feed(input),
+        Err(_) => KAZAM_ERR_UTF8,
+    }
+}
+
+/// Serialize the tracked state to JSON (the `serde` form of `TrackedBattle`).
+///
+/// Returns null if `tracker` is null or poisoned. Free the result with
+/// [`kazam_string_free`].
+///
+/// # Safety
+///
+/// `tracker` must be null or a live pointer from [`kazam_tracker_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_tracker_state_json(tracker: *const KazamTracker) -> *mut c_char {
+    let Some(tracker) = (unsafe { tracker.as_ref() }) else {
+        return std::ptr::null_mut();
+    };
+    if tracker.poisoned {
+        return std::ptr::null_mut();
+    }
+    guard(std::ptr::null_mut(), || {
+        serde_json::to_string(&tracker.battle)
+            .ok()
+            .and_then(|json| CString::new(json).ok())
+            .map_or(std::ptr::null_mut(), CString::into_raw)
+    })
+}
+
+/// Free a string returned by this crate. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this crate that has not already
+/// been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        guard((), || drop(unsafe { CString::from_raw(s) }));
+    }
+}
+
+/// Layout version of the feature vector
+#[unsafe(no_mangle)]
+pub extern "C" fn kazam_features_version() -> u32 {
+    features::FEATURES_VERSION
+}
+
+/// Number of `f32`s in the feature vector
+#[unsafe(no_mangle)]
+pub extern "C" fn kazam_features_len() -> usize {
+    features::FEATURES_LEN
+}
+
+/// Write the feature vector into `out`.
+///
+/// Returns the vector length. Nothing is written if `out` is null or `len` is
+/// smaller than that, so callers can pass null to query the size. Returns 0
+/// if `tracker` is null or poisoned, or if encoding panicked.
+///
+/// # Safety
+///
+/// `tracker` must be null or a live pointer from [`kazam_tracker_new`], and
+/// `out` must be null or valid for writes of `len` `f32`s.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazam_tracker_features(
+    tracker: *const KazamTracker,
+    out: *mut f32,
+    len: usize,
+) -> usize {
+    let Some(tracker) = (unsafe { tracker.as_ref() }) else {
+        return 0;
+    };
+    if tracker.poisoned {
+        return 0;
+    }
+    if out.is_null() || len < features::FEATURES_LEN {
+        return features::FEATURES_LEN;
+    }
+    guard(0, || {
+        let encoded = features::encode(&tracker.battle);
+        unsafe { std::slice::from_raw_parts_mut(out, encoded.len()) }.copy_from_slice(&encoded);
+        features::FEATURES_LEN
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use features::{FIELD_OFFSET, POKEMON_FEATURES, SIDE_FEATURES};
+    use kazam_battle::{StrictnessMode, TrackerConfig, Type};
+    use kazam_protocol::parse_server_message;
+
+    const LOG: &str = r#"|player|p1|Alice|1
+|player|p2|Bob|2
+|gametype|singles
+|gen|9
+|start
+|switch|p1a: Garchomp|Garchomp, L50, M|100/100
+|switch|p2a: Gengar|Gengar, L50, M|100/100
+|turn|1
+|move|p1a: Garchomp|Stealth Rock|p2a: Gengar
+|-sidestart|p2: Bob|move: Stealth Rock
+|move|p2a: Gengar|Will-O-Wisp|p1a: Garchomp
+|-status|p1a: Garchomp|brn
+|-weather|Sandstorm
+|turn|2"#;
+
+    fn fed_tracker() -> *mut KazamTracker {
+        let tracker = kazam_tracker_new();
+        let lines = CString::new(LOG).unwrap();
+        assert_eq!(unsafe { kazam_tracker_set_viewpoint(tracker, 1) }, KAZAM_OK);
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, lines.as_ptr()) },
+            KAZAM_OK
+        );
+        tracker
+    }
+
+    #[test]
+    fn test_state_json_matches_native_tracker() {
+        let tracker = fed_tracker();
+
+        let mut native = TrackedBattle::for_player(Player::P1);
+        for line in LOG.lines() {
+            native.apply_message(&parse_server_message(line).unwrap());
+        }
+
+        let json = unsafe { kazam_tracker_state_json(tracker) };
+        assert!(!json.is_null());
+        let text = unsafe { CStr::from_ptr(json) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { kazam_string_free(json) };
+
+        let parsed: TrackedBattle = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed, native);
+        assert_eq!(parsed.turn, 2);
+        unsafe { kazam_tracker_free(tracker) };
+    }
+
+    #[test]
+    fn test_feature_vector_layout() {
+        let tracker = fed_tracker();
+        assert_eq!(kazam_features_version(), 1);
+        assert_eq!(kazam_features_len(), 716);
+
+        let mut out = vec![-1.0f32; kazam_features_len()];
+        let written = unsafe { kazam_tracker_features(tracker, out.as_mut_ptr(), out.len()) };
+        assert_eq!(written, 716);
+
+        // p1 Garchomp: present, full HP, active, burned, no known types
+        assert_eq!(&out[0..5], &[1.0, 1.0, 0.0, 1.0, 1.0]);
+        assert!(out[17..35].iter().all(|&v| v == 0.0));
+        assert_eq!(out[36], 0.25); // one revealed move
+        assert!(out[39..57].iter().all(|&v| v == 0.0)); // Stealth Rock has no attack type
+
+        // Second party slot is empty
+        assert!(
+            out[POKEMON_FEATURES..2 * POKEMON_FEATURES]
+                .iter()
+                .all(|&v| v == 0.0)
+        );
+
+        // Stealth Rock on p2's side, sand on the field
+        assert_eq!(out[SIDE_FEATURES + 342], 1.0);
+        assert_eq!(out[342], 0.0);
+        assert_eq!(out[FIELD_OFFSET + 2], 1.0);
+        assert_eq!(out[FIELD_OFFSET..FIELD_OFFSET + 8].iter().sum::<f32>(), 1.0);
+
+        // Types show up once they are filled in
+        let battle = unsafe { &mut (*tracker).battle };
+        battle.get_side_mut(Player::P1).unwrap().pokemon[0]
+            .set_types(vec![Type::Dragon, Type::Ground]);
+        let out = features::encode(battle);
+        assert_eq!(out[17 + 14], 1.0); // Dragon
+        assert_eq!(out[17 + 8], 1.0); // Ground
+        assert_eq!(out[17..35].iter().sum::<f32>(), 2.0);
+
+        // Revealed attacks flag their types
+        let attack = CString::new("|move|p1a: Garchomp|Earthquake|p2a: Gengar\n|-immune|p2a: Gengar")
+            .unwrap();
+        assert_eq!(unsafe { kazam_tracker_feed(tracker, attack.as_ptr()) }, KAZAM_OK);
+        let out = features::encode(unsafe { &(*tracker).battle });
+        assert_eq!(out[39 + 8], 1.0); // Ground
+        assert_eq!(out[39..57].iter().sum::<f32>(), 1.0);
+
+        // Terastallizing leaves only the tera type
+        let tera = CString::new("|-terastallize|p1a: Garchomp|Fairy").unwrap();
+        assert_eq!(unsafe { kazam_tracker_feed(tracker, tera.as_ptr()) }, KAZAM_OK);
+        let out = features::encode(unsafe { &(*tracker).battle });
+        assert_eq!(out[35], 1.0);
+        assert_eq!(out[17 + 17], 1.0); // Fairy
+        assert_eq!(out[17..35].iter().sum::<f32>(), 1.0);
+        unsafe { kazam_tracker_free(tracker) };
+    }
+
+    #[test]
+    fn test_features_buffer_too_small() {
+        let tracker = kazam_tracker_new();
+        let mut out = vec![7.0f32; 10];
+        let written = unsafe { kazam_tracker_features(tracker, out.as_mut_ptr(), out.len()) };
+        assert_eq!(written, 716);
+        assert!(out.iter().all(|&v| v == 7.0));
+        assert_eq!(
+            unsafe { kazam_tracker_features(tracker, std::ptr::null_mut(), 0) },
+            716
+        );
+        unsafe { kazam_tracker_free(tracker) };
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let tracker = kazam_tracker_new();
+        let valid = CString::new("|turn|1").unwrap();
+        assert_eq!(
+            unsafe { kazam_tracker_feed(std::ptr::null_mut(), valid.as_ptr()) },
+            KAZAM_ERR_NULL
+        );
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, std::ptr::null()) },
+            KAZAM_ERR_NULL
+        );
+        assert_eq!(
+            unsafe { kazam_tracker_set_viewpoint(tracker, 5) },
+            KAZAM_ERR_ARG
+        );
+
+        let invalid = [0xffu8, 0];
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, invalid.as_ptr().cast()) },
+            KAZAM_ERR_UTF8
+        );
+        assert!(unsafe { kazam_tracker_state_json(std::ptr::null()) }.is_null());
+        let mut out = vec![0.0f32; 716];
+        assert_eq!(
+            unsafe { kazam_tracker_features(std::ptr::null(), out.as_mut_ptr(), out.len()) },
+            0
+        );
+
+        unsafe { kazam_tracker_free(tracker) };
+        unsafe { kazam_tracker_free(std::ptr::null_mut()) };
+        unsafe { kazam_string_free(std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn test_feed_matches_feed_line() {
+        let tracker = kazam_tracker_new();
+        let log = format!(">battle-gen9randombattle-1\n|t:|1718000000\n{LOG}\n|c|☆Bob|gg");
+        let lines = CString::new(log.replace("|turn|1", "|turn|1\n|player|p5|Eve|1")).unwrap();
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, lines.as_ptr()) },
+            KAZAM_ERR_PARSE
+        );
+
+        // Everything after the unparseable line is still applied
+        let mut native = TrackedBattle::new();
+        for line in log.lines() {
+            assert!(native.feed_line(line));
+        }
+        let battle = unsafe { &(*tracker).battle };
+        assert_eq!(battle, &native);
+        assert_eq!(battle.turn, 2);
+        unsafe { kazam_tracker_free(tracker) };
+    }
+
+    #[test]
+    fn test_panic_is_caught() {
+        let tracker = kazam_tracker_new();
+        unsafe {
+            (*tracker).battle = TrackedBattle::with_config(
+                TrackerConfig::new().strictness(StrictnessMode::Panic),
+            );
+        }
+        let lines = CString::new(format!("{LOG}\n|-damage|p2a: Blastoise|50/100")).unwrap();
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, lines.as_ptr()) },
+            KAZAM_ERR_PANIC
+        );
+        assert!(unsafe { (*tracker).is_poisoned() });
+
+        // Every later call fails instead of reading the half-applied state
+        let valid = CString::new("|turn|3").unwrap();
+        assert_eq!(
+            unsafe { kazam_tracker_feed(tracker, valid.as_ptr()) },
+            KAZAM_ERR_PANIC
+        );
+        assert_eq!(
+            unsafe { kazam_tracker_set_viewpoint(tracker, 1) },
+            KAZAM_ERR_PANIC
+        );
+        assert!(unsafe { kazam_tracker_state_json(tracker) }.is_null());
+        let mut out = vec![0.0f32; 716];
+        assert_eq!(
+            unsafe { kazam_tracker_features(tracker, out.as_mut_ptr(), out.len()) },
+            0
+        );
+        unsafe { kazam_tracker_free(tracker) };
+    }
+}
@@ -34,6 +34,25 @@ pub enum ClientCommand {
     /// /timer on|off
     Timer(bool),
 
+    /// /blockchallenges
+    BlockChallenges,
+
+    /// /unblockchallenges
+    AllowChallenges,
+
+    /// /blockpms LEVEL - only staff and users at LEVEL ("+", "autoconfirmed",
+    /// "friends", ...) can PM us; empty for staff only
+    BlockPms(String),
+
+    /// /unblockpms
+    AllowPms,
+
+    /// /hidenext [off] - keep our battles off the room list
+    HideBattles(bool),
+
+    /// /acceptopenteamsheets or /rejectopenteamsheets - answer the prompt at team preview
+    OpenTeamSheets(bool),
+
     /// Raw chat message
     Chat(String),
 
@@ -65,6 +84,15 @@ impl ClientCommand {
             Self::Undo => "/undo".to_string(),
             Self::Forfeit => "/forfeit".to_string(),
             Self::Timer(on) => format!("/timer {}", if *on { "on" } else { "off" }),
+            Self::BlockChallenges => "/blockchallenges".to_string(),
+            Self::AllowChallenges => "/unblockchallenges".to_string(),
+            Self::BlockPms(level) if level.is_empty() => "/blockpms".to_string(),
+            Self::BlockPms(level) => format!("/blockpms {}", level),
+            Self::AllowPms => "/unblockpms".to_string(),
+            Self::HideBattles(true) => "/hidenext".to_string(),
+            Self::HideBattles(false) => "/hidenext off".to_string(),
+            Self::OpenTeamSheets(true) => "/acceptopenteamsheets".to_string(),
+            Self::OpenTeamSheets(false) => "/rejectopenteamsheets".to_string(),
             Self::Chat(message) => message.clone(),
             Self::Raw(command) => command.clone(),
         }
@@ -72,6 +100,7 @@ impl ClientCommand {
 }
 
 /// Client message with optional room context
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClientMessage {
     pub room_id: Option<String>,
     pub command: ClientCommand,
@@ -5,11 +5,12 @@ pub mod server;
 
 pub use client::{ClientCommand, ClientMessage};
 pub use server::{
-    ActivePokemon, BattleInfo, BattleRequest, ChallengeInfo, ChallengeState, Format, FormatSection,
-    GameType, HpStatus, MaxMoveSlot, MaxMoves, MoveSlot, Player, PlayerInfo, Pokemon,
-    PokemonDetails, PokemonStats, PreviewPokemon, RoomType, SearchState, ServerFrame,
-    ServerMessage, Side, SideInfo, SidePokemon, Stat, User, ZMoveInfo, parse_server_frame,
-    parse_server_message,
+    ActivePokemon, BattleInfo, BattleRequest, ChallengeInfo, ChallengeState, ChatContent, Format,
+    FormatSection, GameType, HpStatus, MaxMoveSlot, MaxMoves, ModAction, ModActionKind,
+    ModerationEvent, MoveSlot, Player, PlayerInfo, Pokemon, PokemonDetails, PokemonStats,
+    PreviewPokemon, RequestKind, RoomType, SearchState, ServerFrame, ServerMessage, Side, SideInfo,
+    SidePokemon, Stat, SwapBoostKind, TimerInfo, User, ZMoveInfo, parse_server_frame,
+    parse_server_message, same_user, user_id,
 };
 
 #[derive(Error, Debug)]
@@ -1,9 +1,10 @@
 //! Shared types for battle protocol messages
 
 use crate::ParseError;
+use serde::{Deserialize, Serialize};
 
 /// Player in a battle (p1, p2, p3, p4)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Player {
     P1,
     P2,
@@ -33,7 +34,7 @@ impl Player {
 }
 
 /// Pokemon identifier in the form "POSITION: NAME" (e.g., "p1a: Pikachu")
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pokemon {
     /// Player who owns this pokemon
     pub player: Player,
@@ -147,7 +148,7 @@ impl HpStatus {
 }
 
 /// Game type
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameType {
     Singles,
     Doubles,
@@ -169,32 +170,7 @@ impl GameType {
     }
 }
 
-/// Stat abbreviation
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Stat {
-    Atk,
-    Def,
-    Spa,
-    Spd,
-    Spe,
-    Accuracy,
-    Evasion,
-}
-
-impl Stat {
-    pub fn parse(s: &str) -> Option<Self> {
-        match s {
-            "atk" => Some(Stat::Atk),
-            "def" => Some(Stat::Def),
-            "spa" => Some(Stat::Spa),
-            "spd" => Some(Stat::Spd),
-            "spe" => Some(Stat::Spe),
-            "accuracy" => Some(Stat::Accuracy),
-            "evasion" => Some(Stat::Evasion),
-            _ => None,
-        }
-    }
-}
+pub use kazam_battle_core::Stat;
 
 /// Side of the field (for side conditions)
 #[derive(Debug, Clone, PartialEq)]
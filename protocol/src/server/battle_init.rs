@@ -110,6 +110,19 @@ pub fn parse_teampreview(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::TeamPreview(count))
 }
 
+/// Parse |showteam|PLAYER|PACKEDTEAM
+pub fn parse_showteam(parts: &[&str]) -> Result<ServerMessage> {
+    let player = parts
+        .get(2)
+        .and_then(|s| Player::parse(s))
+        .ok_or_else(|| anyhow::anyhow!("Missing player"))?;
+
+    // The packed team uses '|' between fields, so rejoin the rest of the line
+    let team = parts.get(3..).map(|p| p.join("|")).unwrap_or_default();
+
+    Ok(ServerMessage::ShowTeam { player, team })
+}
+
 /// Parse |start
 pub fn parse_start(_parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::BattleStart)
@@ -16,6 +16,7 @@ pub fn parse_move(parts: &[&str]) -> Result<ServerMessage> {
     let mut miss = false;
     let mut still = false;
     let mut anim = None;
+    let mut from = None;
 
     for part in parts.iter().skip(5) {
         if *part == "[miss]" {
@@ -24,6 +25,8 @@ pub fn parse_move(parts: &[&str]) -> Result<ServerMessage> {
             still = true;
         } else if let Some(anim_move) = part.strip_prefix("[anim] ") {
             anim = Some(anim_move.to_string());
+        } else if let Some(effect) = part.strip_prefix("[from]") {
+            from = Some(effect.trim_start().to_string());
         }
     }
 
@@ -34,6 +37,7 @@ pub fn parse_move(parts: &[&str]) -> Result<ServerMessage> {
         miss,
         still,
         anim,
+        from,
     })
 }
 
@@ -7,12 +7,22 @@ use super::battle::{parse_hp_status, parse_pokemon, Pokemon, Side, Stat};
 use super::ServerMessage;
 use anyhow::Result;
 
-/// Parse |-fail|POKEMON|ACTION
+/// Parse |-fail|POKEMON|ACTION with optional [from]EFFECT
 pub fn parse_fail(parts: &[&str]) -> Result<ServerMessage> {
     let pokemon = parse_pokemon(parts, 2)?;
-    let action = parts.get(3).map(|s| s.to_string());
+    let action = parts
+        .get(3)
+        .filter(|s| !s.starts_with('['))
+        .map(|s| s.to_string());
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
 
-    Ok(ServerMessage::Fail { pokemon, action })
+    Ok(ServerMessage::Fail {
+        pokemon,
+        action,
+        from,
+    })
 }
 
 /// Parse |-block|POKEMON|EFFECT|MOVE|ATTACKER
@@ -89,12 +99,19 @@ pub fn parse_sethp(parts: &[&str]) -> Result<ServerMessage> {
     })
 }
 
-/// Parse |-status|POKEMON|STATUS
+/// Parse |-status|POKEMON|STATUS with optional [from]EFFECT
 pub fn parse_status(parts: &[&str]) -> Result<ServerMessage> {
     let pokemon = parse_pokemon(parts, 2)?;
     let status = parts.get(3).unwrap_or(&"").to_string();
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
 
-    Ok(ServerMessage::Status { pokemon, status })
+    Ok(ServerMessage::Status {
+        pokemon,
+        status,
+        from,
+    })
 }
 
 /// Parse |-curestatus|POKEMON|STATUS
@@ -122,11 +139,15 @@ pub fn parse_boost(parts: &[&str]) -> Result<ServerMessage> {
         .get(4)
         .and_then(|s| s.parse().ok())
         .ok_or_else(|| anyhow::anyhow!("Missing amount"))?;
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
 
     Ok(ServerMessage::Boost {
         pokemon,
         stat,
         amount,
+        from,
     })
 }
 
@@ -141,11 +162,15 @@ pub fn parse_unboost(parts: &[&str]) -> Result<ServerMessage> {
         .get(4)
         .and_then(|s| s.parse().ok())
         .ok_or_else(|| anyhow::anyhow!("Missing amount"))?;
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
 
     Ok(ServerMessage::Unboost {
         pokemon,
         stat,
         amount,
+        from,
     })
 }
 
@@ -168,19 +193,76 @@ pub fn parse_setboost(parts: &[&str]) -> Result<ServerMessage> {
     })
 }
 
-/// Parse |-swapboost|SOURCE|TARGET|STATS
+/// What swapped boosts in a |-swapboost|
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwapBoostKind {
+    /// Defense and Special Defense
+    GuardSwap,
+
+    /// Attack and Special Attack
+    PowerSwap,
+
+    /// Every stat
+    HeartSwap,
+
+    /// Any other source, with its [from] effect if given
+    Other(Option<String>),
+}
+
+impl SwapBoostKind {
+    /// Label a swap from its [from] effect, or failing that its stats
+    fn classify(from: Option<&str>, stats: &[Stat]) -> Self {
+        match from.map(|f| f.strip_prefix("move: ").unwrap_or(f)) {
+            Some("Guard Swap") => Self::GuardSwap,
+            Some("Power Swap") => Self::PowerSwap,
+            Some("Heart Swap") => Self::HeartSwap,
+            Some(other) => Self::Other(Some(other.to_string())),
+            None => match stats {
+                [] => Self::HeartSwap,
+                [Stat::Def, Stat::Spd] | [Stat::Spd, Stat::Def] => Self::GuardSwap,
+                [Stat::Atk, Stat::Spa] | [Stat::Spa, Stat::Atk] => Self::PowerSwap,
+                _ => Self::Other(None),
+            },
+        }
+    }
+}
+
+/// Every stat Heart Swap exchanges
+const ALL_STATS: [Stat; 7] = [
+    Stat::Atk,
+    Stat::Def,
+    Stat::Spa,
+    Stat::Spd,
+    Stat::Spe,
+    Stat::Accuracy,
+    Stat::Evasion,
+];
+
+/// Parse |-swapboost|SOURCE|TARGET|STATS with optional [from]EFFECT
+///
+/// Heart Swap sends no stats, so it swaps all of them.
 pub fn parse_swapboost(parts: &[&str]) -> Result<ServerMessage> {
     let source = parse_pokemon(parts, 2)?;
     let target = parse_pokemon(parts, 3)?;
-    let stats: Vec<Stat> = parts
+    let mut stats: Vec<Stat> = parts
         .get(4)
+        .filter(|s| !s.starts_with('['))
         .map(|s| s.split(',').filter_map(|s| Stat::parse(s.trim())).collect())
         .unwrap_or_default();
+    let from = parts
+        .iter()
+        .skip(4)
+        .find_map(|p| p.strip_prefix("[from] "));
+    let kind = SwapBoostKind::classify(from, &stats);
+    if stats.is_empty() && kind == SwapBoostKind::HeartSwap {
+        stats = ALL_STATS.to_vec();
+    }
 
     Ok(ServerMessage::SwapBoost {
         source,
         target,
         stats,
+        kind,
     })
 }
 
@@ -232,8 +314,19 @@ pub fn parse_copyboost(parts: &[&str]) -> Result<ServerMessage> {
 pub fn parse_weather(parts: &[&str]) -> Result<ServerMessage> {
     let weather = parts.get(2).unwrap_or(&"none").to_string();
     let upkeep = parts.contains(&"[upkeep]");
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
+    let of = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[of] ").and_then(Pokemon::parse));
 
-    Ok(ServerMessage::Weather { weather, upkeep })
+    Ok(ServerMessage::Weather {
+        weather,
+        upkeep,
+        from,
+        of,
+    })
 }
 
 /// Parse |-fieldstart|CONDITION
@@ -259,15 +352,23 @@ pub fn parse_sidestart(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::SideStart { side, condition })
 }
 
-/// Parse |-sideend|SIDE|CONDITION
+/// Parse |-sideend|SIDE|CONDITION or |-sideend|SIDE|CONDITION|[from] EFFECT|[of] POKEMON
 pub fn parse_sideend(parts: &[&str]) -> Result<ServerMessage> {
     let side = parts
         .get(2)
         .and_then(|s| Side::parse(s))
         .ok_or_else(|| anyhow::anyhow!("Missing side"))?;
     let condition = parts.get(3).unwrap_or(&"").to_string();
+    let from = parts
+        .iter()
+        .skip(4)
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
 
-    Ok(ServerMessage::SideEnd { side, condition })
+    Ok(ServerMessage::SideEnd {
+        side,
+        condition,
+        from,
+    })
 }
 
 /// Parse |-swapsideconditions
@@ -309,10 +410,19 @@ pub fn parse_resisted(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::Resisted(pokemon))
 }
 
-/// Parse |-immune|POKEMON
+/// Parse |-immune|POKEMON with optional [from]EFFECT
 pub fn parse_immune(parts: &[&str]) -> Result<ServerMessage> {
     let pokemon = parse_pokemon(parts, 2)?;
-    Ok(ServerMessage::Immune(pokemon))
+    let from = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
+    Ok(ServerMessage::Immune { pokemon, from })
+}
+
+/// Parse |-ohko|POKEMON
+pub fn parse_ohko(parts: &[&str]) -> Result<ServerMessage> {
+    let pokemon = parse_pokemon(parts, 2)?;
+    Ok(ServerMessage::Ohko(pokemon))
 }
 
 /// Parse |-item|POKEMON|ITEM with optional [from]EFFECT
@@ -350,11 +460,15 @@ pub fn parse_ability(parts: &[&str]) -> Result<ServerMessage> {
     let from = parts
         .iter()
         .find_map(|p| p.strip_prefix("[from] ").map(|s| s.to_string()));
+    let of = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[of] ").and_then(Pokemon::parse));
 
     Ok(ServerMessage::Ability {
         pokemon,
         ability,
         from,
+        of,
     })
 }
 
@@ -399,6 +513,14 @@ pub fn parse_burst(parts: &[&str]) -> Result<ServerMessage> {
     })
 }
 
+/// Parse |-terastallize|POKEMON|TYPE
+pub fn parse_terastallize(parts: &[&str]) -> Result<ServerMessage> {
+    let pokemon = parse_pokemon(parts, 2)?;
+    let tera_type = parts.get(3).unwrap_or(&"").to_string();
+
+    Ok(ServerMessage::Terastallize { pokemon, tera_type })
+}
+
 /// Parse |-zpower|POKEMON
 pub fn parse_zpower(parts: &[&str]) -> Result<ServerMessage> {
     let pokemon = parse_pokemon(parts, 2)?;
@@ -415,13 +537,23 @@ pub fn parse_zbroken(parts: &[&str]) -> Result<ServerMessage> {
 pub fn parse_activate(parts: &[&str]) -> Result<ServerMessage> {
     // First part might be a Pokemon or an effect
     let pokemon = parts.get(2).and_then(|s| Pokemon::parse(s));
-    let effect = if pokemon.is_some() {
-        parts.get(3).unwrap_or(&"").to_string()
-    } else {
-        parts.get(2).unwrap_or(&"").to_string()
-    };
+    let effect_index = if pokemon.is_some() { 3 } else { 2 };
+    let effect = parts.get(effect_index).unwrap_or(&"").to_string();
+    // Tags like [of] and [consumed] are not details
+    let detail = parts
+        .get(effect_index + 1)
+        .filter(|s| !s.is_empty() && !s.starts_with('['))
+        .map(|s| s.to_string());
+    let of = parts
+        .iter()
+        .find_map(|p| p.strip_prefix("[of] ").and_then(Pokemon::parse));
 
-    Ok(ServerMessage::Activate { pokemon, effect })
+    Ok(ServerMessage::Activate {
+        pokemon,
+        effect,
+        detail,
+        of,
+    })
 }
 
 /// Parse |-hint|MESSAGE
@@ -467,6 +599,19 @@ pub fn parse_prepare(parts: &[&str]) -> Result<ServerMessage> {
     })
 }
 
+/// Parse |-anim|SOURCE|MOVE or |-anim|SOURCE|MOVE|TARGET
+pub fn parse_anim(parts: &[&str]) -> Result<ServerMessage> {
+    let source = parse_pokemon(parts, 2)?;
+    let move_name = parts.get(3).unwrap_or(&"").to_string();
+    let target = parts.get(4).and_then(|s| Pokemon::parse(s));
+
+    Ok(ServerMessage::Anim {
+        source,
+        move_name,
+        target,
+    })
+}
+
 /// Parse |-mustrecharge|POKEMON
 pub fn parse_mustrecharge(parts: &[&str]) -> Result<ServerMessage> {
     let pokemon = parse_pokemon(parts, 2)?;
@@ -504,3 +649,162 @@ pub fn parse_singleturn(parts: &[&str]) -> Result<ServerMessage> {
 
     Ok(ServerMessage::SingleTurn { pokemon, move_name })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{Player, parse_server_message};
+
+    use super::*;
+
+    #[test]
+    fn test_swapboost_kinds() {
+        let guard = parse_server_message(
+            "|-swapboost|p1a: Shuckle|p2a: Dondozo|def, spd|[from] move: Guard Swap",
+        )
+        .unwrap();
+        let ServerMessage::SwapBoost { stats, kind, .. } = guard else {
+            panic!("{guard:?}");
+        };
+        assert_eq!(stats, vec![Stat::Def, Stat::Spd]);
+        assert_eq!(kind, SwapBoostKind::GuardSwap);
+
+        let heart = parse_server_message(
+            "|-swapboost|p1a: Manaphy|p2a: Dragonite|[from] move: Heart Swap",
+        )
+        .unwrap();
+        let ServerMessage::SwapBoost { stats, kind, .. } = heart else {
+            panic!("{heart:?}");
+        };
+        assert_eq!(stats, ALL_STATS.to_vec());
+        assert_eq!(kind, SwapBoostKind::HeartSwap);
+
+        let power = parse_server_message("|-swapboost|p1a: Magearna|p2a: Kingambit|atk, spa")
+            .unwrap();
+        assert!(matches!(
+            power,
+            ServerMessage::SwapBoost {
+                kind: SwapBoostKind::PowerSwap,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_activate_detail() {
+        let poltergeist =
+            parse_server_message("|-activate|p2a: Corviknight|move: Poltergeist|Leftovers")
+                .unwrap();
+        let ServerMessage::Activate {
+            pokemon,
+            effect,
+            detail,
+            ..
+        } = poltergeist
+        else {
+            panic!("{poltergeist:?}");
+        };
+        assert_eq!(pokemon.unwrap().player, Player::P2);
+        assert_eq!(effect, "move: Poltergeist");
+        assert_eq!(detail.as_deref(), Some("Leftovers"));
+
+        let protect = parse_server_message("|-activate|p1a: Garchomp|move: Protect").unwrap();
+        assert!(matches!(protect, ServerMessage::Activate { detail: None, .. }));
+        let tagged = parse_server_message(
+            "|-activate|p1a: Dragonite|ability: Multiscale|[of] p2a: Gholdengo",
+        )
+        .unwrap();
+        let ServerMessage::Activate { detail, of, .. } = tagged else {
+            panic!("{tagged:?}");
+        };
+        assert_eq!(detail, None);
+        assert_eq!(of, Pokemon::parse("p2a: Gholdengo"));
+    }
+
+    #[test]
+    fn test_ability_from_of() {
+        assert_eq!(
+            parse_server_message(
+                "|-ability|p1a: Gardevoir|Intimidate|[from] ability: Trace|[of] p2a: Gyarados"
+            )
+            .unwrap(),
+            ServerMessage::Ability {
+                pokemon: Pokemon::parse("p1a: Gardevoir").unwrap(),
+                ability: "Intimidate".to_string(),
+                from: Some("ability: Trace".to_string()),
+                of: Pokemon::parse("p2a: Gyarados"),
+            }
+        );
+        let plain = parse_server_message("|-ability|p2a: Gyarados|Intimidate|boost").unwrap();
+        assert!(matches!(plain, ServerMessage::Ability { from: None, of: None, .. }));
+    }
+
+    #[test]
+    fn test_weather_from_of() {
+        assert_eq!(
+            parse_server_message("|-weather|RainDance|[from] ability: Drizzle|[of] p1a: Pelipper")
+                .unwrap(),
+            ServerMessage::Weather {
+                weather: "RainDance".to_string(),
+                upkeep: false,
+                from: Some("ability: Drizzle".to_string()),
+                of: Pokemon::parse("p1a: Pelipper"),
+            }
+        );
+        let upkeep = parse_server_message("|-weather|RainDance|[upkeep]").unwrap();
+        assert!(matches!(
+            upkeep,
+            ServerMessage::Weather {
+                upkeep: true,
+                from: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_terastallize() {
+        assert_eq!(
+            parse_server_message("|-terastallize|p1a: Terapagos|Stellar").unwrap(),
+            ServerMessage::Terastallize {
+                pokemon: Pokemon::parse("p1a: Terapagos").unwrap(),
+                tera_type: "Stellar".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_anim() {
+        assert_eq!(
+            parse_server_message("|-anim|p1a: Venusaur|Solar Beam|p2a: Swampert").unwrap(),
+            ServerMessage::Anim {
+                source: Pokemon::parse("p1a: Venusaur").unwrap(),
+                move_name: "Solar Beam".to_string(),
+                target: Pokemon::parse("p2a: Swampert"),
+            }
+        );
+        let untargeted = parse_server_message("|-anim|p1a: Venusaur|Solar Beam").unwrap();
+        assert!(matches!(untargeted, ServerMessage::Anim { target: None, .. }));
+    }
+
+    #[test]
+    fn test_sideend_from() {
+        let defog = parse_server_message(
+            "|-sideend|p2: Bob|Stealth Rock|[from] move: Defog|[of] p1a: Corviknight",
+        )
+        .unwrap();
+        let ServerMessage::SideEnd {
+            side,
+            condition,
+            from,
+        } = defog
+        else {
+            panic!("{defog:?}");
+        };
+        assert_eq!(side.player, Player::P2);
+        assert_eq!(condition, "Stealth Rock");
+        assert_eq!(from.as_deref(), Some("move: Defog"));
+
+        let expired = parse_server_message("|-sideend|p1: Alice|Reflect").unwrap();
+        assert!(matches!(expired, ServerMessage::SideEnd { from: None, .. }));
+    }
+}
@@ -19,6 +19,45 @@ pub fn parse_inactive(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::Inactive(message))
 }
 
+/// Battle timer information carried by an |inactive| message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerInfo {
+    /// "Battle timer is ON: ..."
+    On,
+
+    /// "Time left: N sec this turn | M sec total" (only sent to the player it concerns)
+    TimeLeft { turn_seconds: u32, total_seconds: u32 },
+
+    /// "USER has N seconds left." (public, sent for either player)
+    PlayerTimeLeft { username: String, seconds: u32 },
+}
+
+impl TimerInfo {
+    /// Parse the message of an |inactive| line, returns None for other notices
+    pub fn parse(message: &str) -> Option<Self> {
+        if message.starts_with("Battle timer is ON") {
+            return Some(Self::On);
+        }
+
+        if let Some(rest) = message.strip_prefix("Time left: ") {
+            let mut fields = rest.split('|').map(|f| f.trim());
+            let turn_seconds = fields.next()?.strip_suffix(" sec this turn")?.parse().ok()?;
+            let total_seconds = fields.next()?.strip_suffix(" sec total")?.parse().ok()?;
+            return Some(Self::TimeLeft {
+                turn_seconds,
+                total_seconds,
+            });
+        }
+
+        let (username, rest) = message.rsplit_once(" has ")?;
+        let seconds = rest.strip_suffix(" seconds left.")?.parse().ok()?;
+        Some(Self::PlayerTimeLeft {
+            username: username.to_string(),
+            seconds,
+        })
+    }
+}
+
 /// Parse |inactiveoff|MESSAGE
 pub fn parse_inactiveoff(parts: &[&str]) -> Result<ServerMessage> {
     let message = parts.get(2).unwrap_or(&"").to_string();
@@ -40,6 +79,16 @@ pub fn parse_turn(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::Turn(turn))
 }
 
+/// Parse |t:|TIMESTAMP
+pub fn parse_battle_timestamp(parts: &[&str]) -> Result<ServerMessage> {
+    let timestamp = parts
+        .get(2)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("Missing battle timestamp"))?;
+
+    Ok(ServerMessage::BattleTimestamp(timestamp))
+}
+
 /// Parse |win|USER
 pub fn parse_win(parts: &[&str]) -> Result<ServerMessage> {
     let user = parts.get(2).unwrap_or(&"").to_string();
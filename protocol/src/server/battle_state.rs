@@ -3,6 +3,7 @@
 //! These types track the state of a battle room.
 
 use super::battle::{GameType, Player};
+use super::{same_user, user_id};
 
 /// Information about a battle, collected during initialization
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -55,10 +56,35 @@ impl BattleInfo {
         self.players.iter().find(|p| p.player == player)
     }
 
+    /// Get player info by username, compared by user id
+    pub fn player_by_name(&self, username: &str) -> Option<&PlayerInfo> {
+        let id = user_id(username);
+        self.players.iter().find(|p| user_id(&p.username) == id)
+    }
+
+    /// Apply a |n| rename of a player, returns whether one matched `old_id`
+    pub fn rename_player(&mut self, old_id: &str, new_name: &str) -> bool {
+        let id = user_id(old_id);
+        match self.players.iter_mut().find(|p| user_id(&p.username) == id) {
+            Some(player) => {
+                player.username = new_name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Check if the battle has ended
     pub fn is_ended(&self) -> bool {
         self.winner.is_some() || self.tie
     }
+
+    /// Check if `username` won, compared by user id
+    pub fn is_winner(&self, username: &str) -> bool {
+        self.winner
+            .as_deref()
+            .is_some_and(|winner| same_user(winner, username))
+    }
 }
 
 /// Information about a player in a battle
@@ -0,0 +1,135 @@
+//! Chat and PM message bodies, which can carry server formatting commands
+
+/// What a chat or PM message body shows, with the server's leading command
+/// (`/html`, `/uhtml NAME,`, `/raw`, `/log`, `/error`, `/text`) resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatContent {
+    /// Ordinary text, or a `/text` body
+    Text(String),
+
+    /// `/html` or `/raw` HTML
+    Html(String),
+
+    /// `/uhtml NAME,HTML`: named HTML a later `/uhtmlchange` can replace
+    Uhtml { name: String, html: String },
+
+    /// `/uhtmlchange NAME,HTML`: replaces the named HTML
+    UhtmlChange { name: String, html: String },
+
+    /// `/log` text, shown as a modlog-style line
+    Log(String),
+
+    /// `/error` text, shown as an error
+    Error(String),
+}
+
+impl ChatContent {
+    /// Resolve a message body; anything unrecognized is plain text
+    ///
+    /// A leading `//` is the escape for text starting with `/`, and other
+    /// commands such as `/me` are left as they are.
+    pub fn parse(message: &str) -> Self {
+        if let Some(text) = message.strip_prefix("//") {
+            return Self::Text(format!("/{text}"));
+        }
+        let Some((command, rest)) = message.strip_prefix('/').and_then(|m| m.split_once(' '))
+        else {
+            return Self::Text(message.to_string());
+        };
+        match command {
+            "html" | "raw" => Self::Html(rest.to_string()),
+            "uhtml" | "uhtmlchange" => {
+                let Some((name, html)) = rest.split_once(',') else {
+                    return Self::Text(message.to_string());
+                };
+                let (name, html) = (name.trim().to_string(), html.trim_start().to_string());
+                if command == "uhtml" {
+                    Self::Uhtml { name, html }
+                } else {
+                    Self::UhtmlChange { name, html }
+                }
+            }
+            "log" => Self::Log(rest.to_string()),
+            "error" => Self::Error(rest.to_string()),
+            "text" => Self::Text(rest.to_string()),
+            _ => Self::Text(message.to_string()),
+        }
+    }
+
+    /// The text or HTML shown, without the command
+    pub fn body(&self) -> &str {
+        match self {
+            Self::Text(body) | Self::Html(body) | Self::Log(body) | Self::Error(body) => body,
+            Self::Uhtml { html, .. } | Self::UhtmlChange { html, .. } => html,
+        }
+    }
+
+    /// Whether the body is HTML rather than text
+    pub fn is_html(&self) -> bool {
+        matches!(
+            self,
+            Self::Html(_) | Self::Uhtml { .. } | Self::UhtmlChange { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_forms() {
+        assert_eq!(
+            ChatContent::parse("/html <b>Garchomp</b>: Dragon/Ground"),
+            ChatContent::Html("<b>Garchomp</b>: Dragon/Ground".to_string())
+        );
+        assert_eq!(
+            ChatContent::parse("/raw <div class=\"infobox\">hi</div>"),
+            ChatContent::Html("<div class=\"infobox\">hi</div>".to_string())
+        );
+        assert_eq!(
+            ChatContent::parse("/uhtml dexsearch-1, <table><tr><td>Garchomp</td></tr></table>"),
+            ChatContent::Uhtml {
+                name: "dexsearch-1".to_string(),
+                html: "<table><tr><td>Garchomp</td></tr></table>".to_string(),
+            }
+        );
+        assert_eq!(
+            ChatContent::parse("/uhtmlchange dexsearch-1,<i>expired</i>"),
+            ChatContent::UhtmlChange {
+                name: "dexsearch-1".to_string(),
+                html: "<i>expired</i>".to_string(),
+            }
+        );
+        assert!(ChatContent::parse("/html <br>").is_html());
+    }
+
+    #[test]
+    fn test_text_forms() {
+        assert_eq!(
+            ChatContent::parse("/log Zarel was muted by Kazam for 7 minutes."),
+            ChatContent::Log("Zarel was muted by Kazam for 7 minutes.".to_string())
+        );
+        assert_eq!(
+            ChatContent::parse("/error That command is not available here."),
+            ChatContent::Error("That command is not available here.".to_string())
+        );
+        assert_eq!(
+            ChatContent::parse("/text Dex search results: none"),
+            ChatContent::Text("Dex search results: none".to_string())
+        );
+        assert_eq!(
+            ChatContent::parse("//dt garchomp"),
+            ChatContent::Text("/dt garchomp".to_string())
+        );
+        for raw in [
+            "hello | world",
+            "/me waves",
+            "/uhtml missing-comma",
+            "/html",
+        ] {
+            assert_eq!(ChatContent::parse(raw), ChatContent::Text(raw.to_string()));
+        }
+        assert_eq!(ChatContent::parse("/error oops").body(), "oops");
+    }
+}
@@ -5,7 +5,9 @@ mod battle_init;
 mod battle_major;
 mod battle_minor;
 mod battle_progress;
+mod chat;
 mod global;
+mod moderation;
 mod room;
 
 use anyhow::Result;
@@ -14,10 +16,14 @@ use serde_json::Value;
 use std::collections::HashMap;
 
 pub use battle::{GameType, HpStatus, Player, Pokemon, PokemonDetails, Side, Stat};
+pub use battle_minor::SwapBoostKind;
+pub use battle_progress::TimerInfo;
 pub use battle_state::{BattleInfo, PlayerInfo, PreviewPokemon};
+pub use chat::ChatContent;
+pub use moderation::{ModAction, ModActionKind, ModerationEvent};
 pub use request::{
-    ActivePokemon, BattleRequest, MaxMoveSlot, MaxMoves, MoveSlot, PokemonStats, SideInfo,
-    SidePokemon, ZMoveInfo,
+    ActivePokemon, BattleRequest, MaxMoveSlot, MaxMoves, MoveSlot, PokemonStats, RequestKind,
+    SideInfo, SidePokemon, ZMoveInfo,
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -53,6 +59,46 @@ impl User {
             away,
         })
     }
+
+    /// The username as a user id (see [`user_id`])
+    pub fn id(&self) -> String {
+        user_id(&self.username)
+    }
+}
+
+/// The user string [`User::parse`] reads: rank, name, and `@!` when away
+impl std::fmt::Display for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.rank, self.username)?;
+        if self.away {
+            f.write_str("@!")?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert a username to the server's user id, as its `toID` does: lowercase
+/// the whole name, then keep only ASCII letters and digits
+///
+/// Rank symbols, stars, spaces and punctuation all drop out, so "☆Alice" and
+/// " alice" give "alice". Lowercasing first matters for the few non-ASCII
+/// letters whose lowercase is ASCII, such as the Kelvin sign. Two names
+/// belong to the same account exactly when their ids are equal.
+pub fn user_id(name: &str) -> String {
+    let keep = |c: &char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    if name.is_ascii() {
+        return name
+            .chars()
+            .map(|c| c.to_ascii_lowercase())
+            .filter(keep)
+            .collect();
+    }
+    name.to_lowercase().chars().filter(keep).collect()
+}
+
+/// Whether two names belong to the same account, compared by [`user_id`]
+pub fn same_user(a: &str, b: &str) -> bool {
+    user_id(a) == user_id(b)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -95,6 +141,9 @@ pub enum ServerMessage {
     /// |init|ROOMTYPE
     Init(RoomType),
 
+    /// |noinit|REASON|MESSAGE - a room join was refused
+    NoInit { reason: String, message: String },
+
     /// |title|TITLE
     Title(String),
 
@@ -147,6 +196,24 @@ pub enum ServerMessage {
     /// |uhtmlchange|NAME|HTML
     UhtmlChange { name: String, html: String },
 
+    // ===================
+    // Moderation
+    // ===================
+    /// |unlink|USER or |unlink|hide|USER
+    Unlink { user: String },
+
+    /// |hidelines|TYPE|USER|SHOWREVEALBUTTON|LINECOUNT
+    HideLines {
+        user: String,
+        line_count: Option<u32>,
+    },
+
+    /// |badge|USER|BADGEDATA
+    Badge { user: String, badge_data: String },
+
+    /// Modlog echo line (e.g. "USER was warned by STAFF.")
+    ModAction(ModAction),
+
     // ===================
     // Battle Initialization
     // ===================
@@ -189,6 +256,9 @@ pub enum ServerMessage {
     /// |teampreview or |teampreview|NUMBER
     TeamPreview(Option<u8>),
 
+    /// |showteam|PLAYER|PACKEDTEAM - open team sheet revealed at team preview
+    ShowTeam { player: Player, team: String },
+
     /// |start - indicates battle has started
     BattleStart,
 
@@ -210,6 +280,9 @@ pub enum ServerMessage {
     /// |turn|NUMBER
     Turn(u32),
 
+    /// |t:|TIMESTAMP - server time at the start of a turn's actions (battle rooms)
+    BattleTimestamp(i64),
+
     /// |win|USER
     Win(String),
 
@@ -227,6 +300,8 @@ pub enum ServerMessage {
         miss: bool,
         still: bool,
         anim: Option<String>,
+        /// Effect that caused the move (e.g. "ability: Magic Bounce")
+        from: Option<String>,
     },
 
     /// |switch|POKEMON|DETAILS|HP STATUS
@@ -280,10 +355,11 @@ pub enum ServerMessage {
     // ===================
     // Minor Actions
     // ===================
-    /// |-fail|POKEMON|ACTION?
+    /// |-fail|POKEMON|ACTION? with optional [from]EFFECT
     Fail {
         pokemon: Pokemon,
         action: Option<String>,
+        from: Option<String>,
     },
 
     /// |-block|POKEMON|EFFECT|MOVE?|ATTACKER?
@@ -324,8 +400,12 @@ pub enum ServerMessage {
         from: Option<String>,
     },
 
-    /// |-status|POKEMON|STATUS
-    Status { pokemon: Pokemon, status: String },
+    /// |-status|POKEMON|STATUS with optional [from]EFFECT
+    Status {
+        pokemon: Pokemon,
+        status: String,
+        from: Option<String>,
+    },
 
     /// |-curestatus|POKEMON|STATUS
     CureStatus { pokemon: Pokemon, status: String },
@@ -338,6 +418,7 @@ pub enum ServerMessage {
         pokemon: Pokemon,
         stat: Stat,
         amount: i8,
+        from: Option<String>,
     },
 
     /// |-unboost|POKEMON|STAT|AMOUNT
@@ -345,6 +426,7 @@ pub enum ServerMessage {
         pokemon: Pokemon,
         stat: Stat,
         amount: i8,
+        from: Option<String>,
     },
 
     /// |-setboost|POKEMON|STAT|AMOUNT
@@ -355,10 +437,14 @@ pub enum ServerMessage {
     },
 
     /// |-swapboost|SOURCE|TARGET|STATS
+    ///
+    /// `stats` lists every swapped stat, including for Heart Swap, which
+    /// sends none.
     SwapBoost {
         source: Pokemon,
         target: Pokemon,
         stats: Vec<Stat>,
+        kind: SwapBoostKind,
     },
 
     /// |-invertboost|POKEMON
@@ -383,8 +469,13 @@ pub enum ServerMessage {
     /// |-copyboost|SOURCE|TARGET
     CopyBoost { source: Pokemon, target: Pokemon },
 
-    /// |-weather|WEATHER
-    Weather { weather: String, upkeep: bool },
+    /// |-weather|WEATHER, with [upkeep] or [from] EFFECT|[of] POKEMON
+    Weather {
+        weather: String,
+        upkeep: bool,
+        from: Option<String>,
+        of: Option<Pokemon>,
+    },
 
     /// |-fieldstart|CONDITION
     FieldStart(String),
@@ -395,8 +486,12 @@ pub enum ServerMessage {
     /// |-sidestart|SIDE|CONDITION
     SideStart { side: Side, condition: String },
 
-    /// |-sideend|SIDE|CONDITION
-    SideEnd { side: Side, condition: String },
+    /// |-sideend|SIDE|CONDITION or |-sideend|SIDE|CONDITION|[from] EFFECT
+    SideEnd {
+        side: Side,
+        condition: String,
+        from: Option<String>,
+    },
 
     /// |-swapsideconditions
     SwapSideConditions,
@@ -416,8 +511,14 @@ pub enum ServerMessage {
     /// |-resisted|POKEMON
     Resisted(Pokemon),
 
-    /// |-immune|POKEMON
-    Immune(Pokemon),
+    /// |-immune|POKEMON with optional [from]EFFECT
+    Immune {
+        pokemon: Pokemon,
+        from: Option<String>,
+    },
+
+    /// |-ohko|POKEMON
+    Ohko(Pokemon),
 
     /// |-item|POKEMON|ITEM
     Item {
@@ -434,11 +535,12 @@ pub enum ServerMessage {
         eat: bool,
     },
 
-    /// |-ability|POKEMON|ABILITY
+    /// |-ability|POKEMON|ABILITY, optionally |[from] EFFECT|[of] SOURCE
     Ability {
         pokemon: Pokemon,
         ability: String,
         from: Option<String>,
+        of: Option<Pokemon>,
     },
 
     /// |-endability|POKEMON
@@ -460,16 +562,24 @@ pub enum ServerMessage {
         item: String,
     },
 
+    /// |-terastallize|POKEMON|TYPE
+    Terastallize { pokemon: Pokemon, tera_type: String },
+
     /// |-zpower|POKEMON
     ZPower(Pokemon),
 
     /// |-zbroken|POKEMON
     ZBroken(Pokemon),
 
-    /// |-activate|EFFECT
+    /// |-activate|POKEMON|EFFECT|DETAIL
+    ///
+    /// `detail` is the argument after the effect, such as the item Poltergeist
+    /// reveals. `of` is the other Pokemon involved, such as Guard Split's target.
     Activate {
         pokemon: Option<Pokemon>,
         effect: String,
+        detail: Option<String>,
+        of: Option<Pokemon>,
     },
 
     /// |-hint|MESSAGE
@@ -494,6 +604,16 @@ pub enum ServerMessage {
         defender: Option<Pokemon>,
     },
 
+    /// |-anim|SOURCE|MOVE|TARGET?
+    ///
+    /// A move's animation shown without a |move| line, such as a two-turn
+    /// move that skips its charge turn
+    Anim {
+        source: Pokemon,
+        move_name: String,
+        target: Option<Pokemon>,
+    },
+
     /// |-mustrecharge|POKEMON
     MustRecharge(Pokemon),
 
@@ -611,7 +731,10 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
     }
 
     if !line.starts_with('|') {
-        return Ok(ServerMessage::Raw(line.to_string()));
+        return Ok(match ModAction::parse(line) {
+            Some(action) => ServerMessage::ModAction(action),
+            None => ServerMessage::Raw(line.to_string()),
+        });
     }
 
     let parts: Vec<&str> = line.split('|').collect();
@@ -638,6 +761,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "leave" | "l" => room::parse_leave(&parts, false),
         "L" => room::parse_leave(&parts, true),
         "init" => room::parse_init(&parts),
+        "noinit" => room::parse_noinit(&parts),
         "title" => room::parse_title(&parts),
         "users" => room::parse_users(&parts),
         "chat" | "c" => room::parse_chat(&parts, None),
@@ -651,6 +775,11 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "uhtml" => room::parse_uhtml(&parts),
         "uhtmlchange" => room::parse_uhtmlchange(&parts),
 
+        // Moderation
+        "unlink" => Ok(moderation::parse_unlink(&parts, line)),
+        "hidelines" => Ok(moderation::parse_hidelines(&parts, line)),
+        "badge" => Ok(moderation::parse_badge(&parts, line)),
+
         // Battle initialization
         "player" => battle_init::parse_player(&parts),
         "teamsize" => battle_init::parse_teamsize(&parts),
@@ -662,6 +791,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "clearpoke" => battle_init::parse_clearpoke(&parts),
         "poke" => battle_init::parse_poke(&parts),
         "teampreview" => battle_init::parse_teampreview(&parts),
+        "showteam" => battle_init::parse_showteam(&parts),
         "start" => battle_init::parse_start(&parts),
 
         // Battle progress
@@ -670,6 +800,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "inactiveoff" => battle_progress::parse_inactiveoff(&parts),
         "upkeep" => battle_progress::parse_upkeep(&parts),
         "turn" => battle_progress::parse_turn(&parts),
+        "t:" => battle_progress::parse_battle_timestamp(&parts),
         "win" => battle_progress::parse_win(&parts),
         "tie" => battle_progress::parse_tie(&parts),
 
@@ -717,6 +848,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "-supereffective" => battle_minor::parse_supereffective(&parts),
         "-resisted" => battle_minor::parse_resisted(&parts),
         "-immune" => battle_minor::parse_immune(&parts),
+        "-ohko" => battle_minor::parse_ohko(&parts),
         "-item" => battle_minor::parse_item(&parts),
         "-enditem" => battle_minor::parse_enditem(&parts),
         "-ability" => battle_minor::parse_ability(&parts),
@@ -725,6 +857,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "-mega" => battle_minor::parse_mega(&parts),
         "-primal" => battle_minor::parse_primal(&parts),
         "-burst" => battle_minor::parse_burst(&parts),
+        "-terastallize" => battle_minor::parse_terastallize(&parts),
         "-zpower" => battle_minor::parse_zpower(&parts),
         "-zbroken" => battle_minor::parse_zbroken(&parts),
         "-activate" => battle_minor::parse_activate(&parts),
@@ -734,6 +867,7 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         "-combine" => battle_minor::parse_combine(&parts),
         "-waiting" => battle_minor::parse_waiting(&parts),
         "-prepare" => battle_minor::parse_prepare(&parts),
+        "-anim" => battle_minor::parse_anim(&parts),
         "-mustrecharge" => battle_minor::parse_mustrecharge(&parts),
         "-nothing" => battle_minor::parse_nothing(&parts),
         "-hitcount" => battle_minor::parse_hitcount(&parts),
@@ -743,3 +877,59 @@ pub fn parse_server_message(line: &str) -> Result<ServerMessage> {
         _ => Ok(ServerMessage::Raw(line.to_string())),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANKS: &[char] = &[' ', '+', '%', '@', '*', '#', '&', '~', '☆', '!', '‽'];
+    const NAMES: &[&str] = &["Alice", "Bob Smith", "Guest 12", "x.y-z", "Zoë", "K9"];
+
+    #[test]
+    fn test_user_id_matches_server_to_id() {
+        assert_eq!(user_id("☆Alice"), "alice");
+        assert_eq!(user_id(" Bob Smith"), "bobsmith");
+        assert_eq!(user_id("@Zoë"), "zo");
+        assert_eq!(user_id("Mr. Mime-Z 2"), "mrmimez2");
+        // The Kelvin sign lowercases to an ASCII k before filtering
+        assert_eq!(user_id("\u{212A}azam"), "kazam");
+        assert!(same_user("☆Alice", "alice"));
+        assert!(!same_user("Alice", "Alicia"));
+    }
+
+    #[test]
+    fn test_user_parse_format_round_trip() {
+        for &rank in RANKS {
+            for &name in NAMES {
+                for away in [false, true] {
+                    let user = User {
+                        rank,
+                        username: name.to_string(),
+                        away,
+                    };
+                    let text = user.to_string();
+                    let parsed = User::parse(&text).unwrap();
+                    assert_eq!(parsed, user, "{text:?}");
+                    assert_eq!(parsed.id(), user_id(name), "{text:?}");
+                    // The rank and away status never leak into the id
+                    assert_eq!(user_id(&text), user_id(name), "{text:?}");
+                    assert!(same_user(&text, name), "{text:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_user_status_suffix() {
+        let user = User::parse("@Alice@!Out to lunch").unwrap();
+        assert_eq!(
+            (user.rank, user.username.as_str(), user.away),
+            ('@', "Alice", true)
+        );
+        // A status that isn't away is dropped when formatting, and reads back the same
+        let busy = User::parse("+Bob@Busy").unwrap();
+        assert!(!busy.away);
+        assert_eq!(busy.to_string(), "+Bob");
+        assert_eq!(User::parse(&busy.to_string()), Some(busy));
+    }
+}
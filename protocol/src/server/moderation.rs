@@ -0,0 +1,314 @@
+//! Moderation messages seen in chat rooms
+
+use super::ServerMessage;
+
+/// Kind of moderation action echoed into a room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModActionKind {
+    Warn,
+    Mute,
+    Lock,
+    Ban,
+    Unmute,
+    Unlock,
+    Unban,
+    Promote,
+    Demote,
+    /// Any other command or verb, lowercased
+    Other(String),
+}
+
+impl ModActionKind {
+    fn from_verb(verb: &str) -> Self {
+        let verb = verb.to_lowercase();
+        let verb = verb.trim_start_matches('/');
+        match verb {
+            "warn" | "warned" => Self::Warn,
+            "mute" | "muted" | "hourmute" | "hourmuted" => Self::Mute,
+            "lock" | "locked" | "weeklock" | "weeklocked" | "namelock" | "namelocked" => Self::Lock,
+            "ban" | "banned" | "roomban" | "roombanned" | "blacklisted" => Self::Ban,
+            "unmute" | "unmuted" => Self::Unmute,
+            "unlock" | "unlocked" => Self::Unlock,
+            "unban" | "unbanned" | "roomunban" | "roomunbanned" => Self::Unban,
+            "promote" | "promoted" | "roompromote" | "roompromoted" => Self::Promote,
+            "demote" | "demoted" | "roomdemote" | "roomdemoted" => Self::Demote,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// A modlog echo such as "Foo was muted by Bar for 7 minutes." or "(%Bar used /warn Foo)"
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModAction {
+    /// The line as received
+    pub raw: String,
+
+    /// What was done
+    pub kind: ModActionKind,
+
+    /// User the action was applied to, if named
+    pub target: Option<String>,
+
+    /// Staff member who performed the action
+    pub by: String,
+}
+
+impl ModAction {
+    /// Recognize a modlog echo line, returns None for anything else
+    pub fn parse(line: &str) -> Option<Self> {
+        let raw = line.to_string();
+        let text = line.trim();
+        let text = text
+            .strip_prefix('(')
+            .and_then(|t| t.strip_suffix(')'))
+            .unwrap_or(text);
+
+        // "TARGET was VERB by USER[ for DURATION][.][ (REASON)]"
+        if let Some((target, rest)) = text.split_once(" was ")
+            && let Some((verb, rest)) = rest.split_once(' ')
+            && let Some(rest) = rest.strip_prefix("by ").or_else(|| {
+                // "promoted to Driver by USER"
+                rest.split_once(" by ").map(|(_, by)| by)
+            })
+        {
+            // Only known verbs, so ordinary sentences aren't mistaken for modlog lines
+            let kind = ModActionKind::from_verb(verb);
+            if matches!(kind, ModActionKind::Other(_)) {
+                return None;
+            }
+            let by = staff_name(rest)?;
+            return Some(Self {
+                raw,
+                kind,
+                target: Some(strip_rank(target).to_string()),
+                by: by.to_string(),
+            });
+        }
+
+        // "USER used /COMMAND[ TARGET[, REASON]]", where a name with spaces
+        // must carry its rank so ordinary sentences aren't taken for one
+        if let Some((by, rest)) = text.split_once(" used /")
+            && (!by.contains(' ') || strip_rank(by) != by)
+        {
+            let mut words = rest.splitn(2, ' ');
+            let command = words.next().filter(|c| !c.is_empty())?;
+            let target = words
+                .next()
+                .and_then(|t| t.split(',').next())
+                .map(|t| strip_rank(t.trim()).to_string())
+                .filter(|t| !t.is_empty());
+            return Some(Self {
+                raw,
+                kind: ModActionKind::from_verb(command),
+                target,
+                by: strip_rank(by).to_string(),
+            });
+        }
+
+        None
+    }
+}
+
+/// Umbrella for moderation messages delivered to a single handler callback
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModerationEvent {
+    /// A user's links were disabled
+    Unlink { user: String },
+
+    /// A user's chat lines were hidden
+    HideLines {
+        user: String,
+        line_count: Option<u32>,
+    },
+
+    /// A user's badge changed
+    Badge { user: String, badge_data: String },
+
+    /// A modlog echo
+    ModAction(ModAction),
+}
+
+impl ModerationEvent {
+    /// Extract the moderation event carried by a message, if any
+    pub fn from_message(message: &ServerMessage) -> Option<Self> {
+        match message {
+            ServerMessage::Unlink { user } => Some(Self::Unlink { user: user.clone() }),
+            ServerMessage::HideLines { user, line_count } => Some(Self::HideLines {
+                user: user.clone(),
+                line_count: *line_count,
+            }),
+            ServerMessage::Badge { user, badge_data } => Some(Self::Badge {
+                user: user.clone(),
+                badge_data: badge_data.clone(),
+            }),
+            ServerMessage::ModAction(action) => Some(Self::ModAction(action.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The name after "by ", without its rank, up to " for DURATION", the closing
+/// period, " (REASON)" or the end of the line; names may contain spaces
+fn staff_name(rest: &str) -> Option<&str> {
+    let end = [" for ", ". ", " ("]
+        .iter()
+        .filter_map(|tail| rest.find(tail))
+        .min()
+        .unwrap_or(rest.len());
+    let name = rest[..end].trim_end_matches('.').trim();
+    Some(strip_rank(name)).filter(|n| !n.is_empty())
+}
+
+fn strip_rank(name: &str) -> &str {
+    name.trim_start_matches(['~', '&', '#', '@', '%', '*', '+', '§', '☆'])
+}
+
+/// |unlink|USER or |unlink|hide|USER
+pub fn parse_unlink(parts: &[&str], line: &str) -> ServerMessage {
+    let user = match parts.get(2) {
+        Some(&"hide") => parts.get(3),
+        other => other,
+    };
+    match user.filter(|u| !u.is_empty()) {
+        Some(user) => ServerMessage::Unlink {
+            user: user.to_string(),
+        },
+        None => ServerMessage::Raw(line.to_string()),
+    }
+}
+
+/// |hidelines|TYPE|USER|SHOWREVEALBUTTON|LINECOUNT
+pub fn parse_hidelines(parts: &[&str], line: &str) -> ServerMessage {
+    match parts.get(3).filter(|u| !u.is_empty()) {
+        Some(user) => ServerMessage::HideLines {
+            user: user.to_string(),
+            line_count: parts.get(5).and_then(|c| c.parse().ok()),
+        },
+        None => ServerMessage::Raw(line.to_string()),
+    }
+}
+
+/// |badge|USER|BADGEDATA
+pub fn parse_badge(parts: &[&str], line: &str) -> ServerMessage {
+    match parts.get(2).filter(|u| !u.is_empty()) {
+        Some(user) if parts.len() > 3 => ServerMessage::Badge {
+            user: user.to_string(),
+            badge_data: parts[3..].join("|"),
+        },
+        _ => ServerMessage::Raw(line.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_server_message;
+
+    fn action(line: &str) -> ModAction {
+        match parse_server_message(line).unwrap() {
+            ServerMessage::ModAction(action) => action,
+            other => panic!("expected ModAction, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_modlog_echo_lines() {
+        let warn = action("Spammer was warned by %Helper. (flooding)");
+        assert_eq!(warn.kind, ModActionKind::Warn);
+        assert_eq!(warn.target.as_deref(), Some("Spammer"));
+        assert_eq!(warn.by, "Helper");
+        assert_eq!(warn.raw, "Spammer was warned by %Helper. (flooding)");
+
+        let mute = action("Spammer was muted by Helper for 7 minutes. (spam)");
+        assert_eq!(mute.kind, ModActionKind::Mute);
+        assert_eq!(mute.by, "Helper");
+
+        let ban = action("Troll was banned from lobby by @Moderator. (trolling)");
+        assert_eq!(ban.kind, ModActionKind::Ban);
+        assert_eq!(ban.target.as_deref(), Some("Troll"));
+        assert_eq!(ban.by, "Moderator");
+
+        let promote = action("Newbie was promoted to Room Voice by Owner.");
+        assert_eq!(promote.kind, ModActionKind::Promote);
+        assert_eq!(promote.by, "Owner");
+
+        let used = action("(%Helper used /warn Spammer, stop flooding)");
+        assert_eq!(used.kind, ModActionKind::Warn);
+        assert_eq!(used.target.as_deref(), Some("Spammer"));
+        assert_eq!(used.by, "Helper");
+
+        let other = action("(@Moderator used /modchat autoconfirmed)");
+        assert_eq!(other.kind, ModActionKind::Other("modchat".to_string()));
+    }
+
+    #[test]
+    fn test_multi_word_staff_names() {
+        let mute = action("Spam Bot was muted by %Kind Helper for 7 minutes. (spam)");
+        assert_eq!(mute.kind, ModActionKind::Mute);
+        assert_eq!(mute.target.as_deref(), Some("Spam Bot"));
+        assert_eq!(mute.by, "Kind Helper");
+
+        let warn = action("Spammer was warned by Kind Helper. (flooding)");
+        assert_eq!(warn.by, "Kind Helper");
+
+        let ban = action("Troll was banned from lobby by @The Big Moderator.");
+        assert_eq!(ban.by, "The Big Moderator");
+
+        // No closing period: the name runs to the end of the line
+        let promote = action("Newbie was promoted to Room Voice by Room Owner");
+        assert_eq!(promote.kind, ModActionKind::Promote);
+        assert_eq!(promote.by, "Room Owner");
+
+        let used = action("(%Kind Helper used /warn Spam Bot, stop flooding)");
+        assert_eq!(used.kind, ModActionKind::Warn);
+        assert_eq!(used.target.as_deref(), Some("Spam Bot"));
+        assert_eq!(used.by, "Kind Helper");
+    }
+
+    #[test]
+    fn test_moderation_messages() {
+        assert_eq!(
+            parse_server_message("|unlink|hide|spammer").unwrap(),
+            ServerMessage::Unlink {
+                user: "spammer".to_string()
+            }
+        );
+        assert_eq!(
+            parse_server_message("|unlink|spammer").unwrap(),
+            ServerMessage::Unlink {
+                user: "spammer".to_string()
+            }
+        );
+        assert_eq!(
+            parse_server_message("|hidelines|hide|spammer|1|3").unwrap(),
+            ServerMessage::HideLines {
+                user: "spammer".to_string(),
+                line_count: Some(3),
+            }
+        );
+        assert_eq!(
+            parse_server_message("|badge|champion|gold|Season 1 Champion").unwrap(),
+            ServerMessage::Badge {
+                user: "champion".to_string(),
+                badge_data: "gold|Season 1 Champion".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_lines_fall_back_to_raw() {
+        for line in [
+            "Welcome to the Lobby!",
+            "The tournament was won by nobody in particular",
+            "Someone I know used /help once",
+            "|unlink|",
+            "|hidelines|hide",
+            "|badge|champion",
+        ] {
+            assert!(
+                matches!(parse_server_message(line).unwrap(), ServerMessage::Raw(_)),
+                "{line}"
+            );
+        }
+    }
+}
@@ -2,8 +2,8 @@
 //!
 //! These types represent the JSON structure of |request| messages.
 
-use super::battle::Player;
-use serde::Deserialize;
+use super::battle::{Player, Stat};
+use serde::{Deserialize, Serialize};
 
 /// A battle request asking the player to make a decision
 #[derive(Debug, Clone, PartialEq, Deserialize)]
@@ -34,6 +34,24 @@ pub struct BattleRequest {
     /// No action needed (e.g., between turns)
     #[serde(default)]
     pub no_cancel: bool,
+
+    /// Set by the client when this request's rqid is not newer than one
+    /// we already answered (a re-sent request after an undo or restart)
+    #[serde(skip)]
+    pub is_stale: bool,
+}
+
+/// The shape of a request, which decides what sections it carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Pick a lead order; no active Pokemon yet
+    TeamPreview,
+    /// Replace fainted or ejected Pokemon; no move data for the active slots
+    ForceSwitch,
+    /// Choose moves or switches for the active slots
+    Move,
+    /// Nothing to choose until the opponent acts; may still list our side
+    Wait,
 }
 
 impl BattleRequest {
@@ -42,6 +60,21 @@ impl BattleRequest {
         serde_json::from_value(json.clone()).ok()
     }
 
+    /// Which of the request shapes this is
+    pub fn kind(&self) -> RequestKind {
+        if self.wait {
+            RequestKind::Wait
+        } else if self.team_preview {
+            RequestKind::TeamPreview
+        } else if self.is_force_switch() {
+            RequestKind::ForceSwitch
+        } else if self.active.is_some() {
+            RequestKind::Move
+        } else {
+            RequestKind::Wait
+        }
+    }
+
     /// Check if this request requires a decision
     pub fn needs_decision(&self) -> bool {
         !self.wait && (self.team_preview || self.force_switch.is_some() || self.active.is_some())
@@ -55,18 +88,37 @@ impl BattleRequest {
             .unwrap_or(false)
     }
 
+    /// Check if this request picks a fainted Pokemon to revive with Revival Blessing
+    pub fn is_reviving(&self) -> bool {
+        self.side
+            .as_ref()
+            .is_some_and(|s| s.pokemon.iter().any(|p| p.reviving))
+    }
+
     /// Get available pokemon to switch to
+    ///
+    /// While reviving these are the fainted Pokemon instead.
     pub fn available_switches(&self) -> Vec<&SidePokemon> {
+        let reviving = self.is_reviving();
         self.side
             .as_ref()
             .map(|s| {
                 s.pokemon
                     .iter()
-                    .filter(|p| !p.active && !p.is_fainted())
+                    .filter(|p| p.is_switch_target(reviving))
                     .collect()
             })
             .unwrap_or_default()
     }
+
+    /// Check if "switch `slot`" (1-based team order) names a valid target
+    pub fn can_switch_to(&self, slot: usize) -> bool {
+        let reviving = self.is_reviving();
+        self.side
+            .as_ref()
+            .and_then(|s| s.pokemon.get(slot.checked_sub(1)?))
+            .is_some_and(|p| p.is_switch_target(reviving))
+    }
 }
 
 /// Information about an active pokemon in battle
@@ -120,7 +172,7 @@ impl ActivePokemon {
         self.moves
             .iter()
             .enumerate()
-            .filter(|(_, m)| !m.disabled && m.pp > 0)
+            .filter(|(_, m)| !m.disabled && (m.pp > 0 || m.max_pp == 0))
             .collect()
     }
 
@@ -142,10 +194,13 @@ pub struct MoveSlot {
     pub id: String,
 
     /// Current PP
+    ///
+    /// PP fields are omitted (0 here) when locked into a move like Outrage.
+    #[serde(default)]
     pub pp: u32,
 
     /// Maximum PP
-    #[serde(rename = "maxpp")]
+    #[serde(rename = "maxpp", default)]
     pub max_pp: u32,
 
     /// Target type (normal, self, allySide, etc.)
@@ -260,6 +315,11 @@ pub struct SidePokemon {
     /// Whether already terastallized
     #[serde(default)]
     pub terastallized: Option<String>,
+
+    /// Whether this active Pokemon used Revival Blessing and is picking a
+    /// fainted teammate to revive
+    #[serde(default)]
+    pub reviving: bool,
 }
 
 impl SidePokemon {
@@ -268,6 +328,11 @@ impl SidePokemon {
         self.condition == "0 fnt" || self.condition.ends_with(" fnt")
     }
 
+    /// Whether a switch choice can pick this Pokemon; only fainted ones while reviving
+    pub fn is_switch_target(&self, reviving: bool) -> bool {
+        !self.active && self.is_fainted() == reviving
+    }
+
     /// Get current HP as a fraction (current, max)
     pub fn hp(&self) -> Option<(u32, u32)> {
         let hp_part = self.condition.split_whitespace().next()?;
@@ -278,7 +343,7 @@ impl SidePokemon {
     /// Get HP as a percentage (0-100)
     pub fn hp_percent(&self) -> u32 {
         self.hp()
-            .map(|(cur, max)| if max > 0 { cur * 100 / max } else { 0 })
+            .and_then(|(cur, max)| (cur * 100).checked_div(max))
             .unwrap_or(0)
     }
 
@@ -294,7 +359,7 @@ impl SidePokemon {
 }
 
 /// Pokemon stats
-#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct PokemonStats {
     pub atk: u32,
     pub def: u32,
@@ -302,3 +367,122 @@ pub struct PokemonStats {
     pub spd: u32,
     pub spe: u32,
 }
+
+impl PokemonStats {
+    /// One stat's value; None for accuracy and evasion, which aren't stats here
+    pub fn get(&self, stat: Stat) -> Option<u32> {
+        match stat {
+            Stat::Atk => Some(self.atk),
+            Stat::Def => Some(self.def),
+            Stat::Spa => Some(self.spa),
+            Stat::Spd => Some(self.spd),
+            Stat::Spe => Some(self.spe),
+            Stat::Accuracy | Stat::Evasion => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, stat: Stat) -> Option<&mut u32> {
+        match stat {
+            Stat::Atk => Some(&mut self.atk),
+            Stat::Def => Some(&mut self.def),
+            Stat::Spa => Some(&mut self.spa),
+            Stat::Spd => Some(&mut self.spd),
+            Stat::Spe => Some(&mut self.spe),
+            Stat::Accuracy | Stat::Evasion => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_with_no_cancel() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{"active":[{"moves":[{"move":"Protect","id":"protect","pp":16,"maxpp":16,"target":"self","disabled":false}],"trapped":true}],"side":{"name":"Alice","id":"p1","pokemon":[{"ident":"p1: Dragapult","details":"Dragapult, L50, F","condition":"131/163","active":true,"stats":{"atk":125,"def":95,"spa":120,"spd":95,"spe":162},"moves":["protect"],"baseAbility":"infiltrator","item":"choicespecs","pokeball":"pokeball","ability":"infiltrator"}]},"noCancel":true,"rqid":7}"#,
+        )
+        .unwrap();
+
+        let request = BattleRequest::parse(&json).unwrap();
+        assert_eq!(request.rqid, Some(7));
+        assert!(request.no_cancel);
+        assert!(!request.is_stale);
+        assert!(request.needs_decision());
+    }
+
+    #[test]
+    fn test_request_kinds() {
+        let kind = |json: &str| {
+            BattleRequest::parse(&serde_json::from_str(json).unwrap())
+                .unwrap()
+                .kind()
+        };
+        assert_eq!(
+            kind(r#"{"teamPreview":true,"rqid":1}"#),
+            RequestKind::TeamPreview
+        );
+        assert_eq!(
+            kind(r#"{"forceSwitch":[true],"rqid":2}"#),
+            RequestKind::ForceSwitch
+        );
+        assert_eq!(
+            kind(r#"{"active":[{"moves":[]}],"rqid":3}"#),
+            RequestKind::Move
+        );
+        assert_eq!(kind(r#"{"wait":true,"rqid":4}"#), RequestKind::Wait);
+        // A partner's forced switch in doubles leaves us nothing to choose
+        assert_eq!(
+            kind(r#"{"forceSwitch":[false],"rqid":5}"#),
+            RequestKind::Wait
+        );
+    }
+
+    fn revival_request() -> BattleRequest {
+        let json = include_str!("../../testdata/revival_blessing_request.json");
+        BattleRequest::parse(&serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_revival_blessing_request() {
+        let request = revival_request();
+        assert!(request.is_reviving());
+        assert_eq!(request.kind(), RequestKind::ForceSwitch);
+
+        let targets: Vec<_> = request
+            .available_switches()
+            .iter()
+            .map(|p| p.species())
+            .collect();
+        assert_eq!(targets, vec!["Garganacl", "Kingambit", "Dondozo"]);
+    }
+
+    #[test]
+    fn test_switch_to_fainted_only_when_reviving() {
+        let mut request = revival_request();
+        // switch 5 is the fainted Dondozo, switch 6 the healthy Gholdengo
+        assert!(request.can_switch_to(5));
+        assert!(!request.can_switch_to(6));
+        assert!(!request.can_switch_to(1));
+        assert!(!request.can_switch_to(7));
+
+        request.side.as_mut().unwrap().pokemon[0].reviving = false;
+        assert!(!request.is_reviving());
+        assert!(!request.can_switch_to(5));
+        assert!(request.can_switch_to(6));
+        assert!(!request.can_switch_to(0));
+    }
+
+    #[test]
+    fn test_locked_move_without_pp() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{"active":[{"moves":[{"move":"Outrage","id":"outrage"}],"trapped":true}],"rqid":3}"#,
+        )
+        .unwrap();
+
+        let request = BattleRequest::parse(&json).unwrap();
+        let active = &request.active.unwrap()[0];
+        assert_eq!(active.available_moves().len(), 1);
+        assert!(!active.can_switch());
+    }
+}
@@ -38,6 +38,17 @@ pub fn parse_init(parts: &[&str]) -> Result<ServerMessage> {
     Ok(ServerMessage::Init(room_type))
 }
 
+pub fn parse_noinit(parts: &[&str]) -> Result<ServerMessage> {
+    if parts.len() < 3 {
+        return Err(ParseError::MissingField("noinit reason".to_string()).into());
+    }
+
+    Ok(ServerMessage::NoInit {
+        reason: parts[2].to_string(),
+        message: parts[3..].join("|"),
+    })
+}
+
 pub fn parse_title(parts: &[&str]) -> Result<ServerMessage> {
     if parts.len() < 3 {
         return Err(ParseError::MissingField("title field".to_string()).into());
@@ -51,12 +62,13 @@ pub fn parse_users(parts: &[&str]) -> Result<ServerMessage> {
         return Err(ParseError::MissingField("users field".to_string()).into());
     }
 
-    // User list is comma-separated, first entry is the user count
+    // User list is comma-separated, first entry is the user count; a regular
+    // user's rank is a space, so entries aren't trimmed
     let user_list = parts[2];
     let users: Vec<User> = user_list
         .split(',')
         .skip(1) // First element is the count
-        .filter_map(|u| User::parse(u.trim()))
+        .filter_map(User::parse)
         .collect();
 
     Ok(ServerMessage::Users(users))
@@ -195,3 +207,20 @@ pub fn parse_uhtmlchange(parts: &[&str]) -> Result<ServerMessage> {
         html: parts[3..].join("|"),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_users_keep_blank_rank() {
+        let Ok(ServerMessage::Users(users)) = parse_users(&["", "users", "2, Alice,@Bob"]) else {
+            panic!("expected |users|");
+        };
+        let names: Vec<_> = users
+            .iter()
+            .map(|u| (u.rank, u.username.as_str()))
+            .collect();
+        assert_eq!(names, [(' ', "Alice"), ('@', "Bob")]);
+    }
+}
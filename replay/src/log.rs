@@ -25,6 +25,7 @@ pub struct ReplayLog {
 
 impl ReplayLog {
     /// Parse a replay transcript from a string.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(log: &str) -> Result<Self, ReplayError> {
         let events = log
             .lines()
@@ -0,0 +1,130 @@
+//! Downloading replay logs from the public replay server's JSON endpoint
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, bail};
+use reqwest::StatusCode;
+use reqwest::header::RETRY_AFTER;
+
+const REPLAY_JSON: &str = "https://replay.pokemonshowdown.com";
+
+/// Attempts at a replay while the server asks us to slow down
+const MAX_ATTEMPTS: u32 = 4;
+
+pub enum Fetched {
+    Log(String),
+    /// Deleted, made private, or never existed
+    Missing,
+}
+
+/// Spaces requests at least `delay` apart
+pub struct RateLimiter {
+    delay: Duration,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(delay: Duration) -> Self {
+        Self { delay, last: None }
+    }
+
+    /// Time to wait at `now` before the next request
+    fn remaining(&self, now: Instant) -> Duration {
+        self.last.map_or(Duration::ZERO, |last| {
+            (last + self.delay).saturating_duration_since(now)
+        })
+    }
+
+    pub async fn wait(&mut self) {
+        tokio::time::sleep(self.remaining(Instant::now())).await;
+        self.last = Some(Instant::now());
+    }
+}
+
+pub struct Fetcher {
+    http: reqwest::Client,
+    limiter: RateLimiter,
+}
+
+impl Fetcher {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            limiter: RateLimiter::new(delay),
+        }
+    }
+
+    /// Download a replay's log, backing off when rate limited
+    pub async fn fetch(&mut self, id: &str) -> anyhow::Result<Fetched> {
+        let url = format!("{REPLAY_JSON}/{id}.json");
+        for attempt in 1..=MAX_ATTEMPTS {
+            self.limiter.wait().await;
+            let response = self
+                .http
+                .get(&url)
+                .send()
+                .await
+                .with_context(|| format!("requesting {url}"))?;
+            match response.status() {
+                StatusCode::NOT_FOUND => return Ok(Fetched::Missing),
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                    if attempt < MAX_ATTEMPTS =>
+                {
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok()?.parse().ok())
+                        .map_or(self.limiter.delay * 4 * attempt, Duration::from_secs);
+                    eprintln!("{id}: rate limited, retrying in {retry_after:?}");
+                    tokio::time::sleep(retry_after).await;
+                }
+                status if status.is_success() => {
+                    let body = response.text().await?;
+                    return log_from_json(&body).map(Fetched::Log);
+                }
+                status => bail!("{url}: {status}"),
+            }
+        }
+        bail!("{url}: still rate limited after {MAX_ATTEMPTS} attempts")
+    }
+}
+
+/// The protocol log from a replay JSON document
+pub fn log_from_json(body: &str) -> anyhow::Result<String> {
+    let json: serde_json::Value = serde_json::from_str(body).context("replay is not JSON")?;
+    match json.get("log").and_then(|log| log.as_str()) {
+        Some(log) if !log.trim().is_empty() => Ok(log.to_string()),
+        _ => bail!("replay JSON has no log"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_from_json() {
+        let body = r#"{"id":"gen9ou-1","format":"[Gen 9] OU","log":"|j|Alice\n|turn|1\n"}"#;
+        assert_eq!(log_from_json(body).unwrap(), "|j|Alice\n|turn|1\n");
+        assert!(log_from_json(r#"{"id":"gen9ou-1","log":""}"#).is_err());
+        assert!(log_from_json("<html>Not found</html>").is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter_spacing() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(2));
+        let start = Instant::now();
+        assert_eq!(limiter.remaining(start), Duration::ZERO);
+
+        limiter.last = Some(start);
+        assert_eq!(limiter.remaining(start), Duration::from_secs(2));
+        assert_eq!(
+            limiter.remaining(start + Duration::from_millis(500)),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(
+            limiter.remaining(start + Duration::from_secs(3)),
+            Duration::ZERO
+        );
+    }
+}
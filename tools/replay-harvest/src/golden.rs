@@ -0,0 +1,98 @@
+//! Golden snapshots: the tracker's final state for a log, and what it couldn't fit
+
+use kazam_battle::diff::diff_battles;
+use kazam_battle::{StrictnessMode, TrackedBattle, TrackerConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Golden {
+    /// Inconsistencies the tracker recorded, as displayed
+    pub inconsistencies: Vec<String>,
+
+    /// Final tracked state
+    pub state: TrackedBattle,
+}
+
+impl Golden {
+    /// Track a whole log from a spectator's view
+    ///
+    /// Every inconsistency is recorded rather than panicking, so one bad line
+    /// still leaves a snapshot; strict runs fail on the count instead.
+    pub fn track(log: &str) -> Self {
+        let config = TrackerConfig::new().strictness(StrictnessMode::Silent);
+        let state = TrackedBattle::from_log_with_config(log, config);
+        Self {
+            inconsistencies: state
+                .inconsistencies()
+                .iter()
+                .map(|i| i.to_string())
+                .collect(),
+            state,
+        }
+    }
+
+    /// Differences from a stored snapshot (`self`) to a fresh one, one per line
+    pub fn differences(&self, fresh: &Self) -> Vec<String> {
+        let mut lines: Vec<String> = diff_battles(&self.state, &fresh.state)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        if self.inconsistencies != fresh.inconsistencies {
+            lines.push(format!(
+                "inconsistencies: {} -> {}",
+                self.inconsistencies.len(),
+                fresh.inconsistencies.len()
+            ));
+            lines.extend(
+                fresh
+                    .inconsistencies
+                    .iter()
+                    .filter(|i| !self.inconsistencies.contains(i))
+                    .map(|i| format!("  new: {i}")),
+            );
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "|player|p1|Alice|1\n|player|p2|Bob|2\n|gametype|singles\n\
+        |switch|p1a: Garchomp|Garchomp, L50, M|100/100\n\
+        |switch|p2a: Heatran|Heatran, L50, M|100/100\n|turn|1\n\
+        |move|p1a: Garchomp|Earthquake|p2a: Heatran\n|-damage|p2a: Heatran|10/100\n|turn|2\n";
+
+    #[test]
+    fn test_round_trip_matches() {
+        let golden = Golden::track(LOG);
+        assert!(golden.inconsistencies.is_empty());
+        let stored: Golden =
+            serde_json::from_str(&serde_json::to_string(&golden).unwrap()).unwrap();
+        assert_eq!(
+            stored.differences(&Golden::track(LOG)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_reports_state_and_inconsistency_changes() {
+        let stored = Golden::track(LOG);
+        let fresh = Golden::track(&format!(
+            "{LOG}|-damage|p2a: Heatran|0 fnt\n|faint|p2a: Heatran\n"
+        ));
+        let lines = stored.differences(&fresh);
+        assert!(!lines.is_empty());
+        assert!(
+            lines
+                .iter()
+                .all(|line| !line.starts_with("inconsistencies"))
+        );
+
+        let fresh = Golden::track(&format!("{LOG}|-damage|p2a: Ghost|50/100\n"));
+        let lines = stored.differences(&fresh);
+        assert_eq!(lines[0], "inconsistencies: 0 -> 1");
+        assert!(lines[1].starts_with("  new: "));
+    }
+}
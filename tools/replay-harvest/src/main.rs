@@ -0,0 +1,365 @@
+//! Keep a corpus of replays and golden tracker snapshots of them
+//!
+//! ```text
+//! cargo run -p replay-harvest -- [OPTIONS] [SOURCE]...
+//! ```
+//!
+//! Each SOURCE is a replay ID (or replay URL) to download from the public
+//! replay server, or a local `.log` file to copy in. New sources join the
+//! fixtures directory's manifest (see [`manifest`]). Every listed replay is
+//! then tracked from its cached log, and the final state and inconsistencies
+//! written as its golden snapshot.
+//!
+//! `--verify` re-tracks every cached log instead and diffs the result against
+//! its stored snapshot, writing nothing and never touching the network. It
+//! exits non-zero if any snapshot is stale, so tracker changes can be checked
+//! locally before the goldens are regenerated.
+//!
+//! `--emit-turn-records FORMAT` instead writes a
+//! [`TurnRecord`](kazam_battle::analytics::TurnRecord) for every turn of every
+//! cached log to stdout, as `jsonl` or `csv`, keyed by replay id. Like
+//! `--verify` it only reads the fixtures directory.
+//!
+//! Options:
+//!
+//! - `--dir DIR`: fixtures directory (default `battle/testdata/replays`)
+//! - `--delay-ms MS`: spacing between replay server requests (default 1000)
+//! - `--strict`: also fail on any replay with inconsistencies
+//! - `--verify`: check the snapshots instead of writing them
+//! - `--emit-turn-records jsonl|csv`: print per-turn records instead
+
+mod fetch;
+mod golden;
+mod manifest;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use kazam_battle::analytics::{write_csv, write_json_lines};
+use kazam_battle::{StrictnessMode, TrackedBattle, TrackerConfig};
+use kazam_protocol::parse_server_message;
+
+use fetch::{Fetched, Fetcher};
+use golden::Golden;
+use manifest::{Manifest, Source, file_id, log_path, replay_id, snapshot_path};
+
+const DEFAULT_DIR: &str = "battle/testdata/replays";
+
+/// Output format of `--emit-turn-records`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecordFormat {
+    JsonLines,
+    Csv,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    dir: PathBuf,
+    delay: Duration,
+    strict: bool,
+    verify: bool,
+    emit: Option<RecordFormat>,
+    sources: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        dir: PathBuf::from(DEFAULT_DIR),
+        delay: Duration::from_millis(1000),
+        strict: false,
+        verify: false,
+        emit: None,
+        sources: Vec::new(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => options.dir = args.next().context("--dir needs a directory")?.into(),
+            "--delay-ms" => {
+                let ms = args.next().context("--delay-ms needs a number")?;
+                options.delay = Duration::from_millis(ms.parse().context("--delay-ms")?);
+            }
+            "--strict" => options.strict = true,
+            "--verify" => options.verify = true,
+            "--emit-turn-records" => {
+                let format = args.next().context("--emit-turn-records needs a format")?;
+                options.emit = Some(match format.as_str() {
+                    "jsonl" => RecordFormat::JsonLines,
+                    "csv" => RecordFormat::Csv,
+                    _ => bail!("unknown turn record format {format}, expected jsonl or csv"),
+                });
+            }
+            flag if flag.starts_with("--") => bail!("unknown option {flag}"),
+            _ => options.sources.push(arg),
+        }
+    }
+    if options.verify && !options.sources.is_empty() {
+        bail!("--verify checks the manifest and takes no sources");
+    }
+    if options.emit.is_some() && (options.verify || !options.sources.is_empty()) {
+        bail!("--emit-turn-records reads the manifest and takes no sources or --verify");
+    }
+    Ok(options)
+}
+
+/// Add each source to the manifest, copying local logs into the directory
+fn add_sources(manifest: &mut Manifest, dir: &Path, sources: &[String]) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    for source in sources {
+        let path = Path::new(source);
+        if path.extension().is_some_and(|ext| ext == "log") {
+            let id = file_id(path)?;
+            fs::copy(path, log_path(dir, &id))
+                .with_context(|| format!("copying {}", path.display()))?;
+            manifest.add(&id, Source::File);
+        } else {
+            manifest.add(&replay_id(source)?, Source::Api);
+        }
+    }
+    Ok(())
+}
+
+/// Fetch missing logs and write every snapshot; the number of failures
+async fn harvest(options: &Options) -> anyhow::Result<usize> {
+    let mut manifest = Manifest::load(&options.dir)?;
+    add_sources(&mut manifest, &options.dir, &options.sources)?;
+    let mut fetcher = Fetcher::new(options.delay);
+    let mut failures = 0;
+
+    for entry in manifest.replays.clone() {
+        if entry.missing {
+            println!("{}: skipped, no longer on the replay server", entry.id);
+            continue;
+        }
+        let path = log_path(&options.dir, &entry.id);
+        let log = if path.exists() {
+            fs::read_to_string(&path)?
+        } else if entry.source == Source::Api {
+            match fetcher.fetch(&entry.id).await {
+                Ok(Fetched::Log(log)) => {
+                    fs::write(&path, &log)?;
+                    log
+                }
+                Ok(Fetched::Missing) => {
+                    println!("{}: not found, marked missing", entry.id);
+                    manifest.mark_missing(&entry.id);
+                    continue;
+                }
+                Err(e) => {
+                    println!("{}: FAILED {e:#}", entry.id);
+                    failures += 1;
+                    continue;
+                }
+            }
+        } else {
+            println!("{}: FAILED local log {} is gone", entry.id, path.display());
+            failures += 1;
+            continue;
+        };
+
+        let golden = Golden::track(&log);
+        let json = serde_json::to_string_pretty(&golden)? + "\n";
+        fs::write(snapshot_path(&options.dir, &entry.id), json)?;
+        println!(
+            "{}: {} turns, {} inconsistencies",
+            entry.id,
+            golden.state.turn,
+            golden.inconsistencies.len()
+        );
+        if options.strict && !golden.inconsistencies.is_empty() {
+            failures += 1;
+        }
+    }
+    manifest.save(&options.dir)?;
+    Ok(failures)
+}
+
+/// Re-track every cached log against its snapshot; the number of failures
+fn verify(options: &Options) -> anyhow::Result<usize> {
+    let manifest = Manifest::load(&options.dir)?;
+    let mut failures = 0;
+    for entry in manifest.replays.iter().filter(|entry| !entry.missing) {
+        let checked = fs::read_to_string(log_path(&options.dir, &entry.id))
+            .context("no cached log")
+            .and_then(|log| {
+                let text = fs::read_to_string(snapshot_path(&options.dir, &entry.id))
+                    .context("no snapshot")?;
+                let stored: Golden = serde_json::from_str(&text).context("malformed snapshot")?;
+                Ok((stored, Golden::track(&log)))
+            });
+        let (stored, fresh) = match checked {
+            Ok(pair) => pair,
+            Err(e) => {
+                println!("{}: FAILED {e:#}", entry.id);
+                failures += 1;
+                continue;
+            }
+        };
+
+        let differences = stored.differences(&fresh);
+        if differences.is_empty() {
+            println!("{}: ok", entry.id);
+        } else {
+            println!("{}: CHANGED (stored vs fresh)", entry.id);
+            for line in &differences {
+                println!("  {line}");
+            }
+            failures += 1;
+        }
+        if options.strict && !fresh.inconsistencies.is_empty() {
+            println!(
+                "{}: {} inconsistencies",
+                entry.id,
+                fresh.inconsistencies.len()
+            );
+            failures += 1;
+        }
+    }
+    Ok(failures)
+}
+
+/// Write turn records for every cached log; the number of failures
+fn emit_turn_records(
+    options: &Options,
+    format: RecordFormat,
+    out: impl Write,
+) -> anyhow::Result<usize> {
+    let manifest = Manifest::load(&options.dir)?;
+    let mut records = Vec::new();
+    let mut failures = 0;
+    for entry in manifest.replays.iter().filter(|entry| !entry.missing) {
+        let log = match fs::read_to_string(log_path(&options.dir, &entry.id)) {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("{}: FAILED no cached log: {e}", entry.id);
+                failures += 1;
+                continue;
+            }
+        };
+        // Lines the tracker would skip are skipped here too
+        let messages: Vec<_> = log
+            .lines()
+            .filter_map(|line| parse_server_message(line).ok())
+            .collect();
+        let config = TrackerConfig::new().strictness(StrictnessMode::Silent);
+        records.extend(TrackedBattle::with_config(config).turn_records(&entry.id, &messages));
+    }
+    match format {
+        RecordFormat::JsonLines => write_json_lines(&records, out)?,
+        RecordFormat::Csv => write_csv(&records, out)?,
+    }
+    Ok(failures)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let result = match parse_args(std::env::args().skip(1)) {
+        Ok(options) if options.verify => verify(&options),
+        Ok(options) => match options.emit {
+            Some(format) => emit_turn_records(&options, format, io::stdout().lock()),
+            None => harvest(&options).await,
+        },
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(failures) => {
+            eprintln!("replay-harvest: {failures} failed");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("replay-harvest: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kazam_battle::analytics::TurnRecord;
+
+    fn args(line: &str) -> anyhow::Result<Options> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = args("--dir fixtures --delay-ms 250 --strict gen9ou-1 local.log").unwrap();
+        assert_eq!(options.dir, PathBuf::from("fixtures"));
+        assert_eq!(options.delay, Duration::from_millis(250));
+        assert!(options.strict && !options.verify);
+        assert_eq!(options.sources, ["gen9ou-1", "local.log"]);
+
+        assert_eq!(args("").unwrap().dir, PathBuf::from(DEFAULT_DIR));
+        assert!(args("--verify").unwrap().verify);
+        assert!(args("--verify gen9ou-1").is_err());
+        assert_eq!(
+            args("--emit-turn-records csv").unwrap().emit,
+            Some(RecordFormat::Csv)
+        );
+        assert!(args("--emit-turn-records xml").is_err());
+        assert!(args("--emit-turn-records jsonl gen9ou-1").is_err());
+        assert!(args("--delay-ms soon").is_err());
+        assert!(args("--fast").is_err());
+    }
+
+    #[test]
+    fn test_verify_local_logs() {
+        let dir =
+            std::env::temp_dir().join(format!("replay-harvest-verify-{}", std::process::id()));
+        let log = dir.join("input").join("mirror.log");
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(
+            &log,
+            "|player|p1|Alice|1\n|player|p2|Bob|2\n\
+             |switch|p1a: Garchomp|Garchomp, L50, M|100/100\n\
+             |switch|p2a: Heatran|Heatran, L50, M|100/100\n|turn|1\n",
+        )
+        .unwrap();
+        let mut options = Options {
+            dir: dir.join("fixtures"),
+            delay: Duration::ZERO,
+            strict: true,
+            verify: false,
+            emit: None,
+            sources: vec![log.display().to_string()],
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(runtime.block_on(harvest(&options)).unwrap(), 0);
+        let manifest = Manifest::load(&options.dir).unwrap();
+        assert_eq!(manifest.get("mirror").unwrap().source, Source::File);
+
+        options.verify = true;
+        options.sources.clear();
+        assert_eq!(verify(&options).unwrap(), 0);
+
+        // The leads coming out close turn 0, and the rest closes turn 1
+        let mut out = Vec::new();
+        assert_eq!(
+            emit_turn_records(&options, RecordFormat::JsonLines, &mut out).unwrap(),
+            0
+        );
+        let records: Vec<TurnRecord> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].battle_id, "mirror");
+        assert_eq!(records[0].p2.slots[0].species, "Heatran");
+
+        // A log that no longer matches its snapshot fails verification
+        let cached = log_path(&options.dir, "mirror");
+        let changed = fs::read_to_string(&cached).unwrap() + "|-damage|p2a: Heatran|50/100\n";
+        fs::write(&cached, changed).unwrap();
+        assert_eq!(verify(&options).unwrap(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+//! The fixtures directory's manifest: which replays it holds and where from
+//!
+//! `manifest.json` lists one entry per replay. Each entry's log is cached as
+//! `<id>.log` and its golden snapshot as `<id>.json`, next to the manifest.
+//!
+//! ```json
+//! {
+//!   "replays": [
+//!     { "id": "gen9ou-2200000000", "source": "api" },
+//!     { "id": "vgc-mirror", "source": "file" },
+//!     { "id": "gen9ou-2100000000", "source": "api", "missing": true }
+//!   ]
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Base URL of the public replay server
+const REPLAY_HOST: &str = "https://replay.pokemonshowdown.com/";
+
+/// Where a replay's log came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The replay server's JSON endpoint
+    Api,
+    /// A local .log file, copied into the directory
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub source: Source,
+
+    /// The replay server no longer has it; skipped until removed by hand
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub replays: Vec<Entry>,
+}
+
+impl Manifest {
+    /// Load `dir`'s manifest; a directory without one is empty
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("malformed manifest {}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(dir)?;
+        let text = serde_json::to_string_pretty(self)? + "\n";
+        fs::write(dir.join(MANIFEST_FILE), text)?;
+        Ok(())
+    }
+
+    /// Add a replay unless one with the same ID is listed; true if added
+    pub fn add(&mut self, id: &str, source: Source) -> bool {
+        if self.get(id).is_some() {
+            return false;
+        }
+        self.replays.push(Entry {
+            id: id.to_string(),
+            source,
+            missing: false,
+        });
+        true
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        self.replays.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn mark_missing(&mut self, id: &str) {
+        if let Some(entry) = self.replays.iter_mut().find(|entry| entry.id == id) {
+            entry.missing = true;
+        }
+    }
+}
+
+/// Cached log and snapshot paths for a replay in `dir`
+pub fn log_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.log"))
+}
+
+pub fn snapshot_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.json"))
+}
+
+/// Replay ID from an ID or a replay page or JSON URL
+///
+/// IDs become file names, so anything but letters, digits and `-` is rejected.
+pub fn replay_id(arg: &str) -> anyhow::Result<String> {
+    let id = arg.strip_prefix(REPLAY_HOST).unwrap_or(arg);
+    let id = id
+        .trim_end_matches('/')
+        .trim_end_matches(".json")
+        .trim_end_matches(".log");
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("not a replay ID: {arg:?}");
+    }
+    Ok(id.to_string())
+}
+
+/// Manifest ID for a local log: its file name without the extension
+pub fn file_id(path: &Path) -> anyhow::Result<String> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .with_context(|| format!("no file name in {}", path.display()))?;
+    replay_id(stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_format() {
+        let text = r#"{"replays": [
+            {"id": "gen9ou-1", "source": "api"},
+            {"id": "mirror", "source": "file"},
+            {"id": "gen9ou-2", "source": "api", "missing": true}
+        ]}"#;
+        let manifest: Manifest = serde_json::from_str(text).unwrap();
+        assert_eq!(manifest.replays.len(), 3);
+        assert_eq!(manifest.replays[1].source, Source::File);
+        assert!(!manifest.replays[0].missing);
+        assert!(manifest.replays[2].missing);
+
+        // Round trip, leaving out `missing` when false
+        let saved = serde_json::to_string(&manifest).unwrap();
+        assert_eq!(saved.matches("missing").count(), 1);
+        assert_eq!(serde_json::from_str::<Manifest>(&saved).unwrap(), manifest);
+    }
+
+    #[test]
+    fn test_add_and_mark_missing() {
+        let mut manifest = Manifest::default();
+        assert!(manifest.add("gen9ou-1", Source::Api));
+        assert!(!manifest.add("gen9ou-1", Source::File));
+        assert_eq!(manifest.get("gen9ou-1").unwrap().source, Source::Api);
+
+        manifest.mark_missing("gen9ou-1");
+        assert!(manifest.get("gen9ou-1").unwrap().missing);
+        assert!(manifest.get("gen9ou-2").is_none());
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let dir = std::env::temp_dir().join(format!("replay-harvest-{}", std::process::id()));
+        assert_eq!(Manifest::load(&dir).unwrap(), Manifest::default());
+
+        let mut manifest = Manifest::default();
+        manifest.add("gen9ou-1", Source::Api);
+        manifest.save(&dir).unwrap();
+        assert_eq!(Manifest::load(&dir).unwrap(), manifest);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay_ids() {
+        assert_eq!(replay_id("gen9ou-2200000000").unwrap(), "gen9ou-2200000000");
+        assert_eq!(
+            replay_id("https://replay.pokemonshowdown.com/gen9ou-2200000000.json").unwrap(),
+            "gen9ou-2200000000"
+        );
+        // Private replays carry a password suffix
+        assert_eq!(
+            replay_id("gen9ou-2200000000-abcdefpw").unwrap(),
+            "gen9ou-2200000000-abcdefpw"
+        );
+        assert!(replay_id("../etc/passwd").is_err());
+        assert!(replay_id("").is_err());
+        assert_eq!(file_id(Path::new("logs/mirror.log")).unwrap(), "mirror");
+    }
+}